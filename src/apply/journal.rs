@@ -0,0 +1,240 @@
+//! Write-ahead intents for runtime sysfs writes. Before each write, the
+//! path and its original value are appended to a journal next to the state
+//! file and synced to disk; each checkpoint folds them into the state and
+//! empties the journal. If bop dies between a write and the next
+//! checkpoint, the original value is still on disk, and the next apply,
+//! revert or `bop auto` reconciles it into the state (`bop status` reads
+//! it without writing anything).
+
+use super::{ApplyState, SysfsChange};
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const JOURNAL_FILE: &str = "/var/lib/bop/intents.jsonl";
+
+/// A sysfs write about to happen, one JSON line in the journal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Intent {
+    pub path: String,
+    /// Raw contents before the write, as [`SysfsChange::original_value`]
+    pub original_value: String,
+    pub new_value: String,
+}
+
+impl Intent {
+    pub fn new(
+        path: impl Into<String>,
+        original: impl Into<String>,
+        new: impl Into<String>,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            original_value: original.into(),
+            new_value: new.into(),
+        }
+    }
+
+    pub fn to_change(&self) -> SysfsChange {
+        SysfsChange::new(&self.path, &self.original_value, &self.new_value)
+    }
+
+    /// Whether the write landed, judging by the value there now. A file
+    /// that still holds the original was never written (or the write was a
+    /// no-op); one that can't be read is assumed written.
+    fn landed(&self) -> bool {
+        match std::fs::read(&self.path) {
+            Ok(current) => {
+                crate::sysfs::normalize_value(&String::from_utf8_lossy(&current))
+                    != crate::sysfs::normalize_value(&self.original_value)
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+/// The journal beside the state file (under `--state-dir` if given).
+pub fn journal_path() -> PathBuf {
+    super::state_file_path().with_file_name("intents.jsonl")
+}
+
+/// Append `intent` to the journal at `path` and sync it, so it's on disk
+/// before the write it describes.
+pub fn append(path: &Path, intent: &Intent) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| Error::State(format!("failed to create state dir: {}", e)))?;
+    }
+    let mut line = serde_json::to_string(intent)
+        .map_err(|e| Error::State(format!("failed to serialize intent: {}", e)))?;
+    line.push('\n');
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| Error::State(format!("failed to open journal: {}", e)))?;
+    file.write_all(line.as_bytes())
+        .and_then(|()| file.sync_all())
+        .map_err(|e| Error::State(format!("failed to write journal: {}", e)))
+}
+
+/// The intents in the journal at `path`, oldest first; empty if there is
+/// none. A torn last line is dropped: the sync never finished, so its write
+/// never started.
+pub fn read(path: &Path) -> Result<Vec<Intent>> {
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(Error::State(format!("failed to read journal: {}", e))),
+    };
+    let lines: Vec<&str> = data.lines().filter(|l| !l.trim().is_empty()).collect();
+    let mut intents = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        match serde_json::from_str(line) {
+            Ok(intent) => intents.push(intent),
+            Err(_) if i + 1 == lines.len() && !data.ends_with('\n') => {}
+            Err(e) => {
+                return Err(Error::State(format!(
+                    "failed to parse journal line {}: {}",
+                    i + 1,
+                    e
+                )));
+            }
+        }
+    }
+    Ok(intents)
+}
+
+/// Empty the journal once its intents are in the saved state.
+pub fn clear(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(Error::State(format!("failed to clear journal: {}", e))),
+    }
+}
+
+/// Record the intents whose writes landed in `state`, returning how many
+/// were added. A path the state already records keeps its original: that
+/// one is older.
+pub fn fold(state: &mut ApplyState, intents: &[Intent]) -> usize {
+    let mut added = 0;
+    for intent in intents {
+        if state.sysfs_changes.iter().any(|c| c.path == intent.path) || !intent.landed() {
+            continue;
+        }
+        state.sysfs_changes.push(intent.to_change());
+        added += 1;
+    }
+    added
+}
+
+/// Fold the journal at `journal` into the state file at `state_path` and
+/// empty it. Returns how many writes were recovered.
+pub fn reconcile_at(state_path: &Path, journal: &Path) -> Result<usize> {
+    let intents = read(journal)?;
+    if intents.is_empty() {
+        return clear(journal).map(|()| 0);
+    }
+    let existing = ApplyState::load_from(state_path)?;
+    let mut state = existing.clone().unwrap_or_else(|| ApplyState {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        ..Default::default()
+    });
+    let added = fold(&mut state, &intents);
+    if added > 0 || existing.is_some() {
+        state.save_to(state_path)?;
+    }
+    clear(journal)?;
+    Ok(added)
+}
+
+/// Reconcile the system's journal, left behind by a run that died before
+/// its checkpoint.
+pub fn reconcile() -> Result<usize> {
+    reconcile_at(&super::state_file_path(), &journal_path())
+}
+
+/// The saved state with any leftover intents folded in, without writing
+/// anything; for readers that may not own the state dir.
+pub fn load_state() -> Result<Option<ApplyState>> {
+    let state = ApplyState::load()?;
+    let intents = read(&journal_path()).unwrap_or_default();
+    if intents.is_empty() {
+        return Ok(state);
+    }
+    let mut state = state.unwrap_or_default();
+    fold(&mut state, &intents);
+    Ok(state.has_recorded_changes().then_some(state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_append_and_read_back() {
+        let tmp = TempDir::new().unwrap();
+        let journal = tmp.path().join("intents.jsonl");
+        assert!(read(&journal).unwrap().is_empty());
+
+        let first = Intent::new("/sys/a", "balanced\n", "power");
+        let second = Intent::new("/sys/b", "[on] auto\n", "auto");
+        append(&journal, &first).unwrap();
+        append(&journal, &second).unwrap();
+        assert_eq!(read(&journal).unwrap(), [first.clone(), second]);
+
+        // Killed mid-append: the partial line is dropped
+        let mut data = std::fs::read_to_string(&journal).unwrap();
+        data.push_str("{\"path\":\"/sys/c\",\"orig");
+        std::fs::write(&journal, &data).unwrap();
+        assert_eq!(read(&journal).unwrap().len(), 2);
+
+        // Anything else unreadable is an error, not silently lost originals
+        std::fs::write(&journal, "garbage\n").unwrap();
+        assert!(read(&journal).is_err());
+
+        clear(&journal).unwrap();
+        assert!(!journal.exists());
+        clear(&journal).unwrap();
+    }
+
+    #[test]
+    fn test_reconcile_keeps_older_originals() {
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+        let journal = tmp.path().join("intents.jsonl");
+        let a = tmp.path().join("a");
+        let b = tmp.path().join("b");
+        std::fs::write(&a, "new").unwrap();
+        std::fs::write(&b, "new").unwrap();
+        let a = a.to_string_lossy().into_owned();
+        let b = b.to_string_lossy().into_owned();
+
+        ApplyState {
+            timestamp: "earlier".to_string(),
+            sysfs_changes: vec![SysfsChange::new(&a, "first", "new")],
+            ..Default::default()
+        }
+        .save_to(&state_path)
+        .unwrap();
+        append(&journal, &Intent::new(&a, "second", "new")).unwrap();
+        append(&journal, &Intent::new(&b, "old", "new")).unwrap();
+
+        assert_eq!(reconcile_at(&state_path, &journal).unwrap(), 1);
+        let state = ApplyState::load_from(&state_path).unwrap().unwrap();
+        assert_eq!(state.timestamp, "earlier");
+        let originals: Vec<_> = state
+            .sysfs_changes
+            .iter()
+            .map(|c| (c.path.as_str(), c.original_value.as_str()))
+            .collect();
+        assert_eq!(originals, [(a.as_str(), "first"), (b.as_str(), "old")]);
+        assert!(!journal.exists());
+
+        // Nothing left to do
+        assert_eq!(reconcile_at(&state_path, &journal).unwrap(), 0);
+    }
+}
@@ -1,10 +1,16 @@
+use super::offline::OfflineRoot;
 use crate::error::{Error, Result};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
-const SYSTEMD_BOOT_ENTRIES_DIR: &str = "/boot/loader/entries";
-const GRUB_DEFAULT: &str = "/etc/default/grub";
+pub const SYSTEMD_BOOT_ENTRIES_DIR: &str = "/boot/loader/entries";
+pub const GRUB_DEFAULT: &str = "/etc/default/grub";
 const GRUB_CMDLINE_VAR: &str = "GRUB_CMDLINE_LINUX_DEFAULT";
+/// Params for every boot entry, ahead of the default ones; bop adds nothing
+/// here but drops params from it on request.
+const GRUB_CMDLINE_ALL_VAR: &str = "GRUB_CMDLINE_LINUX";
+/// Where `grub-mkconfig` output goes, first existing one wins.
+pub const GRUB_CFG_PATHS: &[&str] = &["/boot/grub/grub.cfg", "/boot/grub2/grub.cfg"];
 
 /// Detected bootloader type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -46,12 +52,19 @@ pub struct KernelParamBackup {
 
 /// Add kernel parameters to the detected bootloader configuration.
 pub fn add_kernel_params(params: &[String]) -> Result<Vec<KernelParamBackup>> {
+    edit_kernel_params(params, &[])
+}
+
+/// Add `params` and drop every token named in `remove` (`name` or
+/// `name=value`, matched by name) in one pass, so each changed file gets a
+/// single backup of its original content and revert restores both.
+pub fn edit_kernel_params(params: &[String], remove: &[String]) -> Result<Vec<KernelParamBackup>> {
     match detect_bootloader()? {
         BootloaderType::SystemdBoot => {
-            add_kernel_params_systemd_boot(params, Path::new(SYSTEMD_BOOT_ENTRIES_DIR))
+            add_kernel_params_systemd_boot(params, remove, Path::new(SYSTEMD_BOOT_ENTRIES_DIR))
         }
         BootloaderType::Grub => {
-            let backups = add_kernel_params_grub(params, Path::new(GRUB_DEFAULT))?;
+            let backups = add_kernel_params_grub(params, remove, Path::new(GRUB_DEFAULT))?;
             if !backups.is_empty() {
                 regenerate_grub_config()?;
             }
@@ -60,6 +73,49 @@ pub fn add_kernel_params(params: &[String]) -> Result<Vec<KernelParamBackup>> {
     }
 }
 
+/// Like [`edit_kernel_params`], for the bootloader configuration inside an
+/// offline root. Every path, including each boot entry, is resolved within the root,
+/// and backups record the paths the booted image will see. grub.cfg is not
+/// regenerated: that takes the image's own `grub-mkconfig`.
+pub fn edit_kernel_params_offline(
+    params: &[String],
+    remove: &[String],
+    root: &OfflineRoot,
+) -> Result<Vec<KernelParamBackup>> {
+    let entries_dir = root.resolve(SYSTEMD_BOOT_ENTRIES_DIR)?;
+    let backups = if entries_dir.exists() {
+        let entries = list_entry_files(&entries_dir)?
+            .iter()
+            .filter_map(|entry| entry.file_name())
+            .map(|name| {
+                root.resolve(&format!(
+                    "{}/{}",
+                    SYSTEMD_BOOT_ENTRIES_DIR,
+                    name.to_string_lossy()
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        add_kernel_params_to_entries(params, remove, &entries, &entries_dir)?
+    } else {
+        let grub = root.resolve(GRUB_DEFAULT)?;
+        if !grub.exists() {
+            return Err(Error::Bootloader(format!(
+                "no supported bootloader found in {} (checked systemd-boot and GRUB)",
+                root.path().display()
+            )));
+        }
+        add_kernel_params_grub(params, remove, &grub)?
+    };
+
+    Ok(backups
+        .into_iter()
+        .map(|backup| KernelParamBackup {
+            path: root.target_path(Path::new(&backup.path)),
+            ..backup
+        })
+        .collect())
+}
+
 /// Remove kernel parameters from the detected bootloader configuration.
 pub fn remove_kernel_params(params: &[String]) -> Result<()> {
     match detect_bootloader()? {
@@ -107,12 +163,49 @@ pub fn restore_kernel_param_backups(backups: &[KernelParamBackup]) -> Result<()>
     Ok(())
 }
 
+/// Like [`restore_kernel_param_backups`], for backups recorded on a system
+/// whose root is mounted at `root` (e.g. from a rescue shell). Each path is
+/// resolved within the root, and a backup whose file is missing there is
+/// reported instead of recreated: usually /boot or the ESP isn't mounted.
+/// grub.cfg is not regenerated; that takes the system's own `grub-mkconfig`.
+pub fn restore_kernel_param_backups_offline(
+    backups: &[KernelParamBackup],
+    root: &OfflineRoot,
+) -> Result<()> {
+    let restore = |backup: &KernelParamBackup| -> std::result::Result<(), String> {
+        let path = root.resolve(&backup.path).map_err(|e| e.to_string())?;
+        if !path.is_file() {
+            return Err(format!(
+                "{} not found (is the partition holding it mounted?)",
+                path.display()
+            ));
+        }
+        std::fs::write(&path, &backup.original_content)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    };
+    let errors: Vec<String> = backups
+        .iter()
+        .filter_map(|backup| restore(backup).err())
+        .collect();
+
+    if !errors.is_empty() {
+        return Err(Error::Bootloader(format!(
+            "failed to restore {} of {} entries: {}",
+            errors.len(),
+            backups.len(),
+            errors.join("; ")
+        )));
+    }
+    Ok(())
+}
+
 // ---------------------------------------------------------------------------
 // systemd-boot implementation
 // ---------------------------------------------------------------------------
 
 fn add_kernel_params_systemd_boot(
     params: &[String],
+    remove: &[String],
     entries_dir: &Path,
 ) -> Result<Vec<KernelParamBackup>> {
     if !entries_dir.exists() {
@@ -123,6 +216,15 @@ fn add_kernel_params_systemd_boot(
     }
 
     let entries = list_entry_files(entries_dir)?;
+    add_kernel_params_to_entries(params, remove, &entries, entries_dir)
+}
+
+fn add_kernel_params_to_entries(
+    params: &[String],
+    remove: &[String],
+    entries: &[PathBuf],
+    entries_dir: &Path,
+) -> Result<Vec<KernelParamBackup>> {
     let mut backups = Vec::new();
 
     if entries.is_empty() {
@@ -132,12 +234,12 @@ fn add_kernel_params_systemd_boot(
         )));
     }
 
-    for entry in &entries {
+    for entry in entries {
         let path = entry.clone();
         let content = std::fs::read_to_string(&path)
             .map_err(|e| Error::Bootloader(format!("failed to read {}: {}", path.display(), e)))?;
 
-        let new_content = build_content_with_added_params(&content, &path, params)?;
+        let new_content = build_content_with_added_params(&content, &path, params, remove)?;
 
         if new_content != content {
             backups.push(KernelParamBackup {
@@ -170,10 +272,7 @@ fn remove_kernel_params_systemd_boot(params: &[String], entries_dir: &Path) -> R
         let content = std::fs::read_to_string(&path)
             .map_err(|e| Error::Bootloader(format!("failed to read {}: {}", path.display(), e)))?;
 
-        let param_names: Vec<&str> = params
-            .iter()
-            .map(|p| p.split('=').next().unwrap_or(p))
-            .collect();
+        let param_names = param_names(params);
 
         let new_content = build_content_with_removed_params(&content, &param_names);
         if new_content != content {
@@ -195,58 +294,94 @@ fn list_entry_files(entries_dir: &Path) -> Result<Vec<PathBuf>> {
         .collect())
 }
 
+/// systemd-boot joins every `options` line of an entry into one command
+/// line, so params are set across all of them: an existing value is replaced
+/// where it first appears, later copies are dropped, and new params go on
+/// the last line. Lines whose params don't change are left byte for byte.
 fn build_content_with_added_params(
     content: &str,
     path: &Path,
     params: &[String],
+    remove: &[String],
 ) -> Result<String> {
-    let mut new_lines = Vec::new();
-    let mut options_found = false;
-
-    for line in content.lines() {
-        if line.starts_with("options") {
-            options_found = true;
-            new_lines.push(add_params_to_line(line, params));
-        } else {
-            new_lines.push(line.to_string());
-        }
-    }
+    let remove_names = param_names(remove);
+    let original: Vec<Vec<String>> = content
+        .lines()
+        .filter(|line| line.starts_with("options"))
+        .map(options_tokens)
+        .collect();
 
-    if !options_found {
+    if original.is_empty() {
         return Err(Error::Bootloader(format!(
             "no 'options' line found in {}",
             path.display()
         )));
     }
 
-    Ok(preserve_newline(&new_lines.join("\n"), content))
+    let mut groups = original.clone();
+    for tokens in &mut groups {
+        tokens.retain(|token| !remove_names.contains(&param_name(token)));
+    }
+    set_params(&mut groups, params);
+
+    let mut edited = groups.into_iter().zip(original);
+    Ok(map_lines(content, |line| {
+        if !line.starts_with("options") {
+            return line.to_string();
+        }
+        match edited.next() {
+            Some((tokens, before)) if tokens != before => options_line(&tokens),
+            _ => line.to_string(),
+        }
+    }))
 }
 
 fn build_content_with_removed_params(content: &str, param_names: &[&str]) -> String {
-    let mut new_lines = Vec::new();
-
-    for line in content.lines() {
-        if line.starts_with("options") {
-            new_lines.push(remove_params_from_line(line, param_names));
+    map_lines(content, |line| {
+        if !line.starts_with("options") {
+            return line.to_string();
+        }
+        let tokens = options_tokens(line);
+        let kept: Vec<String> = tokens
+            .iter()
+            .filter(|token| !param_names.contains(&param_name(token)))
+            .cloned()
+            .collect();
+        if kept.len() == tokens.len() {
+            line.to_string()
         } else {
-            new_lines.push(line.to_string());
+            options_line(&kept)
         }
-    }
+    })
+}
 
-    preserve_newline(&new_lines.join("\n"), content)
+/// The params on an `options` line, without the keyword.
+fn options_tokens(line: &str) -> Vec<String> {
+    line.split_whitespace().skip(1).map(String::from).collect()
+}
+
+fn options_line(tokens: &[String]) -> String {
+    std::iter::once("options")
+        .chain(tokens.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 // ---------------------------------------------------------------------------
 // GRUB implementation
 // ---------------------------------------------------------------------------
 
-/// Add kernel parameters to `/etc/default/grub`.
+/// Add kernel parameters to `/etc/default/grub`, dropping those in `remove`.
 /// Returns backups if changes were made.
-fn add_kernel_params_grub(params: &[String], grub_path: &Path) -> Result<Vec<KernelParamBackup>> {
+fn add_kernel_params_grub(
+    params: &[String],
+    remove: &[String],
+    grub_path: &Path,
+) -> Result<Vec<KernelParamBackup>> {
     let content = std::fs::read_to_string(grub_path)
         .map_err(|e| Error::Bootloader(format!("failed to read {}: {}", grub_path.display(), e)))?;
 
-    let new_content = build_grub_content_with_added_params(&content, params)?;
+    let new_content = build_grub_content_with_added_params(&content, params, remove)?;
 
     if new_content == content {
         return Ok(Vec::new());
@@ -274,10 +409,7 @@ fn remove_kernel_params_grub(params: &[String], grub_path: &Path) -> Result<bool
     let content = std::fs::read_to_string(grub_path)
         .map_err(|e| Error::Bootloader(format!("failed to read {}: {}", grub_path.display(), e)))?;
 
-    let param_names: Vec<&str> = params
-        .iter()
-        .map(|p| p.split('=').next().unwrap_or(p))
-        .collect();
+    let param_names = param_names(params);
 
     let new_content = build_grub_content_with_removed_params(&content, &param_names);
 
@@ -292,55 +424,91 @@ fn remove_kernel_params_grub(params: &[String], grub_path: &Path) -> Result<bool
     Ok(true)
 }
 
-fn build_grub_content_with_added_params(content: &str, params: &[String]) -> Result<String> {
-    let mut new_lines = Vec::new();
-    let mut found = false;
-
-    for line in content.lines() {
-        if is_grub_cmdline_line(line) {
-            found = true;
-            new_lines.push(modify_grub_cmdline(line, |value| {
-                add_params_to_value(value, params)
-            }));
-        } else {
-            new_lines.push(line.to_string());
-        }
-    }
-
-    if !found {
+fn build_grub_content_with_added_params(
+    content: &str,
+    params: &[String],
+    remove: &[String],
+) -> Result<String> {
+    if !content.lines().any(is_grub_cmdline_line) {
         return Err(Error::Bootloader(format!(
             "no {} line found in GRUB config",
             GRUB_CMDLINE_VAR
         )));
     }
 
-    Ok(preserve_newline(&new_lines.join("\n"), content))
+    let remove_names = param_names(remove);
+    Ok(map_lines(content, |line| {
+        if is_grub_var_line(line, GRUB_CMDLINE_ALL_VAR) && !remove_names.is_empty() {
+            return modify_grub_cmdline(line, |value| {
+                remove_params_from_value(value, &remove_names)
+            });
+        }
+        if !is_grub_cmdline_line(line) {
+            return line.to_string();
+        }
+        modify_grub_cmdline(line, |value| {
+            if remove_names.is_empty() {
+                add_params_to_value(value, params)
+            } else {
+                add_params_to_value(&remove_params_from_value(value, &remove_names), params)
+            }
+        })
+    }))
 }
 
 fn build_grub_content_with_removed_params(content: &str, param_names: &[&str]) -> String {
-    let mut new_lines = Vec::new();
-
-    for line in content.lines() {
-        if is_grub_cmdline_line(line) {
-            new_lines.push(modify_grub_cmdline(line, |value| {
-                remove_params_from_value(value, param_names)
-            }));
-        } else {
-            new_lines.push(line.to_string());
+    map_lines(content, |line| {
+        if !is_grub_cmdline_line(line) {
+            return line.to_string();
         }
-    }
-
-    preserve_newline(&new_lines.join("\n"), content)
+        modify_grub_cmdline(line, |value| remove_params_from_value(value, param_names))
+    })
 }
 
 /// Check if a line is the GRUB_CMDLINE_LINUX_DEFAULT assignment.
 fn is_grub_cmdline_line(line: &str) -> bool {
+    is_grub_var_line(line, GRUB_CMDLINE_VAR)
+}
+
+fn is_grub_var_line(line: &str, var: &str) -> bool {
     let trimmed = line.trim_start();
-    trimmed.starts_with(GRUB_CMDLINE_VAR) && trimmed[GRUB_CMDLINE_VAR.len()..].starts_with('=')
+    trimmed.starts_with(var) && trimmed[var.len()..].starts_with('=')
+}
+
+/// Every param on the command-line lines of a boot entry or GRUB config.
+fn cmdline_tokens(content: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for line in content.lines() {
+        if line.starts_with("options") {
+            tokens.extend(options_tokens(line));
+        } else if is_grub_cmdline_line(line) || is_grub_var_line(line, GRUB_CMDLINE_ALL_VAR) {
+            modify_grub_cmdline(line, |value| {
+                tokens.extend(value.split_whitespace().map(String::from));
+                String::new()
+            });
+        }
+    }
+    tokens
+}
+
+/// The names in `remove` that an edit recorded in `backups` actually
+/// dropped: each was on a command line of a file the edit changed, and
+/// the edit drops every copy from the lines it handles.
+pub fn removed_params(remove: &[String], backups: &[KernelParamBackup]) -> Vec<String> {
+    let found: Vec<String> = backups
+        .iter()
+        .flat_map(|b| cmdline_tokens(&b.original_content))
+        .collect();
+    remove
+        .iter()
+        .filter(|name| found.iter().any(|t| param_name(t) == param_name(name)))
+        .cloned()
+        .collect()
 }
 
 /// Parse a `GRUB_CMDLINE_LINUX_DEFAULT="..."` line, apply a transformation
-/// to the value, and reconstruct the line preserving quoting style.
+/// to the value, and reconstruct the line preserving quoting style. Anything
+/// after the value, such as a trailing `# comment`, is kept as is.
 fn modify_grub_cmdline(line: &str, f: impl FnOnce(&str) -> String) -> String {
     // Find the = sign
     let eq_pos = match line.find('=') {
@@ -351,37 +519,59 @@ fn modify_grub_cmdline(line: &str, f: impl FnOnce(&str) -> String) -> String {
     let prefix = &line[..=eq_pos]; // "GRUB_CMDLINE_LINUX_DEFAULT="
     let raw_value = &line[eq_pos + 1..];
 
-    // Detect and strip quotes
-    let (open_quote, value, close_quote) = if let Some(stripped) = raw_value.strip_prefix('"') {
-        let inner = stripped.strip_suffix('"').unwrap_or(stripped);
-        ("\"", inner, "\"")
-    } else if let Some(stripped) = raw_value.strip_prefix('\'') {
-        let inner = stripped.strip_suffix('\'').unwrap_or(stripped);
-        ("'", inner, "'")
-    } else {
-        ("\"", raw_value, "\"")
+    // Split into quote, value and whatever follows the closing quote
+    let (quote, value, rest) = match raw_value.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let inner = &raw_value[1..];
+            match closing_quote(inner, quote) {
+                Some(end) => (quote, &inner[..end], &inner[end + 1..]),
+                None => (quote, inner, ""),
+            }
+        }
+        // Unquoted: the shell takes the value up to the first blank
+        _ => {
+            let end = raw_value
+                .find(char::is_whitespace)
+                .unwrap_or(raw_value.len());
+            ('"', &raw_value[..end], &raw_value[end..])
+        }
     };
 
-    let new_value = f(value);
-    format!("{}{}{}{}", prefix, open_quote, new_value, close_quote)
+    format!("{}{}{}{}{}", prefix, quote, f(value), quote, rest)
+}
+
+/// Byte offset of the quote that closes a value opened with `quote`.
+/// Inside double quotes a backslash escapes the next character.
+fn closing_quote(inner: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in inner.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && quote == '"' {
+            escaped = true;
+        } else if c == quote {
+            return Some(i);
+        }
+    }
+    None
 }
 
 /// Run `grub-mkconfig` to regenerate `/boot/grub/grub.cfg`.
 fn regenerate_grub_config() -> Result<()> {
-    let output_path = if Path::new("/boot/grub/grub.cfg").exists() {
-        "/boot/grub/grub.cfg"
-    } else if Path::new("/boot/grub2/grub.cfg").exists() {
-        "/boot/grub2/grub.cfg"
-    } else {
-        return Err(Error::Bootloader(
-            "grub.cfg not found at /boot/grub/grub.cfg or /boot/grub2/grub.cfg".into(),
-        ));
+    let Some(output_path) = GRUB_CFG_PATHS
+        .iter()
+        .copied()
+        .find(|path| Path::new(path).exists())
+    else {
+        return Err(Error::Bootloader(format!(
+            "grub.cfg not found at {}",
+            GRUB_CFG_PATHS.join(" or ")
+        )));
     };
 
-    let status = std::process::Command::new("grub-mkconfig")
+    let status = crate::cmd::Cmd::new("grub-mkconfig")
         .args(["-o", output_path])
-        .status()
-        .map_err(|e| Error::Bootloader(format!("failed to run grub-mkconfig: {}", e)))?;
+        .status()?;
 
     if !status.success() {
         return Err(Error::Bootloader(format!(
@@ -396,112 +586,71 @@ fn regenerate_grub_config() -> Result<()> {
 // Shared param manipulation helpers
 // ---------------------------------------------------------------------------
 
-/// Add params to a space-separated line like `options root=UUID=abc quiet`.
-/// Used for systemd-boot `options` lines.
-fn add_params_to_line(line: &str, params: &[String]) -> String {
-    let mut options_line = line.to_string();
-
-    for param in params {
-        let param_name = param.split('=').next().unwrap_or(param);
-
-        // Check if this exact param=value already exists.
-        let already_set = options_line
-            .split_whitespace()
-            .any(|word| word == param.as_str());
-        if already_set {
-            continue;
-        }
-
-        // Replace existing value in place, or append if not present.
-        let words: Vec<&str> = options_line.split_whitespace().collect();
-        let mut found = false;
-        let replaced: Vec<&str> = words
-            .into_iter()
-            .map(|word| {
-                let word_name = word.split('=').next().unwrap_or(word);
-                if word_name == param_name && word != "options" {
-                    found = true;
-                    param.as_str()
-                } else {
-                    word
-                }
-            })
-            .collect();
-        options_line = replaced.join(" ");
-        if !found {
-            options_line.push(' ');
-            options_line.push_str(param);
-        }
-    }
-
-    options_line
-}
-
-/// Remove params from a space-separated line like `options root=UUID=abc quiet`.
-/// Used for systemd-boot `options` lines.
-fn remove_params_from_line(line: &str, param_names: &[&str]) -> String {
-    let words: Vec<&str> = line.split_whitespace().collect();
-    let filtered: Vec<&str> = words
-        .into_iter()
-        .filter(|word| {
-            if *word == "options" {
-                return true;
-            }
-            let word_name = word.split('=').next().unwrap_or(word);
-            !param_names.contains(&word_name)
-        })
-        .collect();
-    filtered.join(" ")
-}
-
 /// Add params to a bare value string (no prefix keyword). Used for GRUB values.
 fn add_params_to_value(value: &str, params: &[String]) -> String {
-    let mut tokens: Vec<String> = value.split_whitespace().map(String::from).collect();
+    let mut groups = [value.split_whitespace().map(String::from).collect()];
+    set_params(&mut groups, params);
+    groups[0].join(" ")
+}
 
+/// Set each param across `groups`, token lists that together make up one
+/// command line. An existing same-name param is replaced in place and any
+/// later copies are dropped, so no group keeps a conflicting value; params
+/// not present at all are appended to the last group.
+fn set_params(groups: &mut [Vec<String>], params: &[String]) {
     for param in params {
-        let param_name = param.split('=').next().unwrap_or(param);
-
-        // Check if exact param=value already exists.
-        if tokens.iter().any(|t| t == param) {
-            continue;
-        }
-
-        // Replace existing same-name param in place, or append.
+        let name = param_name(param);
         let mut found = false;
-        for token in &mut tokens {
-            let token_name = token.split('=').next().unwrap_or(token);
-            if token_name == param_name {
-                *token = param.clone();
+        for tokens in groups.iter_mut() {
+            tokens.retain_mut(|token| {
+                if param_name(token) != name {
+                    return true;
+                }
+                if found {
+                    return false;
+                }
                 found = true;
-                break;
-            }
+                *token = param.clone();
+                true
+            });
         }
-        if !found {
-            tokens.push(param.clone());
+        if !found && let Some(last) = groups.last_mut() {
+            last.push(param.clone());
         }
     }
-
-    tokens.join(" ")
 }
 
 /// Remove params from a bare value string. Used for GRUB values.
 fn remove_params_from_value(value: &str, param_names: &[&str]) -> String {
     value
         .split_whitespace()
-        .filter(|token| {
-            let name = token.split('=').next().unwrap_or(token);
-            !param_names.contains(&name)
-        })
+        .filter(|token| !param_names.contains(&param_name(token)))
         .collect::<Vec<_>>()
         .join(" ")
 }
 
-fn preserve_newline(new_content: &str, original_content: &str) -> String {
-    if original_content.ends_with('\n') {
-        format!("{}\n", new_content)
-    } else {
-        new_content.to_string()
-    }
+/// "name=value" -> "name"; bare names pass through.
+fn param_names(params: &[String]) -> Vec<&str> {
+    params.iter().map(|p| param_name(p)).collect()
+}
+
+fn param_name(param: &str) -> &str {
+    param.split('=').next().unwrap_or(param)
+}
+
+/// Rewrite `content` line by line. Each line keeps its own ending (`\n`,
+/// `\r\n` or none at end of file), so CRLF files stay CRLF.
+fn map_lines(content: &str, mut f: impl FnMut(&str) -> String) -> String {
+    content
+        .split_inclusive('\n')
+        .map(|raw| {
+            let line = raw
+                .strip_suffix('\n')
+                .map(|l| l.strip_suffix('\r').unwrap_or(l))
+                .unwrap_or(raw);
+            f(line) + &raw[line.len()..]
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -584,7 +733,7 @@ options root=UUID=abc quiet acpi.ec_no_wakeup=0 rtc_cmos.use_acpi_alarm=0
             "acpi.ec_no_wakeup=1".to_string(),
             "rtc_cmos.use_acpi_alarm=1".to_string(),
         ];
-        let backups = add_kernel_params_systemd_boot(&params, &entries).expect("apply params");
+        let backups = add_kernel_params_systemd_boot(&params, &[], &entries).expect("apply params");
 
         assert_eq!(backups.len(), 1);
         assert_eq!(backups[0].path, entry.display().to_string());
@@ -614,7 +763,7 @@ options root=UUID=abc quiet acpi.ec_no_wakeup=0 rtc_cmos.use_acpi_alarm=0
             "acpi.ec_no_wakeup=1".to_string(),
             "rtc_cmos.use_acpi_alarm=1".to_string(),
         ];
-        let backups = add_kernel_params_systemd_boot(&params, &entries).expect("apply params");
+        let backups = add_kernel_params_systemd_boot(&params, &[], &entries).expect("apply params");
 
         assert!(backups.is_empty());
         let after = fs::read_to_string(&entry).expect("read entry");
@@ -653,17 +802,140 @@ options root=UUID=abc quiet acpi.ec_no_wakeup=0 rtc_cmos.use_acpi_alarm=0
         fs::write(&entry, content).expect("write entry");
 
         let params = vec!["acpi.ec_no_wakeup=1".to_string()];
-        let backups = add_kernel_params_systemd_boot(&params, &entries).expect("apply params");
+        let backups = add_kernel_params_systemd_boot(&params, &[], &entries).expect("apply params");
 
         assert_eq!(backups.len(), 1);
         let after = fs::read_to_string(&entry).expect("read entry");
         assert_eq!(after, "options root=UUID=abc acpi.ec_no_wakeup=1 quiet\n");
     }
 
+    #[test]
+    fn test_multiple_options_lines_are_edited_as_one_command_line() {
+        let content = "\
+title Linux
+options root=UUID=abc quiet
+options acpi.ec_no_wakeup=0 amdgpu.dcdebugmask=0x10
+initrd /initramfs-linux.img
+";
+        let path = Path::new("linux.conf");
+        let params = vec![
+            "acpi.ec_no_wakeup=1".to_string(),
+            "rtc_cmos.use_acpi_alarm=1".to_string(),
+        ];
+        let remove = vec!["amdgpu.dcdebugmask".to_string()];
+
+        let after = build_content_with_added_params(content, path, &params, &remove).unwrap();
+        assert_eq!(
+            after,
+            "\
+title Linux
+options root=UUID=abc quiet
+options acpi.ec_no_wakeup=1 rtc_cmos.use_acpi_alarm=1
+initrd /initramfs-linux.img
+"
+        );
+        // Applying again changes nothing
+        assert_eq!(
+            build_content_with_added_params(&after, path, &params, &[]).unwrap(),
+            after
+        );
+
+        let names = param_names(&params);
+        assert_eq!(
+            build_content_with_removed_params(&after, &names),
+            "title Linux\noptions root=UUID=abc quiet\noptions\ninitrd /initramfs-linux.img\n"
+        );
+    }
+
+    #[test]
+    fn test_conflicting_copies_across_options_lines_are_dropped() {
+        let content = "options quiet acpi.ec_no_wakeup=0\noptions  acpi.ec_no_wakeup=0 splash\n";
+        let params = vec!["acpi.ec_no_wakeup=1".to_string()];
+        assert_eq!(
+            build_content_with_added_params(content, Path::new("e.conf"), &params, &[]).unwrap(),
+            "options quiet acpi.ec_no_wakeup=1\noptions splash\n"
+        );
+    }
+
+    #[test]
+    fn test_options_line_crlf_endings_are_kept() {
+        let content = "title Linux\r\noptions root=UUID=abc quiet\r\n";
+        let params = vec!["acpi.ec_no_wakeup=1".to_string()];
+        let after =
+            build_content_with_added_params(content, Path::new("e.conf"), &params, &[]).unwrap();
+        assert_eq!(
+            after,
+            "title Linux\r\noptions root=UUID=abc quiet acpi.ec_no_wakeup=1\r\n"
+        );
+        assert_eq!(
+            build_content_with_removed_params(&after, &param_names(&params)),
+            content
+        );
+    }
+
     // -----------------------------------------------------------------------
     // GRUB
     // -----------------------------------------------------------------------
 
+    #[test]
+    fn test_grub_keeps_trailing_comment() {
+        let params = vec!["acpi.ec_no_wakeup=1".to_string()];
+        let content = "GRUB_CMDLINE_LINUX_DEFAULT=\"quiet\"  # keep quiet\n";
+        let after = build_grub_content_with_added_params(content, &params, &[]).unwrap();
+        assert_eq!(
+            after,
+            "GRUB_CMDLINE_LINUX_DEFAULT=\"quiet acpi.ec_no_wakeup=1\"  # keep quiet\n"
+        );
+        assert_eq!(
+            build_grub_content_with_removed_params(&after, &param_names(&params)),
+            content
+        );
+
+        assert_eq!(
+            build_grub_content_with_added_params(
+                "GRUB_CMDLINE_LINUX_DEFAULT='quiet' # single\n",
+                &params,
+                &[]
+            )
+            .unwrap(),
+            "GRUB_CMDLINE_LINUX_DEFAULT='quiet acpi.ec_no_wakeup=1' # single\n"
+        );
+        assert_eq!(
+            build_grub_content_with_added_params(
+                "GRUB_CMDLINE_LINUX_DEFAULT=quiet # unquoted\n",
+                &params,
+                &[]
+            )
+            .unwrap(),
+            "GRUB_CMDLINE_LINUX_DEFAULT=\"quiet acpi.ec_no_wakeup=1\" # unquoted\n"
+        );
+    }
+
+    #[test]
+    fn test_grub_escaped_quote_does_not_end_value() {
+        let content = "GRUB_CMDLINE_LINUX_DEFAULT=\"quiet dyndbg=\\\"file x +p\\\"\" # c\n";
+        let params = vec!["acpi.ec_no_wakeup=1".to_string()];
+        assert_eq!(
+            build_grub_content_with_added_params(content, &params, &[]).unwrap(),
+            "GRUB_CMDLINE_LINUX_DEFAULT=\"quiet dyndbg=\\\"file x +p\\\" acpi.ec_no_wakeup=1\" # c\n"
+        );
+    }
+
+    #[test]
+    fn test_grub_crlf_endings_are_kept() {
+        let content = "GRUB_TIMEOUT=5\r\nGRUB_CMDLINE_LINUX_DEFAULT=\"quiet\"\r\nGRUB_DEFAULT=0";
+        let params = vec!["acpi.ec_no_wakeup=1".to_string()];
+        let after = build_grub_content_with_added_params(content, &params, &[]).unwrap();
+        assert_eq!(
+            after,
+            "GRUB_TIMEOUT=5\r\nGRUB_CMDLINE_LINUX_DEFAULT=\"quiet acpi.ec_no_wakeup=1\"\r\nGRUB_DEFAULT=0"
+        );
+        assert_eq!(
+            build_grub_content_with_removed_params(&after, &param_names(&params)),
+            content
+        );
+    }
+
     #[test]
     fn test_add_kernel_params_grub_appends_new() {
         let tmp = TempDir::new().unwrap();
@@ -678,7 +950,7 @@ options root=UUID=abc quiet acpi.ec_no_wakeup=0 rtc_cmos.use_acpi_alarm=0
             "acpi.ec_no_wakeup=1".to_string(),
             "rtc_cmos.use_acpi_alarm=1".to_string(),
         ];
-        let backups = add_kernel_params_grub(&params, &grub).unwrap();
+        let backups = add_kernel_params_grub(&params, &[], &grub).unwrap();
 
         assert_eq!(backups.len(), 1);
         let after = fs::read_to_string(&grub).unwrap();
@@ -697,7 +969,7 @@ options root=UUID=abc quiet acpi.ec_no_wakeup=0 rtc_cmos.use_acpi_alarm=0
         .unwrap();
 
         let params = vec!["acpi.ec_no_wakeup=1".to_string()];
-        let backups = add_kernel_params_grub(&params, &grub).unwrap();
+        let backups = add_kernel_params_grub(&params, &[], &grub).unwrap();
 
         assert_eq!(backups.len(), 1);
         let after = fs::read_to_string(&grub).unwrap();
@@ -716,7 +988,7 @@ options root=UUID=abc quiet acpi.ec_no_wakeup=0 rtc_cmos.use_acpi_alarm=0
         .unwrap();
 
         let params = vec!["acpi.ec_no_wakeup=1".to_string()];
-        let backups = add_kernel_params_grub(&params, &grub).unwrap();
+        let backups = add_kernel_params_grub(&params, &[], &grub).unwrap();
 
         assert!(backups.is_empty());
     }
@@ -728,13 +1000,74 @@ options root=UUID=abc quiet acpi.ec_no_wakeup=0 rtc_cmos.use_acpi_alarm=0
         fs::write(&grub, "GRUB_CMDLINE_LINUX_DEFAULT='quiet'\n").unwrap();
 
         let params = vec!["acpi.ec_no_wakeup=1".to_string()];
-        let backups = add_kernel_params_grub(&params, &grub).unwrap();
+        let backups = add_kernel_params_grub(&params, &[], &grub).unwrap();
 
         assert_eq!(backups.len(), 1);
         let after = fs::read_to_string(&grub).unwrap();
         assert!(after.contains("'quiet acpi.ec_no_wakeup=1'"));
     }
 
+    #[test]
+    fn test_grub_removes_from_both_cmdline_vars_and_reports_only_found() {
+        let tmp = TempDir::new().unwrap();
+        let grub = tmp.path().join("grub");
+        fs::write(
+            &grub,
+            "GRUB_CMDLINE_LINUX=\"rd.luks amdgpu.dcdebugmask=0x10\"\n\
+             GRUB_CMDLINE_LINUX_DEFAULT=\"quiet\"\n",
+        )
+        .unwrap();
+
+        let remove = vec![
+            "amdgpu.dcdebugmask".to_string(),
+            "amdgpu.abmlevel".to_string(),
+        ];
+        let backups = add_kernel_params_grub(&[], &remove, &grub).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&grub).unwrap(),
+            "GRUB_CMDLINE_LINUX=\"rd.luks\"\nGRUB_CMDLINE_LINUX_DEFAULT=\"quiet\"\n"
+        );
+        assert_eq!(
+            removed_params(&remove, &backups),
+            vec!["amdgpu.dcdebugmask".to_string()]
+        );
+        // Nothing changed, nothing removed
+        assert!(removed_params(&remove, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_edit_kernel_params_removes_and_adds_with_one_backup() {
+        let tmp = TempDir::new().unwrap();
+        let entries = tmp.path().join("entries");
+        fs::create_dir_all(&entries).unwrap();
+        let entry = entries.join("linux.conf");
+        let content = "options root=UUID=abc amdgpu.dcdebugmask=0x10 quiet\n";
+        fs::write(&entry, content).unwrap();
+        let grub = tmp.path().join("grub");
+        let grub_content = "GRUB_CMDLINE_LINUX_DEFAULT=\"quiet amdgpu.dcdebugmask=0x10\"\n";
+        fs::write(&grub, grub_content).unwrap();
+
+        let params = vec!["acpi.ec_no_wakeup=1".to_string()];
+        let remove = vec!["amdgpu.dcdebugmask".to_string()];
+
+        let backups = add_kernel_params_systemd_boot(&params, &remove, &entries).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].original_content, content);
+        assert_eq!(
+            fs::read_to_string(&entry).unwrap(),
+            "options root=UUID=abc quiet acpi.ec_no_wakeup=1\n"
+        );
+
+        let backups = add_kernel_params_grub(&[], &remove, &grub).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].original_content, grub_content);
+        assert_eq!(
+            fs::read_to_string(&grub).unwrap(),
+            "GRUB_CMDLINE_LINUX_DEFAULT=\"quiet\"\n"
+        );
+    }
+
     #[test]
     fn test_remove_kernel_params_grub() {
         let tmp = TempDir::new().unwrap();
@@ -778,7 +1111,7 @@ options root=UUID=abc quiet acpi.ec_no_wakeup=0 rtc_cmos.use_acpi_alarm=0
         fs::write(&grub, original).unwrap();
 
         let params = vec!["acpi.ec_no_wakeup=1".to_string()];
-        let backups = add_kernel_params_grub(&params, &grub).unwrap();
+        let backups = add_kernel_params_grub(&params, &[], &grub).unwrap();
 
         assert_eq!(backups.len(), 1);
         assert_eq!(backups[0].original_content, original);
@@ -808,7 +1141,7 @@ GRUB_CMDLINE_LINUX=\"\"
         fs::write(&grub, content).unwrap();
 
         let params = vec!["acpi.ec_no_wakeup=1".to_string()];
-        add_kernel_params_grub(&params, &grub).unwrap();
+        add_kernel_params_grub(&params, &[], &grub).unwrap();
 
         let after = fs::read_to_string(&grub).unwrap();
         assert!(after.contains("# Comment line"));
@@ -829,7 +1162,7 @@ GRUB_CMDLINE_LINUX_DEFAULT=\"quiet\"
         fs::write(&grub, content).unwrap();
 
         let params = vec!["acpi.ec_no_wakeup=1".to_string()];
-        add_kernel_params_grub(&params, &grub).unwrap();
+        add_kernel_params_grub(&params, &[], &grub).unwrap();
 
         let after = fs::read_to_string(&grub).unwrap();
         // GRUB_CMDLINE_LINUX should be untouched
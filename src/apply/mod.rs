@@ -1,25 +1,60 @@
+pub mod journal;
 pub mod kernel_params;
+pub mod offline;
+pub mod powercap;
+pub mod probe;
+pub mod rollback;
 pub mod services;
+pub mod sysctl;
 pub mod sysfs_writer;
 pub mod systemd;
+pub mod unit_migration;
+pub mod usb_ports;
+pub mod wifi;
 
+use crate::audit::display::PSR_DEBUG_MASK_PARAM;
 use crate::config::{BopConfig, EppConfig};
 use crate::detect::HardwareInfo;
 use crate::error::{Error, Result};
-use crate::preset::{PlatformProfilePolicy, PresetKnobs, UsbPolicy};
+use crate::managed::{ManagedFile, Ownership};
+use crate::preset::{PlatformProfilePolicy, Preset, PresetKnobs, UsbPolicy};
+use crate::progress::{Progress, ProgressSink};
+use crate::quirks::RuntimePmAction;
 use crate::sysfs::SysfsRoot;
-use colored::Colorize;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 #[cfg(test)]
 use std::sync::{LazyLock, Mutex};
+use std::time::Instant;
 
 const STATE_DIR: &str = "/var/lib/bop";
-const STATE_FILE: &str = "/var/lib/bop/state.json";
+pub const STATE_FILE: &str = "/var/lib/bop/state.json";
+pub const MODPROBE_DIR: &str = "/etc/modprobe.d";
+
+/// Power daemons that overwrite the sysfs values bop sets; apply disables
+/// the ones running.
+pub const CONFLICTING_SERVICES: &[&str] = &["tlp.service", "power-profiles-daemon.service"];
 
 #[cfg(test)]
 static STATE_FILE_OVERRIDE: LazyLock<Mutex<Option<PathBuf>>> = LazyLock::new(|| Mutex::new(None));
 
+/// Set by `--state-dir` before anything reads the state.
+static STATE_DIR_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Keep the state file (and the status cache) in `dir` instead of
+/// /var/lib/bop for the rest of the process. Only the first call counts.
+pub fn set_state_dir(dir: impl Into<PathBuf>) {
+    let _ = STATE_DIR_OVERRIDE.set(dir.into());
+}
+
+/// The directory given to [`set_state_dir`], if any. A state there belongs
+/// to whoever could write it, not to the system.
+pub fn custom_state_dir() -> Option<&'static Path> {
+    STATE_DIR_OVERRIDE.get().map(PathBuf::as_path)
+}
+
 fn state_file_path() -> PathBuf {
     #[cfg(test)]
     {
@@ -32,7 +67,10 @@ fn state_file_path() -> PathBuf {
         }
     }
 
-    PathBuf::from(STATE_FILE)
+    match custom_state_dir() {
+        Some(dir) => dir.join("state.json"),
+        None => PathBuf::from(STATE_FILE),
+    }
 }
 
 fn state_dir_path() -> PathBuf {
@@ -48,32 +86,214 @@ pub struct ApplyState {
     pub timestamp: String,
     pub sysfs_changes: Vec<SysfsChange>,
     pub kernel_params_added: Vec<String>,
+    /// Names of user kernel params dropped at the config's request; the
+    /// backups below hold the entries as they were.
+    #[serde(default)]
+    pub kernel_params_removed: Vec<String>,
     #[serde(default)]
     pub kernel_param_backups: Vec<kernel_params::KernelParamBackup>,
     pub services_disabled: Vec<String>,
+    /// When each of `services_disabled` was disabled (RFC 3339), so a
+    /// before/after comparison doesn't take a later re-apply's timestamp
+    /// for it. Empty in older state files.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub services_disabled_at: BTreeMap<String, String>,
     pub systemd_units_created: Vec<String>,
     pub modprobe_files_created: Vec<String>,
+    /// WiFi manager drop-ins (NetworkManager conf.d)
+    #[serde(default)]
+    pub wifi_files_created: Vec<String>,
+    /// udev rules that undo runtime changes (re-enabling USB ports)
+    #[serde(default)]
+    pub udev_files_created: Vec<String>,
+    /// sysctl.d drop-ins (offline roots only; the unit sets sysctls on a
+    /// running system)
+    #[serde(default)]
+    pub sysctl_files_created: Vec<String>,
     pub acpi_wakeup_toggled: Vec<String>,
     #[serde(default)]
     pub brightness_original: Option<u64>,
+    /// Backlight device `brightness_original` belongs to. None in older
+    /// state files, where restore falls back to picking the panel again.
+    #[serde(default)]
+    pub brightness_device: Option<String>,
+    /// Content hashes of the files created above (unit, modprobe, WiFi and
+    /// sysctl drop-ins, udev rules).
+    /// Empty in state files written before the manifest existed.
+    #[serde(default)]
+    pub managed_files: Vec<ManagedFile>,
+    /// Wakeup counters at apply time, so the sleep audit can measure what
+    /// happened since. Not a change; revert ignores it.
+    #[serde(default)]
+    pub wakeup_baseline: Option<WakeupBaseline>,
+    /// Preset the changes were made for. None in older state files.
+    #[serde(default)]
+    pub preset: Option<Preset>,
+    /// Kernel boot the changes were made in, so status can tell a reboot
+    /// that lost them from drift. None in older state files.
+    #[serde(default)]
+    pub boot_id: Option<String>,
+    /// Plan items `bop auto` held back for a later run, e.g. WiFi power
+    /// save during a large transfer. Not a change; revert ignores it.
+    #[serde(default)]
+    pub deferred: Vec<String>,
+    /// Applied by `bop auto` for a closed lid (`[auto] on_lid_close`);
+    /// opening it goes back to what the power source calls for.
+    #[serde(default)]
+    pub lid_closed: bool,
+    /// Applied by `bop auto` with the battery at or below `[auto]
+    /// critical_battery_percent`, latency-sensitive items held back
+    #[serde(default)]
+    pub critical_battery: bool,
+    /// Wall-clock time of each phase the apply ran, to find slow
+    /// `grub-mkconfig` or `systemctl` calls. Not a change; revert ignores it.
+    #[serde(default)]
+    pub timings: BTreeMap<Phase, PhaseTiming>,
+    /// From the start of the first phase to the end of the last. None in
+    /// older state files.
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    /// WiFi power save `bop auto` set for a `[[network.exceptions]]` entry;
+    /// leaving that network or reverting puts the original back.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wifi_exception: Option<wifi::ExceptionApplied>,
+    /// Timer that will revert these changes unless kept (`bop apply
+    /// --rollback-after`). Not a change; it carries over to later applies
+    /// while the timer runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollback: Option<rollback::PendingRollback>,
+}
+
+/// A phase slower than this gets the timing breakdown printed after apply.
+pub const SLOW_PHASE_MS: u64 = 2_000;
+
+/// When a phase of the last apply started and how long it ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseTiming {
+    /// Since execution started
+    pub started_ms: u64,
+    pub duration_ms: u64,
+}
+
+/// Path of the kernel's per-boot random id.
+pub const BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+
+/// Id of the running kernel boot.
+pub fn current_boot_id() -> Option<String> {
+    std::fs::read_to_string(BOOT_ID_PATH)
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// Snapshot of kernel wakeup counters, keyed by source name.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct WakeupBaseline {
+    pub suspend_count: Option<u64>,
+    pub sources: Vec<WakeupBaselineEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WakeupBaselineEntry {
+    pub name: String,
+    pub wakeup_count: u64,
+    pub active_count: u64,
+}
+
+impl WakeupBaseline {
+    pub fn capture(platform: &crate::detect::platform::PlatformInfo) -> Self {
+        Self {
+            suspend_count: platform.suspend_count,
+            sources: platform
+                .wakeup_counters
+                .iter()
+                .map(|c| WakeupBaselineEntry {
+                    name: c.name.clone(),
+                    wakeup_count: c.wakeup_count,
+                    active_count: c.active_count,
+                })
+                .collect(),
+        }
+    }
 }
 
+/// A recorded sysfs write. `original_value`/`new_value` are raw file contents
+/// (whitespace and choice brackets included); the `_normalized` fields hold
+/// what revert writes back and what status compares against.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SysfsChange {
     pub path: String,
     pub original_value: String,
     pub new_value: String,
+    /// Empty in state files written before normalization was recorded
+    #[serde(default)]
+    pub original_normalized: String,
+    #[serde(default)]
+    pub new_normalized: String,
+}
+
+impl SysfsChange {
+    pub fn new(
+        path: impl Into<String>,
+        original: impl Into<String>,
+        new: impl Into<String>,
+    ) -> Self {
+        let original_value = original.into();
+        let new_value = new.into();
+        Self {
+            path: path.into(),
+            original_normalized: crate::sysfs::normalize_value(&original_value),
+            new_normalized: crate::sysfs::normalize_value(&new_value),
+            original_value,
+            new_value,
+        }
+    }
+
+    /// Value to write back on revert.
+    pub fn restore_value(&self) -> String {
+        if self.original_normalized.is_empty() {
+            crate::sysfs::normalize_value(&self.original_value)
+        } else {
+            self.original_normalized.clone()
+        }
+    }
+
+    /// Value `bop status` expects to read back.
+    pub fn expected_value(&self) -> String {
+        if self.new_normalized.is_empty() {
+            crate::sysfs::normalize_value(&self.new_value)
+        } else {
+            self.new_normalized.clone()
+        }
+    }
 }
 
 impl ApplyState {
+    /// Whether any phase of the apply took longer than [`SLOW_PHASE_MS`].
+    pub fn has_slow_phase(&self) -> bool {
+        self.timings.values().any(|t| t.duration_ms > SLOW_PHASE_MS)
+    }
+
+    /// Record `service` as disabled now.
+    pub(crate) fn service_disabled(&mut self, service: &str) {
+        self.services_disabled.push(service.to_string());
+        self.services_disabled_at
+            .insert(service.to_string(), chrono::Utc::now().to_rfc3339());
+    }
+
     fn has_recorded_changes(&self) -> bool {
         !self.sysfs_changes.is_empty()
             || !self.kernel_params_added.is_empty()
+            || !self.kernel_params_removed.is_empty()
             || !self.services_disabled.is_empty()
             || !self.systemd_units_created.is_empty()
             || !self.modprobe_files_created.is_empty()
+            || !self.wifi_files_created.is_empty()
+            || !self.udev_files_created.is_empty()
+            || !self.sysctl_files_created.is_empty()
             || !self.acpi_wakeup_toggled.is_empty()
             || self.brightness_original.is_some()
+            || self.wifi_exception.is_some()
     }
 
     pub(crate) fn file_path() -> PathBuf {
@@ -88,11 +308,15 @@ impl ApplyState {
     }
 
     pub fn load() -> Result<Option<Self>> {
-        let path = state_file_path();
+        Self::load_from(&state_file_path())
+    }
+
+    /// Load a state file from an explicit path, e.g. inside an offline root.
+    pub fn load_from(path: &Path) -> Result<Option<Self>> {
         if !path.exists() {
             return Ok(None);
         }
-        let data = std::fs::read_to_string(&path)
+        let data = std::fs::read_to_string(path)
             .map_err(|e| Error::State(format!("failed to read state file: {}", e)))?;
         let state: Self = serde_json::from_str(&data)
             .map_err(|e| Error::State(format!("failed to parse state file: {}", e)))?;
@@ -102,9 +326,18 @@ impl ApplyState {
     pub fn save(&self) -> Result<()> {
         std::fs::create_dir_all(state_dir_path())
             .map_err(|e| Error::State(format!("failed to create state dir: {}", e)))?;
+        self.save_to(&state_file_path())
+    }
+
+    /// Write the state file to an explicit path, creating its directory.
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| Error::State(format!("failed to create state dir: {}", e)))?;
+        }
         let data = serde_json::to_string_pretty(self)
             .map_err(|e| Error::State(format!("failed to serialize state: {}", e)))?;
-        std::fs::write(state_file_path(), data)
+        std::fs::write(path, data)
             .map_err(|e| Error::State(format!("failed to write state file: {}", e)))?;
         Ok(())
     }
@@ -119,38 +352,199 @@ impl ApplyState {
     }
 }
 
+/// The steps of plan execution, in the order they run. State is saved
+/// after each one, so a failure leaves everything from earlier phases
+/// recorded for revert. The order is part of the contract: changing it
+/// means moving a variant here, not code in the executor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Runtime sysfs writes
+    Sysfs,
+    /// /proc/acpi/wakeup toggles
+    AcpiWakeup,
+    /// Kernel command line in the bootloader config
+    KernelParams,
+    /// Conflicting services stopped and disabled
+    Services,
+    /// modprobe.d drop-ins
+    Modprobe,
+    /// The NetworkManager WiFi power save drop-in
+    Wifi,
+    /// bop-powersave.service, last because it replays the sysfs writes
+    Persistence,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 7] = [
+        Phase::Sysfs,
+        Phase::AcpiWakeup,
+        Phase::KernelParams,
+        Phase::Services,
+        Phase::Modprobe,
+        Phase::Wifi,
+        Phase::Persistence,
+    ];
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Phase::Sysfs => "sysfs",
+            Phase::AcpiWakeup => "ACPI wakeup",
+            Phase::KernelParams => "kernel parameters",
+            Phase::Services => "services",
+            Phase::Modprobe => "module options",
+            Phase::Wifi => "WiFi power save",
+            Phase::Persistence => "persistence unit",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 /// Plan of changes to apply.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ApplyPlan {
     pub sysfs_writes: Vec<PlannedSysfsWrite>,
     pub kernel_params: Vec<String>,
+    /// Existing kernel params to drop, by name
+    pub kernel_params_remove: Vec<String>,
     pub services_to_disable: Vec<String>,
+    /// Conflicting services left alone because active units require them;
+    /// `bop apply --force-disable` moves them into `services_to_disable`
+    pub services_blocked: Vec<BlockedService>,
     pub acpi_wakeup_disable: Vec<String>,
     pub systemd_service: bool,
     pub modprobe_configs: Vec<ModprobeConfig>,
+    /// How WiFi power save is turned on, if at all: through the manager
+    /// that owns the interface, or `iw` in the persistence unit
+    pub wifi_power_save: Option<wifi::WifiPowerSave>,
+    /// Per-device include/exclude reasoning, shown by `bop apply --explain`
+    pub explanations: Vec<PlanExplanation>,
+    /// Preset the plan was built for, recorded in the state. Set by the
+    /// caller, which resolved it; the builders only see knobs.
+    pub preset: Option<Preset>,
 }
 
 impl ApplyPlan {
     /// Returns true if the plan contains no actions.
-    /// Note: `systemd_service` is excluded because it only triggers when
-    /// sysfs_writes is non-empty (it's a dependent flag, not an independent action).
+    /// Note: `systemd_service` alone is not an action; the unit is only
+    /// generated to replay sysfs writes.
     pub fn is_empty(&self) -> bool {
-        self.sysfs_writes.is_empty()
-            && self.kernel_params.is_empty()
-            && self.services_to_disable.is_empty()
-            && self.acpi_wakeup_disable.is_empty()
-            && self.modprobe_configs.is_empty()
+        self.phases().is_empty()
+    }
+
+    /// How many of the plan's items run in `phase`. Every kind of item
+    /// belongs to exactly one phase.
+    pub fn items_in(&self, phase: Phase) -> usize {
+        match phase {
+            Phase::Sysfs => self.sysfs_writes.len(),
+            Phase::AcpiWakeup => self.acpi_wakeup_disable.len(),
+            Phase::KernelParams => self.kernel_params.len() + self.kernel_params_remove.len(),
+            Phase::Services => self.services_to_disable.len(),
+            Phase::Modprobe => self.modprobe_configs.len(),
+            Phase::Wifi => {
+                usize::from(self.wifi_power_save == Some(wifi::WifiPowerSave::NetworkManager))
+            }
+            Phase::Persistence => {
+                usize::from(self.systemd_service && !self.sysfs_writes.is_empty())
+            }
+        }
+    }
+
+    /// The phases with something to do, in execution order.
+    pub fn phases(&self) -> Vec<Phase> {
+        Phase::ALL
+            .into_iter()
+            .filter(|&phase| self.items_in(phase) > 0)
+            .collect()
+    }
+
+    /// Disable blocked services anyway, for `--force-disable`.
+    pub fn force_disable_blocked(&mut self) {
+        self.services_to_disable
+            .extend(self.services_blocked.drain(..).map(|b| b.service));
+    }
+
+    /// Drop the items that cost responsiveness (USB autosuspend, WiFi power
+    /// save) and keep the rest, for a nearly empty battery. Returns how
+    /// many were dropped.
+    pub fn hold_latency_sensitive(&mut self) -> usize {
+        let before = self.sysfs_writes.len();
+        self.sysfs_writes.retain(|w| !is_usb_autosuspend(&w.path));
+        let dropped = before - self.sysfs_writes.len();
+        dropped + usize::from(self.wifi_power_save.take().is_some())
+    }
+}
+
+/// Whether `path` is a USB device's runtime power control.
+fn is_usb_autosuspend(path: &str) -> bool {
+    path.starts_with("/sys/bus/usb/devices/") && path.ends_with("/power/control")
+}
+
+/// Which of the applied items [`switch_layer`] may change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerScope {
+    /// USB autosuspend and WiFi power save, the items
+    /// [`ApplyPlan::hold_latency_sensitive`] drops
+    LatencySensitive,
+    /// Everything that takes effect at runtime: sysfs writes, ACPI wakeup,
+    /// services and WiFi power save. Kernel parameters and module options
+    /// wait for a reboot anyway.
+    Runtime,
+    /// WiFi power save and services, the items `bop auto` defers while the
+    /// WiFi is busy or during quiet hours
+    Deferred,
+}
+
+impl LayerScope {
+    fn covers_sysfs(self, path: &str) -> bool {
+        match self {
+            LayerScope::LatencySensitive => is_usb_autosuspend(path),
+            LayerScope::Runtime => true,
+            LayerScope::Deferred => false,
+        }
+    }
+
+    fn covers_services(self) -> bool {
+        matches!(self, LayerScope::Runtime | LayerScope::Deferred)
     }
 }
 
-#[derive(Debug, Clone)]
+/// A service bop would disable, kept because other units require it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlockedService {
+    pub service: String,
+    pub required_by: Vec<String>,
+}
+
+impl BlockedService {
+    /// "not disabling power-profiles-daemon.service: required by gnome-shell-portal-helper.service"
+    pub fn warning(&self) -> String {
+        format!(
+            "not disabling {}: required by {}",
+            self.service,
+            self.required_by.join(", ")
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct PlannedSysfsWrite {
     pub path: String,
     pub value: String,
     pub description: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanExplanation {
+    pub subject: String,
+    pub included: bool,
+    pub reason: String,
+    pub factors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct ModprobeConfig {
     pub filename: String,
     pub content: String,
@@ -192,7 +586,9 @@ pub fn build_plan(
     knobs: &PresetKnobs,
     config: Option<&BopConfig>,
 ) -> ApplyPlan {
-    build_plan_inner(hw, sysfs, knobs, config)
+    let plan = build_plan_inner(hw, sysfs, knobs, config);
+    crate::crash::note_plan(&plan);
+    plan
 }
 
 /// Build a reduced plan: only volatile sysfs writes, no persistent changes.
@@ -203,14 +599,21 @@ pub fn build_plan_reduced(
     config: Option<&BopConfig>,
 ) -> ApplyPlan {
     let full = build_plan(hw, sysfs, knobs, config);
-    ApplyPlan {
+    let plan = ApplyPlan {
         sysfs_writes: full.sysfs_writes,
         kernel_params: Vec::new(),
+        kernel_params_remove: Vec::new(),
         services_to_disable: Vec::new(),
+        services_blocked: Vec::new(),
         acpi_wakeup_disable: full.acpi_wakeup_disable,
         systemd_service: false,
         modprobe_configs: Vec::new(),
-    }
+        wifi_power_save: None,
+        explanations: full.explanations,
+        preset: full.preset,
+    };
+    crate::crash::note_plan(&plan);
+    plan
 }
 
 fn build_plan_inner(
@@ -222,10 +625,15 @@ fn build_plan_inner(
     let mut plan = ApplyPlan {
         sysfs_writes: Vec::new(),
         kernel_params: Vec::new(),
+        kernel_params_remove: Vec::new(),
         services_to_disable: Vec::new(),
+        services_blocked: Vec::new(),
         acpi_wakeup_disable: Vec::new(),
         systemd_service: true,
         modprobe_configs: Vec::new(),
+        wifi_power_save: None,
+        explanations: Vec::new(),
+        preset: None,
     };
 
     // CPU: EPP — only consult adaptive config when the preset enables EPP
@@ -245,9 +653,38 @@ fn build_plan_inner(
         None
     };
 
+    let epp_needs_change = |epp: &str, target: &str| {
+        crate::audit::cpu_power::epp_needs_change(epp, target, knobs.epp_locked)
+    };
+    // One write per cpufreq policy that needs it, through its first CPU
+    if let Some(ref target_epp) = target_epp {
+        for policy in &hw.cpu.policies {
+            if !policy
+                .epp
+                .as_deref()
+                .is_some_and(|epp| epp_needs_change(epp, target_epp))
+            {
+                continue;
+            }
+            let path = policy
+                .cpu_path("energy_performance_preference")
+                .filter(|path| sysfs.exists(path))
+                .unwrap_or_else(|| policy.path("energy_performance_preference"));
+            plan.sysfs_writes.push(PlannedSysfsWrite {
+                path: format!("/{}", path),
+                value: target_epp.clone(),
+                description: format!("Set {} EPP to {}", policy.name, target_epp),
+            });
+        }
+    }
+    // Without the policy layout, every CPU when cpu0 needs it
     if let Some(ref target_epp) = target_epp
-        && hw.cpu.epp.as_deref() != Some(target_epp)
-        && (knobs.epp_locked || hw.cpu.epp.as_deref() != Some("power"))
+        && hw.cpu.policies.is_empty()
+        && hw
+            .cpu
+            .epp
+            .as_deref()
+            .is_none_or(|epp| epp_needs_change(epp, target_epp))
         && let Ok(cpus) = sysfs.list_dir("sys/devices/system/cpu")
     {
         for cpu in cpus {
@@ -267,27 +704,52 @@ fn build_plan_inner(
         }
     }
 
-    // Platform profile
-    match knobs.platform_profile {
-        PlatformProfilePolicy::ForceLowPower => {
-            if hw.platform.platform_profile.as_deref() != Some("low-power") {
-                plan.sysfs_writes.push(PlannedSysfsWrite {
-                    path: "/sys/firmware/acpi/platform_profile".to_string(),
-                    value: "low-power".to_string(),
-                    description: "Set platform profile to low-power".to_string(),
-                });
+    // Intel EPB on drivers without EPP (acpi-cpufreq) — follows the EPP target
+    if let Some(ref target_epp) = target_epp
+        && hw.cpu.uses_epb()
+        && let Ok(cpus) = sysfs.list_dir("sys/devices/system/cpu")
+    {
+        let target = crate::audit::cpu_power::epb_for_epp(target_epp);
+        for cpu in cpus {
+            if !(cpu.starts_with("cpu") && cpu[3..].chars().all(|c| c.is_ascii_digit())) {
+                continue;
             }
-        }
-        PlatformProfilePolicy::FixPerformance => {
-            if hw.platform.platform_profile.as_deref() == Some("performance") {
+            let path = format!("sys/devices/system/cpu/{}/power/energy_perf_bias", cpu);
+            if let Some(current) = sysfs
+                .read_optional(&path)
+                .unwrap_or(None)
+                .and_then(|v| v.parse::<u8>().ok())
+                && current < target
+            {
                 plan.sysfs_writes.push(PlannedSysfsWrite {
-                    path: "/sys/firmware/acpi/platform_profile".to_string(),
-                    value: "low-power".to_string(),
-                    description: "Set platform profile to low-power".to_string(),
+                    path: format!("/{}", path),
+                    value: target.to_string(),
+                    description: format!("Set {} energy_perf_bias to {}", cpu, target),
                 });
             }
         }
-        PlatformProfilePolicy::NoChange => {}
+    }
+
+    // Platform profile
+    let profile = hw.platform.platform_profile_target();
+    let needs_low_power = match knobs.platform_profile {
+        // Unreadable, or "custom" from vendor tuning that only an explicit
+        // override replaces
+        _ if profile.current.is_none() => false,
+        _ if profile.is_custom() && !knobs.platform_profile_locked => false,
+        PlatformProfilePolicy::ForceLowPower => profile.current.as_deref() != Some("low-power"),
+        PlatformProfilePolicy::FixPerformance => profile.current.as_deref() == Some("performance"),
+        PlatformProfilePolicy::NoChange => false,
+    };
+    if needs_low_power {
+        plan.sysfs_writes.push(PlannedSysfsWrite {
+            path: profile.path,
+            value: "low-power".to_string(),
+            description: match profile.owner {
+                Some(owner) => format!("Set platform profile to low-power (handler: {})", owner),
+                None => "Set platform profile to low-power".to_string(),
+            },
+        });
     }
 
     // ASPM
@@ -307,60 +769,73 @@ fn build_plan_inner(
         }
     }
 
-    // PCI runtime PM -> auto
+    // PCI runtime PM -> auto, honoring the per-device quirk table
     if knobs.pci_runtime_pm {
         for dev in &hw.pci.devices {
-            if dev.runtime_pm.as_deref() != Some("auto") {
-                plan.sysfs_writes.push(PlannedSysfsWrite {
-                    path: format!("/sys/bus/pci/devices/{}/power/control", dev.address),
-                    value: "auto".to_string(),
-                    description: format!("Enable runtime PM for PCI {}", dev.address),
-                });
+            if dev.runtime_pm.as_deref() == Some("auto") {
+                continue;
+            }
+            let quirk = match (dev.vendor.as_deref(), dev.device.as_deref()) {
+                (Some(vendor), Some(device)) => crate::quirks::lookup_pci(vendor, device),
+                _ => None,
+            };
+            match quirk.map(|q| q.action) {
+                Some(RuntimePmAction::NeverAuto) => continue,
+                Some(RuntimePmAction::NeedsModuleParam { module, param }) => {
+                    let config = crate::quirks::modprobe_config(module, param);
+                    if !plan
+                        .modprobe_configs
+                        .iter()
+                        .any(|c| c.filename == config.filename)
+                    {
+                        plan.modprobe_configs.push(config);
+                    }
+                }
+                Some(RuntimePmAction::SafeAuto) | None => {}
             }
+            plan.sysfs_writes.push(PlannedSysfsWrite {
+                path: format!("/sys/bus/pci/devices/{}/power/control", dev.address),
+                value: "auto".to_string(),
+                description: format!("Enable runtime PM for PCI {}", dev.address),
+            });
         }
     }
 
     // USB autosuspend -> auto
-    if knobs.usb_autosuspend != UsbPolicy::NoChange
-        && let Ok(devices) = sysfs.list_dir("sys/bus/usb/devices")
-    {
-        for device in devices {
-            if device.contains(':') {
+    if knobs.usb_autosuspend != UsbPolicy::NoChange {
+        let skip_input_expansion = knobs.usb_autosuspend == UsbPolicy::SkipInputExpansion;
+        for device in &hw.usb.devices {
+            if device.control.as_deref().is_none_or(|c| c == "auto") {
                 continue;
             }
-            let path = format!("sys/bus/usb/devices/{}/power/control", device);
-            if let Some(val) = sysfs.read_optional(&path).unwrap_or(None)
-                && val != "auto"
-            {
-                if knobs.usb_autosuspend == UsbPolicy::SkipInputExpansion {
-                    let product = sysfs
-                        .read_optional(format!("sys/bus/usb/devices/{}/product", device))
-                        .unwrap_or(None)
-                        .unwrap_or_default()
-                        .to_lowercase();
-
-                    let is_input = product.contains("keyboard")
-                        || product.contains("mouse")
-                        || product.contains("trackpad")
-                        || product.contains("touchpad");
-                    let is_expansion = product.contains("expansion")
-                        || product.contains("displayport")
-                        || product.contains("hdmi");
-
-                    if is_input || is_expansion {
-                        continue;
-                    }
-                }
-
+            let decision = device.autosuspend_decision(skip_input_expansion);
+            if decision.include {
                 plan.sysfs_writes.push(PlannedSysfsWrite {
-                    path: format!("/{}", path),
+                    path: format!("/sys/bus/usb/devices/{}/power/control", device.name),
                     value: "auto".to_string(),
-                    description: format!("Enable autosuspend for USB {}", device),
+                    description: format!("Enable autosuspend for USB {}", device.name),
                 });
             }
+            plan.explanations.push(PlanExplanation {
+                subject: format!("USB {}", device.name),
+                included: decision.include,
+                reason: decision.reason.to_string(),
+                factors: decision.factors,
+            });
         }
     }
 
+    // Empty USB ports -> powered off, only with this boot's kernel log to
+    // show they never had a device. A copied root's log isn't its own.
+    if knobs.usb_port_disable && hw.usb.ports.iter().any(usb_ports::is_candidate) {
+        let history = if sysfs.is_host() {
+            usb_ports::read_kernel_log().map(|log| usb_ports::parse_connect_history(&log))
+        } else {
+            None
+        };
+        plan_port_disables(&mut plan, &hw.usb.ports, history.as_ref());
+    }
+
     // Audio power save
     if knobs.audio_power_save
         && let Some(val) = sysfs
@@ -401,23 +876,79 @@ fn build_plan_inner(
         });
     }
 
-    // Turbo boost
-    if let Some(desired) = knobs.turbo_boost
-        && hw.cpu.has_boost
-        && hw.cpu.boost_enabled != desired
+    // Idle dGPU PCIe link -> 2.5 GT/s through its port's bandwidth control
+    if knobs.pcie_link_downgrade
+        && let Some(gpu) = hw.pci.discrete_gpu()
+        && gpu.holds_idle_link_above_minimum()
     {
-        let (value, description) = if desired {
-            ("1", "Enable CPU turbo boost")
-        } else {
-            ("0", "Disable CPU turbo boost")
+        let control = hw.pci.link_speed_control(gpu);
+        let mut factors = vec![format!(
+            "link at {}",
+            gpu.link.current_summary().unwrap_or_default()
+        )];
+        let target = control.and_then(|c| c.max_state.filter(|max| c.cur_state != Some(*max)));
+        let reason = match (control, target) {
+            (Some(control), Some(max_state)) => {
+                factors.push(format!("bandwidth control on port {}", control.port));
+                plan.sysfs_writes.push(PlannedSysfsWrite {
+                    path: format!("/{}/cur_state", control.path),
+                    value: max_state.to_string(),
+                    description: format!("Pin idle dGPU {} PCIe link at 2.5 GT/s", gpu.address),
+                });
+                "idle dGPU link above 2.5 GT/s"
+            }
+            (Some(_), None) => "port bandwidth control already at its lowest speed",
+            (None, _) => "no PCIe bandwidth control for its port (manual only)",
         };
-        plan.sysfs_writes.push(PlannedSysfsWrite {
-            path: "/sys/devices/system/cpu/cpufreq/boost".to_string(),
-            value: value.to_string(),
-            description: description.to_string(),
+        plan.explanations.push(PlanExplanation {
+            subject: format!("dGPU link {}", gpu.address),
+            included: target.is_some(),
+            reason: reason.to_string(),
+            factors,
         });
     }
 
+    // Turbo boost: each policy with its own switch that needs it, else the
+    // global one
+    if let Some(desired) = knobs.turbo_boost {
+        let (value, verb) = if desired {
+            ("1", "Enable")
+        } else {
+            ("0", "Disable")
+        };
+        for group in hw.cpu.boost_groups() {
+            if group.value == desired {
+                continue;
+            }
+            if group.policies.is_empty() {
+                plan.sysfs_writes.push(PlannedSysfsWrite {
+                    path: "/sys/devices/system/cpu/cpufreq/boost".to_string(),
+                    value: value.to_string(),
+                    description: format!("{} CPU turbo boost", verb),
+                });
+            }
+            for policy in &group.policies {
+                let path = policy
+                    .cpu_path("boost")
+                    .filter(|path| sysfs.exists(path))
+                    .unwrap_or_else(|| policy.path("boost"));
+                plan.sysfs_writes.push(PlannedSysfsWrite {
+                    path: format!("/{}", path),
+                    value: value.to_string(),
+                    description: format!("{} CPU turbo boost on {}", verb, policy.name),
+                });
+            }
+        }
+    }
+
+    // Package power limit, capped through RAPL's long-term constraint
+    if let Some(watts) = knobs.package_power_limit_w {
+        let floor = crate::profile::package_power_floor_w(hw);
+        let (writes, explanation) = powercap::plan(&hw.powercap, watts, floor);
+        plan.sysfs_writes.extend(writes);
+        plan.explanations.push(explanation);
+    }
+
     // NMI watchdog -> disable
     if knobs.nmi_watchdog_disable
         && let Some(val) = sysfs
@@ -446,34 +977,150 @@ fn build_plan_inner(
         });
     }
 
-    // Kernel params
+    // Sleep mode -> s2idle where an AMD kernel picked deep. Rides with the
+    // wakeup filter: both only matter to suspend. The runtime switch and
+    // the kernel parameter that keeps it go in together or not at all.
+    let force_s2idle = knobs.acpi_wakeup_filter && crate::audit::sleep::forces_s2idle(hw);
+    if force_s2idle {
+        plan.sysfs_writes.push(PlannedSysfsWrite {
+            path: "/sys/power/mem_sleep".to_string(),
+            value: "s2idle".to_string(),
+            description: "Switch suspend from deep to s2idle".to_string(),
+        });
+        let mut factors = vec![
+            "AMD CPU".to_string(),
+            format!(
+                "mem_sleep offers {}",
+                hw.platform.mem_sleep_available.join(", ")
+            ),
+            format!(
+                "LPS0 device {}",
+                hw.platform.lps0_device.as_deref().unwrap_or_default()
+            ),
+        ];
+        factors.push(match hw.platform.low_power_s0_idle {
+            Some(_) => "FADT low-power S0 idle flag set".to_string(),
+            None => "FADT not readable".to_string(),
+        });
+        plan.explanations.push(PlanExplanation {
+            subject: "mem_sleep".to_string(),
+            included: true,
+            reason: "kernel selected deep on an s2idle platform".to_string(),
+            factors,
+        });
+    }
+
+    // Kernel params: only those whose current value the rule rejects
     if knobs.kernel_params {
-        if hw.kernel_param_value("acpi.ec_no_wakeup").as_deref() != Some("1") {
-            plan.kernel_params.push("acpi.ec_no_wakeup=1".to_string());
+        use crate::audit::kernel_params::{self as params, ParamSpec};
+
+        let mut wanted: Vec<ParamSpec> = Vec::new();
+        if crate::profile::wants_ec_no_wakeup(hw, knobs) {
+            wanted.push(params::EC_NO_WAKEUP);
+        }
+        if force_s2idle {
+            wanted.push(params::MEM_SLEEP_S2IDLE);
         }
-        if hw.kernel_param_value("rtc_cmos.use_acpi_alarm").as_deref() != Some("1") {
-            plan.kernel_params
-                .push("rtc_cmos.use_acpi_alarm=1".to_string());
+        wanted.push(params::RTC_ACPI_ALARM);
+        // An explicit value is fixed wherever it's out of range; an absent
+        // one only matters with an NVMe drive
+        if params::has_nvme(hw) || params::NVME_APST_LATENCY.current(hw).is_some() {
+            wanted.push(params::NVME_APST_LATENCY);
         }
+        // ABM through the runtime attribute where the kernel has it
+        let abm_runtime = hw
+            .gpu
+            .panel_power_savings_path
+            .as_deref()
+            .zip(hw.gpu.panel_power_savings);
+        if hw.gpu.is_amd() && hw.display.supports_abm() {
+            match abm_runtime {
+                Some((path, level)) => plan_abm_runtime(&mut plan, hw, path, level),
+                None => wanted.push(params::ABM_LEVEL),
+            }
+        }
+        plan.kernel_params.extend(
+            wanted
+                .iter()
+                .filter(|spec| !spec.satisfied(hw))
+                .map(ParamSpec::param),
+        );
+
+        // Only what `bop about-safety` lists. A user's dcdebugmask, say, is
+        // usually a PSR flicker workaround: bop never sets it, and only
+        // drops it when the config asks for PSR back.
+        plan.kernel_params.retain(|p| {
+            params::SET_PARAMS
+                .iter()
+                .any(|spec| p.split('=').next() == Some(spec.name))
+        });
         if hw.gpu.is_amd()
-            && hw
-                .kernel_param_value("amdgpu.abmlevel")
-                .and_then(|v| v.parse::<u32>().ok())
-                .is_none_or(|v| v < 3)
+            && let Some(mask) = hw.kernel_param_value(PSR_DEBUG_MASK_PARAM)
         {
-            plan.kernel_params.push("amdgpu.abmlevel=3".to_string());
+            let force_psr = config.is_some_and(|c| c.display.force_psr);
+            if force_psr {
+                plan.kernel_params_remove
+                    .push(PSR_DEBUG_MASK_PARAM.to_string());
+            }
+            plan.explanations.push(PlanExplanation {
+                subject: PSR_DEBUG_MASK_PARAM.to_string(),
+                included: force_psr,
+                reason: if force_psr {
+                    "[display] force_psr = true: removed to re-enable PSR"
+                } else {
+                    "set by the user, kept (set [display] force_psr = true to remove)"
+                }
+                .to_string(),
+                factors: vec![format!(
+                    "{}={} on the kernel command line",
+                    PSR_DEBUG_MASK_PARAM, mask
+                )],
+            });
         }
     }
 
-    // Services to disable — tlp/power-profiles-daemon can overwrite sysfs values we set
-    if knobs.has_any_active() {
-        for svc in &["tlp.service", "power-profiles-daemon.service"] {
-            if is_service_active_or_enabled(svc) {
+    // Services to disable — tlp/power-profiles-daemon can overwrite sysfs values we set.
+    // One that an active unit requires is only reported: disabling it would
+    // take the dependent (e.g. a desktop portal) down with it.
+    if knobs.has_any_active() && sysfs.is_host() {
+        for svc in CONFLICTING_SERVICES {
+            if !is_service_active_or_enabled(svc) {
+                continue;
+            }
+            let required_by = services::required_by(svc);
+            if required_by.is_empty() {
                 plan.services_to_disable.push(svc.to_string());
+            } else {
+                plan.services_blocked.push(BlockedService {
+                    service: svc.to_string(),
+                    required_by,
+                });
             }
         }
     }
 
+    // WiFi power save, through whoever manages the interface
+    if knobs.has_any_active()
+        && let Some(iface) = hw.network.wifi_interface.as_deref()
+    {
+        let manager = if sysfs.is_host() {
+            wifi::WifiManager::detect(iface)
+        } else {
+            wifi::WifiManager::from_probes(None, false)
+        };
+        let iwd_conf = (manager == wifi::WifiManager::Iwd)
+            .then(|| std::fs::read_to_string(wifi::IWD_MAIN_CONF).ok())
+            .flatten();
+        let (action, explanation) = wifi::plan(
+            iface,
+            hw.network.wifi_driver.as_deref(),
+            manager,
+            iwd_conf.as_deref(),
+        );
+        plan.wifi_power_save = action;
+        plan.explanations.push(explanation);
+    }
+
     // ACPI wakeup sources to disable
     if knobs.acpi_wakeup_filter {
         for source in &hw.platform.acpi_wakeup_sources {
@@ -483,31 +1130,152 @@ fn build_plan_inner(
         }
     }
 
+    // User pins win over the preset and [overrides]: bop never writes them
+    if let Some(pins) = config.map(|c| &c.pins).filter(|p| !p.is_empty()) {
+        plan.sysfs_writes.retain(|write| {
+            let Some(pin) = pins.get(&write.path) else {
+                return true;
+            };
+            plan.explanations.push(PlanExplanation {
+                subject: write.path.clone(),
+                included: false,
+                reason: pin.to_string(),
+                factors: vec![
+                    format!("[pins] \"{}\"", pin.pattern),
+                    format!("bop would set {}", write.value),
+                ],
+            });
+            false
+        });
+        // Without the runtime write, the boot parameter is all the ABM left
+        let abm_param = crate::audit::kernel_params::ABM_LEVEL.name;
+        if plan.kernel_params_remove.iter().any(|p| p == abm_param)
+            && !plan
+                .sysfs_writes
+                .iter()
+                .any(|w| w.path.ends_with("/panel_power_savings"))
+        {
+            plan.kernel_params_remove.retain(|p| p != abm_param);
+        }
+    }
+
     plan
 }
 
+/// Raise the runtime ABM level where it's low, and drop `amdgpu.abmlevel`
+/// whenever the runtime level is bop's: amdgpu reapplies the parameter on
+/// resume, so keeping both means they fight.
+fn plan_abm_runtime(plan: &mut ApplyPlan, hw: &HardwareInfo, path: &str, level: u32) {
+    use crate::audit::kernel_params::{ABM_LEVEL, abm_conflicts, abm_runtime_target};
+
+    let target = abm_runtime_target(level);
+    let conflict = abm_conflicts(hw);
+    if target == level && !conflict {
+        return;
+    }
+    plan.sysfs_writes.push(PlannedSysfsWrite {
+        path: format!("/{}", path),
+        value: target.to_string(),
+        description: format!("Set panel ABM level to {}", target),
+    });
+
+    let Some(param) = ABM_LEVEL.current(hw) else {
+        return;
+    };
+    plan.kernel_params_remove.push(ABM_LEVEL.name.to_string());
+    plan.explanations.push(PlanExplanation {
+        subject: ABM_LEVEL.name.to_string(),
+        included: true,
+        reason: "removed: ABM is set at runtime through panel_power_savings instead".to_string(),
+        factors: vec![
+            format!("{}={} on the kernel command line", ABM_LEVEL.name, param),
+            format!("panel_power_savings={}, bop sets {}", level, target),
+        ],
+    });
+}
+
+/// Power off the empty USB ports among `ports`; `history` is this boot's
+/// connect history, None without a kernel log.
+pub fn plan_port_disables(
+    plan: &mut ApplyPlan,
+    ports: &[crate::detect::usb::UsbPort],
+    history: Option<&std::collections::BTreeSet<String>>,
+) {
+    for port in ports.iter().filter(|p| usb_ports::is_candidate(p)) {
+        let decision = usb_ports::decide(port, ports, history);
+        if decision.include {
+            plan.sysfs_writes.push(PlannedSysfsWrite {
+                path: format!("/{}/disable", port.path()),
+                value: "1".to_string(),
+                description: format!("Power off empty USB port {}", port.name),
+            });
+        }
+        plan.explanations
+            .push(usb_ports::explanation(port, decision));
+    }
+}
+
 fn is_service_active_or_enabled(service: &str) -> bool {
-    std::process::Command::new("systemctl")
+    crate::cmd::Cmd::new("systemctl")
         .args(["is-active", "--quiet", service])
         .status()
         .is_ok_and(|s| s.success())
-        || std::process::Command::new("systemctl")
+        || crate::cmd::Cmd::new("systemctl")
             .args(["is-enabled", "--quiet", service])
             .status()
             .is_ok_and(|s| s.success())
 }
 
 trait ApplyOps {
+    /// Whether these ops act on the running system. Offline ops only
+    /// install persistent artifacts, so runtime steps are skipped.
+    fn live_system(&self) -> bool {
+        true
+    }
     fn write_sysfs(&mut self, path: &str, value: &str) -> Result<()>;
-    fn toggle_acpi_wakeup(&mut self, device: &str) -> Result<()>;
-    fn add_kernel_params(
+    /// Bring an ACPI wakeup source to `enabled`, verified; false if it
+    /// already was.
+    fn toggle_acpi_wakeup(&mut self, device: &str, enabled: bool) -> Result<bool>;
+    fn edit_kernel_params(
         &mut self,
         params: &[String],
+        remove: &[String],
     ) -> Result<Vec<kernel_params::KernelParamBackup>>;
     fn disable_service(&mut self, service: &str) -> Result<()>;
-    fn generate_service(&mut self, hw: &HardwareInfo, plan: &ApplyPlan) -> Result<PathBuf>;
+    fn enable_service(&mut self, service: &str) -> Result<()>;
+    fn write_modprobe_config(
+        &mut self,
+        config: &ModprobeConfig,
+        ownership: &Ownership,
+    ) -> Result<ManagedFile>;
+    /// Write the NetworkManager drop-in and have it reread its config.
+    fn write_wifi_drop_in(&mut self, content: &str, ownership: &Ownership) -> Result<ManagedFile>;
+    /// Remove the NetworkManager drop-in at `path` and have it reread its
+    /// config. False if it was already gone.
+    fn remove_wifi_drop_in(&mut self, path: &Path, ownership: &Ownership) -> Result<bool>;
+    /// Write the USB port re-enable rule and have udev reread its rules.
+    fn write_usb_port_rule(&mut self, content: &str, ownership: &Ownership) -> Result<ManagedFile>;
+    /// Remove the USB port rule at `path` and have udev reread its rules.
+    /// False if it was already gone.
+    fn remove_usb_port_rule(&mut self, path: &Path, ownership: &Ownership) -> Result<bool>;
+    /// Write the sysctl.d drop-in, read at the next boot.
+    fn write_sysctl_drop_in(&mut self, content: &str, ownership: &Ownership)
+    -> Result<ManagedFile>;
+    fn generate_service(&mut self, plan: &ApplyPlan, ownership: &Ownership) -> Result<ManagedFile>;
     fn enable_systemd_service(&mut self) -> Result<()>;
+    fn load_state(&mut self) -> Result<Option<ApplyState>> {
+        ApplyState::load()
+    }
     fn save_state(&mut self, state: &ApplyState) -> Result<()>;
+    /// Ownership of the files in `manifest`, as recorded in the state.
+    fn ownership(&self, manifest: &[ManagedFile], force: bool) -> Ownership {
+        Ownership::new(manifest, force)
+    }
+    /// Where sysfs write intents are journaled; None where no runtime
+    /// writes happen.
+    fn journal_path(&self) -> Option<PathBuf> {
+        Some(journal::journal_path())
+    }
 }
 
 struct RealApplyOps;
@@ -517,23 +1285,71 @@ impl ApplyOps for RealApplyOps {
         sysfs_writer::write_sysfs(path, value)
     }
 
-    fn toggle_acpi_wakeup(&mut self, device: &str) -> Result<()> {
-        sysfs_writer::toggle_acpi_wakeup(device)
+    fn toggle_acpi_wakeup(&mut self, device: &str, enabled: bool) -> Result<bool> {
+        sysfs_writer::toggle_acpi_wakeup(device, enabled)
     }
 
-    fn add_kernel_params(
+    fn edit_kernel_params(
         &mut self,
         params: &[String],
+        remove: &[String],
     ) -> Result<Vec<kernel_params::KernelParamBackup>> {
-        kernel_params::add_kernel_params(params)
+        kernel_params::edit_kernel_params(params, remove)
     }
 
     fn disable_service(&mut self, service: &str) -> Result<()> {
         services::disable_service(service)
     }
 
-    fn generate_service(&mut self, hw: &HardwareInfo, plan: &ApplyPlan) -> Result<PathBuf> {
-        systemd::generate_service(hw, plan)
+    fn enable_service(&mut self, service: &str) -> Result<()> {
+        services::enable_service(service)
+    }
+
+    fn write_modprobe_config(
+        &mut self,
+        config: &ModprobeConfig,
+        ownership: &Ownership,
+    ) -> Result<ManagedFile> {
+        let path = PathBuf::from(MODPROBE_DIR).join(&config.filename);
+        ownership.write(&path, &config.content)?;
+        Ok(ManagedFile::new(path.to_string_lossy(), &config.content))
+    }
+
+    fn write_wifi_drop_in(&mut self, content: &str, ownership: &Ownership) -> Result<ManagedFile> {
+        ownership.write(Path::new(wifi::NM_DROP_IN_PATH), content)?;
+        wifi::reload_network_manager();
+        Ok(ManagedFile::new(wifi::NM_DROP_IN_PATH, content))
+    }
+
+    fn remove_wifi_drop_in(&mut self, path: &Path, ownership: &Ownership) -> Result<bool> {
+        let removed = ownership.remove(path)?;
+        wifi::reload_network_manager();
+        Ok(removed)
+    }
+
+    fn write_usb_port_rule(&mut self, content: &str, ownership: &Ownership) -> Result<ManagedFile> {
+        ownership.write(Path::new(usb_ports::UDEV_RULE_PATH), content)?;
+        usb_ports::reload_udev_rules();
+        Ok(ManagedFile::new(usb_ports::UDEV_RULE_PATH, content))
+    }
+
+    fn remove_usb_port_rule(&mut self, path: &Path, ownership: &Ownership) -> Result<bool> {
+        let removed = ownership.remove(path)?;
+        usb_ports::reload_udev_rules();
+        Ok(removed)
+    }
+
+    fn write_sysctl_drop_in(
+        &mut self,
+        content: &str,
+        ownership: &Ownership,
+    ) -> Result<ManagedFile> {
+        ownership.write(Path::new(sysctl::DROP_IN_PATH), content)?;
+        Ok(ManagedFile::new(sysctl::DROP_IN_PATH, content))
+    }
+
+    fn generate_service(&mut self, plan: &ApplyPlan, ownership: &Ownership) -> Result<ManagedFile> {
+        systemd::generate_service(plan, ownership)
     }
 
     fn enable_systemd_service(&mut self) -> Result<()> {
@@ -552,26 +1368,61 @@ fn persist_state_checkpoint(
 ) -> Result<()> {
     if !dry_run && state.has_recorded_changes() {
         ops.save_state(state)?;
+        // The intents are in the state now
+        if let Some(path) = ops.journal_path() {
+            journal::clear(&path)?;
+        }
     }
     Ok(())
 }
 
+#[cfg(test)]
 fn execute_plan_with_ops(
     plan: &ApplyPlan,
     hw: &HardwareInfo,
     dry_run: bool,
     ops: &mut impl ApplyOps,
 ) -> Result<ApplyState> {
+    let mut state = ApplyState::default();
+    execute_plan_into(
+        plan,
+        hw,
+        dry_run,
+        false,
+        ops,
+        &mut state,
+        &mut crate::progress::Quiet,
+    )?;
+    Ok(state)
+}
+
+/// Execute into a caller-owned state so the changes made before a failure
+/// stay visible to the caller (e.g. for `--report`). Generated files only
+/// replace files bop owns, unless `force`.
+fn execute_plan_into(
+    plan: &ApplyPlan,
+    hw: &HardwareInfo,
+    dry_run: bool,
+    force: bool,
+    ops: &mut impl ApplyOps,
+    state: &mut ApplyState,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
+    // A run that died between a sysfs write and its checkpoint left the
+    // original in the journal; get it into the state before reading that.
+    if !dry_run && let Some(path) = ops.journal_path() {
+        recover_journal(ops, &path, progress)?;
+    }
+
     // Load previous state up front, before any checkpoint can overwrite the file.
     let previous_state = if !dry_run {
-        match ApplyState::load() {
+        match ops.load_state() {
             Ok(s) => s,
             Err(e) => {
-                eprintln!(
-                    "{} Failed to load previous state ({}); proceeding without merge.",
-                    "!".yellow(),
+                progress.emit(Progress::Warning(format!(
+                    "Failed to load previous state ({}); proceeding without merge.",
                     e
-                );
+                )));
                 None
             }
         }
@@ -579,98 +1430,389 @@ fn execute_plan_with_ops(
         None
     };
 
-    let mut state = ApplyState {
+    let ownership = ops.ownership(
+        previous_state
+            .as_ref()
+            .map(|s| s.managed_files.as_slice())
+            .unwrap_or_default(),
+        force,
+    );
+
+    // The caller may have adjusted the plan since it was built
+    crate::crash::note_plan(plan);
+    let live = ops.live_system();
+    *state = ApplyState {
         timestamp: chrono::Utc::now().to_rfc3339(),
+        // Counters from the build host mean nothing to an offline root
+        wakeup_baseline: live.then(|| WakeupBaseline::capture(&hw.platform)),
+        boot_id: live.then(current_boot_id).flatten(),
+        preset: plan.preset,
+        // Still pending: the timer reverts whatever the state holds then
+        rollback: previous_state.as_ref().and_then(|s| s.rollback.clone()),
         ..Default::default()
     };
 
-    let sysfs = SysfsRoot::system();
-
-    // Apply runtime sysfs writes.
-    for write in &plan.sysfs_writes {
-        let relative = write.path.strip_prefix('/').unwrap_or(&write.path);
-        let original = sysfs
-            .read_optional(relative)
-            .unwrap_or(None)
-            .unwrap_or_default();
-
+    let started = Instant::now();
+    let elapsed_ms = || started.elapsed().as_millis() as u64;
+    let phases = plan.phases();
+    let mut enabled = Ok(());
+    for (i, &phase) in phases.iter().enumerate() {
         if dry_run {
-            println!(
-                "  [dry-run] {} -> {} (was: {})",
-                write.path, write.value, original
-            );
-        } else {
-            ops.write_sysfs(&write.path, &write.value)?;
-            state.sysfs_changes.push(SysfsChange {
-                path: write.path.clone(),
-                original_value: original,
-                new_value: write.value.clone(),
-            });
+            progress.emit(Progress::Info(format!(
+                "Phase {}/{}: {}",
+                i + 1,
+                phases.len(),
+                phase
+            )));
+        }
+        let started_ms = elapsed_ms();
+        let result = match phase {
+            Phase::Sysfs => run_sysfs_phase(
+                plan,
+                live,
+                dry_run,
+                previous_state.as_ref(),
+                &ownership,
+                ops,
+                state,
+                progress,
+            ),
+            Phase::AcpiWakeup => run_acpi_wakeup_phase(plan, live, dry_run, ops, state, progress),
+            Phase::KernelParams => run_kernel_params_phase(
+                plan,
+                dry_run,
+                previous_state.as_ref(),
+                ops,
+                state,
+                progress,
+            ),
+            Phase::Services => run_services_phase(plan, live, dry_run, ops, state, progress),
+            Phase::Modprobe => run_modprobe_phase(plan, dry_run, &ownership, ops, state, progress),
+            Phase::Wifi => run_wifi_phase(dry_run, &ownership, ops, state, progress),
+            Phase::Persistence => {
+                let generated =
+                    run_persistence_phase(plan, dry_run, &ownership, ops, state, progress);
+                // Timed with the phase, as `systemctl` can be the slow part
+                if generated.is_ok() && !dry_run {
+                    enabled = ops.enable_systemd_service();
+                }
+                generated
+            }
+        };
+        state.timings.insert(
+            phase,
+            PhaseTiming {
+                started_ms,
+                duration_ms: elapsed_ms() - started_ms,
+            },
+        );
+        state.duration_ms = Some(elapsed_ms());
+        // Including what a failing phase got done before it stopped
+        crate::crash::note_state(state);
+        result?;
+        persist_state_checkpoint(ops, state, dry_run)?;
+    }
+
+    // Enabling records nothing new, so a failure is raised after the last
+    // checkpoint: the unit is in the state for revert either way.
+    enabled
+}
+
+/// Fold the intents left in the journal at `path` into the saved state.
+fn recover_journal(
+    ops: &mut impl ApplyOps,
+    path: &Path,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
+    let intents = journal::read(path)?;
+    if !intents.is_empty() {
+        let mut state = ops.load_state()?.unwrap_or_else(|| ApplyState {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            ..Default::default()
+        });
+        let recovered = journal::fold(&mut state, &intents);
+        if recovered > 0 {
+            progress.emit(Progress::Warning(format!(
+                "Recovered {} original value(s) from an interrupted run",
+                recovered
+            )));
+        }
+        if state.has_recorded_changes() {
+            ops.save_state(&state)?;
+        }
+    }
+    journal::clear(path)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_sysfs_phase(
+    plan: &ApplyPlan,
+    live: bool,
+    dry_run: bool,
+    previous_state: Option<&ApplyState>,
+    ownership: &Ownership,
+    ops: &mut impl ApplyOps,
+    state: &mut ApplyState,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
+    if !live {
+        progress.emit(Progress::Info(format!(
+            "  Skipping {} sysfs writes for the offline root; bop-powersave.service applies them at boot",
+            plan.sysfs_writes.len()
+        )));
+        if let Some(content) = sysctl::drop_in(&plan.sysfs_writes) {
+            if dry_run {
+                progress.emit(Progress::Info(format!(
+                    "  [dry-run] Write {}",
+                    sysctl::DROP_IN_PATH
+                )));
+            } else {
+                let file = ops.write_sysctl_drop_in(&content, ownership)?;
+                state.sysctl_files_created.push(file.path.clone());
+                state.managed_files.push(file);
+            }
+        }
+        return Ok(());
+    }
+    let sysfs = SysfsRoot::system();
+    for write in &plan.sysfs_writes {
+        let relative = write.path.strip_prefix('/').unwrap_or(&write.path);
+        // Keep the raw contents; SysfsChange derives the restorable form.
+        // A path an earlier run already changed keeps that run's original.
+        let original = match previous_state
+            .and_then(|s| s.sysfs_changes.iter().find(|c| c.path == write.path))
+        {
+            Some(change) => change.original_value.clone(),
+            None => sysfs
+                .read_bytes_optional(relative)
+                .unwrap_or(None)
+                .map(|b| String::from_utf8_lossy(&b).into_owned())
+                .unwrap_or_default(),
+        };
+
+        if dry_run {
+            progress.emit(Progress::Info(format!(
+                "  [dry-run] {} -> {} (was: {})",
+                write.path,
+                write.value,
+                crate::sysfs::normalize_value(&original)
+            )));
+        } else {
+            // On disk before the write, so a crash can't lose the original
+            let intent = journal::Intent::new(&write.path, original, &write.value);
+            if let Some(path) = ops.journal_path() {
+                journal::append(&path, &intent)?;
+            }
+            ops.write_sysfs(&write.path, &write.value)?;
+            state.sysfs_changes.push(intent.to_change());
+        }
+    }
+
+    // Powered-off USB ports come back when their hub sees a device
+    if let Some(rule) = usb_ports::udev_rule(plan.sysfs_writes.iter().map(|w| w.path.as_str())) {
+        if dry_run {
+            progress.emit(Progress::Info(format!(
+                "  [dry-run] Write {}",
+                usb_ports::UDEV_RULE_PATH
+            )));
+        } else {
+            let file = ops.write_usb_port_rule(&rule, ownership)?;
+            state.udev_files_created.push(file.path.clone());
+            state.managed_files.push(file);
         }
     }
+    Ok(())
+}
 
-    // ACPI wakeup toggling.
+fn run_acpi_wakeup_phase(
+    plan: &ApplyPlan,
+    live: bool,
+    dry_run: bool,
+    ops: &mut impl ApplyOps,
+    state: &mut ApplyState,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
+    if !live {
+        progress.emit(Progress::Info(format!(
+            "  Skipping {} ACPI wakeup changes for the offline root; bop-powersave.service applies them at boot",
+            plan.acpi_wakeup_disable.len()
+        )));
+        return Ok(());
+    }
     for device in &plan.acpi_wakeup_disable {
         if dry_run {
-            println!("  [dry-run] Disable ACPI wakeup: {}", device);
-        } else if is_wakeup_enabled(device, &sysfs) {
-            // /proc/acpi/wakeup is a toggle - only flip currently enabled sources.
-            ops.toggle_acpi_wakeup(device)?;
+            progress.emit(Progress::Info(format!(
+                "  [dry-run] Disable ACPI wakeup: {}",
+                device
+            )));
+        } else if ops.toggle_acpi_wakeup(device, false)? {
+            // /proc/acpi/wakeup is a toggle; record only sources we flipped
             state.acpi_wakeup_toggled.push(device.clone());
         }
     }
-    persist_state_checkpoint(ops, &state, dry_run)?;
+    Ok(())
+}
 
-    // Kernel params.
-    if !plan.kernel_params.is_empty() {
-        if dry_run {
-            println!(
+fn run_kernel_params_phase(
+    plan: &ApplyPlan,
+    dry_run: bool,
+    previous_state: Option<&ApplyState>,
+    ops: &mut impl ApplyOps,
+    state: &mut ApplyState,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
+    if dry_run {
+        if !plan.kernel_params.is_empty() {
+            progress.emit(Progress::Info(format!(
                 "  [dry-run] Add kernel params: {}",
                 plan.kernel_params.join(" ")
-            );
-        } else {
-            let backups = ops.add_kernel_params(&plan.kernel_params)?;
-            merge_kernel_param_state(
-                &mut state,
-                &plan.kernel_params,
-                backups,
-                previous_state.as_ref(),
-            );
+            )));
         }
-    }
-    persist_state_checkpoint(ops, &state, dry_run)?;
+        if !plan.kernel_params_remove.is_empty() {
+            progress.emit(Progress::Info(format!(
+                "  [dry-run] Remove kernel params: {}",
+                plan.kernel_params_remove.join(" ")
+            )));
+        }
+        return Ok(());
+    }
+    let backups = ops.edit_kernel_params(&plan.kernel_params, &plan.kernel_params_remove)?;
+    // Only what left a file this time or an earlier apply; a param the edit
+    // never found was not removed, and revert must not claim to restore it
+    let removed_now = kernel_params::removed_params(&plan.kernel_params_remove, &backups);
+    state.kernel_params_removed = plan
+        .kernel_params_remove
+        .iter()
+        .filter(|name| {
+            removed_now.contains(name)
+                || previous_state.is_some_and(|prev| prev.kernel_params_removed.contains(name))
+        })
+        .cloned()
+        .collect();
+    merge_kernel_param_state(state, &plan.kernel_params, backups, previous_state);
+    Ok(())
+}
 
-    // Service management.
+fn run_services_phase(
+    plan: &ApplyPlan,
+    live: bool,
+    dry_run: bool,
+    ops: &mut impl ApplyOps,
+    state: &mut ApplyState,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
+    if !live {
+        progress.emit(Progress::Info(format!(
+            "  Skipping service changes for the offline root: {}",
+            plan.services_to_disable.join(", ")
+        )));
+        return Ok(());
+    }
     for svc in &plan.services_to_disable {
         if dry_run {
-            println!("  [dry-run] Disable service: {}", svc);
+            progress.emit(Progress::Info(format!(
+                "  [dry-run] Disable service: {}",
+                svc
+            )));
         } else {
             ops.disable_service(svc)?;
-            state.services_disabled.push(svc.clone());
+            state.service_disabled(svc);
         }
     }
-    persist_state_checkpoint(ops, &state, dry_run)?;
+    Ok(())
+}
 
-    // Generate/enable persistence service.
-    if plan.systemd_service && !plan.sysfs_writes.is_empty() {
+/// Module parameters take effect on the next module load.
+fn run_modprobe_phase(
+    plan: &ApplyPlan,
+    dry_run: bool,
+    ownership: &Ownership,
+    ops: &mut impl ApplyOps,
+    state: &mut ApplyState,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
+    for config in &plan.modprobe_configs {
         if dry_run {
-            println!("  [dry-run] Generate bop-powersave.service");
+            progress.emit(Progress::Info(format!(
+                "  [dry-run] Write {}/{}",
+                MODPROBE_DIR, config.filename
+            )));
         } else {
-            let unit_path = ops.generate_service(hw, plan)?;
-            state
-                .systemd_units_created
-                .push(unit_path.to_string_lossy().into_owned());
-            // Persist immediately so a later enable failure can still be reverted.
-            persist_state_checkpoint(ops, &state, dry_run)?;
-            ops.enable_systemd_service()?;
+            let file = ops.write_modprobe_config(config, ownership)?;
+            state.modprobe_files_created.push(file.path.clone());
+            state.managed_files.push(file);
         }
     }
+    Ok(())
+}
+
+/// WiFi power save through NetworkManager, applied on the next connect.
+fn run_wifi_phase(
+    dry_run: bool,
+    ownership: &Ownership,
+    ops: &mut impl ApplyOps,
+    state: &mut ApplyState,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
+    if dry_run {
+        progress.emit(Progress::Info(format!(
+            "  [dry-run] Write {}",
+            wifi::NM_DROP_IN_PATH
+        )));
+        return Ok(());
+    }
+    let file = ops.write_wifi_drop_in(&wifi::nm_drop_in(), ownership)?;
+    state.wifi_files_created.push(file.path.clone());
+    state.managed_files.push(file);
+    Ok(())
+}
+
+/// Write the persistence unit; it is enabled after the checkpoint that
+/// records it.
+fn run_persistence_phase(
+    plan: &ApplyPlan,
+    dry_run: bool,
+    ownership: &Ownership,
+    ops: &mut impl ApplyOps,
+    state: &mut ApplyState,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
+    if dry_run {
+        progress.emit(Progress::Info(
+            "  [dry-run] Generate bop-powersave.service".to_string(),
+        ));
+        return Ok(());
+    }
+    let unit = ops.generate_service(plan, ownership)?;
+    state.systemd_units_created.push(unit.path.clone());
+    state.managed_files.push(unit);
+    Ok(())
+}
 
+/// Execute the apply plan, reporting each step to `progress`. With `force`,
+/// generated files may replace files bop doesn't recognise as its own.
+pub fn execute_plan(
+    plan: &ApplyPlan,
+    hw: &HardwareInfo,
+    dry_run: bool,
+    force: bool,
+    progress: &mut dyn ProgressSink,
+) -> Result<ApplyState> {
+    let mut state = ApplyState::default();
+    execute_plan_tracked(plan, hw, dry_run, force, &mut state, progress)?;
     Ok(state)
 }
 
-/// Execute the apply plan.
-pub fn execute_plan(plan: &ApplyPlan, hw: &HardwareInfo, dry_run: bool) -> Result<ApplyState> {
+/// Like [`execute_plan`], but records into `state` as it goes; on error,
+/// `state` holds everything that was changed before the failure.
+pub fn execute_plan_tracked(
+    plan: &ApplyPlan,
+    hw: &HardwareInfo,
+    dry_run: bool,
+    force: bool,
+    state: &mut ApplyState,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
     if !dry_run && !nix::unistd::geteuid().is_root() {
         return Err(Error::NotRoot {
             operation: "apply".to_string(),
@@ -681,7 +1823,245 @@ pub fn execute_plan(plan: &ApplyPlan, hw: &HardwareInfo, dry_run: bool) -> Resul
     check_conflicts()?;
 
     let mut ops = RealApplyOps;
-    execute_plan_with_ops(plan, hw, dry_run, &mut ops)
+    execute_plan_into(plan, hw, dry_run, force, &mut ops, state, progress)
+}
+
+/// Bring the items of `state` that `scope` covers to what `plan` asks for,
+/// in place: only the items that differ are written or restored, and the
+/// rest of the state stays as the last full apply left it. For `bop auto`'s
+/// layers, which change a few items of an apply rather than all of them.
+pub fn switch_layer(
+    plan: &ApplyPlan,
+    scope: LayerScope,
+    state: &mut ApplyState,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
+    let mut ops = RealApplyOps;
+    switch_layer_with_ops(plan, scope, &SysfsRoot::system(), &mut ops, state, progress)
+}
+
+fn switch_layer_with_ops(
+    plan: &ApplyPlan,
+    scope: LayerScope,
+    sysfs: &SysfsRoot,
+    ops: &mut impl ApplyOps,
+    state: &mut ApplyState,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
+    let result = switch_layer_items(plan, scope, sysfs, ops, state, progress);
+    // What got done before a failure is recorded all the same
+    if plan.preset.is_some() {
+        state.preset = plan.preset;
+    }
+    ops.save_state(state)?;
+    if let Some(path) = ops.journal_path() {
+        journal::clear(&path)?;
+    }
+    result
+}
+
+fn switch_layer_items(
+    plan: &ApplyPlan,
+    scope: LayerScope,
+    sysfs: &SysfsRoot,
+    ops: &mut impl ApplyOps,
+    state: &mut ApplyState,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
+    let ownership = Ownership::new(&state.managed_files, false);
+    let mut sysfs_changed = false;
+
+    // Restore what the plan no longer asks for
+    let dropped: Vec<SysfsChange> = state
+        .sysfs_changes
+        .iter()
+        .filter(|c| {
+            scope.covers_sysfs(&c.path) && !plan.sysfs_writes.iter().any(|w| w.path == c.path)
+        })
+        .cloned()
+        .collect();
+    for change in dropped {
+        let restore = change.restore_value();
+        ops.write_sysfs(&change.path, &restore)?;
+        progress.emit(Progress::Step(format!(
+            "{} {} -> {}",
+            change.path,
+            change.expected_value(),
+            restore
+        )));
+        state.sysfs_changes.retain(|c| c.path != change.path);
+        sysfs_changed = true;
+    }
+
+    // Write what it asks for that isn't there yet, keeping the original an
+    // earlier run recorded
+    for write in plan
+        .sysfs_writes
+        .iter()
+        .filter(|w| scope.covers_sysfs(&w.path))
+    {
+        let current = state
+            .sysfs_changes
+            .iter()
+            .position(|c| c.path == write.path);
+        let original = match current {
+            Some(i)
+                if state.sysfs_changes[i].expected_value()
+                    == crate::sysfs::normalize_value(&write.value) =>
+            {
+                continue;
+            }
+            Some(i) => state.sysfs_changes[i].original_value.clone(),
+            None => sysfs
+                .read_bytes_optional(write.path.trim_start_matches('/'))
+                .unwrap_or(None)
+                .map(|b| String::from_utf8_lossy(&b).into_owned())
+                .unwrap_or_default(),
+        };
+        let intent = journal::Intent::new(&write.path, original, &write.value);
+        if let Some(path) = ops.journal_path() {
+            journal::append(&path, &intent)?;
+        }
+        ops.write_sysfs(&write.path, &write.value)?;
+        progress.emit(Progress::Step(format!("{} -> {}", write.path, write.value)));
+        match current {
+            Some(i) => state.sysfs_changes[i] = intent.to_change(),
+            None => state.sysfs_changes.push(intent.to_change()),
+        }
+        sysfs_changed = true;
+    }
+
+    // Ports powered off now come back through the rule for their hubs
+    if scope == LayerScope::Runtime {
+        let rule = usb_ports::udev_rule(state.sysfs_changes.iter().map(|c| c.path.as_str()));
+        let current = state.udev_files_created.first().cloned();
+        match (rule, current) {
+            (Some(rule), current) => {
+                let unchanged = current.as_ref().is_some_and(|path| {
+                    state
+                        .managed_files
+                        .contains(&ManagedFile::new(path.as_str(), &rule))
+                });
+                if !unchanged {
+                    let file = ops.write_usb_port_rule(&rule, &ownership)?;
+                    state.udev_files_created.retain(|p| *p != file.path);
+                    state.managed_files.retain(|m| m.path != file.path);
+                    state.udev_files_created.push(file.path.clone());
+                    state.managed_files.push(file);
+                }
+            }
+            (None, Some(path)) => {
+                ops.remove_usb_port_rule(Path::new(&path), &ownership)?;
+                state.udev_files_created.retain(|p| *p != path);
+                state.managed_files.retain(|m| m.path != path);
+            }
+            (None, None) => {}
+        }
+    }
+
+    if scope == LayerScope::Runtime {
+        for device in &plan.acpi_wakeup_disable {
+            if !state.acpi_wakeup_toggled.contains(device)
+                && ops.toggle_acpi_wakeup(device, false)?
+            {
+                progress.emit(Progress::Step(format!("disabled wakeup {}", device)));
+                state.acpi_wakeup_toggled.push(device.clone());
+            }
+        }
+        let restored: Vec<String> = state
+            .acpi_wakeup_toggled
+            .iter()
+            .filter(|d| !plan.acpi_wakeup_disable.contains(d))
+            .cloned()
+            .collect();
+        for device in restored {
+            ops.toggle_acpi_wakeup(&device, true)?;
+            progress.emit(Progress::Step(format!("enabled wakeup {}", device)));
+            state.acpi_wakeup_toggled.retain(|d| *d != device);
+        }
+    }
+
+    if scope.covers_services() {
+        for svc in &plan.services_to_disable {
+            if !state.services_disabled.contains(svc) {
+                ops.disable_service(svc)?;
+                progress.emit(Progress::Step(format!("disabled {}", svc)));
+                state.service_disabled(svc);
+            }
+        }
+        let restored: Vec<String> = state
+            .services_disabled
+            .iter()
+            .filter(|s| !plan.services_to_disable.contains(s))
+            .cloned()
+            .collect();
+        for svc in restored {
+            ops.enable_service(&svc)?;
+            progress.emit(Progress::Step(format!("enabled {}", svc)));
+            state.services_disabled.retain(|s| *s != svc);
+            state.services_disabled_at.remove(&svc);
+        }
+    }
+
+    // WiFi power save through NetworkManager takes effect on the next connect
+    let wifi_wanted = plan.wifi_power_save == Some(wifi::WifiPowerSave::NetworkManager);
+    match (wifi_wanted, state.wifi_files_created.first().cloned()) {
+        (true, None) => {
+            let file = ops.write_wifi_drop_in(&wifi::nm_drop_in(), &ownership)?;
+            progress.emit(Progress::Step(format!("wrote {}", file.path)));
+            state.wifi_files_created.push(file.path.clone());
+            state.managed_files.push(file);
+        }
+        (false, Some(path)) => {
+            ops.remove_wifi_drop_in(Path::new(&path), &ownership)?;
+            progress.emit(Progress::Step(format!("removed {}", path)));
+            state.wifi_files_created.retain(|p| *p != path);
+            state.managed_files.retain(|m| m.path != path);
+        }
+        _ => {}
+    }
+
+    // The unit replays the recorded writes at boot; keep it in step
+    if sysfs_changed && !state.systemd_units_created.is_empty() {
+        let replay = ApplyPlan {
+            sysfs_writes: state
+                .sysfs_changes
+                .iter()
+                .map(|c| PlannedSysfsWrite {
+                    path: c.path.clone(),
+                    value: c.expected_value(),
+                    description: String::new(),
+                })
+                .collect(),
+            ..plan.clone()
+        };
+        let unit = ops.generate_service(&replay, &ownership)?;
+        state.managed_files.retain(|m| m.path != unit.path);
+        state.managed_files.push(unit);
+    }
+    Ok(())
+}
+
+/// Install the persistent parts of `plan` (kernel params, modprobe configs,
+/// the sysctl drop-in, the persistence unit and its enable link, the state
+/// file) into an image's root filesystem instead of the running system.
+/// Runtime sysfs writes, ACPI wakeup toggles and service changes are
+/// skipped; the unit applies the runtime settings on first boot, where
+/// `bop status`/`revert` find the state. `target` is the sysfs `hw` was
+/// detected from: the machine the image is for, not the build host.
+#[allow(clippy::too_many_arguments)]
+pub fn execute_plan_offline(
+    plan: &ApplyPlan,
+    hw: &HardwareInfo,
+    target: &SysfsRoot,
+    root: &offline::OfflineRoot,
+    dry_run: bool,
+    force: bool,
+    state: &mut ApplyState,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
+    let mut ops = offline::OfflineOps::new(root, target);
+    execute_plan_into(plan, hw, dry_run, force, &mut ops, state, progress)
 }
 
 fn merge_kernel_param_state(
@@ -711,7 +2091,7 @@ fn merge_kernel_param_state(
 }
 
 fn check_conflicts() -> Result<()> {
-    if std::process::Command::new("systemctl")
+    if crate::cmd::Cmd::new("systemctl")
         .args(["is-active", "--quiet", "tlp.service"])
         .status()
         .is_ok_and(|s| s.success())
@@ -723,22 +2103,12 @@ fn check_conflicts() -> Result<()> {
     Ok(())
 }
 
-fn is_wakeup_enabled(device: &str, sysfs: &SysfsRoot) -> bool {
-    if let Ok(wakeup) = sysfs.read("proc/acpi/wakeup") {
-        for line in wakeup.lines() {
-            if line.starts_with(device) {
-                return line.contains("*enabled");
-            }
-        }
-    }
-    false
-}
-
 fn should_disable_acpi_wakeup_source(
     source: &crate::detect::platform::AcpiWakeupSource,
     hw: &HardwareInfo,
 ) -> bool {
-    if !source.enabled || source.device == "XHC0" {
+    // Writing a shared name would toggle whichever is listed first
+    if !source.enabled || source.ambiguous || source.device == "XHC0" {
         return false;
     }
 
@@ -785,102 +2155,91 @@ fn is_usb_pci_device(device: &crate::detect::pci::PciDevice) -> bool {
     })
 }
 
-pub fn print_plan(plan: &ApplyPlan) {
-    use colored::Colorize;
-
-    println!("{}", "Apply Plan".bold().underline());
-    println!();
-
-    if !plan.sysfs_writes.is_empty() {
-        println!("  {} Runtime sysfs changes:", ">>".cyan());
-        for write in &plan.sysfs_writes {
-            println!(
-                "     {} {}",
-                write.description.dimmed(),
-                write.path.dimmed()
-            );
-        }
-        println!();
-    }
-
-    if !plan.kernel_params.is_empty() {
-        println!("  {} Kernel parameters (requires reboot):", ">>".cyan());
-        for param in &plan.kernel_params {
-            println!("     {}", param);
-        }
-        println!();
-    }
-
-    if !plan.services_to_disable.is_empty() {
-        println!("  {} Services to disable:", ">>".cyan());
-        for svc in &plan.services_to_disable {
-            println!("     {}", svc);
-        }
-        println!();
-    }
-
-    if !plan.acpi_wakeup_disable.is_empty() {
-        println!(
-            "  {} ACPI wakeup sources to disable (volatile, resets on reboot):",
-            ">>".cyan()
-        );
-        for dev in &plan.acpi_wakeup_disable {
-            println!("     {}", dev);
-        }
-        println!();
-    }
-
-    if plan.systemd_service {
-        println!(
-            "  {} Will generate bop-powersave.service for boot persistence",
-            ">>".cyan()
-        );
-        println!();
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::path::Path;
     use tempfile::TempDir;
 
+    /// Where a simulated kill lands around a sysfs write.
+    #[derive(Clone, Copy)]
+    enum Kill {
+        /// Intent journaled, value not yet written
+        BeforeWrite,
+        /// Value written, state not yet checkpointed
+        AfterWrite,
+    }
+
     struct TestApplyOps {
         state_path: PathBuf,
+        /// Where absolute sysfs paths land, for plans written against /sys
+        sysfs_root: Option<PathBuf>,
+        /// Die at the nth sysfs write (0-based)
+        kill: Option<(usize, Kill)>,
+        sysfs_writes: usize,
         fail_add_kernel_params: bool,
         fail_generate_service: bool,
         fail_enable_service: bool,
-        checkpoint_count: usize,
+        /// How long enabling the unit takes
+        enable_delay: std::time::Duration,
+        /// The phase of every op called, in call order
+        phase_log: Vec<Phase>,
+        /// The phase of the last op before each state save
+        checkpoints: Vec<Option<Phase>>,
+        wakeup: sysfs_writer::MockWakeupTable,
     }
 
     impl TestApplyOps {
         fn new(state_path: PathBuf) -> Self {
             Self {
                 state_path,
+                sysfs_root: None,
+                kill: None,
+                sysfs_writes: 0,
                 fail_add_kernel_params: false,
                 fail_generate_service: false,
                 fail_enable_service: false,
-                checkpoint_count: 0,
+                enable_delay: std::time::Duration::ZERO,
+                phase_log: Vec::new(),
+                checkpoints: Vec::new(),
+                wakeup: sysfs_writer::MockWakeupTable::new(""),
             }
         }
     }
 
     impl ApplyOps for TestApplyOps {
         fn write_sysfs(&mut self, path: &str, value: &str) -> Result<()> {
-            std::fs::write(path, value).map_err(|source| Error::SysfsWrite {
-                path: PathBuf::from(path),
-                source,
-            })
+            self.phase_log.push(Phase::Sysfs);
+            let n = self.sysfs_writes;
+            self.sysfs_writes += 1;
+            let killed = || Err(Error::Other(format!("killed at sysfs write {}", n)));
+            if let Some((at, Kill::BeforeWrite)) = self.kill
+                && at == n
+            {
+                return killed();
+            }
+            let path = match &self.sysfs_root {
+                Some(root) => root.join(path.trim_start_matches('/')),
+                None => PathBuf::from(path),
+            };
+            std::fs::write(&path, value).map_err(|source| Error::SysfsWrite { path, source })?;
+            match self.kill {
+                Some((at, Kill::AfterWrite)) if at == n => killed(),
+                _ => Ok(()),
+            }
         }
 
-        fn toggle_acpi_wakeup(&mut self, _device: &str) -> Result<()> {
-            Ok(())
+        fn toggle_acpi_wakeup(&mut self, device: &str, enabled: bool) -> Result<bool> {
+            self.phase_log.push(Phase::AcpiWakeup);
+            sysfs_writer::toggle_acpi_wakeup_in(&mut self.wakeup, device, enabled)
         }
 
-        fn add_kernel_params(
+        fn edit_kernel_params(
             &mut self,
             _params: &[String],
+            _remove: &[String],
         ) -> Result<Vec<kernel_params::KernelParamBackup>> {
+            self.phase_log.push(Phase::KernelParams);
             if self.fail_add_kernel_params {
                 return Err(Error::Other("injected kernel params failure".to_string()));
             }
@@ -888,27 +2247,109 @@ mod tests {
         }
 
         fn disable_service(&mut self, _service: &str) -> Result<()> {
+            self.phase_log.push(Phase::Services);
+            Ok(())
+        }
+
+        fn enable_service(&mut self, _service: &str) -> Result<()> {
+            self.phase_log.push(Phase::Services);
             Ok(())
         }
 
-        fn generate_service(&mut self, _hw: &HardwareInfo, _plan: &ApplyPlan) -> Result<PathBuf> {
+        fn write_modprobe_config(
+            &mut self,
+            config: &ModprobeConfig,
+            ownership: &Ownership,
+        ) -> Result<ManagedFile> {
+            self.phase_log.push(Phase::Modprobe);
+            let dir = self.state_path.with_file_name("modprobe.d");
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = dir.join(&config.filename);
+            ownership.write(&path, &config.content)?;
+            Ok(ManagedFile::new(path.to_string_lossy(), &config.content))
+        }
+
+        fn write_wifi_drop_in(
+            &mut self,
+            content: &str,
+            ownership: &Ownership,
+        ) -> Result<ManagedFile> {
+            self.phase_log.push(Phase::Wifi);
+            let dir = self.state_path.with_file_name("conf.d");
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("bop-wifi-powersave.conf");
+            ownership.write(&path, content)?;
+            Ok(ManagedFile::new(path.to_string_lossy(), content))
+        }
+
+        fn remove_wifi_drop_in(&mut self, path: &Path, ownership: &Ownership) -> Result<bool> {
+            self.phase_log.push(Phase::Wifi);
+            ownership.remove(path)
+        }
+
+        fn write_usb_port_rule(
+            &mut self,
+            content: &str,
+            ownership: &Ownership,
+        ) -> Result<ManagedFile> {
+            self.phase_log.push(Phase::Sysfs);
+            let dir = self.state_path.with_file_name("rules.d");
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("86-bop-usb-ports.rules");
+            ownership.write(&path, content)?;
+            Ok(ManagedFile::new(path.to_string_lossy(), content))
+        }
+
+        fn remove_usb_port_rule(&mut self, path: &Path, ownership: &Ownership) -> Result<bool> {
+            self.phase_log.push(Phase::Sysfs);
+            ownership.remove(path)
+        }
+
+        fn write_sysctl_drop_in(
+            &mut self,
+            content: &str,
+            ownership: &Ownership,
+        ) -> Result<ManagedFile> {
+            self.phase_log.push(Phase::Sysfs);
+            let dir = self.state_path.with_file_name("sysctl.d");
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("60-bop.conf");
+            ownership.write(&path, content)?;
+            Ok(ManagedFile::new(path.to_string_lossy(), content))
+        }
+
+        fn generate_service(
+            &mut self,
+            _plan: &ApplyPlan,
+            _ownership: &Ownership,
+        ) -> Result<ManagedFile> {
+            self.phase_log.push(Phase::Persistence);
             if self.fail_generate_service {
                 return Err(Error::Other(
                     "injected systemd generation failure".to_string(),
                 ));
             }
-            Ok(PathBuf::from("/etc/systemd/system/bop-powersave.service"))
+            Ok(ManagedFile::new(
+                "/etc/systemd/system/bop-powersave.service",
+                "",
+            ))
         }
 
         fn enable_systemd_service(&mut self) -> Result<()> {
+            self.phase_log.push(Phase::Persistence);
+            std::thread::sleep(self.enable_delay);
             if self.fail_enable_service {
                 return Err(Error::Other("injected systemd enable failure".to_string()));
             }
             Ok(())
         }
 
+        fn load_state(&mut self) -> Result<Option<ApplyState>> {
+            ApplyState::load_from(&self.state_path)
+        }
+
         fn save_state(&mut self, state: &ApplyState) -> Result<()> {
-            self.checkpoint_count += 1;
+            self.checkpoints.push(self.phase_log.last().copied());
             if let Some(parent) = self.state_path.parent() {
                 std::fs::create_dir_all(parent)
                     .map_err(|e| Error::State(format!("failed to create state dir: {}", e)))?;
@@ -919,6 +2360,10 @@ mod tests {
                 .map_err(|e| Error::State(format!("failed to write state file: {}", e)))?;
             Ok(())
         }
+
+        fn journal_path(&self) -> Option<PathBuf> {
+            Some(self.state_path.with_file_name("intents.jsonl"))
+        }
     }
 
     fn minimal_hw() -> HardwareInfo {
@@ -938,13 +2383,41 @@ mod tests {
                 description: "test write".to_string(),
             }],
             kernel_params: Vec::new(),
+            kernel_params_remove: Vec::new(),
             services_to_disable: Vec::new(),
+            services_blocked: Vec::new(),
             acpi_wakeup_disable: Vec::new(),
             systemd_service: true,
             modprobe_configs: Vec::new(),
+            wifi_power_save: None,
+            explanations: Vec::new(),
+            preset: None,
         }
     }
 
+    #[test]
+    fn test_blocked_services_warn_until_forced() {
+        let mut plan = ApplyPlan {
+            services_to_disable: vec!["tlp.service".to_string()],
+            services_blocked: vec![BlockedService {
+                service: "power-profiles-daemon.service".to_string(),
+                required_by: vec!["gnome-shell-portal-helper.service".to_string()],
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            plan.services_blocked[0].warning(),
+            "not disabling power-profiles-daemon.service: required by gnome-shell-portal-helper.service"
+        );
+
+        plan.force_disable_blocked();
+        assert_eq!(
+            plan.services_to_disable,
+            ["tlp.service", "power-profiles-daemon.service"]
+        );
+        assert!(plan.services_blocked.is_empty());
+    }
+
     #[test]
     fn test_execute_plan_persists_sysfs_state_before_systemd_generation_failure() {
         let tmp = TempDir::new().unwrap();
@@ -966,19 +2439,174 @@ mod tests {
         assert_eq!(persisted.sysfs_changes[0].original_value, "old");
         assert_eq!(persisted.sysfs_changes[0].new_value, "new");
         assert!(persisted.systemd_units_created.is_empty());
+        assert_eq!(ops.checkpoints, [Some(Phase::Sysfs)]);
     }
 
-    #[test]
-    fn test_execute_plan_persists_created_unit_before_systemd_enable_failure() {
+    /// Two writes, "old" -> "new", killed at the second one; the in-memory
+    /// state dies with the process.
+    fn killed_apply(kill: Kill) -> (TempDir, PathBuf, [String; 2]) {
         let tmp = TempDir::new().unwrap();
         let state_path = tmp.path().join("state.json");
-        let sysfs_path = tmp.path().join("sysfs-value");
-        std::fs::write(&sysfs_path, "old").unwrap();
+        let paths = ["a", "b"].map(|name| {
+            let path = tmp.path().join(name);
+            std::fs::write(&path, "old").unwrap();
+            path.to_string_lossy().into_owned()
+        });
+        let mut plan = basic_plan(Path::new(&paths[0]));
+        plan.sysfs_writes.push(PlannedSysfsWrite {
+            path: paths[1].clone(),
+            value: "new".to_string(),
+            description: "test write".to_string(),
+        });
+        let mut ops = TestApplyOps::new(state_path.clone());
+        ops.kill = Some((1, kill));
+        assert!(execute_plan_with_ops(&plan, &minimal_hw(), false, &mut ops).is_err());
+        assert!(!state_path.exists());
+        (tmp, state_path, paths)
+    }
 
-        let hw = minimal_hw();
-        let mut plan = basic_plan(&sysfs_path);
-        plan.kernel_params = vec!["acpi.ec_no_wakeup=1".to_string()];
-        plan.services_to_disable = vec!["dummy.service".to_string()];
+    fn recorded(state_path: &Path) -> Vec<(String, String)> {
+        read_state(state_path)
+            .sysfs_changes
+            .into_iter()
+            .map(|c| (c.path, c.original_value))
+            .collect()
+    }
+
+    #[test]
+    fn test_kill_between_intent_and_write_records_only_landed_writes() {
+        let (_tmp, state_path, paths) = killed_apply(Kill::BeforeWrite);
+        let journal = state_path.with_file_name("intents.jsonl");
+        assert_eq!(journal::read(&journal).unwrap().len(), 2);
+
+        assert_eq!(journal::reconcile_at(&state_path, &journal).unwrap(), 1);
+        assert_eq!(
+            recorded(&state_path),
+            [(paths[0].clone(), "old".to_string())]
+        );
+        assert!(!journal.exists());
+    }
+
+    #[test]
+    fn test_kill_between_write_and_checkpoint_keeps_originals_for_the_next_apply() {
+        let (_tmp, state_path, paths) = killed_apply(Kill::AfterWrite);
+        assert_eq!(std::fs::read_to_string(&paths[1]).unwrap(), "new");
+
+        // The next apply finds both values already changed, but records the
+        // journaled originals rather than what it reads now
+        let mut plan = basic_plan(Path::new(&paths[0]));
+        plan.systemd_service = false;
+        plan.sysfs_writes.push(PlannedSysfsWrite {
+            path: paths[1].clone(),
+            value: "new".to_string(),
+            description: "test write".to_string(),
+        });
+        let mut ops = TestApplyOps::new(state_path.clone());
+        execute_plan_with_ops(&plan, &minimal_hw(), false, &mut ops).unwrap();
+        assert_eq!(recorded(&state_path), paths.map(|p| (p, "old".to_string())));
+        assert!(!state_path.with_file_name("intents.jsonl").exists());
+    }
+
+    #[test]
+    fn test_checkpoint_empties_the_journal() {
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+        let sysfs_path = tmp.path().join("sysfs-value");
+        std::fs::write(&sysfs_path, "old").unwrap();
+        let mut ops = TestApplyOps::new(state_path.clone());
+        execute_plan_with_ops(&basic_plan(&sysfs_path), &minimal_hw(), false, &mut ops).unwrap();
+        assert!(!state_path.with_file_name("intents.jsonl").exists());
+        assert_eq!(recorded(&state_path).len(), 1);
+    }
+
+    #[test]
+    fn test_state_records_plan_preset() {
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+        let sysfs_path = tmp.path().join("sysfs-value");
+        std::fs::write(&sysfs_path, "old").unwrap();
+
+        let mut plan = basic_plan(&sysfs_path);
+        plan.preset = Some(Preset::Saver);
+        let mut ops = TestApplyOps::new(state_path.clone());
+        execute_plan_with_ops(&plan, &minimal_hw(), false, &mut ops).unwrap();
+
+        let raw = std::fs::read_to_string(&state_path).unwrap();
+        assert!(raw.contains("\"preset\": \"saver\""));
+        let loaded = ApplyState::load_from(&state_path).unwrap().unwrap();
+        assert_eq!(loaded.preset, Some(Preset::Saver));
+
+        // State files from before the field load with the preset unknown
+        let legacy = serde_json::to_string(&ApplyState::default())
+            .unwrap()
+            .replace(",\"preset\":null", "");
+        assert!(!legacy.contains("preset"));
+        std::fs::write(&state_path, legacy).unwrap();
+        let loaded = ApplyState::load_from(&state_path).unwrap().unwrap();
+        assert_eq!(loaded.preset, None);
+    }
+
+    #[test]
+    fn test_execute_plan_retries_acpi_wakeup_when_the_table_shifts() {
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+        let sysfs_path = tmp.path().join("sysfs-value");
+        std::fs::write(&sysfs_path, "old").unwrap();
+
+        let hw = minimal_hw();
+        let mut plan = basic_plan(&sysfs_path);
+        plan.acpi_wakeup_disable = vec!["XHC1".to_string(), "LID0".to_string()];
+        let mut ops = TestApplyOps::new(state_path.clone());
+        ops.wakeup = sysfs_writer::MockWakeupTable::new(
+            "XHC1\tS3\t*enabled\tpci:0000:c1:00.4\n\
+             XHC10\tS3\t*enabled\tpci:0000:c4:00.3\n\
+             LID0\tS4\t*disabled\tplatform:PNP0C0D:00\n",
+        );
+        // A dock re-enumerates the table and the first write hits XHC10
+        ops.wakeup.misdirected_writes = 1;
+
+        execute_plan_with_ops(&plan, &hw, false, &mut ops).unwrap();
+        assert_eq!(ops.wakeup.enabled("XHC1"), Some(false));
+        assert_eq!(ops.wakeup.enabled("LID0"), Some(false));
+        // LID0 was already disabled, so revert must not flip it back
+        assert_eq!(read_state(&state_path).acpi_wakeup_toggled, ["XHC1"]);
+    }
+
+    #[test]
+    fn test_execute_plan_fails_when_acpi_wakeup_does_not_stick() {
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+        let sysfs_path = tmp.path().join("sysfs-value");
+        std::fs::write(&sysfs_path, "old").unwrap();
+
+        let hw = minimal_hw();
+        let mut plan = basic_plan(&sysfs_path);
+        plan.acpi_wakeup_disable = vec!["XHC1".to_string()];
+        let mut ops = TestApplyOps::new(state_path);
+        ops.wakeup = sysfs_writer::MockWakeupTable::new(
+            "XHC1\tS3\t*enabled\tpci:0000:c1:00.4\n\
+             XHC10\tS3\t*enabled\tpci:0000:c4:00.3\n",
+        );
+        ops.wakeup.misdirected_writes = 2;
+
+        let err = execute_plan_with_ops(&plan, &hw, false, &mut ops).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::AcpiWakeupToggle { ref device, enabled: false, .. } if device == "XHC1"
+        ));
+    }
+
+    #[test]
+    fn test_execute_plan_persists_created_unit_before_systemd_enable_failure() {
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+        let sysfs_path = tmp.path().join("sysfs-value");
+        std::fs::write(&sysfs_path, "old").unwrap();
+
+        let hw = minimal_hw();
+        let mut plan = basic_plan(&sysfs_path);
+        plan.kernel_params = vec!["acpi.ec_no_wakeup=1".to_string()];
+        plan.services_to_disable = vec!["dummy.service".to_string()];
 
         let mut ops = TestApplyOps::new(state_path.clone());
         ops.fail_enable_service = true;
@@ -994,7 +2622,647 @@ mod tests {
             persisted.systemd_units_created,
             vec!["/etc/systemd/system/bop-powersave.service".to_string()]
         );
-        assert_eq!(ops.checkpoint_count, 4);
+        // One checkpoint per phase with work, the last one before enabling
+        assert_eq!(
+            plan.phases(),
+            [
+                Phase::Sysfs,
+                Phase::KernelParams,
+                Phase::Services,
+                Phase::Persistence
+            ]
+        );
+        assert_eq!(
+            ops.checkpoints,
+            plan.phases().into_iter().map(Some).collect::<Vec<_>>()
+        );
+        assert_eq!(ops.phase_log.last(), Some(&Phase::Persistence));
+    }
+
+    #[test]
+    fn test_crash_bundle_holds_changes_made_before_a_failing_phase() {
+        let tmp = TempDir::new().unwrap();
+        let sysfs_path = tmp.path().join("sysfs-value");
+        std::fs::write(&sysfs_path, "old").unwrap();
+
+        let mut plan = basic_plan(&sysfs_path);
+        plan.kernel_params = vec!["acpi.ec_no_wakeup=1".to_string()];
+        plan.services_to_disable = vec!["dummy.service".to_string()];
+        let mut ops = TestApplyOps::new(tmp.path().join("state.json"));
+        ops.fail_add_kernel_params = true;
+
+        let err = execute_plan_with_ops(&plan, &minimal_hw(), false, &mut ops).unwrap_err();
+        let bundle = crate::crash::CrashBundle::capture(
+            &SysfsRoot::new(tmp.path()),
+            "bop apply",
+            vec![err.to_string()],
+        );
+
+        assert_eq!(bundle.plan.unwrap().kernel_params, plan.kernel_params);
+        let state = bundle.state.unwrap();
+        assert_eq!(state.sysfs_changes.len(), 1);
+        assert!(state.kernel_params_added.is_empty());
+        assert!(state.services_disabled.is_empty());
+    }
+
+    #[test]
+    fn test_crash_bundle_after_last_phase_holds_full_state() {
+        let tmp = TempDir::new().unwrap();
+        let sysfs_path = tmp.path().join("sysfs-value");
+        std::fs::write(&sysfs_path, "old").unwrap();
+
+        let plan = basic_plan(&sysfs_path);
+        let mut ops = TestApplyOps::new(tmp.path().join("state.json"));
+        ops.fail_enable_service = true;
+
+        let err = execute_plan_with_ops(&plan, &minimal_hw(), false, &mut ops).unwrap_err();
+        let bundle = crate::crash::CrashBundle::capture(
+            &SysfsRoot::new(tmp.path()),
+            "bop auto",
+            vec![err.to_string()],
+        );
+
+        let state = bundle.state.unwrap();
+        assert_eq!(state.sysfs_changes.len(), 1);
+        assert_eq!(
+            state.systemd_units_created,
+            vec!["/etc/systemd/system/bop-powersave.service".to_string()]
+        );
+    }
+
+    fn plan_with_every_phase(sysfs_path: &Path) -> ApplyPlan {
+        let mut plan = basic_plan(sysfs_path);
+        plan.acpi_wakeup_disable = vec!["XHC1".to_string()];
+        plan.kernel_params = vec!["acpi.ec_no_wakeup=1".to_string()];
+        plan.services_to_disable = vec!["dummy.service".to_string()];
+        plan.modprobe_configs = vec![crate::quirks::modprobe_config(
+            "rtsx_pci",
+            "enable_runtime_pm=1",
+        )];
+        plan.wifi_power_save = Some(wifi::WifiPowerSave::NetworkManager);
+        plan
+    }
+
+    #[test]
+    fn test_execute_plan_runs_phases_in_order_with_a_checkpoint_each() {
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+        let sysfs_path = tmp.path().join("sysfs-value");
+        std::fs::write(&sysfs_path, "old").unwrap();
+
+        let plan = plan_with_every_phase(&sysfs_path);
+        assert_eq!(plan.phases(), Phase::ALL);
+        let mut ops = TestApplyOps::new(state_path);
+        ops.wakeup = sysfs_writer::MockWakeupTable::new("XHC1\tS3\t*enabled\tpci:0000:c1:00.4\n");
+        execute_plan_with_ops(&plan, &minimal_hw(), false, &mut ops).unwrap();
+
+        let mut order = ops.phase_log.clone();
+        order.dedup();
+        assert_eq!(order, Phase::ALL);
+        assert_eq!(
+            ops.checkpoints,
+            Phase::ALL.into_iter().map(Some).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_execute_plan_times_each_phase() {
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+        let sysfs_path = tmp.path().join("sysfs-value");
+        std::fs::write(&sysfs_path, "old").unwrap();
+
+        let mut plan = plan_with_every_phase(&sysfs_path);
+        plan.services_to_disable.clear();
+        let mut ops = TestApplyOps::new(state_path.clone());
+        ops.wakeup = sysfs_writer::MockWakeupTable::new("XHC1\tS3\t*enabled\tpci:0000:c1:00.4\n");
+        ops.enable_delay = std::time::Duration::from_millis(50);
+        execute_plan_with_ops(&plan, &minimal_hw(), false, &mut ops).unwrap();
+
+        let state = ApplyState::load_from(&state_path).unwrap().unwrap();
+        let timed: Vec<Phase> = state.timings.keys().copied().collect();
+        assert_eq!(timed, plan.phases());
+        // Enabling the unit counts toward its phase
+        assert!(state.timings[&Phase::Persistence].duration_ms >= 50);
+        // Each phase starts once the one before it has finished
+        let mut end = 0;
+        for timing in state.timings.values() {
+            assert!(timing.started_ms >= end);
+            end = timing.started_ms + timing.duration_ms;
+        }
+        assert!(state.duration_ms.is_some_and(|total| total >= end));
+
+        // Older state files have neither
+        let old: ApplyState = serde_json::from_str(
+            r#"{"timestamp":"t","sysfs_changes":[],"kernel_params_added":[],"services_disabled":[],"systemd_units_created":[],"modprobe_files_created":[],"acpi_wakeup_toggled":[]}"#,
+        )
+        .unwrap();
+        assert!(old.timings.is_empty() && old.duration_ms.is_none());
+        assert!(!old.has_slow_phase());
+
+        let mut slow = state.clone();
+        slow.timings.insert(
+            Phase::KernelParams,
+            PhaseTiming {
+                started_ms: 10,
+                duration_ms: SLOW_PHASE_MS + 1,
+            },
+        );
+        assert!(slow.has_slow_phase());
+        let json = serde_json::to_value(&slow).unwrap();
+        assert_eq!(
+            json["timings"]["kernel_params"]["duration_ms"],
+            SLOW_PHASE_MS + 1
+        );
+    }
+
+    #[test]
+    fn test_dry_run_announces_each_phase() {
+        let tmp = TempDir::new().unwrap();
+        let sysfs_path = tmp.path().join("sysfs-value");
+        std::fs::write(&sysfs_path, "old").unwrap();
+
+        let mut plan = plan_with_every_phase(&sysfs_path);
+        plan.kernel_params.clear();
+        plan.modprobe_configs.clear();
+        let mut ops = TestApplyOps::new(tmp.path().join("state.json"));
+        let mut progress: Vec<Progress> = Vec::new();
+        let mut state = ApplyState::default();
+        execute_plan_into(
+            &plan,
+            &minimal_hw(),
+            true,
+            false,
+            &mut ops,
+            &mut state,
+            &mut progress,
+        )
+        .unwrap();
+
+        let headers: Vec<String> = progress
+            .iter()
+            .filter_map(|p| match p {
+                Progress::Info(line) if line.starts_with("Phase ") => Some(line.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            headers,
+            [
+                "Phase 1/5: sysfs",
+                "Phase 2/5: ACPI wakeup",
+                "Phase 3/5: services",
+                "Phase 4/5: WiFi power save",
+                "Phase 5/5: persistence unit",
+            ]
+        );
+        assert!(ops.phase_log.is_empty());
+        assert!(ops.checkpoints.is_empty());
+    }
+
+    #[test]
+    fn test_execute_plan_records_modprobe_files() {
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+
+        let hw = minimal_hw();
+        let plan = ApplyPlan {
+            modprobe_configs: vec![crate::quirks::modprobe_config(
+                "rtsx_pci",
+                "enable_runtime_pm=1",
+            )],
+            ..Default::default()
+        };
+        let mut ops = TestApplyOps::new(state_path.clone());
+
+        let state = execute_plan_with_ops(&plan, &hw, false, &mut ops).unwrap();
+        let written = tmp.path().join("modprobe.d/bop-rtsx_pci.conf");
+        assert_eq!(
+            state.modprobe_files_created,
+            vec![written.to_string_lossy().into_owned()]
+        );
+        assert!(
+            std::fs::read_to_string(&written)
+                .unwrap()
+                .contains("options rtsx_pci enable_runtime_pm=1")
+        );
+        assert_eq!(
+            read_state(&state_path).modprobe_files_created,
+            state.modprobe_files_created
+        );
+        let content = std::fs::read_to_string(&written).unwrap();
+        assert!(crate::managed::has_header(&content));
+        assert_eq!(
+            read_state(&state_path).managed_files,
+            vec![ManagedFile::new(written.to_string_lossy(), &content)]
+        );
+    }
+
+    #[test]
+    fn test_execute_plan_records_usb_port_rule_with_port_disables() {
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+        let port = tmp.path().join("1-0:1.0/usb1-port2");
+        std::fs::create_dir_all(&port).unwrap();
+        std::fs::write(port.join("disable"), "0\n").unwrap();
+        let disable = port.join("disable").to_string_lossy().into_owned();
+
+        let plan = ApplyPlan {
+            sysfs_writes: vec![PlannedSysfsWrite {
+                path: disable.clone(),
+                value: "1".to_string(),
+                description: "Power off empty USB port usb1-port2".to_string(),
+            }],
+            ..Default::default()
+        };
+        let mut ops = TestApplyOps::new(state_path.clone());
+
+        let state = execute_plan_with_ops(&plan, &minimal_hw(), false, &mut ops).unwrap();
+        let written = tmp.path().join("rules.d/86-bop-usb-ports.rules");
+        assert_eq!(
+            state.udev_files_created,
+            vec![written.to_string_lossy().into_owned()]
+        );
+        let rule = std::fs::read_to_string(&written).unwrap();
+        assert!(rule.contains(&format!(
+            "KERNELS==\"usb1\", RUN+=\"/bin/sh -c 'echo 0 > {}'\"",
+            disable
+        )));
+        assert_eq!(state.sysfs_changes[0].restore_value(), "0");
+        assert_eq!(
+            read_state(&state_path).managed_files,
+            vec![ManagedFile::new(written.to_string_lossy(), &rule)]
+        );
+    }
+
+    #[test]
+    fn test_execute_plan_keeps_a_pending_rollback() {
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+        let pending = rollback::PendingRollback {
+            unit: rollback::UNIT_NAME.to_string(),
+            due: "2026-01-01T00:30:00+00:00".to_string(),
+            boot_id: None,
+        };
+        ApplyState {
+            rollback: Some(pending.clone()),
+            ..Default::default()
+        }
+        .save_to(&state_path)
+        .unwrap();
+        let profile = tmp.path().join("platform_profile");
+        std::fs::write(&profile, "balanced\n").unwrap();
+
+        let plan = ApplyPlan {
+            sysfs_writes: vec![PlannedSysfsWrite {
+                path: profile.to_string_lossy().into_owned(),
+                value: "low-power".to_string(),
+                description: "Set platform profile to low-power".to_string(),
+            }],
+            ..Default::default()
+        };
+        let mut ops = TestApplyOps::new(state_path.clone());
+        let state = execute_plan_with_ops(&plan, &minimal_hw(), false, &mut ops).unwrap();
+        assert_eq!(state.rollback, Some(pending.clone()));
+        assert_eq!(read_state(&state_path).rollback, Some(pending));
+    }
+
+    #[test]
+    fn test_execute_plan_records_wifi_drop_in() {
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+
+        let hw = minimal_hw();
+        let plan = ApplyPlan {
+            wifi_power_save: Some(wifi::WifiPowerSave::NetworkManager),
+            ..Default::default()
+        };
+        assert!(!plan.is_empty());
+        let mut ops = TestApplyOps::new(state_path.clone());
+
+        let state = execute_plan_with_ops(&plan, &hw, false, &mut ops).unwrap();
+        let written = tmp.path().join("conf.d/bop-wifi-powersave.conf");
+        assert_eq!(
+            state.wifi_files_created,
+            vec![written.to_string_lossy().into_owned()]
+        );
+        let content = std::fs::read_to_string(&written).unwrap();
+        assert!(content.contains("wifi.powersave = 3"));
+        let saved = read_state(&state_path);
+        assert_eq!(saved.wifi_files_created, state.wifi_files_created);
+        assert_eq!(
+            saved.managed_files,
+            vec![ManagedFile::new(written.to_string_lossy(), &content)]
+        );
+        // Nothing for the unit to do on NetworkManager's behalf
+        assert!(state.systemd_units_created.is_empty());
+    }
+
+    fn sysfs_write(path: &str, value: &str) -> PlannedSysfsWrite {
+        PlannedSysfsWrite {
+            path: path.to_string(),
+            value: value.to_string(),
+            description: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_switch_layer_touches_only_the_latency_sensitive_items() {
+        const USB: &str = "/sys/bus/usb/devices/1-1/power/control";
+        const EPP: &str = "/sys/devices/system/cpu/cpufreq/policy0/energy_performance_preference";
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+        let sysfs = SysfsRoot::new(tmp.path());
+        let mut ops = TestApplyOps::new(state_path.clone());
+        ops.sysfs_root = Some(tmp.path().to_path_buf());
+        let on_disk = |path: &str| {
+            std::fs::read_to_string(tmp.path().join(path.trim_start_matches('/'))).unwrap()
+        };
+        for (path, value) in [(USB, "auto"), (EPP, "power")] {
+            let file = tmp.path().join(path.trim_start_matches('/'));
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, value).unwrap();
+        }
+
+        let full = ApplyPlan {
+            sysfs_writes: vec![sysfs_write(USB, "auto"), sysfs_write(EPP, "power")],
+            systemd_service: true,
+            wifi_power_save: Some(wifi::WifiPowerSave::NetworkManager),
+            preset: Some(Preset::Moderate),
+            ..Default::default()
+        };
+        let drop_in = ops
+            .write_wifi_drop_in(&wifi::nm_drop_in(), &Ownership::default())
+            .unwrap();
+        let mut state = ApplyState {
+            sysfs_changes: vec![
+                SysfsChange::new(USB, "on\n", "auto"),
+                SysfsChange::new(EPP, "balance_performance\n", "power"),
+            ],
+            systemd_units_created: vec![systemd::SERVICE_PATH.to_string()],
+            wifi_files_created: vec![drop_in.path.clone()],
+            managed_files: vec![drop_in.clone()],
+            services_disabled: vec!["tlp.service".to_string()],
+            preset: Some(Preset::Moderate),
+            ..Default::default()
+        };
+
+        let mut held = full.clone();
+        held.hold_latency_sensitive();
+        switch_layer_with_ops(
+            &held,
+            LayerScope::LatencySensitive,
+            &sysfs,
+            &mut ops,
+            &mut state,
+            &mut crate::progress::Quiet,
+        )
+        .unwrap();
+        assert_eq!(on_disk(USB), "on");
+        assert_eq!(on_disk(EPP), "power");
+        assert_eq!(state.sysfs_changes.len(), 1);
+        assert_eq!(state.sysfs_changes[0].path, EPP);
+        assert!(state.wifi_files_created.is_empty());
+        assert!(!Path::new(&drop_in.path).exists());
+        assert_eq!(state.services_disabled, ["tlp.service"]);
+        // No GRUB, services or module options, only the unit kept in step
+        assert!(
+            ops.phase_log
+                .iter()
+                .all(|p| matches!(p, Phase::Sysfs | Phase::Wifi | Phase::Persistence))
+        );
+        assert_eq!(read_state(&state_path).sysfs_changes.len(), 1);
+
+        switch_layer_with_ops(
+            &full,
+            LayerScope::LatencySensitive,
+            &sysfs,
+            &mut ops,
+            &mut state,
+            &mut crate::progress::Quiet,
+        )
+        .unwrap();
+        assert_eq!(on_disk(USB), "auto");
+        let usb = state.sysfs_changes.iter().find(|c| c.path == USB).unwrap();
+        assert_eq!(usb.restore_value(), "on");
+        assert_eq!(
+            state.wifi_files_created,
+            std::slice::from_ref(&drop_in.path)
+        );
+        assert!(Path::new(&drop_in.path).exists());
+
+        // Already there: nothing written again
+        let writes = ops.sysfs_writes;
+        switch_layer_with_ops(
+            &full,
+            LayerScope::LatencySensitive,
+            &sysfs,
+            &mut ops,
+            &mut state,
+            &mut crate::progress::Quiet,
+        )
+        .unwrap();
+        assert_eq!(ops.sysfs_writes, writes);
+    }
+
+    #[test]
+    fn test_switch_layer_deferred_leaves_sysfs_and_wakeup_alone() {
+        const EPP: &str = "/sys/devices/system/cpu/cpufreq/policy0/energy_performance_preference";
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+        let sysfs = SysfsRoot::new(tmp.path());
+        let mut ops = TestApplyOps::new(state_path.clone());
+        ops.sysfs_root = Some(tmp.path().to_path_buf());
+        let epp = tmp.path().join(EPP.trim_start_matches('/'));
+        std::fs::create_dir_all(epp.parent().unwrap()).unwrap();
+        std::fs::write(&epp, "balance_power").unwrap();
+
+        // Applied with WiFi power save and the service change deferred
+        let mut state = ApplyState {
+            sysfs_changes: vec![SysfsChange::new(
+                EPP,
+                "balance_performance\n",
+                "balance_power",
+            )],
+            acpi_wakeup_toggled: vec!["XHC0".to_string()],
+            deferred: vec!["wifi_power_save".to_string(), "services".to_string()],
+            preset: Some(Preset::Moderate),
+            ..Default::default()
+        };
+        // The same preset's plan, now that they can go through; its EPP
+        // differs from what's applied, which a resume doesn't touch
+        let plan = ApplyPlan {
+            sysfs_writes: vec![sysfs_write(EPP, "power")],
+            services_to_disable: vec!["tlp.service".to_string()],
+            acpi_wakeup_disable: vec!["GPP1".to_string()],
+            wifi_power_save: Some(wifi::WifiPowerSave::NetworkManager),
+            preset: Some(Preset::Moderate),
+            ..Default::default()
+        };
+        switch_layer_with_ops(
+            &plan,
+            LayerScope::Deferred,
+            &sysfs,
+            &mut ops,
+            &mut state,
+            &mut crate::progress::Quiet,
+        )
+        .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&epp).unwrap(), "balance_power");
+        assert_eq!(ops.sysfs_writes, 0);
+        assert_eq!(state.sysfs_changes[0].expected_value(), "balance_power");
+        assert_eq!(state.acpi_wakeup_toggled, ["XHC0"]);
+        assert_eq!(state.services_disabled, ["tlp.service"]);
+        assert_eq!(state.wifi_files_created.len(), 1);
+        assert!(!ops.phase_log.contains(&Phase::Sysfs));
+    }
+
+    #[test]
+    fn test_switch_layer_runtime_changes_only_what_differs() {
+        const EPP: &str = "/sys/devices/system/cpu/cpufreq/policy0/energy_performance_preference";
+        const PROFILE: &str = "/sys/firmware/acpi/platform_profile";
+        const ASPM: &str = "/sys/module/pcie_aspm/parameters/policy";
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+        let sysfs = SysfsRoot::new(tmp.path());
+        let mut ops = TestApplyOps::new(state_path.clone());
+        ops.sysfs_root = Some(tmp.path().to_path_buf());
+        ops.wakeup = sysfs_writer::MockWakeupTable::new(
+            "XHC0\tS3\t*disabled\tpci:0000:c1:00.3\n\
+             GPP1\tS4\t*enabled\tpci:0000:00:02.1\n",
+        );
+        let on_disk = |path: &str| {
+            std::fs::read_to_string(tmp.path().join(path.trim_start_matches('/'))).unwrap()
+        };
+        for (path, value) in [
+            (EPP, "balance_power"),
+            (PROFILE, "balanced"),
+            (ASPM, "powersave"),
+        ] {
+            let file = tmp.path().join(path.trim_start_matches('/'));
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, value).unwrap();
+        }
+        let mut state = ApplyState {
+            sysfs_changes: vec![
+                SysfsChange::new(EPP, "balance_performance\n", "balance_power"),
+                SysfsChange::new(ASPM, "default\n", "powersave"),
+            ],
+            services_disabled: vec!["tlp.service".to_string()],
+            services_disabled_at: BTreeMap::from([(
+                "tlp.service".to_string(),
+                "2026-10-01T08:00:00+00:00".to_string(),
+            )]),
+            acpi_wakeup_toggled: vec!["XHC0".to_string()],
+            kernel_params_added: vec!["amd_pstate=active".to_string()],
+            preset: Some(Preset::Moderate),
+            ..Default::default()
+        };
+
+        // The lid's preset: more EPP, a platform profile, no ASPM write,
+        // GPP1 instead of XHC0, one more service
+        let lid = ApplyPlan {
+            sysfs_writes: vec![sysfs_write(EPP, "power"), sysfs_write(PROFILE, "low-power")],
+            services_to_disable: vec![
+                "tlp.service".to_string(),
+                "power-profiles-daemon.service".to_string(),
+            ],
+            acpi_wakeup_disable: vec!["GPP1".to_string()],
+            kernel_params: vec![
+                "amd_pstate=active".to_string(),
+                "pcie_aspm=force".to_string(),
+            ],
+            preset: Some(Preset::Supersaver),
+            ..Default::default()
+        };
+        switch_layer_with_ops(
+            &lid,
+            LayerScope::Runtime,
+            &sysfs,
+            &mut ops,
+            &mut state,
+            &mut crate::progress::Quiet,
+        )
+        .unwrap();
+
+        assert_eq!(on_disk(EPP), "power");
+        assert_eq!(on_disk(PROFILE), "low-power");
+        assert_eq!(on_disk(ASPM), "default");
+        let epp = state.sysfs_changes.iter().find(|c| c.path == EPP).unwrap();
+        assert_eq!(epp.restore_value(), "balance_performance");
+        let profile = state
+            .sysfs_changes
+            .iter()
+            .find(|c| c.path == PROFILE)
+            .unwrap();
+        assert_eq!(profile.restore_value(), "balanced");
+        assert!(!state.sysfs_changes.iter().any(|c| c.path == ASPM));
+        assert_eq!(
+            state.services_disabled,
+            ["tlp.service", "power-profiles-daemon.service"]
+        );
+        // The service kept from before keeps the time it was disabled
+        assert_eq!(
+            state.services_disabled_at["tlp.service"],
+            "2026-10-01T08:00:00+00:00"
+        );
+        assert!(
+            state
+                .services_disabled_at
+                .contains_key("power-profiles-daemon.service")
+        );
+        assert_eq!(state.acpi_wakeup_toggled, ["GPP1"]);
+        assert_eq!(ops.wakeup.enabled("XHC0"), Some(true));
+        assert_eq!(ops.wakeup.enabled("GPP1"), Some(false));
+        // Boot-time items wait for the next full apply
+        assert_eq!(state.kernel_params_added, ["amd_pstate=active"]);
+        assert!(!ops.phase_log.contains(&Phase::KernelParams));
+        assert_eq!(state.preset, Some(Preset::Supersaver));
+        assert_eq!(read_state(&state_path).preset, Some(Preset::Supersaver));
+    }
+
+    #[test]
+    fn test_execute_plan_refuses_to_replace_foreign_modprobe_file() {
+        let tmp = TempDir::new().unwrap();
+        let state_path = tmp.path().join("state.json");
+        let written = tmp.path().join("modprobe.d/bop-rtsx_pci.conf");
+        std::fs::create_dir_all(written.parent().unwrap()).unwrap();
+        std::fs::write(&written, "options rtsx_pci delink=1\n").unwrap();
+
+        let hw = minimal_hw();
+        let plan = ApplyPlan {
+            modprobe_configs: vec![crate::quirks::modprobe_config(
+                "rtsx_pci",
+                "enable_runtime_pm=1",
+            )],
+            ..Default::default()
+        };
+        let mut ops = TestApplyOps::new(state_path.clone());
+
+        let result = execute_plan_with_ops(&plan, &hw, false, &mut ops);
+        assert!(matches!(result, Err(Error::NotManaged { .. })));
+        assert_eq!(
+            std::fs::read_to_string(&written).unwrap(),
+            "options rtsx_pci delink=1\n"
+        );
+
+        // Listed in the previous state's manifest: bop wrote it, header or not
+        ApplyState {
+            managed_files: vec![ManagedFile::new(
+                written.to_string_lossy(),
+                "options rtsx_pci delink=1\n",
+            )],
+            ..Default::default()
+        }
+        .save_to(&state_path)
+        .unwrap();
+        execute_plan_with_ops(&plan, &hw, false, &mut ops).unwrap();
+        assert!(
+            std::fs::read_to_string(&written)
+                .unwrap()
+                .contains("enable_runtime_pm=1")
+        );
     }
 
     #[test]
@@ -1004,12 +3272,11 @@ mod tests {
 
         let previous_state = ApplyState {
             timestamp: "2026-01-01T00:00:00Z".to_string(),
-            sysfs_changes: vec![SysfsChange {
-                path: "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference"
-                    .to_string(),
-                original_value: "performance".to_string(),
-                new_value: "balance_power".to_string(),
-            }],
+            sysfs_changes: vec![SysfsChange::new(
+                "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference",
+                "performance",
+                "balance_power",
+            )],
             ..Default::default()
         };
         std::fs::write(
@@ -1022,10 +3289,15 @@ mod tests {
         let plan = ApplyPlan {
             sysfs_writes: Vec::new(),
             kernel_params: vec!["acpi.ec_no_wakeup=1".to_string()],
+            kernel_params_remove: Vec::new(),
             services_to_disable: Vec::new(),
+            services_blocked: Vec::new(),
             acpi_wakeup_disable: Vec::new(),
             systemd_service: false,
             modprobe_configs: Vec::new(),
+            wifi_power_save: None,
+            explanations: Vec::new(),
+            preset: None,
         };
 
         let mut ops = TestApplyOps::new(state_path.clone());
@@ -1033,7 +3305,8 @@ mod tests {
 
         let result = execute_plan_with_ops(&plan, &hw, false, &mut ops);
         assert!(result.is_err());
-        assert_eq!(ops.checkpoint_count, 0);
+        assert_eq!(ops.phase_log, [Phase::KernelParams]);
+        assert!(ops.checkpoints.is_empty());
 
         let persisted = read_state(&state_path);
         assert_eq!(persisted.timestamp, previous_state.timestamp);
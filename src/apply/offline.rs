@@ -0,0 +1,492 @@
+use super::{
+    ApplyOps, ApplyPlan, ApplyState, MODPROBE_DIR, ModprobeConfig, STATE_FILE, kernel_params,
+    sysctl, systemd, wifi,
+};
+use crate::error::{Error, Result};
+use crate::managed::{ManagedFile, Ownership};
+use crate::sysfs::SysfsRoot;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+
+/// Same limit as the kernel's, so link loops end in an error.
+const MAX_SYMLINKS: usize = 40;
+
+/// The root filesystem of an image being built (mkosi, chroot, nspawn),
+/// addressed the way the booted image will see it.
+#[derive(Debug, Clone)]
+pub struct OfflineRoot {
+    root: PathBuf,
+}
+
+impl OfflineRoot {
+    pub fn new(path: &Path) -> Result<Self> {
+        let root = path
+            .canonicalize()
+            .map_err(|e| Error::Other(format!("offline root {}: {}", path.display(), e)))?;
+        if !root.is_dir() {
+            return Err(Error::Other(format!(
+                "offline root {} is not a directory",
+                root.display()
+            )));
+        }
+        if root == Path::new("/") {
+            return Err(Error::Other(
+                "offline root must not be / (use plain `bop apply` for the running system)"
+                    .to_string(),
+            ));
+        }
+        Ok(Self { root })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Map an absolute path in the target (e.g. "/etc/modprobe.d") to where
+    /// it lives on the host. Symlinks inside the tree are followed as the
+    /// booted image would follow them: absolute link targets restart at the
+    /// root and `..` stops there, so the result never leaves the root. A
+    /// `..` in `target` itself is rejected.
+    pub fn resolve(&self, target: &str) -> Result<PathBuf> {
+        let escape = || Error::Other(format!("path escapes the offline root: {}", target));
+
+        let path = Path::new(target);
+        if !path.is_absolute() {
+            return Err(escape());
+        }
+
+        // Remaining components, next one last
+        let mut pending: Vec<OsString> = Vec::new();
+        for component in path.components().rev() {
+            match component {
+                Component::Normal(name) => pending.push(name.to_os_string()),
+                Component::ParentDir => return Err(escape()),
+                Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+            }
+        }
+
+        let mut inside: Vec<OsString> = Vec::new();
+        let mut links = 0;
+        while let Some(name) = pending.pop() {
+            if name == ".." {
+                inside.pop();
+                continue;
+            }
+
+            let candidate = self
+                .root
+                .join(inside.iter().collect::<PathBuf>())
+                .join(&name);
+            let is_link = std::fs::symlink_metadata(&candidate)
+                .is_ok_and(|meta| meta.file_type().is_symlink());
+            if !is_link {
+                inside.push(name);
+                continue;
+            }
+
+            links += 1;
+            if links > MAX_SYMLINKS {
+                return Err(Error::Other(format!(
+                    "too many levels of symbolic links resolving {}",
+                    target
+                )));
+            }
+            let link = std::fs::read_link(&candidate).map_err(|e| {
+                Error::Other(format!(
+                    "failed to read link {}: {}",
+                    candidate.display(),
+                    e
+                ))
+            })?;
+            if link.is_absolute() {
+                inside.clear();
+            }
+            for component in link.components().rev() {
+                match component {
+                    Component::Normal(part) => pending.push(part.to_os_string()),
+                    Component::ParentDir => pending.push(OsString::from("..")),
+                    Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+                }
+            }
+        }
+
+        let resolved = self.root.join(inside.iter().collect::<PathBuf>());
+        if !resolved.starts_with(&self.root) {
+            return Err(escape());
+        }
+        Ok(resolved)
+    }
+
+    /// A host path under the root as the booted image will see it.
+    pub fn target_path(&self, host: &Path) -> String {
+        match host.strip_prefix(&self.root) {
+            Ok(relative) => format!("/{}", relative.display()),
+            Err(_) => host.display().to_string(),
+        }
+    }
+
+    fn write(&self, target: &str, content: &str) -> Result<PathBuf> {
+        let path = self.resolve(target)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                Error::Other(format!("failed to create {}: {}", parent.display(), e))
+            })?;
+        }
+        std::fs::write(&path, content)
+            .map_err(|e| Error::Other(format!("failed to write {}: {}", path.display(), e)))?;
+        Ok(path)
+    }
+}
+
+/// Apply operations that install persistent artifacts into an offline root.
+/// Nothing on the running system is touched: runtime values are left to
+/// the persistence unit on the image's first boot.
+pub(super) struct OfflineOps<'a> {
+    root: &'a OfflineRoot,
+    /// The hardware the image is for, as the unit will find it at boot
+    target: &'a SysfsRoot,
+}
+
+impl<'a> OfflineOps<'a> {
+    pub(super) fn new(root: &'a OfflineRoot, target: &'a SysfsRoot) -> Self {
+        Self { root, target }
+    }
+}
+
+impl ApplyOps for OfflineOps<'_> {
+    fn live_system(&self) -> bool {
+        false
+    }
+
+    fn write_sysfs(&mut self, path: &str, _value: &str) -> Result<()> {
+        Err(Error::Other(format!(
+            "refusing to write {} for an offline root",
+            path
+        )))
+    }
+
+    fn toggle_acpi_wakeup(&mut self, device: &str, _enabled: bool) -> Result<bool> {
+        Err(Error::Other(format!(
+            "refusing to toggle ACPI wakeup {} for an offline root",
+            device
+        )))
+    }
+
+    fn edit_kernel_params(
+        &mut self,
+        params: &[String],
+        remove: &[String],
+    ) -> Result<Vec<kernel_params::KernelParamBackup>> {
+        kernel_params::edit_kernel_params_offline(params, remove, self.root)
+    }
+
+    fn disable_service(&mut self, service: &str) -> Result<()> {
+        Err(Error::Other(format!(
+            "refusing to stop {} for an offline root",
+            service
+        )))
+    }
+
+    fn enable_service(&mut self, service: &str) -> Result<()> {
+        Err(Error::Other(format!(
+            "refusing to re-enable {} for an offline root",
+            service
+        )))
+    }
+
+    fn write_modprobe_config(
+        &mut self,
+        config: &ModprobeConfig,
+        ownership: &Ownership,
+    ) -> Result<ManagedFile> {
+        let target = format!("{}/{}", MODPROBE_DIR, config.filename);
+        ownership.check(&self.root.resolve(&target)?)?;
+        self.root.write(&target, &config.content)?;
+        Ok(ManagedFile::new(target, &config.content))
+    }
+
+    fn write_wifi_drop_in(&mut self, content: &str, ownership: &Ownership) -> Result<ManagedFile> {
+        ownership.check(&self.root.resolve(wifi::NM_DROP_IN_PATH)?)?;
+        self.root.write(wifi::NM_DROP_IN_PATH, content)?;
+        Ok(ManagedFile::new(wifi::NM_DROP_IN_PATH, content))
+    }
+
+    fn remove_wifi_drop_in(&mut self, path: &Path, _ownership: &Ownership) -> Result<bool> {
+        Err(Error::Other(format!(
+            "refusing to remove {} for an offline root",
+            path.display()
+        )))
+    }
+
+    fn write_usb_port_rule(
+        &mut self,
+        _content: &str,
+        _ownership: &Ownership,
+    ) -> Result<ManagedFile> {
+        Err(Error::Other(
+            "refusing to write the USB port rule for an offline root".to_string(),
+        ))
+    }
+
+    fn remove_usb_port_rule(&mut self, path: &Path, _ownership: &Ownership) -> Result<bool> {
+        Err(Error::Other(format!(
+            "refusing to remove {} for an offline root",
+            path.display()
+        )))
+    }
+
+    fn write_sysctl_drop_in(
+        &mut self,
+        content: &str,
+        ownership: &Ownership,
+    ) -> Result<ManagedFile> {
+        ownership.check(&self.root.resolve(sysctl::DROP_IN_PATH)?)?;
+        self.root.write(sysctl::DROP_IN_PATH, content)?;
+        Ok(ManagedFile::new(sysctl::DROP_IN_PATH, content))
+    }
+
+    fn generate_service(&mut self, plan: &ApplyPlan, ownership: &Ownership) -> Result<ManagedFile> {
+        let unit = systemd::render_service_in(plan, self.target);
+        ownership.check(&self.root.resolve(systemd::SERVICE_PATH)?)?;
+        self.root.write(systemd::SERVICE_PATH, &unit)?;
+        Ok(ManagedFile::new(systemd::SERVICE_PATH, &unit))
+    }
+
+    /// What `systemctl enable` would do: link the unit into the wants
+    /// directory of its `WantedBy=` target.
+    fn enable_systemd_service(&mut self) -> Result<()> {
+        let wants = self.root.resolve(systemd::WANTS_DIR)?;
+        std::fs::create_dir_all(&wants)
+            .map_err(|e| Error::Other(format!("failed to create {}: {}", wants.display(), e)))?;
+        let link = wants.join(systemd::SERVICE_NAME);
+        if std::fs::symlink_metadata(&link).is_ok() {
+            std::fs::remove_file(&link).map_err(|e| {
+                Error::Other(format!("failed to replace {}: {}", link.display(), e))
+            })?;
+        }
+        std::os::unix::fs::symlink(systemd::SERVICE_PATH, &link)
+            .map_err(|e| Error::Other(format!("failed to create {}: {}", link.display(), e)))
+    }
+
+    fn load_state(&mut self) -> Result<Option<ApplyState>> {
+        ApplyState::load_from(&self.root.resolve(STATE_FILE)?)
+    }
+
+    fn save_state(&mut self, state: &ApplyState) -> Result<()> {
+        state.save_to(&self.root.resolve(STATE_FILE)?)
+    }
+
+    /// Manifest paths are as the image sees them
+    fn ownership(&self, manifest: &[ManagedFile], force: bool) -> Ownership {
+        Ownership::resolved(manifest, force, |path| self.root.resolve(path).ok())
+    }
+
+    fn journal_path(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use tempfile::TempDir;
+
+    fn offline_root() -> (TempDir, OfflineRoot) {
+        let tmp = TempDir::new().unwrap();
+        let root = OfflineRoot::new(tmp.path()).unwrap();
+        (tmp, root)
+    }
+
+    #[test]
+    fn test_resolve_plain_paths() {
+        let (_tmp, root) = offline_root();
+        assert_eq!(
+            root.resolve("/etc/modprobe.d/bop.conf").unwrap(),
+            root.path().join("etc/modprobe.d/bop.conf")
+        );
+        assert_eq!(root.resolve("/").unwrap(), root.path());
+        assert_eq!(
+            root.target_path(&root.path().join("var/lib/bop/state.json")),
+            "/var/lib/bop/state.json"
+        );
+    }
+
+    #[test]
+    fn test_resolve_rejects_traversal() {
+        let (_tmp, root) = offline_root();
+        for bad in [
+            "../etc/passwd",
+            "etc/passwd",
+            "/../etc/passwd",
+            "/etc/../../etc/passwd",
+            "/etc/modprobe.d/../../../../tmp/x",
+        ] {
+            assert!(root.resolve(bad).is_err(), "{} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_resolve_keeps_symlinks_inside_root() {
+        let (_tmp, root) = offline_root();
+        let base = root.path();
+        fs::create_dir_all(base.join("usr/lib/modprobe.d")).unwrap();
+        fs::create_dir_all(base.join("etc")).unwrap();
+
+        // Absolute link: on the host this would point at the host's /usr
+        symlink("/usr/lib/modprobe.d", base.join("etc/modprobe.d")).unwrap();
+        assert_eq!(
+            root.resolve("/etc/modprobe.d/bop.conf").unwrap(),
+            base.join("usr/lib/modprobe.d/bop.conf")
+        );
+
+        // Relative link climbing far above the root stops at the root
+        symlink("../../../../../../tmp", base.join("etc/escape")).unwrap();
+        assert_eq!(root.resolve("/etc/escape/x").unwrap(), base.join("tmp/x"));
+
+        // Link straight at a host path
+        symlink("/etc/shadow", base.join("etc/shadow-link")).unwrap();
+        assert_eq!(
+            root.resolve("/etc/shadow-link").unwrap(),
+            base.join("etc/shadow")
+        );
+    }
+
+    #[test]
+    fn test_resolve_detects_symlink_loops() {
+        let (_tmp, root) = offline_root();
+        symlink("/b", root.path().join("a")).unwrap();
+        symlink("/a", root.path().join("b")).unwrap();
+        assert!(root.resolve("/a/x").is_err());
+    }
+
+    #[test]
+    fn test_write_through_hostile_symlink_stays_inside() {
+        let outside = TempDir::new().unwrap();
+        let (_tmp, root) = offline_root();
+        fs::create_dir_all(root.path().join("etc")).unwrap();
+        // A link to a real host directory by absolute path
+        symlink(outside.path(), root.path().join("etc/modprobe.d")).unwrap();
+
+        let target = SysfsRoot::new(root.path().join("sys-target"));
+        let mut ops = OfflineOps::new(&root, &target);
+        let config = ModprobeConfig {
+            filename: "bop.conf".to_string(),
+            content: "options snd_hda_intel power_save=1\n".to_string(),
+        };
+        let file = ops
+            .write_modprobe_config(&config, &Ownership::default())
+            .unwrap();
+        assert_eq!(file.path, "/etc/modprobe.d/bop.conf");
+        assert!(!outside.path().join("bop.conf").exists());
+        let inside = root.resolve("/etc/modprobe.d/bop.conf").unwrap();
+        assert!(inside.starts_with(root.path()));
+        assert!(inside.exists());
+    }
+
+    #[test]
+    fn test_execute_plan_offline_installs_into_root() {
+        let (_tmp, root) = offline_root();
+        let base = root.path();
+        fs::create_dir_all(base.join("boot/loader/entries")).unwrap();
+        fs::write(
+            base.join("boot/loader/entries/linux.conf"),
+            "title Linux\nlinux /vmlinuz\noptions root=UUID=abc rw\n",
+        )
+        .unwrap();
+
+        let hw_tmp = TempDir::new().unwrap();
+        let target = SysfsRoot::new(hw_tmp.path());
+        let hw = crate::detect::HardwareInfo::detect(&target);
+        let plan = ApplyPlan {
+            sysfs_writes: vec![
+                super::super::PlannedSysfsWrite {
+                    path: "/sys/module/pcie_aspm/parameters/policy".to_string(),
+                    value: "powersupersave".to_string(),
+                    description: "PCIe ASPM".to_string(),
+                },
+                super::super::PlannedSysfsWrite {
+                    path: "/proc/sys/kernel/nmi_watchdog".to_string(),
+                    value: "0".to_string(),
+                    description: "NMI watchdog".to_string(),
+                },
+            ],
+            kernel_params: vec!["acpi.ec_no_wakeup=1".to_string()],
+            services_to_disable: vec!["dummy.service".to_string()],
+            systemd_service: true,
+            modprobe_configs: vec![crate::quirks::modprobe_config(
+                "rtsx_pci",
+                "enable_runtime_pm=1",
+            )],
+            wifi_power_save: Some(wifi::WifiPowerSave::NetworkManager),
+            ..Default::default()
+        };
+
+        let mut state = ApplyState::default();
+        super::super::execute_plan_offline(
+            &plan,
+            &hw,
+            &target,
+            &root,
+            false,
+            false,
+            &mut state,
+            &mut crate::progress::Quiet,
+        )
+        .unwrap();
+
+        let entry = fs::read_to_string(base.join("boot/loader/entries/linux.conf")).unwrap();
+        assert!(entry.contains("acpi.ec_no_wakeup=1"));
+        assert!(base.join("etc/modprobe.d/bop-rtsx_pci.conf").exists());
+        let sysctl = fs::read_to_string(base.join("etc/sysctl.d/60-bop.conf")).unwrap();
+        assert!(sysctl.contains("kernel.nmi_watchdog = 0"));
+        assert!(!sysctl.contains("pcie_aspm"));
+        assert!(
+            base.join("etc/NetworkManager/conf.d/bop-wifi-powersave.conf")
+                .exists()
+        );
+        assert!(
+            base.join("etc/systemd/system/bop-powersave.service")
+                .exists()
+        );
+        assert_eq!(
+            fs::read_link(
+                base.join("etc/systemd/system/multi-user.target.wants/bop-powersave.service")
+            )
+            .unwrap(),
+            PathBuf::from(systemd::SERVICE_PATH)
+        );
+
+        // State lives in the image and names paths as the image sees them
+        let saved = ApplyState::load_from(&base.join("var/lib/bop/state.json"))
+            .unwrap()
+            .unwrap();
+        assert!(saved.sysfs_changes.is_empty());
+        assert!(saved.services_disabled.is_empty());
+        assert_eq!(
+            saved.kernel_param_backups[0].path,
+            "/boot/loader/entries/linux.conf"
+        );
+        assert_eq!(
+            saved.modprobe_files_created,
+            vec!["/etc/modprobe.d/bop-rtsx_pci.conf".to_string()]
+        );
+        assert_eq!(
+            saved.sysctl_files_created,
+            vec!["/etc/sysctl.d/60-bop.conf".to_string()]
+        );
+        assert!(
+            saved
+                .managed_files
+                .iter()
+                .any(|m| m.path == "/etc/sysctl.d/60-bop.conf")
+        );
+    }
+
+    #[test]
+    fn test_root_itself_is_rejected() {
+        assert!(OfflineRoot::new(Path::new("/")).is_err());
+        assert!(OfflineRoot::new(Path::new("/nonexistent/bop-root")).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+//! Capping the CPU package's long-term RAPL power limit (supersaver with
+//! `[aggressive] package_power_limit_w`). On machines whose firmware
+//! ignores EPP and the platform profile for sustained load, this is the one
+//! lever that lowers it. The limit is only ever lowered, never raised, and
+//! the original is recorded like any other sysfs write, so `bop revert`
+//! and a run of `bop auto` on AC put it back.
+
+use super::{PlanExplanation, PlannedSysfsWrite};
+use crate::detect::powercap::{PowercapInfo, uw_to_watts, watts_to_uw};
+
+/// Why `watts` can't be set, if it can't: below the profile's `floor_w`
+/// the package can't hold even a light desktop load.
+pub fn refusal(watts: u32, floor_w: u32) -> Option<String> {
+    (watts < floor_w).then(|| format!("{}W is below this machine's {}W floor", watts, floor_w))
+}
+
+/// The writes that cap every writable package zone at `watts`, and the
+/// reasoning for `bop apply --explain`.
+pub fn plan(
+    powercap: &PowercapInfo,
+    watts: u32,
+    floor_w: u32,
+) -> (Vec<PlannedSysfsWrite>, PlanExplanation) {
+    let mut writes = Vec::new();
+    let mut factors = vec![format!("[aggressive] package_power_limit_w = {}", watts)];
+    let target = watts_to_uw(watts);
+    let reason = if let Some(refusal) = refusal(watts, floor_w) {
+        refusal
+    } else {
+        let zones: Vec<_> = powercap.writable_packages().collect();
+        for zone in &zones {
+            let current = zone.power_limit_uw.unwrap_or_default();
+            factors.push(format!(
+                "{} limit {:.1}W",
+                zone.path.rsplit('/').next().unwrap_or(&zone.path),
+                uw_to_watts(current)
+            ));
+            if current > target {
+                writes.push(PlannedSysfsWrite {
+                    path: zone.limit_path(),
+                    value: target.to_string(),
+                    description: format!(
+                        "Cap {} power at {}W (was {:.1}W)",
+                        zone.name,
+                        watts,
+                        uw_to_watts(current)
+                    ),
+                });
+            }
+        }
+        if zones.is_empty() {
+            match powercap.package_limit_uw() {
+                Some(_) => "package powercap zone is read-only (manual only)".to_string(),
+                None => "no package powercap zone".to_string(),
+            }
+        } else if writes.is_empty() {
+            format!("package limit already at or below {}W", watts)
+        } else {
+            format!("package limit above {}W", watts)
+        }
+    };
+    let explanation = PlanExplanation {
+        subject: "package power limit".to_string(),
+        included: !writes.is_empty(),
+        reason,
+        factors,
+    };
+    (writes, explanation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::powercap::PowercapZone;
+
+    fn package(entry: &str, limit_w: u64, writable: bool) -> PowercapZone {
+        PowercapZone {
+            path: format!("sys/class/powercap/{}", entry),
+            name: "package-0".to_string(),
+            power_limit_uw: Some(limit_w * 1_000_000),
+            max_power_uw: None,
+            writable,
+        }
+    }
+
+    #[test]
+    fn test_limit_below_floor_is_refused() {
+        let powercap = PowercapInfo {
+            zones: vec![package("intel-rapl:0", 28, true)],
+        };
+        let (writes, explanation) = plan(&powercap, 9, 10);
+        assert!(writes.is_empty());
+        assert!(!explanation.included);
+        assert_eq!(explanation.reason, "9W is below this machine's 10W floor");
+
+        let (writes, _) = plan(&powercap, 10, 10);
+        assert_eq!(writes.len(), 1);
+        assert_eq!(writes[0].value, "10000000");
+    }
+
+    #[test]
+    fn test_caps_every_writable_package_above_the_limit() {
+        let powercap = PowercapInfo {
+            zones: vec![
+                package("intel-rapl:0", 28, true),
+                package("intel-rapl-mmio:0", 28, true),
+            ],
+        };
+        let (writes, explanation) = plan(&powercap, 15, 10);
+        assert!(explanation.included);
+        let paths: Vec<_> = writes.iter().map(|w| w.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "/sys/class/powercap/intel-rapl:0/constraint_0_power_limit_uw",
+                "/sys/class/powercap/intel-rapl-mmio:0/constraint_0_power_limit_uw",
+            ]
+        );
+        assert!(writes.iter().all(|w| w.value == "15000000"));
+        assert_eq!(
+            writes[0].description,
+            "Cap package-0 power at 15W (was 28.0W)"
+        );
+
+        // Never raised
+        let (writes, explanation) = plan(&powercap, 35, 10);
+        assert!(writes.is_empty());
+        assert_eq!(explanation.reason, "package limit already at or below 35W");
+    }
+
+    #[test]
+    fn test_read_only_zone_is_manual() {
+        let powercap = PowercapInfo {
+            zones: vec![package("intel-rapl:0", 28, false)],
+        };
+        let (writes, explanation) = plan(&powercap, 15, 10);
+        assert!(writes.is_empty());
+        assert_eq!(
+            explanation.reason,
+            "package powercap zone is read-only (manual only)"
+        );
+        let (_, explanation) = plan(&PowercapInfo::default(), 15, 10);
+        assert_eq!(explanation.reason, "no package powercap zone");
+    }
+}
@@ -0,0 +1,184 @@
+//! `bop doctor --probe`: write each planned sysfs value's current value back
+//! to find out, before `bop apply`, which writes the kernel or a driver
+//! would refuse. Nothing changes; a value read and written back is the value
+//! that was there.
+
+use super::ApplyPlan;
+use super::sysfs_writer::ACPI_WAKEUP_PATH;
+use crate::error::Error;
+use crate::sysfs::SysfsRoot;
+use serde::Serialize;
+use std::io::ErrorKind;
+
+/// Why /proc/acpi/wakeup is never probed.
+const ACPI_WAKEUP_SKIP: &str =
+    "writing a device name toggles its wakeup state, so there is no same-value write";
+
+/// What writing a path's own value back did.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", content = "detail", rename_all = "snake_case")]
+pub enum ProbeStatus {
+    Writable,
+    /// Refused with EACCES/EPERM
+    Denied,
+    /// Any other failure, e.g. the driver rejecting the value
+    Error(String),
+    /// Not probed, and why
+    Skipped(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeResult {
+    pub path: String,
+    pub description: String,
+    /// What was read and written back
+    pub value: Option<String>,
+    #[serde(flatten)]
+    pub status: ProbeStatus,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProbeReport {
+    pub results: Vec<ProbeResult>,
+}
+
+impl ProbeReport {
+    /// Writes actually tried.
+    pub fn probed(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|r| !matches!(r.status, ProbeStatus::Skipped(_)))
+            .count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|r| matches!(r.status, ProbeStatus::Denied | ProbeStatus::Error(_)))
+            .count()
+    }
+
+    /// e.g. "3 of 24 planned writes would fail"
+    pub fn summary(&self) -> String {
+        let probed = self.probed();
+        let noun = if probed == 1 { "write" } else { "writes" };
+        format!(
+            "{} of {} planned {} would fail",
+            self.failed(),
+            probed,
+            noun
+        )
+    }
+}
+
+/// The value selected in a sysfs choice list ("default [powersave] ..."),
+/// or the value itself.
+fn selected_value(value: &str) -> &str {
+    value
+        .split_whitespace()
+        .find_map(|token| token.strip_prefix('[')?.strip_suffix(']'))
+        .unwrap_or(value)
+}
+
+fn classify(err: &Error) -> ProbeStatus {
+    let source = match err {
+        Error::SysfsRead { source, .. } | Error::SysfsWrite { source, .. } => source,
+        other => return ProbeStatus::Error(other.to_string()),
+    };
+    match source.kind() {
+        ErrorKind::PermissionDenied => ProbeStatus::Denied,
+        _ => ProbeStatus::Error(err.to_string()),
+    }
+}
+
+/// Read each of the plan's sysfs paths and write what was read back. ACPI
+/// wakeup entries are listed as skipped.
+pub fn probe_plan(plan: &ApplyPlan, sysfs: &SysfsRoot) -> ProbeReport {
+    let mut results = Vec::new();
+    for write in &plan.sysfs_writes {
+        let (value, status) = if write.path == ACPI_WAKEUP_PATH {
+            (None, ProbeStatus::Skipped(ACPI_WAKEUP_SKIP.to_string()))
+        } else {
+            probe_path(sysfs, write.path.trim_start_matches('/'))
+        };
+        results.push(ProbeResult {
+            path: write.path.clone(),
+            description: write.description.clone(),
+            value,
+            status,
+        });
+    }
+    for device in &plan.acpi_wakeup_disable {
+        results.push(ProbeResult {
+            path: ACPI_WAKEUP_PATH.to_string(),
+            description: format!("Disable {} wakeup", device),
+            value: None,
+            status: ProbeStatus::Skipped(ACPI_WAKEUP_SKIP.to_string()),
+        });
+    }
+    ProbeReport { results }
+}
+
+fn probe_path(sysfs: &SysfsRoot, relative: &str) -> (Option<String>, ProbeStatus) {
+    let current = match sysfs.read(relative) {
+        Ok(current) => current,
+        Err(e) => return (None, classify(&e)),
+    };
+    let value = selected_value(&current).to_string();
+    let status = match sysfs.write(relative, &value) {
+        Ok(()) => ProbeStatus::Writable,
+        Err(e) => classify(&e),
+    };
+    (Some(value), status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_selected_value_picks_bracketed_choice() {
+        assert_eq!(
+            selected_value("default performance [powersave] powersupersave"),
+            "powersave"
+        );
+        assert_eq!(selected_value("auto"), "auto");
+        assert_eq!(selected_value("[low-power]"), "low-power");
+    }
+
+    #[test]
+    fn test_classify_separates_denied_from_other_failures() {
+        let write_err = |kind| Error::SysfsWrite {
+            path: PathBuf::from("/sys/x"),
+            source: std::io::Error::from(kind),
+        };
+        assert_eq!(
+            classify(&write_err(ErrorKind::PermissionDenied)),
+            ProbeStatus::Denied
+        );
+        assert!(matches!(
+            classify(&write_err(ErrorKind::InvalidInput)),
+            ProbeStatus::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_summary_counts_only_probed_writes() {
+        let result = |status| ProbeResult {
+            path: "/sys/x".to_string(),
+            description: String::new(),
+            value: None,
+            status,
+        };
+        let report = ProbeReport {
+            results: vec![
+                result(ProbeStatus::Writable),
+                result(ProbeStatus::Denied),
+                result(ProbeStatus::Error("EINVAL".to_string())),
+                result(ProbeStatus::Skipped("toggle".to_string())),
+            ],
+        };
+        assert_eq!(report.summary(), "2 of 3 planned writes would fail");
+    }
+}
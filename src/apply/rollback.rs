@@ -0,0 +1,328 @@
+//! `bop apply --rollback-after`: a transient systemd timer that runs `bop
+//! revert --quiet` unless the changes are kept first with `bop apply
+//! --confirm-keep`. The pending rollback is recorded in the state so
+//! `bop status` can show it.
+//!
+//! Transient units live in the running boot only. A reboot cancels the
+//! timer; the runtime changes go with it, but kernel parameters, modprobe
+//! configs and the persistence unit stay until `bop revert`.
+
+use crate::apply::ApplyState;
+use crate::error::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Transient unit name; systemd-run creates `bop-rollback.timer` and the
+/// `bop-rollback.service` it starts.
+pub const UNIT_NAME: &str = "bop-rollback";
+
+const DESCRIPTION: &str = "bop automatic rollback";
+
+/// Shortest delay accepted: less leaves no time to check the changes.
+pub const MIN_DELAY: Duration = Duration::from_secs(60);
+
+/// An automatic rollback waiting on its timer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingRollback {
+    /// Transient unit name, without the `.timer` suffix
+    pub unit: String,
+    /// When the timer fires (RFC 3339)
+    pub due: String,
+    /// Boot the timer was started in
+    pub boot_id: Option<String>,
+}
+
+impl PendingRollback {
+    pub fn timer(&self) -> String {
+        format!("{}.timer", self.unit)
+    }
+
+    /// Time left at `now`, zero once due; None when `due` doesn't parse.
+    pub fn remaining(&self, now: DateTime<Utc>) -> Option<Duration> {
+        let due = DateTime::parse_from_rfc3339(&self.due).ok()?;
+        Some((due.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+    }
+
+    /// Whether the system has rebooted since the timer was started, taking
+    /// the timer with it.
+    pub fn lost_to_reboot(&self, boot_id: &str) -> bool {
+        self.boot_id.as_deref().is_some_and(|b| b != boot_id)
+    }
+}
+
+/// "in 24 minutes", rounded up so the last minute doesn't read as zero.
+pub fn describe_remaining(remaining: Duration) -> String {
+    match remaining.as_secs().div_ceil(60) {
+        0 => "now".to_string(),
+        1 => "in 1 minute".to_string(),
+        minutes => format!("in {} minutes", minutes),
+    }
+}
+
+/// Starts and stops the transient timer.
+pub trait TimerOps {
+    /// Run `command` once, `delay` from now, as transient unit `unit`.
+    fn start(
+        &mut self,
+        unit: &str,
+        description: &str,
+        delay: Duration,
+        command: &[String],
+    ) -> Result<()>;
+    /// Stop `unit`'s timer. Ok when it isn't running, including when it
+    /// never existed.
+    fn stop(&mut self, unit: &str) -> Result<()>;
+}
+
+/// The real timer, through systemd-run and systemctl.
+pub struct SystemdRun;
+
+impl TimerOps for SystemdRun {
+    fn start(
+        &mut self,
+        unit: &str,
+        description: &str,
+        delay: Duration,
+        command: &[String],
+    ) -> Result<()> {
+        let status = crate::cmd::Cmd::new("systemd-run")
+            .arg(format!("--unit={}", unit))
+            .arg(format!("--on-active={}s", delay.as_secs()))
+            .arg("--timer-property=AccuracySec=1s")
+            .arg(format!("--description={}", description))
+            .args(command)
+            .status()?;
+        if !status.success() {
+            return Err(Error::Other(format!(
+                "systemd-run failed to start {}.timer",
+                unit
+            )));
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self, unit: &str) -> Result<()> {
+        let timer = format!("{}.timer", unit);
+        // Fails for a unit that isn't loaded, which is fine; only a timer
+        // still running afterwards is a problem
+        let _ = crate::cmd::Cmd::new("systemctl")
+            .args(["stop", &timer])
+            .status();
+        let still_active = crate::cmd::Cmd::new("systemctl")
+            .args(["is-active", "--quiet", &timer])
+            .status()
+            .is_ok_and(|s| s.success());
+        if still_active {
+            return Err(Error::Other(format!("failed to stop {}", timer)));
+        }
+        Ok(())
+    }
+}
+
+/// How long the timer's revert waits for a bop operation already running
+/// (an auto switch, say) instead of giving up on the rollback.
+pub const LOCK_WAIT: Duration = Duration::from_secs(120);
+
+/// What the timer runs: this binary's `revert --quiet`, against the same
+/// state directory, waiting its turn for the operation lock.
+pub fn revert_command() -> Vec<String> {
+    let exe = std::env::current_exe()
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "bop".to_string());
+    let mut command = vec![exe];
+    if let Some(dir) = crate::apply::custom_state_dir() {
+        command.extend([
+            "--state-dir".to_string(),
+            dir.to_string_lossy().into_owned(),
+        ]);
+    }
+    command.extend([
+        "revert".to_string(),
+        "--wait".to_string(),
+        LOCK_WAIT.as_secs().to_string(),
+        "--quiet".to_string(),
+    ]);
+    command
+}
+
+/// Start the rollback timer and record it in `state`. A timer left by an
+/// earlier apply is stopped first, so only the new one can fire.
+pub fn schedule(
+    ops: &mut dyn TimerOps,
+    state: &mut ApplyState,
+    delay: Duration,
+    now: DateTime<Utc>,
+    boot_id: Option<String>,
+) -> Result<()> {
+    if delay < MIN_DELAY {
+        return Err(Error::Other(format!(
+            "rollback delay must be at least {}s",
+            MIN_DELAY.as_secs()
+        )));
+    }
+    ops.stop(UNIT_NAME)?;
+    ops.start(UNIT_NAME, DESCRIPTION, delay, &revert_command())?;
+    let due = now + chrono::TimeDelta::seconds(delay.as_secs() as i64);
+    state.rollback = Some(PendingRollback {
+        unit: UNIT_NAME.to_string(),
+        due: due.to_rfc3339(),
+        boot_id,
+    });
+    Ok(())
+}
+
+/// Keep the changes: stop the pending timer and clear its record. False
+/// when no rollback was pending.
+pub fn keep(ops: &mut dyn TimerOps, state: &mut ApplyState) -> Result<bool> {
+    let Some(pending) = &state.rollback else {
+        return Ok(false);
+    };
+    ops.stop(&pending.unit)?;
+    state.rollback = None;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records calls instead of running systemd-run.
+    #[derive(Default)]
+    struct MockTimer {
+        calls: Vec<String>,
+        running: Option<String>,
+        fail_stop: bool,
+    }
+
+    impl TimerOps for MockTimer {
+        fn start(
+            &mut self,
+            unit: &str,
+            _description: &str,
+            delay: Duration,
+            command: &[String],
+        ) -> Result<()> {
+            if self.running.is_some() {
+                return Err(Error::Other(format!("Unit {}.timer already exists", unit)));
+            }
+            self.calls.push(format!(
+                "start {} {}s {}",
+                unit,
+                delay.as_secs(),
+                command[1..].join(" ")
+            ));
+            self.running = Some(unit.to_string());
+            Ok(())
+        }
+
+        fn stop(&mut self, unit: &str) -> Result<()> {
+            if self.fail_stop {
+                return Err(Error::Other(format!("failed to stop {}.timer", unit)));
+            }
+            self.calls.push(format!("stop {}", unit));
+            self.running = None;
+            Ok(())
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-10-15T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_schedule_starts_timer_and_records_it() {
+        let mut timer = MockTimer::default();
+        let mut state = ApplyState::default();
+        schedule(
+            &mut timer,
+            &mut state,
+            Duration::from_secs(30 * 60),
+            now(),
+            Some("boot-a".to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            timer.calls,
+            [
+                "stop bop-rollback",
+                "start bop-rollback 1800s revert --wait 120 --quiet"
+            ]
+        );
+        let pending = state.rollback.unwrap();
+        assert_eq!(pending.timer(), "bop-rollback.timer");
+        assert_eq!(pending.due, "2026-10-15T10:30:00+00:00");
+        let later = now() + chrono::TimeDelta::seconds(6 * 60 + 20);
+        assert_eq!(
+            pending.remaining(later),
+            Some(Duration::from_secs(23 * 60 + 40))
+        );
+        assert_eq!(
+            describe_remaining(pending.remaining(later).unwrap()),
+            "in 24 minutes"
+        );
+        let overdue = now() + chrono::TimeDelta::hours(1);
+        assert_eq!(pending.remaining(overdue), Some(Duration::ZERO));
+        assert!(!pending.lost_to_reboot("boot-a"));
+        assert!(pending.lost_to_reboot("boot-b"));
+    }
+
+    #[test]
+    fn test_schedule_replaces_an_earlier_timer() {
+        let mut timer = MockTimer {
+            running: Some(UNIT_NAME.to_string()),
+            ..Default::default()
+        };
+        let mut state = ApplyState::default();
+        schedule(&mut timer, &mut state, MIN_DELAY, now(), None).unwrap();
+        assert_eq!(timer.running.as_deref(), Some(UNIT_NAME));
+        assert!(state.rollback.is_some());
+    }
+
+    #[test]
+    fn test_schedule_rejects_short_delay_and_failed_start() {
+        let mut timer = MockTimer::default();
+        let mut state = ApplyState::default();
+        assert!(schedule(&mut timer, &mut state, Duration::from_secs(5), now(), None).is_err());
+        assert!(timer.calls.is_empty());
+
+        // An earlier timer that won't stop would still fire
+        timer.fail_stop = true;
+        assert!(schedule(&mut timer, &mut state, MIN_DELAY, now(), None).is_err());
+        assert!(state.rollback.is_none());
+    }
+
+    #[test]
+    fn test_keep_stops_timer_and_clears_record() {
+        let mut timer = MockTimer::default();
+        let mut state = ApplyState::default();
+        assert!(!keep(&mut timer, &mut state).unwrap());
+        assert!(timer.calls.is_empty());
+
+        schedule(&mut timer, &mut state, MIN_DELAY, now(), None).unwrap();
+        assert!(keep(&mut timer, &mut state).unwrap());
+        assert_eq!(timer.calls.last().unwrap(), "stop bop-rollback");
+        assert!(timer.running.is_none());
+        assert!(state.rollback.is_none());
+    }
+
+    #[test]
+    fn test_keep_leaves_record_when_timer_wont_stop() {
+        let mut timer = MockTimer::default();
+        let mut state = ApplyState::default();
+        schedule(&mut timer, &mut state, MIN_DELAY, now(), None).unwrap();
+        timer.fail_stop = true;
+        assert!(keep(&mut timer, &mut state).is_err());
+        assert!(state.rollback.is_some());
+    }
+
+    #[test]
+    fn test_describe_remaining() {
+        assert_eq!(describe_remaining(Duration::ZERO), "now");
+        assert_eq!(describe_remaining(Duration::from_secs(10)), "in 1 minute");
+        assert_eq!(describe_remaining(Duration::from_secs(90)), "in 2 minutes");
+    }
+}
@@ -1,21 +1,128 @@
 use crate::error::{Error, Result};
 
+/// Dependency properties that make a unit fail or stop without the one it
+/// names. `Wants=` is left out: the dependent survives a disabled service.
+const HARD_DEPENDENCIES: &[&str] = &["Requires", "Requisite", "BindsTo"];
+
+/// The direct reverse dependencies in `systemctl list-dependencies --reverse
+/// --plain <unit>` output. The first line is the unit itself; its direct
+/// dependents are indented one level (two spaces), deeper lines are theirs.
+pub fn parse_reverse_dependencies(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let unit = line.strip_prefix("  ")?;
+            (!unit.starts_with(' ') && !unit.is_empty()).then(|| unit.trim().to_string())
+        })
+        .collect()
+}
+
+/// One unit from `systemctl show --property=Id,ActiveState,Requires,...`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnitDependencies {
+    pub id: String,
+    pub active_state: String,
+    /// Units named by `Requires=`, `Requisite=` or `BindsTo=`
+    pub hard_dependencies: Vec<String>,
+}
+
+/// Parse `systemctl show` output for several units: one `Key=value` block
+/// per unit, blocks separated by blank lines.
+pub fn parse_unit_dependencies(output: &str) -> Vec<UnitDependencies> {
+    let mut units = Vec::new();
+    let mut current = UnitDependencies::default();
+    for line in output.lines().chain(std::iter::once("")) {
+        if line.trim().is_empty() {
+            if !current.id.is_empty() {
+                units.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key {
+            "Id" => current.id = value.to_string(),
+            "ActiveState" => current.active_state = value.to_string(),
+            k if HARD_DEPENDENCIES.contains(&k) => current
+                .hard_dependencies
+                .extend(value.split_whitespace().map(String::from)),
+            _ => {}
+        }
+    }
+    units
+}
+
+/// Active units other than bop's own that would break if `service` were
+/// disabled, sorted.
+pub fn blocking_dependents(service: &str, units: &[UnitDependencies]) -> Vec<String> {
+    let mut blocking: Vec<String> = units
+        .iter()
+        .filter(|u| {
+            u.active_state == "active"
+                && !u.id.starts_with("bop-")
+                && u.hard_dependencies.iter().any(|d| d == service)
+        })
+        .map(|u| u.id.clone())
+        .collect();
+    blocking.sort();
+    blocking.dedup();
+    blocking
+}
+
+/// Query the units that require `service`: one call to list the reverse
+/// dependencies, one batched `systemctl show` for all of them. Empty if
+/// systemctl isn't available.
+pub fn required_by(service: &str) -> Vec<String> {
+    let Ok(out) = crate::cmd::Cmd::new("systemctl")
+        .args([
+            "list-dependencies",
+            "--reverse",
+            "--plain",
+            "--no-pager",
+            service,
+        ])
+        .output()
+    else {
+        return Vec::new();
+    };
+    let dependents = parse_reverse_dependencies(&String::from_utf8_lossy(&out.stdout));
+    if dependents.is_empty() {
+        return Vec::new();
+    }
+
+    let mut properties = vec!["Id", "ActiveState"];
+    properties.extend(HARD_DEPENDENCIES);
+    let Ok(out) = crate::cmd::Cmd::new("systemctl")
+        .arg("show")
+        .arg(format!("--property={}", properties.join(",")))
+        .args(&dependents)
+        .output()
+    else {
+        return Vec::new();
+    };
+    blocking_dependents(
+        service,
+        &parse_unit_dependencies(&String::from_utf8_lossy(&out.stdout)),
+    )
+}
+
 /// Disable and stop a systemd service.
 pub fn disable_service(service: &str) -> Result<()> {
     // Stop first
-    let _ = std::process::Command::new("systemctl")
+    let _ = crate::cmd::Cmd::new("systemctl")
         .args(["stop", service])
         .status();
 
     // Then disable
-    let status = std::process::Command::new("systemctl")
+    let status = crate::cmd::Cmd::new("systemctl")
         .args(["disable", service])
-        .status()
-        .map_err(|e| Error::Other(format!("failed to disable {}: {}", service, e)))?;
+        .status()?;
 
     if !status.success() {
         // Mask it as a fallback (some services re-enable themselves)
-        let _ = std::process::Command::new("systemctl")
+        let _ = crate::cmd::Cmd::new("systemctl")
             .args(["mask", service])
             .status();
     }
@@ -26,14 +133,13 @@ pub fn disable_service(service: &str) -> Result<()> {
 /// Re-enable a previously disabled service.
 pub fn enable_service(service: &str) -> Result<()> {
     // Unmask first in case we masked it
-    let _ = std::process::Command::new("systemctl")
+    let _ = crate::cmd::Cmd::new("systemctl")
         .args(["unmask", service])
         .status();
 
-    let status = std::process::Command::new("systemctl")
+    let status = crate::cmd::Cmd::new("systemctl")
         .args(["enable", service])
-        .status()
-        .map_err(|e| Error::Other(format!("failed to enable {}: {}", service, e)))?;
+        .status()?;
 
     if !status.success() {
         return Err(Error::Other(format!("systemctl enable {} failed", service)));
@@ -41,3 +147,108 @@ pub fn enable_service(service: &str) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `systemctl list-dependencies --reverse --plain power-profiles-daemon.service`
+    /// on a GNOME desktop where a portal helper requires ppd
+    const ENTANGLED_REVERSE: &str = "\
+power-profiles-daemon.service
+  gnome-shell-portal-helper.service
+  graphical.target
+    multi-user.target
+  bop-powersave.service
+";
+
+    const ENTANGLED_SHOW: &str = "\
+Id=gnome-shell-portal-helper.service
+ActiveState=active
+Requires=dbus.socket power-profiles-daemon.service
+Requisite=
+BindsTo=
+
+Id=graphical.target
+ActiveState=active
+Requires=multi-user.target
+Requisite=
+BindsTo=
+
+Id=bop-powersave.service
+ActiveState=active
+Requires=power-profiles-daemon.service
+Requisite=
+BindsTo=
+";
+
+    /// Only pulled in by `Wants=` from a target, and an inactive unit
+    /// that requires it
+    const CLEAN_REVERSE: &str = "\
+power-profiles-daemon.service
+  graphical.target
+  ppd-tweaks.service
+";
+
+    const CLEAN_SHOW: &str = "\
+Id=graphical.target
+ActiveState=active
+Requires=multi-user.target
+Requisite=
+BindsTo=
+
+Id=ppd-tweaks.service
+ActiveState=inactive
+Requires=power-profiles-daemon.service
+Requisite=
+BindsTo=
+";
+
+    #[test]
+    fn test_parse_reverse_dependencies_keeps_direct_dependents() {
+        assert_eq!(
+            parse_reverse_dependencies(ENTANGLED_REVERSE),
+            [
+                "gnome-shell-portal-helper.service",
+                "graphical.target",
+                "bop-powersave.service"
+            ]
+        );
+        assert!(parse_reverse_dependencies("tlp.service\n").is_empty());
+        assert!(parse_reverse_dependencies("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_unit_dependencies() {
+        let units = parse_unit_dependencies(ENTANGLED_SHOW);
+        assert_eq!(units.len(), 3);
+        assert_eq!(units[0].id, "gnome-shell-portal-helper.service");
+        assert_eq!(units[0].active_state, "active");
+        assert_eq!(
+            units[0].hard_dependencies,
+            ["dbus.socket", "power-profiles-daemon.service"]
+        );
+        assert_eq!(units[1].hard_dependencies, ["multi-user.target"]);
+    }
+
+    #[test]
+    fn test_blocking_dependents_entangled() {
+        let units = parse_unit_dependencies(ENTANGLED_SHOW);
+        // bop's own unit is ignored; the target only depends on something else
+        assert_eq!(
+            blocking_dependents("power-profiles-daemon.service", &units),
+            ["gnome-shell-portal-helper.service"]
+        );
+    }
+
+    #[test]
+    fn test_blocking_dependents_clean() {
+        assert_eq!(
+            parse_reverse_dependencies(CLEAN_REVERSE),
+            ["graphical.target", "ppd-tweaks.service"]
+        );
+        let units = parse_unit_dependencies(CLEAN_SHOW);
+        assert!(blocking_dependents("power-profiles-daemon.service", &units).is_empty());
+        assert!(blocking_dependents("power-profiles-daemon.service", &[]).is_empty());
+    }
+}
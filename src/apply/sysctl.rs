@@ -0,0 +1,55 @@
+//! Sysctls as a `/etc/sysctl.d` drop-in. On the running system bop writes
+//! `/proc/sys` like any other file and the persistence unit repeats the
+//! writes at boot. An image built with `--offline-root` gets the drop-in as
+//! well, so systemd-sysctl sets them on its first boot before any unit runs.
+
+use super::PlannedSysfsWrite;
+
+pub const DROP_IN_PATH: &str = "/etc/sysctl.d/60-bop.conf";
+
+const PROC_SYS: &str = "/proc/sys/";
+
+/// A drop-in making each `/proc/sys` write in `writes`; None when there
+/// are none.
+pub fn drop_in(writes: &[PlannedSysfsWrite]) -> Option<String> {
+    let lines: Vec<String> = writes
+        .iter()
+        .filter_map(|write| {
+            let key = write.path.strip_prefix(PROC_SYS)?;
+            Some(format!("{} = {}\n", key.replace('/', "."), write.value))
+        })
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+    Some(format!("{}\n{}", crate::managed::header(), lines.concat()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &str, value: &str) -> PlannedSysfsWrite {
+        PlannedSysfsWrite {
+            path: path.to_string(),
+            value: value.to_string(),
+            description: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_drop_in_keeps_only_sysctls() {
+        let writes = [
+            write("/proc/sys/kernel/nmi_watchdog", "0"),
+            write("/sys/module/pcie_aspm/parameters/policy", "powersave"),
+            write("/proc/sys/vm/dirty_writeback_centisecs", "1500"),
+        ];
+        let content = drop_in(&writes).unwrap();
+        assert!(crate::managed::has_header(&content));
+        assert!(
+            content.ends_with("\nkernel.nmi_watchdog = 0\nvm.dirty_writeback_centisecs = 1500\n")
+        );
+
+        assert!(drop_in(&writes[1..2]).is_none());
+    }
+}
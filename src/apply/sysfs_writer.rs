@@ -1,65 +1,268 @@
+use crate::detect::acpi_wakeup;
 use crate::error::{Error, Result};
 use std::path::{Path, PathBuf};
-#[cfg(test)]
-use std::sync::{LazyLock, Mutex};
-
-const ACPI_WAKEUP_PATH: &str = "/proc/acpi/wakeup";
 
-#[cfg(test)]
-static ACPI_WAKEUP_PATH_OVERRIDE: LazyLock<Mutex<Option<PathBuf>>> =
-    LazyLock::new(|| Mutex::new(None));
+pub const ACPI_WAKEUP_PATH: &str = "/proc/acpi/wakeup";
 
 fn acpi_wakeup_path() -> PathBuf {
-    #[cfg(test)]
-    {
-        if let Some(path) = ACPI_WAKEUP_PATH_OVERRIDE
-            .lock()
-            .expect("acpi wakeup path override lock poisoned")
-            .clone()
+    PathBuf::from(ACPI_WAKEUP_PATH)
+}
+
+/// Write a value to a sysfs path (absolute path).
+pub fn write_sysfs(path: &str, value: &str) -> Result<()> {
+    std::fs::write(path, value).map_err(|e| Error::SysfsWrite {
+        path: Path::new(path).to_path_buf(),
+        source: e,
+    })
+}
+
+/// The /proc/acpi/wakeup table. Reads return the whole file; writing a
+/// device name flips that device's state.
+pub trait WakeupTable {
+    fn read(&mut self) -> Result<String>;
+    fn write(&mut self, device: &str) -> Result<()>;
+}
+
+/// The running system's table.
+pub struct ProcAcpiWakeup;
+
+impl WakeupTable for ProcAcpiWakeup {
+    fn read(&mut self) -> Result<String> {
+        let path = acpi_wakeup_path();
+        std::fs::read_to_string(&path).map_err(|e| Error::SysfsRead { path, source: e })
+    }
+
+    fn write(&mut self, device: &str) -> Result<()> {
+        let path = acpi_wakeup_path();
+        std::fs::write(&path, device).map_err(|e| Error::SysfsWrite { path, source: e })
+    }
+}
+
+/// `device`'s line and state in the table, None if it isn't listed. A
+/// name listed twice is an error: the write would toggle the first.
+fn wakeup_entry(table: &str, device: &str) -> Result<Option<(String, Option<bool>)>> {
+    let entries = acpi_wakeup::parse(table);
+    Ok(acpi_wakeup::lookup(&entries, device)?.map(|e| (e.line.clone(), e.enabled)))
+}
+
+/// Bring an ACPI wakeup source to `enabled` on the running system.
+pub fn toggle_acpi_wakeup(device: &str, enabled: bool) -> Result<bool> {
+    toggle_acpi_wakeup_in(&mut ProcAcpiWakeup, device, enabled)
+}
+
+/// Bring `device` to `enabled` through the toggle interface, verifying the
+/// result. The table can be re-enumerated between read and write (dock
+/// hotplug), so a write may land on another entry or fail with EINVAL: the
+/// state is re-read after each write and the toggle retried once. An entry
+/// a misdirected write flipped is flipped back. Returns whether anything
+/// was written, i.e. false if the source was already there.
+pub fn toggle_acpi_wakeup_in(
+    table: &mut dyn WakeupTable,
+    device: &str,
+    enabled: bool,
+) -> Result<bool> {
+    let initial = table.read()?;
+    let result = toggle_entry(table, &initial, device, enabled);
+    if !matches!(result, Ok(false)) {
+        restore_neighbours(table, &initial, device);
+    }
+    result
+}
+
+fn toggle_entry(
+    table: &mut dyn WakeupTable,
+    initial: &str,
+    device: &str,
+    enabled: bool,
+) -> Result<bool> {
+    let before = wakeup_entry(initial, device)?;
+    // A source that isn't listed can't wake anything
+    if before.is_none() && !enabled {
+        return Ok(false);
+    }
+    let mut after = before.clone();
+    for attempt in 0..2 {
+        // Already there: nothing to flip (a previous write may have landed late)
+        if after.as_ref().and_then(|(_, e)| *e) == Some(enabled) {
+            return Ok(attempt > 0);
+        }
+        if let Err(e) = table.write(device)
+            && attempt == 1
         {
-            return path;
+            return Err(e);
         }
+        after = wakeup_entry(&table.read()?, device)?;
     }
+    if after.as_ref().and_then(|(_, e)| *e) == Some(enabled) {
+        return Ok(true);
+    }
+    Err(Error::AcpiWakeupToggle {
+        device: device.to_string(),
+        enabled,
+        before: before.map(|(line, _)| line),
+        after: after.map(|(line, _)| line),
+    })
+}
 
-    PathBuf::from(ACPI_WAKEUP_PATH)
+/// Flip back every entry other than `device` whose state no longer matches
+/// `initial`. Best effort: one write each, and a name listed twice is left
+/// alone, since writing it could flip the other entry.
+fn restore_neighbours(table: &mut dyn WakeupTable, initial: &str, device: &str) {
+    let initial = acpi_wakeup::parse(initial);
+    let Ok(now) = table.read() else {
+        return;
+    };
+    let now = acpi_wakeup::parse(&now);
+    let flipped: Vec<&str> = now
+        .iter()
+        .filter(|e| e.device != device && !acpi_wakeup::is_ambiguous(&now, e))
+        .filter(|e| {
+            initial.iter().any(|i| {
+                i.device == e.device
+                    && i.index == e.index
+                    && i.enabled.is_some()
+                    && i.enabled != e.enabled
+            })
+        })
+        .map(|e| e.device.as_str())
+        .collect();
+    for name in flipped {
+        let _ = table.write(name);
+    }
 }
 
+/// A wakeup table in memory that can misdirect writes, the way a
+/// re-enumerated /proc/acpi/wakeup does.
 #[cfg(test)]
-pub(crate) struct AcpiWakeupPathGuard {
-    _guard: std::marker::PhantomData<()>,
+pub(crate) struct MockWakeupTable {
+    pub content: String,
+    /// This many upcoming writes flip the entry after the named one instead
+    pub misdirected_writes: usize,
+    pub writes: Vec<String>,
 }
 
 #[cfg(test)]
-impl Drop for AcpiWakeupPathGuard {
-    fn drop(&mut self) {
-        *ACPI_WAKEUP_PATH_OVERRIDE
-            .lock()
-            .expect("acpi wakeup path override lock poisoned") = None;
+impl MockWakeupTable {
+    pub fn new(content: &str) -> Self {
+        Self {
+            content: content.to_string(),
+            misdirected_writes: 0,
+            writes: Vec::new(),
+        }
+    }
+
+    pub fn enabled(&self, device: &str) -> Option<bool> {
+        wakeup_entry(&self.content, device).unwrap()?.1
     }
 }
 
 #[cfg(test)]
-pub(crate) fn set_acpi_wakeup_path_override_for_tests(path: PathBuf) -> AcpiWakeupPathGuard {
-    let mut guard = ACPI_WAKEUP_PATH_OVERRIDE
-        .lock()
-        .expect("acpi wakeup path override lock poisoned");
-    *guard = Some(path);
-    AcpiWakeupPathGuard {
-        _guard: std::marker::PhantomData,
+impl WakeupTable for MockWakeupTable {
+    fn read(&mut self) -> Result<String> {
+        Ok(self.content.clone())
     }
-}
 
-/// Write a value to a sysfs path (absolute path).
-pub fn write_sysfs(path: &str, value: &str) -> Result<()> {
-    std::fs::write(path, value).map_err(|e| Error::SysfsWrite {
-        path: Path::new(path).to_path_buf(),
-        source: e,
-    })
+    fn write(&mut self, device: &str) -> Result<()> {
+        self.writes.push(device.to_string());
+        let mut lines: Vec<String> = self.content.lines().map(String::from).collect();
+        let Some(mut index) = lines
+            .iter()
+            .position(|l| l.split_whitespace().next() == Some(device))
+        else {
+            return Err(Error::SysfsWrite {
+                path: PathBuf::from(ACPI_WAKEUP_PATH),
+                source: std::io::Error::from_raw_os_error(nix::libc::EINVAL),
+            });
+        };
+        if self.misdirected_writes > 0 {
+            self.misdirected_writes -= 1;
+            index = (index + 1) % lines.len();
+        }
+        let line = &mut lines[index];
+        *line = if line.contains("*enabled") {
+            line.replace("*enabled", "*disabled")
+        } else {
+            line.replace("*disabled", "*enabled")
+        };
+        self.content = lines.join("\n") + "\n";
+        Ok(())
+    }
 }
 
-/// Toggle an ACPI wakeup source.
-/// /proc/acpi/wakeup uses a toggle interface -- writing the device name flips its state.
-pub fn toggle_acpi_wakeup(device: &str) -> Result<()> {
-    let path = acpi_wakeup_path();
-    std::fs::write(&path, device).map_err(|e| Error::SysfsWrite { path, source: e })
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "\
+XHC1\tS3\t*enabled\tpci:0000:c1:00.4
+XHC10\tS3\t*enabled\tpci:0000:c4:00.3
+LID0\tS4\t*enabled\tplatform:PNP0C0D:00
+";
+
+    #[test]
+    fn test_toggle_verifies_and_skips_sources_already_in_place() {
+        let mut table = MockWakeupTable::new(TABLE);
+        assert!(toggle_acpi_wakeup_in(&mut table, "XHC1", false).unwrap());
+        assert_eq!(table.enabled("XHC1"), Some(false));
+        assert_eq!(table.enabled("XHC10"), Some(true));
+
+        // No write when the source is already where it should be
+        assert!(!toggle_acpi_wakeup_in(&mut table, "XHC1", false).unwrap());
+        assert_eq!(table.writes, ["XHC1"]);
+    }
+
+    #[test]
+    fn test_toggle_retries_once_after_a_misdirected_write() {
+        let mut table = MockWakeupTable::new(TABLE);
+        table.misdirected_writes = 1;
+        assert!(toggle_acpi_wakeup_in(&mut table, "XHC1", false).unwrap());
+        assert_eq!(table.enabled("XHC1"), Some(false));
+        // The first write flipped XHC10 instead; it is put back
+        assert_eq!(table.enabled("XHC10"), Some(true));
+        assert_eq!(table.enabled("LID0"), Some(true));
+        assert_eq!(table.writes, ["XHC1", "XHC1", "XHC10"]);
+    }
+
+    #[test]
+    fn test_toggle_reports_before_and_after_lines() {
+        let mut table = MockWakeupTable::new(TABLE);
+        table.misdirected_writes = 2;
+        let err = toggle_acpi_wakeup_in(&mut table, "XHC1", false).unwrap_err();
+        match err {
+            Error::AcpiWakeupToggle {
+                device,
+                enabled,
+                before,
+                after,
+            } => {
+                assert_eq!(device, "XHC1");
+                assert!(!enabled);
+                assert_eq!(
+                    before.as_deref(),
+                    Some("XHC1\tS3\t*enabled\tpci:0000:c1:00.4")
+                );
+                assert_eq!(after, before);
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+        // Both writes flipped XHC10; two flips leave it as it was
+        assert_eq!(table.enabled("XHC10"), Some(true));
+        assert_eq!(table.writes.len(), 2);
+
+        // A source missing from the table can't wake anything, but can't be
+        // enabled either
+        assert!(!toggle_acpi_wakeup_in(&mut table, "GPP6", false).unwrap());
+        let err = toggle_acpi_wakeup_in(&mut table, "GPP6", true).unwrap_err();
+        assert!(matches!(err, Error::SysfsWrite { .. }));
+    }
+
+    #[test]
+    fn test_toggle_refuses_a_duplicated_name() {
+        let mut table = MockWakeupTable::new(
+            "GPP6\tS4\t*enabled\tpci:0000:00:02.2\nGPP6\tS4\t*enabled\tpci:0000:00:02.4\n",
+        );
+        let err = toggle_acpi_wakeup_in(&mut table, "GPP6", false).unwrap_err();
+        assert!(matches!(err, Error::AmbiguousWakeupDevice { .. }));
+        assert!(table.writes.is_empty());
+    }
 }
@@ -1,48 +1,208 @@
 use crate::apply::ApplyPlan;
-use crate::detect::HardwareInfo;
+use crate::apply::wifi::WifiPowerSave;
 use crate::error::{Error, Result};
-use std::path::{Path, PathBuf};
+use crate::managed::{ManagedFile, Ownership};
+use crate::sysfs::SysfsRoot;
+use std::path::Path;
 
-const SERVICE_PATH: &str = "/etc/systemd/system/bop-powersave.service";
+pub const SERVICE_NAME: &str = "bop-powersave.service";
+pub const SERVICE_PATH: &str = "/etc/systemd/system/bop-powersave.service";
+/// Where `systemctl enable` links the unit, per its `WantedBy=`.
+pub const WANTS_DIR: &str = "/etc/systemd/system/multi-user.target.wants";
+
+/// Written by the unit after each run. /run survives `systemctl soft-reboot`,
+/// so `bop status` can tell whether the unit ran again in the new userspace.
+pub const PERSIST_MARKER_PATH: &str = "/run/bop/persist-marker";
+
+/// How long after the unit a kept power daemon gets to restore its own
+/// settings before bop's are written once more. power-profiles-daemon
+/// applies its saved profile a moment after it reports started.
+pub const DAEMON_SETTLE_SECS: u32 = 5;
+
+/// sysfs class directories numbered in probe order, with the attribute
+/// that names each one. The number can differ next boot, so the unit finds
+/// the directory by that name instead.
+const NUMBERED_CLASSES: &[(&str, &str)] = &[
+    ("/sys/class/thermal/cooling_device", "type"),
+    ("/sys/class/platform-profile/platform-profile-", "name"),
+];
+
+/// How the unit reaches a sysfs file at boot.
+#[derive(Debug, PartialEq, Eq)]
+enum BootTarget {
+    /// Same path every boot
+    Fixed(String),
+    /// `leaf` under each directory matching `glob` whose `key` attribute,
+    /// when given, reads the value recorded now
+    Found {
+        glob: String,
+        key: Option<(String, String)>,
+        leaf: String,
+    },
+}
+
+/// Where the unit writes `path` next boot; None when the directory's name
+/// can't be read now, leaving the write to the running boot.
+fn boot_target(path: &str, sysfs: &SysfsRoot) -> Option<BootTarget> {
+    for (prefix, key) in NUMBERED_CLASSES {
+        let Some((number, leaf)) = path
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.split_once('/'))
+        else {
+            continue;
+        };
+        if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+        let dir = format!("{}{}", prefix, number);
+        let name = sysfs
+            .read_optional(format!("{}/{}", dir.trim_start_matches('/'), key))
+            .unwrap_or(None)
+            .filter(|name| !name.is_empty())?;
+        return Some(BootTarget::Found {
+            glob: format!("{}*", prefix),
+            key: Some((key.to_string(), name)),
+            leaf: leaf.to_string(),
+        });
+    }
+    // DRM cards are numbered in probe order too; the connector after the
+    // card number ("-eDP-1") is stable
+    if let Some((card, leaf)) = path
+        .strip_prefix("/sys/class/drm/card")
+        .and_then(|rest| rest.split_once('/'))
+        && let Some((number, connector)) = card.split_once('-')
+        && !number.is_empty()
+        && number.bytes().all(|b| b.is_ascii_digit())
+    {
+        return Some(BootTarget::Found {
+            glob: format!("/sys/class/drm/card*-{}", connector),
+            key: None,
+            leaf: leaf.to_string(),
+        });
+    }
+    Some(BootTarget::Fixed(path.to_string()))
+}
+
+/// The `ExecStart=` line writing `value` to `target`.
+fn write_line(value: &str, target: &BootTarget) -> String {
+    // Value and path go in as positional args so the script body never
+    // sees them; only systemd's own quoting applies.
+    match target {
+        BootTarget::Fixed(path) => format!(
+            "ExecStart=/bin/sh -c 'printf %%s \"$$1\" > \"$$2\"' bop-write {} {}",
+            quote_arg(value),
+            quote_arg(path)
+        ),
+        // $3 is left unquoted so the shell expands the glob
+        BootTarget::Found { glob, key, leaf } => {
+            let (key, name) = key.clone().unwrap_or_default();
+            format!(
+                "ExecStart=/bin/sh -c 'for d in $$3; do [ -e \"$$d/$$2\" ] || continue; \
+                 [ -z \"$$4\" ] || [ \"$$(cat \"$$d/$$4\")\" = \"$$5\" ] || continue; \
+                 printf %%s \"$$1\" > \"$$d/$$2\"; done' bop-find {} {} {} {} {}",
+                quote_arg(value),
+                quote_arg(leaf),
+                quote_arg(glob),
+                quote_arg(&key),
+                quote_arg(&name)
+            )
+        }
+    }
+}
+
+/// Conflicting daemons the plan leaves running (required by other units).
+/// They come up at boot too and may overwrite what the unit writes.
+fn kept_daemons(plan: &ApplyPlan) -> Vec<&str> {
+    plan.services_blocked
+        .iter()
+        .map(|b| b.service.as_str())
+        .collect()
+}
 
 /// Generate a systemd oneshot service that applies sysfs settings on boot.
-pub fn generate_service(hw: &HardwareInfo, plan: &ApplyPlan) -> Result<PathBuf> {
+pub fn generate_service(plan: &ApplyPlan, ownership: &Ownership) -> Result<ManagedFile> {
+    let unit = render_service(plan);
+    ownership.write(Path::new(SERVICE_PATH), &unit)?;
+    Ok(ManagedFile::new(SERVICE_PATH, &unit))
+}
+
+/// Render the unit file contents.
+pub fn render_service(plan: &ApplyPlan) -> String {
+    render_service_in(plan, &SysfsRoot::system())
+}
+
+/// Render the unit, naming numbered sysfs directories by what `sysfs`
+/// reads for them now.
+pub fn render_service_in(plan: &ApplyPlan, sysfs: &SysfsRoot) -> String {
     let mut exec_lines = Vec::new();
 
-    // Sysfs writes
-    for write in &plan.sysfs_writes {
-        // Use tee to write values (handles permission properly under systemd)
-        exec_lines.push(format!(
-            "ExecStart=/bin/bash -c 'echo \"{}\" > \"{}\"'",
-            write.value, write.path
-        ));
-    }
+    // Sysfs writes. Empty USB ports are chosen from this boot's connect
+    // history, which says nothing about the next one, so they stay runtime.
+    let sysfs_lines: Vec<String> = plan
+        .sysfs_writes
+        .iter()
+        .filter(|w| !crate::apply::usb_ports::is_port_disable_path(&w.path))
+        .filter_map(|write| {
+            boot_target(&write.path, sysfs).map(|target| write_line(&write.value, &target))
+        })
+        .collect();
+    exec_lines.extend(sysfs_lines.iter().cloned());
 
     // ACPI wakeup sources (volatile, need to be re-applied each boot)
     for device in &plan.acpi_wakeup_disable {
-        // Need to check state before toggling since it's a toggle interface
+        // Need to check state before toggling since it's a toggle interface.
+        // The name is matched whole: XHC must not match XHC1's line
         exec_lines.push(format!(
-            "ExecStart=/bin/bash -c 'grep -q \"^{}.*\\*enabled\" /proc/acpi/wakeup && echo \"{}\" > /proc/acpi/wakeup || true'",
+            "ExecStart=/bin/bash -c 'grep -q \"^{}[[:space:]].*\\*enabled\" /proc/acpi/wakeup && echo \"{}\" > /proc/acpi/wakeup || true'",
             device, device
         ));
     }
 
-    // WiFi power save
-    if let Some(ref iface) = hw.network.wifi_interface {
+    // WiFi power save, when no manager owns the interface
+    if let Some(WifiPowerSave::Iw(iface)) = &plan.wifi_power_save {
         exec_lines.push(format!(
             "ExecStart=/usr/bin/iw dev {} set power_save on",
             iface
         ));
     }
 
-    let unit = format!(
-        r#"# Generated by bop (Battery Optimization Project)
-# Do not edit manually -- use `bop apply` to regenerate or `bop revert` to remove
+    // Record which userspace generation we ran in ($$ escapes systemd's own expansion)
+    exec_lines.push(format!(
+        "ExecStartPost=/bin/bash -c 'mkdir -p /run/bop && echo \"$$(cat /proc/sys/kernel/random/boot_id) $$(systemctl show -P SoftRebootsCount 2>/dev/null)\" > {}'",
+        PERSIST_MARKER_PATH
+    ));
+
+    // A kept daemon may still overwrite them after it starts: write them
+    // once more from a transient timer, so boot doesn't wait for it to
+    // settle. Best effort ("-"): the first writes already went in.
+    let kept = kept_daemons(plan);
+    if !kept.is_empty() {
+        exec_lines.extend(sysfs_lines.iter().map(|line| {
+            format!(
+                "ExecStartPost=-/usr/bin/systemd-run --quiet --collect --on-active={}s \
+                 --timer-property=AccuracySec=1s {}",
+                DAEMON_SETTLE_SECS,
+                line.trim_start_matches("ExecStart=")
+            )
+        }));
+    }
+
+    // Start after the kept daemons, so they don't overwrite the first
+    // writes. Ordering only: starting them is up to whoever requires them.
+    let ordering: String = kept.iter().map(|svc| format!("After={}\n", svc)).collect();
+
+    // Conflicts/Before soft-reboot.target stops the unit on `systemctl soft-reboot`,
+    // clearing RemainAfterExit so multi-user.target starts it again afterwards.
+    format!(
+        r#"{}
+# Use `bop apply` to regenerate or `bop revert` to remove
 
 [Unit]
 Description=bop power optimization (sysfs runtime settings)
 After=multi-user.target
 Wants=multi-user.target
+{}Conflicts=soft-reboot.target
+Before=soft-reboot.target
 
 [Service]
 Type=oneshot
@@ -52,34 +212,44 @@ RemainAfterExit=yes
 [Install]
 WantedBy=multi-user.target
 "#,
+        crate::managed::header(),
+        ordering,
         exec_lines.join("\n")
-    );
-
-    std::fs::write(SERVICE_PATH, &unit).map_err(|e| {
-        Error::Other(format!(
-            "failed to write systemd service {}: {}",
-            SERVICE_PATH, e
-        ))
-    })?;
+    )
+}
 
-    Ok(PathBuf::from(SERVICE_PATH))
+/// Quote one word for a systemd `ExecStart=` line. Inside double quotes
+/// systemd still unescapes `\\`/`\"` and expands `%` specifiers and `$` vars.
+fn quote_arg(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '%' => out.push_str("%%"),
+            '$' => out.push_str("$$"),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
 }
 
 /// Enable the bop-powersave service.
 pub fn enable_service() -> Result<()> {
-    let status = std::process::Command::new("systemctl")
+    let status = crate::cmd::Cmd::new("systemctl")
         .args(["daemon-reload"])
-        .status()
-        .map_err(|e| Error::Other(format!("systemctl daemon-reload failed: {}", e)))?;
+        .status()?;
 
     if !status.success() {
         return Err(Error::Other("systemctl daemon-reload failed".to_string()));
     }
 
-    let status = std::process::Command::new("systemctl")
+    let status = crate::cmd::Cmd::new("systemctl")
         .args(["enable", "bop-powersave.service"])
-        .status()
-        .map_err(|e| Error::Other(format!("systemctl enable failed: {}", e)))?;
+        .status()?;
 
     if !status.success() {
         return Err(Error::Other(
@@ -90,26 +260,300 @@ pub fn enable_service() -> Result<()> {
     Ok(())
 }
 
-/// Disable and remove the bop-powersave service.
-pub fn remove_service() -> Result<()> {
+/// Disable and remove the bop-powersave service. A unit at that path that
+/// `ownership` doesn't accept is left alone, enabled or not.
+pub fn remove_service(ownership: &Ownership) -> Result<()> {
     let service_path = Path::new(SERVICE_PATH);
 
     if service_path.exists() {
-        let _ = std::process::Command::new("systemctl")
+        ownership.check(service_path)?;
+
+        let _ = crate::cmd::Cmd::new("systemctl")
             .args(["disable", "bop-powersave.service"])
             .status();
 
-        let _ = std::process::Command::new("systemctl")
+        let _ = crate::cmd::Cmd::new("systemctl")
             .args(["stop", "bop-powersave.service"])
             .status();
 
         std::fs::remove_file(service_path)
             .map_err(|e| Error::Other(format!("failed to remove {}: {}", SERVICE_PATH, e)))?;
 
-        let _ = std::process::Command::new("systemctl")
+        let _ = crate::cmd::Cmd::new("systemctl")
             .args(["daemon-reload"])
             .status();
     }
 
+    let _ = std::fs::remove_file(PERSIST_MARKER_PATH);
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply::{BlockedService, PlannedSysfsWrite};
+
+    const PPD: &str = "power-profiles-daemon.service";
+
+    fn profile_plan() -> ApplyPlan {
+        ApplyPlan {
+            sysfs_writes: vec![PlannedSysfsWrite {
+                path: "/sys/firmware/acpi/platform_profile".to_string(),
+                value: "low-power".to_string(),
+                description: String::new(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn profile_writes(unit: &str) -> usize {
+        unit.lines()
+            .filter(|l| l.starts_with("ExecStart=") && l.contains("platform_profile"))
+            .count()
+    }
+
+    #[test]
+    fn test_unit_with_ppd_disabled_has_no_ordering_on_it() {
+        let mut plan = profile_plan();
+        plan.services_to_disable = vec![PPD.to_string()];
+        let unit = render_service(&plan);
+        assert!(!unit.contains(PPD));
+        assert!(!unit.contains("systemd-run"));
+        assert_eq!(profile_writes(&unit), 1);
+    }
+
+    #[test]
+    fn test_unit_with_ppd_kept_starts_after_it_and_writes_again() {
+        let mut plan = profile_plan();
+        plan.services_blocked = vec![BlockedService {
+            service: PPD.to_string(),
+            required_by: vec!["gnome-shell-portal-helper.service".to_string()],
+        }];
+        let unit = render_service(&plan);
+        assert!(unit.contains(&format!(
+            "\nWants=multi-user.target\nAfter={}\nConflicts=soft-reboot.target\n",
+            PPD
+        )));
+        assert!(!unit.contains(&format!("Wants={}", PPD)));
+        // Written once in the unit, once more from a timer that doesn't hold up boot
+        assert_eq!(profile_writes(&unit), 1);
+        let write = unit
+            .lines()
+            .find(|l| l.starts_with("ExecStart="))
+            .unwrap()
+            .trim_start_matches("ExecStart=");
+        let again: Vec<&str> = unit
+            .lines()
+            .filter(|l| l.starts_with("ExecStartPost=-/usr/bin/systemd-run"))
+            .collect();
+        assert_eq!(again.len(), 1);
+        assert!(again[0].contains(&format!("--on-active={}s", DAEMON_SETTLE_SECS)));
+        assert!(again[0].ends_with(write));
+        assert!(!unit.contains("/bin/sleep"));
+
+        // `--force-disable` moves it over: back to a single pass
+        plan.force_disable_blocked();
+        let unit = render_service(&plan);
+        assert!(!unit.contains(&format!("After={}", PPD)));
+        assert!(!unit.contains("systemd-run"));
+        assert_eq!(profile_writes(&unit), 1);
+    }
+
+    #[test]
+    fn test_unit_reruns_after_soft_reboot() {
+        let unit = render_service(&ApplyPlan::default());
+        assert!(unit.contains("\nConflicts=soft-reboot.target\n"));
+        assert!(unit.contains("\nBefore=soft-reboot.target\n"));
+        assert!(unit.contains("\nRemainAfterExit=yes\n"));
+        assert!(unit.contains("\nWantedBy=multi-user.target\n"));
+    }
+
+    #[test]
+    fn test_unit_writes_persist_marker() {
+        let unit = render_service(&ApplyPlan::default());
+        let marker = unit
+            .lines()
+            .find(|l| l.starts_with("ExecStartPost="))
+            .expect("marker line");
+        assert!(marker.contains(PERSIST_MARKER_PATH));
+        // Command substitutions must be escaped from systemd's $ expansion
+        assert!(marker.contains("$$(cat /proc/sys/kernel/random/boot_id)"));
+        assert!(marker.contains("$$(systemctl show -P SoftRebootsCount"));
+        assert!(!marker.replace("$$", "").contains('$'));
+    }
+
+    #[test]
+    fn test_unit_includes_sysfs_writes() {
+        let plan = ApplyPlan {
+            sysfs_writes: vec![PlannedSysfsWrite {
+                path: "/sys/firmware/acpi/platform_profile".to_string(),
+                value: "low-power".to_string(),
+                description: String::new(),
+            }],
+            ..Default::default()
+        };
+        let unit = render_service(&plan);
+        assert!(unit.contains(
+            "ExecStart=/bin/sh -c 'printf %%s \"$$1\" > \"$$2\"' bop-write \"low-power\" \"/sys/firmware/acpi/platform_profile\""
+        ));
+    }
+
+    fn single_write(path: &str) -> ApplyPlan {
+        ApplyPlan {
+            sysfs_writes: vec![PlannedSysfsWrite {
+                path: path.to_string(),
+                value: "3".to_string(),
+                description: String::new(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_unit_finds_numbered_class_directories_by_name() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().join("sys/class/thermal/cooling_device7");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("type"), "PCIe_Port_Link_Speed_0000:00:01.1\n").unwrap();
+        let sysfs = SysfsRoot::new(tmp.path());
+
+        let unit = render_service_in(
+            &single_write("/sys/class/thermal/cooling_device7/cur_state"),
+            &sysfs,
+        );
+        assert!(!unit.contains("cooling_device7"));
+        assert!(unit.contains(
+            "bop-find \"3\" \"cur_state\" \"/sys/class/thermal/cooling_device*\" \"type\" \"PCIe_Port_Link_Speed_0000:00:01.1\""
+        ));
+
+        // Nothing names the directory: the write stays with the running boot
+        let unit = render_service_in(
+            &single_write("/sys/class/thermal/cooling_device2/cur_state"),
+            &sysfs,
+        );
+        assert!(!unit.contains("cooling_device"));
+    }
+
+    #[test]
+    fn test_unit_finds_platform_profile_handler_by_name() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp
+            .path()
+            .join("sys/class/platform-profile/platform-profile-1");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("name"), "amd-pmf\n").unwrap();
+        let sysfs = SysfsRoot::new(tmp.path());
+
+        let mut plan = single_write("/sys/class/platform-profile/platform-profile-1/profile");
+        plan.sysfs_writes[0].value = "low-power".to_string();
+        let unit = render_service_in(&plan, &sysfs);
+        assert!(!unit.contains("platform-profile-1"));
+        assert!(unit.contains(
+            "bop-find \"low-power\" \"profile\" \"/sys/class/platform-profile/platform-profile-*\" \"name\" \"amd-pmf\""
+        ));
+    }
+
+    #[test]
+    fn test_unit_finds_drm_connector_on_any_card() {
+        let unit = render_service_in(
+            &single_write("/sys/class/drm/card1-eDP-1/amdgpu/panel_power_savings"),
+            &SysfsRoot::new("/nonexistent"),
+        );
+        assert!(!unit.contains("card1"));
+        assert!(unit.contains(
+            "bop-find \"3\" \"amdgpu/panel_power_savings\" \"/sys/class/drm/card*-eDP-1\" \"\" \"\""
+        ));
+    }
+
+    #[test]
+    fn test_unit_leaves_usb_port_disables_to_the_running_boot() {
+        let plan = ApplyPlan {
+            sysfs_writes: vec![PlannedSysfsWrite {
+                path: "/sys/bus/usb/devices/1-0:1.0/usb1-port2/disable".to_string(),
+                value: "1".to_string(),
+                description: String::new(),
+            }],
+            ..Default::default()
+        };
+        assert!(!render_service(&plan).contains("usb1-port2"));
+    }
+
+    #[test]
+    fn test_unit_matches_wakeup_names_whole() {
+        let plan = ApplyPlan {
+            acpi_wakeup_disable: vec!["XHC".to_string(), "XHC1".to_string()],
+            ..Default::default()
+        };
+        let unit = render_service(&plan);
+        let tmp = tempfile::TempDir::new().unwrap();
+        let table = tmp.path().join("wakeup");
+        std::fs::write(
+            &table,
+            "XHC\tS3\t*disabled\tpci:0000:c1:00.3\nXHC1\tS3\t*enabled\tpci:0000:c1:00.4\n",
+        )
+        .unwrap();
+        // Run each unit line's check against the table: only XHC1 is armed
+        let armed = |device: &str| {
+            let line = unit
+                .lines()
+                .find(|l| l.contains(&format!("echo \"{}\"", device)))
+                .unwrap();
+            let pattern = line
+                .split_once("grep -q \"")
+                .and_then(|(_, rest)| rest.split_once('"'))
+                .unwrap()
+                .0;
+            std::process::Command::new("grep")
+                .args(["-q", pattern])
+                .arg(&table)
+                .status()
+                .unwrap()
+                .success()
+        };
+        assert!(!armed("XHC"));
+        assert!(armed("XHC1"));
+    }
+
+    #[test]
+    fn test_unit_sets_wifi_power_save_only_without_a_manager() {
+        let mut plan = ApplyPlan {
+            wifi_power_save: Some(WifiPowerSave::Iw("wlan0".to_string())),
+            ..Default::default()
+        };
+        assert!(
+            render_service(&plan).contains("\nExecStart=/usr/bin/iw dev wlan0 set power_save on\n")
+        );
+
+        plan.wifi_power_save = Some(WifiPowerSave::NetworkManager);
+        assert!(!render_service(&plan).contains("iw dev"));
+    }
+
+    #[test]
+    fn test_quote_arg_keeps_value_one_word() {
+        assert_eq!(quote_arg("s2idle [deep]"), "\"s2idle [deep]\"");
+        assert_eq!(quote_arg("a\"b"), "\"a\\\"b\"");
+        assert_eq!(quote_arg("50%"), "\"50%%\"");
+        assert_eq!(quote_arg("$HOME"), "\"$$HOME\"");
+        assert_eq!(quote_arg("x\n"), "\"x\\n\"");
+        assert_eq!(quote_arg("c:\\"), "\"c:\\\\\"");
+    }
+
+    #[test]
+    fn test_unit_writes_values_with_spaces_whole() {
+        let plan = ApplyPlan {
+            sysfs_writes: vec![PlannedSysfsWrite {
+                path: "/sys/power/mem_sleep".to_string(),
+                value: "low-power balanced".to_string(),
+                description: String::new(),
+            }],
+            ..Default::default()
+        };
+        let unit = render_service(&plan);
+        let line = unit
+            .lines()
+            .find(|l| l.contains("/sys/power/mem_sleep"))
+            .unwrap();
+        assert!(line.ends_with("bop-write \"low-power balanced\" \"/sys/power/mem_sleep\""));
+    }
+}
@@ -0,0 +1,312 @@
+//! `bop upgrade-artifacts`: rewrite a persistence unit written by an older
+//! bop in the current format, without a revert and re-apply. Early units
+//! wrote each sysfs value with an inline `echo "value" > "path"` in the
+//! script body; the current one passes value and path as arguments. The
+//! new unit is rendered from the saved state, swapped in with a rename and
+//! recorded in the state's manifest. Nothing on the running system but the
+//! unit file changes.
+
+use super::wifi::WifiPowerSave;
+use super::{ApplyPlan, ApplyState, BlockedService, PlannedSysfsWrite, systemd};
+use crate::error::{Error, Result};
+use crate::managed::{self, ManagedFile};
+use crate::progress::{Progress, ProgressSink};
+use std::io::Write;
+use std::path::Path;
+
+/// Script body of an inline-echo sysfs write, up to the value.
+const INLINE_ECHO_PREFIX: &str = "ExecStart=/bin/bash -c 'echo \"";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitFormat {
+    /// Values passed as arguments to a fixed script
+    Current,
+    /// Values and paths spliced into the script body
+    InlineEcho,
+}
+
+/// The format of a persistence unit's contents.
+pub fn detect_format(unit: &str) -> UnitFormat {
+    if unit
+        .lines()
+        .any(|l| l.starts_with(INLINE_ECHO_PREFIX) && !l.contains("/proc/acpi/wakeup"))
+    {
+        UnitFormat::InlineEcho
+    } else {
+        UnitFormat::Current
+    }
+}
+
+/// What an inline-echo unit does at boot.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct LegacyUnit {
+    /// (path, value)
+    writes: Vec<(String, String)>,
+    acpi_wakeup: Vec<String>,
+    iw: Option<String>,
+    /// Units it's ordered after, other than multi-user.target: the power
+    /// daemons the plan kept
+    after: Vec<String>,
+    /// `Exec*` lines the old generator never wrote
+    unknown: Vec<String>,
+}
+
+fn parse_legacy(unit: &str) -> LegacyUnit {
+    let mut parsed = LegacyUnit {
+        after: unit
+            .lines()
+            .filter_map(|l| l.strip_prefix("After="))
+            .flat_map(str::split_whitespace)
+            .filter(|u| *u != "multi-user.target")
+            .map(str::to_string)
+            .collect(),
+        ..Default::default()
+    };
+    for line in unit.lines().filter(|l| l.starts_with("Exec")) {
+        if let Some(device) = line
+            .strip_prefix("ExecStart=/bin/bash -c 'grep -q \"^")
+            .and_then(|rest| rest.split_once(".*\\*enabled\" /proc/acpi/wakeup && echo \""))
+            .and_then(|(device, rest)| {
+                (rest == format!("{}\" > /proc/acpi/wakeup || true'", device)).then_some(device)
+            })
+        {
+            parsed.acpi_wakeup.push(device.to_string());
+        } else if let Some((value, path)) = line
+            .strip_prefix(INLINE_ECHO_PREFIX)
+            .and_then(|rest| rest.strip_suffix("\"'"))
+            .and_then(|rest| rest.split_once("\" > \""))
+        {
+            parsed.writes.push((path.to_string(), value.to_string()));
+        } else if let Some(iface) = line
+            .strip_prefix("ExecStart=/usr/bin/iw dev ")
+            .and_then(|rest| rest.strip_suffix(" set power_save on"))
+        {
+            parsed.iw = Some(iface.to_string());
+        } else {
+            parsed.unknown.push(line.to_string());
+        }
+    }
+    parsed
+}
+
+/// Whether `unit` at `path` is still what bop wrote: it matches the state's
+/// manifest entry, or, for state files from before the manifest, carries
+/// the header and only lines bop generated, with the values it recorded.
+fn as_written(unit: &str, path: &str, state: &ApplyState) -> bool {
+    if let Some(entry) = state.managed_files.iter().find(|m| m.path == path) {
+        return entry.hash == managed::content_hash(unit.as_bytes());
+    }
+    let parsed = parse_legacy(unit);
+    managed::has_header(unit)
+        && parsed.unknown.is_empty()
+        && parsed.writes.iter().all(|(path, value)| {
+            state
+                .sysfs_changes
+                .iter()
+                .any(|c| &c.path == path && &c.new_value == value)
+        })
+}
+
+/// The plan a unit for `state` is rendered from. WiFi power save through
+/// `iw` isn't in the state, so it's carried over from the old unit; so are
+/// wakeup sources that were already off when applied (the state only lists
+/// the ones bop toggled), and the kept daemons it was ordered after.
+fn plan_from_state(state: &ApplyState, legacy: &LegacyUnit) -> ApplyPlan {
+    let mut acpi_wakeup_disable = state.acpi_wakeup_toggled.clone();
+    for device in &legacy.acpi_wakeup {
+        if !acpi_wakeup_disable.contains(device) {
+            acpi_wakeup_disable.push(device.clone());
+        }
+    }
+    ApplyPlan {
+        sysfs_writes: state
+            .sysfs_changes
+            .iter()
+            .map(|c| PlannedSysfsWrite {
+                path: c.path.clone(),
+                value: c.new_value.clone(),
+                description: String::new(),
+            })
+            .collect(),
+        acpi_wakeup_disable,
+        wifi_power_save: legacy.iw.clone().map(WifiPowerSave::Iw),
+        services_blocked: legacy
+            .after
+            .iter()
+            .map(|service| BlockedService {
+                service: service.clone(),
+                required_by: Vec::new(),
+            })
+            .collect(),
+        systemd_service: true,
+        preset: state.preset,
+        ..Default::default()
+    }
+}
+
+/// What [`migrate_at`] did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Migration {
+    /// No unit at the path
+    NotInstalled,
+    /// Already in the current format
+    Current,
+    /// Rewritten, and the state's manifest entry updated
+    Migrated(ManagedFile),
+    /// In the old format, but edited since bop wrote it; left alone
+    Modified,
+}
+
+/// Replace `path` with `content` through a rename, so a crash leaves the
+/// old unit or the new one, never half of either.
+fn swap(path: &Path, content: &str) -> Result<()> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.bop-new", name));
+    let write = || -> std::io::Result<()> {
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)
+    };
+    write().map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        Error::Other(format!("failed to replace {}: {}", path.display(), e))
+    })
+}
+
+/// Move the unit at `path` to the current format, rendered from `state`,
+/// and record it in `state`. An edited old unit is only replaced with
+/// `force`. The caller saves the state and reloads systemd.
+pub fn migrate_at(path: &Path, state: &mut ApplyState, force: bool) -> Result<Migration> {
+    let unit = match std::fs::read_to_string(path) {
+        Ok(unit) => unit,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Migration::NotInstalled),
+        Err(e) => {
+            return Err(Error::Other(format!(
+                "failed to read {}: {}",
+                path.display(),
+                e
+            )));
+        }
+    };
+    if detect_format(&unit) == UnitFormat::Current {
+        return Ok(Migration::Current);
+    }
+    let recorded_as = path.to_string_lossy().into_owned();
+    if !force && !as_written(&unit, &recorded_as, state) {
+        return Ok(Migration::Modified);
+    }
+
+    let content = systemd::render_service(&plan_from_state(state, &parse_legacy(&unit)));
+    swap(path, &content)?;
+
+    let file = ManagedFile::new(&recorded_as, &content);
+    state.managed_files.retain(|m| m.path != recorded_as);
+    state.managed_files.push(file.clone());
+    if !state.systemd_units_created.contains(&recorded_as) {
+        state.systemd_units_created.push(recorded_as);
+    }
+    Ok(Migration::Migrated(file))
+}
+
+/// `bop upgrade-artifacts`: migrate the installed persistence unit.
+pub fn upgrade_artifacts(force: bool, progress: &mut dyn ProgressSink) -> Result<()> {
+    if !nix::unistd::geteuid().is_root() {
+        return Err(Error::NotRoot {
+            operation: "upgrade-artifacts".to_string(),
+        });
+    }
+    super::journal::reconcile()?;
+    let Some(mut state) = ApplyState::load()? else {
+        progress.emit(Progress::Info(
+            "No saved state found. Nothing to upgrade.".to_string(),
+        ));
+        return Ok(());
+    };
+
+    match migrate_at(Path::new(systemd::SERVICE_PATH), &mut state, force)? {
+        Migration::NotInstalled => progress.emit(Progress::Info(format!(
+            "{} is not installed. Nothing to upgrade.",
+            systemd::SERVICE_PATH
+        ))),
+        Migration::Current => progress.emit(Progress::Info(format!(
+            "{} is already in the current format.",
+            systemd::SERVICE_PATH
+        ))),
+        Migration::Modified => progress.emit(Progress::Warning(format!(
+            "{} is in the old format but was modified since bop wrote it; left as is. \
+             Run `sudo bop upgrade-artifacts --force` to replace it anyway.",
+            systemd::SERVICE_PATH
+        ))),
+        Migration::Migrated(_) => {
+            state.save()?;
+            let reloaded = crate::cmd::Cmd::new("systemctl")
+                .args(["daemon-reload"])
+                .status()
+                .is_ok_and(|s| s.success());
+            if !reloaded {
+                progress.emit(Progress::Warning(
+                    "systemctl daemon-reload failed; the new unit is used from the next reload or boot."
+                        .to_string(),
+                ));
+            }
+            progress.emit(Progress::Success(format!(
+                "Rewrote {} in the current format. Runtime settings were not touched.",
+                systemd::SERVICE_PATH
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_legacy_lines() {
+        let parsed = parse_legacy(
+            "ExecStart=/bin/bash -c 'echo \"s2idle [deep]\" > \"/sys/power/mem_sleep\"'\n\
+             ExecStart=/bin/bash -c 'grep -q \"^GPP6.*\\*enabled\" /proc/acpi/wakeup && echo \"GPP6\" > /proc/acpi/wakeup || true'\n\
+             ExecStart=/usr/bin/iw dev wlp1s0 set power_save on\n\
+             ExecStartPre=/bin/true\n",
+        );
+        assert_eq!(
+            parsed,
+            LegacyUnit {
+                writes: vec![(
+                    "/sys/power/mem_sleep".to_string(),
+                    "s2idle [deep]".to_string()
+                )],
+                acpi_wakeup: vec!["GPP6".to_string()],
+                iw: Some("wlp1s0".to_string()),
+                after: Vec::new(),
+                unknown: vec!["ExecStartPre=/bin/true".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_kept_daemons_carry_over() {
+        let old = "[Unit]\n\
+                   After=multi-user.target\n\
+                   After=power-profiles-daemon.service\n\
+                   [Service]\n\
+                   ExecStart=/bin/bash -c 'echo \"low-power\" > \"/sys/firmware/acpi/platform_profile\"'\n";
+        let mut state = ApplyState::default();
+        state.sysfs_changes.push(super::super::SysfsChange::new(
+            "/sys/firmware/acpi/platform_profile",
+            "balanced",
+            "low-power",
+        ));
+        let legacy = parse_legacy(old);
+        assert_eq!(legacy.after, vec!["power-profiles-daemon.service"]);
+
+        let unit = systemd::render_service(&plan_from_state(&state, &legacy));
+        assert!(unit.contains("\nAfter=power-profiles-daemon.service\n"));
+        assert!(unit.contains("systemd-run"), "still written again after it");
+    }
+}
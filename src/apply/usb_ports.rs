@@ -0,0 +1,242 @@
+//! Powering off empty USB ports through `<hub>-portN/disable` (aggressive
+//! presets only). A port is a candidate only if nothing was plugged into it,
+//! or into its other-speed peer, since boot: the kernel log is the only
+//! record of that, so without it nothing is disabled.
+//!
+//! A disabled port doesn't see a plug, so a udev rule re-enables it when
+//! anything is added on the same hub. The device that went into the dead
+//! port needs one replug after that; `bop revert` or a reboot brings every
+//! port back too.
+
+use super::PlanExplanation;
+use crate::detect::usb::{UsbPort, port_device_name};
+use std::collections::BTreeSet;
+
+/// Re-enables ports bop disabled when a device appears on their hub.
+pub const UDEV_RULE_PATH: &str = "/etc/udev/rules.d/86-bop-usb-ports.rules";
+
+/// Whether to power off a port, and why.
+#[derive(Debug, Clone)]
+pub struct PortDecision {
+    pub include: bool,
+    pub reason: &'static str,
+    pub factors: Vec<String>,
+}
+
+/// This boot's kernel log, from the journal. None when journalctl isn't
+/// available.
+pub fn read_kernel_log() -> Option<String> {
+    let output = crate::cmd::Cmd::new("journalctl")
+        .args(["-k", "-b", "-q", "--no-pager", "-o", "cat"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Devices the kernel enumerated or disconnected this boot, by name
+/// ("1-2", "3-1.4"), from lines like "usb 1-2: new high-speed USB device
+/// number 3 using xhci_hcd" and "usb 1-2: USB disconnect, device number 3".
+pub fn parse_connect_history(log: &str) -> BTreeSet<String> {
+    let mut seen = BTreeSet::new();
+    for line in log.lines() {
+        let Some(rest) = line.split("usb ").nth(1) else {
+            continue;
+        };
+        let Some((name, message)) = rest.split_once(": ") else {
+            continue;
+        };
+        if (message.starts_with("new ") || message.starts_with("USB disconnect"))
+            && name.contains('-')
+            && !name.contains(char::is_whitespace)
+        {
+            seen.insert(name.to_string());
+        }
+    }
+    seen
+}
+
+/// Ports the plan can consider at all: ones with a `disable` switch that
+/// are on.
+pub fn is_candidate(port: &UsbPort) -> bool {
+    port.disabled == Some(false)
+}
+
+/// Decide a candidate port against the other ports (for its peer) and the
+/// connect history, None when the kernel log couldn't be read.
+pub fn decide(
+    port: &UsbPort,
+    ports: &[UsbPort],
+    history: Option<&BTreeSet<String>>,
+) -> PortDecision {
+    let peer = port
+        .peer
+        .as_deref()
+        .and_then(|name| ports.iter().find(|p| p.name == name));
+    let mut factors = Vec::new();
+    if let Some(connect_type) = &port.connect_type {
+        factors.push(format!("connect_type: {}", connect_type));
+    }
+    if let Some(peer) = &port.peer {
+        factors.push(format!("peer: {}", peer));
+    }
+    let used_since_boot = |p: &UsbPort| {
+        history.is_some_and(|seen| p.device_name().is_some_and(|device| seen.contains(&device)))
+    };
+
+    let (include, reason) = if port.connected || peer.is_some_and(|p| p.connected) {
+        (false, "a device is plugged in")
+    } else if port.connect_type.as_deref() == Some("hardwired") {
+        (false, "hardwired to a built-in device")
+    } else if port.connect_type.as_deref() == Some("not used") {
+        (false, "not wired to a connector")
+    } else if history.is_none() {
+        (
+            false,
+            "no kernel log to tell whether it was used since boot",
+        )
+    } else if used_since_boot(port) || peer.is_some_and(used_since_boot) {
+        (false, "had a device since boot")
+    } else {
+        (true, "empty since boot")
+    };
+    PortDecision {
+        include,
+        reason,
+        factors,
+    }
+}
+
+/// The plan explanation for a port decision.
+pub fn explanation(port: &UsbPort, decision: PortDecision) -> PlanExplanation {
+    PlanExplanation {
+        subject: format!("USB port {}", port.name),
+        included: decision.include,
+        reason: decision.reason.to_string(),
+        factors: decision.factors,
+    }
+}
+
+/// Have udev pick up a written or removed rule.
+pub fn reload_udev_rules() {
+    let _ = crate::cmd::Cmd::new("udevadm")
+        .args(["control", "--reload-rules"])
+        .status();
+}
+
+/// Whether a sysfs path is a USB port's `disable` switch.
+pub fn is_port_disable_path(path: &str) -> bool {
+    path.strip_suffix("/disable")
+        .and_then(|dir| dir.rsplit('/').next())
+        .is_some_and(|port| port_device_name(port).is_some())
+}
+
+/// The hub device a port `disable` path belongs to: "usb1" for
+/// ".../1-0:1.0/usb1-port2/disable", "1-1" for ".../1-1:1.0/1-1-port3/disable".
+fn hub_of(path: &str) -> Option<String> {
+    let mut parts = path.rsplit('/');
+    let _disable = parts.next()?;
+    let port = parts.next()?;
+    let interface = parts.next()?;
+    UsbPort {
+        name: port.to_string(),
+        interface: interface.to_string(),
+        ..Default::default()
+    }
+    .hub()
+}
+
+/// udev rule turning each disabled port back on when a device is added
+/// anywhere on its hub. None when none of `paths` is a port switch.
+pub fn udev_rule<'a>(paths: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let mut rules = String::new();
+    for path in paths.into_iter().filter(|p| is_port_disable_path(p)) {
+        let Some(hub) = hub_of(path) else {
+            continue;
+        };
+        rules.push_str(&format!(
+            "ACTION==\"add\", SUBSYSTEM==\"usb\", ENV{{DEVTYPE}}==\"usb_device\", KERNELS==\"{}\", RUN+=\"/bin/sh -c 'echo 0 > {}'\"\n",
+            hub, path
+        ));
+    }
+    if rules.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "{}\n# Re-enables USB ports bop powered off. Removed by `bop revert`\n{}",
+        crate::managed::header(),
+        rules
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port(name: &str, interface: &str) -> UsbPort {
+        UsbPort {
+            name: name.to_string(),
+            interface: interface.to_string(),
+            disabled: Some(false),
+            connect_type: Some("hotplug".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_connect_history_from_kernel_log() {
+        let log = "\
+usb 1-2: new high-speed USB device number 3 using xhci_hcd
+usb 1-2: New USB device found, idVendor=0bda, idProduct=5634
+usb 3-1.4: USB disconnect, device number 5
+usb usb1-port3: over-current condition
+xhci_hcd 0000:c1:00.3: xHCI Host Controller
+";
+        let seen = parse_connect_history(log);
+        assert_eq!(
+            seen.into_iter().collect::<Vec<_>>(),
+            ["1-2".to_string(), "3-1.4".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_decide_needs_history_and_an_unused_peer() {
+        let mut usb2 = port("usb1-port2", "1-0:1.0");
+        usb2.peer = Some("usb2-port2".to_string());
+        let usb3 = port("usb2-port2", "2-0:1.0");
+        let ports = [usb2.clone(), usb3];
+
+        let empty = BTreeSet::new();
+        assert!(decide(&usb2, &ports, Some(&empty)).include);
+        assert!(!decide(&usb2, &ports, None).include);
+
+        // A USB 3 stick on the peer counts for both halves
+        let seen = BTreeSet::from(["2-2".to_string()]);
+        let decision = decide(&usb2, &ports, Some(&seen));
+        assert!(!decision.include);
+        assert_eq!(decision.reason, "had a device since boot");
+
+        let mut hardwired = port("usb1-port5", "1-0:1.0");
+        hardwired.connect_type = Some("hardwired".to_string());
+        assert!(!decide(&hardwired, &ports, Some(&empty)).include);
+    }
+
+    #[test]
+    fn test_udev_rule_targets_each_ports_hub() {
+        let rule = udev_rule([
+            "/sys/bus/usb/devices/1-0:1.0/usb1-port2/disable",
+            "/sys/bus/usb/devices/1-1:1.0/1-1-port3/disable",
+            "/sys/bus/usb/devices/1-2/power/control",
+        ])
+        .unwrap();
+        assert!(crate::managed::has_header(&rule));
+        assert!(rule.contains(
+            "KERNELS==\"usb1\", RUN+=\"/bin/sh -c 'echo 0 > /sys/bus/usb/devices/1-0:1.0/usb1-port2/disable'\""
+        ));
+        assert!(rule.contains("KERNELS==\"1-1\""));
+        assert!(!rule.contains("power/control"));
+        assert!(udev_rule(["/sys/bus/usb/devices/1-2/power/control"]).is_none());
+    }
+}
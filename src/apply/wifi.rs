@@ -0,0 +1,344 @@
+//! WiFi power save through whichever manager owns the interface.
+//! NetworkManager applies its own `wifi.powersave` on every connect, so an
+//! `iw` write behind its back only lasts until the next reconnect; bop
+//! hands it a conf.d drop-in instead. iwd turns power save on by itself
+//! unless its main.conf says otherwise. Only an unmanaged interface gets
+//! `iw` from the persistence unit.
+
+use crate::apply::PlanExplanation;
+use crate::cmd::Cmd;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+pub const NM_DROP_IN_PATH: &str = "/etc/NetworkManager/conf.d/bop-wifi-powersave.conf";
+pub const IWD_MAIN_CONF: &str = "/etc/iwd/main.conf";
+
+/// NetworkManager's device state for a device it leaves alone.
+const NM_STATE_UNMANAGED: u32 = 10;
+
+/// Who configures the WiFi interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WifiManager {
+    NetworkManager,
+    Iwd,
+    Unmanaged,
+}
+
+impl WifiManager {
+    /// NetworkManager if nmcli reports `iface` as a device it manages,
+    /// otherwise iwd if its service is running. With NetworkManager on the
+    /// iwd backend, NetworkManager is the one to configure.
+    pub fn detect(iface: &str) -> Self {
+        let nmcli = Cmd::new("nmcli")
+            .args(["-t", "-f", "GENERAL.STATE", "dev", "show", iface])
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).into_owned());
+        let iwd_active = Cmd::new("systemctl")
+            .args(["is-active", "--quiet", "iwd.service"])
+            .status()
+            .is_ok_and(|s| s.success());
+        Self::from_probes(nmcli.as_deref(), iwd_active)
+    }
+
+    /// `nmcli_state` is the output of `nmcli -t -f GENERAL.STATE dev show
+    /// <iface>`, `None` if nmcli is missing or failed (NetworkManager not
+    /// running, or no such device).
+    pub fn from_probes(nmcli_state: Option<&str>, iwd_active: bool) -> Self {
+        match nmcli_state.and_then(parse_nmcli_state) {
+            Some(state) if state != NM_STATE_UNMANAGED => WifiManager::NetworkManager,
+            _ if iwd_active => WifiManager::Iwd,
+            _ => WifiManager::Unmanaged,
+        }
+    }
+}
+
+impl std::fmt::Display for WifiManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WifiManager::NetworkManager => write!(f, "NetworkManager"),
+            WifiManager::Iwd => write!(f, "iwd"),
+            WifiManager::Unmanaged => write!(f, "unmanaged"),
+        }
+    }
+}
+
+/// The numeric state from `GENERAL.STATE:100 (connected)`.
+pub fn parse_nmcli_state(output: &str) -> Option<u32> {
+    let value = output
+        .lines()
+        .find_map(|l| l.strip_prefix("GENERAL.STATE:"))?;
+    value.split_whitespace().next()?.parse().ok()
+}
+
+/// Whether iwd's main.conf turns power save off for `driver`:
+/// `PowerSaveDisable=` under `[DriverQuirks]` lists drivers, with `*` and
+/// `?` globs.
+pub fn iwd_disables_power_save(main_conf: &str, driver: Option<&str>) -> bool {
+    let mut in_quirks = false;
+    for line in main_conf.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_quirks = line == "[DriverQuirks]";
+            continue;
+        }
+        if !in_quirks {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if key.trim() != "PowerSaveDisable" {
+            continue;
+        }
+        return value
+            .split(',')
+            .map(str::trim)
+            .any(|pattern| pattern == "*" || driver.is_some_and(|d| glob_match(pattern, d)));
+    }
+    false
+}
+
+fn glob_match(pattern: &str, name: &str) -> bool {
+    fn matches(p: &[u8], n: &[u8]) -> bool {
+        match (p.split_first(), n.split_first()) {
+            (None, None) => true,
+            (Some((b'*', rest)), _) => matches(rest, n) || (!n.is_empty() && matches(p, &n[1..])),
+            (Some((b'?', rest)), Some((_, n_rest))) => matches(rest, n_rest),
+            (Some((c, rest)), Some((d, n_rest))) if c == d => matches(rest, n_rest),
+            _ => false,
+        }
+    }
+    matches(pattern.as_bytes(), name.as_bytes())
+}
+
+/// How bop turns WiFi power save on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WifiPowerSave {
+    /// `iw dev <iface> set power_save on` from the persistence unit
+    Iw(String),
+    /// The [`NM_DROP_IN_PATH`] drop-in
+    NetworkManager,
+}
+
+/// `wifi.powersave = 3` (enable) for every connection without its own
+/// setting; a connection's explicit value still wins.
+pub fn nm_drop_in() -> String {
+    format!(
+        "{}\n# Removed by `bop revert`\n[connection]\nwifi.powersave = 3\n",
+        crate::managed::header()
+    )
+}
+
+/// What to do for `iface` owned by `manager`, and why. `iwd_conf` is iwd's
+/// main.conf, if there is one.
+pub fn plan(
+    iface: &str,
+    driver: Option<&str>,
+    manager: WifiManager,
+    iwd_conf: Option<&str>,
+) -> (Option<WifiPowerSave>, PlanExplanation) {
+    let subject = format!("WiFi power save ({})", iface);
+    let factors = vec![format!("managed by {}", manager)];
+    let (action, included, reason) = match manager {
+        WifiManager::NetworkManager => (
+            Some(WifiPowerSave::NetworkManager),
+            true,
+            format!("wifi.powersave = 3 in {}", NM_DROP_IN_PATH),
+        ),
+        WifiManager::Iwd if iwd_conf.is_some_and(|c| iwd_disables_power_save(c, driver)) => (
+            None,
+            false,
+            format!(
+                "{} disables it for this driver, kept (remove it from PowerSaveDisable to allow)",
+                IWD_MAIN_CONF
+            ),
+        ),
+        WifiManager::Iwd => (None, false, "iwd already keeps it on".to_string()),
+        WifiManager::Unmanaged => (
+            Some(WifiPowerSave::Iw(iface.to_string())),
+            true,
+            "iw from bop-powersave.service at boot".to_string(),
+        ),
+    };
+    (
+        action,
+        PlanExplanation {
+            subject,
+            included,
+            reason,
+            factors,
+        },
+    )
+}
+
+/// How long an `iw` call may take before it's given up on.
+const IW_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Power save set with iw for a `[[network.exceptions]]` entry, as recorded
+/// in the state so it can be put back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExceptionApplied {
+    pub iface: String,
+    pub ssid: String,
+    /// Whether power save was on before the exception
+    pub original: bool,
+}
+
+/// `iw dev <iface> set power_save on|off`; false if iw failed.
+pub fn set_power_save(iface: &str, on: bool) -> bool {
+    Cmd::new("iw")
+        .args([
+            "dev",
+            iface,
+            "set",
+            "power_save",
+            if on { "on" } else { "off" },
+        ])
+        .timeout(IW_TIMEOUT)
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Whether power save is on for `iface`, from `iw dev <iface> get
+/// power_save`. None when iw is missing or fails.
+pub fn power_save(iface: &str) -> Option<bool> {
+    let output = Cmd::new("iw")
+        .args(["dev", iface, "get", "power_save"])
+        .timeout(IW_TIMEOUT)
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    parse_iw_power_save(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// "Power save: on" -> Some(true).
+pub fn parse_iw_power_save(output: &str) -> Option<bool> {
+    match output.trim().strip_prefix("Power save:")?.trim() {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Ask NetworkManager to reread its config. Best effort: the drop-in is
+/// picked up on the next restart anyway.
+pub fn reload_network_manager() {
+    let _ = Cmd::new("nmcli")
+        .args(["general", "reload", "conf"])
+        .status();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_iw_power_save() {
+        assert_eq!(parse_iw_power_save("Power save: on\n"), Some(true));
+        assert_eq!(parse_iw_power_save("Power save: off\n"), Some(false));
+        assert_eq!(
+            parse_iw_power_save("command failed: No such device (-19)\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_nmcli_state() {
+        assert_eq!(
+            parse_nmcli_state("GENERAL.STATE:100 (connected)\n"),
+            Some(100)
+        );
+        assert_eq!(
+            parse_nmcli_state("GENERAL.STATE:10 (unmanaged)\n"),
+            Some(10)
+        );
+        assert_eq!(parse_nmcli_state(""), None);
+        assert_eq!(
+            parse_nmcli_state("Error: Device 'wlan0' not found.\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_manager_from_probes() {
+        let connected = "GENERAL.STATE:100 (connected)\n";
+        let disconnected = "GENERAL.STATE:30 (disconnected)\n";
+        let unmanaged = "GENERAL.STATE:10 (unmanaged)\n";
+
+        assert_eq!(
+            WifiManager::from_probes(Some(connected), false),
+            WifiManager::NetworkManager
+        );
+        // NetworkManager on the iwd backend: NetworkManager decides
+        assert_eq!(
+            WifiManager::from_probes(Some(disconnected), true),
+            WifiManager::NetworkManager
+        );
+        assert_eq!(
+            WifiManager::from_probes(Some(unmanaged), true),
+            WifiManager::Iwd
+        );
+        assert_eq!(WifiManager::from_probes(None, true), WifiManager::Iwd);
+        assert_eq!(
+            WifiManager::from_probes(Some(unmanaged), false),
+            WifiManager::Unmanaged
+        );
+        assert_eq!(
+            WifiManager::from_probes(None, false),
+            WifiManager::Unmanaged
+        );
+    }
+
+    #[test]
+    fn test_iwd_power_save_disable() {
+        let conf = "\
+[General]
+EnableNetworkConfiguration=true
+
+[DriverQuirks]
+PowerSaveDisable=rtw88_*, mt7921e
+";
+        assert!(iwd_disables_power_save(conf, Some("mt7921e")));
+        assert!(iwd_disables_power_save(conf, Some("rtw88_8822ce")));
+        assert!(!iwd_disables_power_save(conf, Some("iwlwifi")));
+        assert!(!iwd_disables_power_save(conf, None));
+
+        let all = "[DriverQuirks]\nPowerSaveDisable=*\n";
+        assert!(iwd_disables_power_save(all, None));
+
+        // Same key outside its section doesn't count
+        let misplaced = "[General]\nPowerSaveDisable=*\n";
+        assert!(!iwd_disables_power_save(misplaced, Some("iwlwifi")));
+        assert!(!iwd_disables_power_save("", Some("iwlwifi")));
+    }
+
+    #[test]
+    fn test_plan_follows_manager() {
+        let (action, why) = plan("wlan0", None, WifiManager::NetworkManager, None);
+        assert_eq!(action, Some(WifiPowerSave::NetworkManager));
+        assert!(why.included);
+        assert_eq!(why.factors, vec!["managed by NetworkManager"]);
+
+        let (action, _) = plan("wlan0", None, WifiManager::Unmanaged, None);
+        assert_eq!(action, Some(WifiPowerSave::Iw("wlan0".to_string())));
+
+        let (action, why) = plan("wlan0", Some("iwlwifi"), WifiManager::Iwd, None);
+        assert_eq!(action, None);
+        assert_eq!(why.reason, "iwd already keeps it on");
+
+        let conf = "[DriverQuirks]\nPowerSaveDisable=iwlwifi\n";
+        let (action, why) = plan("wlan0", Some("iwlwifi"), WifiManager::Iwd, Some(conf));
+        assert_eq!(action, None);
+        assert!(!why.included);
+        assert!(why.reason.contains("PowerSaveDisable"));
+    }
+
+    #[test]
+    fn test_nm_drop_in_is_managed_and_enables_power_save() {
+        let content = nm_drop_in();
+        assert!(crate::managed::has_header(&content));
+        assert!(content.contains("[connection]\nwifi.powersave = 3\n"));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::audit::{Finding, Severity};
+use crate::audit::{Finding, PowerContext, Severity};
 use crate::sysfs::SysfsRoot;
 
 pub fn check(sysfs: &SysfsRoot) -> Vec<Finding> {
@@ -7,14 +7,17 @@ pub fn check(sysfs: &SysfsRoot) -> Vec<Finding> {
     // Check HDA Intel power save
     let power_save_path = "sys/module/snd_hda_intel/parameters/power_save";
     if let Some(val) = sysfs.read_optional(power_save_path).unwrap_or(None) {
+        let raw = sysfs.raw_values();
         match val.as_str() {
             "0" => {
                 findings.push(
                     Finding::new(Severity::Low, "Audio", "HDA Intel power save disabled")
+                        .applies_on(PowerContext::Battery)
                         .current("0 (disabled)")
                         .recommended("1 (1 second timeout)")
                         .impact("~0.1-0.3W savings when audio idle")
                         .path(power_save_path)
+                        .evidence_from(&raw, power_save_path)
                         .weight(2),
                 );
             }
@@ -29,10 +32,12 @@ pub fn check(sysfs: &SysfsRoot) -> Vec<Finding> {
                         "Audio",
                         format!("HDA power_save set to {} (non-standard)", val),
                     )
+                    .applies_on(PowerContext::Battery)
                     .current(&val)
                     .recommended("1")
                     .impact("Standard value is 1 second")
                     .path(power_save_path)
+                    .evidence_from(&raw, power_save_path)
                     .weight(1),
                 );
             }
@@ -46,10 +51,12 @@ pub fn check(sysfs: &SysfsRoot) -> Vec<Finding> {
     {
         findings.push(
             Finding::new(Severity::Low, "Audio", "HDA controller power save disabled")
+                .applies_on(PowerContext::Battery)
                 .current("N (disabled)")
                 .recommended("Y (enabled)")
                 .impact("Controller stays powered when idle")
                 .path(controller_path)
+                .evidence_from(&sysfs.raw_values(), controller_path)
                 .weight(2),
         );
     }
@@ -0,0 +1,60 @@
+use crate::audit::{Finding, Severity};
+use crate::conflicts::{self, ConflictKind};
+use crate::sysfs::SysfsRoot;
+
+/// Rules, units and rc.local lines outside bop that write what bop manages.
+/// Reads /etc relative to the sysfs root, so a fixture tree without one
+/// yields nothing.
+pub fn check(sysfs: &SysfsRoot) -> Vec<Finding> {
+    conflicts::scan(&sysfs.path(""))
+        .into_iter()
+        .map(|c| {
+            let what = match c.kind {
+                ConflictKind::UdevRule => "udev rule",
+                ConflictKind::SystemdUnit => "systemd unit",
+                ConflictKind::RcLocal => "rc.local",
+            };
+            let file = c.file.display().to_string();
+            Finding::new(
+                Severity::Medium,
+                "Conflicts",
+                format!("{} {}:{} also sets {}", what, file, c.line, c.target),
+            )
+            .current(&c.text)
+            .recommended("remove it, or run `sudo bop doctor --disarm`")
+            .impact("Undoes bop's setting on hotplug or boot")
+            .path(&file)
+            .key(c.line.to_string())
+            .evidence(format!("{}:{}", file, c.line), c.text)
+            .manual_only()
+            .weight(6)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_conflict_findings_name_file_and_line() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("etc/udev/rules.d");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("99-tlp-leftover.rules"),
+            "ACTION==\"add\", SUBSYSTEM==\"usb\", ATTR{power/autosuspend}=\"2\"\n",
+        )
+        .unwrap();
+
+        let findings = check(&SysfsRoot::new(tmp.path()));
+        assert_eq!(findings.len(), 1);
+        assert!(
+            findings[0]
+                .description
+                .ends_with("99-tlp-leftover.rules:1 also sets USB autosuspend")
+        );
+        assert!(findings[0].id().ends_with("99-tlp-leftover.rules#1"));
+    }
+}
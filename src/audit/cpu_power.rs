@@ -1,5 +1,7 @@
-use crate::audit::{Finding, Severity};
+use crate::audit::{Finding, PowerContext, Severity};
 use crate::detect::HardwareInfo;
+use crate::detect::cpu::{PolicyGroup, format_cpu_list};
+use crate::detect::platform::CUSTOM_PROFILE;
 use crate::preset::{PlatformProfilePolicy, Preset, PresetKnobs};
 
 pub fn check(hw: &HardwareInfo) -> Vec<Finding> {
@@ -25,6 +27,25 @@ fn epp_rank(epp: &str) -> u8 {
     }
 }
 
+/// Map an EPP target onto the equivalent energy_perf_bias value, using the
+/// same buckets the kernel uses when translating EPB strings.
+pub fn epb_for_epp(epp: &str) -> u8 {
+    match epp {
+        "performance" => 0,
+        "balance_performance" => 6,
+        "balance_power" => 8,
+        "power" => 15,
+        _ => 6,
+    }
+}
+
+/// Whether EPP `current` gets rewritten to `target`. "power" is already the
+/// most power-saving value and is left alone unless EPP is locked. Shared
+/// with build_plan so audit and apply agree.
+pub fn epp_needs_change(current: &str, target: &str, locked: bool) -> bool {
+    current != target && (locked || current != "power")
+}
+
 pub fn check_with_knobs(hw: &HardwareInfo, knobs: &PresetKnobs) -> Vec<Finding> {
     let force_low_power = knobs.platform_profile == PlatformProfilePolicy::ForceLowPower;
     let mut findings = Vec::new();
@@ -38,10 +59,15 @@ pub fn check_with_knobs(hw: &HardwareInfo, knobs: &PresetKnobs) -> Vec<Finding>
                 "CPU",
                 format!("Using '{}' instead of amd-pstate - EPP unavailable", driver),
             )
+            .applies_on(PowerContext::Battery)
             .current(driver)
             .recommended("amd-pstate-epp")
             .impact("~2-5W savings; enables fine-grained energy/performance tuning")
-            .path("cpu0/cpufreq/scaling_driver")
+            .path("/sys/devices/system/cpu/cpu0/cpufreq/scaling_driver")
+            .evidence_from(
+                &hw.raw,
+                "sys/devices/system/cpu/cpu0/cpufreq/scaling_driver",
+            )
             .weight(9),
         );
     }
@@ -58,60 +84,34 @@ pub fn check_with_knobs(hw: &HardwareInfo, knobs: &PresetKnobs) -> Vec<Finding>
                 "CPU",
                 "amd-pstate in active mode — guided or passive may improve idle power",
             )
+            .applies_on(PowerContext::Battery)
             .current("active")
             .recommended("Experiment with guided mode (kernel param amd_pstate=guided)")
             .impact("Potentially 1-2W better idle power (varies by workload)")
-            .path("sys/devices/system/cpu/amd_pstate/status")
+            .path("/sys/devices/system/cpu/amd_pstate/status")
+            .evidence_from(&hw.raw, "sys/devices/system/cpu/amd_pstate/status")
             .weight(0),
         );
     }
 
-    // Check EPP — only when the knob would change it
-    if let Some(ref target_epp) = knobs.epp
-        && let Some(ref epp) = hw.cpu.epp
-    {
-        let target: &str = target_epp;
-        let current_rank = epp_rank(epp);
-        let target_rank = epp_rank(target);
-
-        // Flag EPP drift — use string comparison to match build_plan's logic.
-        // Skip when current is "power" since build_plan won't overwrite it
-        // (power is already the most power-saving value), unless EPP is locked.
-        if epp.as_str() != target && (knobs.epp_locked || epp != "power") {
-            let (severity, weight, impact) = if current_rank < target_rank {
-                // Moving to more power-saving
-                match epp.as_str() {
-                    "performance" => (Severity::High, 8, "~2-3W savings"),
-                    "balance_performance" => (Severity::Medium, 6, "~1-3W savings"),
-                    _ => (Severity::Low, 3, "~0.5-1W savings"),
-                }
-            } else {
-                // Moving to more performant (e.g. adaptive high-battery)
-                (
-                    Severity::Info,
-                    1,
-                    "EPP will be adjusted for current conditions",
-                )
-            };
-            findings.push(
-                Finding::new(
-                    severity,
-                    "CPU",
-                    format!("EPP at '{}' — target is '{}'", epp, target),
-                )
-                .current(epp.as_str())
-                .recommended(target)
-                .impact(impact)
-                .path("cpu*/cpufreq/energy_performance_preference")
-                .weight(weight),
-            );
+    // Check EPP — only when the knob would change it; one finding per
+    // group of policies sharing a value (P- and E-cores can differ)
+    if let Some(ref target_epp) = knobs.epp {
+        for group in hw.cpu.epp_groups() {
+            check_epp(hw, &group, target_epp, knobs.epp_locked, &mut findings);
         }
     }
 
     // Check platform profile — only when the knob would change it
+    let profile_target = hw.platform.platform_profile_target();
     if knobs.platform_profile != PlatformProfilePolicy::NoChange
-        && let Some(ref profile) = hw.platform.platform_profile
+        && let Some(ref profile) = profile_target.current
     {
+        // Name the handler so a value that snaps back is explicable
+        let current = hw
+            .platform
+            .platform_profile_summary()
+            .unwrap_or_else(|| profile.clone());
         match profile.as_str() {
             "performance" => {
                 findings.push(
@@ -120,10 +120,12 @@ pub fn check_with_knobs(hw: &HardwareInfo, knobs: &PresetKnobs) -> Vec<Finding>
                         "CPU",
                         "Platform profile set to performance (TDP: 45W)",
                     )
-                    .current("performance")
+                    .applies_on(PowerContext::Battery)
+                    .current(&current)
                     .recommended("low-power")
                     .impact("~1-2W savings at idle, lower TDP cap")
-                    .path("/sys/firmware/acpi/platform_profile")
+                    .path(&profile_target.path)
+                    .evidence_from(&hw.raw, &profile_target.path)
                     .weight(7),
                 );
             }
@@ -134,11 +136,12 @@ pub fn check_with_knobs(hw: &HardwareInfo, knobs: &PresetKnobs) -> Vec<Finding>
                             Severity::Low,
                             "CPU",
                             "Platform profile at balanced — low-power reduces TDP for battery savings",
-                        )
-                        .current("balanced")
+                        ).applies_on(PowerContext::Battery)
+                        .current(&current)
                         .recommended("low-power")
                         .impact("~0.5-1W savings with lower TDP cap (reduced sustained performance)")
-                        .path("/sys/firmware/acpi/platform_profile")
+                        .path(&profile_target.path)
+                        .evidence_from(&hw.raw, &profile_target.path)
                         .weight(3),
                     );
                 } else {
@@ -147,11 +150,12 @@ pub fn check_with_knobs(hw: &HardwareInfo, knobs: &PresetKnobs) -> Vec<Finding>
                             Severity::Info,
                             "CPU",
                             "Platform profile at balanced — low-power saves ~0.5-1W but throttles more",
-                        )
-                        .current("balanced")
+                        ).applies_on(PowerContext::Battery)
+                        .current(&current)
                         .recommended("low-power (trades sustained performance for battery)")
                         .impact("~0.5-1W savings with lower TDP cap")
-                        .path("/sys/firmware/acpi/platform_profile")
+                        .path(&profile_target.path)
+                        .evidence_from(&hw.raw, &profile_target.path)
                         .weight(0),
                     );
                 }
@@ -159,6 +163,25 @@ pub fn check_with_knobs(hw: &HardwareInfo, knobs: &PresetKnobs) -> Vec<Finding>
             "low-power" => {
                 // Optimal
             }
+            CUSTOM_PROFILE => {
+                let description = if knobs.platform_profile_locked {
+                    "Platform profile is custom — overrides.platform_profile replaces it with low-power"
+                } else {
+                    "Platform profile is custom — vendor per-knob tuning is active, left as is"
+                };
+                findings.push(
+                    Finding::new(Severity::Info, "CPU", description)
+                        .applies_on(PowerContext::Battery)
+                        .current(&current)
+                        .recommended(
+                            "low-power (set overrides.platform_profile = true to replace custom)",
+                        )
+                        .impact("Depends on the vendor tuning behind it")
+                        .path(&profile_target.path)
+                        .evidence_from(&hw.raw, &profile_target.path)
+                        .weight(0),
+                );
+            }
             _ => {}
         }
     }
@@ -175,45 +198,227 @@ pub fn check_with_knobs(hw: &HardwareInfo, knobs: &PresetKnobs) -> Vec<Finding>
                 "CPU",
                 format!("Governor '{}' suboptimal with amd-pstate", governor),
             )
+            .applies_on(PowerContext::Battery)
             .current(governor)
             .recommended("powersave")
             .impact("amd-pstate uses EPP for power/perf balance; powersave governor is correct")
-            .path("cpu*/cpufreq/scaling_governor")
+            .path("/sys/devices/system/cpu/cpu*/cpufreq/scaling_governor")
+            .evidence_from(
+                &hw.raw,
+                "sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+            )
             .weight(4),
         );
     }
 
-    // Flag turbo when knobs would change it
-    if let Some(desired) = knobs.turbo_boost
-        && hw.cpu.has_boost
-        && hw.cpu.boost_enabled != desired
+    // acpi-cpufreq has no EPP; the governor is the main lever
+    if knobs.epp.is_some()
+        && hw.cpu.is_acpi_cpufreq()
+        && let Some(ref governor) = hw.cpu.governor
     {
-        let (message, current, recommended, impact, weight) = if desired {
-            (
-                "Turbo boost disabled — knobs request re-enabling it",
-                "disabled",
-                "enabled",
-                "Restores peak single-thread performance",
-                2,
-            )
-        } else {
-            (
-                "Turbo boost enabled — disabling saves power under bursty loads",
-                "enabled",
-                "disabled (significant single-thread performance loss)",
-                "~2-5W savings under load at cost of peak performance",
-                4,
-            )
-        };
-        findings.push(
-            Finding::new(Severity::Low, "CPU", message)
+        match governor.as_str() {
+            "performance" => findings.push(
+                Finding::new(
+                    Severity::Medium,
+                    "CPU",
+                    "Governor 'performance' pins acpi-cpufreq at max frequency",
+                )
+                .applies_on(PowerContext::Battery)
+                .current(governor)
+                .recommended("schedutil")
+                .impact("~1-3W savings at idle")
+                .path("/sys/devices/system/cpu/cpu*/cpufreq/scaling_governor")
+                .evidence_from(
+                    &hw.raw,
+                    "sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+                )
+                .weight(5),
+            ),
+            "ondemand" => findings.push(
+                Finding::new(
+                    Severity::Low,
+                    "CPU",
+                    "Governor 'ondemand' ramps up eagerly - schedutil tracks load better",
+                )
+                .applies_on(PowerContext::Battery)
+                .current(governor)
+                .recommended("schedutil")
+                .impact("~0.2-0.5W savings under light load")
+                .path("/sys/devices/system/cpu/cpu*/cpufreq/scaling_governor")
+                .evidence_from(
+                    &hw.raw,
+                    "sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+                )
+                .weight(2),
+            ),
+            _ => {}
+        }
+    }
+
+    // Intel energy_perf_bias — only when the driver has no EPP. Judged on
+    // every CPU, as apply writes every CPU; the lowest sets the severity
+    if let Some(ref target_epp) = knobs.epp
+        && hw.cpu.uses_epb()
+        && let Some(&(_, epb)) = hw.cpu.energy_perf_bias.iter().min_by_key(|&&(_, epb)| epb)
+    {
+        let target = epb_for_epp(target_epp);
+        if epb < target {
+            let (severity, weight) = if epb < 4 {
+                (Severity::Medium, 6)
+            } else {
+                (Severity::Low, 3)
+            };
+            let lowest: Vec<u32> = hw
+                .cpu
+                .energy_perf_bias
+                .iter()
+                .filter(|&&(_, v)| v == epb)
+                .map(|&(cpu, _)| cpu)
+                .collect();
+            let current = if lowest.len() == hw.cpu.energy_perf_bias.len() {
+                epb.to_string()
+            } else {
+                format!("{} on CPUs {}", epb, format_cpu_list(&lowest))
+            };
+            findings.push(
+                Finding::new(
+                    severity,
+                    "CPU",
+                    format!(
+                        "Energy/performance bias at {} (0=performance, 15=powersave)",
+                        epb
+                    ),
+                )
+                .applies_on(PowerContext::Battery)
                 .current(current)
-                .recommended(recommended)
-                .impact(impact)
-                .path("sys/devices/system/cpu/cpufreq/boost")
+                .recommended(format!("{} (6-8 on battery)", target))
+                .impact("~0.5-2W savings; biases turbo and C-state decisions toward efficiency")
+                .path("/sys/devices/system/cpu/cpu*/power/energy_perf_bias")
+                .evidence_from(
+                    &hw.raw,
+                    &format!(
+                        "sys/devices/system/cpu/cpu{}/power/energy_perf_bias",
+                        lowest[0]
+                    ),
+                )
                 .weight(weight),
-        );
+            );
+        }
+    }
+
+    // Flag turbo when knobs would change it, per group of policies with
+    // their own switch
+    if let Some(desired) = knobs.turbo_boost {
+        for group in hw.cpu.boost_groups() {
+            check_boost(hw, &group, desired, &mut findings);
+        }
     }
 
     findings
 }
+
+/// Turbo finding for one group of policies, or for the global switch when
+/// the group has none. Named and keyed like [`check_epp`]'s.
+fn check_boost(
+    hw: &HardwareInfo,
+    group: &PolicyGroup<'_, bool>,
+    desired: bool,
+    findings: &mut Vec<Finding>,
+) {
+    if group.value == desired {
+        return;
+    }
+    let (message, current, recommended, impact, weight) = if desired {
+        (
+            "Turbo boost disabled — knobs request re-enabling it",
+            "disabled",
+            "enabled",
+            "Restores peak single-thread performance",
+            2,
+        )
+    } else {
+        (
+            "Turbo boost enabled — disabling saves power under bursty loads",
+            "enabled",
+            "disabled (significant single-thread performance loss)",
+            "~2-5W savings under load at cost of peak performance",
+            4,
+        )
+    };
+    let description = match &group.label {
+        Some(label) => format!("{} on {}", message, label),
+        None => message.to_string(),
+    };
+    let (path, evidence) = match group.policies.first() {
+        Some(policy) => (
+            "/sys/devices/system/cpu/cpu*/cpufreq/boost",
+            policy.path("boost"),
+        ),
+        None => (
+            "/sys/devices/system/cpu/cpufreq/boost",
+            "sys/devices/system/cpu/cpufreq/boost".to_string(),
+        ),
+    };
+    let mut finding = Finding::new(Severity::Low, "CPU", description)
+        .applies_on(PowerContext::Battery)
+        .current(current)
+        .recommended(recommended)
+        .impact(impact)
+        .path(path)
+        .evidence_from(&hw.raw, &evidence)
+        .weight(weight);
+    if let Some(label) = &group.label {
+        finding = finding.key(label);
+    }
+    findings.push(finding);
+}
+
+/// EPP finding for one group of policies. A group that is one of several is
+/// named in the description and keyed by its label, so each keeps its own id.
+fn check_epp(
+    hw: &HardwareInfo,
+    group: &PolicyGroup<'_, String>,
+    target: &str,
+    locked: bool,
+    findings: &mut Vec<Finding>,
+) {
+    let epp = group.value.as_str();
+    if !epp_needs_change(epp, target, locked) {
+        return;
+    }
+    let (severity, weight, impact) = if epp_rank(epp) < epp_rank(target) {
+        // Moving to more power-saving
+        match epp {
+            "performance" => (Severity::High, 8, "~2-3W savings"),
+            "balance_performance" => (Severity::Medium, 6, "~1-3W savings"),
+            _ => (Severity::Low, 3, "~0.5-1W savings"),
+        }
+    } else {
+        // Moving to more performant (e.g. adaptive high-battery)
+        (
+            Severity::Info,
+            1,
+            "EPP will be adjusted for current conditions",
+        )
+    };
+    let description = match &group.label {
+        Some(label) => format!("EPP at '{}' on {} — target is '{}'", epp, label, target),
+        None => format!("EPP at '{}' — target is '{}'", epp, target),
+    };
+    let evidence = match group.policies.first() {
+        Some(policy) => policy.path("energy_performance_preference"),
+        None => "sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference".to_string(),
+    };
+    let mut finding = Finding::new(severity, "CPU", description)
+        .applies_on(PowerContext::Battery)
+        .current(epp)
+        .recommended(target)
+        .impact(impact)
+        .path("/sys/devices/system/cpu/cpu*/cpufreq/energy_performance_preference")
+        .evidence_from(&hw.raw, &evidence)
+        .weight(weight);
+    if let Some(label) = &group.label {
+        finding = finding.key(label);
+    }
+    findings.push(finding);
+}
@@ -1,7 +1,10 @@
-use crate::audit::{Finding, Severity};
+use crate::audit::{Finding, PowerContext, Severity};
 use crate::detect::HardwareInfo;
 use crate::sysfs::SysfsRoot;
 
+/// AMD display core debug mask; bit 0x10 disables PSR.
+pub const PSR_DEBUG_MASK_PARAM: &str = "amdgpu.dcdebugmask";
+
 pub fn check(hw: &HardwareInfo, sysfs: &SysfsRoot) -> Vec<Finding> {
     let mut findings = Vec::new();
 
@@ -30,10 +33,13 @@ pub fn check(hw: &HardwareInfo, sysfs: &SysfsRoot) -> Vec<Finding> {
                             "Display",
                             format!("Backlight at {}% - reducing saves significant power", pct),
                         )
+                        .applies_on(PowerContext::Battery)
                         .current(format!("{}%", pct))
                         .recommended("30-50% for indoor use")
                         .impact("Display is often the largest power consumer")
                         .path(format!("{}/brightness", base))
+                        .evidence_from(&sysfs.raw_values(), &format!("{}/brightness", base))
+                        .evidence_from(&sysfs.raw_values(), &format!("{}/max_brightness", base))
                         .weight(0), // Info only
                     );
                 }
@@ -58,7 +64,9 @@ pub fn check(hw: &HardwareInfo, sysfs: &SysfsRoot) -> Vec<Finding> {
                         "Display",
                         "Consider reducing display refresh rate to 60Hz on battery",
                     )
+                    .applies_on(PowerContext::Battery)
                     .impact("~1W savings (measured on Framework 16 with 165Hz panel)")
+                    .evidence_from(&sysfs.raw_values(), &status_path)
                     .path(status_path)
                     .weight(0),
                 );
@@ -67,20 +75,46 @@ pub fn check(hw: &HardwareInfo, sysfs: &SysfsRoot) -> Vec<Finding> {
         }
     }
 
-    // Check if PSR (Panel Self-Refresh) is disabled via amdgpu.dcdebugmask
-    if hw.gpu.is_amd() && hw.has_kernel_param("amdgpu.dcdebugmask") {
+    // OLED panels get no benefit from ABM; power scales with what's on screen
+    if let Some(panel) = &hw.display.panel
+        && hw.display.is_oled()
+    {
+        findings.push(
+            Finding::new(
+                Severity::Info,
+                "Display",
+                "OLED panel - ABM does not apply, brightness and content drive power",
+            )
+            .applies_on(PowerContext::Battery)
+            .current(panel.model.as_deref().unwrap_or(&panel.manufacturer))
+            .recommended("Lower brightness and prefer dark themes on battery")
+            .impact("OLED power scales with lit pixels and their brightness")
+            .path(format!("/sys/class/drm/{}/edid", panel.connector))
+            .weight(0),
+        );
+    }
+
+    // PSR (Panel Self-Refresh) disabled via amdgpu.dcdebugmask. Users add it
+    // to work around flicker, so this is acknowledged rather than fought.
+    if hw.gpu.is_amd() && hw.has_kernel_param(PSR_DEBUG_MASK_PARAM) {
         let mask_value = hw
-            .kernel_param_value("amdgpu.dcdebugmask")
+            .kernel_param_value(PSR_DEBUG_MASK_PARAM)
             .unwrap_or_default();
         findings.push(
             Finding::new(
                 Severity::Info,
                 "Display",
-                "Panel Self-Refresh may be disabled (amdgpu.dcdebugmask set)",
-            )
+                "PSR disabled by user kernel parameter",
+            ).applies_on(PowerContext::Battery)
             .current(&mask_value)
-            .recommended("Remove amdgpu.dcdebugmask once PSR bugs are fixed")
+            .recommended(format!(
+                "Remove {}={} to re-enable, known flicker trade-off ([display] force_psr = true lets bop remove it)",
+                PSR_DEBUG_MASK_PARAM, mask_value
+            ))
             .impact("~0.5-1.5W potential savings when PSR works correctly")
+            .path("/proc/cmdline")
+            .evidence_from(&hw.raw, "proc/cmdline")
+            .key(PSR_DEBUG_MASK_PARAM)
             .weight(0),
         );
     }
@@ -1,5 +1,6 @@
-use crate::audit::{Finding, Severity};
-use crate::detect::HardwareInfo;
+use crate::audit::{Finding, PowerContext, Severity};
+use crate::detect::{HardwareInfo, display};
+use crate::preset::PresetKnobs;
 
 pub fn check(hw: &HardwareInfo) -> Vec<Finding> {
     let mut findings = Vec::new();
@@ -12,16 +13,22 @@ pub fn check(hw: &HardwareInfo) -> Vec<Finding> {
     if let Some(ref dpm) = hw.gpu.dpm_level
         && dpm != "auto"
     {
+        let level_path = format!(
+            "{}/power_dpm_force_performance_level",
+            hw.gpu.card_path.as_deref().unwrap_or_default()
+        );
         findings.push(
             Finding::new(
                 Severity::Medium,
                 "GPU",
                 format!("GPU DPM level '{}' instead of auto", dpm),
             )
+            .applies_on(PowerContext::Battery)
             .current(dpm)
             .recommended("auto")
             .impact("GPU may not enter low-power states")
-            .path("power_dpm_force_performance_level")
+            .path(format!("/{}", level_path))
+            .evidence_from(&hw.raw, &level_path)
             .weight(5),
         );
     }
@@ -30,19 +37,128 @@ pub fn check(hw: &HardwareInfo) -> Vec<Finding> {
     if let Some(ref power_state) = hw.gpu.dgpu_power_state
         && power_state != "D3cold"
     {
+        let state_path = format!(
+            "{}/power_state",
+            hw.gpu.dgpu_card_path.as_deref().unwrap_or_default()
+        );
         findings.push(
             Finding::new(
                 Severity::Medium,
                 "GPU",
                 format!("Discrete GPU in {} instead of D3cold", power_state),
             )
+            .applies_on(PowerContext::Battery)
             .current(power_state)
             .recommended("D3cold")
             .impact("~5-8W savings when dGPU is idle")
-            .path("power_state")
+            .path(format!("/{}", state_path))
+            .evidence_from(&hw.raw, &state_path)
             .weight(7),
         );
     }
 
     findings
 }
+
+/// Memory clock held at its top level while the iGPU drives several
+/// displays. amdgpu can't switch memory clocks within the vertical blank
+/// of more than one display, so it pins them high; idle draw goes up by
+/// several watts and no setting brings it down. Reported so a good score
+/// with short battery life has an explanation.
+pub fn check_multi_display_mclk(hw: &HardwareInfo) -> Vec<Finding> {
+    if !hw.gpu.is_amd() || !hw.ac.is_on_battery() {
+        return Vec::new();
+    }
+    let (Some(card), Some(pinned)) = (hw.gpu.card_name(), hw.gpu.mclk_pinned_at_max()) else {
+        return Vec::new();
+    };
+    let displays: Vec<&str> = hw.display.active_on(card).collect();
+    let external = displays.iter().filter(|c| !display::is_internal(c)).count();
+    if displays.len() < 2 || external == 0 {
+        return Vec::new();
+    }
+
+    let path = format!(
+        "{}/pp_dpm_mclk",
+        hw.gpu.card_path.as_deref().unwrap_or_default()
+    );
+    vec![
+        Finding::new(
+            Severity::Info,
+            "GPU",
+            format!(
+                "iGPU memory clock pinned at {} with {} displays ({} external)",
+                pinned.clock,
+                displays.len(),
+                external
+            ),
+        )
+        .applies_on(PowerContext::Battery)
+        .current(format!(
+            "level {} of {} ({})",
+            pinned.index,
+            hw.gpu.mclk_levels.len() - 1,
+            displays.join(", ")
+        ))
+        .recommended("Nothing to set: amdgpu keeps memory clocks up with several displays; unplug one to let them drop")
+        .impact("~5W more at idle while the displays stay connected")
+        .path(format!("/{}", path))
+        .evidence_from(&hw.raw, &path)
+        .manual_only()
+        .weight(0),
+    ]
+}
+
+/// An idle discrete GPU whose PCIe link stays trained above 2.5 GT/s. Some
+/// Framework 16 firmware leaves the dGPU at Gen4 x8 while it sits idle.
+pub fn check_link(hw: &HardwareInfo, knobs: &PresetKnobs) -> Vec<Finding> {
+    let Some(gpu) = hw.pci.discrete_gpu() else {
+        return Vec::new();
+    };
+    if !gpu.holds_idle_link_above_minimum() {
+        return Vec::new();
+    }
+    let current = gpu.link.current_summary().unwrap_or_default();
+    let current = match gpu.link.max_summary() {
+        Some(max) => format!("{} (max {})", current, max),
+        None => current,
+    };
+    let description = format!("Idle discrete GPU link held at {}", current);
+    let impact = "~0.5W savings while the dGPU idles";
+
+    let finding = match hw.pci.link_speed_control(gpu) {
+        // Already pinned at the lowest speed; the link reports stale data
+        Some(control) if control.cur_state.is_some() && control.cur_state == control.max_state => {
+            return Vec::new();
+        }
+        Some(control) => {
+            let recommended = if knobs.pcie_link_downgrade {
+                "2.5 GT/s (Gen1)"
+            } else {
+                "2.5 GT/s (Gen1, supersaver preset)"
+            };
+            Finding::new(Severity::Info, "GPU", description).applies_on(PowerContext::Battery)
+                .current(current)
+                .recommended(recommended)
+                .impact(impact)
+                .path(format!("/{}/cur_state", control.path))
+                .evidence_from(&hw.raw, &format!("{}/cur_state", control.path))
+        }
+        None => Finding::new(Severity::Info, "GPU", description).applies_on(PowerContext::Battery)
+            .current(current)
+            .recommended(
+                "Let the dGPU reach D3cold (pcie_port_pm=force), or a kernel with PCIe bandwidth control (CONFIG_PCIE_THERMAL)",
+            )
+            .impact(impact)
+            .path(format!(
+                "/sys/bus/pci/devices/{}/current_link_speed",
+                gpu.address
+            ))
+            .evidence_from(
+                &hw.raw,
+                &format!("sys/bus/pci/devices/{}/current_link_speed", gpu.address),
+            )
+            .manual_only(),
+    };
+    vec![finding.weight(0)]
+}
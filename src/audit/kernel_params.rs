@@ -1,101 +1,745 @@
-use crate::audit::{Finding, Severity};
+use crate::audit::{Finding, PowerContext, Severity};
 use crate::detect::HardwareInfo;
 
+/// Which values of a kernel parameter are good enough. Audit and
+/// `build_plan` both go through it, so an acceptable value that isn't bop's
+/// own neither raises a finding nor gets replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamRule {
+    /// Set at all, bare or with any value
+    Present,
+    Equals(&'static str),
+    NumericAtLeast(u64),
+    NumericAtMost(u64),
+    /// Bounds included
+    NumericInRange(u64, u64),
+}
+
+impl ParamRule {
+    /// Whether `value` meets the rule: None when the parameter is absent,
+    /// `Some("")` when it is given bare.
+    pub fn accepts(self, value: Option<&str>) -> bool {
+        let Some(value) = value else {
+            return false;
+        };
+        match self {
+            ParamRule::Present => true,
+            ParamRule::Equals(expected) => value == expected,
+            ParamRule::NumericAtLeast(min) => parse_number(value).is_some_and(|v| v >= min),
+            ParamRule::NumericAtMost(max) => parse_number(value).is_some_and(|v| v <= max),
+            ParamRule::NumericInRange(min, max) => {
+                parse_number(value).is_some_and(|v| (min..=max).contains(&v))
+            }
+        }
+    }
+}
+
+/// A number as the kernel parses integer parameters: decimal, or hex with
+/// a `0x` prefix.
+fn parse_number(value: &str) -> Option<u64> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// A kernel parameter bop sets, the value it writes, and what it accepts
+/// in its place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParamSpec {
+    pub name: &'static str,
+    /// Empty for a bare parameter
+    pub value: &'static str,
+    pub rule: ParamRule,
+}
+
+pub const EC_NO_WAKEUP: ParamSpec = ParamSpec {
+    name: "acpi.ec_no_wakeup",
+    value: "1",
+    rule: ParamRule::Equals("1"),
+};
+pub const RTC_ACPI_ALARM: ParamSpec = ParamSpec {
+    name: "rtc_cmos.use_acpi_alarm",
+    value: "1",
+    rule: ParamRule::Equals("1"),
+};
+/// Level 3 and up dim the backlight further for the same content
+pub const ABM_LEVEL: ParamSpec = ParamSpec {
+    name: "amdgpu.abmlevel",
+    value: "3",
+    rule: ParamRule::NumericAtLeast(3),
+};
+/// Below 1ms APST is kept out of the states that save power; 0 turns it
+/// off. The kernel's own default is the value bop writes.
+pub const NVME_APST_LATENCY: ParamSpec = ParamSpec {
+    name: "nvme_core.default_ps_max_latency_us",
+    value: "100000",
+    rule: ParamRule::NumericInRange(1000, 100_000),
+};
+pub const MEM_SLEEP_S2IDLE: ParamSpec = ParamSpec {
+    name: "mem_sleep_default",
+    value: "s2idle",
+    rule: ParamRule::Equals("s2idle"),
+};
+
+/// Every parameter `build_plan` may add. A value the user already gave
+/// one of them is replaced with bop's, not kept alongside.
+pub const SET_PARAMS: &[ParamSpec] = &[
+    EC_NO_WAKEUP,
+    MEM_SLEEP_S2IDLE,
+    RTC_ACPI_ALARM,
+    NVME_APST_LATENCY,
+    ABM_LEVEL,
+];
+
+/// User parameters `build_plan` may remove, and when.
+pub const REMOVED_PARAMS: &[(&str, &str)] = &[
+    (
+        crate::audit::display::PSR_DEBUG_MASK_PARAM,
+        "when [display] force_psr asks",
+    ),
+    (ABM_LEVEL.name, "when it would fight the runtime ABM level"),
+];
+
+impl ParamSpec {
+    /// `name=value`, or just `name` for a bare one, as written to the boot
+    /// entry.
+    pub fn param(&self) -> String {
+        if self.value.is_empty() {
+            self.name.to_string()
+        } else {
+            format!("{}={}", self.name, self.value)
+        }
+    }
+
+    /// The value on the running command line; `Some("")` when given bare.
+    pub fn current(&self, hw: &HardwareInfo) -> Option<String> {
+        hw.kernel_param_value(self.name)
+            .or_else(|| hw.has_kernel_param(self.name).then(String::new))
+    }
+
+    pub fn satisfied(&self, hw: &HardwareInfo) -> bool {
+        self.rule.accepts(self.current(hw).as_deref())
+    }
+}
+
+/// Whether the machine has an NVMe controller (PCI class 01:08).
+pub fn has_nvme(hw: &HardwareInfo) -> bool {
+    hw.pci
+        .devices
+        .iter()
+        .any(|d| d.class.as_deref().is_some_and(|c| c.starts_with("0x0108")))
+}
+
 pub fn check(hw: &HardwareInfo) -> Vec<Finding> {
+    check_with_opts(hw, true)
+}
+
+/// Kernel param checks; `ec_no_wakeup` controls whether acpi.ec_no_wakeup=1
+/// is recommended (profile- and config-dependent).
+pub fn check_with_opts(hw: &HardwareInfo, ec_no_wakeup: bool) -> Vec<Finding> {
+    let mut findings = if ec_no_wakeup {
+        check_ec_no_wakeup(hw)
+    } else {
+        Vec::new()
+    };
+    findings.extend(check_generic(hw));
+    findings
+}
+
+/// A finding citing the command line for `spec`.
+fn param_finding(
+    hw: &HardwareInfo,
+    spec: &ParamSpec,
+    severity: Severity,
+    description: &str,
+) -> Finding {
+    Finding::new(severity, "Kernel", description)
+        .recommended(spec.param())
+        .path("/proc/cmdline")
+        .evidence_from(&hw.raw, "/proc/cmdline")
+        .key(spec.name)
+}
+
+pub fn check_ec_no_wakeup(hw: &HardwareInfo) -> Vec<Finding> {
+    let spec = EC_NO_WAKEUP;
+    if spec.satisfied(hw) {
+        return Vec::new();
+    }
+    let finding = match spec.current(hw) {
+        None => param_finding(
+            hw,
+            &spec,
+            Severity::High,
+            "EC wakeup not disabled - causes high sleep drain",
+        )
+        .current("unset")
+        .weight(9),
+        Some(value) => param_finding(
+            hw,
+            &spec,
+            Severity::Medium,
+            "acpi.ec_no_wakeup not set to 1",
+        )
+        .current(value)
+        .weight(7),
+    };
+    vec![finding.impact("~5-7% sleep drain reduction")]
+}
+
+fn check_generic(hw: &HardwareInfo) -> Vec<Finding> {
     let mut findings = Vec::new();
 
-    // Check for acpi.ec_no_wakeup=1
-    if !hw.has_kernel_param("acpi.ec_no_wakeup") {
-        findings.push(
-            Finding::new(
-                Severity::High,
-                "Kernel",
-                "EC wakeup not disabled - causes high sleep drain",
+    let rtc = RTC_ACPI_ALARM;
+    if !rtc.satisfied(hw) {
+        let finding = match rtc.current(hw) {
+            None => param_finding(
+                hw,
+                &rtc,
+                Severity::Medium,
+                "RTC ACPI alarm not enabled - prevents deepest sleep states",
+            )
+            .current("unset"),
+            Some(value) => param_finding(
+                hw,
+                &rtc,
+                Severity::Medium,
+                "rtc_cmos.use_acpi_alarm not set to 1",
+            )
+            .current(format!("{}={}", rtc.name, value)),
+        };
+        findings.push(finding.impact("Enables deeper CPU sleep states").weight(5));
+    }
+
+    // Any explicit value is judged; leaving it to the kernel only counts
+    // against a machine that has an NVMe drive
+    let nvme = NVME_APST_LATENCY;
+    match nvme.current(hw) {
+        _ if nvme.satisfied(hw) => {}
+        None if !has_nvme(hw) => {}
+        None => findings.push(
+            param_finding(
+                hw,
+                &nvme,
+                Severity::Low,
+                "NVMe APST latency limit not set on the kernel command line",
             )
             .current("unset")
-            .recommended("acpi.ec_no_wakeup=1")
-            .impact("~5-7% sleep drain reduction")
-            .path("/proc/cmdline")
-            .weight(9),
-        );
-    } else if hw.kernel_param_value("acpi.ec_no_wakeup") != Some("1".to_string()) {
-        findings.push(
-            Finding::new(Severity::Medium, "Kernel", "acpi.ec_no_wakeup not set to 1")
-                .current(
-                    hw.kernel_param_value("acpi.ec_no_wakeup")
-                        .unwrap_or_default(),
-                )
-                .recommended("acpi.ec_no_wakeup=1")
-                .impact("~5-7% sleep drain reduction")
-                .path("/proc/cmdline")
-                .weight(7),
-        );
+            .impact("Makes sure APST can reach the drive's low-power states")
+            .applies_on(PowerContext::Battery)
+            .weight(2),
+        ),
+        Some(value) => {
+            let description = if parse_number(&value) == Some(0) {
+                "NVMe APST disabled - drive stays in highest power state"
+            } else {
+                "NVMe APST latency limit outside 1000-100000us - keeps the drive out of its deepest power states"
+            };
+            findings.push(
+                param_finding(hw, &nvme, Severity::Medium, description)
+                    .current(format!("{}={}", nvme.name, value))
+                    .impact("~0.5-1W savings from NVMe power state transitions")
+                    .weight(5),
+            );
+        }
     }
 
-    // Check for rtc_cmos.use_acpi_alarm=1
-    if !hw.has_kernel_param("rtc_cmos.use_acpi_alarm") {
-        findings.push(
-            Finding::new(
+    // ABM, on backlit panels only — OLED has no backlight to dim
+    if hw.gpu.is_amd() && hw.display.supports_abm() {
+        findings.extend(check_abm(hw));
+    }
+
+    findings
+}
+
+/// Lowest `panel_power_savings` level accepted, as [`ABM_LEVEL`] accepts
+/// for the parameter.
+const ABM_RUNTIME_MIN: u32 = 3;
+
+/// Level to write to `panel_power_savings`: the minimum accepted, or the
+/// current one if that is already higher.
+pub fn abm_runtime_target(level: u32) -> u32 {
+    level.max(ABM_RUNTIME_MIN)
+}
+
+/// Whether `amdgpu.abmlevel` is on the command line at a level other than
+/// the runtime `panel_power_savings` one. amdgpu reapplies the parameter
+/// on resume, so the two then fight and the backlight visibly pumps.
+pub fn abm_conflicts(hw: &HardwareInfo) -> bool {
+    hw.gpu.panel_power_savings.is_some_and(|level| {
+        ABM_LEVEL
+            .current(hw)
+            .is_some_and(|value| parse_number(&value) != Some(u64::from(level)))
+    })
+}
+
+/// ABM is set either by `amdgpu.abmlevel` at boot or, on kernels with the
+/// eDP connector's `panel_power_savings`, at runtime. bop uses the runtime
+/// attribute where it exists, so one finding covers both.
+fn check_abm(hw: &HardwareInfo) -> Option<Finding> {
+    let abm = ABM_LEVEL;
+    let (Some(path), Some(level)) = (
+        hw.gpu.panel_power_savings_path.as_deref(),
+        hw.gpu.panel_power_savings,
+    ) else {
+        if abm.satisfied(hw) {
+            return None;
+        }
+        return Some(match abm.current(hw) {
+            None => param_finding(
+                hw,
+                &abm,
                 Severity::Medium,
-                "Kernel",
-                "RTC ACPI alarm not enabled - prevents deepest sleep states",
+                "AMD Adaptive Backlight Management not enabled",
             )
-            .current("unset")
-            .recommended("rtc_cmos.use_acpi_alarm=1")
-            .impact("Enables deeper CPU sleep states")
-            .path("/proc/cmdline")
+            .current("unset (level 0)")
+            .impact("~0.5-1W display power saving")
             .weight(5),
-        );
-    }
+            Some(value) => param_finding(hw, &abm, Severity::Low, "ABM level below recommended")
+                .current(format!("{}={}", abm.name, value))
+                .impact("Higher levels save more display power")
+                .weight(3),
+        });
+    };
 
-    // Check for NVMe APST disabled
-    if let Some(ref val) = hw.kernel_param_value("nvme_core.default_ps_max_latency_us")
-        && val == "0"
-    {
-        findings.push(
-            Finding::new(
+    let target = abm_runtime_target(level);
+    let finding = |severity, description: &str| {
+        Finding::new(severity, "Display", description)
+            .recommended(format!("panel_power_savings={}", target))
+            .path(format!("/{}", path))
+            .evidence_from(&hw.raw, path)
+    };
+    if abm_conflicts(hw) {
+        let param = abm.current(hw).unwrap_or_default();
+        return Some(
+            finding(
                 Severity::Medium,
-                "Kernel",
-                "NVMe APST disabled - drive stays in highest power state",
+                "ABM set at boot and at runtime to different levels - brightness pumps after resume",
             )
-            .current("nvme_core.default_ps_max_latency_us=0")
-            .recommended("Remove parameter (let APST work normally)")
-            .impact("~0.5-1W savings from NVMe power state transitions")
-            .path("/proc/cmdline")
+            .current(format!(
+                "panel_power_savings={} now, {}={} again after resume",
+                level, abm.name, param
+            ))
+            .recommended(format!(
+                "panel_power_savings={} and no {}",
+                target, abm.name
+            ))
+            .evidence_from(&hw.raw, "/proc/cmdline")
+            .impact("Steady backlight, and ~0.5-1W display power saving at level 3")
             .weight(5),
         );
     }
+    match level {
+        0 => Some(
+            finding(
+                Severity::Medium,
+                "AMD Adaptive Backlight Management not enabled",
+            )
+            .current("panel_power_savings=0")
+            .impact("~0.5-1W display power saving")
+            .weight(5),
+        ),
+        level if level < ABM_RUNTIME_MIN => Some(
+            finding(Severity::Low, "ABM level below recommended")
+                .current(format!("panel_power_savings={}", level))
+                .impact("Higher levels save more display power")
+                .weight(3),
+        ),
+        _ => None,
+    }
+}
 
-    // Check for amdgpu.abmlevel
-    if hw.gpu.is_amd() {
-        match hw.kernel_param_value("amdgpu.abmlevel") {
-            None => {
-                findings.push(
-                    Finding::new(
-                        Severity::Medium,
-                        "Kernel",
-                        "AMD Adaptive Backlight Management not enabled",
-                    )
-                    .current("unset (level 0)")
-                    .recommended("amdgpu.abmlevel=3")
-                    .impact("~0.5-1W display power saving")
-                    .path("/proc/cmdline")
-                    .weight(5),
-                );
+/// How long after resume a logind event still counts as part of the wake.
+const WAKE_WINDOW_SECS: f64 = 15.0;
+/// Need a few lid wakes before concluding the lid can't wake the machine.
+const MIN_LID_SESSIONS: usize = 2;
+
+#[derive(Debug, Default, PartialEq)]
+struct SleepSession {
+    lid_opened: bool,
+    power_key: bool,
+}
+
+/// Read this boot's suspend/resume and lid/power-key events from the journal.
+/// None when journalctl isn't available.
+pub fn read_sleep_log() -> Option<String> {
+    let output = crate::cmd::Cmd::new("journalctl")
+        .args([
+            "-b",
+            "-q",
+            "--no-pager",
+            "-o",
+            "short-unix",
+            "-u",
+            "systemd-logind.service",
+            "-u",
+            "systemd-suspend.service",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Split a `short-unix` journal dump into sleep sessions, recording which
+/// logind input events arrived shortly after each resume.
+fn parse_sleep_sessions(log: &str) -> Vec<SleepSession> {
+    let mut sessions = Vec::new();
+    let mut current: Option<(f64, SleepSession)> = None;
+
+    for line in log.lines() {
+        let Some(ts) = line
+            .split_whitespace()
+            .next()
+            .and_then(|t| t.parse::<f64>().ok())
+        else {
+            continue;
+        };
+
+        if line.contains("Entering sleep state") {
+            if let Some((_, session)) = current.take() {
+                sessions.push(session);
             }
-            Some(ref val) if val.parse::<u32>().unwrap_or(0) < 3 => {
-                findings.push(
-                    Finding::new(Severity::Low, "Kernel", "ABM level below recommended")
-                        .current(format!("amdgpu.abmlevel={}", val))
-                        .recommended("amdgpu.abmlevel=3")
-                        .impact("Higher levels save more display power")
-                        .path("/proc/cmdline")
-                        .weight(3),
-                );
+        } else if line.contains("returned from sleep") || line.contains("System resumed") {
+            if let Some((_, session)) = current.take() {
+                sessions.push(session);
+            }
+            current = Some((ts, SleepSession::default()));
+        } else if let Some((resumed_at, session)) = current.as_mut()
+            && ts - *resumed_at <= WAKE_WINDOW_SECS
+        {
+            if line.contains("Lid opened") {
+                session.lid_opened = true;
+            } else if line.contains("Power key pressed") {
+                session.power_key = true;
             }
-            _ => {}
         }
     }
+    if let Some((_, session)) = current {
+        sessions.push(session);
+    }
+    sessions
+}
 
-    findings
+/// True when every resume that followed a lid open also needed the power key.
+fn lid_wake_broken(sessions: &[SleepSession]) -> bool {
+    let lid: Vec<_> = sessions.iter().filter(|s| s.lid_opened).collect();
+    lid.len() >= MIN_LID_SESSIONS && lid.iter().all(|s| s.power_key)
+}
+
+/// Post-reboot verification for acpi.ec_no_wakeup=1: if the sleep log shows
+/// that opening the lid never wakes the machine, suggest backing it out.
+pub fn check_lid_wake(hw: &HardwareInfo, sleep_log: Option<&str>) -> Vec<Finding> {
+    if hw.kernel_param_value("acpi.ec_no_wakeup").as_deref() != Some("1") {
+        return Vec::new();
+    }
+    let Some(log) = sleep_log else {
+        return Vec::new();
+    };
+    let sessions = parse_sleep_sessions(log);
+    if !lid_wake_broken(&sessions) {
+        return Vec::new();
+    }
+
+    vec![
+        Finding::new(
+            Severity::High,
+            "Kernel",
+            "Lid open no longer wakes the system - acpi.ec_no_wakeup=1 likely blocks it",
+        )
+        .current(format!(
+            "{} resumes needed the power button after lid open",
+            sessions.iter().filter(|s| s.lid_opened).count()
+        ))
+        .recommended("Remove it: sudo bop revert --only kernel-params")
+        .impact("Restores lid wake; set overrides.ec_no_wakeup = false to keep it off")
+        .path("/proc/cmdline")
+        .evidence_from(&hw.raw, "/proc/cmdline")
+        .key("lid_wake")
+        .weight(8),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::pci::{PciDevice, PcieLink};
+    use crate::sysfs::SysfsRoot;
+    use tempfile::TempDir;
+
+    fn hw_with_cmdline(cmdline: &str) -> HardwareInfo {
+        let tmp = TempDir::new().unwrap();
+        let mut hw = HardwareInfo::detect(&SysfsRoot::new(tmp.path()));
+        hw.kernel_cmdline = cmdline.to_string();
+        hw
+    }
+
+    fn with_nvme(mut hw: HardwareInfo) -> HardwareInfo {
+        hw.pci.devices.push(PciDevice {
+            address: "0000:02:00.0".to_string(),
+            class: Some("0x010802".to_string()),
+            vendor: None,
+            device: None,
+            driver: Some("nvme".to_string()),
+            runtime_pm: None,
+            runtime_status: None,
+            boot_vga: None,
+            gpu_busy_percent: None,
+            link: PcieLink::default(),
+            upstream_ports: Vec::new(),
+        });
+        hw
+    }
+
+    fn nvme_finding(hw: &HardwareInfo) -> Option<Finding> {
+        check(hw)
+            .into_iter()
+            .find(|f| f.recommended_value.starts_with(NVME_APST_LATENCY.name))
+    }
+
+    #[test]
+    fn test_param_rules() {
+        assert!(ParamRule::Present.accepts(Some("")));
+        assert!(ParamRule::Present.accepts(Some("0")));
+        assert!(!ParamRule::Present.accepts(None));
+
+        assert!(ParamRule::Equals("1").accepts(Some("1")));
+        assert!(!ParamRule::Equals("1").accepts(Some("0")));
+        assert!(!ParamRule::Equals("1").accepts(Some("")));
+
+        assert!(ParamRule::NumericAtLeast(3).accepts(Some("3")));
+        assert!(ParamRule::NumericAtLeast(3).accepts(Some("4")));
+        assert!(!ParamRule::NumericAtLeast(3).accepts(Some("2")));
+        assert!(!ParamRule::NumericAtLeast(3).accepts(Some("high")));
+
+        assert!(ParamRule::NumericAtMost(10).accepts(Some("10")));
+        assert!(!ParamRule::NumericAtMost(10).accepts(Some("11")));
+
+        let range = ParamRule::NumericInRange(1000, 100_000);
+        assert!(range.accepts(Some("1000")));
+        assert!(range.accepts(Some("100000")));
+        assert!(range.accepts(Some("0x157c")));
+        assert!(!range.accepts(Some("999")));
+        assert!(!range.accepts(Some("100001")));
+        assert!(!range.accepts(Some("")));
+        assert!(!range.accepts(None));
+    }
+
+    #[test]
+    fn test_spec_reads_bare_and_valued_params() {
+        let hw = hw_with_cmdline("root=UUID=abc quiet amdgpu.abmlevel=0x4");
+        let quiet = ParamSpec {
+            name: "quiet",
+            value: "",
+            rule: ParamRule::Present,
+        };
+        assert_eq!(quiet.param(), "quiet");
+        assert_eq!(ABM_LEVEL.param(), "amdgpu.abmlevel=3");
+        assert_eq!(quiet.current(&hw).as_deref(), Some(""));
+        assert!(quiet.satisfied(&hw));
+        assert_eq!(ABM_LEVEL.current(&hw).as_deref(), Some("0x4"));
+        assert!(ABM_LEVEL.satisfied(&hw));
+        assert_eq!(RTC_ACPI_ALARM.current(&hw), None);
+        assert!(!RTC_ACPI_ALARM.satisfied(&hw));
+    }
+
+    #[test]
+    fn test_nvme_latency_in_range_accepted() {
+        let hw = with_nvme(hw_with_cmdline(
+            "root=UUID=abc nvme_core.default_ps_max_latency_us=5500",
+        ));
+        assert!(nvme_finding(&hw).is_none());
+    }
+
+    #[test]
+    fn test_nvme_latency_zero_and_too_low_flagged() {
+        let hw = hw_with_cmdline("root=UUID=abc nvme_core.default_ps_max_latency_us=0");
+        let finding = nvme_finding(&hw).unwrap();
+        assert_eq!(finding.severity, Severity::Medium);
+        assert!(finding.description.contains("APST disabled"));
+
+        let hw = hw_with_cmdline("root=UUID=abc nvme_core.default_ps_max_latency_us=500");
+        let finding = nvme_finding(&hw).unwrap();
+        assert_eq!(finding.severity, Severity::Medium);
+        assert!(finding.description.contains("outside 1000-100000us"));
+        assert_eq!(
+            finding.recommended_value,
+            "nvme_core.default_ps_max_latency_us=100000"
+        );
+    }
+
+    #[test]
+    fn test_nvme_latency_absent_needs_nvme_drive() {
+        let hw = hw_with_cmdline("root=UUID=abc");
+        assert!(nvme_finding(&hw).is_none());
+
+        let finding = nvme_finding(&with_nvme(hw)).unwrap();
+        assert_eq!(finding.severity, Severity::Low);
+        assert_eq!(finding.applies_on, PowerContext::Battery);
+    }
+
+    #[test]
+    fn test_abm_conflict_compares_levels_not_spelling() {
+        let with_runtime = |cmdline: &str, level: Option<u32>| {
+            let mut hw = hw_with_cmdline(cmdline);
+            hw.gpu.panel_power_savings = level;
+            hw
+        };
+        assert!(abm_conflicts(&with_runtime("amdgpu.abmlevel=1", Some(3))));
+        assert!(!abm_conflicts(&with_runtime(
+            "amdgpu.abmlevel=0x3",
+            Some(3)
+        )));
+        assert!(!abm_conflicts(&with_runtime("quiet", Some(0))));
+        assert!(!abm_conflicts(&with_runtime("amdgpu.abmlevel=1", None)));
+
+        assert_eq!(abm_runtime_target(0), 3);
+        assert_eq!(abm_runtime_target(4), 4);
+    }
+
+    fn session(suspend_at: u32, resume_at: u32, events: &[(u32, &str)]) -> String {
+        let mut log = format!(
+            "{suspend_at}.000000 fw systemd-sleep[900]: Entering sleep state 'suspend'...\n\
+             {resume_at}.000000 fw systemd-sleep[900]: System returned from sleep operation 'suspend'.\n"
+        );
+        for (ts, msg) in events {
+            log.push_str(&format!("{ts}.500000 fw systemd-logind[512]: {msg}\n"));
+        }
+        log
+    }
+
+    #[test]
+    fn test_parse_sleep_sessions() {
+        let log = format!(
+            "-- Boot abc --\n{}{}",
+            session(
+                100,
+                200,
+                &[(201, "Lid opened."), (202, "Power key pressed short.")]
+            ),
+            session(300, 400, &[(401, "Lid opened.")]),
+        );
+        let sessions = parse_sleep_sessions(&log);
+        assert_eq!(
+            sessions,
+            vec![
+                SleepSession {
+                    lid_opened: true,
+                    power_key: true
+                },
+                SleepSession {
+                    lid_opened: true,
+                    power_key: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_events_outside_wake_window_ignored() {
+        let log = session(
+            100,
+            200,
+            &[(201, "Lid opened."), (500, "Power key pressed.")],
+        );
+        let sessions = parse_sleep_sessions(&log);
+        assert!(sessions[0].lid_opened);
+        assert!(!sessions[0].power_key);
+    }
+
+    #[test]
+    fn test_lid_wake_broken_requires_every_lid_session() {
+        let broken = format!(
+            "{}{}",
+            session(
+                100,
+                200,
+                &[(201, "Lid opened."), (202, "Power key pressed short.")]
+            ),
+            session(
+                300,
+                400,
+                &[(401, "Lid opened."), (401, "Power key pressed.")]
+            ),
+        );
+        assert!(lid_wake_broken(&parse_sleep_sessions(&broken)));
+
+        let mixed = format!(
+            "{}{}",
+            session(
+                100,
+                200,
+                &[(201, "Lid opened."), (202, "Power key pressed short.")]
+            ),
+            session(300, 400, &[(401, "Lid opened.")]),
+        );
+        assert!(!lid_wake_broken(&parse_sleep_sessions(&mixed)));
+
+        // A single data point isn't enough
+        let once = session(
+            100,
+            200,
+            &[(201, "Lid opened."), (202, "Power key pressed.")],
+        );
+        assert!(!lid_wake_broken(&parse_sleep_sessions(&once)));
+    }
+
+    #[test]
+    fn test_check_lid_wake_only_when_param_active() {
+        let log = format!(
+            "{}{}",
+            session(
+                100,
+                200,
+                &[(201, "Lid opened."), (202, "Power key pressed short.")]
+            ),
+            session(
+                300,
+                400,
+                &[(401, "Lid opened."), (402, "Power key pressed short.")]
+            ),
+        );
+
+        let hw = hw_with_cmdline("root=UUID=abc acpi.ec_no_wakeup=1");
+        let findings = check_lid_wake(&hw, Some(&log));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::High);
+        assert!(
+            findings[0]
+                .recommended_value
+                .contains("bop revert --only kernel-params")
+        );
+
+        let hw = hw_with_cmdline("root=UUID=abc");
+        assert!(check_lid_wake(&hw, Some(&log)).is_empty());
+
+        let hw = hw_with_cmdline("root=UUID=abc acpi.ec_no_wakeup=1");
+        assert!(check_lid_wake(&hw, None).is_empty());
+    }
+
+    #[test]
+    fn test_check_with_opts_ec_no_wakeup_toggle() {
+        let hw = hw_with_cmdline("root=UUID=abc");
+        let with = check_with_opts(&hw, true);
+        let without = check_with_opts(&hw, false);
+        assert!(
+            with.iter()
+                .any(|f| f.recommended_value == "acpi.ec_no_wakeup=1")
+        );
+        assert!(
+            !without
+                .iter()
+                .any(|f| f.recommended_value == "acpi.ec_no_wakeup=1")
+        );
+        assert!(
+            without
+                .iter()
+                .any(|f| f.recommended_value == "rtc_cmos.use_acpi_alarm=1")
+        );
+    }
 }
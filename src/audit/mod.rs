@@ -1,19 +1,103 @@
 pub mod audio;
+pub mod conflicts;
 pub mod cpu_power;
 pub mod display;
 pub mod gpu_power;
 pub mod kernel_params;
 pub mod network_power;
 pub mod pci_power;
+pub mod powercap;
+pub mod retimer;
+pub mod service_cpu;
 pub mod services;
 pub mod sleep;
 pub mod sysctl;
+pub mod upgrade;
 pub mod usb_power;
 
-use serde::Serialize;
+use crate::apply::{ApplyState, WakeupBaseline};
+use crate::detect::ac::AcInfo;
+use crate::idle_drain::{DrainAlert, DrainHistory, HISTORY_PATH, unix_now};
+use crate::pins::Pins;
+use crate::sysfs::{RawValues, SysfsRoot};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Where an audit reads the machine from.
+pub struct AuditContext<'a> {
+    pub sysfs: &'a SysfsRoot,
+    /// Consult only the sysfs tree: no commands, and no bop state file.
+    /// For auditing a materialized snapshot reproducibly.
+    pub offline: bool,
+    /// May run systemctl, journalctl or iw. Those checks are the slow part
+    /// of an audit.
+    pub commands: bool,
+}
+
+impl<'a> AuditContext<'a> {
+    /// Audit the running system. Another root is audited like a snapshot.
+    pub fn live(sysfs: &'a SysfsRoot) -> Self {
+        if !sysfs.is_host() {
+            return Self::offline(sysfs);
+        }
+        Self {
+            sysfs,
+            offline: false,
+            commands: true,
+        }
+    }
+
+    /// Audit the running system without starting a single command, fast
+    /// enough for a login banner (`bop audit --oneline`). Service conflicts,
+    /// WiFi power save and the sleep journal go unchecked.
+    pub fn quick(sysfs: &'a SysfsRoot) -> Self {
+        if !sysfs.is_host() {
+            return Self::offline(sysfs);
+        }
+        Self {
+            sysfs,
+            offline: false,
+            commands: false,
+        }
+    }
+
+    /// Audit a sysfs tree alone, e.g. a snapshot fixture.
+    pub fn offline(sysfs: &'a SysfsRoot) -> Self {
+        Self {
+            sysfs,
+            offline: true,
+            commands: false,
+        }
+    }
+
+    /// This boot's suspend/lid journal, when it may be read.
+    pub fn sleep_log(&self) -> Option<String> {
+        if !self.commands {
+            return None;
+        }
+        kernel_params::read_sleep_log()
+    }
+
+    /// Wakeup counters recorded by the last apply, when they may be read.
+    pub fn wakeup_baseline(&self) -> Option<WakeupBaseline> {
+        if self.offline {
+            return None;
+        }
+        ApplyState::load().ok().flatten()?.wakeup_baseline
+    }
+
+    /// The idle drain alert `bop monitor` is currently seeing, if any.
+    pub fn drain_alert(&self) -> Option<DrainAlert> {
+        if self.offline {
+            return None;
+        }
+        DrainHistory::load(Path::new(HISTORY_PATH)).current_alert(unix_now())
+    }
+}
 
 /// Severity of an audit finding.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Severity {
     Info,
     Low,
@@ -21,6 +105,74 @@ pub enum Severity {
     High,
 }
 
+impl Severity {
+    /// 0 for Info up to 3 for High, so JSON consumers can sort without
+    /// knowing the variants.
+    pub fn rank(self) -> u8 {
+        self as u8
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Low => write!(f, "low"),
+            Severity::Medium => write!(f, "medium"),
+            Severity::High => write!(f, "high"),
+        }
+    }
+}
+
+/// Whether `bop apply` can act on a finding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum Remedy {
+    #[default]
+    Apply,
+    /// No kernel interface bop can write; the fix is a firmware setting,
+    /// a boot parameter to weigh by hand, or a newer kernel.
+    ManualOnly,
+}
+
+/// The power source a finding matters on. EPP or runtime PM only cost
+/// battery life; a conflicting service or kernel parameter matters either
+/// way. Also the view `bop audit --context` scores and lists findings for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum PowerContext {
+    Battery,
+    Ac,
+    #[default]
+    Both,
+}
+
+impl PowerContext {
+    /// The view for the power source the machine is running on.
+    pub fn current(ac: &AcInfo) -> Self {
+        if ac.is_on_ac() {
+            PowerContext::Ac
+        } else {
+            PowerContext::Battery
+        }
+    }
+
+    /// Whether a finding that applies on `self` counts in the `view`.
+    pub fn relevant_in(self, view: PowerContext) -> bool {
+        self == PowerContext::Both || view == PowerContext::Both || self == view
+    }
+}
+
+impl std::fmt::Display for PowerContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PowerContext::Battery => write!(f, "battery"),
+            PowerContext::Ac => write!(f, "ac"),
+            PowerContext::Both => write!(f, "both"),
+        }
+    }
+}
+
 /// A single audit finding.
 #[derive(Debug, Clone, Serialize)]
 pub struct Finding {
@@ -32,8 +184,20 @@ pub struct Finding {
     pub impact: String,
     /// The sysfs/config path this finding relates to
     pub path: Option<String>,
+    /// Tells apart findings that share a path, e.g. kernel parameters
+    /// all living in /proc/cmdline. Part of [`Finding::id`].
+    #[serde(skip)]
+    pub key: Option<String>,
+    pub remedy: Remedy,
+    pub applies_on: PowerContext,
+    /// The path is in the user's `[pins]`, so bop leaves it as it is
+    pub pinned: bool,
     /// Weight for scoring (0-10)
     pub weight: u32,
+    /// Files the check was based on and their contents as read, for
+    /// `--verbose-json` and `bop audit --explain`
+    #[serde(skip)]
+    pub evidence: Vec<(String, String)>,
 }
 
 impl Finding {
@@ -50,7 +214,12 @@ impl Finding {
             recommended_value: String::new(),
             impact: String::new(),
             path: None,
+            key: None,
+            remedy: Remedy::Apply,
+            applies_on: PowerContext::Both,
+            pinned: false,
             weight: 0,
+            evidence: Vec::new(),
         }
     }
 
@@ -69,8 +238,31 @@ impl Finding {
         self
     }
 
+    /// What the finding concerns. A path relative to the sysfs root
+    /// ("sys/...", as checks read it) is stored absolute, so ids and pins
+    /// see one form.
     pub fn path(mut self, value: impl Into<String>) -> Self {
-        self.path = Some(value.into());
+        let value = value.into();
+        self.path = Some(if value.starts_with("sys/") || value.starts_with("proc/") {
+            format!("/{}", value)
+        } else {
+            value
+        });
+        self
+    }
+
+    pub fn key(mut self, value: impl Into<String>) -> Self {
+        self.key = Some(value.into());
+        self
+    }
+
+    pub fn manual_only(mut self) -> Self {
+        self.remedy = Remedy::ManualOnly;
+        self
+    }
+
+    pub fn applies_on(mut self, context: PowerContext) -> Self {
+        self.applies_on = context;
         self
     }
 
@@ -78,6 +270,112 @@ impl Finding {
         self.weight = value;
         self
     }
+
+    pub fn evidence(mut self, path: impl Into<String>, raw: impl Into<String>) -> Self {
+        self.evidence.push((path.into(), raw.into()));
+        self
+    }
+
+    /// Cite `path` with what was read from it, if anything was. Never reads.
+    pub fn evidence_from(self, raw: &RawValues, path: &str) -> Self {
+        match raw.get(path) {
+            Some(value) => self.evidence(format!("/{}", path.trim_start_matches('/')), value),
+            None => self,
+        }
+    }
+}
+
+impl Finding {
+    /// Key that identifies the same finding across audit runs: category plus
+    /// the path it concerns (and its key, when several findings share the
+    /// path), or the description for path-less findings. Ids never include
+    /// measured values, so a finding whose numbers move keeps its id.
+    pub fn id(&self) -> String {
+        let mut id = format!(
+            "{}:{}",
+            self.category.to_lowercase(),
+            self.path.as_deref().unwrap_or(&self.description)
+        );
+        if let Some(key) = &self.key {
+            id.push('#');
+            id.push_str(key);
+        }
+        id
+    }
+}
+
+/// Canonical finding order: severity (highest first), then category, then
+/// [`Finding::id`]. Independent of the order checks run in, so output and
+/// diffs stay stable when checks are reshuffled.
+pub fn canonical_order(a: &Finding, b: &Finding) -> std::cmp::Ordering {
+    b.severity
+        .cmp(&a.severity)
+        .then_with(|| a.category.cmp(&b.category))
+        .then_with(|| a.id().cmp(&b.id()))
+}
+
+/// Sort findings into [`canonical_order`].
+pub fn sort_findings(findings: &mut [Finding]) {
+    findings.sort_by(canonical_order);
+}
+
+/// What changed between two audits of the same machine.
+#[derive(Debug, Clone, Default)]
+pub struct FindingsDiff {
+    pub appeared: Vec<Finding>,
+    pub resolved: Vec<Finding>,
+    /// Same finding, different details: (before, after)
+    pub changed: Vec<(Finding, Finding)>,
+}
+
+impl FindingsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.appeared.is_empty() && self.resolved.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Pair up `before` and `after` by `id`, repeated ids in order. Returns
+/// the pairs, then what only `after` has, then what only `before` has.
+pub(crate) fn match_by_id<'a, T>(
+    before: &'a [T],
+    after: &'a [T],
+    id: impl Fn(&T) -> String,
+) -> (Vec<(&'a T, &'a T)>, Vec<&'a T>, Vec<&'a T>) {
+    let mut pairs = Vec::new();
+    let mut appeared = Vec::new();
+    let mut unmatched: Vec<&T> = before.iter().collect();
+    for new in after {
+        let new_id = id(new);
+        match unmatched.iter().position(|old| id(old) == new_id) {
+            Some(i) => pairs.push((unmatched.remove(i), new)),
+            None => appeared.push(new),
+        }
+    }
+    (pairs, appeared, unmatched)
+}
+
+/// Compare findings by [`Finding::id`]. Repeated ids are matched in order.
+pub fn diff_findings(before: &[Finding], after: &[Finding]) -> FindingsDiff {
+    let (pairs, appeared, resolved) = match_by_id(before, after, Finding::id);
+    let mut diff = FindingsDiff {
+        appeared: appeared.into_iter().cloned().collect(),
+        resolved: resolved.into_iter().cloned().collect(),
+        changed: pairs
+            .into_iter()
+            .filter(|(old, new)| {
+                old.severity != new.severity
+                    || old.description != new.description
+                    || old.current_value != new.current_value
+                    || old.recommended_value != new.recommended_value
+            })
+            .map(|(old, new)| (old.clone(), new.clone()))
+            .collect(),
+    };
+
+    sort_findings(&mut diff.appeared);
+    sort_findings(&mut diff.resolved);
+    diff.changed.sort_by(|a, b| canonical_order(&a.1, &b.1));
+    diff
 }
 
 /// Calculate audit score (0-100) from findings.
@@ -98,3 +396,150 @@ pub fn calculate_score(findings: &[Finding]) -> u32 {
     let score = (100.0 * (1.0 - penalty_ratio)).round() as u32;
     score.min(100)
 }
+
+/// Mark the findings whose path the user has pinned. They still count in
+/// the score; the mark says why `bop apply` won't act on them.
+pub fn annotate_pins(findings: &mut [Finding], pins: &Pins) {
+    if pins.is_empty() {
+        return;
+    }
+    for finding in findings {
+        finding.pinned = finding
+            .path
+            .as_deref()
+            .is_some_and(|path| pins.for_finding(path).is_some());
+    }
+}
+
+/// [`calculate_score`] over the findings relevant in `view`, e.g. the AC
+/// score leaves out battery-only findings.
+pub fn context_score(findings: &[Finding], view: PowerContext) -> u32 {
+    let relevant: Vec<Finding> = findings
+        .iter()
+        .filter(|f| f.applies_on.relevant_in(view))
+        .cloned()
+        .collect();
+    calculate_score(&relevant)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(category: &str, path: &str, current: &str) -> Finding {
+        Finding::new(Severity::Medium, category, format!("{} needs tuning", path))
+            .current(current)
+            .path(path)
+    }
+
+    #[test]
+    fn test_diff_findings() {
+        let before = vec![
+            finding("CPU", "/sys/epp", "performance"),
+            finding("Audio", "/sys/power_save", "0"),
+            Finding::new(Severity::Info, "Sleep", "deep sleep in use"),
+        ];
+        let after = vec![
+            finding("CPU", "/sys/epp", "balance_performance"),
+            Finding::new(Severity::Info, "Sleep", "deep sleep in use"),
+            finding("PCI", "/sys/aspm", "default"),
+        ];
+
+        let diff = diff_findings(&before, &after);
+        assert_eq!(diff.appeared.len(), 1);
+        assert_eq!(
+            finding("CPU", "sys/epp", "performance").id(),
+            before[0].id()
+        );
+        assert_eq!(diff.appeared[0].id(), "pci:/sys/aspm");
+        assert_eq!(diff.resolved.len(), 1);
+        assert_eq!(diff.resolved[0].id(), "audio:/sys/power_save");
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].1.current_value, "balance_performance");
+
+        assert!(diff_findings(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn test_canonical_order() {
+        let mut findings = vec![
+            finding("PCIe", "/sys/b", "x"),
+            Finding::new(Severity::Info, "Sleep", "deep sleep in use"),
+            Finding::new(Severity::High, "Kernel", "EC wakeup").path("/proc/cmdline"),
+            finding("Audio", "/sys/z", "x"),
+            finding("PCIe", "/sys/a", "x"),
+        ];
+        let mut reversed = findings.clone();
+        reversed.reverse();
+
+        sort_findings(&mut findings);
+        sort_findings(&mut reversed);
+        let ids: Vec<String> = findings.iter().map(|f| f.id()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "kernel:/proc/cmdline",
+                "audio:/sys/z",
+                "pcie:/sys/a",
+                "pcie:/sys/b",
+                "sleep:deep sleep in use",
+            ]
+        );
+        assert_eq!(ids, reversed.iter().map(|f| f.id()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_context_scores() {
+        let findings = vec![
+            finding("CPU", "/sys/epp", "performance")
+                .applies_on(PowerContext::Battery)
+                .weight(8),
+            Finding::new(Severity::High, "Services", "tlp conflicts").weight(6),
+        ];
+        assert_eq!(calculate_score(&findings), 30);
+        assert_eq!(context_score(&findings, PowerContext::Battery), 30);
+        assert_eq!(context_score(&findings, PowerContext::Both), 30);
+        // Only the service conflict counts on AC
+        assert_eq!(context_score(&findings, PowerContext::Ac), 40);
+        assert_eq!(context_score(&findings[..1], PowerContext::Ac), 100);
+
+        assert!(PowerContext::Ac.relevant_in(PowerContext::Ac));
+        assert!(!PowerContext::Ac.relevant_in(PowerContext::Battery));
+        assert!(PowerContext::Battery.relevant_in(PowerContext::Both));
+    }
+
+    #[test]
+    fn test_annotate_pins() {
+        let mut findings = vec![
+            finding(
+                "CPU",
+                "cpu*/cpufreq/energy_performance_preference",
+                "balance_performance",
+            ),
+            finding("CPU", "cpufreq/boost", "1"),
+            Finding::new(Severity::High, "Services", "tlp conflicts"),
+        ];
+        let mut pins = Pins::default();
+        annotate_pins(&mut findings, &pins);
+        assert!(findings.iter().all(|f| !f.pinned));
+
+        pins.insert(
+            "/sys/devices/system/cpu/cpu*/cpufreq/energy_performance_preference",
+            "balance_performance",
+        );
+        annotate_pins(&mut findings, &pins);
+        let pinned: Vec<bool> = findings.iter().map(|f| f.pinned).collect();
+        assert_eq!(pinned, vec![true, false, false]);
+    }
+
+    #[test]
+    fn test_severity_serializes_lowercase_with_rank() {
+        assert_eq!(
+            serde_json::to_value(Severity::Medium).unwrap(),
+            serde_json::json!("medium")
+        );
+        assert_eq!(Severity::Info.rank(), 0);
+        assert_eq!(Severity::High.rank(), 3);
+        assert_eq!(Severity::Low.to_string(), "low");
+    }
+}
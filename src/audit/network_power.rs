@@ -1,4 +1,4 @@
-use crate::audit::{Finding, Severity};
+use crate::audit::{Finding, PowerContext, Severity};
 use crate::detect::HardwareInfo;
 
 pub fn check(hw: &HardwareInfo) -> Vec<Finding> {
@@ -6,7 +6,7 @@ pub fn check(hw: &HardwareInfo) -> Vec<Finding> {
 
     // Check WiFi power save via iw command
     if let Some(ref iface) = hw.network.wifi_interface {
-        match std::process::Command::new("iw")
+        match crate::cmd::Cmd::new("iw")
             .args(["dev", iface, "get", "power_save"])
             .output()
         {
@@ -15,10 +15,12 @@ pub fn check(hw: &HardwareInfo) -> Vec<Finding> {
                 if stdout.contains("Power save: off") {
                     findings.push(
                         Finding::new(Severity::Medium, "Network", "WiFi power save disabled")
+                            .applies_on(PowerContext::Battery)
                             .current("off")
                             .recommended("on")
                             .impact("~0.5W savings")
                             .path(format!("iw dev {} set power_save on", iface))
+                            .evidence(format!("iw dev {} get power_save", iface), stdout)
                             .weight(5),
                     );
                 }
@@ -31,6 +33,7 @@ pub fn check(hw: &HardwareInfo) -> Vec<Finding> {
                         "Network",
                         "Could not check WiFi power save (iw not available)",
                     )
+                    .applies_on(PowerContext::Battery)
                     .current("unknown")
                     .recommended("on")
                     .impact("~0.5W if disabled")
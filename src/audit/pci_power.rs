@@ -1,6 +1,8 @@
-use crate::audit::{Finding, Severity};
+use crate::audit::{Finding, PowerContext, Severity};
 use crate::detect::HardwareInfo;
+use crate::detect::pci::PciDevice;
 use crate::preset::{Preset, PresetKnobs};
+use crate::quirks::RuntimePmAction;
 
 pub fn check(hw: &HardwareInfo) -> Vec<Finding> {
     check_with_knobs(hw, &Preset::Moderate.knobs())
@@ -81,10 +83,12 @@ pub fn check_with_knobs(hw: &HardwareInfo, knobs: &PresetKnobs) -> Vec<Finding>
                     "PCIe",
                     format!("ASPM policy at '{}' — target is '{}'", current, target),
                 )
+                .applies_on(PowerContext::Battery)
                 .current(current.as_str())
                 .recommended(target)
                 .impact(impact)
                 .path("/sys/module/pcie_aspm/parameters/policy")
+                .evidence_from(&hw.raw, "/sys/module/pcie_aspm/parameters/policy")
                 .weight(weight),
             );
         }
@@ -92,26 +96,86 @@ pub fn check_with_knobs(hw: &HardwareInfo, knobs: &PresetKnobs) -> Vec<Finding>
 
     // Check per-device runtime PM
     if knobs.pci_runtime_pm {
-        let non_auto = hw.pci.devices_without_runtime_pm();
+        let non_auto: Vec<_> = hw
+            .pci
+            .devices_without_runtime_pm()
+            .into_iter()
+            .filter(|d| quirk_for(d).is_none_or(|q| q.action != RuntimePmAction::NeverAuto))
+            .collect();
         if !non_auto.is_empty() {
-            findings.push(
-                Finding::new(
-                    Severity::Medium,
-                    "PCIe",
-                    format!(
-                        "{}/{} PCI devices not using runtime power management",
-                        non_auto.len(),
-                        hw.pci.devices.len()
-                    ),
+            let finding = Finding::new(
+                Severity::Medium,
+                "PCIe",
+                format!(
+                    "{}/{} PCI devices not using runtime power management",
+                    non_auto.len(),
+                    hw.pci.devices.len()
+                ),
+            )
+            .applies_on(PowerContext::Battery)
+            .current(format!("{} devices set to 'on'", non_auto.len()))
+            .recommended("All devices set to 'auto'")
+            .impact("~0.5W savings from idle device power gating")
+            .path("/sys/bus/pci/devices/*/power/control")
+            .weight(5);
+            findings.push(non_auto.iter().fold(finding, |finding, dev| {
+                finding.evidence_from(
+                    &hw.raw,
+                    &format!("sys/bus/pci/devices/{}/power/control", dev.address),
                 )
-                .current(format!("{} devices set to 'on'", non_auto.len()))
-                .recommended("All devices set to 'auto'")
-                .impact("~0.5W savings from idle device power gating")
-                .path("/sys/bus/pci/devices/*/power/control")
-                .weight(5),
-            );
+            }));
+        }
+
+        // Known-quirky devices (mostly SD card readers)
+        for dev in &hw.pci.devices {
+            let Some(quirk) = quirk_for(dev) else {
+                continue;
+            };
+            let path = format!("/sys/bus/pci/devices/{}/power/control", dev.address);
+            match quirk.action {
+                RuntimePmAction::NeedsModuleParam { module, param }
+                    if dev.runtime_pm.as_deref() != Some("auto") =>
+                {
+                    findings.push(
+                        Finding::new(
+                            Severity::Low,
+                            "PCIe",
+                            format!(
+                                "{} known to require {}.{} for runtime PM",
+                                quirk.name, module, param
+                            ),
+                        )
+                        .applies_on(PowerContext::Battery)
+                        .current(dev.runtime_pm.as_deref().unwrap_or("unknown"))
+                        .recommended(format!("options {} {}", module, param))
+                        .impact(quirk.note)
+                        .evidence_from(&hw.raw, &path)
+                        .path(path)
+                        .weight(2),
+                    );
+                }
+                RuntimePmAction::NeverAuto => {
+                    findings.push(
+                        Finding::new(
+                            Severity::Info,
+                            "PCIe",
+                            format!("{} runtime PM left off ({})", quirk.name, quirk.note),
+                        )
+                        .applies_on(PowerContext::Battery)
+                        .current(dev.runtime_pm.as_deref().unwrap_or("unknown"))
+                        .evidence_from(&hw.raw, &path)
+                        .path(path)
+                        .weight(0),
+                    );
+                }
+                _ => {}
+            }
         }
     }
 
     findings
 }
+
+fn quirk_for(dev: &PciDevice) -> Option<&'static crate::quirks::PciQuirk> {
+    crate::quirks::lookup_pci(dev.vendor.as_deref()?, dev.device.as_deref()?)
+}
@@ -0,0 +1,120 @@
+use crate::audit::{Finding, PowerContext, Severity};
+use crate::detect::HardwareInfo;
+use crate::detect::powercap::uw_to_watts;
+use crate::preset::PresetKnobs;
+use crate::profile::HardwareProfile;
+
+/// The CPU package's long-term power limit next to what the platform
+/// profile nominally allows, so a firmware or thermald cap that's lower
+/// (or higher) than expected shows up. Information only, unless
+/// `[aggressive] package_power_limit_w` asks for a lower one.
+pub fn check(
+    hw: &HardwareInfo,
+    knobs: &PresetKnobs,
+    profile: &dyn HardwareProfile,
+) -> Vec<Finding> {
+    let powercap = &hw.powercap;
+    let Some(zone) = powercap
+        .writable_packages()
+        .next()
+        .or_else(|| powercap.zones.iter().find(|z| z.is_package()))
+    else {
+        return Vec::new();
+    };
+    let Some(limit) = zone.power_limit_uw.filter(|&uw| uw > 0) else {
+        return Vec::new();
+    };
+    let limit_w = uw_to_watts(limit);
+
+    let platform_profile = hw.platform.platform_profile.as_deref();
+    let nominal = platform_profile.and_then(|p| Some((p, profile.nominal_tdp_w(p)?)));
+    let description = match nominal {
+        Some((name, tdp)) => format!(
+            "Package power limit {:.1}W, {}W nominal for platform profile {}",
+            limit_w, tdp, name
+        ),
+        None => format!("Package power limit {:.1}W", limit_w),
+    };
+
+    let floor = profile.package_power_floor_w();
+    let recommended = match knobs.package_power_limit_w {
+        Some(watts) => match crate::apply::powercap::refusal(watts, floor) {
+            Some(refusal) => format!("unchanged ({})", refusal),
+            None if !zone.writable => format!("{}W (zone is read-only)", watts),
+            None if crate::detect::powercap::watts_to_uw(watts) < limit => format!("{}W", watts),
+            None => "unchanged".to_string(),
+        },
+        None => "unchanged".to_string(),
+    };
+
+    let path = zone.limit_path();
+    let mut finding = Finding::new(Severity::Info, "CPU", description)
+        .applies_on(PowerContext::Battery)
+        .current(format!("{:.1}W", limit_w))
+        .recommended(recommended)
+        .impact("Caps sustained CPU+iGPU power; set [aggressive] package_power_limit_w to lower it")
+        .path(&path)
+        .evidence_from(&hw.raw, &path)
+        .weight(0);
+    if !zone.writable {
+        finding = finding.manual_only();
+    }
+    vec![finding]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::powercap::PowercapZone;
+    use crate::preset::Preset;
+    use crate::profile::{framework16_amd::Framework16Amd, generic_laptop::GenericLaptop};
+    use crate::sysfs::SysfsRoot;
+    use tempfile::TempDir;
+
+    fn hw_with_limit(watts: u64, writable: bool) -> (TempDir, HardwareInfo) {
+        let tmp = TempDir::new().unwrap();
+        let mut hw = HardwareInfo::detect(&SysfsRoot::new(tmp.path()));
+        hw.powercap.zones = vec![PowercapZone {
+            path: "sys/class/powercap/intel-rapl:0".to_string(),
+            name: "package-0".to_string(),
+            power_limit_uw: Some(watts * 1_000_000),
+            max_power_uw: None,
+            writable,
+        }];
+        hw.platform.platform_profile = Some("low-power".to_string());
+        (tmp, hw)
+    }
+
+    #[test]
+    fn test_limit_against_nominal_tdp() {
+        let (_tmp, hw) = hw_with_limit(28, true);
+        let mut knobs = Preset::Supersaver.knobs();
+        let findings = check(&hw, &knobs, &Framework16Amd);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].description,
+            "Package power limit 28.0W, 30W nominal for platform profile low-power"
+        );
+        assert_eq!(findings[0].recommended_value, "unchanged");
+
+        knobs.package_power_limit_w = Some(15);
+        let findings = check(&hw, &knobs, &GenericLaptop);
+        assert_eq!(findings[0].description, "Package power limit 28.0W");
+        assert_eq!(findings[0].recommended_value, "15W");
+
+        // Framework 16's floor is 10W
+        knobs.package_power_limit_w = Some(9);
+        let findings = check(&hw, &knobs, &Framework16Amd);
+        assert_eq!(
+            findings[0].recommended_value,
+            "unchanged (9W is below this machine's 10W floor)"
+        );
+    }
+
+    #[test]
+    fn test_read_only_limit_is_manual() {
+        let (_tmp, hw) = hw_with_limit(28, false);
+        let findings = check(&hw, &Preset::Supersaver.knobs(), &GenericLaptop);
+        assert_eq!(findings[0].remedy, crate::audit::Remedy::ManualOnly);
+    }
+}
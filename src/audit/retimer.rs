@@ -0,0 +1,120 @@
+use crate::audit::{Finding, PowerContext, Severity};
+use crate::detect::HardwareInfo;
+use crate::detect::typec::TypecInfo;
+use crate::idle_drain::{DrainAlert, HISTORY_PATH};
+
+/// A stuck retimer can't be seen directly, only as idle draw that stays
+/// above the usual. Power cycling the port clears it; listing what's
+/// plugged in helps pick which one.
+pub fn check(hw: &HardwareInfo, alert: Option<&DrainAlert>) -> Vec<Finding> {
+    let Some(alert) = alert else {
+        return Vec::new();
+    };
+    vec![
+        Finding::new(Severity::High, "USB-C", summary(alert))
+            .applies_on(PowerContext::Battery)
+            .current(format!("{:.1}W idle", alert.watts))
+            .recommended(remedy(&hw.typec))
+            .impact(format!(
+                "~{:.1}W until the port is power cycled (usual idle {:.1}W)",
+                alert.excess_watts(),
+                alert.baseline_watts
+            ))
+            .path(HISTORY_PATH)
+            .manual_only()
+            .weight(8),
+    ]
+}
+
+pub fn summary(alert: &DrainAlert) -> String {
+    format!(
+        "Idle draw {:.1}W above usual for {} min, a USB-C retimer may be stuck",
+        alert.excess_watts(),
+        alert.minutes()
+    )
+}
+
+/// What to do about it, naming the ports that have something plugged in.
+pub fn remedy(typec: &TypecInfo) -> String {
+    let connected = connected_ports(typec);
+    if connected.is_empty() {
+        "Replug the last dock or expansion card removed, or shut down fully".to_string()
+    } else {
+        format!(
+            "Replug the affected port's card or dock, or shut down fully (connected: {})",
+            connected.join(", ")
+        )
+    }
+}
+
+/// "port1 (powered by the laptop)" for every port with a partner.
+fn connected_ports(typec: &TypecInfo) -> Vec<String> {
+    typec
+        .ports
+        .iter()
+        .filter(|p| p.partner.is_some())
+        .map(|p| {
+            let role = if p.partner_draws_bus_power() {
+                "powered by the laptop"
+            } else if p.power_role.as_deref() == Some("sink") {
+                "charging the laptop"
+            } else {
+                "connected"
+            };
+            format!("{} ({})", p.name, role)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::typec::{TypecPartner, TypecPort};
+    use crate::sysfs::SysfsRoot;
+    use tempfile::TempDir;
+
+    fn alert() -> DrainAlert {
+        DrainAlert {
+            since: 0,
+            last_seen: 900,
+            baseline_watts: 6.0,
+            watts: 7.6,
+        }
+    }
+
+    #[test]
+    fn test_no_alert_no_finding() {
+        let tmp = TempDir::new().unwrap();
+        let hw = HardwareInfo::detect(&SysfsRoot::new(tmp.path()));
+        assert!(check(&hw, None).is_empty());
+    }
+
+    #[test]
+    fn test_alert_lists_connected_ports() {
+        let tmp = TempDir::new().unwrap();
+        let mut hw = HardwareInfo::detect(&SysfsRoot::new(tmp.path()));
+        let port = |name: &str, role: &str, partner: bool| TypecPort {
+            name: name.to_string(),
+            power_role: Some(role.to_string()),
+            partner: partner.then(TypecPartner::default),
+            ..Default::default()
+        };
+        hw.typec.ports = vec![
+            port("port0", "sink", true),
+            port("port1", "source", false),
+            port("port2", "source", true),
+        ];
+
+        let findings = check(&hw, Some(&alert()));
+        assert_eq!(findings.len(), 1);
+        let finding = &findings[0];
+        assert_eq!(finding.severity, Severity::High);
+        assert!(finding.description.contains("1.6W above usual for 15 min"));
+        assert!(
+            finding
+                .recommended_value
+                .contains("connected: port0 (charging the laptop), port2 (powered by the laptop)")
+        );
+        assert_eq!(finding.remedy, crate::audit::Remedy::ManualOnly);
+    }
+}
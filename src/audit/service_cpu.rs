@@ -0,0 +1,361 @@
+use crate::audit::{Finding, PowerContext, Severity};
+use crate::detect::HardwareInfo;
+use crate::sysfs::SysfsRoot;
+
+/// Cumulative CPU time since boot above which a watched unit is reported.
+/// Ten minutes of CPU is well past what these services need when idle.
+const CPU_SECONDS_THRESHOLD: f64 = 600.0;
+
+const CGROUP_ROOT: &str = "sys/fs/cgroup";
+
+/// Which systemd instance a unit runs under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitScope {
+    System,
+    User,
+}
+
+/// A userspace service known to dominate battery drain when left running.
+#[derive(Debug, Clone, Copy)]
+pub struct WatchedUnit {
+    pub unit: &'static str,
+    pub scope: UnitScope,
+    pub mitigation: &'static str,
+}
+
+const WATCHLIST: &[WatchedUnit] = &[
+    WatchedUnit {
+        unit: "tracker-miner-fs-3.service",
+        scope: UnitScope::User,
+        mitigation: "systemctl --user mask tracker-miner-fs-3.service, or limit indexed folders",
+    },
+    WatchedUnit {
+        unit: "localsearch-3.service",
+        scope: UnitScope::User,
+        mitigation: "systemctl --user mask localsearch-3.service, or limit indexed folders",
+    },
+    WatchedUnit {
+        unit: "plasma-baloorunner.service",
+        scope: UnitScope::User,
+        mitigation: "balooctl6 disable, or exclude large folders from indexing",
+    },
+    WatchedUnit {
+        unit: "kde-baloo.service",
+        scope: UnitScope::User,
+        mitigation: "balooctl6 disable, or exclude large folders from indexing",
+    },
+    WatchedUnit {
+        unit: "clamav-freshclam.service",
+        scope: UnitScope::System,
+        mitigation: "lower Checks in freshclam.conf, or run it from a daily timer",
+    },
+    WatchedUnit {
+        unit: "clamav-daemon.service",
+        scope: UnitScope::System,
+        mitigation: "systemctl mask clamav-daemon.service if on-access scanning isn't needed",
+    },
+    WatchedUnit {
+        unit: "packagekit.service",
+        scope: UnitScope::System,
+        mitigation: "systemctl mask packagekit.service, or disable background refresh",
+    },
+    WatchedUnit {
+        unit: "plocate-updatedb.service",
+        scope: UnitScope::System,
+        mitigation: "move plocate-updatedb.timer to a weekly OnCalendar",
+    },
+    WatchedUnit {
+        unit: "man-db.service",
+        scope: UnitScope::System,
+        mitigation: "move man-db.timer to a weekly OnCalendar",
+    },
+];
+
+/// Parse `usage_usec` from a cgroup v2 `cpu.stat` file into CPU seconds.
+pub fn parse_cpu_stat(content: &str) -> Option<f64> {
+    content.lines().find_map(|line| {
+        let (key, value) = line.split_once(' ')?;
+        if key != "usage_usec" {
+            return None;
+        }
+        value
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .map(|usec| usec as f64 / 1e6)
+    })
+}
+
+/// Unit names from `systemctl list-unit-files --no-legend --plain` output
+/// whose state means they can run: enabled, or started by something else
+/// (static units behind a timer, indirect units behind a socket).
+pub fn parse_unit_files(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let unit = parts.next()?;
+            let state = parts.next()?;
+            let runnable = state.starts_with("enabled") || state == "static" || state == "indirect";
+            runnable.then(|| unit.to_string())
+        })
+        .collect()
+}
+
+/// Query every watched unit of `scope` in a single systemctl call. Units
+/// that aren't installed are simply absent from the output.
+fn enabled_units(scope: UnitScope) -> Vec<String> {
+    let units: Vec<&str> = WATCHLIST
+        .iter()
+        .filter(|w| w.scope == scope)
+        .map(|w| w.unit)
+        .collect();
+
+    let mut cmd = crate::cmd::Cmd::new("systemctl");
+    if scope == UnitScope::User {
+        cmd.arg("--global");
+    }
+    cmd.args(["list-unit-files", "--no-legend", "--plain"])
+        .args(&units);
+
+    match cmd.output() {
+        Ok(out) => parse_unit_files(&String::from_utf8_lossy(&out.stdout)),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// cgroup directories a unit may live in: system.slice for system units,
+/// every logged-in user's manager slices for user units.
+fn unit_cgroups(sysfs: &SysfsRoot, unit: &WatchedUnit) -> Vec<String> {
+    match unit.scope {
+        UnitScope::System => vec![format!("{}/system.slice/{}", CGROUP_ROOT, unit.unit)],
+        UnitScope::User => {
+            let users_base = format!("{}/user.slice", CGROUP_ROOT);
+            let mut dirs = Vec::new();
+            for user_slice in sysfs.list_dir(&users_base).unwrap_or_default() {
+                let Some(uid) = user_slice
+                    .strip_prefix("user-")
+                    .and_then(|s| s.strip_suffix(".slice"))
+                else {
+                    continue;
+                };
+                let manager = format!("{}/{}/user@{}.service", users_base, user_slice, uid);
+                for slice in sysfs.list_dir(&manager).unwrap_or_default() {
+                    if slice.ends_with(".slice") {
+                        dirs.push(format!("{}/{}/{}", manager, slice, unit.unit));
+                    }
+                }
+            }
+            dirs
+        }
+    }
+}
+
+/// CPU seconds the unit has used since boot, summed across users for user
+/// units. `None` when no readable cgroup exists (not running, cgroup v1,
+/// or permission denied).
+/// Total CPU time across the unit's cgroups, with the cpu.stat files it
+/// was summed from.
+fn cpu_seconds(sysfs: &SysfsRoot, unit: &WatchedUnit) -> Option<(f64, Vec<String>)> {
+    let samples: Vec<(f64, String)> = unit_cgroups(sysfs, unit)
+        .iter()
+        .filter_map(|dir| {
+            let path = format!("{}/cpu.stat", dir);
+            sysfs
+                .read_optional(&path)
+                .ok()
+                .flatten()
+                .and_then(|content| parse_cpu_stat(&content))
+                .map(|seconds| (seconds, path))
+        })
+        .collect();
+
+    (!samples.is_empty()).then(|| {
+        let total = samples.iter().map(|(seconds, _)| seconds).sum();
+        (total, samples.into_iter().map(|(_, path)| path).collect())
+    })
+}
+
+/// Info findings for watched units in `enabled` whose CPU time exceeds the
+/// threshold. Advisory only: nothing here is applied.
+pub fn check_units(sysfs: &SysfsRoot, enabled: &[String]) -> Vec<Finding> {
+    // cpu.stat with usage_usec only exists on the unified (v2) hierarchy
+    if !sysfs.exists(format!("{}/cgroup.controllers", CGROUP_ROOT)) {
+        return Vec::new();
+    }
+
+    WATCHLIST
+        .iter()
+        .filter(|w| enabled.iter().any(|u| u == w.unit))
+        .filter_map(|w| {
+            let (seconds, stat_paths) = cpu_seconds(sysfs, w)?;
+            if seconds < CPU_SECONDS_THRESHOLD {
+                return None;
+            }
+            let raw = sysfs.raw_values();
+            let finding = Finding::new(
+                Severity::Info,
+                "Services",
+                format!("{} has used {:.0}s of CPU since boot", w.unit, seconds),
+            )
+            .applies_on(PowerContext::Battery)
+            .current(format!("{:.0} CPU-seconds", seconds))
+            .recommended(w.mitigation)
+            .impact("Background CPU load keeps cores out of deep idle on battery")
+            .path(w.unit)
+            .weight(0);
+            Some(
+                stat_paths
+                    .iter()
+                    .fold(finding, |finding, path| finding.evidence_from(&raw, path)),
+            )
+        })
+        .collect()
+}
+
+pub fn check(hw: &HardwareInfo, sysfs: &SysfsRoot) -> Vec<Finding> {
+    if !hw.ac.is_on_battery() {
+        return Vec::new();
+    }
+
+    let mut enabled = enabled_units(UnitScope::System);
+    enabled.extend(enabled_units(UnitScope::User));
+    check_units(sysfs, &enabled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write_cpu_stat(root: &std::path::Path, dir: &str, usage_usec: u64) {
+        let path = root.join(dir);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(
+            path.join("cpu.stat"),
+            format!(
+                "usage_usec {}\nuser_usec {}\nsystem_usec 0\nnr_periods 0\n",
+                usage_usec, usage_usec
+            ),
+        )
+        .unwrap();
+    }
+
+    fn cgroup_v2_root() -> TempDir {
+        let tmp = TempDir::new().unwrap();
+        let cg = tmp.path().join(CGROUP_ROOT);
+        fs::create_dir_all(&cg).unwrap();
+        fs::write(cg.join("cgroup.controllers"), "cpuset cpu io memory pids\n").unwrap();
+        tmp
+    }
+
+    #[test]
+    fn test_parse_cpu_stat() {
+        let stat = "usage_usec 1234567890\nuser_usec 1000000000\nsystem_usec 234567890\n\
+                    nr_periods 0\nnr_throttled 0\nthrottled_usec 0\n";
+        assert_eq!(parse_cpu_stat(stat), Some(1234.56789));
+        assert_eq!(parse_cpu_stat("user_usec 5\n"), None);
+        assert_eq!(parse_cpu_stat("usage_usec garbage\n"), None);
+        assert_eq!(parse_cpu_stat(""), None);
+    }
+
+    #[test]
+    fn test_parse_unit_files_keeps_runnable_states() {
+        let output = "clamav-freshclam.service enabled enabled\n\
+                      man-db.service static -\n\
+                      packagekit.service masked enabled\n\
+                      clamav-daemon.service disabled enabled\n\
+                      tracker-miner-fs-3.service enabled-runtime enabled\n";
+        assert_eq!(
+            parse_unit_files(output),
+            vec![
+                "clamav-freshclam.service",
+                "man-db.service",
+                "tracker-miner-fs-3.service"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_only_units_over_threshold_are_reported() {
+        let tmp = cgroup_v2_root();
+        // 20 minutes: over
+        write_cpu_stat(
+            tmp.path(),
+            "sys/fs/cgroup/system.slice/clamav-freshclam.service",
+            1_200_000_000,
+        );
+        // 1 minute: under
+        write_cpu_stat(
+            tmp.path(),
+            "sys/fs/cgroup/system.slice/packagekit.service",
+            60_000_000,
+        );
+        let sysfs = SysfsRoot::new(tmp.path());
+
+        let enabled = vec![
+            "clamav-freshclam.service".to_string(),
+            "packagekit.service".to_string(),
+        ];
+        let findings = check_units(&sysfs, &enabled);
+        assert_eq!(findings.len(), 1);
+        assert!(
+            findings[0]
+                .description
+                .starts_with("clamav-freshclam.service")
+        );
+        assert_eq!(findings[0].severity, Severity::Info);
+        assert_eq!(findings[0].current_value, "1200 CPU-seconds");
+    }
+
+    #[test]
+    fn test_user_units_sum_across_sessions() {
+        let tmp = cgroup_v2_root();
+        let base = "sys/fs/cgroup/user.slice";
+        write_cpu_stat(
+            tmp.path(),
+            &format!(
+                "{}/user-1000.slice/user@1000.service/session.slice/tracker-miner-fs-3.service",
+                base
+            ),
+            400_000_000,
+        );
+        write_cpu_stat(
+            tmp.path(),
+            &format!(
+                "{}/user-1001.slice/user@1001.service/app.slice/tracker-miner-fs-3.service",
+                base
+            ),
+            300_000_000,
+        );
+        let sysfs = SysfsRoot::new(tmp.path());
+
+        let findings = check_units(&sysfs, &["tracker-miner-fs-3.service".to_string()]);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].current_value, "700 CPU-seconds");
+    }
+
+    #[test]
+    fn test_degrades_on_cgroup_v1_or_unreadable_stats() {
+        // No cgroup.controllers: v1 hierarchy, where cpu.stat means something else
+        let tmp = TempDir::new().unwrap();
+        write_cpu_stat(
+            tmp.path(),
+            "sys/fs/cgroup/system.slice/clamav-daemon.service",
+            9_000_000_000,
+        );
+        let sysfs = SysfsRoot::new(tmp.path());
+        assert!(check_units(&sysfs, &["clamav-daemon.service".to_string()]).is_empty());
+
+        // v2, but the unit's stats don't parse
+        let tmp = cgroup_v2_root();
+        let dir = tmp
+            .path()
+            .join("sys/fs/cgroup/system.slice/clamav-daemon.service");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("cpu.stat"), "nr_periods 0\n").unwrap();
+        let sysfs = SysfsRoot::new(tmp.path());
+        assert!(check_units(&sysfs, &["clamav-daemon.service".to_string()]).is_empty());
+    }
+}
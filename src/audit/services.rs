@@ -79,14 +79,14 @@ pub fn check() -> Vec<Finding> {
 }
 
 fn is_service_active(service: &str) -> bool {
-    std::process::Command::new("systemctl")
+    crate::cmd::Cmd::new("systemctl")
         .args(["is-active", "--quiet", service])
         .status()
         .is_ok_and(|s| s.success())
 }
 
 fn is_service_enabled(service: &str) -> bool {
-    std::process::Command::new("systemctl")
+    crate::cmd::Cmd::new("systemctl")
         .args(["is-enabled", "--quiet", service])
         .status()
         .is_ok_and(|s| s.success())
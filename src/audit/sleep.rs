@@ -1,19 +1,126 @@
-use crate::audit::{Finding, Severity};
+use crate::apply::WakeupBaseline;
+use crate::audit::{Finding, PowerContext, Severity};
 use crate::detect::HardwareInfo;
+use crate::detect::platform::{AcpiWakeupSource, PlatformInfo};
+use crate::detect::usb_topology;
 use crate::sysfs::SysfsRoot;
 
 /// Controllers that should keep wakeup enabled (internal devices).
 const ESSENTIAL_WAKE_CONTROLLERS: &[&str] = &["XHC0"];
 
-pub fn check(hw: &HardwareInfo, sysfs: &SysfsRoot) -> Vec<Finding> {
+/// Wakes per suspend at which a single source counts as the culprit.
+/// A genuine wake source (lid, power button) sits near 1.
+const SPURIOUS_WAKES_PER_SUSPEND: f64 = 3.0;
+
+/// A wakeup source's activity since the baseline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WakeRate {
+    pub name: String,
+    /// ACPI wake device the source belongs to, if any (e.g. "XHC1")
+    pub acpi_device: Option<String>,
+    pub wakeups: u64,
+    pub activations: u64,
+    pub suspends: u64,
+    pub per_suspend: f64,
+}
+
+impl WakeRate {
+    /// Source name with its ACPI device when known: "0000:c1:00.3 (XHC1)".
+    pub fn label(&self) -> String {
+        match &self.acpi_device {
+            Some(acpi) => format!("{} ({})", self.name, acpi),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Counter increase since `baseline`. A counter lower than its baseline was
+/// reset (reboot) or wrapped, so everything it holds happened since.
+fn counter_delta(baseline: u64, current: u64) -> u64 {
+    if current >= baseline {
+        current - baseline
+    } else {
+        current
+    }
+}
+
+/// Rank wakeup sources by wakes per suspend since `baseline` (or since boot
+/// without one), highest first. Sources that appeared after the baseline
+/// count from zero; sources that have since disappeared are dropped. Empty
+/// when no suspend happened in the window, since there's nothing to divide by.
+pub fn rank_wake_sources(
+    baseline: Option<&WakeupBaseline>,
+    platform: &PlatformInfo,
+) -> Vec<WakeRate> {
+    let Some(current_suspends) = platform.suspend_count else {
+        return Vec::new();
+    };
+    let base_suspends = baseline.and_then(|b| b.suspend_count).unwrap_or(0);
+    let suspends = counter_delta(base_suspends, current_suspends);
+    if suspends == 0 {
+        return Vec::new();
+    }
+    // Once the suspend counter reset, so did every source counter
+    let rebooted = current_suspends < base_suspends;
+
+    let mut rates: Vec<WakeRate> = platform
+        .wakeup_counters
+        .iter()
+        .filter_map(|counter| {
+            let base = baseline
+                .filter(|_| !rebooted)
+                .and_then(|b| b.sources.iter().find(|s| s.name == counter.name));
+            let wakeups = counter_delta(base.map_or(0, |b| b.wakeup_count), counter.wakeup_count);
+            if wakeups == 0 {
+                return None;
+            }
+            Some(WakeRate {
+                name: counter.name.clone(),
+                acpi_device: platform.acpi_device_for(counter).map(String::from),
+                wakeups,
+                activations: counter_delta(
+                    base.map_or(0, |b| b.active_count),
+                    counter.active_count,
+                ),
+                suspends,
+                per_suspend: wakeups as f64 / suspends as f64,
+            })
+        })
+        .collect();
+
+    rates.sort_by(|a, b| {
+        b.per_suspend
+            .total_cmp(&a.per_suspend)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    rates
+}
+
+/// Whether bop switches sleep to s2idle: an AMD CPU on the modern
+/// (uPEP/LPS0) suspend path where the kernel still selected `deep`.
+pub fn forces_s2idle(hw: &HardwareInfo) -> bool {
+    hw.cpu.is_amd() && hw.platform.deep_selected_over_s2idle()
+}
+
+/// `baseline` is the wakeup baseline from the last apply, if any; wake rates
+/// count from boot without one.
+pub fn check(
+    hw: &HardwareInfo,
+    sysfs: &SysfsRoot,
+    baseline: Option<&WakeupBaseline>,
+) -> Vec<Finding> {
     let mut findings = Vec::new();
 
     // Check for unnecessary ACPI wakeup sources
     let mut unnecessary_enabled = Vec::new();
     for source in &hw.platform.acpi_wakeup_sources {
-        if source.enabled && !ESSENTIAL_WAKE_CONTROLLERS.contains(&source.device.as_str()) {
+        // A shared name can't be toggled on its own, by bop or by hand
+        if source.enabled
+            && !source.ambiguous
+            && !ESSENTIAL_WAKE_CONTROLLERS.contains(&source.device.as_str())
+        {
             // Check if this controller has any real devices attached
-            let has_devices = controller_has_devices(&source.device, sysfs);
+            let has_devices = controller_has_devices(source, sysfs);
             if !has_devices {
                 unnecessary_enabled.push(source.device.clone());
             }
@@ -30,16 +137,77 @@ pub fn check(hw: &HardwareInfo, sysfs: &SysfsRoot) -> Vec<Finding> {
                     unnecessary_enabled.len()
                 ),
             )
+            .applies_on(PowerContext::Battery)
             .current(format!("Enabled: {}", unnecessary_enabled.join(", ")))
             .recommended("Disable all except XHC0 (internal keyboard/BT)")
             .impact("Reduces spurious wakeups during sleep")
             .path("/proc/acpi/wakeup")
+            .evidence_from(&hw.raw, "/proc/acpi/wakeup")
             .weight(6),
         );
     }
 
+    // Rank wake sources by how often they fire per suspend since apply
+    let rates = rank_wake_sources(baseline, &hw.platform);
+    if let Some(top) = rates.first()
+        && top.per_suspend >= SPURIOUS_WAKES_PER_SUSPEND
+    {
+        let since = if baseline.is_some() {
+            "since apply"
+        } else {
+            "since boot"
+        };
+        let mut current = format!(
+            "{} wakeups over {} suspends {} (active {} times)",
+            top.wakeups, top.suspends, since, top.activations
+        );
+        let others: Vec<String> = rates[1..]
+            .iter()
+            .take(2)
+            .map(|r| format!("{} {:.1}/suspend", r.label(), r.per_suspend))
+            .collect();
+        if !others.is_empty() {
+            current.push_str(&format!("; next: {}", others.join(", ")));
+        }
+        findings.push(
+            Finding::new(
+                Severity::High,
+                "Sleep",
+                format!(
+                    "{} wakes the system {:.1} times per suspend",
+                    top.label(),
+                    top.per_suspend
+                ),
+            )
+            .applies_on(PowerContext::Battery)
+            .current(current)
+            .recommended("Disable wakeup for this device (power/wakeup = disabled)")
+            .impact("Spurious wakes keep the system out of sleep and drain the battery overnight")
+            .path("/sys/class/wakeup")
+            .weight(7),
+        );
+    }
+
     // Check sleep state
-    if hw.platform.mem_sleep.as_deref() != Some("s2idle")
+    if forces_s2idle(hw) {
+        findings.push(
+            Finding::new(
+                Severity::Medium,
+                "Sleep",
+                "Kernel selects deep sleep on an s2idle platform",
+            )
+            .applies_on(PowerContext::Battery)
+            .current(format!(
+                "deep (offered: {})",
+                hw.platform.mem_sleep_available.join(", ")
+            ))
+            .recommended("s2idle, and mem_sleep_default=s2idle on the kernel command line")
+            .impact("This AMD platform is built for s2idle; resume from deep can fail or hang")
+            .path("/sys/power/mem_sleep")
+            .evidence_from(&hw.raw, "/sys/power/mem_sleep")
+            .weight(6),
+        );
+    } else if hw.platform.mem_sleep.as_deref() != Some("s2idle")
         && let Some(ref mem_sleep) = hw.platform.mem_sleep
     {
         findings.push(
@@ -48,10 +216,13 @@ pub fn check(hw: &HardwareInfo, sysfs: &SysfsRoot) -> Vec<Finding> {
                 "Sleep",
                 "System using deep sleep instead of s2idle",
             )
+            .applies_on(PowerContext::Battery)
             .current(mem_sleep)
             .recommended("s2idle (for AMD platforms)")
             .impact("s2idle is recommended for modern AMD; deep may work but has less testing")
             .path("/sys/power/mem_sleep")
+            .evidence_from(&hw.raw, "/sys/power/mem_sleep")
+            .manual_only()
             .weight(2),
         );
     }
@@ -59,68 +230,131 @@ pub fn check(hw: &HardwareInfo, sysfs: &SysfsRoot) -> Vec<Finding> {
     findings
 }
 
-/// Check if a USB controller (e.g., XHC1) has actual USB devices connected.
-/// This traces through the PCI device -> USB root hub -> USB device chain.
-fn controller_has_devices(controller_name: &str, sysfs: &SysfsRoot) -> bool {
-    // Map controller names to PCI search patterns
-    // We look through USB buses to find devices beyond root hubs
-    let usb_base = "sys/bus/usb/devices";
-    let Ok(usb_devices) = sysfs.list_dir(usb_base) else {
-        return false;
-    };
+/// Whether anything is plugged in behind a wakeup source's controller.
+/// Sources that aren't PCI devices have no USB behind them.
+fn controller_has_devices(source: &AcpiWakeupSource, sysfs: &SysfsRoot) -> bool {
+    source
+        .sysfs_node
+        .as_deref()
+        .and_then(|node| node.strip_prefix("pci:"))
+        .is_some_and(|pci| usb_topology::controller_has_devices(sysfs, pci))
+}
 
-    // Find root hubs and their children for this controller
-    // The controller name appears in the ACPI path -- we need to match via PCI address
-    // For simplicity, we check if any non-root-hub USB devices exist on the buses
-    // associated with this controller
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply::WakeupBaselineEntry;
+    use crate::detect::platform::WakeupCounter;
 
-    // First, find the PCI address of this controller from ACPI wakeup
-    // The wakeup source format includes the PCI device path
-    let proc_wakeup = match sysfs.read("proc/acpi/wakeup") {
-        Ok(w) => w,
-        Err(_) => return false,
-    };
+    fn platform(suspends: u64, counters: &[(&str, u64)]) -> PlatformInfo {
+        PlatformInfo {
+            suspend_count: Some(suspends),
+            wakeup_counters: counters
+                .iter()
+                .map(|(name, count)| WakeupCounter {
+                    name: name.to_string(),
+                    wakeup_count: *count,
+                    active_count: *count,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
 
-    let mut pci_addr = None;
-    for line in proc_wakeup.lines() {
-        if line.starts_with(controller_name) {
-            // Extract PCI address from the line (last field, format: pci:0000:XX:XX.X)
-            if let Some(pci_part) = line.split_whitespace().find(|p| p.starts_with("pci:")) {
-                pci_addr = Some(pci_part.trim_start_matches("pci:").to_string());
-            }
-            break;
+    fn baseline(suspends: u64, counters: &[(&str, u64)]) -> WakeupBaseline {
+        WakeupBaseline {
+            suspend_count: Some(suspends),
+            sources: counters
+                .iter()
+                .map(|(name, count)| WakeupBaselineEntry {
+                    name: name.to_string(),
+                    wakeup_count: *count,
+                    active_count: *count,
+                })
+                .collect(),
         }
     }
 
-    let Some(pci_addr) = pci_addr else {
-        return false;
-    };
+    #[test]
+    fn test_rank_by_wakes_per_suspend_since_baseline() {
+        let base = baseline(10, &[("PNP0C0D:00", 10), ("0000:c1:00.3", 50)]);
+        let now = platform(14, &[("PNP0C0D:00", 14), ("0000:c1:00.3", 450)]);
 
-    // Now find USB buses associated with this PCI device
-    // USB root hubs are at /sys/bus/usb/devices/usbN, and they link back to the PCI device
-    for usb_dev in &usb_devices {
-        if !usb_dev.starts_with("usb") {
-            continue;
-        }
+        let rates = rank_wake_sources(Some(&base), &now);
+        assert_eq!(rates.len(), 2);
+        assert_eq!(rates[0].name, "0000:c1:00.3");
+        assert_eq!(rates[0].wakeups, 400);
+        assert_eq!(rates[0].activations, 400);
+        assert_eq!(rates[0].suspends, 4);
+        assert_eq!(rates[0].per_suspend, 100.0);
+        assert_eq!(rates[1].name, "PNP0C0D:00");
+        assert_eq!(rates[1].per_suspend, 1.0);
+    }
 
-        // Check if this root hub's parent PCI device matches
-        let dev_path = format!("{}/{}", usb_base, usb_dev);
-        let resolved = sysfs.path(&dev_path);
-        if let Ok(canonical) = std::fs::canonicalize(&resolved) {
-            let canonical_str = canonical.to_string_lossy();
-            if canonical_str.contains(&pci_addr) {
-                // This root hub belongs to our controller
-                // Check if it has any child devices (beyond the root hub itself)
-                let bus_num = usb_dev.trim_start_matches("usb");
-                for other_dev in &usb_devices {
-                    // Child devices have format: N-X or N-X.Y (where N is bus number)
-                    if other_dev.starts_with(&format!("{}-", bus_num)) && !other_dev.contains(':') {
-                        return true;
-                    }
-                }
-            }
-        }
+    #[test]
+    fn test_rank_attributes_sources_to_acpi_devices() {
+        use crate::detect::platform::AcpiWakeupSource;
+        let source = |device: &str, node: &str| AcpiWakeupSource {
+            device: device.to_string(),
+            sysfs_node: Some(node.to_string()),
+            status: "enabled".to_string(),
+            enabled: true,
+            ambiguous: false,
+        };
+        let mut now = platform(2, &[("0000:c1:00.4", 20), ("xhci-hcd.3.auto", 8)]);
+        now.wakeup_counters[1].device = Some("0000:c3:00.3".to_string());
+        now.acpi_wakeup_sources = vec![
+            source("XHC1", "pci:0000:c1:00.4"),
+            source("XHC3", "pci:0000:c3:00.3"),
+        ];
+
+        let rates = rank_wake_sources(None, &now);
+        assert_eq!(rates[0].acpi_device.as_deref(), Some("XHC1"));
+        assert_eq!(rates[0].label(), "0000:c1:00.4 (XHC1)");
+        assert_eq!(rates[1].label(), "xhci-hcd.3.auto (XHC3)");
     }
 
-    false
+    #[test]
+    fn test_rank_without_baseline_counts_since_boot() {
+        let now = platform(5, &[("PNP0C0D:00", 5), ("AMDI0010:00", 0)]);
+        let rates = rank_wake_sources(None, &now);
+        assert_eq!(rates.len(), 1);
+        assert_eq!(rates[0].per_suspend, 1.0);
+    }
+
+    #[test]
+    fn test_rank_handles_reset_counters() {
+        // Reboot since apply: suspend and source counters restart from zero
+        let base = baseline(40, &[("0000:c1:00.3", 900)]);
+        let now = platform(2, &[("0000:c1:00.3", 30)]);
+        let rates = rank_wake_sources(Some(&base), &now);
+        assert_eq!(rates[0].wakeups, 30);
+        assert_eq!(rates[0].suspends, 2);
+        assert_eq!(rates[0].per_suspend, 15.0);
+
+        // A single source counter wrapping while the suspend count didn't
+        let base = baseline(3, &[("0000:c1:00.3", u64::MAX - 5)]);
+        let now = platform(5, &[("0000:c1:00.3", 8)]);
+        assert_eq!(rank_wake_sources(Some(&base), &now)[0].wakeups, 8);
+    }
+
+    #[test]
+    fn test_rank_sources_appearing_and_disappearing() {
+        let base = baseline(10, &[("gone:00", 5), ("PNP0C0D:00", 10)]);
+        let now = platform(12, &[("PNP0C0D:00", 12), ("new:00", 9)]);
+        let rates = rank_wake_sources(Some(&base), &now);
+        let names: Vec<&str> = rates.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["new:00", "PNP0C0D:00"]);
+        assert_eq!(rates[0].per_suspend, 4.5);
+    }
+
+    #[test]
+    fn test_rank_empty_without_suspends() {
+        let base = baseline(7, &[("0000:c1:00.3", 1)]);
+        assert!(rank_wake_sources(Some(&base), &platform(7, &[("0000:c1:00.3", 99)])).is_empty());
+        let mut unknown = platform(7, &[("0000:c1:00.3", 99)]);
+        unknown.suspend_count = None;
+        assert!(rank_wake_sources(None, &unknown).is_empty());
+    }
 }
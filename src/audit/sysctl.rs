@@ -1,4 +1,4 @@
-use crate::audit::{Finding, Severity};
+use crate::audit::{Finding, PowerContext, Severity};
 use crate::preset::PresetKnobs;
 use crate::sysfs::SysfsRoot;
 
@@ -18,10 +18,12 @@ pub fn check_with_knobs(sysfs: &SysfsRoot, knobs: &PresetKnobs) -> Vec<Finding>
                 "Kernel",
                 "NMI watchdog enabled - generates interrupts that prevent deep C-states",
             )
+            .applies_on(PowerContext::Battery)
             .current("1")
             .recommended("0")
             .impact("~0.1-0.5W savings")
             .path("/proc/sys/kernel/nmi_watchdog")
+            .evidence_from(&sysfs.raw_values(), "proc/sys/kernel/nmi_watchdog")
             .weight(4),
         );
     }
@@ -39,10 +41,12 @@ pub fn check_with_knobs(sysfs: &SysfsRoot, knobs: &PresetKnobs) -> Vec<Finding>
                 "Kernel",
                 "Disk writeback interval too frequent - wakes storage unnecessarily",
             )
+            .applies_on(PowerContext::Battery)
             .current(&val)
             .recommended(target.to_string())
             .impact("Reduces storage wakeups (minor savings on NVMe)")
             .path("/proc/sys/vm/dirty_writeback_centisecs")
+            .evidence_from(&sysfs.raw_values(), "proc/sys/vm/dirty_writeback_centisecs")
             .weight(2),
         );
     }
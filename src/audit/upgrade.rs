@@ -0,0 +1,268 @@
+//! "What changed for your hardware" after a bop upgrade. Each text audit
+//! records the bop version and the findings it reported; the first audit
+//! under a newer version compares its findings with those, so new checks
+//! and changed recommendations read as bop's doing rather than something
+//! breaking. A finding the machine stopped triggering (after `bop apply`,
+//! say) shows up the same way as a dropped check, so the section says
+//! "no longer reported" rather than guessing which it was. Audits under
+//! another preset or power context find different things, so only one
+//! under the same ones is compared.
+
+use super::{Finding, PowerContext, Severity, match_by_id};
+use crate::preset::Preset;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const HISTORY_PATH: &str = "/var/lib/bop/audit-history.json";
+
+/// [`HISTORY_PATH`], or next to the state file under `--state-dir`.
+pub fn history_path() -> PathBuf {
+    match crate::apply::custom_state_dir() {
+        Some(dir) => dir.join("audit-history.json"),
+        None => PathBuf::from(HISTORY_PATH),
+    }
+}
+
+/// What an earlier audit said about one finding.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeenFinding {
+    /// [`Finding::id`]
+    pub id: String,
+    pub severity: Severity,
+    pub description: String,
+    pub recommended_value: String,
+}
+
+impl SeenFinding {
+    fn new(finding: &Finding) -> Self {
+        Self {
+            id: finding.id(),
+            severity: finding.severity,
+            description: finding.description.clone(),
+            recommended_value: finding.recommended_value.clone(),
+        }
+    }
+}
+
+/// The last text audit: which bop ran it, under what, and what it found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditHistory {
+    pub version: String,
+    /// None in records written before it was kept
+    #[serde(default)]
+    pub preset: Option<Preset>,
+    /// None in records written before it was kept
+    #[serde(default)]
+    pub context: Option<PowerContext>,
+    pub findings: Vec<SeenFinding>,
+}
+
+impl AuditHistory {
+    /// A record of `findings` from the running bop.
+    pub fn current(findings: &[Finding], preset: Preset, context: PowerContext) -> Self {
+        Self::new(env!("CARGO_PKG_VERSION"), findings).under(preset, context)
+    }
+
+    pub fn new(version: &str, findings: &[Finding]) -> Self {
+        Self {
+            version: version.to_string(),
+            preset: None,
+            context: None,
+            findings: findings.iter().map(SeenFinding::new).collect(),
+        }
+    }
+
+    pub fn under(mut self, preset: Preset, context: PowerContext) -> Self {
+        self.preset = Some(preset);
+        self.context = Some(context);
+        self
+    }
+
+    /// Whether the two audits ran under the same preset and context. An
+    /// older record that didn't keep them is taken as the same.
+    fn comparable(&self, other: &Self) -> bool {
+        fn same<T: PartialEq>(a: Option<T>, b: Option<T>) -> bool {
+            a.is_none() || b.is_none() || a == b
+        }
+        same(self.preset, other.preset) && same(self.context, other.context)
+    }
+
+    /// Whether `self` should replace `last` on disk: always, except that an
+    /// upgrade's record waits for an audit it can be compared with.
+    pub fn replaces(&self, last: &Self) -> bool {
+        !is_upgrade(last, self) || last.comparable(self)
+    }
+
+    pub fn load(path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Best effort: an audit without root has nowhere to write, and the
+    /// notes then wait for one that does. Written aside and renamed, so a
+    /// concurrent audit never reads half a record.
+    pub fn save(&self, path: &Path) {
+        let Ok(json) = serde_json::to_string_pretty(self) else {
+            return;
+        };
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        if std::fs::write(&tmp, json).is_ok() && std::fs::rename(&tmp, path).is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+    }
+}
+
+/// How the findings moved between the last audit and this one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeNotes {
+    pub from: String,
+    pub to: String,
+    /// Reported now, not by the last audit
+    pub new_checks: Vec<SeenFinding>,
+    /// Same finding, different recommendation or severity: (before, now)
+    pub changed: Vec<(SeenFinding, SeenFinding)>,
+    /// Reported by the last audit, not now
+    pub no_longer_reported: Vec<SeenFinding>,
+}
+
+impl UpgradeNotes {
+    pub fn is_empty(&self) -> bool {
+        self.new_checks.is_empty() && self.changed.is_empty() && self.no_longer_reported.is_empty()
+    }
+}
+
+/// Whether `now` was run by a newer bop than `last`.
+fn is_upgrade(last: &AuditHistory, now: &AuditHistory) -> bool {
+    match (parse_version(&last.version), parse_version(&now.version)) {
+        (Some(last), Some(now)) => now > last,
+        _ => false,
+    }
+}
+
+/// Compare with the last audit when it was run by an older bop under the
+/// same preset and context; None otherwise, so the notes show once per
+/// upgrade.
+pub fn upgrade_notes(last: &AuditHistory, now: &AuditHistory) -> Option<UpgradeNotes> {
+    if !is_upgrade(last, now) || !last.comparable(now) {
+        return None;
+    }
+    let id = |f: &SeenFinding| f.id.clone();
+    let (pairs, appeared, gone) = match_by_id(&last.findings, &now.findings, id);
+    Some(UpgradeNotes {
+        from: last.version.clone(),
+        to: now.version.clone(),
+        new_checks: appeared.into_iter().cloned().collect(),
+        changed: pairs
+            .into_iter()
+            .filter(|(old, new)| {
+                old.recommended_value != new.recommended_value || old.severity != new.severity
+            })
+            .map(|(old, new)| (old.clone(), new.clone()))
+            .collect(),
+        no_longer_reported: gone.into_iter().cloned().collect(),
+    })
+}
+
+/// "0.4.1" as [0, 4, 1]; a pre-release suffix ("-rc1") is ignored.
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    let release = version.split(['-', '+']).next()?;
+    release.split('.').map(|part| part.parse().ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(category: &str, path: &str, recommended: &str) -> Finding {
+        Finding::new(Severity::Medium, category, format!("{} needs tuning", path))
+            .path(path)
+            .recommended(recommended)
+    }
+
+    #[test]
+    fn test_version_bump_lists_new_changed_and_gone() {
+        let last = AuditHistory::new(
+            "0.4.0",
+            &[
+                finding("CPU", "/sys/epp", "balance_power"),
+                finding("Audio", "/sys/power_save", "1"),
+                finding("PCI", "/sys/aspm", "powersave"),
+            ],
+        );
+        let now = AuditHistory::new(
+            "0.5.0",
+            &[
+                finding("CPU", "/sys/epp", "power"),
+                finding("PCI", "/sys/aspm", "powersave"),
+                finding("USB", "/sys/autosuspend", "auto"),
+            ],
+        );
+
+        let notes = upgrade_notes(&last, &now).unwrap();
+        assert_eq!((notes.from.as_str(), notes.to.as_str()), ("0.4.0", "0.5.0"));
+        assert_eq!(notes.new_checks.len(), 1);
+        assert_eq!(notes.new_checks[0].id, "usb:/sys/autosuspend");
+        assert_eq!(notes.changed.len(), 1);
+        assert_eq!(notes.changed[0].0.recommended_value, "balance_power");
+        assert_eq!(notes.changed[0].1.recommended_value, "power");
+        assert_eq!(notes.no_longer_reported.len(), 1);
+        assert_eq!(notes.no_longer_reported[0].id, "audio:/sys/power_save");
+    }
+
+    #[test]
+    fn test_no_notes_without_an_upgrade() {
+        let findings = [finding("CPU", "/sys/epp", "power")];
+        let last = AuditHistory::new("0.5.0", &findings);
+        assert!(upgrade_notes(&last, &AuditHistory::new("0.5.0", &[])).is_none());
+        // A downgrade, or a version that doesn't parse
+        assert!(upgrade_notes(&last, &AuditHistory::new("0.4.9", &[])).is_none());
+        assert!(upgrade_notes(&last, &AuditHistory::new("dev", &[])).is_none());
+
+        let notes = upgrade_notes(&last, &AuditHistory::new("0.10.0-rc1", &findings)).unwrap();
+        assert!(notes.is_empty());
+    }
+
+    #[test]
+    fn test_notes_wait_for_the_same_preset_and_context() {
+        let findings = [finding("CPU", "/sys/epp", "power")];
+        let last = AuditHistory::new("0.4.0", &findings).under(Preset::Moderate, PowerContext::Ac);
+        let other = AuditHistory::new("0.5.0", &[]).under(Preset::Supersaver, PowerContext::Ac);
+        assert!(upgrade_notes(&last, &other).is_none());
+        assert!(!other.replaces(&last), "keeps the record to compare with");
+
+        let same = AuditHistory::new("0.5.0", &[]).under(Preset::Moderate, PowerContext::Ac);
+        assert_eq!(
+            upgrade_notes(&last, &same)
+                .unwrap()
+                .no_longer_reported
+                .len(),
+            1
+        );
+        assert!(same.replaces(&last));
+
+        // Without an upgrade pending, any audit is the new record
+        let later = AuditHistory::new("0.5.0", &[]).under(Preset::Supersaver, PowerContext::Both);
+        assert!(later.replaces(&same));
+        // Records from before the preset was kept compare with anything
+        assert!(upgrade_notes(&AuditHistory::new("0.4.0", &findings), &other).is_some());
+    }
+
+    #[test]
+    fn test_history_round_trips() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("bop/audit-history.json");
+        assert!(AuditHistory::load(&path).is_none());
+
+        let history = AuditHistory::new("0.4.0", &[finding("CPU", "/sys/epp", "power")])
+            .under(Preset::Moderate, PowerContext::Battery);
+        history.save(&path);
+        assert_eq!(AuditHistory::load(&path), Some(history));
+        assert_eq!(
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+            1
+        );
+    }
+}
@@ -1,4 +1,5 @@
-use crate::audit::{Finding, Severity};
+use crate::audit::{Finding, PowerContext, Severity};
+use crate::detect::usb::UsbInfo;
 use crate::preset::{Preset, PresetKnobs, UsbPolicy};
 use crate::sysfs::SysfsRoot;
 
@@ -23,68 +24,47 @@ pub fn check_with_knobs(sysfs: &SysfsRoot, knobs: &PresetKnobs) -> Vec<Finding>
 
     let all_devices = knobs.usb_autosuspend == UsbPolicy::All;
 
-    // Check USB autosuspend
-    let usb_base = "sys/bus/usb/devices";
-    if let Ok(devices) = sysfs.list_dir(usb_base) {
-        let mut no_autosuspend = 0;
-        let mut total = 0;
+    // Check USB autosuspend, counting only devices `bop apply` would change
+    let usb = UsbInfo::detect(sysfs);
+    let mut no_autosuspend = 0;
+    let mut total = 0;
+    let raw = sysfs.raw_values();
+    let mut evidence = Vec::new();
 
-        for device in &devices {
-            // Skip interfaces (contain ':')
-            if device.contains(':') {
-                continue;
-            }
-
-            let control_path = format!("{}/{}/power/control", usb_base, device);
-            if let Some(control) = sysfs.read_optional(&control_path).unwrap_or(None) {
-                total += 1;
-                if control != "auto" {
-                    if all_devices {
-                        no_autosuspend += 1;
-                    } else {
-                        let product = sysfs
-                            .read_optional(format!("{}/{}/product", usb_base, device))
-                            .unwrap_or(None)
-                            .unwrap_or_default()
-                            .to_lowercase();
-
-                        let is_input = product.contains("keyboard")
-                            || product.contains("mouse")
-                            || product.contains("trackpad")
-                            || product.contains("touchpad");
-                        let is_expansion = product.contains("expansion")
-                            || product.contains("displayport")
-                            || product.contains("hdmi");
-
-                        if !is_input && !is_expansion {
-                            no_autosuspend += 1;
-                        }
-                    }
-                }
-            }
+    for device in &usb.devices {
+        let Some(control) = device.control.as_deref() else {
+            continue;
+        };
+        total += 1;
+        if control != "auto" && device.autosuspend_decision(!all_devices).include {
+            no_autosuspend += 1;
+            evidence.push(format!("sys/bus/usb/devices/{}/power/control", device.name));
         }
+    }
 
-        if no_autosuspend > 0 {
-            findings.push(
-                Finding::new(
-                    Severity::Low,
-                    "USB",
-                    format!(
-                        "{}/{} USB devices not using autosuspend",
-                        no_autosuspend, total
-                    ),
-                )
-                .current(format!("{} devices set to 'on'", no_autosuspend))
-                .recommended("All devices set to 'auto'")
-                .impact(if all_devices {
-                    "Power savings from idle USB devices (may cause input latency)"
-                } else {
-                    "Minor power savings from idle USB devices"
-                })
-                .path("/sys/bus/usb/devices/*/power/control")
-                .weight(2),
-            );
+    if no_autosuspend > 0 {
+        let mut finding = Finding::new(
+            Severity::Low,
+            "USB",
+            format!(
+                "{}/{} USB devices not using autosuspend",
+                no_autosuspend, total
+            ),
+        )
+        .applies_on(PowerContext::Battery)
+        .current(format!("{} devices set to 'on'", no_autosuspend))
+        .recommended("All devices set to 'auto'")
+        .impact(if all_devices {
+            "Power savings from idle USB devices (may cause input latency)"
+        } else {
+            "Minor power savings from idle USB devices"
+        })
+        .path("/sys/bus/usb/devices/*/power/control")
+        .weight(2);
+        for path in &evidence {
+            finding = finding.evidence_from(&raw, path);
         }
+        findings.push(finding);
     }
 
     findings
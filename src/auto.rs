@@ -1,111 +1,220 @@
-use crate::apply::ApplyState;
-use crate::detect::HardwareInfo;
+use crate::apply::wifi::{ExceptionApplied, set_power_save};
+use crate::apply::{ApplyState, LayerScope};
+use crate::config::{LidAction, NetworkException};
+use crate::detect::ac::AcInfo;
+use crate::detect::battery::BatteryInfo;
+use crate::detect::lid::LidInfo;
+use crate::detect::{HardwareInfo, LazyHardware};
 use crate::error::{Error, Result};
+use crate::managed::Ownership;
+use crate::notify::EventKind;
 use crate::preset::Preset;
 use crate::sysfs::SysfsRoot;
 use colored::Colorize;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::Path;
+use std::time::Duration;
 
-const UDEV_RULE_PATH: &str = "/etc/udev/rules.d/85-bop.rules";
-const LOCK_DIR: &str = "/run/bop";
-const LOCK_FILE: &str = "/run/bop/auto.lock";
+pub const UDEV_RULE_PATH: &str = "/etc/udev/rules.d/85-bop.rules";
 
-fn udev_rule_content(cli_preset: Option<Preset>, config_path: Option<&Path>) -> String {
-    let mut args = String::from("/usr/bin/bop");
-    if let Some(path) = config_path {
-        // Resolve to absolute path (udev runs from /) and quote for spaces.
-        // Use canonicalize for existing files, fall back to joining with cwd.
-        let abs = path.canonicalize().unwrap_or_else(|_| {
-            if path.is_absolute() {
-                path.to_path_buf()
-            } else {
-                std::env::current_dir()
-                    .map(|cwd| cwd.join(path))
-                    .unwrap_or_else(|_| path.to_path_buf())
-            }
-        });
-        let path_str = abs.display().to_string();
-        if path_str.contains(' ') || path_str.contains('\'') {
-            // Shell-safe single quoting: replace ' with '\'' (end quote, literal ', start quote)
-            let escaped = path_str.replace('\'', "'\\''");
-            args.push_str(&format!(" --config '{}'", escaped));
+/// Where acpid looks for event hooks. The lid switch raises no uevent, so
+/// udev can't run `bop auto` on it; acpid can.
+pub const ACPID_EVENTS_DIR: &str = "/etc/acpi/events";
+
+/// acpid hook that runs `bop auto` on lid events, for `[auto] on_lid_close`.
+pub const LID_HOOK_PATH: &str = "/etc/acpi/events/bop-lid";
+
+/// logind's config and drop-ins, read in this order.
+const LOGIND_CONF: &str = "/etc/systemd/logind.conf";
+const LOGIND_CONF_DIR: &str = "/etc/systemd/logind.conf.d";
+
+/// How long WiFi traffic is sampled before turning power save on.
+const WIFI_SAMPLE_WINDOW: Duration = Duration::from_secs(1);
+
+/// [`ApplyState::deferred`] entry for WiFi power save held back while busy.
+pub const DEFERRED_WIFI_POWER_SAVE: &str = "wifi_power_save";
+/// [`ApplyState::deferred`] entry for service changes held back during
+/// quiet hours.
+pub const DEFERRED_SERVICES: &str = "services";
+
+/// Absolute, shell-quoted form of a path for the udev RUN command.
+fn udev_path_arg(path: &Path) -> String {
+    // Resolve to absolute path (udev runs from /) and quote for spaces.
+    // Use canonicalize for existing files, fall back to joining with cwd.
+    let abs = path.canonicalize().unwrap_or_else(|_| {
+        if path.is_absolute() {
+            path.to_path_buf()
         } else {
-            args.push_str(&format!(" --config {}", path_str));
+            std::env::current_dir()
+                .map(|cwd| cwd.join(path))
+                .unwrap_or_else(|_| path.to_path_buf())
         }
+    });
+    let path_str = abs.display().to_string();
+    if path_str.contains(' ') || path_str.contains('\'') {
+        // Shell-safe single quoting: replace ' with '\'' (end quote, literal ', start quote)
+        format!("'{}'", path_str.replace('\'', "'\\''"))
+    } else {
+        path_str
+    }
+}
+
+/// The `bop auto` command line the udev rule and the lid hook run.
+fn auto_command(
+    cli_preset: Option<Preset>,
+    config_path: Option<&Path>,
+    report_path: Option<&Path>,
+) -> String {
+    let mut args = String::from("/usr/bin/bop");
+    if let Some(path) = config_path {
+        args.push_str(&format!(" --config {}", udev_path_arg(path)));
     }
     if let Some(preset) = cli_preset {
         args.push_str(&format!(" --preset {}", preset));
     }
     args.push_str(" auto");
-    let bin = args;
+    if let Some(path) = report_path {
+        args.push_str(&format!(" --report {}", udev_path_arg(path)));
+    }
+    args
+}
+
+fn udev_rule_content(
+    cli_preset: Option<Preset>,
+    config_path: Option<&Path>,
+    report_path: Option<&Path>,
+) -> String {
     format!(
-        r#"# Managed by bop — do not edit
+        r#"{}
 ACTION=="change", SUBSYSTEM=="power_supply", KERNEL!="hidpp_battery*", RUN+="{}"
 "#,
-        bin
+        crate::managed::header(),
+        auto_command(cli_preset, config_path, report_path)
     )
 }
 
-/// Outcome of an auto-switching run.
-#[derive(Debug, PartialEq, Eq)]
-pub enum AutoOutcome {
-    Applied,
-    Reverted,
-    NoOp,
-    /// On battery but nothing to change (system already matches preset).
-    AlreadyOptimal,
-    NoProfile,
-    NoAcAdapter,
+fn lid_hook_content(
+    cli_preset: Option<Preset>,
+    config_path: Option<&Path>,
+    report_path: Option<&Path>,
+) -> String {
+    format!(
+        "{}\nevent=button/lid.*\naction={}\n",
+        crate::managed::header(),
+        auto_command(cli_preset, config_path, report_path)
+    )
 }
 
-/// Lock guard that removes the lock file on drop.
-struct LockGuard {
-    path: PathBuf,
+/// What can tell bop the lid moved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LidEventSource {
+    /// acpid runs hooks from [`ACPID_EVENTS_DIR`]
+    Acpid,
+    /// Nothing; the lid is only looked at when a power event runs auto
+    Unavailable,
 }
 
-impl Drop for LockGuard {
-    fn drop(&mut self) {
-        let _ = fs::remove_file(&self.path);
+pub fn lid_event_source(acpid_events_dir: &Path) -> LidEventSource {
+    if acpid_events_dir.is_dir() {
+        LidEventSource::Acpid
+    } else {
+        LidEventSource::Unavailable
     }
 }
 
-/// Try to acquire the lock file. Returns None if another bop auto is running.
-fn acquire_lock() -> Option<LockGuard> {
-    let lock_dir = Path::new(LOCK_DIR);
-    if !lock_dir.exists() && fs::create_dir_all(lock_dir).is_err() {
-        return None;
-    }
-
-    let lock_path = PathBuf::from(LOCK_FILE);
-
-    // Try to create the lock file atomically
-    match fs::OpenOptions::new()
-        .write(true)
-        .create_new(true)
-        .open(&lock_path)
-    {
-        Ok(file) => {
-            use std::io::Write;
-            let pid = std::process::id();
-            let _ = write!(&file, "{}", pid);
-            Some(LockGuard { path: lock_path })
-        }
-        Err(_) => {
-            // Lock file exists — check if the PID is still alive
-            if let Ok(contents) = fs::read_to_string(&lock_path)
-                && let Ok(pid) = contents.trim().parse::<u32>()
+/// logind's `HandleLidSwitch` from its config files in the order read,
+/// later ones overriding earlier ones. systemd's default is `suspend`.
+fn logind_lid_switch(configs: &[String]) -> String {
+    let mut action = "suspend".to_string();
+    for config in configs {
+        for line in config.lines() {
+            let line = line.trim();
+            if line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=')
+                && key.trim() == "HandleLidSwitch"
             {
-                let proc_path = format!("/proc/{}", pid);
-                if !Path::new(&proc_path).exists() {
-                    // Stale lock — remove and retry
-                    let _ = fs::remove_file(&lock_path);
-                    return acquire_lock();
-                }
+                action = value.trim().to_string();
             }
-            None
         }
     }
+    action
+}
+
+/// logind's config files, main file first, then drop-ins by name.
+fn read_logind_configs() -> Vec<String> {
+    let mut paths = vec![std::path::PathBuf::from(LOGIND_CONF)];
+    if let Ok(entries) = fs::read_dir(LOGIND_CONF_DIR) {
+        let mut dropins: Vec<_> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "conf"))
+            .collect();
+        dropins.sort();
+        paths.extend(dropins);
+    }
+    paths
+        .iter()
+        .filter_map(|p| fs::read_to_string(p).ok())
+        .collect()
+}
+
+/// The closed-lid layer: the preset `[auto] on_lid_close` calls for while
+/// the lid is shut, None while it's open or the lid is ignored.
+fn lid_layer(
+    config: &crate::config::BopConfig,
+    sysfs: &SysfsRoot,
+    preset: Preset,
+) -> Option<Preset> {
+    if config.auto.on_lid_close == LidAction::Nothing {
+        return None;
+    }
+    LidInfo::detect(sysfs)
+        .closed
+        .then(|| config.auto.on_lid_close.preset(preset))
+        .flatten()
+}
+
+/// Points above `[auto] critical_battery_percent` the battery has to reach
+/// before the critical layer lets go.
+const CRITICAL_HYSTERESIS_PERCENT: u32 = 3;
+
+/// The critical-battery layer: on battery at or below `[auto]
+/// critical_battery_percent`, where responsiveness matters more than the
+/// last few minutes of runtime. Once in (`was_critical`), it holds until
+/// the battery is [`CRITICAL_HYSTERESIS_PERCENT`] above the threshold, so a
+/// reading that wobbles across it doesn't switch on every event.
+fn critical_layer(
+    config: &crate::config::BopConfig,
+    ac: &AcInfo,
+    battery: &BatteryInfo,
+    was_critical: bool,
+) -> bool {
+    let Some(threshold) = config.auto.critical_battery_percent else {
+        return false;
+    };
+    let limit = u32::from(threshold)
+        + if was_critical {
+            CRITICAL_HYSTERESIS_PERCENT
+        } else {
+            0
+        };
+    ac.is_on_battery()
+        && battery
+            .capacity_percent
+            .is_some_and(|percent| percent <= limit)
+}
+
+/// Outcome of an auto-switching run.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AutoOutcome {
+    Applied,
+    Reverted,
+    NoOp,
+    /// On battery but nothing to change (system already matches preset).
+    AlreadyOptimal,
+    NoProfile,
+    NoAcAdapter,
 }
 
 /// Log an auto-switching event to the systemd journal via `logger`.
@@ -124,45 +233,526 @@ fn log_to_journal(outcome: &AutoOutcome) {
         AutoOutcome::NoProfile => ("warning", "No hardware profile matched — skipping"),
         AutoOutcome::NoAcAdapter => ("debug", "No AC adapter detected"),
     };
+    log_message(priority, message);
+}
+
+/// Like [`log_to_journal`], but an apply for a closed lid says so rather
+/// than blaming the battery.
+fn log_outcome(outcome: &AutoOutcome, lid_closed: bool) {
+    match outcome {
+        AutoOutcome::Applied if lid_closed => {
+            log_message("info", "Lid closed — power optimizations applied")
+        }
+        _ => log_to_journal(outcome),
+    }
+}
+
+/// Record an apply or revert in the event file, for the D-Bus service and,
+/// when `show`, as a desktop notification.
+fn record_event(kind: EventKind, body: &str, show: bool) {
+    let event = crate::notify::NotifyEvent::new(kind, "bop", body);
+    let _ = crate::notify::record(&if show { event } else { event.quiet() });
+}
+
+fn critical_notification(battery: &BatteryInfo) -> String {
+    let level = battery
+        .capacity_percent
+        .map_or_else(|| "critically low".to_string(), |p| format!("at {}%", p));
+    format!(
+        "Battery {}: USB autosuspend and WiFi power save are off so the system stays responsive. Save your work.",
+        level
+    )
+}
+
+fn applied_notification(lid_closed: bool) -> String {
+    let context = if lid_closed {
+        "lid closed"
+    } else {
+        "on battery"
+    };
+    format!("Power optimizations applied ({})", context)
+}
 
-    let _ = std::process::Command::new("logger")
+fn log_message(priority: &str, message: &str) {
+    let _ = crate::cmd::Cmd::new("logger")
         .args(["-t", "bop", "-p", &format!("user.{}", priority), message])
         .status();
 }
 
+/// What [`sync_network_exception`] has to do.
+#[derive(Debug, PartialEq, Eq)]
+enum ExceptionStep<'a> {
+    /// Nothing changed
+    Keep,
+    /// Set `exception`'s power save on `iface`. `original` carries over
+    /// from the exception in effect before, if any.
+    Set {
+        iface: &'a str,
+        exception: &'a NetworkException,
+        original: Option<bool>,
+    },
+    /// No exception matches any more: put the original back
+    Restore(&'a ExceptionApplied),
+}
+
+fn exception_step<'a>(
+    iface: Option<&'a str>,
+    exception: Option<&'a NetworkException>,
+    applied: Option<&'a ExceptionApplied>,
+) -> ExceptionStep<'a> {
+    match (iface, exception, applied) {
+        (Some(iface), Some(exception), applied) => match applied {
+            Some(a) if a.iface == iface && a.ssid == exception.ssid => ExceptionStep::Keep,
+            Some(a) if a.iface == iface => ExceptionStep::Set {
+                iface,
+                exception,
+                original: Some(a.original),
+            },
+            _ => ExceptionStep::Set {
+                iface,
+                exception,
+                original: None,
+            },
+        },
+        (_, _, Some(applied)) => ExceptionStep::Restore(applied),
+        (_, _, None) => ExceptionStep::Keep,
+    }
+}
+
+/// Honour a `[[network.exceptions]]` entry for the connected SSID. The
+/// persistence unit turns WiFi power save on at boot; an exception sets it
+/// for the current network instead. No SSID (iw missing, not connected,
+/// hidden network) means no exception applies, and once none does, the
+/// setting from before goes back. `applied` is the exception in effect so
+/// far; returns the one in effect now, for [`ApplyState::wifi_exception`].
+fn sync_network_exception(
+    network: &crate::detect::network::NetworkInfo,
+    config: &crate::config::BopConfig,
+    applied: Option<&ExceptionApplied>,
+) -> Option<ExceptionApplied> {
+    let ssid = if config.network.exceptions.is_empty() {
+        None
+    } else {
+        network.current_ssid()
+    };
+    let exception = config.network.exception_for(ssid.as_deref());
+    match exception_step(network.wifi_interface.as_deref(), exception, applied) {
+        ExceptionStep::Keep => applied.cloned(),
+        ExceptionStep::Restore(applied) => {
+            let setting = if applied.original { "on" } else { "off" };
+            if !set_power_save(&applied.iface, applied.original) {
+                eprintln!(
+                    "{} Failed to set WiFi power save back {} on {}",
+                    "!".yellow(),
+                    setting,
+                    applied.iface
+                );
+                return Some(applied.clone());
+            }
+            log_message(
+                "info",
+                &format!(
+                    "WiFi power save back {} on {}: left network \"{}\"",
+                    setting, applied.iface, applied.ssid
+                ),
+            );
+            None
+        }
+        ExceptionStep::Set {
+            iface,
+            exception,
+            original,
+        } => {
+            // Read before the first exception changes it
+            let original = original
+                .or_else(|| crate::apply::wifi::power_save(iface))
+                .unwrap_or(true);
+            let setting = if exception.power_save { "on" } else { "off" };
+            if !set_power_save(iface, exception.power_save) {
+                eprintln!(
+                    "{} Failed to set WiFi power save {} for network \"{}\"",
+                    "!".yellow(),
+                    setting,
+                    exception.ssid
+                );
+                return applied.cloned();
+            }
+            log_message(
+                "info",
+                &format!(
+                    "WiFi power save {} on {}: exception for network \"{}\"",
+                    setting, iface, exception.ssid
+                ),
+            );
+            Some(ExceptionApplied {
+                iface: iface.to_string(),
+                ssid: exception.ssid.clone(),
+                original,
+            })
+        }
+    }
+}
+
+/// WiFi throughput in bytes/s, when it's above `[network] busy_mb_per_sec`.
+fn wifi_busy(
+    hw: &HardwareInfo,
+    sysfs: &SysfsRoot,
+    config: &crate::config::BopConfig,
+) -> Option<u64> {
+    let threshold = config.network.busy_mb_per_sec.saturating_mul(1_000_000);
+    if threshold == 0 {
+        return None;
+    }
+    let iface = hw.network.wifi_interface.as_deref()?;
+    crate::detect::network::sample_throughput(sysfs, iface, WIFI_SAMPLE_WINDOW)
+        .filter(|rate| *rate > threshold)
+}
+
+/// Hold WiFi power save back while the link is busy: switching it on mid
+/// transfer cuts throughput and can drop the connection (mt7921e). Already
+/// on (`applied`), there is nothing to hold back. Returns the items held
+/// back, for [`ApplyState::deferred`].
+fn defer_busy_items(
+    plan: &mut crate::apply::ApplyPlan,
+    hw: &HardwareInfo,
+    sysfs: &SysfsRoot,
+    config: &crate::config::BopConfig,
+    applied: bool,
+) -> Vec<String> {
+    if plan.wifi_power_save.is_none() || applied {
+        return Vec::new();
+    }
+    let Some(rate) = wifi_busy(hw, sysfs, config) else {
+        return Vec::new();
+    };
+    plan.wifi_power_save = None;
+    log_message(
+        "info",
+        &format!(
+            "WiFi busy ({:.1} MB/s); WiFi power save deferred until it goes quiet",
+            rate as f64 / 1_000_000.0
+        ),
+    );
+    vec![DEFERRED_WIFI_POWER_SAVE.to_string()]
+}
+
+/// What an auto run may touch; see [`quiet_allows`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuietFeature {
+    /// Applying on battery, reverting on AC
+    PowerSource,
+    Notifications,
+    /// Dimming on battery and restoring the saved level
+    Brightness,
+    /// Disabling services
+    Services,
+    /// Applying items an earlier run deferred
+    DeferredItems,
+}
+
+/// Whether `feature` runs, `quiet` being whether `[auto] quiet_hours` are
+/// on. Quiet hours keep to what the power source calls for; whatever the
+/// user would see or hear waits until they end.
+pub fn quiet_allows(feature: QuietFeature, quiet: bool) -> bool {
+    !quiet || feature == QuietFeature::PowerSource
+}
+
+/// Hold service changes back during quiet hours: stopping a unit can
+/// interrupt whatever it was doing, so the services stay as `disabled`
+/// (what the saved state records). Returns the items held back, for
+/// [`ApplyState::deferred`]; a run timed for the end of quiet hours
+/// applies them.
+fn defer_quiet_items(
+    plan: &mut crate::apply::ApplyPlan,
+    quiet: bool,
+    disabled: &[String],
+) -> Vec<String> {
+    if quiet_allows(QuietFeature::Services, quiet) || plan.services_to_disable == disabled {
+        return Vec::new();
+    }
+    plan.services_to_disable = disabled.to_vec();
+    log_message(
+        "info",
+        "Quiet hours — service changes deferred until they end",
+    );
+    vec![DEFERRED_SERVICES.to_string()]
+}
+
+/// Transient unit that re-runs `bop auto` when quiet hours end.
+const QUIET_END_UNIT: &str = "bop-quiet-end";
+
+/// Run this same `bop auto` again when quiet hours end, so items held back
+/// for them don't wait on the next power supply event (which, plugged in
+/// overnight, may not come until the next unplug). Re-arming moves the
+/// timer rather than adding one.
+fn run_after_quiet_hours(quiet_hours: crate::quiet_hours::QuietHours) {
+    use crate::apply::rollback::{SystemdRun, TimerOps};
+
+    let delay = quiet_hours.until_end(chrono::Local::now().time());
+    let exe = std::env::current_exe()
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "bop".to_string());
+    let command: Vec<String> = std::iter::once(exe)
+        .chain(std::env::args().skip(1))
+        .collect();
+    let mut timer = SystemdRun;
+    let result = timer.stop(QUIET_END_UNIT).and_then(|_| {
+        timer.start(
+            QUIET_END_UNIT,
+            "bop run after quiet hours",
+            delay.max(Duration::from_secs(1)),
+            &command,
+        )
+    });
+    if let Err(e) = result {
+        log_message(
+            "warning",
+            &format!("Failed to schedule a run for the end of quiet hours: {}", e),
+        );
+    }
+}
+
+/// What a run should do, given the power source and the saved state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    /// Nothing to do
+    Stay,
+    Apply,
+    /// On battery with changes made for another preset (e.g. a manual
+    /// `bop apply` before auto was enabled): revert them, apply this one.
+    /// A lid change switches the runtime items in place instead; see
+    /// [`layer_scope`].
+    Reapply {
+        from: Preset,
+    },
+    /// On battery with items an earlier run deferred: apply them once they
+    /// can go through, leaving the rest of the applied changes alone
+    Resume,
+    /// The battery crossed `[auto] critical_battery_percent`: take the
+    /// latency-sensitive items back (`entering`) or put them back in place,
+    /// leaving the rest of the applied changes alone
+    Critical {
+        entering: bool,
+    },
+    Revert,
+}
+
+/// Decide the transition for `preset`, the one this run would apply on
+/// battery. `lid` is the closed-lid layer's preset; while set it wins over
+/// the power source, and once cleared the power source decides again, so
+/// the outcome depends only on the current lid and power source, never on
+/// the order they changed in. `critical` is the critical-battery layer,
+/// which only changes what gets applied, not whether. State that doesn't
+/// record its preset is taken to match.
+pub fn decide(
+    ac: &AcInfo,
+    state: Option<&ApplyState>,
+    preset: Preset,
+    lid: Option<Preset>,
+    critical: bool,
+) -> Transition {
+    let target = match lid {
+        Some(lid) => Some(lid),
+        None if ac.is_on_battery() => Some(preset),
+        None if ac.is_on_ac() => None,
+        None => return Transition::Stay,
+    };
+    match (state, target) {
+        (None, Some(_)) => Transition::Apply,
+        (Some(_), None) => Transition::Revert,
+        (Some(state), Some(target)) => match state.preset {
+            Some(from) if from != target => Transition::Reapply { from },
+            _ if state.critical_battery != critical => Transition::Critical { entering: critical },
+            _ if !state.deferred.is_empty() => Transition::Resume,
+            _ => Transition::Stay,
+        },
+        (None, None) => Transition::Stay,
+    }
+}
+
+/// The items a transition switches in place instead of reverting and
+/// applying again: the critical battery's latency-sensitive ones, the
+/// deferred ones on resume, or for a lid change whatever differs at runtime
+/// between the lid's preset and the power source's. None for a full
+/// re-apply.
+fn layer_scope(
+    transition: Transition,
+    lid: Option<Preset>,
+    lid_was_closed: bool,
+) -> Option<LayerScope> {
+    match transition {
+        Transition::Critical { .. } => Some(LayerScope::LatencySensitive),
+        Transition::Resume => Some(LayerScope::Deferred),
+        Transition::Reapply { .. } if lid.is_some() || lid_was_closed => Some(LayerScope::Runtime),
+        _ => None,
+    }
+}
+
+/// A backlight level saved by an earlier run and not yet restored.
+type SavedBacklight = (u64, Option<String>);
+
+/// Undo the saved changes, backlight first. During quiet hours the
+/// backlight stays as it is: its saved level is taken out of the state
+/// before the revert and returned, so a re-apply can keep it. Before a
+/// re-apply (`reapply`), a pending `--rollback-after` timer stays armed for
+/// what gets applied next.
+fn revert_existing(
+    state: Option<&ApplyState>,
+    sysfs: &SysfsRoot,
+    quiet: bool,
+    reapply: bool,
+) -> Result<Option<SavedBacklight>> {
+    let mut kept = None;
+    if let Some(state) = state
+        && let Some(original) = state.brightness_original
+    {
+        if quiet_allows(QuietFeature::Brightness, quiet) {
+            if let Err(e) =
+                crate::brightness::restore(original, state.brightness_device.as_deref(), sysfs)
+            {
+                eprintln!("{} Failed to restore backlight: {}", "!".yellow(), e);
+            }
+        } else {
+            let mut without = state.clone();
+            without.brightness_original = None;
+            without.brightness_device = None;
+            without.save()?;
+            kept = Some((original, state.brightness_device.clone()));
+        }
+    }
+    crate::revert::revert(None, false, reapply, &mut crate::output::Terminal)?;
+    Ok(kept)
+}
+
 /// Core auto-switching logic. Called by udev or `bop auto`.
-pub fn run(cli_preset: Option<Preset>, config: &crate::config::BopConfig) -> Result<AutoOutcome> {
+/// With `report`, a run that applies optimizations appends a rollout record there.
+/// If another bop operation is running, waits up to `wait` for it, else no-op.
+pub fn run(
+    cli_preset: Option<Preset>,
+    config: &crate::config::BopConfig,
+    report: Option<&Path>,
+    wait: Option<Duration>,
+) -> Result<AutoOutcome> {
     if !nix::unistd::geteuid().is_root() {
         return Err(Error::NotRoot {
             operation: "auto".to_string(),
         });
     }
 
-    let _lock = match acquire_lock() {
-        Some(guard) => guard,
-        None => return Ok(AutoOutcome::NoOp),
+    let _lock = match crate::lock::acquire("auto", wait) {
+        Ok(lock) => lock,
+        Err(Error::Locked(_)) => return Ok(AutoOutcome::NoOp),
+        Err(e) => return Err(e),
     };
 
     let sysfs = SysfsRoot::system();
-    let hw = HardwareInfo::detect(&sysfs);
+    let lazy = LazyHardware::new(&sysfs);
 
-    if !hw.ac.found {
+    if !lazy.ac().found {
         let outcome = AutoOutcome::NoAcAdapter;
         log_to_journal(&outcome);
         return Ok(outcome);
     }
 
-    let profile = crate::profile::detect_profile(&hw);
-    if profile.is_none() {
-        let outcome = AutoOutcome::NoProfile;
+    // A run that died mid-apply left originals in the journal
+    crate::apply::journal::reconcile()?;
+    let existing_state = ApplyState::load()?;
+    let effective_preset = crate::config::resolve_preset(config, cli_preset);
+    let lid = lid_layer(config, &sysfs, effective_preset);
+    let lid_was_closed = existing_state.as_ref().is_some_and(|s| s.lid_closed);
+    let was_critical = existing_state.as_ref().is_some_and(|s| s.critical_battery);
+    let pending_rollback = existing_state.as_ref().and_then(|s| s.rollback.clone());
+    let critical = critical_layer(config, lazy.ac(), lazy.battery(), was_critical);
+    let quiet = config.auto.quiet_hours.is_some_and(|q| q.active_now());
+
+    // Most udev events change nothing (already applied on battery, already
+    // reverted on AC); settle those from the AC section and the lid alone.
+    let transition = decide(
+        lazy.ac(),
+        existing_state.as_ref(),
+        effective_preset,
+        lid,
+        critical,
+    );
+    if transition == Transition::Stay {
+        // Same preset either way (e.g. `apply` on battery): nothing to
+        // change, but the state should say which layer it now serves
+        if let Some(mut state) = existing_state {
+            let mut changed = state.lid_closed != lid.is_some();
+            state.lid_closed = lid.is_some();
+            // The connected network may have changed since
+            if !config.network.exceptions.is_empty() || state.wifi_exception.is_some() {
+                let exception =
+                    sync_network_exception(lazy.network(), config, state.wifi_exception.as_ref());
+                changed |= exception != state.wifi_exception;
+                state.wifi_exception = exception;
+            }
+            if changed {
+                state.save()?;
+            }
+        }
+        let outcome = AutoOutcome::NoOp;
         log_to_journal(&outcome);
         return Ok(outcome);
     }
+    let target_preset = lid.unwrap_or(effective_preset);
 
-    let existing_state = ApplyState::load()?;
-    let state_exists = existing_state.is_some();
+    let hw = lazy.into_full();
+    let Some(profile) = crate::profile::detect_profile(&hw) else {
+        let outcome = AutoOutcome::NoProfile;
+        log_to_journal(&outcome);
+        return Ok(outcome);
+    };
+
+    if transition != Transition::Revert {
+        let layer = layer_scope(transition, lid, lid_was_closed);
+        let mut kept_backlight = None;
+        if let Transition::Reapply { from } = transition {
+            if lid.is_some() {
+                log_message(
+                    "info",
+                    &format!(
+                        "Lid closed — switching from preset {} to {}",
+                        from, target_preset
+                    ),
+                );
+            } else if lid_was_closed {
+                log_message(
+                    "info",
+                    &format!(
+                        "Lid opened — back from preset {} to {}",
+                        from, target_preset
+                    ),
+                );
+            } else {
+                let message = format!(
+                    "optimizations were applied with preset {}, now {}; re-applying",
+                    from, target_preset
+                );
+                eprintln!("{} {}", "!".yellow(), message);
+                log_message("warning", &message);
+            }
+            if layer.is_none() {
+                kept_backlight = revert_existing(existing_state.as_ref(), &sysfs, quiet, true)?;
+            }
+        }
+        if transition == Transition::Resume {
+            if !quiet_allows(QuietFeature::DeferredItems, quiet) {
+                if let Some(quiet_hours) = config.auto.quiet_hours {
+                    run_after_quiet_hours(quiet_hours);
+                }
+                let outcome = AutoOutcome::NoOp;
+                log_to_journal(&outcome);
+                return Ok(outcome);
+            }
+            if wifi_busy(&hw, &sysfs, config).is_some() {
+                let outcome = AutoOutcome::NoOp;
+                log_to_journal(&outcome);
+                return Ok(outcome);
+            }
+            log_message("info", "Applying deferred items");
+        }
 
-    if hw.ac.is_on_battery() && !state_exists {
         // Check inhibitors
         let inhibitors = crate::inhibitors::check_inhibitors().unwrap_or_default();
         let scope = crate::inhibitors::should_apply(&config.inhibitors.mode, &inhibitors);
@@ -173,45 +763,142 @@ pub fn run(cli_preset: Option<Preset>, config: &crate::config::BopConfig) -> Res
             return Ok(outcome);
         }
 
-        let effective_preset = crate::config::resolve_preset(config, cli_preset);
-        let mut knobs = crate::config::resolve_knobs(config, effective_preset);
-        let plan = match scope {
+        let mut knobs = crate::config::resolve_knobs(config, target_preset);
+        let mut plan = match scope {
             crate::inhibitors::ApplyScope::Reduced => {
                 knobs.clamp_for_reduced();
                 crate::apply::build_plan_reduced(&hw, &sysfs, &knobs, Some(config))
             }
             _ => crate::apply::build_plan(&hw, &sysfs, &knobs, Some(config)),
         };
+        plan.preset = Some(target_preset);
+        if critical {
+            plan.hold_latency_sensitive();
+        }
+        // A layer switch starts from what the state has; a full apply from
+        // nothing
+        let current = layer.and(existing_state.as_ref());
+        let mut deferred = defer_busy_items(
+            &mut plan,
+            &hw,
+            &sysfs,
+            config,
+            current.is_some_and(|s| !s.wifi_files_created.is_empty()),
+        );
+        deferred.extend(defer_quiet_items(
+            &mut plan,
+            quiet,
+            current.map_or(&[][..], |s| s.services_disabled.as_slice()),
+        ));
+        if deferred.iter().any(|item| item == DEFERRED_SERVICES)
+            && let Some(quiet_hours) = config.auto.quiet_hours
+        {
+            run_after_quiet_hours(quiet_hours);
+        }
+        let dim = config.brightness.auto_dim && quiet_allows(QuietFeature::Brightness, quiet);
+        let notify = quiet_allows(QuietFeature::Notifications, quiet);
+        let (event_kind, event_body, show_event) = if critical && !was_critical {
+            (
+                EventKind::Critical,
+                critical_notification(&hw.battery),
+                config.notifications.enabled && notify,
+            )
+        } else {
+            (
+                EventKind::Applied,
+                applied_notification(lid.is_some()),
+                config.notifications.enabled && config.notifications.on_apply && notify,
+            )
+        };
+
+        let wifi_exception = sync_network_exception(
+            &hw.network,
+            config,
+            current.and_then(|s| s.wifi_exception.as_ref()),
+        );
+
+        if let Some(scope) = layer
+            && let Some(mut state) = existing_state
+        {
+            crate::apply::switch_layer(&plan, scope, &mut state, &mut crate::output::Terminal)?;
+            if scope == LayerScope::LatencySensitive {
+                // Only WiFi power save is in play; other deferred items stay
+                // for the run that can apply them
+                deferred.retain(|item| item == DEFERRED_WIFI_POWER_SAVE);
+                deferred.extend(
+                    state
+                        .deferred
+                        .iter()
+                        .filter(|item| *item != DEFERRED_WIFI_POWER_SAVE)
+                        .cloned(),
+                );
+            }
+            state.deferred = deferred;
+            state.lid_closed = lid.is_some();
+            state.critical_battery = critical;
+            state.wifi_exception = wifi_exception;
+            state.save()?;
+            let outcome = AutoOutcome::Applied;
+            match transition {
+                Transition::Critical { entering: true } => log_message(
+                    "warning",
+                    "Battery critical — USB autosuspend and WiFi power save taken back",
+                ),
+                Transition::Critical { entering: false } => log_message(
+                    "info",
+                    "Battery above critical — USB autosuspend and WiFi power save restored",
+                ),
+                _ => log_outcome(&outcome, lid.is_some()),
+            }
+            record_event(event_kind, &event_body, show_event);
+            return Ok(outcome);
+        }
 
         if plan.is_empty() {
             // Dim backlight even for empty plans (e.g. already-optimized system)
-            let mut dimmed = false;
-            if config.brightness.auto_dim {
+            let mut dimmed = None;
+            if dim {
                 match crate::brightness::dim(&config.brightness, &sysfs) {
-                    Ok(Some(original)) => {
-                        let state = ApplyState {
-                            timestamp: chrono::Utc::now().to_rfc3339(),
-                            brightness_original: Some(original),
-                            ..Default::default()
-                        };
-                        state.save()?;
-                        dimmed = true;
-                    }
-                    Ok(None) => {}
+                    Ok(changed) => dimmed = changed,
                     Err(e) => {
                         eprintln!("{} Failed to dim backlight: {}", "!".yellow(), e);
                     }
                 }
             }
+            // A critical layer with nothing to apply is still recorded, so
+            // the next battery event doesn't announce it again; so are a
+            // level quiet hours left dimmed and deferred items
+            let backlight = match (&dimmed, kept_backlight) {
+                (Some(d), _) => Some((d.original, Some(d.device.clone()))),
+                (None, kept) => kept,
+            };
+            if backlight.is_some() || critical || !deferred.is_empty() || wifi_exception.is_some() {
+                let (brightness_original, brightness_device) = backlight.unzip();
+                let state = ApplyState {
+                    timestamp: chrono::Utc::now().to_rfc3339(),
+                    brightness_original,
+                    brightness_device: brightness_device.flatten(),
+                    boot_id: crate::apply::current_boot_id(),
+                    preset: Some(target_preset),
+                    deferred,
+                    lid_closed: lid.is_some(),
+                    critical_battery: critical,
+                    wifi_exception,
+                    rollback: pending_rollback,
+                    ..Default::default()
+                };
+                state.save()?;
+            }
+            let dimmed = dimmed.is_some();
             let outcome = if dimmed {
                 AutoOutcome::Applied
             } else {
                 AutoOutcome::AlreadyOptimal
             };
-            log_to_journal(&outcome);
+            log_outcome(&outcome, lid.is_some());
 
-            if dimmed && config.notifications.enabled && config.notifications.on_apply {
-                let _ = crate::notify::send("bop", "Power optimizations applied (on battery)");
+            if dimmed || event_kind == EventKind::Critical {
+                record_event(event_kind, &event_body, show_event);
             }
 
             return Ok(outcome);
@@ -220,16 +907,58 @@ pub fn run(cli_preset: Option<Preset>, config: &crate::config::BopConfig) -> Res
         // Apply optimizations first, then dim backlight only on success.
         // This avoids leaving the screen dimmed with no state to restore
         // if apply fails before any checkpoint.
-        let mut state = crate::apply::execute_plan(&plan, &hw, false)?;
+        let mut rollout = report.map(|_| {
+            let findings = profile.audit_with_opts(&hw, target_preset, &knobs);
+            crate::report::ApplyReport::new(&sysfs, "auto", target_preset, profile.name(), false)
+                .scores(&findings, &plan)
+        });
+        let mut state = ApplyState::default();
+        let result = crate::apply::execute_plan_tracked(
+            &plan,
+            &hw,
+            false,
+            false,
+            &mut state,
+            &mut crate::output::Terminal,
+        );
+        if let (Some(path), Some(rollout)) = (report, rollout.as_mut()) {
+            rollout.finish(
+                &plan,
+                &state,
+                &sysfs,
+                result.as_ref().err().map(ToString::to_string),
+            );
+            if let Err(e) = rollout.append_to(path) {
+                eprintln!("{} Failed to write report: {}", "!".yellow(), e);
+            }
+        }
+        result?;
 
-        if config.brightness.auto_dim {
+        if let Some((original, device)) = kept_backlight {
+            state.brightness_original = Some(original);
+            state.brightness_device = device;
+        }
+        if !deferred.is_empty()
+            || lid.is_some()
+            || critical
+            || state.brightness_original.is_some()
+            || wifi_exception.is_some()
+        {
+            state.deferred = deferred;
+            state.lid_closed = lid.is_some();
+            state.critical_battery = critical;
+            state.wifi_exception = wifi_exception;
+            state.save()?;
+        }
+
+        if dim {
             match crate::brightness::dim(&config.brightness, &sysfs) {
-                Ok(original) => {
-                    if original.is_some() {
-                        state.brightness_original = original;
-                        state.save()?;
-                    }
+                Ok(Some(dimmed)) => {
+                    state.brightness_original = Some(dimmed.original);
+                    state.brightness_device = Some(dimmed.device);
+                    state.save()?;
                 }
+                Ok(None) => {}
                 Err(e) => {
                     eprintln!("{} Failed to dim backlight: {}", "!".yellow(), e);
                 }
@@ -237,44 +966,48 @@ pub fn run(cli_preset: Option<Preset>, config: &crate::config::BopConfig) -> Res
         }
 
         let outcome = AutoOutcome::Applied;
-        log_to_journal(&outcome);
+        log_outcome(&outcome, lid.is_some());
 
-        if config.notifications.enabled && config.notifications.on_apply {
-            let _ = crate::notify::send("bop", "Power optimizations applied (on battery)");
-        }
+        record_event(event_kind, &event_body, show_event);
 
         Ok(outcome)
-    } else if hw.ac.is_on_ac() && state_exists {
-        // Restore brightness before reverting other changes
-        if let Some(ref state) = existing_state
-            && let Some(original) = state.brightness_original
-            && let Err(e) = crate::brightness::restore(original, &sysfs)
-        {
-            eprintln!("{} Failed to restore backlight: {}", "!".yellow(), e);
-        }
-
+    } else {
         // On AC, optimizations applied — revert them
-        crate::revert::revert()?;
+        if revert_existing(existing_state.as_ref(), &sysfs, quiet, false)?.is_some() {
+            log_message("info", "Quiet hours — backlight left as it is");
+        }
         let outcome = AutoOutcome::Reverted;
-        log_to_journal(&outcome);
-
-        if config.notifications.enabled && config.notifications.on_revert {
-            let _ = crate::notify::send("bop", "Power optimizations reverted (on AC)");
+        if lid_was_closed {
+            log_message(
+                "info",
+                "Lid opened on AC — closed-lid optimizations reverted",
+            );
+        } else {
+            log_to_journal(&outcome);
         }
 
-        Ok(outcome)
-    } else {
-        let outcome = AutoOutcome::NoOp;
-        log_to_journal(&outcome);
+        record_event(
+            EventKind::Reverted,
+            "Power optimizations reverted (on AC)",
+            config.notifications.enabled
+                && config.notifications.on_revert
+                && quiet_allows(QuietFeature::Notifications, quiet),
+        );
+
         Ok(outcome)
     }
 }
 
-/// Install udev rule and apply immediately if on battery.
+/// Install udev rule and apply immediately if on battery. An existing rule
+/// at the same path is only replaced if it is bop's, unless `force`. With
+/// `with_notify`, also install the user unit that shows notifications.
 pub fn enable(
     cli_preset: Option<Preset>,
     config: &crate::config::BopConfig,
     config_path: Option<&Path>,
+    report_path: Option<&Path>,
+    force: bool,
+    with_notify: bool,
 ) -> Result<()> {
     if !nix::unistd::geteuid().is_root() {
         return Err(Error::NotRoot {
@@ -283,11 +1016,18 @@ pub fn enable(
     }
 
     let effective_preset = crate::config::resolve_preset(config, cli_preset);
-    let rule = udev_rule_content(cli_preset, config_path);
-    fs::write(UDEV_RULE_PATH, &rule)
-        .map_err(|e| Error::Other(format!("failed to write udev rule: {}", e)))?;
+    let rule = udev_rule_content(cli_preset, config_path, report_path);
+    let ownership = Ownership::new(&[], force);
+    ownership.write(Path::new(UDEV_RULE_PATH), &rule)?;
 
     reload_udevd();
+    let lid_note = sync_lid_hook(
+        config.auto.on_lid_close,
+        cli_preset,
+        config_path,
+        report_path,
+        &ownership,
+    )?;
 
     let preset_label = match cli_preset {
         Some(p) => p.to_string(),
@@ -299,14 +1039,40 @@ pub fn enable(
         preset_label.bold()
     );
     println!("  Rule installed at {}", UDEV_RULE_PATH);
+    if let Some(note) = lid_note {
+        println!("  {}", note);
+    }
+
+    if with_notify {
+        crate::notify::install_user_unit(&ownership)?;
+        println!(
+            "  Notification listener installed at {} (starts with your next login)",
+            crate::notify::USER_UNIT_PATH
+        );
+        if !config.notifications.enabled {
+            println!(
+                "  {} Notifications are off in the config; set [notifications] enabled = true.",
+                "!".yellow()
+            );
+        }
+    }
 
     // Apply immediately if currently on battery
-    match run(cli_preset, config)? {
+    match run(cli_preset, config, report_path, None)? {
         AutoOutcome::Applied => {
             println!("  {} On battery — optimizations applied.", ">>".green());
         }
         AutoOutcome::NoOp => {
-            println!("  On AC power — optimizations will apply when unplugged.");
+            // `run` settles a no-op before matching a profile, so check here
+            let hw = HardwareInfo::detect_all(&SysfsRoot::system());
+            if crate::profile::detect_profile(&hw).is_none() {
+                println!(
+                    "  {} No hardware profile matched. Auto-switching enabled but no optimizations to apply.",
+                    "!".yellow()
+                );
+            } else {
+                println!("  On AC power — optimizations will apply when unplugged.");
+            }
         }
         AutoOutcome::AlreadyOptimal => {
             println!("  On battery — system already matches preset, no changes needed.");
@@ -326,18 +1092,19 @@ pub fn enable(
     Ok(())
 }
 
-/// Remove udev rule and reload.
-pub fn disable() -> Result<()> {
+/// Remove udev rule and reload. Refuses a rule without bop's header unless
+/// `force`.
+pub fn disable(force: bool) -> Result<()> {
     if !nix::unistd::geteuid().is_root() {
         return Err(Error::NotRoot {
             operation: "auto disable".to_string(),
         });
     }
 
+    let ownership = Ownership::new(&[], force);
     let path = Path::new(UDEV_RULE_PATH);
     if path.exists() {
-        fs::remove_file(path)
-            .map_err(|e| Error::Other(format!("failed to remove udev rule: {}", e)))?;
+        ownership.remove(path)?;
         reload_udevd();
         println!("{} Auto-switching disabled.", ">>".green());
         println!("  Removed {}", UDEV_RULE_PATH);
@@ -345,9 +1112,61 @@ pub fn disable() -> Result<()> {
         println!("Auto-switching is not enabled (no udev rule found).");
     }
 
+    if crate::notify::remove_user_unit(&ownership)? {
+        println!("  Removed {}", crate::notify::USER_UNIT_PATH);
+    }
+    if let Some(note) = sync_lid_hook(LidAction::Nothing, None, None, None, &ownership)? {
+        println!("  {}", note);
+    }
+
     Ok(())
 }
 
+/// Install the lid hook when `on_lid_close` wants lid events, remove one
+/// left from an earlier `enable` otherwise. Returns a line for the user
+/// when there's something to say.
+fn sync_lid_hook(
+    on_lid_close: LidAction,
+    cli_preset: Option<Preset>,
+    config_path: Option<&Path>,
+    report_path: Option<&Path>,
+    ownership: &Ownership,
+) -> Result<Option<String>> {
+    let path = Path::new(LID_HOOK_PATH);
+    if on_lid_close == LidAction::Nothing {
+        if !path.exists() {
+            return Ok(None);
+        }
+        ownership.remove(path)?;
+        reload_acpid();
+        return Ok(Some(format!("Removed {}", LID_HOOK_PATH)));
+    }
+
+    let mut note = match lid_event_source(Path::new(ACPID_EVENTS_DIR)) {
+        LidEventSource::Acpid => {
+            ownership.write(
+                path,
+                &lid_hook_content(cli_preset, config_path, report_path),
+            )?;
+            reload_acpid();
+            format!("Lid hook installed at {}", LID_HOOK_PATH)
+        }
+        LidEventSource::Unavailable => format!(
+            "{} No lid event source (install acpid); the lid is only checked when the power source changes",
+            "!".yellow()
+        ),
+    };
+    let handle = logind_lid_switch(&read_logind_configs());
+    if !matches!(handle.as_str(), "ignore" | "lock") {
+        note.push_str(&format!(
+            "\n  {} logind handles lid close with \"{}\"; set HandleLidSwitch=ignore to keep running with the lid shut",
+            "!".yellow(),
+            handle
+        ));
+    }
+    Ok(Some(note))
+}
+
 /// JSON-serializable representation of auto-switching status.
 #[derive(serde::Serialize)]
 struct AutoStatus {
@@ -355,10 +1174,17 @@ struct AutoStatus {
     preset: Option<String>,
     ac_online: bool,
     optimizations_applied: bool,
+    /// Preset the current changes were made for
+    applied_preset: Option<Preset>,
+    /// The changes are the closed-lid layer's
+    lid_closed: bool,
+    /// `[auto] quiet_hours`, e.g. "23:00-07:00"
+    quiet_hours: Option<String>,
+    quiet_hours_active: bool,
 }
 
 /// Show status of auto-switching.
-pub fn status(json: bool) -> Result<()> {
+pub fn status(json: bool, config: &crate::config::BopConfig) -> Result<()> {
     let rule_path = Path::new(UDEV_RULE_PATH);
     let enabled = rule_path.exists();
 
@@ -381,8 +1207,15 @@ pub fn status(json: bool) -> Result<()> {
     };
 
     let sysfs = SysfsRoot::system();
-    let hw = HardwareInfo::detect(&sysfs);
-    let state_exists = ApplyState::load().ok().and_then(|s| s).is_some();
+    let ac = LazyHardware::new(&sysfs).ac().clone();
+    let state = crate::apply::journal::load_state().ok().flatten();
+    let state_exists = state.is_some();
+    let applied_preset = state.as_ref().and_then(|s| s.preset);
+    let lid_closed = state.as_ref().is_some_and(|s| s.lid_closed);
+    // The rule's preset, when it names one; "config-defined" can't be compared
+    let rule_preset = <Preset as clap::ValueEnum>::from_str(&preset_name, true).ok();
+    let quiet_hours = config.auto.quiet_hours;
+    let quiet_hours_active = quiet_hours.is_some_and(|q| q.active_now());
 
     if json {
         let status = AutoStatus {
@@ -392,8 +1225,12 @@ pub fn status(json: bool) -> Result<()> {
             } else {
                 None
             },
-            ac_online: hw.ac.online,
+            ac_online: ac.online,
             optimizations_applied: state_exists,
+            applied_preset,
+            lid_closed,
+            quiet_hours: quiet_hours.map(|q| q.to_string()),
+            quiet_hours_active,
         };
         let json_str = serde_json::to_string_pretty(&status)
             .map_err(|e| Error::Other(format!("JSON serialization failed: {}", e)))?;
@@ -416,8 +1253,8 @@ pub fn status(json: bool) -> Result<()> {
         println!("  {} {}", "Preset:".bold(), preset_name);
     }
 
-    if hw.ac.found {
-        let ac_state = if hw.ac.online {
+    if ac.found {
+        let ac_state = if ac.online {
             "AC (plugged in)"
         } else {
             "battery"
@@ -434,21 +1271,56 @@ pub fn status(json: bool) -> Result<()> {
     println!(
         "  {} {}",
         "Optimizations:".bold(),
-        if state_exists {
-            "applied".green().to_string()
-        } else {
-            "not applied".dimmed().to_string()
+        match (state_exists, applied_preset) {
+            (true, Some(preset)) if lid_closed => {
+                format!("applied (preset {}, lid closed)", preset)
+                    .green()
+                    .to_string()
+            }
+            (true, Some(preset)) => format!("applied (preset {})", preset).green().to_string(),
+            (true, None) => "applied".green().to_string(),
+            (false, _) => "not applied".dimmed().to_string(),
         }
     );
+    if let (Some(applied), Some(rule)) = (applied_preset, rule_preset)
+        && enabled
+        && !lid_closed
+        && applied != rule
+    {
+        println!(
+            "  {} applied with preset {}, auto uses {}; the next battery event re-applies",
+            "!".yellow(),
+            applied,
+            rule
+        );
+    }
+    if let Some(quiet_hours) = quiet_hours {
+        println!(
+            "  {} {}{}",
+            "Quiet hours:".bold(),
+            quiet_hours,
+            if quiet_hours_active {
+                " (active now)".cyan().to_string()
+            } else {
+                String::new()
+            }
+        );
+    }
 
     Ok(())
 }
 
+fn reload_acpid() {
+    let _ = crate::cmd::Cmd::new("systemctl")
+        .args(["try-reload-or-restart", "acpid.service"])
+        .status();
+}
+
 fn reload_udevd() {
-    let _ = std::process::Command::new("udevadm")
+    let _ = crate::cmd::Cmd::new("udevadm")
         .args(["control", "--reload-rules"])
         .status();
-    let _ = std::process::Command::new("udevadm")
+    let _ = crate::cmd::Cmd::new("udevadm")
         .arg("trigger")
         .args(["--subsystem-match=power_supply"])
         .status();
@@ -460,7 +1332,7 @@ mod tests {
 
     #[test]
     fn test_udev_rule_with_preset() {
-        let rule = udev_rule_content(Some(Preset::Moderate), None);
+        let rule = udev_rule_content(Some(Preset::Moderate), None, None);
         assert!(rule.contains("--preset moderate"));
         assert!(rule.contains("KERNEL!=\"hidpp_battery*\""));
         assert!(rule.contains("SUBSYSTEM==\"power_supply\""));
@@ -468,20 +1340,20 @@ mod tests {
 
     #[test]
     fn test_udev_rule_supersaver() {
-        let rule = udev_rule_content(Some(Preset::Supersaver), None);
+        let rule = udev_rule_content(Some(Preset::Supersaver), None, None);
         assert!(rule.contains("RUN+=\"/usr/bin/bop --preset supersaver auto\""));
         assert!(rule.contains("--preset supersaver"));
     }
 
     #[test]
     fn test_udev_rule_saver() {
-        let rule = udev_rule_content(Some(Preset::Saver), None);
+        let rule = udev_rule_content(Some(Preset::Saver), None, None);
         assert!(rule.contains("--preset saver"));
     }
 
     #[test]
     fn test_udev_rule_no_preset() {
-        let rule = udev_rule_content(None, None);
+        let rule = udev_rule_content(None, None, None);
         assert!(!rule.contains("--preset"));
         assert!(rule.contains("RUN+=\"/usr/bin/bop auto\""));
         assert!(rule.contains("KERNEL!=\"hidpp_battery*\""));
@@ -490,7 +1362,7 @@ mod tests {
     #[test]
     fn test_udev_rule_with_config_path() {
         let path = Path::new("/etc/bop/custom.toml");
-        let rule = udev_rule_content(Some(Preset::Moderate), Some(path));
+        let rule = udev_rule_content(Some(Preset::Moderate), Some(path), None);
         assert!(rule.contains("--config /etc/bop/custom.toml"));
         assert!(rule.contains("--preset moderate"));
         assert!(rule.contains(" auto"));
@@ -499,12 +1371,19 @@ mod tests {
     #[test]
     fn test_udev_rule_config_path_no_preset() {
         let path = Path::new("/etc/bop/custom.toml");
-        let rule = udev_rule_content(None, Some(path));
+        let rule = udev_rule_content(None, Some(path), None);
         assert!(rule.contains("--config /etc/bop/custom.toml"));
         assert!(!rule.contains("--preset"));
         assert!(rule.contains(" auto"));
     }
 
+    #[test]
+    fn test_udev_rule_with_report_path() {
+        let path = Path::new("/var/log/bop report.json");
+        let rule = udev_rule_content(None, None, Some(path));
+        assert!(rule.contains("RUN+=\"/usr/bin/bop auto --report '/var/log/bop report.json'\""));
+    }
+
     #[test]
     fn test_auto_status_json_serialization() {
         let status = AutoStatus {
@@ -512,11 +1391,541 @@ mod tests {
             preset: Some("moderate".to_string()),
             ac_online: true,
             optimizations_applied: false,
+            applied_preset: None,
+            lid_closed: false,
+            quiet_hours: Some("23:00-07:00".to_string()),
+            quiet_hours_active: true,
         };
         let json = serde_json::to_string_pretty(&status).unwrap();
         assert!(json.contains("\"enabled\": true"));
         assert!(json.contains("\"preset\": \"moderate\""));
         assert!(json.contains("\"ac_online\": true"));
         assert!(json.contains("\"optimizations_applied\": false"));
+        assert!(json.contains("\"quiet_hours\": \"23:00-07:00\""));
+        assert!(json.contains("\"quiet_hours_active\": true"));
+    }
+
+    fn ac(online: bool) -> AcInfo {
+        AcInfo {
+            found: true,
+            online,
+            ..Default::default()
+        }
+    }
+
+    fn state(preset: Option<Preset>) -> ApplyState {
+        ApplyState {
+            preset,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_decide_basic_transitions() {
+        let battery = ac(false);
+        let mains = ac(true);
+        let saved = state(Some(Preset::Moderate));
+        assert_eq!(
+            decide(&battery, None, Preset::Moderate, None, false),
+            Transition::Apply
+        );
+        assert_eq!(
+            decide(&mains, Some(&saved), Preset::Moderate, None, false),
+            Transition::Revert
+        );
+        assert_eq!(
+            decide(&mains, None, Preset::Moderate, None, false),
+            Transition::Stay
+        );
+        assert_eq!(
+            decide(&battery, Some(&saved), Preset::Moderate, None, false),
+            Transition::Stay
+        );
+        assert_eq!(
+            decide(&AcInfo::default(), None, Preset::Moderate, None, false),
+            Transition::Stay
+        );
+    }
+
+    #[test]
+    fn test_decide_reapplies_on_preset_mismatch() {
+        // Applied manually with moderate, auto configured for supersaver
+        let saved = state(Some(Preset::Moderate));
+        assert_eq!(
+            decide(&ac(false), Some(&saved), Preset::Supersaver, None, false),
+            Transition::Reapply {
+                from: Preset::Moderate
+            }
+        );
+        // On AC the mismatch doesn't matter; the state is reverted anyway
+        assert_eq!(
+            decide(&ac(true), Some(&saved), Preset::Supersaver, None, false),
+            Transition::Revert
+        );
+        // A state file without a recorded preset is left alone
+        assert_eq!(
+            decide(
+                &ac(false),
+                Some(&state(None)),
+                Preset::Supersaver,
+                None,
+                false
+            ),
+            Transition::Stay
+        );
+    }
+
+    #[test]
+    fn test_decide_resumes_deferred_items_on_battery() {
+        let mut saved = state(Some(Preset::Moderate));
+        saved.deferred = vec![DEFERRED_WIFI_POWER_SAVE.to_string()];
+        assert_eq!(
+            decide(&ac(false), Some(&saved), Preset::Moderate, None, false),
+            Transition::Resume
+        );
+        // Back on AC the whole state goes, deferred items included
+        assert_eq!(
+            decide(&ac(true), Some(&saved), Preset::Moderate, None, false),
+            Transition::Revert
+        );
+        // A preset change re-applies everything anyway
+        assert_eq!(
+            decide(&ac(false), Some(&saved), Preset::Supersaver, None, false),
+            Transition::Reapply {
+                from: Preset::Moderate
+            }
+        );
+    }
+
+    #[test]
+    fn test_decide_closed_lid_layer_wins_over_power_source() {
+        let saved = state(Some(Preset::Moderate));
+        let lid = Some(Preset::Supersaver);
+        // Lid closed on AC with nothing applied
+        assert_eq!(
+            decide(&ac(true), None, Preset::Moderate, lid, false),
+            Transition::Apply
+        );
+        // Lid closed on battery with the battery layer applied
+        assert_eq!(
+            decide(&ac(false), Some(&saved), Preset::Moderate, lid, false),
+            Transition::Reapply {
+                from: Preset::Moderate
+            }
+        );
+        // The lid layer stays through AC changes
+        let closed = state(Some(Preset::Supersaver));
+        assert_eq!(
+            decide(&ac(true), Some(&closed), Preset::Moderate, lid, false),
+            Transition::Stay
+        );
+        // Lid opened: back to what the power source calls for
+        assert_eq!(
+            decide(&ac(false), Some(&closed), Preset::Moderate, None, false),
+            Transition::Reapply {
+                from: Preset::Supersaver
+            }
+        );
+        assert_eq!(
+            decide(&ac(true), Some(&closed), Preset::Moderate, None, false),
+            Transition::Revert
+        );
+        // `apply` on battery asks for what's already there
+        assert_eq!(
+            decide(
+                &ac(false),
+                Some(&saved),
+                Preset::Moderate,
+                Some(Preset::Moderate),
+                false
+            ),
+            Transition::Stay
+        );
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    enum Event {
+        LidClose,
+        LidOpen,
+        PlugAc,
+        UnplugAc,
+        /// Battery charge changed to this percentage
+        Battery(u32),
+    }
+
+    /// Preset applied after each event runs auto, starting on battery with
+    /// the lid open and moderate applied.
+    fn run_events(on_lid_close: LidAction, events: &[Event]) -> Option<Preset> {
+        run_battery_events(on_lid_close, None, events).map(|(preset, _)| preset)
+    }
+
+    /// Like [`run_events`] with `[auto] critical_battery_percent` set to
+    /// `critical`, starting at 50%: the preset applied and whether the
+    /// latency-sensitive items are held back.
+    fn run_battery_events(
+        on_lid_close: LidAction,
+        critical: Option<u8>,
+        events: &[Event],
+    ) -> Option<(Preset, bool)> {
+        let preset = Preset::Moderate;
+        let mut config = crate::config::BopConfig::default();
+        config.auto.critical_battery_percent = critical;
+        let mut online = false;
+        let mut closed = false;
+        let mut battery = BatteryInfo {
+            capacity_percent: Some(50),
+            ..Default::default()
+        };
+        let mut saved = Some(state(Some(preset)));
+        for event in events {
+            match event {
+                Event::LidClose => closed = true,
+                Event::LidOpen => closed = false,
+                Event::PlugAc => online = true,
+                Event::UnplugAc => online = false,
+                Event::Battery(percent) => battery.capacity_percent = Some(*percent),
+            }
+            let lid = closed.then(|| on_lid_close.preset(preset)).flatten();
+            let target = lid.unwrap_or(preset);
+            let was_critical = saved.as_ref().is_some_and(|s| s.critical_battery);
+            let critical = critical_layer(&config, &ac(online), &battery, was_critical);
+            saved = match decide(&ac(online), saved.as_ref(), preset, lid, critical) {
+                Transition::Stay => saved,
+                Transition::Revert => None,
+                Transition::Apply
+                | Transition::Reapply { .. }
+                | Transition::Resume
+                | Transition::Critical { .. } => Some(ApplyState {
+                    critical_battery: critical,
+                    ..state(Some(target))
+                }),
+            };
+        }
+        saved.and_then(|s| Some((s.preset?, s.critical_battery)))
+    }
+
+    #[test]
+    fn test_lid_and_power_events_resolve_regardless_of_order() {
+        use Event::*;
+        let aggressive = LidAction::ApplyAggressive;
+        // Lid close, then AC plug, then lid open: the lid layer held
+        // through the plug, and opening lands on AC's answer
+        assert_eq!(
+            run_events(aggressive, &[LidClose]),
+            Some(Preset::Supersaver)
+        );
+        assert_eq!(
+            run_events(aggressive, &[LidClose, PlugAc]),
+            Some(Preset::Supersaver)
+        );
+        assert_eq!(run_events(aggressive, &[LidClose, PlugAc, LidOpen]), None);
+        // Every order of the same three changes ends the same
+        for events in [
+            [PlugAc, LidClose, LidOpen],
+            [LidClose, LidOpen, PlugAc],
+            [LidClose, PlugAc, LidOpen],
+        ] {
+            assert_eq!(run_events(aggressive, &events), None, "{:?}", events);
+        }
+        // Opening on battery restores exactly the pre-close level
+        assert_eq!(
+            run_events(aggressive, &[LidClose, PlugAc, UnplugAc, LidOpen]),
+            Some(Preset::Moderate)
+        );
+        assert_eq!(
+            run_events(aggressive, &[LidClose, LidOpen]),
+            Some(Preset::Moderate)
+        );
+        // `apply` keeps the configured preset applied on AC with the lid shut
+        assert_eq!(
+            run_events(LidAction::Apply, &[PlugAc, LidClose]),
+            Some(Preset::Moderate)
+        );
+        assert_eq!(
+            run_events(LidAction::Apply, &[PlugAc, LidClose, LidOpen]),
+            None
+        );
+        // `nothing` leaves the power source in charge
+        assert_eq!(run_events(LidAction::Nothing, &[LidClose, PlugAc]), None);
+    }
+
+    #[test]
+    fn test_critical_layer_needs_battery_at_or_below_threshold() {
+        let mut config = crate::config::BopConfig::default();
+        let at = |percent| BatteryInfo {
+            capacity_percent: percent,
+            ..Default::default()
+        };
+        assert!(
+            !critical_layer(&config, &ac(false), &at(Some(2)), false),
+            "off by default"
+        );
+        config.auto.critical_battery_percent = Some(5);
+        assert!(critical_layer(&config, &ac(false), &at(Some(5)), false));
+        assert!(!critical_layer(&config, &ac(false), &at(Some(6)), false));
+        assert!(
+            !critical_layer(&config, &ac(true), &at(Some(3)), true),
+            "charging"
+        );
+        assert!(!critical_layer(&config, &ac(false), &at(None), false));
+    }
+
+    #[test]
+    fn test_critical_layer_holds_until_past_the_margin() {
+        let mut config = crate::config::BopConfig::default();
+        config.auto.critical_battery_percent = Some(5);
+        let at = |percent| BatteryInfo {
+            capacity_percent: Some(percent),
+            ..Default::default()
+        };
+        // 5% ↔ 6% jitter stays in once in
+        assert!(critical_layer(&config, &ac(false), &at(6), true));
+        assert!(critical_layer(&config, &ac(false), &at(8), true));
+        assert!(!critical_layer(&config, &ac(false), &at(9), true));
+        // and doesn't get in before the threshold
+        assert!(!critical_layer(&config, &ac(false), &at(8), false));
+    }
+
+    #[test]
+    fn test_decide_critical_battery_crossings() {
+        let saved = state(Some(Preset::Moderate));
+        let critical = ApplyState {
+            critical_battery: true,
+            ..state(Some(Preset::Moderate))
+        };
+        // Downward crossing with the battery layer applied
+        assert_eq!(
+            decide(&ac(false), Some(&saved), Preset::Moderate, None, true),
+            Transition::Critical { entering: true }
+        );
+        assert_eq!(
+            decide(&ac(false), Some(&critical), Preset::Moderate, None, true),
+            Transition::Stay
+        );
+        // Upward crossing, and plugging in
+        assert_eq!(
+            decide(&ac(false), Some(&critical), Preset::Moderate, None, false),
+            Transition::Critical { entering: false }
+        );
+        assert_eq!(
+            decide(&ac(true), Some(&critical), Preset::Moderate, None, false),
+            Transition::Revert
+        );
+        // Unplugging at a critical level applies straight away; a preset
+        // change re-applies, and the rebuilt plan honours the layer anyway
+        assert_eq!(
+            decide(&ac(false), None, Preset::Moderate, None, true),
+            Transition::Apply
+        );
+        assert_eq!(
+            decide(&ac(false), Some(&saved), Preset::Supersaver, None, true),
+            Transition::Reapply {
+                from: Preset::Moderate
+            }
+        );
+    }
+
+    #[test]
+    fn test_critical_battery_events_restore_the_previous_level() {
+        use Event::*;
+        let nothing = LidAction::Nothing;
+        let moderate = |critical| Some((Preset::Moderate, critical));
+        assert_eq!(
+            run_battery_events(nothing, Some(5), &[Battery(20), Battery(5)]),
+            moderate(true)
+        );
+        // Back above the threshold and its margin, e.g. a brief charge
+        assert_eq!(
+            run_battery_events(nothing, Some(5), &[Battery(4), Battery(9)]),
+            moderate(false)
+        );
+        // A reading wobbling around the threshold doesn't switch back
+        assert_eq!(
+            run_battery_events(
+                nothing,
+                Some(5),
+                &[Battery(5), Battery(6), Battery(5), Battery(6)]
+            ),
+            moderate(true)
+        );
+        // Plugging in reverts as usual; unplugging at a critical level
+        // applies with the items held back, and above it without
+        assert_eq!(
+            run_battery_events(nothing, Some(5), &[Battery(4), PlugAc]),
+            None
+        );
+        assert_eq!(
+            run_battery_events(nothing, Some(5), &[Battery(4), PlugAc, UnplugAc]),
+            moderate(true)
+        );
+        assert_eq!(
+            run_battery_events(
+                nothing,
+                Some(5),
+                &[Battery(4), PlugAc, Battery(30), UnplugAc]
+            ),
+            moderate(false)
+        );
+        // The closed-lid layer keeps its preset through the crossing
+        let aggressive = LidAction::ApplyAggressive;
+        assert_eq!(
+            run_battery_events(aggressive, Some(5), &[LidClose, Battery(3)]),
+            Some((Preset::Supersaver, true))
+        );
+        assert_eq!(
+            run_battery_events(aggressive, Some(5), &[LidClose, Battery(3), LidOpen]),
+            moderate(true)
+        );
+        // Unset, nothing is held back at any level
+        assert_eq!(
+            run_battery_events(nothing, None, &[Battery(1)]),
+            moderate(false)
+        );
+    }
+
+    #[test]
+    fn test_quiet_hours_keep_to_the_power_source() {
+        use QuietFeature::*;
+        for feature in [
+            PowerSource,
+            Notifications,
+            Brightness,
+            Services,
+            DeferredItems,
+        ] {
+            assert!(quiet_allows(feature, false), "{:?}", feature);
+            assert_eq!(
+                quiet_allows(feature, true),
+                feature == PowerSource,
+                "{:?}",
+                feature
+            );
+        }
+    }
+
+    #[test]
+    fn test_quiet_hours_defer_services() {
+        let mut plan = crate::apply::ApplyPlan {
+            services_to_disable: vec!["bluetooth.service".to_string()],
+            ..Default::default()
+        };
+        assert!(defer_quiet_items(&mut plan, false, &[]).is_empty());
+        assert_eq!(plan.services_to_disable.len(), 1);
+
+        assert_eq!(defer_quiet_items(&mut plan, true, &[]), [DEFERRED_SERVICES]);
+        assert!(plan.services_to_disable.is_empty());
+        // Nothing held back, nothing to resume
+        assert!(defer_quiet_items(&mut plan, true, &[]).is_empty());
+
+        // Switching in place keeps the services the state has disabled
+        let disabled = ["tlp.service".to_string()];
+        assert_eq!(
+            defer_quiet_items(&mut plan, true, &disabled),
+            [DEFERRED_SERVICES]
+        );
+        assert_eq!(plan.services_to_disable, disabled);
+    }
+
+    #[test]
+    fn test_lid_and_critical_changes_switch_in_place() {
+        let from = Preset::Moderate;
+        let lid = Some(Preset::Supersaver);
+        assert_eq!(
+            layer_scope(Transition::Reapply { from }, lid, false),
+            Some(LayerScope::Runtime)
+        );
+        assert_eq!(
+            layer_scope(Transition::Reapply { from }, None, true),
+            Some(LayerScope::Runtime)
+        );
+        assert_eq!(
+            layer_scope(Transition::Critical { entering: true }, None, false),
+            Some(LayerScope::LatencySensitive)
+        );
+        // A preset change on the power source alone re-applies in full
+        assert_eq!(layer_scope(Transition::Reapply { from }, None, false), None);
+        assert_eq!(
+            layer_scope(Transition::Resume, lid, true),
+            Some(LayerScope::Deferred)
+        );
+    }
+
+    #[test]
+    fn test_network_exception_steps() {
+        let office = NetworkException {
+            ssid: "OfficeNet".to_string(),
+            power_save: false,
+        };
+        let lab = NetworkException {
+            ssid: "LabNet".to_string(),
+            power_save: true,
+        };
+        let at_office = ExceptionApplied {
+            iface: "wlan0".to_string(),
+            ssid: "OfficeNet".to_string(),
+            original: true,
+        };
+        let wlan0 = Some("wlan0");
+        assert_eq!(exception_step(wlan0, None, None), ExceptionStep::Keep);
+        assert_eq!(
+            exception_step(wlan0, Some(&office), None),
+            ExceptionStep::Set {
+                iface: "wlan0",
+                exception: &office,
+                original: None
+            }
+        );
+        assert_eq!(
+            exception_step(wlan0, Some(&office), Some(&at_office)),
+            ExceptionStep::Keep
+        );
+        // Straight to another exception: the first one's original holds
+        assert_eq!(
+            exception_step(wlan0, Some(&lab), Some(&at_office)),
+            ExceptionStep::Set {
+                iface: "wlan0",
+                exception: &lab,
+                original: Some(true)
+            }
+        );
+        // Left the network, or the WiFi went away
+        assert_eq!(
+            exception_step(wlan0, None, Some(&at_office)),
+            ExceptionStep::Restore(&at_office)
+        );
+        assert_eq!(
+            exception_step(None, None, Some(&at_office)),
+            ExceptionStep::Restore(&at_office)
+        );
+    }
+
+    #[test]
+    fn test_lid_hook_runs_the_same_command_as_the_rule() {
+        let hook = lid_hook_content(Some(Preset::Saver), None, None);
+        assert!(crate::managed::has_header(&hook));
+        assert!(hook.contains("\nevent=button/lid.*\n"));
+        assert!(hook.contains("\naction=/usr/bin/bop --preset saver auto\n"));
+        let rule = udev_rule_content(Some(Preset::Saver), None, None);
+        assert!(rule.contains(&auto_command(Some(Preset::Saver), None, None)));
+    }
+
+    #[test]
+    fn test_lid_event_source() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let events = tmp.path().join("acpi/events");
+        assert_eq!(lid_event_source(&events), LidEventSource::Unavailable);
+        std::fs::create_dir_all(&events).unwrap();
+        assert_eq!(lid_event_source(&events), LidEventSource::Acpid);
+    }
+
+    #[test]
+    fn test_logind_lid_switch() {
+        assert_eq!(logind_lid_switch(&[]), "suspend");
+        let main = "[Login]\n#HandleLidSwitch=ignore\nHandleLidSwitch=hibernate\n".to_string();
+        assert_eq!(logind_lid_switch(std::slice::from_ref(&main)), "hibernate");
+        // A drop-in read later wins
+        let dropin =
+            "[Login]\nHandleLidSwitch = ignore\nHandleLidSwitchDocked=suspend\n".to_string();
+        assert_eq!(logind_lid_switch(&[main, dropin]), "ignore");
     }
 }
@@ -1,27 +1,92 @@
 use crate::config::BrightnessConfig;
 use crate::sysfs::SysfsRoot;
 use anyhow::Result;
+use std::path::{Path, PathBuf};
 
-/// Find the first backlight device and return its sysfs base path (relative to sysfs root).
-fn find_backlight(sysfs: &SysfsRoot) -> Option<String> {
-    let entries = sysfs.list_dir("sys/class/backlight").ok()?;
+const BACKLIGHT_DIR: &str = "sys/class/backlight";
+
+/// A backlight changed by [`dim`]: the device, and the brightness to put back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dimmed {
+    pub device: String,
+    pub original: u64,
+}
+
+/// Resolved sysfs path of the boot GPU (`boot_vga` = 1), which drives the
+/// internal panel.
+fn boot_gpu_path(sysfs: &SysfsRoot) -> Option<PathBuf> {
+    let devices = sysfs.list_dir("sys/bus/pci/devices").ok()?;
+    devices.into_iter().find_map(|addr| {
+        let base = format!("sys/bus/pci/devices/{}", addr);
+        let boot_vga = sysfs
+            .read_optional(format!("{}/boot_vga", base))
+            .unwrap_or(None);
+        (boot_vga.as_deref() == Some("1"))
+            .then(|| sysfs.path(&base).canonicalize().ok())
+            .flatten()
+    })
+}
+
+/// Preference for the panel's backlight, lowest first: a device under the
+/// boot GPU, then by interface type (raw, platform, firmware). None for
+/// devices behind an I2C adapter, i.e. an external monitor's DDC/CI.
+fn panel_rank(sysfs: &SysfsRoot, name: &str, boot_gpu: Option<&Path>) -> Option<(u8, u8)> {
+    let base = format!("{}/{}", BACKLIGHT_DIR, name);
+    let device = sysfs.path(format!("{}/device", base)).canonicalize().ok();
+    if device.as_ref().is_some_and(|d| {
+        d.components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with("i2c-"))
+    }) {
+        return None;
+    }
+    let on_boot_gpu = match (&device, boot_gpu) {
+        (Some(device), Some(gpu)) => device.starts_with(gpu),
+        _ => false,
+    };
+    let kind = sysfs
+        .read_optional(format!("{}/type", base))
+        .unwrap_or(None);
+    let kind_rank = match kind.as_deref() {
+        Some("raw") => 0,
+        Some("platform") => 1,
+        Some("firmware") => 2,
+        _ => 3,
+    };
+    Some((u8::from(!on_boot_gpu), kind_rank))
+}
+
+/// Name of the internal panel's backlight device. Ties are broken by name,
+/// so the choice doesn't depend on directory order.
+pub fn find_backlight(sysfs: &SysfsRoot) -> Option<String> {
+    let boot_gpu = boot_gpu_path(sysfs);
+    let entries = sysfs.list_dir(BACKLIGHT_DIR).ok()?;
     entries
         .into_iter()
-        .next()
-        .map(|name| format!("sys/class/backlight/{}", name))
+        .filter_map(|name| Some((panel_rank(sysfs, &name, boot_gpu.as_deref())?, name)))
+        .min()
+        .map(|(_, name)| name)
+}
+
+/// Brightness of backlight `device` as a percentage of its maximum.
+pub fn percent(sysfs: &SysfsRoot, device: &str) -> Option<u32> {
+    let base = format!("{}/{}", BACKLIGHT_DIR, device);
+    let current: u64 = sysfs.read_parse(format!("{}/brightness", base)).ok()?;
+    let max: u64 = sysfs.read_parse(format!("{}/max_brightness", base)).ok()?;
+    (max > 0).then(|| (current.min(max) * 100 / max) as u32)
 }
 
-/// Dim the backlight to `config.dim_percent`% of current brightness.
-/// Returns the original brightness value for later restoration.
-pub fn dim(config: &BrightnessConfig, sysfs: &SysfsRoot) -> Result<Option<u64>> {
+/// Dim the panel backlight to `config.dim_percent`% of current brightness.
+/// Returns the device and its original brightness for later restoration.
+pub fn dim(config: &BrightnessConfig, sysfs: &SysfsRoot) -> Result<Option<Dimmed>> {
     if !config.auto_dim {
         return Ok(None);
     }
 
-    let base = match find_backlight(sysfs) {
-        Some(b) => b,
+    let device = match find_backlight(sysfs) {
+        Some(d) => d,
         None => return Ok(None),
     };
+    let base = format!("{}/{}", BACKLIGHT_DIR, device);
 
     let current: u64 = sysfs
         .read_optional(format!("{}/brightness", base))
@@ -54,15 +119,25 @@ pub fn dim(config: &BrightnessConfig, sysfs: &SysfsRoot) -> Result<Option<u64>>
         .write(format!("{}/brightness", base), &target.to_string())
         .map_err(|e| anyhow::anyhow!("{}", e))?;
 
-    Ok(Some(current))
+    Ok(Some(Dimmed {
+        device,
+        original: current,
+    }))
 }
 
-/// Restore brightness to a previously saved value.
-pub fn restore(original: u64, sysfs: &SysfsRoot) -> Result<()> {
-    let base = match find_backlight(sysfs) {
-        Some(b) => b,
-        None => return Ok(()),
+/// Restore brightness to a previously saved value, on `device` when the
+/// state recorded which one was dimmed. A device that is gone, or no
+/// backlight at all, counts as restored: the kernel sets a returning panel's
+/// brightness itself, and brightening whatever else is there now would be
+/// wrong.
+pub fn restore(original: u64, device: Option<&str>, sysfs: &SysfsRoot) -> Result<()> {
+    let Some(device) = device.map(str::to_string).or_else(|| find_backlight(sysfs)) else {
+        return Ok(());
     };
+    let base = format!("{}/{}", BACKLIGHT_DIR, device);
+    if !sysfs.exists(&base) {
+        return Ok(());
+    }
 
     sysfs
         .write(format!("{}/brightness", base), &original.to_string())
@@ -85,6 +160,16 @@ mod tests {
         SysfsRoot::new(tmp.path())
     }
 
+    #[test]
+    fn test_percent_of_max_brightness() {
+        let tmp = TempDir::new().unwrap();
+        let sysfs = setup_backlight(&tmp, 48, 96);
+        assert_eq!(find_backlight(&sysfs).as_deref(), Some("amdgpu_bl1"));
+        assert_eq!(percent(&sysfs, "amdgpu_bl1"), Some(50));
+        assert_eq!(percent(&setup_backlight(&tmp, 5, 0), "amdgpu_bl1"), None);
+        assert_eq!(percent(&sysfs, "missing"), None);
+    }
+
     #[test]
     fn test_dim_returns_none_when_auto_dim_disabled() {
         let tmp = TempDir::new().unwrap();
@@ -136,7 +221,13 @@ mod tests {
         };
 
         let result = dim(&config, &sysfs).unwrap();
-        assert_eq!(result, Some(1000));
+        assert_eq!(
+            result,
+            Some(Dimmed {
+                device: "amdgpu_bl1".to_string(),
+                original: 1000
+            })
+        );
 
         // Verify the brightness was written
         let written =
@@ -192,7 +283,7 @@ mod tests {
         let tmp = TempDir::new().unwrap();
         let sysfs = setup_backlight(&tmp, 600, 1000);
 
-        restore(1000, &sysfs).unwrap();
+        restore(1000, Some("amdgpu_bl1"), &sysfs).unwrap();
 
         let written =
             fs::read_to_string(tmp.path().join("sys/class/backlight/amdgpu_bl1/brightness"))
@@ -201,12 +292,150 @@ mod tests {
     }
 
     #[test]
-    fn test_restore_no_backlight_is_ok() {
+    fn test_restore_without_backlight_is_ok() {
+        let tmp = TempDir::new().unwrap();
+        let sysfs = SysfsRoot::new(tmp.path());
+
+        // Nothing left to restore, so revert can drop the saved value
+        restore(1000, None, &sysfs).unwrap();
+        restore(1000, Some("amdgpu_bl1"), &sysfs).unwrap();
+        assert!(!tmp.path().join("sys/class/backlight").exists());
+    }
+
+    /// A backlight device `name` of interface `kind` whose `device` link
+    /// points at `target` (relative to the root), like the kernel's.
+    fn add_backlight(root: &Path, name: &str, kind: &str, target: &str, brightness: u64) {
+        let dir = root.join("sys/class/backlight").join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("type"), kind).unwrap();
+        fs::write(dir.join("brightness"), brightness.to_string()).unwrap();
+        fs::write(dir.join("max_brightness"), "1000").unwrap();
+        let target = root.join(target);
+        fs::create_dir_all(&target).unwrap();
+        std::os::unix::fs::symlink(&target, dir.join("device")).unwrap();
+    }
+
+    fn add_gpu(root: &Path, addr: &str, boot_vga: bool) {
+        let dir = root.join("sys/devices/pci0000:00").join(addr);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("boot_vga"), if boot_vga { "1" } else { "0" }).unwrap();
+        let link = root.join("sys/bus/pci/devices");
+        fs::create_dir_all(&link).unwrap();
+        std::os::unix::fs::symlink(&dir, link.join(addr)).unwrap();
+    }
+
+    /// Hybrid laptop with a backlight on each GPU; `boot` says which GPU
+    /// drives the panel.
+    fn hybrid(boot: &str) -> TempDir {
+        let tmp = TempDir::new().unwrap();
+        add_gpu(tmp.path(), "0000:01:00.0", boot == "nvidia");
+        add_gpu(tmp.path(), "0000:c1:00.0", boot == "amdgpu");
+        add_backlight(
+            tmp.path(),
+            "amdgpu_bl1",
+            "raw",
+            "sys/devices/pci0000:00/0000:c1:00.0",
+            800,
+        );
+        add_backlight(
+            tmp.path(),
+            "nvidia_0",
+            "raw",
+            "sys/devices/pci0000:00/0000:01:00.0",
+            800,
+        );
+        tmp
+    }
+
+    #[test]
+    fn test_find_backlight_prefers_boot_gpu_in_either_order() {
+        // Panel device sorts first
+        let tmp = hybrid("amdgpu");
+        let sysfs = SysfsRoot::new(tmp.path());
+        assert_eq!(find_backlight(&sysfs).as_deref(), Some("amdgpu_bl1"));
+
+        // Panel device sorts last
+        let tmp = hybrid("nvidia");
+        let sysfs = SysfsRoot::new(tmp.path());
+        assert_eq!(find_backlight(&sysfs).as_deref(), Some("nvidia_0"));
+    }
+
+    #[test]
+    fn test_find_backlight_skips_ddc_and_ranks_types() {
         let tmp = TempDir::new().unwrap();
+        // External monitor over DDC/CI sorts first and is also "raw"
+        add_backlight(
+            tmp.path(),
+            "ddcci5",
+            "raw",
+            "sys/devices/pci0000:00/0000:c1:00.0/i2c-5/5-0037",
+            800,
+        );
+        add_backlight(
+            tmp.path(),
+            "acpi_video0",
+            "firmware",
+            "sys/devices/LNXVIDEO:00",
+            800,
+        );
+        add_backlight(
+            tmp.path(),
+            "platform_bl",
+            "platform",
+            "sys/devices/platform/bl",
+            800,
+        );
         let sysfs = SysfsRoot::new(tmp.path());
+        assert_eq!(find_backlight(&sysfs).as_deref(), Some("platform_bl"));
+
+        fs::remove_dir_all(tmp.path().join("sys/class/backlight/platform_bl")).unwrap();
+        assert_eq!(find_backlight(&sysfs).as_deref(), Some("acpi_video0"));
+
+        fs::remove_dir_all(tmp.path().join("sys/class/backlight/acpi_video0")).unwrap();
+        assert_eq!(find_backlight(&sysfs), None);
+    }
+
+    #[test]
+    fn test_restore_targets_recorded_device() {
+        let tmp = hybrid("amdgpu");
+        let sysfs = SysfsRoot::new(tmp.path());
+        let config = BrightnessConfig {
+            auto_dim: true,
+            dim_percent: 50,
+        };
+        let dimmed = dim(&config, &sysfs).unwrap().unwrap();
+        assert_eq!(dimmed.device, "amdgpu_bl1");
+
+        // After a dock event the other GPU drives the display
+        fs::write(
+            tmp.path()
+                .join("sys/devices/pci0000:00/0000:c1:00.0/boot_vga"),
+            "0",
+        )
+        .unwrap();
+        fs::write(
+            tmp.path()
+                .join("sys/devices/pci0000:00/0000:01:00.0/boot_vga"),
+            "1",
+        )
+        .unwrap();
+        restore(dimmed.original, Some(&dimmed.device), &sysfs).unwrap();
+
+        let read = |name: &str| {
+            fs::read_to_string(
+                tmp.path()
+                    .join("sys/class/backlight")
+                    .join(name)
+                    .join("brightness"),
+            )
+            .unwrap()
+        };
+        assert_eq!(read("amdgpu_bl1"), "800");
+        assert_eq!(read("nvidia_0"), "800");
 
-        // Should not error when there's no backlight device
-        let result = restore(1000, &sysfs);
-        assert!(result.is_ok());
+        // A recorded device that went away is done with, not a guess
+        fs::remove_dir_all(tmp.path().join("sys/class/backlight/amdgpu_bl1")).unwrap();
+        restore(1000, Some("amdgpu_bl1"), &sysfs).unwrap();
+        assert_eq!(read("nvidia_0"), "800");
     }
 }
@@ -0,0 +1,530 @@
+//! Battery calibration: measure what the pack really delivers over a full
+//! charge -> discharge -> charge cycle and compare it with the fuel gauge's
+//! `energy_full`. bop never controls charging; `bop battery calibrate` says
+//! when to unplug and plug in and does the bookkeeping. The result is kept
+//! in [`RESULT_PATH`] with the pack's serial number and corrects
+//! remaining-time estimates for that pack until the next calibration.
+
+use crate::detect::battery::BatteryInfo;
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The last completed calibration.
+pub const RESULT_PATH: &str = "/var/lib/bop/battery_calibration.json";
+/// Seconds between readings.
+pub const SAMPLE_SECS: u64 = 30;
+/// The discharge leg runs until the gauge reads this low.
+pub const LOW_PERCENT: u32 = 5;
+/// A longer gap between readings (suspend) hides energy that can't be
+/// measured, so the cycle can't continue.
+pub const MAX_GAP_SECS: u64 = 5 * 60;
+
+/// The battery's `status`, as far as the cycle cares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChargeStatus {
+    Charging,
+    Discharging,
+    Full,
+    /// "Not charging" (e.g. held at a charge limit) or unknown
+    Idle,
+}
+
+impl ChargeStatus {
+    fn from_sysfs(status: Option<&str>) -> Self {
+        match status {
+            Some("Charging") => ChargeStatus::Charging,
+            Some("Discharging") => ChargeStatus::Discharging,
+            Some("Full") => ChargeStatus::Full,
+            _ => ChargeStatus::Idle,
+        }
+    }
+}
+
+/// One battery reading.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reading {
+    /// Unix time in seconds
+    pub time: u64,
+    pub status: ChargeStatus,
+    pub percent: Option<u32>,
+    /// The gauge's remaining energy (`energy_now`)
+    pub energy_wh: Option<f64>,
+    /// The gauge's full capacity (`energy_full`)
+    pub full_wh: Option<f64>,
+    pub power_watts: Option<f64>,
+}
+
+impl Reading {
+    pub fn from_battery(battery: &BatteryInfo, time: u64) -> Self {
+        Reading {
+            time,
+            status: ChargeStatus::from_sysfs(battery.status.as_deref()),
+            percent: battery.capacity_percent,
+            energy_wh: battery.energy_wh(),
+            full_wh: battery.usable_capacity_wh(),
+            power_watts: battery.power_watts(),
+        }
+    }
+
+    /// Full by status, or at 100% on a gauge that never says "Full".
+    fn is_full(&self) -> bool {
+        self.status == ChargeStatus::Full
+            || (self.status != ChargeStatus::Discharging && self.percent == Some(100))
+    }
+}
+
+/// Where the cycle is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Charging up to full on AC
+    WaitingForFull,
+    /// Full; waiting for AC to be unplugged
+    Full,
+    /// Running down to [`LOW_PERCENT`]
+    Discharging,
+    /// Low enough; waiting for AC to be plugged back in
+    Low,
+    /// Charging back to full
+    Charging,
+}
+
+impl Stage {
+    /// What the user should do now.
+    pub fn instruction(&self) -> String {
+        match self {
+            Stage::WaitingForFull => {
+                "Plug in AC and charge to 100% (turn off any charge limit)".to_string()
+            }
+            Stage::Full => "Battery full. Unplug AC and use the laptop as usual".to_string(),
+            Stage::Discharging => format!(
+                "Discharging. Keep AC unplugged and don't suspend until the battery reaches {}%",
+                LOW_PERCENT
+            ),
+            Stage::Low => "Battery low. Plug in AC and let it charge to 100%".to_string(),
+            Stage::Charging => "Charging. Keep AC plugged in until the battery is full".to_string(),
+        }
+    }
+}
+
+/// What a reading did to the cycle.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Continue,
+    /// Moved to a new stage
+    Entered(Stage),
+    Finished(CalibrationResult),
+    /// The cycle can't be completed; why, and what to do
+    Aborted(String),
+}
+
+/// A completed cycle.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CalibrationResult {
+    /// The measured pack's `serial_number`. None if it reports none, or in
+    /// results saved before it was kept
+    #[serde(default)]
+    pub serial: Option<String>,
+    /// Unix time the cycle completed
+    pub finished: u64,
+    /// Estimated real full capacity
+    pub measured_wh: f64,
+    /// `energy_full` at the end of the cycle
+    pub reported_wh: f64,
+    /// Energy the battery delivered from full to the low point
+    pub discharged_wh: f64,
+    /// What the gauge said that stretch held
+    pub gauge_span_wh: f64,
+    /// Energy put back from the low point to full, charging losses included
+    pub charged_wh: f64,
+}
+
+impl CalibrationResult {
+    /// How far `energy_full` overstates (positive) or understates the
+    /// measured capacity, in percent.
+    pub fn gauge_error_percent(&self) -> f64 {
+        (self.reported_wh - self.measured_wh) / self.measured_wh * 100.0
+    }
+
+    /// Real energy per gauge Wh, as measured on the discharge leg.
+    pub fn scale(&self) -> f64 {
+        self.discharged_wh / self.gauge_span_wh
+    }
+
+    /// `battery`'s remaining energy corrected by [`Self::scale`].
+    pub fn corrected_energy_wh(&self, battery: &BatteryInfo) -> Option<f64> {
+        battery.energy_wh().map(|wh| wh * self.scale())
+    }
+
+    /// The result at `path`; `None` if there is none or it doesn't parse.
+    pub fn load(path: &Path) -> Option<Self> {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .filter(|r: &Self| r.gauge_span_wh > 0.0 && r.measured_wh > 0.0)
+    }
+
+    /// [`Self::load`], if the result was measured on `battery`. After a pack
+    /// swap it describes a battery that's gone.
+    pub fn load_for(path: &Path, battery: &BatteryInfo) -> Option<Self> {
+        Self::load(path).filter(|r| r.serial == battery.serial)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let io_err =
+            |e: std::io::Error| Error::Other(format!("failed to write {}: {}", path.display(), e));
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_err)?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Other(format!("failed to serialize calibration: {}", e)))?;
+        std::fs::write(path, json + "\n").map_err(io_err)
+    }
+}
+
+/// The cycle's state machine, fed one [`Reading`] at a time.
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    stage: Stage,
+    last: Option<Reading>,
+    /// Gauge energy and capacity when the discharge began
+    start_energy_wh: f64,
+    start_full_wh: f64,
+    /// Gauge energy at the last discharging reading
+    low_energy_wh: f64,
+    discharged_wh: f64,
+    charged_wh: f64,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Calibration {
+    pub fn new() -> Self {
+        Calibration {
+            stage: Stage::WaitingForFull,
+            last: None,
+            start_energy_wh: 0.0,
+            start_full_wh: 0.0,
+            low_energy_wh: 0.0,
+            discharged_wh: 0.0,
+            charged_wh: 0.0,
+        }
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    pub fn discharged_wh(&self) -> f64 {
+        self.discharged_wh
+    }
+
+    pub fn charged_wh(&self) -> f64 {
+        self.charged_wh
+    }
+
+    pub fn observe(&mut self, reading: Reading) -> Step {
+        let prev = self.last.replace(reading);
+        let measuring = matches!(
+            self.stage,
+            Stage::Discharging | Stage::Low | Stage::Charging
+        );
+        if measuring && let Some(prev) = prev {
+            let gap = reading.time.saturating_sub(prev.time);
+            if gap > MAX_GAP_SECS {
+                return Step::Aborted(format!(
+                    "No reading for {} min (suspended?), so the energy used meanwhile is unknown. Start again from a full charge.",
+                    gap / 60
+                ));
+            }
+        }
+
+        match self.stage {
+            Stage::WaitingForFull if reading.is_full() => self.enter(Stage::Full),
+            Stage::WaitingForFull => Step::Continue,
+            Stage::Full if reading.status == ChargeStatus::Discharging => {
+                let (Some(energy), Some(full), Some(_)) =
+                    (reading.energy_wh, reading.full_wh, reading.power_watts)
+                else {
+                    return Step::Aborted(
+                        "The battery doesn't report its energy and power draw, so a cycle can't be measured."
+                            .to_string(),
+                    );
+                };
+                self.start_energy_wh = energy;
+                self.start_full_wh = full;
+                self.low_energy_wh = energy;
+                self.enter(Stage::Discharging)
+            }
+            Stage::Full => Step::Continue,
+            Stage::Discharging | Stage::Low if reading.status == ChargeStatus::Discharging => {
+                self.discharged_wh += energy_between(prev.as_ref(), &reading);
+                if let Some(energy) = reading.energy_wh {
+                    self.low_energy_wh = energy;
+                }
+                let low = reading.percent.is_some_and(|p| p <= LOW_PERCENT);
+                if self.stage == Stage::Discharging && low {
+                    self.enter(Stage::Low)
+                } else {
+                    Step::Continue
+                }
+            }
+            Stage::Discharging => Step::Aborted(format!(
+                "AC was plugged in at {}, before the battery ran down to {}%. Start again from a full charge.",
+                reading
+                    .percent
+                    .map(|p| format!("{}%", p))
+                    .unwrap_or_else(|| "an unknown charge".to_string()),
+                LOW_PERCENT
+            )),
+            Stage::Low => self.enter(Stage::Charging),
+            Stage::Charging if reading.status == ChargeStatus::Discharging => Step::Aborted(
+                "AC was unplugged before the battery was full again. Start again from a full charge."
+                    .to_string(),
+            ),
+            Stage::Charging => {
+                if reading.status == ChargeStatus::Charging || reading.is_full() {
+                    self.charged_wh += energy_between(prev.as_ref(), &reading);
+                }
+                if reading.is_full() {
+                    self.finish(&reading)
+                } else {
+                    Step::Continue
+                }
+            }
+        }
+    }
+
+    fn enter(&mut self, stage: Stage) -> Step {
+        self.stage = stage;
+        Step::Entered(stage)
+    }
+
+    fn finish(&mut self, reading: &Reading) -> Step {
+        let gauge_span_wh = self.start_energy_wh - self.low_energy_wh;
+        if gauge_span_wh <= 0.0 || self.discharged_wh <= 0.0 {
+            return Step::Aborted(
+                "The gauge's energy reading never dropped during the discharge, so there's nothing to compare against."
+                    .to_string(),
+            );
+        }
+        let measured_wh = self.start_full_wh * self.discharged_wh / gauge_span_wh;
+        Step::Finished(CalibrationResult {
+            serial: None,
+            finished: reading.time,
+            measured_wh,
+            reported_wh: reading.full_wh.unwrap_or(self.start_full_wh),
+            discharged_wh: self.discharged_wh,
+            gauge_span_wh,
+            charged_wh: self.charged_wh,
+        })
+    }
+}
+
+/// Energy moved between two readings: the mean of their power over the
+/// time between them.
+fn energy_between(prev: Option<&Reading>, next: &Reading) -> f64 {
+    let Some(prev) = prev else {
+        return 0.0;
+    };
+    let hours = next.time.saturating_sub(prev.time) as f64 / 3600.0;
+    let watts = match (prev.power_watts, next.power_watts) {
+        (Some(a), Some(b)) => (a + b) / 2.0,
+        (Some(w), None) | (None, Some(w)) => w,
+        (None, None) => 0.0,
+    };
+    watts * hours
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn reading(
+        minute: u64,
+        status: ChargeStatus,
+        percent: u32,
+        energy: f64,
+        watts: f64,
+    ) -> Reading {
+        Reading {
+            time: minute * 60,
+            status,
+            percent: Some(percent),
+            energy_wh: Some(energy),
+            full_wh: Some(50.0),
+            power_watts: Some(watts),
+        }
+    }
+
+    /// Feed `readings` and return every step that wasn't `Continue`.
+    fn run(cal: &mut Calibration, readings: &[Reading]) -> Vec<Step> {
+        readings
+            .iter()
+            .map(|r| cal.observe(*r))
+            .filter(|s| *s != Step::Continue)
+            .collect()
+    }
+
+    /// Full at minute 0, discharging at 10W for 4h (40Wh delivered) while
+    /// the gauge drops from 50Wh to 2.5Wh, then charging back.
+    fn full_cycle() -> Vec<Reading> {
+        use ChargeStatus::*;
+        let mut readings = vec![
+            reading(0, Charging, 99, 49.5, 5.0),
+            reading(1, Full, 100, 50.0, 0.0),
+            reading(2, Discharging, 100, 50.0, 10.0),
+        ];
+        // Gauge drops 47.5Wh over 240 minutes of 10W (40Wh)
+        for i in 1..=48 {
+            let energy = 50.0 - 47.5 * i as f64 / 48.0;
+            let percent = (energy / 50.0 * 100.0).round() as u32;
+            readings.push(reading(2 + i * 5, Discharging, percent, energy, 10.0));
+        }
+        readings.push(reading(243, Charging, 5, 2.5, 20.0));
+        for i in 1..=24 {
+            readings.push(reading(
+                243 + i * 5,
+                Charging,
+                (5 + i * 4).min(99) as u32,
+                2.5 + 2.0 * i as f64,
+                20.0,
+            ));
+        }
+        readings.push(reading(368, Full, 100, 50.0, 20.0));
+        readings
+    }
+
+    #[test]
+    fn test_full_cycle_measures_capacity() {
+        let mut cal = Calibration::new();
+        let steps = run(&mut cal, &full_cycle());
+        assert_eq!(steps.len(), 5);
+        assert_eq!(
+            steps[..4],
+            [
+                Step::Entered(Stage::Full),
+                Step::Entered(Stage::Discharging),
+                Step::Entered(Stage::Low),
+                Step::Entered(Stage::Charging),
+            ]
+        );
+        let Step::Finished(result) = &steps[4] else {
+            panic!("expected a result, got {:?}", steps[4]);
+        };
+        assert!((result.discharged_wh - 40.0).abs() < 1e-9);
+        assert!((result.gauge_span_wh - 47.5).abs() < 1e-9);
+        // The gauge claims 50Wh; it delivered 40Wh for 47.5 gauge Wh
+        assert!((result.measured_wh - 42.105).abs() < 0.001);
+        assert!((result.gauge_error_percent() - 18.75).abs() < 1e-9);
+        assert!(result.charged_wh > 40.0);
+        assert_eq!(result.reported_wh, 50.0);
+    }
+
+    #[test]
+    fn test_ac_plugged_mid_discharge_aborts() {
+        use ChargeStatus::*;
+        let mut cal = Calibration::new();
+        let steps = run(
+            &mut cal,
+            &[
+                reading(0, Full, 100, 50.0, 0.0),
+                reading(1, Discharging, 100, 50.0, 10.0),
+                reading(2, Discharging, 99, 49.8, 10.0),
+                reading(3, Charging, 99, 49.8, 20.0),
+            ],
+        );
+        assert_eq!(steps.len(), 3);
+        let Step::Aborted(why) = &steps[2] else {
+            panic!("expected an abort, got {:?}", steps[2]);
+        };
+        assert!(why.contains("plugged in at 99%"));
+    }
+
+    #[test]
+    fn test_gap_mid_discharge_aborts() {
+        use ChargeStatus::*;
+        let mut cal = Calibration::new();
+        let steps = run(
+            &mut cal,
+            &[
+                reading(0, Full, 100, 50.0, 0.0),
+                reading(1, Discharging, 100, 50.0, 10.0),
+                reading(90, Discharging, 80, 40.0, 10.0),
+            ],
+        );
+        assert!(matches!(&steps[2], Step::Aborted(why) if why.contains("89 min")));
+    }
+
+    #[test]
+    fn test_unplugged_mid_charge_aborts() {
+        let mut readings = full_cycle();
+        readings.truncate(60);
+        readings.push(reading(285, ChargeStatus::Discharging, 40, 20.0, 10.0));
+        let mut cal = Calibration::new();
+        let steps = run(&mut cal, &readings);
+        assert!(matches!(steps.last(), Some(Step::Aborted(why)) if why.contains("unplugged")));
+    }
+
+    #[test]
+    fn test_waits_for_full_and_needs_power_readings() {
+        use ChargeStatus::*;
+        let mut cal = Calibration::new();
+        // Held at a charge limit: not full, nothing happens
+        assert_eq!(cal.observe(reading(0, Idle, 80, 40.0, 0.0)), Step::Continue);
+        assert_eq!(cal.stage(), Stage::WaitingForFull);
+        // 100% while idle on AC counts as full
+        assert_eq!(
+            cal.observe(reading(1, Idle, 100, 50.0, 0.0)),
+            Step::Entered(Stage::Full)
+        );
+        let mut no_power = reading(2, Discharging, 100, 50.0, 0.0);
+        no_power.power_watts = None;
+        assert!(matches!(cal.observe(no_power), Step::Aborted(_)));
+    }
+
+    #[test]
+    fn test_result_corrects_energy_and_round_trips() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("calibration.json");
+        let result = CalibrationResult {
+            serial: Some("0042".to_string()),
+            finished: 1,
+            measured_wh: 40.0,
+            reported_wh: 50.0,
+            discharged_wh: 38.0,
+            gauge_span_wh: 47.5,
+            charged_wh: 42.0,
+        };
+        assert_eq!(result.gauge_error_percent(), 25.0);
+        let battery = BatteryInfo {
+            energy_now_uwh: Some(25_000_000),
+            ..Default::default()
+        };
+        assert_eq!(result.corrected_energy_wh(&battery), Some(20.0));
+
+        assert_eq!(CalibrationResult::load(&path), None);
+        result.save(&path).unwrap();
+        assert_eq!(CalibrationResult::load(&path), Some(result.clone()));
+
+        // Only applied to the pack it was measured on
+        let pack = |serial: Option<&str>| BatteryInfo {
+            serial: serial.map(str::to_string),
+            ..Default::default()
+        };
+        assert_eq!(
+            CalibrationResult::load_for(&path, &pack(Some("0042"))),
+            Some(result)
+        );
+        assert_eq!(
+            CalibrationResult::load_for(&path, &pack(Some("0077"))),
+            None
+        );
+        assert_eq!(CalibrationResult::load_for(&path, &pack(None)), None);
+    }
+}
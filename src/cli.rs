@@ -1,3 +1,4 @@
+use crate::duration::Duration;
 use crate::preset::Preset;
 use clap::{CommandFactory, Parser, Subcommand};
 use clap_complete::Shell;
@@ -28,6 +29,26 @@ pub struct Cli {
     /// Path to config file (overrides system/user configs)
     #[arg(long, global = true)]
     pub config: Option<PathBuf>,
+
+    /// Directory for the state file instead of /var/lib/bop, e.g. to run
+    /// `status` or `revert` against a prepared state. Reverting one needs
+    /// no root, only write access to the files it names
+    #[arg(long, global = true, value_name = "DIR")]
+    pub state_dir: Option<PathBuf>,
+
+    /// On failure, save the error, the plan, the changes made so far and a
+    /// sysfs snapshot to /var/lib/bop/crash for a bug report (the newest 5
+    /// are kept). On by default for `bop auto`; `=false` turns it off
+    #[arg(
+        long,
+        global = true,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = clap::builder::BoolishValueParser::new()
+    )]
+    pub sysfs_snapshot_on_error: Option<bool>,
 }
 
 impl Cli {
@@ -42,28 +63,253 @@ impl Cli {
             None
         }
     }
+
+    /// Whether a failure saves a crash bundle. Bare `bop auto` runs from
+    /// udev with nobody watching, so it does unless told not to.
+    pub fn snapshot_on_error(&self) -> bool {
+        self.sysfs_snapshot_on_error
+            .unwrap_or(matches!(self.command, Command::Auto { action: None, .. }))
+    }
 }
 
 #[derive(Subcommand)]
 pub enum Command {
     /// Scan system and show power optimization findings
-    Audit,
+    Audit {
+        /// Keep running and re-audit whenever the config file changes
+        #[arg(long, conflicts_with = "json")]
+        watch_config: bool,
+
+        /// Power source to judge findings for; defaults to the current one.
+        /// Findings that don't matter there are listed apart and left out of
+        /// its score
+        #[arg(long, value_enum)]
+        context: Option<crate::audit::PowerContext>,
+
+        /// Print the files each finding was based on and what was read from them
+        #[arg(long)]
+        explain: bool,
+
+        /// JSON output that also carries each finding's evidence (implies --json)
+        #[arg(long, conflicts_with = "watch_config")]
+        verbose_json: bool,
+
+        /// Also print what the matched profile does differently from a
+        /// generic laptop, and why
+        #[arg(long)]
+        verbose: bool,
+
+        /// Print one line (score and finding counts) for a login banner, e.g.
+        /// `command -v bop >/dev/null && bop audit --oneline` in
+        /// /etc/profile.d/bop.sh. Runs no external commands and always exits 0
+        #[arg(
+            long,
+            conflicts_with_all = ["watch_config", "explain", "verbose_json", "verbose", "json"]
+        )]
+        oneline: bool,
+    },
 
     /// Apply recommended optimizations
     Apply {
         /// Show what would be changed without applying
         #[arg(long)]
         dry_run: bool,
+
+        /// Also show why each device was included or left alone
+        #[arg(long)]
+        explain: bool,
+
+        /// Append a JSON record of this run (changes, failures, scores) to FILE
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+
+        /// If another bop operation is running, wait up to DURATION (e.g. 30s,
+        /// 2m; a bare number is seconds) for it to finish
+        #[arg(long, value_name = "DURATION")]
+        wait: Option<Duration>,
+
+        /// Install the persistent changes (kernel params, modprobe and sysctl
+        /// drop-ins, persistence unit, state) into the image root DIR instead
+        /// of the running system, e.g. from a mkosi build script
+        #[arg(
+            long,
+            value_name = "DIR",
+            requires = "target_snapshot",
+            conflicts_with_all = ["wait", "report"]
+        )]
+        offline_root: Option<PathBuf>,
+
+        /// Plan for the machine a `bop snapshot -o FILE` was taken on rather
+        /// than this one; required with --offline-root, as the build host's
+        /// hardware says nothing about the image's
+        #[arg(long, value_name = "FILE", requires = "offline_root")]
+        target_snapshot: Option<PathBuf>,
+
+        /// Overwrite or delete generated files even if they lack bop's header
+        /// and aren't in the state manifest
+        #[arg(long)]
+        force: bool,
+
+        /// Disable conflicting services even when active units require them
+        #[arg(long)]
+        force_disable: bool,
+
+        /// Abort if the confirmation prompt gets no answer within DURATION
+        /// (exit status 124), e.g. when a deployment tool allocates a TTY
+        /// nobody is watching
+        #[arg(long, value_name = "DURATION")]
+        confirm_timeout: Option<Duration>,
+
+        /// Revert automatically after DURATION (e.g. 30m) unless kept with
+        /// `bop apply --confirm-keep`; a safety net for risky changes
+        #[arg(long, value_name = "DURATION", conflicts_with_all = ["dry_run", "offline_root"])]
+        rollback_after: Option<Duration>,
+
+        /// Keep the changes of an apply with --rollback-after: cancel the
+        /// pending automatic rollback and apply nothing
+        #[arg(
+            long,
+            conflicts_with_all = [
+                "dry_run", "explain", "report", "offline_root", "force",
+                "force_disable", "confirm_timeout", "rollback_after"
+            ]
+        )]
+        confirm_keep: bool,
+    },
+
+    /// Interactive first-run setup: audit, a few questions, then apply or auto enable
+    Setup {
+        /// Turn AC/battery auto-switching on or off without asking
+        #[arg(long, value_name = "BOOL", value_parser = clap::builder::BoolishValueParser::new())]
+        auto: Option<bool>,
+
+        /// Dim the backlight on battery without asking
+        #[arg(long, value_name = "BOOL", value_parser = clap::builder::BoolishValueParser::new())]
+        dim: Option<bool>,
+
+        /// Run `bop apply` at the end without asking (ignored with auto-switching)
+        #[arg(long, value_name = "BOOL", value_parser = clap::builder::BoolishValueParser::new())]
+        apply: Option<bool>,
+
+        /// Accept the default for every question not set by a flag
+        #[arg(short, long)]
+        yes: bool,
     },
 
     /// Real-time power draw monitoring (RAPL + battery)
-    Monitor,
+    Monitor {
+        #[command(subcommand)]
+        action: Option<MonitorAction>,
+
+        /// Also record every sample as CSV in a capture session under DIR
+        /// (a new file each day or every 10 MB)
+        #[arg(long, value_name = "DIR")]
+        log: Option<PathBuf>,
+
+        /// Append to the latest session in the --log directory instead of
+        /// starting a new one
+        #[arg(long, requires = "log")]
+        resume: bool,
+    },
+
+    /// Battery tools
+    Battery {
+        #[command(subcommand)]
+        action: BatteryAction,
+    },
+
+    /// Hardware profiles
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
 
     /// Undo all changes from saved state
-    Revert,
+    Revert {
+        /// Only revert one category of changes, keeping the rest recorded
+        #[arg(long, value_enum)]
+        only: Option<crate::revert::RevertScope>,
+
+        /// If another bop operation is running, wait up to DURATION (e.g. 30s,
+        /// 2m; a bare number is seconds) for it to finish
+        #[arg(long, value_name = "DURATION")]
+        wait: Option<Duration>,
+
+        /// Overwrite or delete generated files even if they lack bop's header
+        /// and aren't in the state manifest
+        #[arg(long)]
+        force: bool,
+
+        /// Print only warnings and failures, e.g. when run by the automatic
+        /// rollback timer
+        #[arg(long)]
+        quiet: bool,
+    },
+
+    /// Rewrite a persistence unit generated by an older bop in the current
+    /// format, from the saved state; runtime settings are left as they are
+    UpgradeArtifacts {
+        /// Replace the unit even if it was edited since bop wrote it
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Undo kernel parameter changes on a system mounted at DIR, e.g. from a
+    /// live USB when a bad parameter keeps it from booting
+    Rescue {
+        /// Where the system's root filesystem is mounted (with its /boot or
+        /// ESP mounted inside it)
+        #[arg(long, value_name = "DIR")]
+        root: PathBuf,
+
+        /// Only list the boot entry backups and how restoring changes them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Undo everything recorded, not just the kernel parameters
+        #[arg(long)]
+        all: bool,
+
+        /// Delete generated files even if they lack bop's header and aren't
+        /// in the state manifest
+        #[arg(long)]
+        force: bool,
+    },
 
     /// Show current optimization state and detect drift
-    Status,
+    Status {
+        /// For each drifted value, look for who changed it: processes holding
+        /// the file, running power daemons, and daemon defaults it matches
+        #[arg(long)]
+        explain_drift: bool,
+
+        /// Reuse the last check's systemd service results unless unit files
+        /// changed, a disabled service is running again or [status]
+        /// full_check_interval passed (for timers and frequent polling)
+        #[arg(long)]
+        incremental: bool,
+    },
+
+    /// List every file and setting bop can change, what it backs up, and what
+    /// it never touches
+    AboutSafety,
+
+    /// Find udev rules, systemd units and rc.local lines that fight bop's settings
+    Doctor {
+        /// Comment out the conflicting lines (units are disabled instead),
+        /// keeping backups for --undo
+        #[arg(long)]
+        disarm: bool,
+
+        /// Restore everything a previous --disarm changed
+        #[arg(long, conflicts_with = "disarm")]
+        undo: bool,
+
+        /// Write each value `bop apply` would change back to itself and
+        /// report which writes the kernel or a driver refuses; changes nothing
+        #[arg(long, conflicts_with_all = ["disarm", "undo"])]
+        probe: bool,
+    },
 
     /// Manage expansion card wakeup sources (Framework-specific)
     Wake {
@@ -75,6 +321,19 @@ pub enum Command {
     Auto {
         #[command(subcommand)]
         action: Option<AutoAction>,
+
+        /// Append a JSON record to FILE whenever optimizations are applied
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+
+        /// If another bop operation is running, wait up to DURATION (e.g. 30s,
+        /// 2m; a bare number is seconds) instead of skipping
+        #[arg(long, value_name = "DURATION")]
+        wait: Option<Duration>,
+
+        /// Replace or remove the udev rule even if it lacks bop's header
+        #[arg(long)]
+        force: bool,
     },
 
     /// Capture system state as a JSON snapshot for debugging or profile development
@@ -90,6 +349,25 @@ pub enum Command {
         action: ConfigAction,
     },
 
+    /// Show queued apply/revert notifications in this user session (run by
+    /// the bop-notify user service)
+    #[command(hide = true)]
+    NotifyListen,
+
+    /// Serve score, optimization state and power source read-only on the
+    /// system bus for desktop extensions, and re-disable wakeup sources
+    /// before each suspend
+    #[cfg(feature = "dbus")]
+    DbusServe {
+        #[command(subcommand)]
+        action: Option<DbusAction>,
+
+        /// Replace or remove the unit and bus policy even if they lack
+        /// bop's header
+        #[arg(long)]
+        force: bool,
+    },
+
     /// Generate shell completions
     Completions {
         /// Shell to generate completions for (auto-detected if omitted)
@@ -105,18 +383,58 @@ pub enum ConfigAction {
     Init,
     /// Show config file locations and which exist
     Path,
+    /// Check the config for out-of-range or conflicting values; exits
+    /// non-zero on errors
+    Validate,
 }
 
 #[derive(Subcommand)]
 pub enum AutoAction {
     /// Install udev rule for automatic switching and apply immediately
-    Enable,
+    Enable {
+        /// Also install a user service that shows desktop notifications
+        /// for apply/revert events in each graphical session
+        #[arg(long)]
+        with_notify: bool,
+    },
     /// Remove udev rule and stop automatic switching
     Disable,
     /// Show auto-switching status
     Status,
 }
 
+#[cfg(feature = "dbus")]
+#[derive(Subcommand)]
+pub enum DbusAction {
+    /// Install the bus policy and system unit, and start the service
+    Enable,
+    /// Stop the service and remove the unit and bus policy
+    Disable,
+}
+
+#[derive(Subcommand)]
+pub enum MonitorAction {
+    /// Print per-hour averages and a day/night split for a capture session
+    Analyze {
+        /// A session directory, or a --log directory to read its latest session
+        dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BatteryAction {
+    /// Measure the battery's real capacity over a full charge/discharge
+    /// cycle and correct remaining-time estimates with it
+    Calibrate,
+}
+
+#[derive(Subcommand)]
+pub enum ProfileAction {
+    /// Show the profile matched for this machine and what it does
+    /// differently from a generic laptop
+    Show,
+}
+
 #[derive(Subcommand)]
 pub enum WakeAction {
     /// List all USB controllers, connected devices, and wake status
@@ -0,0 +1,346 @@
+//! External commands (systemctl, udevadm, grub-mkconfig, ...) run with a
+//! deadline. Each command gets its own process group so that a timeout
+//! kills everything it started, and the child is always reaped before
+//! returning, so a hung tool can't leave an operation holding the lock.
+
+use crate::error::{Error, Result};
+use nix::sys::signal::{Signal, killpg};
+use nix::unistd::Pid;
+use std::cell::Cell;
+use std::ffi::OsStr;
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::RwLock;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Default limit for a single command.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// grub-mkconfig runs os-prober across every disk, which can take minutes.
+pub const GRUB_MKCONFIG_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Captured stdout/stderr beyond this many bytes is dropped.
+pub const MAX_OUTPUT: usize = 1 << 20;
+
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    pub default: Duration,
+    pub grub_mkconfig: Duration,
+}
+
+static TIMEOUTS: RwLock<Timeouts> = RwLock::new(Timeouts {
+    default: DEFAULT_TIMEOUT,
+    grub_mkconfig: GRUB_MKCONFIG_TIMEOUT,
+});
+
+thread_local! {
+    static SPAWNS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Commands this thread has tried to start, so a test can assert a path
+/// runs none.
+pub fn spawns_on_this_thread() -> usize {
+    SPAWNS.with(Cell::get)
+}
+
+/// Set the limits used by every [`Cmd`] created afterwards.
+pub fn set_timeouts(timeouts: Timeouts) {
+    if let Ok(mut current) = TIMEOUTS.write() {
+        *current = timeouts;
+    }
+}
+
+fn timeout_for(program: &str) -> Duration {
+    let timeouts = TIMEOUTS.read().map(|t| *t).unwrap_or(Timeouts {
+        default: DEFAULT_TIMEOUT,
+        grub_mkconfig: GRUB_MKCONFIG_TIMEOUT,
+    });
+    match program {
+        "grub-mkconfig" => timeouts.grub_mkconfig,
+        _ => timeouts.default,
+    }
+}
+
+/// A [`std::process::Command`] with a timeout and an output cap. stdin is
+/// always /dev/null: the child runs in its own process group, where reading
+/// the terminal would stop it.
+pub struct Cmd {
+    command: Command,
+    /// Program and arguments, for error messages
+    display: String,
+    timeout: Duration,
+    max_output: usize,
+}
+
+impl Cmd {
+    pub fn new(program: &str) -> Self {
+        let mut command = Command::new(program);
+        command.stdin(Stdio::null()).process_group(0);
+        Self {
+            command,
+            display: program.to_string(),
+            timeout: timeout_for(program),
+            max_output: MAX_OUTPUT,
+        }
+    }
+
+    pub fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        self.display.push(' ');
+        self.display.push_str(&arg.as_ref().to_string_lossy());
+        self.command.arg(arg);
+        self
+    }
+
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+
+    pub fn env(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> &mut Self {
+        self.command.env(key, value);
+        self
+    }
+
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn max_output(&mut self, bytes: usize) -> &mut Self {
+        self.max_output = bytes;
+        self
+    }
+
+    /// Run with stdout and stderr inherited, like [`Command::status`].
+    pub fn status(&mut self) -> Result<ExitStatus> {
+        let start = Instant::now();
+        let mut child = self.spawn()?;
+        self.wait(&mut child, start)
+    }
+
+    /// Run and capture stdout and stderr, each truncated to the output cap.
+    pub fn output(&mut self) -> Result<Output> {
+        self.command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        let start = Instant::now();
+        let mut child = self.spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .map(|pipe| read_capped(pipe, self.max_output));
+        let stderr = child
+            .stderr
+            .take()
+            .map(|pipe| read_capped(pipe, self.max_output));
+
+        // On timeout the readers are left behind; they finish once the
+        // killed group's ends of the pipes close.
+        let status = self.wait(&mut child, start)?;
+        let collect = |reader: Option<JoinHandle<Vec<u8>>>| {
+            reader.and_then(|r| r.join().ok()).unwrap_or_default()
+        };
+        Ok(Output {
+            status,
+            stdout: collect(stdout),
+            stderr: collect(stderr),
+        })
+    }
+
+    fn spawn(&mut self) -> Result<Child> {
+        SPAWNS.with(|n| n.set(n.get() + 1));
+        self.command
+            .spawn()
+            .map_err(|e| Error::Other(format!("failed to run {}: {}", self.display, e)))
+    }
+
+    fn wait(&self, child: &mut Child, start: Instant) -> Result<ExitStatus> {
+        let mut poll = Duration::from_millis(1);
+        loop {
+            let status = child
+                .try_wait()
+                .map_err(|e| Error::Other(format!("failed to wait for {}: {}", self.display, e)))?;
+            if let Some(status) = status {
+                return Ok(status);
+            }
+
+            let elapsed = start.elapsed();
+            if elapsed >= self.timeout {
+                // The group id is the child's pid (process_group(0))
+                let _ = killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL);
+                let _ = child.wait();
+                return Err(Error::CommandTimeout {
+                    command: self.display.clone(),
+                    elapsed: start.elapsed(),
+                });
+            }
+            std::thread::sleep(poll.min(self.timeout - elapsed));
+            poll = (poll * 2).min(MAX_POLL_INTERVAL);
+        }
+    }
+}
+
+/// Drain `pipe` on a thread, keeping the first `limit` bytes. Reading
+/// continues past the limit so the child never blocks on a full pipe.
+fn read_capped(mut pipe: impl Read + Send + 'static, limit: usize) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut kept = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            match pipe.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    let room = limit.saturating_sub(kept.len());
+                    kept.extend_from_slice(&buf[..n.min(room)]);
+                }
+            }
+        }
+        kept
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn read_pid(path: &Path) -> i32 {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Ok(pid) = std::fs::read_to_string(path)
+                && let Ok(pid) = pid.trim().parse()
+            {
+                return pid;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "{} never written",
+                path.display()
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Gone from the process table, or a zombie waiting on a reaper other
+    /// than us (a grandchild reparented to init).
+    fn is_dead(pid: i32) -> bool {
+        match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Err(_) => true,
+            Ok(stat) => stat
+                .rsplit_once(") ")
+                .is_some_and(|(_, rest)| rest.starts_with('Z')),
+        }
+    }
+
+    #[test]
+    fn test_output_and_status() {
+        let spawns = spawns_on_this_thread();
+        let out = Cmd::new("sh")
+            .args(["-c", "echo hello; echo oops >&2"])
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        assert_eq!(out.stdout, b"hello\n");
+        assert_eq!(out.stderr, b"oops\n");
+
+        let status = Cmd::new("sh").args(["-c", "exit 3"]).status().unwrap();
+        assert_eq!(status.code(), Some(3));
+
+        assert!(matches!(
+            Cmd::new("/nonexistent/bop-test-tool").status(),
+            Err(Error::Other(_))
+        ));
+        // Attempts count, whether or not the program exists
+        assert_eq!(spawns_on_this_thread(), spawns + 3);
+    }
+
+    #[test]
+    fn test_output_is_capped() {
+        let out = Cmd::new("sh")
+            .args(["-c", "head -c 100000 /dev/zero"])
+            .max_output(1000)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        assert_eq!(out.stdout.len(), 1000);
+    }
+
+    #[test]
+    fn test_timeout_kills_and_reaps_the_process_group() {
+        let tmp = TempDir::new().unwrap();
+        let shell_pid = tmp.path().join("shell.pid");
+        let sleeper_pid = tmp.path().join("sleeper.pid");
+        let script = format!(
+            "echo $$ > {}; sleep 30 & echo $! > {}; wait",
+            shell_pid.display(),
+            sleeper_pid.display()
+        );
+
+        let start = Instant::now();
+        let err = Cmd::new("sh")
+            .args(["-c", &script])
+            .timeout(Duration::from_millis(300))
+            .output()
+            .unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        match err {
+            Error::CommandTimeout { command, elapsed } => {
+                assert!(command.starts_with("sh -c"));
+                assert!(elapsed >= Duration::from_millis(300));
+            }
+            other => panic!("expected CommandTimeout, got {:?}", other),
+        }
+
+        // The shell was reaped by us: not even a zombie is left
+        let shell = read_pid(&shell_pid);
+        assert_eq!(
+            nix::sys::signal::kill(Pid::from_raw(shell), None),
+            Err(nix::errno::Errno::ESRCH)
+        );
+        // Its background child in the same group was killed too
+        let sleeper = read_pid(&sleeper_pid);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !is_dead(sleeper) {
+            assert!(Instant::now() < deadline, "sleeper {} survived", sleeper);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_timeout_releases_the_operation_lock() {
+        let tmp = TempDir::new().unwrap();
+        let lock_path = tmp.path().join("op.lock");
+
+        let holder_path = lock_path.clone();
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let holder = std::thread::spawn(move || -> Result<()> {
+            let _lock = crate::lock::acquire_at(&holder_path, "apply", None)?;
+            locked_tx.send(()).unwrap();
+            Cmd::new("sleep")
+                .arg("30")
+                .timeout(Duration::from_millis(300))
+                .status()?;
+            Ok(())
+        });
+
+        // Waits out the hung command, then gets the lock
+        locked_rx.recv().unwrap();
+        let start = Instant::now();
+        let lock = crate::lock::acquire_at(&lock_path, "revert", Some(Duration::from_secs(10)));
+        assert!(lock.is_ok());
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(matches!(
+            holder.join().unwrap(),
+            Err(Error::CommandTimeout { .. })
+        ));
+    }
+}
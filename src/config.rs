@@ -1,4 +1,6 @@
-use crate::preset::{Preset, PresetKnobs};
+use crate::duration::Duration;
+use crate::pins::Pins;
+use crate::preset::{PlatformProfilePolicy, Preset, PresetKnobs};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::path::{Path, PathBuf};
@@ -9,12 +11,19 @@ use std::path::{Path, PathBuf};
 pub struct BopConfig {
     pub preset: Option<Preset>,
     pub auto: AutoConfig,
+    pub aggressive: AggressiveConfig,
     pub epp: EppConfig,
     pub brightness: BrightnessConfig,
+    pub display: DisplayConfig,
     pub inhibitors: InhibitorConfig,
     pub notifications: NotificationConfig,
+    pub commands: CommandConfig,
+    pub network: NetworkConfig,
+    pub idle_drain: IdleDrainConfig,
+    pub status: StatusConfig,
     #[serde(default)]
     pub overrides: KnobOverrides,
+    pub pins: Pins,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -24,6 +33,52 @@ pub struct AutoConfig {
     /// If true and no preset is set, treated as preset = "supersaver".
     #[serde(default)]
     pub aggressive: bool,
+    /// What `bop auto` does while the lid is closed, on top of what the
+    /// power source calls for. Opening the lid goes back to that.
+    pub on_lid_close: LidAction,
+    /// Battery percentage at or below which `bop auto`, while discharging,
+    /// holds back the optimizations that cost responsiveness (USB
+    /// autosuspend, WiFi power save) so there's time to save work and shut
+    /// down. Going 3 points above it or plugging in restores them. Unset:
+    /// off.
+    pub critical_battery_percent: Option<u8>,
+    /// Daily window ("23:00-07:00", local time) in which `bop auto` still
+    /// follows the power source but sends no notifications, leaves the
+    /// backlight alone and holds service changes until it ends.
+    pub quiet_hours: Option<crate::quiet_hours::QuietHours>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LidAction {
+    /// Apply the supersaver preset, on battery or AC.
+    ApplyAggressive,
+    /// Apply the configured preset, on AC too.
+    Apply,
+    /// Ignore the lid.
+    #[default]
+    Nothing,
+}
+
+impl LidAction {
+    /// The preset a closed lid calls for, given the configured one.
+    pub fn preset(self, configured: Preset) -> Option<Preset> {
+        match self {
+            Self::ApplyAggressive => Some(Preset::Supersaver),
+            Self::Apply => Some(configured),
+            Self::Nothing => None,
+        }
+    }
+}
+
+/// Extra settings for the supersaver preset (`--aggressive`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AggressiveConfig {
+    /// Cap the CPU package's long-term RAPL power limit at this many watts,
+    /// on machines whose powercap zone takes writes. Refused below the
+    /// hardware profile's floor. Unset: the limit is left alone.
+    pub package_power_limit_w: Option<u32>,
 }
 
 /// Per-knob overrides applied on top of the preset.
@@ -32,6 +87,9 @@ pub struct AutoConfig {
 pub struct KnobOverrides {
     pub epp: Option<String>,
     pub aspm_policy: Option<String>,
+    /// Set the platform profile to low-power (true), even over a "custom"
+    /// one, or leave it alone (false)
+    pub platform_profile: Option<bool>,
     pub pci_runtime_pm: Option<bool>,
     pub audio_power_save: Option<bool>,
     pub nmi_watchdog_disable: Option<bool>,
@@ -39,7 +97,10 @@ pub struct KnobOverrides {
     pub kernel_params: Option<bool>,
     pub acpi_wakeup_filter: Option<bool>,
     pub gpu_dpm: Option<bool>,
+    pub pcie_link_downgrade: Option<bool>,
     pub turbo_boost: Option<bool>,
+    /// Force acpi.ec_no_wakeup=1 on or off regardless of hardware profile
+    pub ec_no_wakeup: Option<bool>,
 }
 
 /// Resolve the effective preset: cli_preset > config.preset > migration > Moderate.
@@ -68,6 +129,14 @@ pub fn resolve_knobs(config: &BopConfig, preset: Preset) -> PresetKnobs {
     if let Some(ref aspm) = o.aspm_policy {
         knobs.aspm_policy = Some(Cow::Owned(aspm.clone()));
     }
+    if let Some(v) = o.platform_profile {
+        knobs.platform_profile = if v {
+            PlatformProfilePolicy::ForceLowPower
+        } else {
+            PlatformProfilePolicy::NoChange
+        };
+        knobs.platform_profile_locked = v;
+    }
     if let Some(v) = o.pci_runtime_pm {
         knobs.pci_runtime_pm = v;
     }
@@ -89,9 +158,18 @@ pub fn resolve_knobs(config: &BopConfig, preset: Preset) -> PresetKnobs {
     if let Some(v) = o.gpu_dpm {
         knobs.gpu_dpm = v;
     }
+    if let Some(v) = o.pcie_link_downgrade {
+        knobs.pcie_link_downgrade = v;
+    }
     if let Some(v) = o.turbo_boost {
         knobs.turbo_boost = Some(v);
     }
+    if let Some(v) = o.ec_no_wakeup {
+        knobs.ec_no_wakeup = Some(v);
+    }
+    if preset == Preset::Supersaver {
+        knobs.package_power_limit_w = config.aggressive.package_power_limit_w;
+    }
 
     knobs
 }
@@ -171,6 +249,91 @@ impl Default for BrightnessConfig {
     }
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    /// Remove a user-set amdgpu.dcdebugmask so PSR comes back, accepting
+    /// the flicker it was likely added to avoid.
+    pub force_psr: bool,
+}
+
+/// Thresholds for `bop monitor`'s idle drain watch, which looks for a
+/// USB-C retimer stuck in a high-power state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdleDrainConfig {
+    /// Watts above the usual idle draw that count as excess.
+    pub margin_watts: f64,
+    /// How long the excess must last before it's reported.
+    pub sustain: Duration,
+    /// Also send a desktop notification when it's reported.
+    pub notify: bool,
+}
+
+impl Default for IdleDrainConfig {
+    fn default() -> Self {
+        Self {
+            margin_watts: 1.0,
+            sustain: Duration::from_mins(10),
+            notify: false,
+        }
+    }
+}
+
+/// `bop status --incremental`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusConfig {
+    /// Longest an incremental check reuses service results before querying
+    /// systemd again.
+    pub full_check_interval: Duration,
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        Self {
+            full_check_interval: Duration::from_mins(60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Per-SSID WiFi power save settings, consulted by `bop auto`.
+    pub exceptions: Vec<NetworkException>,
+    /// WiFi traffic (rx+tx, MB/s) above which `bop auto` holds off turning
+    /// WiFi power save on until the link goes quiet. 0 never holds off.
+    pub busy_mb_per_sec: u64,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            exceptions: Vec::new(),
+            busy_mb_per_sec: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkException {
+    /// Exact, case-sensitive SSID as `iw` reports it
+    pub ssid: String,
+    /// WiFi power save while connected to this network
+    pub power_save: bool,
+}
+
+impl NetworkConfig {
+    /// The exception for `ssid`, if any. SSIDs are compared byte for byte
+    /// (they are case-sensitive), and a hidden network with no SSID
+    /// never matches, not even an entry with `ssid = ""`.
+    pub fn exception_for(&self, ssid: Option<&str>) -> Option<&NetworkException> {
+        let ssid = ssid.filter(|s| !s.is_empty())?;
+        self.exceptions.iter().find(|e| e.ssid == ssid)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct InhibitorConfig {
@@ -218,6 +381,36 @@ impl Default for NotificationConfig {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandConfig {
+    /// How long an external command (systemctl, udevadm, ...) may run
+    /// before it is killed.
+    pub timeout: Duration,
+    /// Time allowed for grub-mkconfig, which probes every disk.
+    pub grub_mkconfig_timeout: Duration,
+}
+
+impl Default for CommandConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(crate::cmd::DEFAULT_TIMEOUT.as_secs()),
+            grub_mkconfig_timeout: Duration::from_secs(crate::cmd::GRUB_MKCONFIG_TIMEOUT.as_secs()),
+        }
+    }
+}
+
+impl CommandConfig {
+    pub fn timeouts(&self) -> crate::cmd::Timeouts {
+        crate::cmd::Timeouts {
+            default: std::time::Duration::from_secs(self.timeout.as_secs().max(1)),
+            grub_mkconfig: std::time::Duration::from_secs(
+                self.grub_mkconfig_timeout.as_secs().max(1),
+            ),
+        }
+    }
+}
+
 pub const SYSTEM_CONFIG: &str = "/etc/bop/config.toml";
 
 /// Return the user config path (~/.config/bop/config.toml).
@@ -225,6 +418,356 @@ pub fn user_config_path() -> Option<PathBuf> {
     dirs::config_dir().map(|d| d.join("bop").join("config.toml"))
 }
 
+/// Values the kernel accepts in energy_performance_preference.
+const EPP_VALUES: &[&str] = &[
+    "default",
+    "performance",
+    "balance_performance",
+    "balance_power",
+    "power",
+];
+
+/// Values the kernel accepts in pcie_aspm/parameters/policy.
+const ASPM_POLICIES: &[&str] = &["default", "performance", "powersave", "powersupersave"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueSeverity {
+    /// Kept as written, but probably not what was meant
+    Warning,
+    /// The section it's in falls back to its defaults
+    Error,
+}
+
+/// A value the config loader rejected or doubts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigIssue {
+    /// Dotted path to the value, e.g. `epp.thresholds[1].battery_percent`;
+    /// empty for a problem with a whole file
+    pub field: String,
+    pub message: String,
+    pub severity: IssueSeverity,
+    /// The file the value came from, e.g. `user config ~/.config/bop/config.toml`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl ConfigIssue {
+    fn warning(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+            severity: IssueSeverity::Warning,
+            source: None,
+        }
+    }
+
+    fn error(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: IssueSeverity::Error,
+            ..Self::warning(field, message)
+        }
+    }
+
+    /// Top-level key the field is under, e.g. `epp`.
+    pub fn section(&self) -> &str {
+        self.field.split(['.', '[']).next().unwrap_or_default()
+    }
+
+    /// The `[pins]` key the issue is about, e.g. `/sys/module/foo` for
+    /// `pins."/sys/module/foo"`. A bad pin is dropped on its own.
+    fn pin(&self) -> Option<&str> {
+        self.field
+            .strip_prefix("pins.\"")
+            .and_then(|rest| rest.strip_suffix('"'))
+    }
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(source) = &self.source {
+            write!(f, "{}: ", source)?;
+        }
+        if !self.field.is_empty() {
+            write!(f, "{}: ", self.field)?;
+        }
+        write!(f, "{}", self.message)?;
+        match self.severity {
+            IssueSeverity::Warning => Ok(()),
+            IssueSeverity::Error if self.field.is_empty() => write!(f, "; file ignored"),
+            IssueSeverity::Error if self.pin().is_some() => write!(f, "; pin ignored"),
+            IssueSeverity::Error => write!(f, "; using defaults for [{}]", self.section()),
+        }
+    }
+}
+
+impl BopConfig {
+    /// Check every section's values. Errors are values bop can't act on
+    /// sensibly; warnings are legal but likely mistakes.
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        self.auto.validate(self.preset, &mut issues);
+        self.aggressive.validate(&mut issues);
+        self.epp.validate(&mut issues);
+        self.brightness.validate(&mut issues);
+        self.notifications.validate(&mut issues);
+        self.commands.validate(&mut issues);
+        self.network.validate(&mut issues);
+        self.idle_drain.validate(&mut issues);
+        self.status.validate(&mut issues);
+        self.overrides.validate(&mut issues);
+        for pattern in self.pins.patterns() {
+            if !pattern.starts_with('/') {
+                issues.push(ConfigIssue::error(
+                    format!("pins.\"{}\"", pattern),
+                    "not an absolute path, so it never matches",
+                ));
+            }
+        }
+        issues
+    }
+
+    /// Put `section` back to its defaults.
+    fn reset_section(&mut self, section: &str) {
+        let defaults = Self::default();
+        match section {
+            "preset" => self.preset = defaults.preset,
+            "auto" => self.auto = defaults.auto,
+            "aggressive" => self.aggressive = defaults.aggressive,
+            "epp" => self.epp = defaults.epp,
+            "brightness" => self.brightness = defaults.brightness,
+            "display" => self.display = defaults.display,
+            "inhibitors" => self.inhibitors = defaults.inhibitors,
+            "notifications" => self.notifications = defaults.notifications,
+            "commands" => self.commands = defaults.commands,
+            "network" => self.network = defaults.network,
+            "idle_drain" => self.idle_drain = defaults.idle_drain,
+            "status" => self.status = defaults.status,
+            "overrides" => self.overrides = defaults.overrides,
+            "pins" => self.pins = defaults.pins,
+            _ => {}
+        }
+    }
+}
+
+impl AutoConfig {
+    fn validate(&self, preset: Option<Preset>, issues: &mut Vec<ConfigIssue>) {
+        if self.aggressive {
+            let message = match preset {
+                Some(preset) => format!("deprecated, and ignored since preset = \"{}\"", preset),
+                None => "deprecated; use preset = \"supersaver\"".to_string(),
+            };
+            issues.push(ConfigIssue::warning("auto.aggressive", message));
+        }
+        match self.critical_battery_percent {
+            Some(0) => issues.push(ConfigIssue::warning(
+                "auto.critical_battery_percent",
+                "0 is never reached while the machine runs; leave it unset to turn this off",
+            )),
+            Some(percent) if percent > 100 => issues.push(ConfigIssue::error(
+                "auto.critical_battery_percent",
+                format!("{} is above 100", percent),
+            )),
+            Some(percent) if percent > 50 => issues.push(ConfigIssue::warning(
+                "auto.critical_battery_percent",
+                format!(
+                    "{}% is hardly critical; USB autosuspend and WiFi power save would be off for most of each discharge",
+                    percent
+                ),
+            )),
+            _ => {}
+        }
+        if self.quiet_hours.is_some_and(|q| q.is_empty()) {
+            issues.push(ConfigIssue::warning(
+                "auto.quiet_hours",
+                "starts and ends at the same time, so it never applies",
+            ));
+        }
+    }
+}
+
+impl AggressiveConfig {
+    fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if self.package_power_limit_w == Some(0) {
+            issues.push(ConfigIssue::error(
+                "aggressive.package_power_limit_w",
+                "0 W would stop the CPU; leave it unset to keep the firmware's limit",
+            ));
+        }
+    }
+}
+
+impl EppConfig {
+    fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if self.thresholds.is_empty() {
+            if self.adaptive {
+                issues.push(ConfigIssue::error(
+                    "epp.thresholds",
+                    "adaptive EPP needs at least one threshold",
+                ));
+            }
+            return;
+        }
+        let mut seen = std::collections::BTreeSet::new();
+        for (i, threshold) in self.thresholds.iter().enumerate() {
+            let field = format!("epp.thresholds[{}].battery_percent", i);
+            if threshold.battery_percent > 100 {
+                issues.push(ConfigIssue::error(
+                    field,
+                    format!("{} is above 100", threshold.battery_percent),
+                ));
+            } else if !seen.insert(threshold.battery_percent) {
+                issues.push(ConfigIssue::error(
+                    field,
+                    format!(
+                        "{} appears more than once; only the first entry would ever apply",
+                        threshold.battery_percent
+                    ),
+                ));
+            }
+        }
+        if let Some(highest) = self.thresholds.iter().map(|t| t.battery_percent).max()
+            && highest < 100
+        {
+            issues.push(ConfigIssue::warning(
+                "epp.thresholds",
+                format!(
+                    "highest battery_percent is {}; above it adaptive EPP falls back to the preset's value (add an entry for 100)",
+                    highest
+                ),
+            ));
+        }
+    }
+}
+
+impl BrightnessConfig {
+    fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        match self.dim_percent {
+            0 => issues.push(ConfigIssue::error(
+                "brightness.dim_percent",
+                "0 would turn the backlight off; use 1-100",
+            )),
+            101.. => issues.push(ConfigIssue::error(
+                "brightness.dim_percent",
+                format!("{} is above 100; use 1-100", self.dim_percent),
+            )),
+            100 if self.auto_dim => issues.push(ConfigIssue::warning(
+                "brightness.dim_percent",
+                "100 leaves brightness unchanged, so auto_dim does nothing",
+            )),
+            _ => {}
+        }
+    }
+}
+
+impl NotificationConfig {
+    fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if self.enabled && !self.on_apply && !self.on_revert {
+            issues.push(ConfigIssue::warning(
+                "notifications.enabled",
+                "on_apply and on_revert are both false, so nothing is ever shown",
+            ));
+        }
+    }
+}
+
+impl CommandConfig {
+    fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        for (field, value) in [
+            ("commands.timeout", self.timeout),
+            ("commands.grub_mkconfig_timeout", self.grub_mkconfig_timeout),
+        ] {
+            if value.as_secs() == 0 {
+                issues.push(ConfigIssue::error(
+                    field,
+                    "0s would kill every command as it starts",
+                ));
+            }
+        }
+    }
+}
+
+impl NetworkConfig {
+    fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        let mut seen = std::collections::BTreeSet::new();
+        for (i, exception) in self.exceptions.iter().enumerate() {
+            let field = format!("network.exceptions[{}].ssid", i);
+            if exception.ssid.is_empty() {
+                issues.push(ConfigIssue::warning(
+                    field,
+                    "empty SSID never matches, not even a hidden network",
+                ));
+            } else if !seen.insert(exception.ssid.as_str()) {
+                issues.push(ConfigIssue::warning(
+                    field,
+                    format!(
+                        "\"{}\" appears more than once; the first entry wins",
+                        exception.ssid
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+impl IdleDrainConfig {
+    fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if !self.margin_watts.is_finite() || self.margin_watts <= 0.0 {
+            issues.push(ConfigIssue::error(
+                "idle_drain.margin_watts",
+                format!(
+                    "{} must be above 0, or every idle sample counts as excess",
+                    self.margin_watts
+                ),
+            ));
+        }
+        if self.sustain.as_secs() == 0 {
+            issues.push(ConfigIssue::warning(
+                "idle_drain.sustain",
+                "0s reports any excess at once, including brief spikes",
+            ));
+        }
+    }
+}
+
+impl StatusConfig {
+    fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if self.full_check_interval.as_secs() == 0 {
+            issues.push(ConfigIssue::warning(
+                "status.full_check_interval",
+                "0s makes every `bop status --incremental` a full check",
+            ));
+        }
+    }
+}
+
+impl KnobOverrides {
+    fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if let Some(epp) = &self.epp
+            && !EPP_VALUES.contains(&epp.as_str())
+        {
+            issues.push(ConfigIssue::error(
+                "overrides.epp",
+                format!("\"{}\" isn't one of {}", epp, EPP_VALUES.join(", ")),
+            ));
+        }
+        if let Some(policy) = &self.aspm_policy
+            && !ASPM_POLICIES.contains(&policy.as_str())
+        {
+            issues.push(ConfigIssue::error(
+                "overrides.aspm_policy",
+                format!("\"{}\" isn't one of {}", policy, ASPM_POLICIES.join(", ")),
+            ));
+        }
+        if self.dirty_writeback == Some(0) {
+            issues.push(ConfigIssue::warning(
+                "overrides.dirty_writeback",
+                "0 turns periodic writeback off; dirty pages then only go out under memory pressure",
+            ));
+        }
+    }
+}
+
 /// Generate a default config file as a TOML string with comments.
 pub fn default_config_toml() -> String {
     let header = "\
@@ -253,26 +796,112 @@ pub fn default_config_toml() -> String {
 # kernel_params = true
 # acpi_wakeup_filter = true
 # gpu_dpm = true
+# pcie_link_downgrade = false  # supersaver: pin an idle dGPU's link at 2.5 GT/s
 # turbo_boost = false
+# ec_no_wakeup = false   # default: only on profiles known to need it
+
+# Sysfs values bop must never change. Keys are absolute paths; `*` and `?`
+# match within one path segment, so `cpu*` covers every CPU. The value is
+# what you keep there, or \"leave\" to just keep bop away.
+# - Pinned paths are dropped from every plan: apply, auto (including
+#   adaptive EPP) and the persistence unit. Pins beat the preset and
+#   [overrides]; they only cover sysfs/procfs writes, not kernel
+#   parameters, services or ACPI wakeup sources.
+# - `bop status` checks a pinned path against its pinned value, and skips
+#   \"leave\" paths. `bop audit` marks the matching finding \"pinned by user\".
+# - When several pins match a path, an exact path beats a glob, then the
+#   longest pattern wins.
+# [pins]
+# \"/sys/devices/system/cpu/cpu*/cpufreq/energy_performance_preference\" = \"balance_performance\"
+# \"/sys/module/snd_hda_intel/parameters/power_save\" = \"leave\"
 ";
     let body = toml::to_string_pretty(&BopConfig::default())
         .unwrap_or_else(|_| String::from("# failed to serialize defaults\n"));
     format!("{}\n{}", header, body)
 }
 
-/// Load the system config file if it exists.
-fn load_system() -> Option<toml::Value> {
-    let path = Path::new(SYSTEM_CONFIG);
-    let content = std::fs::read_to_string(path).ok()?;
-    toml::from_str(&content).ok()
+/// Keys from before time values took units: (table, old key, new key,
+/// seconds per unit of the old key's bare number).
+const RENAMED_DURATION_KEYS: &[(&str, &str, &str, i64)] = &[
+    ("commands", "timeout_secs", "timeout", 1),
+    (
+        "commands",
+        "grub_mkconfig_timeout_secs",
+        "grub_mkconfig_timeout",
+        1,
+    ),
+    ("idle_drain", "sustain_minutes", "sustain", 60),
+];
+
+/// Rewrite renamed duration keys in one file's table to their current
+/// names, so an old key in the system config and a new one in the user
+/// config merge like any other. A new key in the same file wins.
+fn migrate_keys(value: &mut toml::Value) {
+    for (table, old, new, unit) in RENAMED_DURATION_KEYS {
+        let Some(table) = value.get_mut(*table).and_then(|t| t.as_table_mut()) else {
+            continue;
+        };
+        let Some(legacy) = table.remove(*old) else {
+            continue;
+        };
+        if table.contains_key(*new) {
+            continue;
+        }
+        let migrated = match legacy {
+            toml::Value::Integer(n) => toml::Value::Integer(n.saturating_mul(*unit)),
+            other => other,
+        };
+        table.insert(new.to_string(), migrated);
+    }
 }
 
-/// Load the user config file (~/.config/bop/config.toml) if it exists.
-fn load_user() -> Option<toml::Value> {
-    let dir = dirs::config_dir()?;
-    let path = dir.join("bop").join("config.toml");
-    let content = std::fs::read_to_string(path).ok()?;
-    toml::from_str(&content).ok()
+/// Parse one config file, migrating renamed keys first.
+pub fn parse(content: &str) -> Result<BopConfig, toml::de::Error> {
+    let mut value: toml::Value = toml::from_str(content)?;
+    migrate_keys(&mut value);
+    value.try_into()
+}
+
+/// One config file's values, with where they came from.
+struct Layer {
+    /// e.g. `user config ~/.config/bop/config.toml`
+    source: String,
+    value: toml::Value,
+}
+
+/// Read one config file with renamed keys migrated. A missing file is
+/// only an issue when it was asked for by name.
+fn load_layer(
+    kind: &str,
+    path: &Path,
+    required: bool,
+    issues: &mut Vec<ConfigIssue>,
+) -> Option<Layer> {
+    let source = format!("{} {}", kind, path.display());
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            issues.push(ConfigIssue {
+                source: Some(source),
+                ..ConfigIssue::error("", format!("failed to read: {}", e))
+            });
+            return None;
+        }
+    };
+    match toml::from_str(&content) {
+        Ok(mut value) => {
+            migrate_keys(&mut value);
+            Some(Layer { source, value })
+        }
+        Err(e) => {
+            issues.push(ConfigIssue {
+                source: Some(source),
+                ..ConfigIssue::error("", format!("failed to parse: {}", e.message()))
+            });
+            None
+        }
+    }
 }
 
 /// Recursively merge two TOML values. Tables are merged key-by-key;
@@ -293,51 +922,133 @@ fn merge_values(base: toml::Value, overlay: toml::Value) -> toml::Value {
     }
 }
 
-/// Load config from a specific path, ignoring system/user files.
-fn load_from_path(path: &Path) -> BopConfig {
-    match std::fs::read_to_string(path) {
-        Ok(content) => toml::from_str(&content).unwrap_or_else(|e| {
-            eprintln!(
-                "warning: failed to parse config at {}: {}",
-                path.display(),
-                e
-            );
-            BopConfig::default()
-        }),
-        Err(e) => {
-            eprintln!(
-                "warning: failed to read config at {}: {}",
-                path.display(),
-                e
-            );
-            BopConfig::default()
+/// Deserialize merged values. A section of the wrong shape (e.g.
+/// `dim_percent = "low"`) is dropped on its own, keeping the rest.
+fn deserialize_sections(value: toml::Value, issues: &mut Vec<ConfigIssue>) -> BopConfig {
+    if let Ok(config) = value.clone().try_into() {
+        return config;
+    }
+    let toml::Value::Table(table) = value else {
+        return BopConfig::default();
+    };
+    let mut kept = toml::Table::new();
+    for (key, mut section) in table {
+        // One bad pin shouldn't cost the others
+        if key == "pins"
+            && let toml::Value::Table(pins) = &mut section
+        {
+            pins.retain(|pattern, value| {
+                let ok = value.is_str();
+                if !ok {
+                    issues.push(ConfigIssue::error(
+                        format!("pins.\"{}\"", pattern),
+                        "the value must be a string",
+                    ));
+                }
+                ok
+            });
+        }
+        let mut candidate = kept.clone();
+        candidate.insert(key.clone(), section);
+        match toml::Value::Table(candidate.clone()).try_into::<BopConfig>() {
+            Ok(_) => kept = candidate,
+            Err(e) => issues.push(ConfigIssue::error(key, e.message().to_string())),
         }
     }
+    toml::Value::Table(kept).try_into().unwrap_or_default()
 }
 
-/// Load the merged config: system defaults, then user overrides.
-/// If `override_path` is provided, use only that file instead.
-pub fn load(override_path: Option<&PathBuf>) -> BopConfig {
-    if let Some(path) = override_path {
-        return load_from_path(path);
-    }
-
-    let system = load_system();
-    let user = load_user();
+/// The last layer that sets `field`, or failing that its section.
+fn source_of(field: &str, layers: &[Layer]) -> Option<String> {
+    let keys: Vec<&str> = field
+        .split('.')
+        .map(|k| k.split('[').next().unwrap_or(k).trim_matches('"'))
+        .take(2)
+        .collect();
+    let sets = |layer: &Layer, depth: usize| {
+        let mut value = Some(&layer.value);
+        for key in &keys[..depth] {
+            value = value.and_then(|v| v.get(*key));
+        }
+        value.is_some()
+    };
+    (1..=keys.len())
+        .rev()
+        .find_map(|depth| layers.iter().rev().find(|l| sets(l, depth)))
+        .map(|layer| layer.source.clone())
+}
 
-    let merged = match (system, user) {
-        (Some(s), Some(u)) => Some(merge_values(s, u)),
-        (Some(v), None) | (None, Some(v)) => Some(v),
-        (None, None) => None,
+/// Load the merged config, system file then user file, or only
+/// `override_path` when given, along with everything wrong with it. A
+/// section with an error falls back to its defaults; the others are kept.
+/// A bad `[pins]` entry is dropped alone.
+pub fn load_checked(override_path: Option<&PathBuf>) -> (BopConfig, Vec<ConfigIssue>) {
+    let mut issues = Vec::new();
+    let layers: Vec<Layer> = match override_path {
+        Some(path) => load_layer("config", path, true, &mut issues)
+            .into_iter()
+            .collect(),
+        None => [
+            load_layer(
+                "system config",
+                Path::new(SYSTEM_CONFIG),
+                false,
+                &mut issues,
+            ),
+            user_config_path()
+                .and_then(|path| load_layer("user config", &path, false, &mut issues)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
     };
 
-    match merged {
-        Some(value) => value.try_into().unwrap_or_else(|e| {
-            eprintln!("warning: failed to deserialize config: {}", e);
-            BopConfig::default()
-        }),
+    let merged = layers
+        .iter()
+        .map(|layer| layer.value.clone())
+        .reduce(merge_values);
+    let mut config = match merged {
+        Some(value) => deserialize_sections(value, &mut issues),
         None => BopConfig::default(),
+    };
+    issues.extend(config.validate());
+
+    for issue in &mut issues {
+        if issue.source.is_none() {
+            issue.source = source_of(&issue.field, &layers);
+        }
+        if issue.severity == IssueSeverity::Error {
+            match issue.pin() {
+                Some(pattern) => config.pins.remove(pattern),
+                None => config.reset_section(issue.section()),
+            }
+        }
     }
+    (config, issues)
+}
+
+/// Print issues from [`load_checked`] to stderr.
+pub fn warn_issues(issues: &[ConfigIssue]) {
+    for issue in issues {
+        let level = match issue.severity {
+            IssueSeverity::Warning => "warning",
+            IssueSeverity::Error => "error",
+        };
+        if issue.source.is_some() {
+            eprintln!("{}: {}", level, issue);
+        } else {
+            eprintln!("{}: config: {}", level, issue);
+        }
+    }
+}
+
+/// Load the merged config: system defaults, then user overrides.
+/// If `override_path` is provided, use only that file instead. Issues are
+/// printed to stderr.
+pub fn load(override_path: Option<&PathBuf>) -> BopConfig {
+    let (config, issues) = load_checked(override_path);
+    warn_issues(&issues);
+    config
 }
 
 #[cfg(test)]
@@ -355,10 +1066,87 @@ mod tests {
         assert_eq!(config.epp.thresholds[0].epp_value, EppHint::Power);
         assert!(!config.brightness.auto_dim);
         assert_eq!(config.brightness.dim_percent, 60);
+        assert!(!config.display.force_psr);
         assert_eq!(config.inhibitors.mode, InhibitorMode::Reduced);
         assert!(!config.notifications.enabled);
         assert!(config.notifications.on_apply);
         assert!(config.notifications.on_revert);
+        assert_eq!(config.commands.timeout, Duration::from_secs(30));
+        assert_eq!(
+            config.commands.grub_mkconfig_timeout,
+            Duration::from_mins(5)
+        );
+        assert_eq!(config.idle_drain.margin_watts, 1.0);
+        assert_eq!(config.idle_drain.sustain, Duration::from_mins(10));
+        assert!(!config.idle_drain.notify);
+        assert_eq!(config.status.full_check_interval, Duration::from_mins(60));
+    }
+
+    #[test]
+    fn test_duration_fields_accept_units_and_legacy_keys() {
+        let config: BopConfig = toml::from_str(
+            r#"
+            [commands]
+            timeout = "45s"
+            grub_mkconfig_timeout = "10m"
+            [idle_drain]
+            sustain = "1h30m"
+            [status]
+            full_check_interval = "30m"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.commands.timeout, Duration::from_secs(45));
+        assert_eq!(
+            config.commands.grub_mkconfig_timeout,
+            Duration::from_mins(10)
+        );
+        assert_eq!(config.idle_drain.sustain, Duration::from_mins(90));
+        assert_eq!(config.status.full_check_interval, Duration::from_mins(30));
+
+        // Keys from before durations took units keep their units
+        let legacy = parse(
+            r#"
+            [commands]
+            timeout_secs = 45
+            grub_mkconfig_timeout_secs = 600
+            [idle_drain]
+            sustain_minutes = 15
+            "#,
+        )
+        .unwrap();
+        assert_eq!(legacy.commands.timeout, Duration::from_secs(45));
+        assert_eq!(
+            legacy.commands.grub_mkconfig_timeout,
+            Duration::from_mins(10)
+        );
+        assert_eq!(legacy.idle_drain.sustain, Duration::from_mins(15));
+        assert_eq!(legacy.commands.timeouts().default.as_secs(), 45);
+
+        let err = toml::from_str::<BopConfig>("[commands]\ntimeout = \"30 sec\"")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("timeout") && err.contains("invalid duration"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_config_show_renders_durations() {
+        let shown = toml::to_string_pretty(&BopConfig::default()).unwrap();
+        assert!(shown.contains("timeout = \"30s\""), "{}", shown);
+        assert!(
+            shown.contains("grub_mkconfig_timeout = \"5m\""),
+            "{}",
+            shown
+        );
+        assert!(shown.contains("sustain = \"10m\""), "{}", shown);
+
+        let reparsed: BopConfig = toml::from_str(&shown).unwrap();
+        assert_eq!(reparsed.idle_drain.sustain, Duration::from_mins(10));
+        assert_eq!(reparsed.commands.timeout, Duration::from_secs(30));
     }
 
     #[test]
@@ -424,6 +1212,7 @@ mod tests {
         assert!(config.epp.adaptive);
         // Defaults for everything else
         assert!(!config.auto.aggressive);
+        assert_eq!(config.auto.on_lid_close, LidAction::Nothing);
         assert!(!config.brightness.auto_dim);
         assert_eq!(config.brightness.dim_percent, 60);
     }
@@ -433,6 +1222,7 @@ mod tests {
         let toml_str = r#"
             [auto]
             aggressive = true
+            on_lid_close = "apply_aggressive"
 
             [epp]
             adaptive = true
@@ -453,6 +1243,9 @@ mod tests {
             auto_dim = true
             dim_percent = 40
 
+            [display]
+            force_psr = true
+
             [inhibitors]
             mode = "skip"
 
@@ -460,24 +1253,96 @@ mod tests {
             enabled = true
             on_apply = true
             on_revert = false
+
+            [commands]
+            timeout = "10s"
+            grub_mkconfig_timeout = "15m"
+
+            [network]
+            busy_mb_per_sec = 20
+
+            [[network.exceptions]]
+            ssid = "OfficeNet"
+            power_save = false
         "#;
         let config: BopConfig = toml::from_str(toml_str).unwrap();
         assert!(config.auto.aggressive);
+        assert_eq!(config.auto.on_lid_close, LidAction::ApplyAggressive);
         assert!(config.epp.adaptive);
         assert_eq!(config.epp.thresholds.len(), 3);
         assert_eq!(config.epp.thresholds[0].battery_percent, 30);
         assert!(config.brightness.auto_dim);
         assert_eq!(config.brightness.dim_percent, 40);
+        assert!(config.display.force_psr);
         assert_eq!(config.inhibitors.mode, InhibitorMode::Skip);
         assert!(config.notifications.enabled);
         assert!(!config.notifications.on_revert);
+        assert_eq!(
+            config.commands.timeouts().grub_mkconfig,
+            std::time::Duration::from_secs(900)
+        );
+        assert_eq!(
+            config.commands.timeouts().default,
+            std::time::Duration::from_secs(10)
+        );
+        assert_eq!(
+            config.network.exceptions,
+            [NetworkException {
+                ssid: "OfficeNet".to_string(),
+                power_save: false,
+            }]
+        );
+        assert_eq!(config.network.busy_mb_per_sec, 20);
+    }
+
+    #[test]
+    fn test_network_exception_matching() {
+        let network = NetworkConfig {
+            exceptions: vec![
+                NetworkException {
+                    ssid: "OfficeNet".to_string(),
+                    power_save: false,
+                },
+                NetworkException {
+                    ssid: "".to_string(),
+                    power_save: false,
+                },
+                NetworkException {
+                    ssid: "Café 5G".to_string(),
+                    power_save: true,
+                },
+            ],
+            ..NetworkConfig::default()
+        };
+        assert!(!network.exception_for(Some("OfficeNet")).unwrap().power_save);
+        assert!(network.exception_for(Some("Café 5G")).unwrap().power_save);
+        // Case-sensitive, no trimming
+        assert!(network.exception_for(Some("officenet")).is_none());
+        assert!(network.exception_for(Some("OfficeNet ")).is_none());
+        // Hidden SSID or not connected
+        assert!(network.exception_for(Some("")).is_none());
+        assert!(network.exception_for(None).is_none());
+        assert!(
+            NetworkConfig::default()
+                .exception_for(Some("OfficeNet"))
+                .is_none()
+        );
     }
 
     #[test]
     fn test_load_from_nonexistent_path() {
-        let config = load_from_path(Path::new("/nonexistent/config.toml"));
+        let (config, issues) = load_checked(Some(&PathBuf::from("/nonexistent/config.toml")));
         // Should return defaults without panicking
         assert!(!config.epp.adaptive);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, IssueSeverity::Error);
+        assert!(
+            issues[0]
+                .to_string()
+                .starts_with("config /nonexistent/config.toml: failed to read"),
+            "{}",
+            issues[0]
+        );
     }
 
     #[test]
@@ -512,7 +1377,10 @@ mod tests {
     #[test]
     fn test_aggressive_migration() {
         let config = BopConfig {
-            auto: AutoConfig { aggressive: true },
+            auto: AutoConfig {
+                aggressive: true,
+                ..Default::default()
+            },
             ..Default::default()
         };
         // resolve_preset should also migrate
@@ -542,6 +1410,45 @@ mod tests {
         // Overridden
         assert_eq!(knobs.turbo_boost, Some(false));
         assert!(!knobs.pci_runtime_pm);
+        assert!(!knobs.platform_profile_locked);
+    }
+
+    #[test]
+    fn test_platform_profile_override_locks_the_profile() {
+        let mut config = BopConfig::default();
+        config.overrides.platform_profile = Some(true);
+        let knobs = resolve_knobs(&config, Preset::Moderate);
+        assert_eq!(knobs.platform_profile, PlatformProfilePolicy::ForceLowPower);
+        assert!(knobs.platform_profile_locked);
+
+        config.overrides.platform_profile = Some(false);
+        let knobs = resolve_knobs(&config, Preset::Supersaver);
+        assert_eq!(knobs.platform_profile, PlatformProfilePolicy::NoChange);
+        assert!(!knobs.platform_profile_locked);
+    }
+
+    #[test]
+    fn test_package_power_limit_is_supersaver_only() {
+        let config: BopConfig = toml::from_str("[aggressive]\npackage_power_limit_w = 15").unwrap();
+        assert!(config.validate().is_empty());
+        assert_eq!(
+            resolve_knobs(&config, Preset::Supersaver).package_power_limit_w,
+            Some(15)
+        );
+        assert_eq!(
+            resolve_knobs(&config, Preset::Saver).package_power_limit_w,
+            None
+        );
+
+        let mut knobs = resolve_knobs(&config, Preset::Supersaver);
+        knobs.clamp_for_reduced();
+        assert_eq!(knobs.package_power_limit_w, None);
+
+        let config: BopConfig = toml::from_str("[aggressive]\npackage_power_limit_w = 0").unwrap();
+        assert_eq!(
+            config.validate()[0].field,
+            "aggressive.package_power_limit_w"
+        );
     }
 
     #[test]
@@ -563,4 +1470,307 @@ mod tests {
         assert_eq!(knobs.aspm_policy.as_deref(), Some("powersave"));
         assert!(knobs.pci_runtime_pm);
     }
+
+    /// (field, severity) of each issue, for compact assertions.
+    fn issues(config: &BopConfig) -> Vec<(String, IssueSeverity)> {
+        config
+            .validate()
+            .into_iter()
+            .map(|i| (i.field, i.severity))
+            .collect()
+    }
+
+    fn issue(field: &str, severity: IssueSeverity) -> (String, IssueSeverity) {
+        (field.to_string(), severity)
+    }
+
+    #[test]
+    fn test_defaults_validate_clean() {
+        assert_eq!(BopConfig::default().validate(), Vec::new());
+    }
+
+    #[test]
+    fn test_validate_brightness_range() {
+        let with = |dim_percent, auto_dim| BopConfig {
+            brightness: BrightnessConfig {
+                auto_dim,
+                dim_percent,
+            },
+            ..Default::default()
+        };
+        let error = vec![issue("brightness.dim_percent", IssueSeverity::Error)];
+        assert_eq!(issues(&with(0, true)), error);
+        assert!(issues(&with(1, true)).is_empty());
+        assert!(issues(&with(99, true)).is_empty());
+        assert!(issues(&with(100, false)).is_empty());
+        assert_eq!(
+            issues(&with(100, true)),
+            vec![issue("brightness.dim_percent", IssueSeverity::Warning)]
+        );
+        assert_eq!(issues(&with(101, true)), error);
+        assert_eq!(issues(&with(150, false)), error);
+    }
+
+    #[test]
+    fn test_validate_epp_thresholds() {
+        let with = |adaptive, percents: &[u8]| BopConfig {
+            epp: EppConfig {
+                adaptive,
+                thresholds: percents
+                    .iter()
+                    .map(|&battery_percent| EppThreshold {
+                        battery_percent,
+                        epp_value: EppHint::BalancePower,
+                    })
+                    .collect(),
+            },
+            ..Default::default()
+        };
+        assert!(issues(&with(true, &[30, 100])).is_empty());
+        assert!(issues(&with(true, &[100])).is_empty());
+        assert!(issues(&with(false, &[])).is_empty());
+        assert_eq!(
+            issues(&with(true, &[])),
+            vec![issue("epp.thresholds", IssueSeverity::Error)]
+        );
+        assert_eq!(
+            issues(&with(true, &[30, 101])),
+            vec![issue(
+                "epp.thresholds[1].battery_percent",
+                IssueSeverity::Error
+            )]
+        );
+        assert_eq!(
+            issues(&with(true, &[50, 30, 50, 100])),
+            vec![issue(
+                "epp.thresholds[2].battery_percent",
+                IssueSeverity::Error
+            )]
+        );
+        assert_eq!(
+            issues(&with(true, &[20, 99])),
+            vec![issue("epp.thresholds", IssueSeverity::Warning)]
+        );
+    }
+
+    #[test]
+    fn test_validate_durations_and_watts() {
+        let mut config = BopConfig::default();
+        config.commands.timeout = Duration::from_secs(0);
+        config.commands.grub_mkconfig_timeout = Duration::from_secs(1);
+        config.idle_drain.margin_watts = 0.0;
+        config.idle_drain.sustain = Duration::from_secs(0);
+        config.status.full_check_interval = Duration::from_secs(0);
+        assert_eq!(
+            issues(&config),
+            vec![
+                issue("commands.timeout", IssueSeverity::Error),
+                issue("idle_drain.margin_watts", IssueSeverity::Error),
+                issue("idle_drain.sustain", IssueSeverity::Warning),
+                issue("status.full_check_interval", IssueSeverity::Warning),
+            ]
+        );
+        config.idle_drain.margin_watts = f64::NAN;
+        assert!(issues(&config).contains(&issue("idle_drain.margin_watts", IssueSeverity::Error)));
+        config.idle_drain.margin_watts = 0.1;
+        assert!(!issues(&config).contains(&issue("idle_drain.margin_watts", IssueSeverity::Error)));
+    }
+
+    #[test]
+    fn test_validate_network_notifications_and_auto() {
+        let mut config = BopConfig::default();
+        config.network.exceptions = vec![
+            NetworkException {
+                ssid: "Home".into(),
+                power_save: false,
+            },
+            NetworkException {
+                ssid: String::new(),
+                power_save: false,
+            },
+            NetworkException {
+                ssid: "Home".into(),
+                power_save: true,
+            },
+        ];
+        config.notifications = NotificationConfig {
+            enabled: true,
+            on_apply: false,
+            on_revert: false,
+        };
+        config.auto.aggressive = true;
+        assert_eq!(
+            issues(&config),
+            vec![
+                issue("auto.aggressive", IssueSeverity::Warning),
+                issue("notifications.enabled", IssueSeverity::Warning),
+                issue("network.exceptions[1].ssid", IssueSeverity::Warning),
+                issue("network.exceptions[2].ssid", IssueSeverity::Warning),
+            ]
+        );
+
+        let mut config = BopConfig::default();
+        for (percent, expected) in [
+            (Some(5), None),
+            (Some(0), Some(IssueSeverity::Warning)),
+            (Some(60), Some(IssueSeverity::Warning)),
+            (Some(101), Some(IssueSeverity::Error)),
+        ] {
+            config.auto.critical_battery_percent = percent;
+            let expected: Vec<_> = expected
+                .map(|severity| issue("auto.critical_battery_percent", severity))
+                .into_iter()
+                .collect();
+            assert_eq!(issues(&config), expected, "{:?}", percent);
+        }
+    }
+
+    #[test]
+    fn test_validate_overrides_and_pins() {
+        let mut config = BopConfig::default();
+        config.overrides.epp = Some("balance_power".into());
+        config.overrides.aspm_policy = Some("powersupersave".into());
+        config.overrides.dirty_writeback = Some(1500);
+        config
+            .pins
+            .insert("/sys/module/snd_hda_intel/parameters/power_save", "leave");
+        assert!(issues(&config).is_empty());
+
+        config.overrides.epp = Some("balanced".into());
+        config.overrides.aspm_policy = Some("l1".into());
+        config.overrides.dirty_writeback = Some(0);
+        config.pins.insert("sys/module/foo", "1");
+        assert_eq!(
+            issues(&config),
+            vec![
+                issue("overrides.epp", IssueSeverity::Error),
+                issue("overrides.aspm_policy", IssueSeverity::Error),
+                issue("overrides.dirty_writeback", IssueSeverity::Warning),
+                issue("pins.\"sys/module/foo\"", IssueSeverity::Error),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors_fall_back_per_section() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [brightness]
+            auto_dim = true
+            dim_percent = 150
+
+            [epp]
+            adaptive = true
+
+            [notifications]
+            enabled = "yes"
+
+            [network]
+            busy_mb_per_sec = 20
+            "#,
+        )
+        .unwrap();
+        let (config, issues) = load_checked(Some(&path));
+        // Bad sections are back to defaults, whole
+        assert_eq!(config.brightness.dim_percent, 60);
+        assert!(!config.brightness.auto_dim);
+        assert!(!config.notifications.enabled);
+        // Good ones are kept
+        assert!(config.epp.adaptive);
+        assert_eq!(config.network.busy_mb_per_sec, 20);
+
+        let fields: Vec<_> = issues.iter().map(|i| i.field.as_str()).collect();
+        assert_eq!(fields, ["notifications", "brightness.dim_percent"]);
+        let source = format!("config {}", path.display());
+        assert!(issues.iter().all(|i| i.source.as_deref() == Some(&source)));
+        assert_eq!(
+            issues[1].to_string(),
+            format!(
+                "{}: brightness.dim_percent: 150 is above 100; use 1-100; using defaults for [brightness]",
+                source
+            )
+        );
+    }
+
+    #[test]
+    fn test_bad_pin_is_dropped_alone() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [pins]
+            "/sys/module/snd_hda_intel/parameters/power_save" = "0"
+            "sys/module/relative" = "1"
+            "/sys/power/mem_sleep" = 3
+            "/sys/firmware/acpi/platform_profile" = "leave"
+            "#,
+        )
+        .unwrap();
+        let (config, issues) = load_checked(Some(&path));
+        let patterns: Vec<_> = config.pins.patterns().collect();
+        assert_eq!(
+            patterns,
+            [
+                "/sys/firmware/acpi/platform_profile",
+                "/sys/module/snd_hda_intel/parameters/power_save"
+            ]
+        );
+        let fields: Vec<_> = issues.iter().map(|i| i.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            [
+                "pins.\"/sys/power/mem_sleep\"",
+                "pins.\"sys/module/relative\""
+            ]
+        );
+        assert!(issues[1].to_string().ends_with("; pin ignored"));
+    }
+
+    #[test]
+    fn test_unparseable_file_is_an_issue() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(&path, "[brightness\n").unwrap();
+        let (config, issues) = load_checked(Some(&path));
+        assert_eq!(config.brightness.dim_percent, 60);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].field.is_empty());
+        assert!(issues[0].to_string().ends_with("; file ignored"));
+    }
+
+    #[test]
+    fn test_issue_source_names_the_file_that_set_it() {
+        let layer = |source: &str, content: &str| Layer {
+            source: source.to_string(),
+            value: toml::from_str(content).unwrap(),
+        };
+        let layers = [
+            layer(
+                "system config /etc/bop/config.toml",
+                "[brightness]\ndim_percent = 150\n[epp]\nadaptive = true\n",
+            ),
+            layer(
+                "user config ~/.config/bop/config.toml",
+                "[brightness]\nauto_dim = true\n",
+            ),
+        ];
+        // The user file has [brightness] but not dim_percent
+        assert_eq!(
+            source_of("brightness.dim_percent", &layers).as_deref(),
+            Some("system config /etc/bop/config.toml")
+        );
+        assert_eq!(
+            source_of("brightness.auto_dim", &layers).as_deref(),
+            Some("user config ~/.config/bop/config.toml")
+        );
+        assert_eq!(
+            source_of("epp.thresholds[0].battery_percent", &layers).as_deref(),
+            Some("system config /etc/bop/config.toml")
+        );
+        assert_eq!(source_of("status.full_check_interval", &layers), None);
+    }
 }
@@ -0,0 +1,839 @@
+//! Leftover udev rules, systemd units and rc.local lines that write the same
+//! knobs bop manages (old TLP rules, `echo powersave` units from wiki
+//! snippets, `powertop --auto-tune`). They silently undo bop's settings on
+//! the next hotplug or boot. Scanning only reads; `bop doctor --disarm`
+//! comments entries out or disables units, keeping backups for `--undo`.
+
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// Relative to the root being scanned
+pub const UDEV_RULES_DIR: &str = "etc/udev/rules.d";
+pub const SYSTEMD_DIR: &str = "etc/systemd/system";
+pub const RC_LOCAL: &str = "etc/rc.local";
+pub const DISARM_STATE: &str = "var/lib/bop/disarmed.json";
+
+/// Prefix put in front of a line `--disarm` comments out.
+pub const DISARM_PREFIX: &str = "# disarmed by bop: ";
+
+/// Fragments of the paths and tools bop writes through, with a label for
+/// reports.
+const MANAGED_PATTERNS: &[(&str, &str)] = &[
+    ("energy_performance_preference", "EPP"),
+    ("scaling_governor", "CPU governor"),
+    ("cpufreq/boost", "CPU boost"),
+    ("platform_profile", "platform profile"),
+    ("pcie_aspm", "ASPM policy"),
+    ("power/control", "runtime PM"),
+    ("power/autosuspend", "USB autosuspend"),
+    ("snd_hda_intel", "audio power save"),
+    ("power_save", "power save"),
+    ("nmi_watchdog", "NMI watchdog"),
+    ("dirty_writeback_centisecs", "dirty writeback"),
+    ("acpi/wakeup", "ACPI wakeup"),
+    ("power_dpm_force_performance_level", "GPU DPM"),
+    ("powertop --auto-tune", "powertop auto-tune"),
+];
+
+/// Where a conflicting entry lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConflictKind {
+    UdevRule,
+    SystemdUnit,
+    RcLocal,
+}
+
+/// One line outside bop that writes something bop manages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub file: PathBuf,
+    /// 1-based
+    pub line: usize,
+    pub text: String,
+    /// What it fights over, e.g. "runtime PM"
+    pub target: &'static str,
+}
+
+fn managed_target(text: &str) -> Option<&'static str> {
+    MANAGED_PATTERNS
+        .iter()
+        .find(|(needle, _)| text.contains(needle))
+        .map(|(_, label)| *label)
+}
+
+/// What a udev rule line writes that bop manages: an `ATTR{...}=` assignment
+/// (not a `==` match) or a `RUN` command naming a managed path.
+pub fn udev_line_target(line: &str) -> Option<&'static str> {
+    let line = line.trim();
+    if line.starts_with('#') {
+        return None;
+    }
+    line.split(',').map(str::trim).find_map(|token| {
+        if let Some(rest) = token.strip_prefix("ATTR{") {
+            let (key, value) = rest.split_once('}')?;
+            let assigns = value.starts_with('=') && !value.starts_with("==");
+            assigns.then(|| managed_target(key)).flatten()
+        } else if token.starts_with("RUN") {
+            managed_target(token)
+        } else {
+            None
+        }
+    })
+}
+
+/// What a unit file line writes that bop manages: an `Exec*=` command
+/// naming a managed path.
+pub fn unit_line_target(line: &str) -> Option<&'static str> {
+    let line = line.trim();
+    let (key, command) = line.split_once('=')?;
+    if !key.trim_end().starts_with("Exec") {
+        return None;
+    }
+    managed_target(command)
+}
+
+/// What a shell script line writes that bop manages.
+pub fn shell_line_target(line: &str) -> Option<&'static str> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    managed_target(line)
+}
+
+fn scan_file(path: &Path, kind: ConflictKind, conflicts: &mut Vec<Conflict>) {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return;
+    };
+    // bop's own rules and units write these paths on purpose
+    if crate::managed::has_header(&content) {
+        return;
+    }
+    let matcher = match kind {
+        ConflictKind::UdevRule => udev_line_target,
+        ConflictKind::SystemdUnit => unit_line_target,
+        ConflictKind::RcLocal => shell_line_target,
+    };
+    for (i, text) in content.lines().enumerate() {
+        if let Some(target) = matcher(text) {
+            conflicts.push(Conflict {
+                kind,
+                file: path.to_path_buf(),
+                line: i + 1,
+                text: text.trim().to_string(),
+                target,
+            });
+        }
+    }
+}
+
+/// Regular files in `dir` with the given extension, sorted. Symlinks are
+/// skipped: masked units and enablement links aren't definitions.
+fn files_with_extension(dir: &Path, extension: &str) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == extension))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Whether `unit` is enabled under `root`: linked from a `.wants`,
+/// `.requires` or `.upholds` directory, as `systemctl enable` leaves it.
+pub fn unit_enabled(root: &Path, unit: &str) -> bool {
+    let Ok(entries) = std::fs::read_dir(root.join(SYSTEMD_DIR)) else {
+        return false;
+    };
+    entries
+        .flatten()
+        .filter(|e| {
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            [".wants", ".requires", ".upholds"]
+                .iter()
+                .any(|suffix| name.ends_with(suffix))
+        })
+        .any(|e| e.path().join(unit).symlink_metadata().is_ok())
+}
+
+/// Conflicting entries under `root` ("/" for the running system). Read-only.
+/// A unit nothing starts can't undo anything, so only enabled ones count.
+pub fn scan(root: &Path) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    for path in files_with_extension(&root.join(UDEV_RULES_DIR), "rules") {
+        scan_file(&path, ConflictKind::UdevRule, &mut conflicts);
+    }
+    for path in files_with_extension(&root.join(SYSTEMD_DIR), "service") {
+        let enabled = path
+            .file_name()
+            .is_some_and(|name| unit_enabled(root, &name.to_string_lossy()));
+        if enabled {
+            scan_file(&path, ConflictKind::SystemdUnit, &mut conflicts);
+        }
+    }
+    scan_file(&root.join(RC_LOCAL), ConflictKind::RcLocal, &mut conflicts);
+    conflicts
+}
+
+/// A power daemon that rewrites knobs bop manages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Daemon {
+    pub unit: &'static str,
+    /// Process name in /proc/<pid>/comm (cut to 15 bytes by the kernel)
+    pub process: &'static str,
+}
+
+/// Daemons `bop status --explain-drift` looks for. TLP runs as a oneshot,
+/// so its unit stays active without a process.
+pub const DAEMONS: &[Daemon] = &[
+    Daemon {
+        unit: "tlp.service",
+        process: "tlp",
+    },
+    Daemon {
+        unit: "power-profiles-daemon.service",
+        process: "power-profiles-",
+    },
+    Daemon {
+        unit: "tuned.service",
+        process: "tuned",
+    },
+    Daemon {
+        unit: "auto-cpufreq.service",
+        process: "auto-cpufreq",
+    },
+    Daemon {
+        unit: "thermald.service",
+        process: "thermald",
+    },
+];
+
+/// A value a daemon writes to a knob unless told otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Signature {
+    pub unit: &'static str,
+    /// Fragment of the sysfs path, as in [`MANAGED_PATTERNS`]
+    pub target: &'static str,
+    pub value: String,
+    /// Where the value comes from, e.g. "balanced profile" or
+    /// "CPU_ENERGY_PERF_POLICY_ON_BAT"
+    pub source: String,
+}
+
+/// power-profiles-daemon's "balanced" profile, which it restores on start.
+const PPD_BALANCED: &[(&str, &str)] = &[
+    ("platform_profile", "balanced"),
+    ("energy_performance_preference", "balance_performance"),
+];
+
+/// TLP settings for knobs bop manages, with the value TLP writes when
+/// unset (its `defaults.conf`), or None where it then leaves the knob alone.
+const TLP_SETTINGS: &[(&str, &str, Option<&str>)] = &[
+    (
+        "CPU_ENERGY_PERF_POLICY_ON_AC",
+        "energy_performance_preference",
+        Some("balance_performance"),
+    ),
+    (
+        "CPU_ENERGY_PERF_POLICY_ON_BAT",
+        "energy_performance_preference",
+        Some("balance_power"),
+    ),
+    (
+        "PLATFORM_PROFILE_ON_AC",
+        "platform_profile",
+        Some("performance"),
+    ),
+    (
+        "PLATFORM_PROFILE_ON_BAT",
+        "platform_profile",
+        Some("low-power"),
+    ),
+    ("RUNTIME_PM_ON_AC", "power/control", Some("on")),
+    ("RUNTIME_PM_ON_BAT", "power/control", Some("auto")),
+    ("PCIE_ASPM_ON_AC", "pcie_aspm", Some("default")),
+    ("PCIE_ASPM_ON_BAT", "pcie_aspm", Some("powersupersave")),
+    ("SOUND_POWER_SAVE_ON_AC", "power_save", Some("1")),
+    ("SOUND_POWER_SAVE_ON_BAT", "power_save", Some("1")),
+    ("NMI_WATCHDOG", "nmi_watchdog", Some("0")),
+    ("CPU_BOOST_ON_AC", "cpufreq/boost", None),
+    ("CPU_BOOST_ON_BAT", "cpufreq/boost", None),
+];
+
+const TLP_CONF: &str = "etc/tlp.conf";
+const TLP_CONF_DIR: &str = "etc/tlp.d";
+
+/// `KEY=value` settings from TLP config files, later files winning.
+/// Values may be quoted; comments, including one after an unquoted value,
+/// and blank lines are skipped.
+pub fn parse_tlp_conf<'a>(files: impl IntoIterator<Item = &'a str>) -> BTreeMap<String, String> {
+    let mut settings = BTreeMap::new();
+    for content in files {
+        for line in content.lines().map(str::trim) {
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            let value = match value.strip_prefix('"') {
+                Some(quoted) => quoted.split('"').next().unwrap_or_default(),
+                None => value.split('#').next().unwrap_or_default().trim(),
+            };
+            settings.insert(key.trim().to_string(), value.to_string());
+        }
+    }
+    settings
+}
+
+/// What each daemon in [`DAEMONS`] would write, given TLP's settings.
+pub fn signatures(tlp: &BTreeMap<String, String>) -> Vec<Signature> {
+    let mut signatures: Vec<Signature> = PPD_BALANCED
+        .iter()
+        .map(|(target, value)| Signature {
+            unit: "power-profiles-daemon.service",
+            target,
+            value: value.to_string(),
+            source: "balanced profile".to_string(),
+        })
+        .collect();
+    for (key, target, default) in TLP_SETTINGS {
+        // Set empty, TLP leaves the knob alone
+        let (value, source) = match (tlp.get(*key), default) {
+            (Some(value), _) if value.is_empty() => continue,
+            (Some(value), _) => (value.as_str(), key.to_string()),
+            (None, Some(default)) => (*default, format!("{} default", key)),
+            (None, None) => continue,
+        };
+        signatures.push(Signature {
+            unit: "tlp.service",
+            target,
+            value: value.to_string(),
+            source,
+        });
+    }
+    signatures
+}
+
+/// [`signatures`] with TLP's config read from under `root`.
+pub fn load_signatures(root: &Path) -> Vec<Signature> {
+    let mut files: Vec<String> = std::fs::read_to_string(root.join(TLP_CONF))
+        .into_iter()
+        .collect();
+    files.extend(
+        files_with_extension(&root.join(TLP_CONF_DIR), "conf")
+            .into_iter()
+            .filter_map(|path| std::fs::read_to_string(path).ok()),
+    );
+    signatures(&parse_tlp_conf(files.iter().map(String::as_str)))
+}
+
+/// The signatures `value` at `path` matches.
+pub fn matching_signatures<'a>(
+    signatures: &'a [Signature],
+    path: &str,
+    value: &str,
+) -> Vec<&'a Signature> {
+    signatures
+        .iter()
+        .filter(|s| path.contains(s.target) && s.value == value)
+        .collect()
+}
+
+/// `content` with the given 1-based lines commented out with
+/// [`DISARM_PREFIX`].
+pub fn comment_out(content: &str, lines: &[usize]) -> String {
+    let mut out: String = content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if lines.contains(&(i + 1)) {
+                format!("{}{}\n", DISARM_PREFIX, line)
+            } else {
+                format!("{}\n", line)
+            }
+        })
+        .collect();
+    if !content.ends_with('\n') {
+        out.pop();
+    }
+    out
+}
+
+/// A file `--disarm` changed, with what it held before.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisarmedFile {
+    pub path: PathBuf,
+    pub original: String,
+    /// Unit disabled instead of editing the file
+    #[serde(default)]
+    pub disabled_unit: Option<String>,
+    /// What that unit was before. None in records written before it was
+    /// kept, which are restored enabled and running.
+    #[serde(default)]
+    pub unit_before: Option<UnitState>,
+}
+
+/// Whether a unit was enabled and running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnitState {
+    pub enabled: bool,
+    pub active: bool,
+}
+
+impl UnitState {
+    /// `unit` as it is now under `root`; whether it runs is only known
+    /// for the running system.
+    fn read(root: &Path, unit: &str) -> Self {
+        let active = root == Path::new("/")
+            && crate::cmd::Cmd::new("systemctl")
+                .args(["is-active", "--quiet", unit])
+                .status()
+                .is_ok_and(|s| s.success());
+        Self {
+            enabled: unit_enabled(root, unit),
+            active,
+        }
+    }
+}
+
+/// The `systemctl` calls that put `unit` back the way `before` says.
+fn restore_unit_args(unit: &str, before: Option<UnitState>) -> Vec<Vec<&str>> {
+    let before = before.unwrap_or(UnitState {
+        enabled: true,
+        active: true,
+    });
+    match (before.enabled, before.active) {
+        (true, true) => vec![vec!["enable", "--now", unit]],
+        (true, false) => vec![vec!["enable", unit]],
+        (false, true) => vec![vec!["start", unit]],
+        (false, false) => Vec::new(),
+    }
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let status = crate::cmd::Cmd::new("systemctl").args(args).status()?;
+    if !status.success() {
+        return Err(Error::Other(format!("systemctl {} failed", args.join(" "))));
+    }
+    Ok(())
+}
+
+/// Backups of everything `bop doctor --disarm` changed, kept apart from
+/// the apply state so `bop auto` and `bop revert` leave them alone.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DisarmState {
+    pub files: Vec<DisarmedFile>,
+}
+
+impl DisarmState {
+    fn path(root: &Path) -> PathBuf {
+        root.join(DISARM_STATE)
+    }
+
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = Self::path(root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = std::fs::read_to_string(&path)
+            .map_err(|e| Error::State(format!("failed to read {}: {}", path.display(), e)))?;
+        serde_json::from_str(&data)
+            .map_err(|e| Error::State(format!("failed to parse {}: {}", path.display(), e)))
+    }
+
+    pub fn save(&self, root: &Path) -> Result<()> {
+        let path = Self::path(root);
+        if self.files.is_empty() {
+            let _ = std::fs::remove_file(&path);
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| Error::State(format!("failed to create state dir: {}", e)))?;
+        }
+        let data = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::State(format!("failed to serialize disarm state: {}", e)))?;
+        std::fs::write(&path, data)
+            .map_err(|e| Error::State(format!("failed to write {}: {}", path.display(), e)))
+    }
+}
+
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    std::fs::write(path, content)
+        .map_err(|e| Error::Other(format!("failed to write {}: {}", path.display(), e)))
+}
+
+/// Neutralize `conflicts` under `root`: udev and rc.local lines are
+/// commented out, units are disabled and stopped (commenting out an
+/// `ExecStart=` would only leave a broken unit). Each file's original is
+/// recorded before it is touched.
+pub fn disarm(root: &Path, conflicts: &[Conflict]) -> Result<Vec<PathBuf>> {
+    let mut state = DisarmState::load(root)?;
+    let mut files: Vec<&Path> = conflicts.iter().map(|c| c.file.as_path()).collect();
+    files.dedup();
+
+    let mut changed = Vec::new();
+    for file in files {
+        if state.files.iter().any(|f| f.path == file) {
+            continue;
+        }
+        let original = std::fs::read_to_string(file)
+            .map_err(|e| Error::Other(format!("failed to read {}: {}", file.display(), e)))?;
+        let in_file: Vec<&Conflict> = conflicts.iter().filter(|c| c.file == file).collect();
+
+        let disabled_unit = if in_file[0].kind == ConflictKind::SystemdUnit {
+            let unit = file
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            Some(unit)
+        } else {
+            None
+        };
+        // Record before changing anything, so a failure below can be undone
+        state.files.push(DisarmedFile {
+            path: file.to_path_buf(),
+            original: original.clone(),
+            disabled_unit: disabled_unit.clone(),
+            unit_before: disabled_unit.as_deref().map(|u| UnitState::read(root, u)),
+        });
+        state.save(root)?;
+
+        match &disabled_unit {
+            Some(unit) => systemctl(&["disable", "--now", unit])?,
+            None => {
+                let lines: Vec<usize> = in_file.iter().map(|c| c.line).collect();
+                write_file(file, &comment_out(&original, &lines))?;
+            }
+        }
+        changed.push(file.to_path_buf());
+    }
+    Ok(changed)
+}
+
+/// Restore everything [`disarm`] changed under `root`. Entries that fail
+/// stay recorded for another try.
+pub fn undo(root: &Path) -> Result<Vec<PathBuf>> {
+    let state = DisarmState::load(root)?;
+    let mut remaining = DisarmState::default();
+    let mut restored = Vec::new();
+    for entry in state.files {
+        let result = match &entry.disabled_unit {
+            Some(unit) => restore_unit_args(unit, entry.unit_before)
+                .iter()
+                .try_for_each(|args| systemctl(args)),
+            None => write_file(&entry.path, &entry.original),
+        };
+        match result {
+            Ok(()) => restored.push(entry.path),
+            Err(_) => remaining.files.push(entry),
+        }
+    }
+    remaining.save(root)?;
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_udev_rule_writes_are_found_but_matches_are_not() {
+        // TLP-era runtime PM rule
+        assert_eq!(
+            udev_line_target(r#"ACTION=="add", SUBSYSTEM=="pci", ATTR{power/control}="auto""#),
+            Some("runtime PM")
+        );
+        assert_eq!(
+            udev_line_target(
+                r#"SUBSYSTEM=="power_supply", ATTR{online}=="0", RUN+="/usr/bin/sh -c 'echo power > /sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference'""#
+            ),
+            Some("EPP")
+        );
+        // Only matching on the attribute, not writing it
+        assert_eq!(
+            udev_line_target(r#"ACTION=="add", ATTR{power/control}=="on", GOTO="end""#),
+            None
+        );
+        assert_eq!(
+            udev_line_target(r#"# ACTION=="add", ATTR{power/control}="auto""#),
+            None
+        );
+        assert_eq!(
+            udev_line_target(r#"SUBSYSTEM=="usb", ATTR{idVendor}=="046d", MODE="0666""#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_unit_and_shell_lines() {
+        assert_eq!(
+            unit_line_target(
+                "ExecStart=/bin/sh -c 'echo powersave | tee /sys/devices/system/cpu/cpu*/cpufreq/scaling_governor'"
+            ),
+            Some("CPU governor")
+        );
+        assert_eq!(
+            unit_line_target("ExecStart=/usr/bin/powertop --auto-tune"),
+            Some("powertop auto-tune")
+        );
+        assert_eq!(
+            unit_line_target("Description=Set power_save for wifi"),
+            None
+        );
+        assert_eq!(
+            shell_line_target("echo 1500 > /proc/sys/vm/dirty_writeback_centisecs"),
+            Some("dirty writeback")
+        );
+        assert_eq!(shell_line_target("# powertop --auto-tune"), None);
+        assert_eq!(shell_line_target("exit 0"), None);
+    }
+
+    fn write(root: &Path, relative: &str, content: &str) -> PathBuf {
+        let path = root.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_scan_reports_file_and_line_and_skips_bop_files() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        let rule = write(
+            root,
+            "etc/udev/rules.d/50-powersave.rules",
+            "# runtime pm\nACTION==\"add\", SUBSYSTEM==\"pci\", ATTR{power/control}=\"auto\"\n",
+        );
+        let unit = write(
+            root,
+            "etc/systemd/system/powertop.service",
+            "[Unit]\nDescription=Powertop tunings\n\n[Service]\nType=oneshot\nExecStart=/usr/bin/powertop --auto-tune\n",
+        );
+        let wants = root.join("etc/systemd/system/multi-user.target.wants");
+        std::fs::create_dir_all(&wants).unwrap();
+        std::os::unix::fs::symlink(&unit, wants.join("powertop.service")).unwrap();
+        // Written once and never enabled: nothing runs it
+        write(
+            root,
+            "etc/systemd/system/nmi-off.service",
+            "[Service]\nType=oneshot\nExecStart=/bin/sh -c 'echo 0 > /proc/sys/kernel/nmi_watchdog'\n",
+        );
+        write(
+            root,
+            "etc/systemd/system/bop-powersave.service",
+            &format!(
+                "{}\n[Service]\nExecStart=/bin/sh -c 'echo auto > /sys/bus/pci/devices/x/power/control'\n",
+                crate::managed::header()
+            ),
+        );
+        write(
+            root,
+            "etc/udev/rules.d/70-other.rules",
+            "KERNEL==\"sda\", MODE=\"0660\"\n",
+        );
+
+        let conflicts = scan(root);
+        let found: Vec<(ConflictKind, &Path, usize, &str)> = conflicts
+            .iter()
+            .map(|c| (c.kind, c.file.as_path(), c.line, c.target))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (ConflictKind::UdevRule, rule.as_path(), 2, "runtime PM"),
+                (
+                    ConflictKind::SystemdUnit,
+                    unit.as_path(),
+                    6,
+                    "powertop auto-tune"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disarm_comments_out_lines_and_undo_restores() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        let original = "#!/bin/sh\necho 0 > /proc/sys/kernel/nmi_watchdog\nexit 0\n";
+        let rc = write(root, "etc/rc.local", original);
+
+        let conflicts = scan(root);
+        assert_eq!(conflicts.len(), 1);
+        // Scanning alone changes nothing
+        assert_eq!(std::fs::read_to_string(&rc).unwrap(), original);
+
+        assert_eq!(disarm(root, &conflicts).unwrap(), std::slice::from_ref(&rc));
+        assert_eq!(
+            std::fs::read_to_string(&rc).unwrap(),
+            "#!/bin/sh\n# disarmed by bop: echo 0 > /proc/sys/kernel/nmi_watchdog\nexit 0\n"
+        );
+        assert!(scan(root).is_empty());
+        assert_eq!(DisarmState::load(root).unwrap().files.len(), 1);
+
+        assert_eq!(undo(root).unwrap(), std::slice::from_ref(&rc));
+        assert_eq!(std::fs::read_to_string(&rc).unwrap(), original);
+        assert!(DisarmState::load(root).unwrap().files.is_empty());
+    }
+
+    #[test]
+    fn test_undo_restores_the_unit_as_it_was() {
+        let state = |enabled, active| Some(UnitState { enabled, active });
+        assert_eq!(
+            restore_unit_args("a.service", state(true, true)),
+            [["enable", "--now", "a.service"]]
+        );
+        assert_eq!(
+            restore_unit_args("a.service", state(true, false)),
+            [["enable", "a.service"]]
+        );
+        assert_eq!(
+            restore_unit_args("a.service", state(false, true)),
+            [["start", "a.service"]]
+        );
+        assert!(restore_unit_args("a.service", state(false, false)).is_empty());
+        // Recorded before the state was kept
+        assert_eq!(
+            restore_unit_args("a.service", None),
+            [["enable", "--now", "a.service"]]
+        );
+    }
+
+    #[test]
+    fn test_daemon_default_signatures() {
+        let defaults = signatures(&BTreeMap::new());
+        let ppd: Vec<(&str, &str)> = defaults
+            .iter()
+            .filter(|s| s.unit == "power-profiles-daemon.service")
+            .map(|s| (s.target, s.value.as_str()))
+            .collect();
+        assert_eq!(
+            ppd,
+            [
+                ("platform_profile", "balanced"),
+                ("energy_performance_preference", "balance_performance"),
+            ]
+        );
+
+        let epp = "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference";
+        let units = |value| {
+            matching_signatures(&defaults, epp, value)
+                .iter()
+                .map(|s| (s.unit, s.source.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            units("balance_performance"),
+            [
+                (
+                    "power-profiles-daemon.service",
+                    "balanced profile".to_string()
+                ),
+                (
+                    "tlp.service",
+                    "CPU_ENERGY_PERF_POLICY_ON_AC default".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            units("balance_power"),
+            [(
+                "tlp.service",
+                "CPU_ENERGY_PERF_POLICY_ON_BAT default".to_string()
+            )]
+        );
+        assert!(units("power").is_empty());
+
+        let profile = "/sys/firmware/acpi/platform_profile";
+        assert_eq!(matching_signatures(&defaults, profile, "balanced").len(), 1);
+        assert_eq!(
+            matching_signatures(&defaults, profile, "low-power")[0].source,
+            "PLATFORM_PROFILE_ON_BAT default"
+        );
+        assert_eq!(
+            matching_signatures(&defaults, "/proc/sys/kernel/nmi_watchdog", "0")[0].unit,
+            "tlp.service"
+        );
+    }
+
+    #[test]
+    fn test_tlp_signatures_follow_its_config() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        write(
+            root,
+            "etc/tlp.conf",
+            "# TLP\nCPU_ENERGY_PERF_POLICY_ON_BAT=power\nPLATFORM_PROFILE_ON_BAT=\"balanced\"\n\
+             RUNTIME_PM_ON_BAT=on  # keeps the dock awake\nSOUND_POWER_SAVE_ON_BAT=\"10\" # seconds\n\
+             PCIE_ASPM_ON_BAT=\"\"\nCPU_BOOST_ON_BAT=0\n",
+        );
+        write(
+            root,
+            "etc/tlp.d/10-local.conf",
+            "CPU_ENERGY_PERF_POLICY_ON_BAT=balance_power\n",
+        );
+
+        let signatures = load_signatures(root);
+        let tlp = |target: &str, source: &str| {
+            signatures
+                .iter()
+                .find(|s| s.unit == "tlp.service" && s.target == target && s.source == source)
+                .map(|s| s.value.clone())
+        };
+        // tlp.d drop-ins win over tlp.conf
+        assert_eq!(
+            tlp(
+                "energy_performance_preference",
+                "CPU_ENERGY_PERF_POLICY_ON_BAT"
+            )
+            .as_deref(),
+            Some("balance_power")
+        );
+        assert_eq!(
+            tlp("platform_profile", "PLATFORM_PROFILE_ON_BAT").as_deref(),
+            Some("balanced")
+        );
+        assert_eq!(
+            tlp("platform_profile", "PLATFORM_PROFILE_ON_AC default").as_deref(),
+            Some("performance")
+        );
+        // A trailing comment isn't part of the value, quoted or not
+        assert_eq!(
+            tlp("power/control", "RUNTIME_PM_ON_BAT").as_deref(),
+            Some("on")
+        );
+        assert_eq!(
+            tlp("power_save", "SOUND_POWER_SAVE_ON_BAT").as_deref(),
+            Some("10")
+        );
+        // Set empty, TLP doesn't touch it
+        assert!(
+            !signatures
+                .iter()
+                .any(|s| s.source.starts_with("PCIE_ASPM_ON_BAT"))
+        );
+        // Boost is left alone unless configured
+        assert_eq!(
+            tlp("cpufreq/boost", "CPU_BOOST_ON_BAT").as_deref(),
+            Some("0")
+        );
+        assert_eq!(tlp("cpufreq/boost", "CPU_BOOST_ON_AC default"), None);
+    }
+
+    #[test]
+    fn test_comment_out_keeps_missing_trailing_newline() {
+        assert_eq!(comment_out("a\nb", &[2]), "a\n# disarmed by bop: b");
+    }
+}
@@ -0,0 +1,218 @@
+//! Crash bundles for bug reports. When a command fails with
+//! `--sysfs-snapshot-on-error` (the default for bare `bop auto`), the error
+//! that reached the top level is saved under [`CRASH_DIR`] together with the
+//! plan the run built, the state execution had recorded so far, and a
+//! [`Snapshot`] of the sysfs values bop reads — the system as it was when
+//! things went wrong, which is rarely what it looks like by the time
+//! someone tries to reproduce it.
+
+use crate::apply::{ApplyPlan, ApplyState};
+use crate::error::{Error, Result};
+use crate::snapshot::Snapshot;
+use crate::sysfs::SysfsRoot;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+pub const CRASH_DIR: &str = "/var/lib/bop/crash";
+
+/// Bundles kept; saving another deletes the oldest.
+pub const KEEP: usize = 5;
+
+/// What the failed run was doing, noted by the apply pipeline as it goes.
+#[derive(Default)]
+struct RunContext {
+    plan: Option<ApplyPlan>,
+    state: Option<ApplyState>,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<RunContext> = RefCell::default();
+}
+
+/// Note `plan` as the one this run is working on.
+pub fn note_plan(plan: &ApplyPlan) {
+    CONTEXT.with(|c| c.borrow_mut().plan = Some(plan.clone()));
+}
+
+/// Note what execution has recorded so far.
+pub fn note_state(state: &ApplyState) {
+    CONTEXT.with(|c| c.borrow_mut().state = Some(state.clone()));
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CrashBundle {
+    pub bop_version: String,
+    pub timestamp: String,
+    /// Command line of the failed run
+    pub command: String,
+    /// The error and its causes, outermost first
+    pub error_chain: Vec<String>,
+    /// Last plan built on this thread; None if the run failed before that
+    pub plan: Option<ApplyPlan>,
+    /// Changes made before the failure; None if execution never started
+    pub state: Option<ApplyState>,
+    pub snapshot: Snapshot,
+}
+
+impl CrashBundle {
+    /// Bundle `error_chain` with what this thread noted (which is cleared)
+    /// and a snapshot of `sysfs`.
+    pub fn capture(sysfs: &SysfsRoot, command: &str, error_chain: Vec<String>) -> Self {
+        let context = CONTEXT.with(RefCell::take);
+        Self {
+            bop_version: env!("CARGO_PKG_VERSION").to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            command: command.to_string(),
+            error_chain,
+            plan: context.plan,
+            state: context.state,
+            snapshot: Snapshot::capture(sysfs),
+        }
+    }
+
+    /// Write the bundle to `<dir>/<timestamp>.json`, then delete all but the
+    /// newest [`KEEP`] bundles. Returns the path written.
+    pub fn save_in(&self, dir: &Path) -> Result<PathBuf> {
+        let io_err =
+            |e: std::io::Error| Error::Other(format!("failed to write {}: {}", dir.display(), e));
+        std::fs::create_dir_all(dir).map_err(io_err)?;
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Other(format!("failed to serialize crash bundle: {}", e)))?;
+        // Colons are awkward in file names; the rest sorts by time as is
+        let path = dir.join(format!("{}.json", self.timestamp.replace(':', "-")));
+        std::fs::write(&path, json).map_err(io_err)?;
+        prune(dir, KEEP).map_err(io_err)?;
+        Ok(path)
+    }
+}
+
+/// Delete the oldest bundles in `dir` beyond `keep`.
+fn prune(dir: &Path, keep: usize) -> std::io::Result<()> {
+    let mut bundles: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    bundles.sort();
+    let excess = bundles.len().saturating_sub(keep);
+    for path in &bundles[..excess] {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply::PlannedSysfsWrite;
+    use tempfile::TempDir;
+
+    fn sysfs_fixture(root: &Path) -> SysfsRoot {
+        std::fs::create_dir_all(root.join("proc")).unwrap();
+        std::fs::write(root.join("proc/cmdline"), "root=UUID=abc quiet\n").unwrap();
+        SysfsRoot::new(root)
+    }
+
+    fn plan() -> ApplyPlan {
+        ApplyPlan {
+            sysfs_writes: vec![PlannedSysfsWrite {
+                path: "/sys/module/pcie_aspm/parameters/policy".to_string(),
+                value: "powersupersave".to_string(),
+                description: "PCIe ASPM".to_string(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn saved(path: &Path) -> serde_json::Value {
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_failure_before_planning_has_no_plan_or_state() {
+        let tmp = TempDir::new().unwrap();
+        let sysfs = sysfs_fixture(&tmp.path().join("root"));
+        let bundle = CrashBundle::capture(
+            &sysfs,
+            "bop apply",
+            vec![
+                "No hardware profile matched".to_string(),
+                "cause".to_string(),
+            ],
+        );
+        let path = bundle.save_in(&tmp.path().join("crash")).unwrap();
+
+        let json = saved(&path);
+        assert_eq!(json["command"], "bop apply");
+        assert_eq!(
+            json["error_chain"],
+            serde_json::json!(["No hardware profile matched", "cause"])
+        );
+        assert!(json["plan"].is_null());
+        assert!(json["state"].is_null());
+        assert_eq!(
+            json["snapshot"]["files"]["proc/cmdline"],
+            "root=UUID=abc quiet"
+        );
+    }
+
+    #[test]
+    fn test_failure_after_planning_carries_the_plan() {
+        let tmp = TempDir::new().unwrap();
+        let sysfs = sysfs_fixture(tmp.path());
+        note_plan(&plan());
+        let bundle = CrashBundle::capture(&sysfs, "bop auto", vec!["lock".to_string()]);
+
+        let plan = bundle.plan.as_ref().unwrap();
+        assert_eq!(plan.sysfs_writes[0].value, "powersupersave");
+        assert!(bundle.state.is_none());
+        let json = serde_json::to_value(&bundle).unwrap();
+        assert_eq!(
+            json["plan"]["sysfs_writes"][0]["path"],
+            "/sys/module/pcie_aspm/parameters/policy"
+        );
+    }
+
+    #[test]
+    fn test_capture_clears_the_noted_run() {
+        let tmp = TempDir::new().unwrap();
+        let sysfs = sysfs_fixture(tmp.path());
+        note_plan(&plan());
+        note_state(&ApplyState::default());
+        let first = CrashBundle::capture(&sysfs, "bop auto", Vec::new());
+        assert!(first.plan.is_some() && first.state.is_some());
+        let second = CrashBundle::capture(&sysfs, "bop auto", Vec::new());
+        assert!(second.plan.is_none() && second.state.is_none());
+    }
+
+    #[test]
+    fn test_save_keeps_newest_bundles() {
+        let tmp = TempDir::new().unwrap();
+        let sysfs = sysfs_fixture(tmp.path());
+        let dir = tmp.path().join("crash");
+        let mut bundle = CrashBundle::capture(&sysfs, "bop auto", Vec::new());
+        for minute in 0..KEEP + 2 {
+            bundle.timestamp = format!("2026-10-15T12:{:02}:00.000Z", minute);
+            bundle.save_in(&dir).unwrap();
+        }
+
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names.len(), KEEP);
+        assert_eq!(names[0], "2026-10-15T12-02-00.000Z.json");
+        assert_eq!(names[KEEP - 1], "2026-10-15T12-06-00.000Z.json");
+    }
+
+    #[test]
+    fn test_save_reports_unwritable_dir() {
+        let tmp = TempDir::new().unwrap();
+        let sysfs = sysfs_fixture(tmp.path());
+        let blocker = tmp.path().join("file");
+        std::fs::write(&blocker, "").unwrap();
+        let bundle = CrashBundle::capture(&sysfs, "bop auto", Vec::new());
+        assert!(bundle.save_in(&blocker.join("crash")).is_err());
+    }
+}
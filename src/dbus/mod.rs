@@ -0,0 +1,705 @@
+//! `bop dbus-serve`: a read-only view of bop on the system bus, so desktop
+//! shell extensions can show the score and optimization state without
+//! polling the CLI. The interface is defined in
+//! `io.github.yv_was_taken.Bop1.xml` next to this file. Properties are
+//! recomputed on a timer; `bop auto` switches arrive through the same event
+//! file the notification listener tails (see [`crate::notify`]). The daemon
+//! also holds a logind delay lock so it can re-disable wakeup sources right
+//! before each suspend (see [`crate::wake::sleep`]).
+
+use crate::error::{Error, Result};
+use crate::managed::Ownership;
+use crate::notify::{EventKind, EventTail, NotifyEvent};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use zbus::blocking::{Connection, connection};
+use zbus::interface;
+use zbus::object_server::SignalEmitter;
+
+pub const BUS_NAME: &str = "io.github.yv_was_taken.Bop";
+pub const OBJECT_PATH: &str = "/io/github/yv_was_taken/Bop";
+pub const INTERFACE_NAME: &str = "io.github.yv_was_taken.Bop1";
+/// Introspection data for [`INTERFACE_NAME`]; the tests hold the served
+/// interface to it.
+pub const INTERFACE_XML: &str = include_str!("io.github.yv_was_taken.Bop1.xml");
+
+pub const UNIT_NAME: &str = "bop-dbus.service";
+pub const UNIT_PATH: &str = "/etc/systemd/system/bop-dbus.service";
+/// Lets root own [`BUS_NAME`] and anyone read it; the system bus refuses
+/// both without a policy.
+pub const POLICY_PATH: &str = "/etc/dbus-1/system.d/io.github.yv_was_taken.Bop.conf";
+
+/// How often properties are recomputed when nothing happens. Each refresh
+/// runs a quick audit and a status check.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+/// How often the event file is checked for auto switches.
+const EVENT_POLL: Duration = Duration::from_secs(1);
+
+const LOGIND: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const LOGIND_MANAGER: &str = "org.freedesktop.login1.Manager";
+
+/// Values of the interface's properties.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Properties {
+    pub score: u32,
+    pub optimizations_applied: bool,
+    /// "ac" or "battery"
+    pub power_source: String,
+    pub drifted_count: u32,
+}
+
+struct BopInterface {
+    properties: Properties,
+}
+
+#[interface(name = "io.github.yv_was_taken.Bop1")]
+impl BopInterface {
+    #[zbus(property)]
+    fn score(&self) -> u32 {
+        self.properties.score
+    }
+
+    #[zbus(property)]
+    fn optimizations_applied(&self) -> bool {
+        self.properties.optimizations_applied
+    }
+
+    #[zbus(property)]
+    fn power_source(&self) -> String {
+        self.properties.power_source.clone()
+    }
+
+    #[zbus(property)]
+    fn drifted_count(&self) -> u32 {
+        self.properties.drifted_count
+    }
+
+    #[zbus(signal)]
+    async fn auto_switched(
+        emitter: &SignalEmitter<'_>,
+        action: &str,
+        message: &str,
+    ) -> zbus::Result<()>;
+}
+
+fn dbus_err(e: zbus::Error) -> Error {
+    Error::Other(format!("D-Bus: {}", e))
+}
+
+/// The interface, exported at [`OBJECT_PATH`] under [`BUS_NAME`].
+pub struct Service {
+    connection: Connection,
+}
+
+impl Service {
+    /// Claim [`BUS_NAME`] on the system bus and serve `properties`.
+    pub fn system(properties: Properties) -> Result<Self> {
+        Self::start(connection::Builder::system(), properties)
+    }
+
+    /// Like [`Service::system`], on the bus at `address` (a private bus in
+    /// tests).
+    pub fn at_address(address: &str, properties: Properties) -> Result<Self> {
+        Self::start(connection::Builder::address(address), properties)
+    }
+
+    fn start(
+        builder: zbus::Result<connection::Builder<'static>>,
+        properties: Properties,
+    ) -> Result<Self> {
+        let connection = builder
+            .and_then(|b| b.name(BUS_NAME))
+            .and_then(|b| b.serve_at(OBJECT_PATH, BopInterface { properties }))
+            .and_then(|b| b.build())
+            .map_err(dbus_err)?;
+        Ok(Self { connection })
+    }
+
+    /// Replace the property values, emitting PropertiesChanged for each one
+    /// that changed.
+    pub fn update(&self, properties: Properties) -> Result<()> {
+        let iface = self
+            .connection
+            .object_server()
+            .interface::<_, BopInterface>(OBJECT_PATH)
+            .map_err(dbus_err)?;
+        let old = std::mem::replace(&mut iface.get_mut().properties, properties.clone());
+        let emitter = iface.signal_emitter();
+        let current = iface.get();
+        zbus::block_on(async {
+            if old.score != properties.score {
+                current.score_changed(emitter).await?;
+            }
+            if old.optimizations_applied != properties.optimizations_applied {
+                current.optimizations_applied_changed(emitter).await?;
+            }
+            if old.power_source != properties.power_source {
+                current.power_source_changed(emitter).await?;
+            }
+            if old.drifted_count != properties.drifted_count {
+                current.drifted_count_changed(emitter).await?;
+            }
+            Ok(())
+        })
+        .map_err(dbus_err)
+    }
+
+    /// Emit AutoSwitched for an apply or revert event; other events are
+    /// ignored.
+    pub fn auto_switched(&self, event: &NotifyEvent) -> Result<()> {
+        let action = match event.kind {
+            EventKind::Applied | EventKind::Critical => "applied",
+            EventKind::Reverted => "reverted",
+            EventKind::Drain => return Ok(()),
+        };
+        let emitter = SignalEmitter::new(self.connection.inner(), OBJECT_PATH).map_err(dbus_err)?;
+        zbus::block_on(BopInterface::auto_switched(&emitter, action, &event.body)).map_err(dbus_err)
+    }
+}
+
+/// `bop dbus-serve`: serve on the system bus until `stop` is set, with
+/// property values from `refresh`. Runs it every [`REFRESH_INTERVAL`] and
+/// after each auto switch, before the switch is signalled, so handlers of
+/// AutoSwitched read the new state.
+pub fn serve(mut refresh: impl FnMut() -> Properties, stop: &AtomicBool) -> Result<()> {
+    let service = Service::system(refresh())?;
+    // Signals block their iterator, so the hook gets a thread and a
+    // connection of its own; the properties keep being served without it
+    std::thread::spawn(|| {
+        if let Err(e) = sleep_hook() {
+            eprintln!("Pre-sleep hook stopped: {}", e);
+        }
+    });
+    let mut tail = EventTail::new(crate::notify::EVENTS_PATH);
+    let mut refreshed = Instant::now();
+
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(EVENT_POLL);
+        let switches: Vec<NotifyEvent> = tail
+            .read_new()
+            .into_iter()
+            .filter(|e| e.kind != EventKind::Drain)
+            .collect();
+        if !switches.is_empty() || refreshed.elapsed() >= REFRESH_INTERVAL {
+            service.update(refresh())?;
+            refreshed = Instant::now();
+        }
+        for event in &switches {
+            service.auto_switched(event)?;
+        }
+    }
+    Ok(())
+}
+
+/// A logind delay lock: suspend waits for it to close, for at most
+/// `InhibitDelayMaxSec`.
+fn inhibit_sleep(manager: &zbus::blocking::Proxy<'_>) -> Result<zbus::zvariant::OwnedFd> {
+    manager
+        .call(
+            "Inhibit",
+            &(
+                "sleep",
+                "bop",
+                "Re-disable ACPI wakeup sources before suspend",
+                "delay",
+            ),
+        )
+        .map_err(dbus_err)
+}
+
+/// Run `hook` on each PrepareForSleep from logind on `connection`: with
+/// true before suspend, while a delay lock holds it back, which is
+/// released once `hook` returns; with false after resume, once a new lock
+/// is taken. Returns when the connection closes.
+pub fn watch_sleep(connection: &Connection, mut hook: impl FnMut(bool)) -> Result<()> {
+    let manager = zbus::blocking::Proxy::new(connection, LOGIND, LOGIND_PATH, LOGIND_MANAGER)
+        .map_err(dbus_err)?;
+    let signals = manager
+        .receive_signal("PrepareForSleep")
+        .map_err(dbus_err)?;
+    let mut lock = Some(inhibit_sleep(&manager)?);
+    for message in signals {
+        let sleeping: bool = message.body().deserialize().map_err(dbus_err)?;
+        if sleeping {
+            hook(true);
+            lock = None;
+        } else {
+            if lock.is_none() {
+                lock = Some(inhibit_sleep(&manager)?);
+            }
+            hook(false);
+        }
+    }
+    drop(lock);
+    Ok(())
+}
+
+/// Re-disable the wakeup sources the last apply disabled before each
+/// suspend, and record after resume whether that landed in time.
+fn sleep_hook() -> Result<()> {
+    use crate::wake::sleep::{self, SleepToggle};
+
+    let connection = Connection::system().map_err(dbus_err)?;
+    let mut pending = None;
+    watch_sleep(&connection, |sleeping| {
+        if sleeping {
+            let devices = crate::apply::ApplyState::load()
+                .ok()
+                .flatten()
+                .map(|state| state.acpi_wakeup_toggled)
+                .unwrap_or_default();
+            if devices.is_empty() {
+                return;
+            }
+            let mut table = crate::apply::sysfs_writer::ProcAcpiWakeup;
+            let mut kmsg: Box<dyn std::io::Write> = match std::fs::OpenOptions::new()
+                .write(true)
+                .open(sleep::KMSG_PATH)
+            {
+                Ok(file) => Box::new(file),
+                Err(_) => Box::new(std::io::sink()),
+            };
+            pending = Some(sleep::before_sleep(
+                &devices,
+                &mut table,
+                &mut kmsg,
+                sleep::monotonic_us(),
+            ));
+        } else if let Some(pre) = pending.take() {
+            let records = sleep::read_kmsg().unwrap_or_default();
+            let toggle = SleepToggle::resolve(&pre, &records, crate::apply::current_boot_id());
+            eprintln!("{}", toggle.describe());
+            for failure in &toggle.failed {
+                eprintln!("  failed: {}", failure);
+            }
+            if let Err(e) = toggle.save(Path::new(sleep::TIMING_PATH)) {
+                eprintln!("{}", e);
+            }
+        }
+    })
+}
+
+/// The system unit that runs the service.
+pub fn render_unit() -> String {
+    format!(
+        "{}
+[Unit]
+Description=bop read-only D-Bus service
+After=dbus.service
+
+[Service]
+Type=dbus
+BusName={}
+ExecStart=/usr/bin/bop dbus-serve
+Restart=on-failure
+
+[Install]
+WantedBy=multi-user.target
+",
+        crate::managed::header(),
+        BUS_NAME
+    )
+}
+
+/// The bus policy: root may own [`BUS_NAME`], anyone may read properties
+/// and introspect. The interface has no methods, so nothing else is
+/// allowed.
+pub fn render_policy() -> String {
+    format!(
+        r#"{header}
+<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-BUS Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <policy user="root">
+    <allow own="{name}"/>
+  </policy>
+  <policy context="default">
+    <allow send_destination="{name}" send_interface="org.freedesktop.DBus.Properties" send_member="Get"/>
+    <allow send_destination="{name}" send_interface="org.freedesktop.DBus.Properties" send_member="GetAll"/>
+    <allow send_destination="{name}" send_interface="org.freedesktop.DBus.Introspectable"/>
+    <allow send_destination="{name}" send_interface="org.freedesktop.DBus.Peer"/>
+  </policy>
+</busconfig>
+"#,
+        header = crate::managed::xml_header(),
+        name = BUS_NAME
+    )
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let status = crate::cmd::Cmd::new("systemctl").args(args).status()?;
+    if !status.success() {
+        return Err(Error::Other(format!("systemctl {} failed", args.join(" "))));
+    }
+    Ok(())
+}
+
+/// `bop dbus-serve enable`: install the policy and unit, then start the
+/// service now and at boot.
+pub fn enable(ownership: &Ownership) -> Result<()> {
+    ownership.write(Path::new(POLICY_PATH), &render_policy())?;
+    ownership.write(Path::new(UNIT_PATH), &render_unit())?;
+    // dbus-daemon picks up new policy files itself; dbus-broker needs a reload
+    let _ = systemctl(&["reload", "dbus.service"]);
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", "--now", UNIT_NAME])
+}
+
+/// `bop dbus-serve disable`: stop the service and remove what
+/// [`enable`] installed. Returns whether anything was there.
+pub fn disable(ownership: &Ownership) -> Result<bool> {
+    let unit = Path::new(UNIT_PATH);
+    let policy = Path::new(POLICY_PATH);
+    ownership.check(unit)?;
+    ownership.check(policy)?;
+
+    let had_unit = unit.exists();
+    if had_unit {
+        let _ = systemctl(&["disable", "--now", UNIT_NAME]);
+    }
+    let removed_unit = ownership.remove(unit)?;
+    let removed_policy = ownership.remove(policy)?;
+    if removed_unit {
+        let _ = systemctl(&["daemon-reload"]);
+    }
+    if removed_policy {
+        let _ = systemctl(&["reload", "dbus.service"]);
+    }
+    Ok(had_unit || removed_policy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::mpsc;
+    use tempfile::TempDir;
+    use zbus::zvariant::OwnedValue;
+
+    /// A dbus-daemon of our own, so the tests touch neither the system nor
+    /// the session bus. Killed on drop.
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+        _dir: TempDir,
+    }
+
+    impl PrivateBus {
+        /// None when dbus-daemon isn't installed.
+        fn start() -> Option<Self> {
+            let dir = TempDir::new().unwrap();
+            // Including the rendered policy makes the daemon reject the
+            // config if the policy doesn't parse
+            let policy = dir.path().join("bop.conf");
+            std::fs::write(&policy, render_policy()).unwrap();
+            let config = dir.path().join("bus.conf");
+            std::fs::write(
+                &config,
+                format!(
+                    r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-BUS Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:path={}</listen>
+  <auth>EXTERNAL</auth>
+  <include>{}</include>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>
+"#,
+                    dir.path().join("bus").display(),
+                    policy.display()
+                ),
+            )
+            .unwrap();
+            let mut daemon = Command::new("dbus-daemon")
+                .arg(format!("--config-file={}", config.display()))
+                .args(["--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+            assert!(!address.trim().is_empty(), "dbus-daemon rejected config");
+            Some(Self {
+                daemon,
+                address: address.trim().to_string(),
+                _dir: dir,
+            })
+        }
+
+        fn client(&self) -> Connection {
+            connection::Builder::address(self.address.as_str())
+                .unwrap()
+                .build()
+                .unwrap()
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    fn properties() -> Properties {
+        Properties {
+            score: 82,
+            optimizations_applied: true,
+            power_source: "battery".to_string(),
+            drifted_count: 1,
+        }
+    }
+
+    fn get_all(client: &Connection) -> HashMap<String, OwnedValue> {
+        zbus::blocking::fdo::PropertiesProxy::builder(client)
+            .destination(BUS_NAME)
+            .unwrap()
+            .path(OBJECT_PATH)
+            .unwrap()
+            .build()
+            .unwrap()
+            .get_all(INTERFACE_NAME.try_into().unwrap())
+            .unwrap()
+    }
+
+    /// Receives the body of the next signal named `member` from the
+    /// service; subscribed before returning, so nothing sent after is missed.
+    fn next_signal<T>(client: &Connection, member: &'static str) -> mpsc::Receiver<T>
+    where
+        T: for<'d> zbus::zvariant::DynamicDeserialize<'d> + Send + 'static,
+    {
+        let rule = zbus::MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .path(OBJECT_PATH)
+            .unwrap()
+            .member(member)
+            .unwrap()
+            .build();
+        let messages = zbus::blocking::MessageIterator::for_match_rule(rule, client, None).unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            if let Some(Ok(message)) = messages.into_iter().next() {
+                let _ = tx.send(message.body().deserialize::<T>().unwrap());
+            }
+        });
+        rx
+    }
+
+    macro_rules! skip_without_bus {
+        () => {
+            match PrivateBus::start() {
+                Some(bus) => bus,
+                None => {
+                    eprintln!("dbus-daemon not installed, skipping");
+                    return;
+                }
+            }
+        };
+    }
+
+    #[test]
+    fn test_properties_marshal_with_interface_types() {
+        let bus = skip_without_bus!();
+        let _service = Service::at_address(&bus.address, properties()).unwrap();
+        let client = bus.client();
+
+        let all = get_all(&client);
+        assert_eq!(all.len(), 4);
+        let signature = |name: &str| all[name].value_signature().to_string();
+        assert_eq!(signature("Score"), "u");
+        assert_eq!(signature("OptimizationsApplied"), "b");
+        assert_eq!(signature("PowerSource"), "s");
+        assert_eq!(signature("DriftedCount"), "u");
+
+        assert_eq!(u32::try_from(&all["Score"]).unwrap(), 82);
+        assert!(bool::try_from(&all["OptimizationsApplied"]).unwrap());
+        assert_eq!(<&str>::try_from(&all["PowerSource"]).unwrap(), "battery");
+        assert_eq!(u32::try_from(&all["DriftedCount"]).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_update_emits_only_changed_properties() {
+        let bus = skip_without_bus!();
+        let service = Service::at_address(&bus.address, properties()).unwrap();
+        let client = bus.client();
+        let changed = next_signal::<(String, HashMap<String, OwnedValue>, Vec<String>)>(
+            &client,
+            "PropertiesChanged",
+        );
+
+        service
+            .update(Properties {
+                power_source: "ac".to_string(),
+                ..properties()
+            })
+            .unwrap();
+
+        let (interface, values, _) = changed.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(interface, INTERFACE_NAME);
+        assert_eq!(values.keys().collect::<Vec<_>>(), ["PowerSource"]);
+        assert_eq!(<&str>::try_from(&values["PowerSource"]).unwrap(), "ac");
+        assert_eq!(
+            <&str>::try_from(&get_all(&client)["PowerSource"]).unwrap(),
+            "ac"
+        );
+    }
+
+    #[test]
+    fn test_auto_switch_events_are_signalled() {
+        let bus = skip_without_bus!();
+        let service = Service::at_address(&bus.address, properties()).unwrap();
+        let client = bus.client();
+        let switched = next_signal::<(String, String)>(&client, "AutoSwitched");
+
+        let drain = NotifyEvent::new(EventKind::Drain, "bop", "idle drain");
+        service.auto_switched(&drain).unwrap();
+        let reverted = NotifyEvent::new(EventKind::Reverted, "bop", "reverted (on AC)").quiet();
+        service.auto_switched(&reverted).unwrap();
+
+        assert_eq!(
+            switched.recv_timeout(Duration::from_secs(5)).unwrap(),
+            ("reverted".to_string(), "reverted (on AC)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_served_interface_matches_xml() {
+        let bus = skip_without_bus!();
+        let _service = Service::at_address(&bus.address, properties()).unwrap();
+        let served = zbus::blocking::fdo::IntrospectableProxy::builder(&bus.client())
+            .destination(BUS_NAME)
+            .unwrap()
+            .path(OBJECT_PATH)
+            .unwrap()
+            .build()
+            .unwrap()
+            .introspect()
+            .unwrap();
+
+        let members = |xml: &str| -> Vec<String> {
+            let start = xml
+                .find(&format!("<interface name=\"{}\">", INTERFACE_NAME))
+                .expect("interface present");
+            let end = start + xml[start..].find("</interface>").unwrap();
+            // One entry per member, args kept with their signal; zbus
+            // orders members its own way
+            let mut members: Vec<String> = Vec::new();
+            for line in xml[start..end].lines().map(str::trim) {
+                if line.starts_with("<arg ") {
+                    members.last_mut().unwrap().push_str(line);
+                } else if ["<property ", "<signal ", "<method "]
+                    .iter()
+                    .any(|tag| line.starts_with(tag))
+                {
+                    members.push(line.to_string());
+                }
+            }
+            members.sort();
+            members
+        };
+        assert_eq!(members(&served), members(INTERFACE_XML));
+    }
+
+    /// Stands in for logind: hands out the read end's peer of a pipe for
+    /// each lock, so the test sees the lock close as EOF.
+    struct FakeLogind {
+        locks: mpsc::Sender<(String, std::io::PipeReader)>,
+    }
+
+    #[interface(name = "org.freedesktop.login1.Manager")]
+    impl FakeLogind {
+        fn inhibit(
+            &self,
+            what: &str,
+            _who: &str,
+            _why: &str,
+            mode: &str,
+        ) -> zbus::zvariant::OwnedFd {
+            let (reader, writer) = std::io::pipe().unwrap();
+            self.locks
+                .send((format!("{}/{}", what, mode), reader))
+                .unwrap();
+            std::os::fd::OwnedFd::from(writer).into()
+        }
+
+        #[zbus(signal)]
+        async fn prepare_for_sleep(emitter: &SignalEmitter<'_>, start: bool) -> zbus::Result<()>;
+    }
+
+    /// Receives once `reader`'s lock has been closed.
+    fn closed(mut reader: std::io::PipeReader) -> mpsc::Receiver<()> {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = std::io::Read::read_to_end(&mut reader, &mut Vec::new());
+            let _ = tx.send(());
+        });
+        rx
+    }
+
+    #[test]
+    fn test_sleep_hook_runs_inside_a_delay_lock() {
+        let bus = skip_without_bus!();
+        let (lock_tx, locks) = mpsc::channel();
+        let logind = connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .name(LOGIND)
+            .unwrap()
+            .serve_at(LOGIND_PATH, FakeLogind { locks: lock_tx })
+            .unwrap()
+            .build()
+            .unwrap();
+        let (hook_tx, hooks) = mpsc::channel();
+        let client = bus.client();
+        std::thread::spawn(move || {
+            let _ = watch_sleep(&client, |sleeping| hook_tx.send(sleeping).unwrap());
+        });
+        let emit = |start: bool| {
+            let emitter = SignalEmitter::new(logind.inner(), LOGIND_PATH).unwrap();
+            zbus::block_on(FakeLogind::prepare_for_sleep(&emitter, start)).unwrap();
+        };
+        let timeout = Duration::from_secs(5);
+
+        let (kind, first) = locks.recv_timeout(timeout).unwrap();
+        assert_eq!(kind, "sleep/delay");
+        let released = closed(first);
+        assert!(released.recv_timeout(Duration::from_millis(200)).is_err());
+
+        emit(true);
+        assert!(hooks.recv_timeout(timeout).unwrap());
+        released.recv_timeout(timeout).unwrap();
+
+        // A new lock before the resume hook runs, for the next suspend
+        emit(false);
+        let (_, second) = locks.recv_timeout(timeout).unwrap();
+        assert!(!hooks.recv_timeout(timeout).unwrap());
+        assert!(
+            closed(second)
+                .recv_timeout(Duration::from_millis(200))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_unit_and_policy_carry_headers() {
+        let unit = render_unit();
+        assert!(crate::managed::has_header(&unit));
+        assert!(unit.contains("\nType=dbus\nBusName=io.github.yv_was_taken.Bop\n"));
+        assert!(unit.contains("ExecStart=/usr/bin/bop dbus-serve"));
+
+        let policy = render_policy();
+        assert!(crate::managed::has_header(&policy));
+        assert!(policy.contains("<allow own=\"io.github.yv_was_taken.Bop\"/>"));
+    }
+}
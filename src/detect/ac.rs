@@ -1,11 +1,27 @@
+use super::power_supply::SupplyAttrs;
 use crate::sysfs::SysfsRoot;
 
-/// AC adapter (mains power) detection.
+/// Name prefix of the UCSI supplies some kernels register per USB-C port,
+/// typed `USB` rather than `Mains` but online while the port charges us.
+const UCSI_SOURCE_PREFIX: &str = "ucsi-source-psy-";
+
+/// One external power supply and whether it is feeding the machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcSupply {
+    pub name: String,
+    pub online: bool,
+}
+
+/// AC adapter (mains power) detection. Machines with several USB-C port
+/// controllers expose one supply each; the machine is on AC if any of
+/// them is online.
 #[derive(Debug, Clone, Default)]
 pub struct AcInfo {
     pub found: bool,
+    /// The online supply, or the first one found when none is
     pub supply_name: Option<String>,
     pub online: bool,
+    pub supplies: Vec<AcSupply>,
 }
 
 impl AcInfo {
@@ -13,34 +29,49 @@ impl AcInfo {
         let mut info = Self::default();
 
         let ps_base = "sys/class/power_supply";
-        let entries = match sysfs.list_dir(ps_base) {
+        let mut entries = match sysfs.list_dir(ps_base) {
             Ok(e) => e,
             Err(_) => return info,
         };
 
+        entries.sort();
         for name in &entries {
-            let base = format!("{}/{}", ps_base, name);
-
-            let ptype = sysfs
-                .read_optional(format!("{}/type", base))
-                .unwrap_or(None);
-            if ptype.as_deref() != Some("Mains") {
+            let attrs = SupplyAttrs::new(sysfs, name);
+            if !is_external_supply(name, attrs.get("type").as_deref()) {
                 continue;
             }
-
-            info.found = true;
-            info.supply_name = Some(name.clone());
-            info.online = sysfs
-                .read_optional(format!("{}/online", base))
-                .unwrap_or(None)
-                .as_deref()
-                == Some("1");
-            break;
+            info.supplies.push(AcSupply {
+                name: name.clone(),
+                online: attrs.get("online").as_deref() == Some("1"),
+            });
         }
 
+        info.found = !info.supplies.is_empty();
+        info.online = info.supplies.iter().any(|s| s.online);
+        info.supply_name = info
+            .supplies
+            .iter()
+            .find(|s| s.online)
+            .or(info.supplies.first())
+            .map(|s| s.name.clone());
         info
     }
 
+    /// Supplies and their states for the hardware summary, e.g.
+    /// `ACAD offline, ucsi-source-psy-USBC000:002 online`.
+    pub fn supplies_summary(&self) -> Option<String> {
+        self.found.then(|| {
+            self.supplies
+                .iter()
+                .map(|s| {
+                    let state = if s.online { "online" } else { "offline" };
+                    format!("{} {}", s.name, state)
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+    }
+
     pub fn is_on_ac(&self) -> bool {
         self.found && self.online
     }
@@ -50,6 +81,14 @@ impl AcInfo {
     }
 }
 
+fn is_external_supply(name: &str, supply_type: Option<&str>) -> bool {
+    match supply_type {
+        Some("Mains") => true,
+        Some("USB") => name.starts_with(UCSI_SOURCE_PREFIX),
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,6 +151,81 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_uevent_fallback() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("sys/class/power_supply/AC");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("uevent"),
+            "POWER_SUPPLY_NAME=AC\nPOWER_SUPPLY_TYPE=Mains\nPOWER_SUPPLY_ONLINE=1\n",
+        )
+        .unwrap();
+        let ac = AcInfo::detect(&SysfsRoot::new(tmp.path()));
+        assert!(ac.is_on_ac());
+
+        // The attribute file, when readable, wins over uevent
+        fs::write(dir.join("online"), "0\n").unwrap();
+        let ac = AcInfo::detect(&SysfsRoot::new(tmp.path()));
+        assert!(ac.is_on_battery());
+    }
+
+    #[test]
+    fn test_any_online_mains_supply_means_ac() {
+        // Both USB-C port controllers expose a Mains supply; the left one
+        // enumerates first
+        for (left, right) in [("0", "0"), ("0", "1"), ("1", "0"), ("1", "1")] {
+            let tmp = TempDir::new().unwrap();
+            make_sysfs(tmp.path(), "ACAD", "Mains", left);
+            make_sysfs(tmp.path(), "ACAD1", "Mains", right);
+            let ac = AcInfo::detect(&SysfsRoot::new(tmp.path()));
+
+            let any_online = left == "1" || right == "1";
+            assert!(ac.found);
+            assert_eq!(ac.is_on_ac(), any_online, "left={left} right={right}");
+            assert_eq!(ac.is_on_battery(), !any_online);
+            assert_eq!(
+                ac.supplies,
+                [
+                    AcSupply {
+                        name: "ACAD".to_string(),
+                        online: left == "1",
+                    },
+                    AcSupply {
+                        name: "ACAD1".to_string(),
+                        online: right == "1",
+                    },
+                ]
+            );
+            let expected_name = if left == "0" && right == "1" {
+                "ACAD1"
+            } else {
+                "ACAD"
+            };
+            assert_eq!(ac.supply_name.as_deref(), Some(expected_name));
+        }
+    }
+
+    #[test]
+    fn test_ucsi_source_supplies_count_as_ac() {
+        let tmp = TempDir::new().unwrap();
+        make_sysfs(tmp.path(), "ucsi-source-psy-USBC000:001", "USB", "0");
+        make_sysfs(tmp.path(), "ucsi-source-psy-USBC000:002", "USB", "1");
+        // Only the UCSI source supplies; other USB-typed ones aren't adapters
+        make_sysfs(tmp.path(), "usb-charger", "USB", "1");
+        let ac = AcInfo::detect(&SysfsRoot::new(tmp.path()));
+        assert!(ac.is_on_ac());
+        assert_eq!(ac.supplies.len(), 2);
+        assert_eq!(
+            ac.supply_name.as_deref(),
+            Some("ucsi-source-psy-USBC000:002")
+        );
+        assert_eq!(
+            ac.supplies_summary().as_deref(),
+            Some("ucsi-source-psy-USBC000:001 offline, ucsi-source-psy-USBC000:002 online")
+        );
+    }
+
     #[test]
     fn test_battery_only_no_mains() {
         let tmp = TempDir::new().unwrap();
@@ -0,0 +1,188 @@
+//! Parser for /proc/acpi/wakeup. The kernel prints one line per device:
+//!
+//! ```text
+//! Device  S-state   Status   Sysfs node
+//! XHC1      S3     *enabled  pci:0000:c1:00.4
+//! ```
+//!
+//! Firmware and kernel versions bend that layout. Some tables list the
+//! same name twice (two GPP6 bridges), the status can be glued to the
+//! S-state ("S4*enabled"), the sysfs node column is missing for devices
+//! without a physical node, and a device with several nodes gets a
+//! continuation line per extra node, with no name. Fields are recognised by
+//! their shape rather than their position.
+//!
+//! Writing a name to the file toggles the first device with that name only,
+//! so a duplicated name can't be switched on its own; [`lookup`] refuses it.
+
+use crate::error::{Error, Result};
+
+/// One device in the table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WakeupEntry {
+    /// ACPI name, e.g. "XHC1"
+    pub device: String,
+    /// Position among entries sharing `device`, 0 for the first
+    pub index: usize,
+    /// e.g. "S4"; None when the column is missing
+    pub sleep_state: Option<String>,
+    /// None when there is no status column
+    pub enabled: Option<bool>,
+    /// Physical nodes as `bus:name`, e.g. "pci:0000:c1:00.4", including
+    /// those from continuation lines
+    pub nodes: Vec<String>,
+    /// The device's own line, as read
+    pub line: String,
+}
+
+impl WakeupEntry {
+    /// The first physical node, the one the device's line names.
+    pub fn sysfs_node(&self) -> Option<&str> {
+        self.nodes.first().map(String::as_str)
+    }
+
+    /// PCI address of the first node, if it is a PCI device.
+    pub fn pci_address(&self) -> Option<&str> {
+        self.sysfs_node()?.strip_prefix("pci:")
+    }
+}
+
+/// `enabled`/`disabled`, with or without the `*` valid flag and any S-state
+/// glued on in front.
+fn parse_status(field: &str) -> Option<bool> {
+    let status = field.rsplit('*').next().unwrap_or(field);
+    if status.ends_with("disabled") {
+        Some(false)
+    } else if status.ends_with("enabled") {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// "S4", or the S-state in front of a glued status ("S4*enabled").
+fn parse_sleep_state(field: &str) -> Option<String> {
+    let state = field.split('*').next().unwrap_or(field);
+    let digits = state.strip_prefix('S')?;
+    (!digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())).then(|| state.to_string())
+}
+
+/// Parse the whole table; unrecognisable lines are skipped.
+pub fn parse(table: &str) -> Vec<WakeupEntry> {
+    let mut entries: Vec<WakeupEntry> = Vec::new();
+    for line in table.lines() {
+        let mut fields = line.split_whitespace().peekable();
+        let Some(&first) = fields.peek() else {
+            continue;
+        };
+        if first == "Device" {
+            continue;
+        }
+        // A continuation line: another node of the device above
+        if line.starts_with(char::is_whitespace) {
+            if let Some(last) = entries.last_mut() {
+                last.nodes.extend(
+                    fields
+                        .filter(|f| parse_status(f).is_none() && f.contains(':'))
+                        .map(String::from),
+                );
+            }
+            continue;
+        }
+        fields.next();
+
+        let mut entry = WakeupEntry {
+            device: first.to_string(),
+            index: entries.iter().filter(|e| e.device == first).count(),
+            sleep_state: None,
+            enabled: None,
+            nodes: Vec::new(),
+            line: line.to_string(),
+        };
+        for field in fields {
+            if let Some(enabled) = parse_status(field) {
+                entry.enabled = Some(enabled);
+                if entry.sleep_state.is_none() {
+                    entry.sleep_state = parse_sleep_state(field);
+                }
+            } else if let Some(state) = parse_sleep_state(field) {
+                entry.sleep_state = Some(state);
+            } else if field.contains(':') {
+                entry.nodes.push(field.to_string());
+            }
+        }
+        entries.push(entry);
+    }
+    entries
+}
+
+/// Whether another entry shares `entry`'s name.
+pub fn is_ambiguous(entries: &[WakeupEntry], entry: &WakeupEntry) -> bool {
+    entries
+        .iter()
+        .filter(|e| e.device == entry.device)
+        .nth(1)
+        .is_some()
+}
+
+/// The entry named `device`, None if it isn't listed. A name listed more
+/// than once is an error naming each entry's node, since writing it would
+/// toggle only the first.
+pub fn lookup<'a>(entries: &'a [WakeupEntry], device: &str) -> Result<Option<&'a WakeupEntry>> {
+    let matches: Vec<&WakeupEntry> = entries.iter().filter(|e| e.device == device).collect();
+    match matches.as_slice() {
+        [] => Ok(None),
+        [entry] => Ok(Some(entry)),
+        _ => Err(Error::AmbiguousWakeupDevice {
+            device: device.to_string(),
+            nodes: matches
+                .iter()
+                .map(|e| e.sysfs_node().unwrap_or("no sysfs node").to_string())
+                .collect(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields_by_shape() {
+        let entries = parse(
+            "Device\tS-state\t  Status   Sysfs node\n\
+             XHC1\t  S3\t*enabled   pci:0000:c1:00.4\n\
+             LID0\t  S4*disabled  platform:PNP0C0D:00\n\
+             PWRB\t  S5\t*enabled\n",
+        );
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].pci_address(), Some("0000:c1:00.4"));
+        assert_eq!(entries[1].sleep_state.as_deref(), Some("S4"));
+        assert_eq!(entries[1].enabled, Some(false));
+        assert_eq!(entries[1].sysfs_node(), Some("platform:PNP0C0D:00"));
+        assert_eq!(entries[2].enabled, Some(true));
+        assert!(entries[2].nodes.is_empty());
+    }
+
+    #[test]
+    fn test_lookup_refuses_duplicates() {
+        let entries = parse(
+            "GPP6\t  S4\t*enabled   pci:0000:00:02.2\n\
+             GPP6\t  S4\t*disabled  pci:0000:00:02.4\n\
+             XHC1\t  S3\t*enabled   pci:0000:c1:00.4\n",
+        );
+        assert_eq!(entries[1].index, 1);
+        assert!(is_ambiguous(&entries, &entries[0]));
+        assert!(!is_ambiguous(&entries, &entries[2]));
+        assert_eq!(lookup(&entries, "XHC1").unwrap(), Some(&entries[2]));
+        assert_eq!(lookup(&entries, "XHC").unwrap(), None);
+
+        let err = lookup(&entries, "GPP6").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("pci:0000:00:02.2, pci:0000:00:02.4"),
+            "{}",
+            err
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use super::power_supply::SupplyAttrs;
 use crate::sysfs::SysfsRoot;
 
 #[derive(Debug, Clone, Default)]
@@ -19,13 +20,8 @@ pub struct BatteryInfo {
     pub cycle_count: Option<u32>,
     pub health_percent: Option<f64>,
     pub supply_name: Option<String>,
-}
-
-fn read_u64(sysfs: &SysfsRoot, path: String) -> Option<u64> {
-    sysfs
-        .read_optional(path)
-        .unwrap_or(None)
-        .and_then(|v| v.parse().ok())
+    /// `serial_number`, when the pack reports a non-blank one
+    pub serial: Option<String>,
 }
 
 impl BatteryInfo {
@@ -44,11 +40,9 @@ impl BatteryInfo {
             None => return info,
         };
 
-        let base = format!("{}/{}", ps_base, bat_name);
+        let attrs = SupplyAttrs::new(sysfs, &bat_name);
 
-        if let Some(ptype) = sysfs
-            .read_optional(format!("{}/type", base))
-            .unwrap_or(None)
+        if let Some(ptype) = attrs.get("type")
             && ptype != "Battery"
         {
             return info;
@@ -56,31 +50,29 @@ impl BatteryInfo {
 
         info.supply_name = Some(bat_name.clone());
 
-        info.present = sysfs
-            .read_optional(format!("{}/present", base))
-            .unwrap_or(None)
-            .as_deref()
-            == Some("1");
+        info.present = attrs.get("present").as_deref() == Some("1");
 
-        info.status = sysfs
-            .read_optional(format!("{}/status", base))
-            .unwrap_or(None);
-        info.capacity_percent = read_u64(sysfs, format!("{}/capacity", base)).map(|v| v as u32);
+        info.status = attrs.get("status");
+        info.capacity_percent = attrs.get_u64("capacity").map(|v| v as u32);
 
         // Energy-based fields (some batteries report these directly)
-        info.energy_now_uwh = read_u64(sysfs, format!("{}/energy_now", base));
-        info.energy_full_uwh = read_u64(sysfs, format!("{}/energy_full", base));
-        info.energy_full_design_uwh = read_u64(sysfs, format!("{}/energy_full_design", base));
-        info.power_now_uw = read_u64(sysfs, format!("{}/power_now", base));
+        info.energy_now_uwh = attrs.get_u64("energy_now");
+        info.energy_full_uwh = attrs.get_u64("energy_full");
+        info.energy_full_design_uwh = attrs.get_u64("energy_full_design");
+        info.power_now_uw = attrs.get_u64("power_now");
 
         // Charge-based fields (other batteries report µAh/µA instead)
-        info.charge_now_uah = read_u64(sysfs, format!("{}/charge_now", base));
-        info.charge_full_uah = read_u64(sysfs, format!("{}/charge_full", base));
-        info.charge_full_design_uah = read_u64(sysfs, format!("{}/charge_full_design", base));
-        info.current_now_ua = read_u64(sysfs, format!("{}/current_now", base));
-        info.voltage_now_uv = read_u64(sysfs, format!("{}/voltage_now", base));
-
-        info.cycle_count = read_u64(sysfs, format!("{}/cycle_count", base)).map(|v| v as u32);
+        info.charge_now_uah = attrs.get_u64("charge_now");
+        info.charge_full_uah = attrs.get_u64("charge_full");
+        info.charge_full_design_uah = attrs.get_u64("charge_full_design");
+        info.current_now_ua = attrs.get_u64("current_now");
+        info.voltage_now_uv = attrs.get_u64("voltage_now");
+
+        info.cycle_count = attrs.get_u64("cycle_count").map(|v| v as u32);
+        info.serial = attrs
+            .get("serial_number")
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
 
         // Calculate health from whichever set of fields is available
         let (full, design) = match (info.energy_full_uwh, info.energy_full_design_uwh) {
@@ -136,3 +128,106 @@ impl BatteryInfo {
         self.status.as_deref() == Some("Discharging")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn battery_dir(root: &Path) -> std::path::PathBuf {
+        let dir = root.join("sys/class/power_supply/BAT1");
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_uevent_only() {
+        let tmp = TempDir::new().unwrap();
+        let dir = battery_dir(tmp.path());
+        fs::write(
+            dir.join("uevent"),
+            "POWER_SUPPLY_NAME=BAT1\n\
+             POWER_SUPPLY_TYPE=Battery\n\
+             POWER_SUPPLY_STATUS=Discharging\n\
+             POWER_SUPPLY_PRESENT=1\n\
+             POWER_SUPPLY_CYCLE_COUNT=42\n\
+             POWER_SUPPLY_ENERGY_FULL_DESIGN=85000000\n\
+             POWER_SUPPLY_ENERGY_FULL=76500000\n\
+             POWER_SUPPLY_ENERGY_NOW=51230000\n\
+             POWER_SUPPLY_POWER_NOW=8500000\n\
+             POWER_SUPPLY_CAPACITY=67\n\
+             POWER_SUPPLY_MANUFACTURER=NVT\n",
+        )
+        .unwrap();
+
+        let bat = BatteryInfo::detect(&SysfsRoot::new(tmp.path()));
+        assert!(bat.present);
+        assert!(bat.is_discharging());
+        assert_eq!(bat.capacity_percent, Some(67));
+        assert_eq!(bat.energy_now_uwh, Some(51_230_000));
+        assert_eq!(bat.power_now_uw, Some(8_500_000));
+        assert_eq!(bat.cycle_count, Some(42));
+        assert_eq!(bat.health_percent, Some(90.0));
+    }
+
+    #[test]
+    fn test_attributes_only() {
+        let tmp = TempDir::new().unwrap();
+        let dir = battery_dir(tmp.path());
+        for (file, value) in [
+            ("type", "Battery"),
+            ("present", "1"),
+            ("status", "Charging"),
+            ("capacity", "80"),
+            ("charge_now", "4000000"),
+            ("voltage_now", "15000000"),
+        ] {
+            fs::write(dir.join(file), format!("{}\n", value)).unwrap();
+        }
+
+        let bat = BatteryInfo::detect(&SysfsRoot::new(tmp.path()));
+        assert!(bat.present);
+        assert_eq!(bat.status.as_deref(), Some("Charging"));
+        assert_eq!(bat.capacity_percent, Some(80));
+        assert_eq!(bat.charge_now_uah, Some(4_000_000));
+        assert_eq!(bat.energy_now_uwh, None);
+        assert_eq!(bat.energy_wh(), Some(60.0));
+    }
+
+    #[test]
+    fn test_attributes_win_over_uevent_and_gaps_are_filled() {
+        let tmp = TempDir::new().unwrap();
+        let dir = battery_dir(tmp.path());
+        fs::write(dir.join("type"), "Battery\n").unwrap();
+        fs::write(dir.join("capacity"), "55\n").unwrap();
+        // Present but empty, as when a read races a driver refresh
+        fs::write(dir.join("energy_now"), "").unwrap();
+        // Unparseable attribute falls back too
+        fs::write(dir.join("power_now"), "N/A\n").unwrap();
+        fs::write(
+            dir.join("uevent"),
+            "POWER_SUPPLY_CAPACITY=99\n\
+             POWER_SUPPLY_PRESENT=1\n\
+             POWER_SUPPLY_ENERGY_NOW=40000000\n\
+             POWER_SUPPLY_POWER_NOW=7000000\n",
+        )
+        .unwrap();
+
+        let bat = BatteryInfo::detect(&SysfsRoot::new(tmp.path()));
+        assert_eq!(bat.capacity_percent, Some(55), "attribute file wins");
+        assert!(bat.present, "missing attribute filled from uevent");
+        assert_eq!(bat.energy_now_uwh, Some(40_000_000));
+        assert_eq!(bat.power_now_uw, Some(7_000_000));
+    }
+
+    #[test]
+    fn test_uevent_type_rejects_non_battery() {
+        let tmp = TempDir::new().unwrap();
+        let dir = battery_dir(tmp.path());
+        fs::write(dir.join("uevent"), "POWER_SUPPLY_TYPE=USB\n").unwrap();
+        let bat = BatteryInfo::detect(&SysfsRoot::new(tmp.path()));
+        assert!(bat.supply_name.is_none());
+    }
+}
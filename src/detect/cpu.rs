@@ -1,5 +1,58 @@
 use crate::sysfs::SysfsRoot;
 
+const CPUFREQ_DIR: &str = "sys/devices/system/cpu/cpufreq";
+
+/// Core type on hybrid CPUs, from the perf PMUs the kernel registers for
+/// each (cpu_core, cpu_atom).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoreType {
+    Performance,
+    Efficiency,
+}
+
+impl std::fmt::Display for CoreType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoreType::Performance => write!(f, "P-cores"),
+            CoreType::Efficiency => write!(f, "E-cores"),
+        }
+    }
+}
+
+/// A cpufreq policy (`cpufreq/policyN`): CPUs that share one set of
+/// frequency controls. Each `cpuN/cpufreq` links to its policy's directory.
+#[derive(Debug, Clone, Default)]
+pub struct CpuPolicy {
+    /// Directory name, e.g. "policy4"
+    pub name: String,
+    /// From affected_cpus, ascending
+    pub cpus: Vec<u32>,
+    pub scaling_driver: Option<String>,
+    pub governor: Option<String>,
+    pub epp: Option<String>,
+    /// The policy's own boost switch; None where only the global
+    /// `cpufreq/boost` exists
+    pub boost: Option<bool>,
+    /// None unless every CPU of the policy is of one hybrid core type
+    pub core_type: Option<CoreType>,
+}
+
+impl CpuPolicy {
+    /// `file` under the policy directory, as detection read it.
+    pub fn path(&self, file: &str) -> String {
+        format!("{}/{}/{}", CPUFREQ_DIR, self.name, file)
+    }
+
+    /// The `cpuN/cpufreq` path of `file` for this policy, through its first
+    /// CPU. Same file as under the policy directory, and the form `[pins]`
+    /// and finding paths use.
+    pub fn cpu_path(&self, file: &str) -> Option<String> {
+        self.cpus
+            .first()
+            .map(|cpu| format!("sys/devices/system/cpu/cpu{}/cpufreq/{}", cpu, file))
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct CpuInfo {
     pub model_name: Option<String>,
@@ -14,6 +67,11 @@ pub struct CpuInfo {
     pub has_boost: bool,
     pub boost_enabled: bool,
     pub amd_pstate_mode: Option<String>,
+    /// Intel energy_perf_bias (0 = performance .. 15 = powersave) of each
+    /// CPU that has one, by CPU number
+    pub energy_perf_bias: Vec<(u32, u8)>,
+    /// By policy number; empty without the `cpufreq/policyN` layout
+    pub policies: Vec<CpuPolicy>,
 }
 
 impl CpuInfo {
@@ -45,20 +103,28 @@ impl CpuInfo {
             }
         }
 
+        info.policies = detect_policies(sysfs);
+        // The first policy stands in where cpu0 has no cpufreq directory
+        // (offline, or a snapshot that only kept the policies)
+        let first = info.policies.first();
+
         // Scaling driver from cpu0
         info.scaling_driver = sysfs
             .read_optional("sys/devices/system/cpu/cpu0/cpufreq/scaling_driver")
-            .unwrap_or(None);
+            .unwrap_or(None)
+            .or_else(|| first.and_then(|p| p.scaling_driver.clone()));
 
         // Governor from cpu0
         info.governor = sysfs
             .read_optional("sys/devices/system/cpu/cpu0/cpufreq/scaling_governor")
-            .unwrap_or(None);
+            .unwrap_or(None)
+            .or_else(|| first.and_then(|p| p.governor.clone()));
 
         // Energy Performance Preference
         info.epp = sysfs
             .read_optional("sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference")
-            .unwrap_or(None);
+            .unwrap_or(None)
+            .or_else(|| first.and_then(|p| p.epp.clone()));
 
         // Available EPP values
         if let Some(avail) = sysfs
@@ -70,12 +136,23 @@ impl CpuInfo {
             info.epp_available = avail.split_whitespace().map(String::from).collect();
         }
 
-        // Count online CPUs
+        // Count online CPUs, and read each one's Intel EPB — the remaining
+        // lever when acpi-cpufreq has no EPP
         if let Ok(entries) = sysfs.list_dir("sys/devices/system/cpu") {
-            info.online_cpus = entries
+            let cpus: Vec<u32> = entries
                 .iter()
-                .filter(|e| e.starts_with("cpu") && e[3..].chars().all(|c| c.is_ascii_digit()))
-                .count() as u32;
+                .filter_map(|e| e.strip_prefix("cpu")?.parse().ok())
+                .collect();
+            info.online_cpus = cpus.len() as u32;
+            info.energy_perf_bias = cpus
+                .into_iter()
+                .filter_map(|cpu| {
+                    let path = format!("sys/devices/system/cpu/cpu{}/power/energy_perf_bias", cpu);
+                    let epb = sysfs.read_optional(path).unwrap_or(None)?.parse().ok()?;
+                    Some((cpu, epb))
+                })
+                .collect();
+            info.energy_perf_bias.sort_unstable();
         }
 
         // amd_pstate mode
@@ -105,8 +182,298 @@ impl CpuInfo {
             .is_some_and(|d| d.starts_with("amd-pstate"))
     }
 
+    pub fn is_acpi_cpufreq(&self) -> bool {
+        self.scaling_driver.as_deref() == Some("acpi-cpufreq")
+    }
+
+    /// EPB is only worth tuning when there's no EPP to do the job.
+    pub fn uses_epb(&self) -> bool {
+        !self.energy_perf_bias.is_empty() && self.epp.is_none()
+    }
+
     pub fn is_zen4(&self) -> bool {
         // Zen 4: family 25 (0x19), models 0x60-0x7F (Phoenix/Ryzen 7040)
         self.is_amd() && self.family == Some(25) && self.model.is_some_and(|m| m >= 0x60)
     }
+
+    /// Policies grouped by their EPP, in policy order, with a label for each
+    /// group when there is more than one. One unlabelled group from cpu0
+    /// without the policy layout.
+    pub fn epp_groups(&self) -> Vec<PolicyGroup<'_, String>> {
+        if self.policies.is_empty() {
+            return self
+                .epp
+                .iter()
+                .map(|epp| PolicyGroup {
+                    value: epp.clone(),
+                    policies: Vec::new(),
+                    label: None,
+                })
+                .collect();
+        }
+        group_policies(&self.policies, |p| p.epp.clone())
+    }
+
+    /// "balance_power (P-cores), power (E-cores)", or the one value when
+    /// every policy agrees.
+    pub fn epp_summary(&self) -> Option<String> {
+        summarize(&self.epp_groups())
+    }
+
+    /// Policies grouped by their own boost switch, labelled as in
+    /// [`Self::epp_groups`]. One group from the global `cpufreq/boost`
+    /// where no policy has a switch of its own.
+    pub fn boost_groups(&self) -> Vec<PolicyGroup<'_, bool>> {
+        if self.policies.iter().any(|p| p.boost.is_some()) {
+            return group_policies(&self.policies, |p| p.boost);
+        }
+        if !self.has_boost {
+            return Vec::new();
+        }
+        vec![PolicyGroup {
+            value: self.boost_enabled,
+            policies: Vec::new(),
+            label: None,
+        }]
+    }
+
+    /// Boost state, per policy where policies have their own switch:
+    /// "enabled (P-cores), disabled (E-cores)".
+    pub fn boost_summary(&self) -> Option<String> {
+        let groups: Vec<_> = self
+            .boost_groups()
+            .into_iter()
+            .map(|g| PolicyGroup {
+                value: if g.value { "enabled" } else { "disabled" }.to_string(),
+                policies: g.policies,
+                label: g.label,
+            })
+            .collect();
+        summarize(&groups)
+    }
+}
+
+/// Policies that share a value.
+#[derive(Debug, Clone)]
+pub struct PolicyGroup<'a, T> {
+    pub value: T,
+    pub policies: Vec<&'a CpuPolicy>,
+    /// Which CPUs these are ("P-cores", "CPUs 0-3"); None when the group
+    /// covers every policy with a value
+    pub label: Option<String>,
+}
+
+/// Group `policies` by `value`, skipping those without one.
+fn group_policies<T: PartialEq>(
+    policies: &[CpuPolicy],
+    value: impl Fn(&CpuPolicy) -> Option<T>,
+) -> Vec<PolicyGroup<'_, T>> {
+    let mut groups: Vec<PolicyGroup<'_, T>> = Vec::new();
+    for policy in policies {
+        let Some(v) = value(policy) else {
+            continue;
+        };
+        match groups.iter_mut().find(|g| g.value == v) {
+            Some(group) => group.policies.push(policy),
+            None => groups.push(PolicyGroup {
+                value: v,
+                policies: vec![policy],
+                label: None,
+            }),
+        }
+    }
+    if groups.len() > 1 {
+        for group in &mut groups {
+            group.label = Some(group_label(&group.policies));
+        }
+    }
+    groups
+}
+
+/// The core type all of `policies` share, else their CPU list.
+fn group_label(policies: &[&CpuPolicy]) -> String {
+    if let Some(core_type) = policies[0].core_type
+        && policies.iter().all(|p| p.core_type == Some(core_type))
+    {
+        return core_type.to_string();
+    }
+    let mut cpus: Vec<u32> = policies
+        .iter()
+        .flat_map(|p| p.cpus.iter().copied())
+        .collect();
+    cpus.sort_unstable();
+    format!("CPUs {}", format_cpu_list(&cpus))
+}
+
+fn summarize(groups: &[PolicyGroup<'_, String>]) -> Option<String> {
+    match groups {
+        [] => None,
+        [only] => Some(only.value.clone()),
+        _ => Some(
+            groups
+                .iter()
+                .map(|g| format!("{} ({})", g.value, g.label.as_deref().unwrap_or_default()))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+    }
+}
+
+fn detect_policies(sysfs: &SysfsRoot) -> Vec<CpuPolicy> {
+    let Ok(entries) = sysfs.list_dir(CPUFREQ_DIR) else {
+        return Vec::new();
+    };
+    let mut names: Vec<(u32, String)> = entries
+        .into_iter()
+        .filter_map(|e| Some((e.strip_prefix("policy")?.parse().ok()?, e)))
+        .collect();
+    names.sort();
+
+    let p_cores = read_cpu_list(sysfs, "sys/devices/cpu_core/cpus");
+    let e_cores = read_cpu_list(sysfs, "sys/devices/cpu_atom/cpus");
+    names
+        .into_iter()
+        .map(|(_, name)| {
+            let dir = format!("{}/{}", CPUFREQ_DIR, name);
+            let read = |file: &str| {
+                sysfs
+                    .read_optional(format!("{}/{}", dir, file))
+                    .unwrap_or(None)
+            };
+            let mut cpus: Vec<u32> = read("affected_cpus")
+                .map(|v| {
+                    v.split_whitespace()
+                        .filter_map(|c| c.parse().ok())
+                        .collect()
+                })
+                .unwrap_or_default();
+            cpus.sort_unstable();
+            let all_in = |set: &[u32]| !cpus.is_empty() && cpus.iter().all(|c| set.contains(c));
+            let core_type = if all_in(&p_cores) {
+                Some(CoreType::Performance)
+            } else if all_in(&e_cores) {
+                Some(CoreType::Efficiency)
+            } else {
+                None
+            };
+            CpuPolicy {
+                scaling_driver: read("scaling_driver"),
+                governor: read("scaling_governor"),
+                epp: read("energy_performance_preference"),
+                boost: read("boost").map(|v| v == "1"),
+                name,
+                cpus,
+                core_type,
+            }
+        })
+        .collect()
+}
+
+/// A kernel cpulist ("0-3,8,10-11"); empty when absent.
+fn read_cpu_list(sysfs: &SysfsRoot, path: &str) -> Vec<u32> {
+    let Some(list) = sysfs.read_optional(path).unwrap_or(None) else {
+        return Vec::new();
+    };
+    let mut cpus = Vec::new();
+    for part in list.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (start, end) = part.split_once('-').unwrap_or((part, part));
+        if let (Ok(start), Ok(end)) = (start.parse::<u32>(), end.parse::<u32>()) {
+            cpus.extend(start..=end);
+        }
+    }
+    cpus
+}
+
+/// Sorted CPU numbers as a cpulist: [0, 1, 2, 3, 8] -> "0-3,8".
+pub fn format_cpu_list(cpus: &[u32]) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < cpus.len() {
+        let start = cpus[i];
+        while i + 1 < cpus.len() && cpus[i + 1] == cpus[i] + 1 {
+            i += 1;
+        }
+        parts.push(if cpus[i] == start {
+            start.to_string()
+        } else {
+            format!("{}-{}", start, cpus[i])
+        });
+        i += 1;
+    }
+    parts.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn policy(root: &std::path::Path, n: u32, cpus: &str, epp: &str, boost: Option<&str>) {
+        let dir = root.join(format!("{}/policy{}", CPUFREQ_DIR, n));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("affected_cpus"), format!("{}\n", cpus)).unwrap();
+        fs::write(dir.join("energy_performance_preference"), epp).unwrap();
+        if let Some(boost) = boost {
+            fs::write(dir.join("boost"), boost).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_cpu_list_round_trip() {
+        let tmp = TempDir::new().unwrap();
+        fs::create_dir_all(tmp.path().join("sys/devices/cpu_atom")).unwrap();
+        fs::write(
+            tmp.path().join("sys/devices/cpu_atom/cpus"),
+            "4-7,10,12-13\n",
+        )
+        .unwrap();
+        let cpus = read_cpu_list(&SysfsRoot::new(tmp.path()), "sys/devices/cpu_atom/cpus");
+        assert_eq!(cpus, vec![4, 5, 6, 7, 10, 12, 13]);
+        assert_eq!(format_cpu_list(&cpus), "4-7,10,12-13");
+        assert_eq!(format_cpu_list(&[]), "");
+    }
+
+    #[test]
+    fn test_policies_without_core_types_are_labelled_by_cpu() {
+        let tmp = TempDir::new().unwrap();
+        // policy10 sorts after policy2 by number, not by name
+        policy(tmp.path(), 10, "10 11", "power", Some("0"));
+        policy(tmp.path(), 0, "0 1", "balance_power", Some("1"));
+        policy(tmp.path(), 2, "2 3", "balance_power", Some("1"));
+        let cpu = CpuInfo::detect(&SysfsRoot::new(tmp.path()));
+
+        let names: Vec<&str> = cpu.policies.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["policy0", "policy2", "policy10"]);
+        assert!(cpu.policies.iter().all(|p| p.core_type.is_none()));
+        assert_eq!(cpu.epp.as_deref(), Some("balance_power"));
+        assert_eq!(
+            cpu.epp_summary().as_deref(),
+            Some("balance_power (CPUs 0-3), power (CPUs 10-11)")
+        );
+        assert_eq!(
+            cpu.boost_summary().as_deref(),
+            Some("enabled (CPUs 0-3), disabled (CPUs 10-11)")
+        );
+        assert_eq!(
+            cpu.policies[2]
+                .cpu_path("energy_performance_preference")
+                .as_deref(),
+            Some("sys/devices/system/cpu/cpu10/cpufreq/energy_performance_preference")
+        );
+    }
+
+    #[test]
+    fn test_agreeing_policies_summarize_to_one_value() {
+        let tmp = TempDir::new().unwrap();
+        policy(tmp.path(), 0, "0", "balance_power", None);
+        policy(tmp.path(), 1, "1", "balance_power", None);
+        let cpu = CpuInfo::detect(&SysfsRoot::new(tmp.path()));
+        let groups = cpu.epp_groups();
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].label.is_none());
+        assert_eq!(cpu.epp_summary().as_deref(), Some("balance_power"));
+        // No per-policy switch and no global one
+        assert_eq!(cpu.boost_summary(), None);
+    }
 }
@@ -0,0 +1,477 @@
+use crate::sysfs::SysfsRoot;
+
+/// Backlight technology of the internal panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanelTechnology {
+    Lcd,
+    Oled,
+    #[default]
+    Unknown,
+}
+
+impl std::fmt::Display for PanelTechnology {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PanelTechnology::Lcd => write!(f, "LCD"),
+            PanelTechnology::Oled => write!(f, "OLED"),
+            PanelTechnology::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Identity of the internal (eDP) panel, decoded from its EDID.
+#[derive(Debug, Clone, Default)]
+pub struct PanelInfo {
+    /// DRM connector, e.g. "card1-eDP-1"
+    pub connector: String,
+    /// Three-letter PNP manufacturer ID, e.g. "BOE", "SDC"
+    pub manufacturer: String,
+    pub product_code: u16,
+    /// Model string from the display name or alphanumeric descriptor
+    pub model: Option<String>,
+    pub technology: PanelTechnology,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DisplayInfo {
+    pub panel: Option<PanelInfo>,
+    /// DRM connectors with a display connected and not switched off, e.g.
+    /// ["card1-DP-3", "card1-eDP-1"]
+    pub active_connectors: Vec<String>,
+}
+
+/// Known laptop panels keyed by PNP manufacturer ID and model prefix.
+/// Used when the EDID descriptors don't spell out the technology.
+const KNOWN_PANELS: &[(&str, &str, PanelTechnology)] = &[
+    // Samsung Display AMOLED laptop panels all carry the ATNA prefix
+    ("SDC", "ATNA", PanelTechnology::Oled),
+    // Framework 16 stock 2560x1600 165Hz panel
+    ("BOE", "NE160QDM", PanelTechnology::Lcd),
+    // Framework 13 2.8K panel
+    ("BOE", "NE135A1M", PanelTechnology::Lcd),
+    // Framework 13 stock 2256x1504 panel
+    ("BOE", "NE135FBM", PanelTechnology::Lcd),
+];
+
+const EDID_HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+const EDID_BLOCK_LEN: usize = 128;
+const DESCRIPTOR_OFFSETS: [usize; 4] = [54, 72, 90, 108];
+const TAG_PRODUCT_NAME: u8 = 0xFC;
+const TAG_ALPHANUMERIC: u8 = 0xFE;
+
+impl DisplayInfo {
+    pub fn detect(sysfs: &SysfsRoot) -> Self {
+        let mut info = Self::default();
+
+        if let Ok(mut entries) = sysfs.list_dir("sys/class/drm") {
+            entries.sort();
+            for entry in &entries {
+                if !entry.starts_with("card") || !entry.contains('-') {
+                    continue;
+                }
+                let read = |attr: &str| {
+                    sysfs
+                        .read_optional(format!("sys/class/drm/{}/{}", entry, attr))
+                        .unwrap_or(None)
+                };
+                if read("status").as_deref() == Some("connected")
+                    && read("enabled").as_deref() != Some("disabled")
+                {
+                    info.active_connectors.push(entry.clone());
+                }
+            }
+            for entry in entries {
+                if !entry.contains("-eDP-") {
+                    continue;
+                }
+                let Some(edid) = sysfs
+                    .read_bytes_optional(format!("sys/class/drm/{}/edid", entry))
+                    .unwrap_or(None)
+                else {
+                    continue;
+                };
+                if let Some(panel) = parse_edid(&entry, &edid) {
+                    info.panel = Some(panel);
+                    break;
+                }
+            }
+        }
+
+        info
+    }
+
+    pub fn is_oled(&self) -> bool {
+        self.panel
+            .as_ref()
+            .is_some_and(|p| p.technology == PanelTechnology::Oled)
+    }
+
+    /// ABM dims the backlight and compensates with pixel gain, so it only
+    /// helps on backlit panels. Unidentified panels are treated as LCD,
+    /// which is what nearly every laptop eDP panel is.
+    pub fn supports_abm(&self) -> bool {
+        !self.is_oled()
+    }
+
+    /// Active connectors on DRM card `card` ("card1"), internal panel
+    /// included.
+    pub fn active_on<'a>(&'a self, card: &'a str) -> impl Iterator<Item = &'a str> {
+        self.active_connectors
+            .iter()
+            .map(String::as_str)
+            .filter(move |c| {
+                c.strip_prefix(card)
+                    .is_some_and(|rest| rest.starts_with('-'))
+            })
+    }
+}
+
+/// Whether a connector drives a built-in panel rather than an external
+/// display.
+pub fn is_internal(connector: &str) -> bool {
+    ["-eDP-", "-LVDS-", "-DSI-"]
+        .iter()
+        .any(|kind| connector.contains(kind))
+}
+
+/// Decode the base EDID block. Returns None for anything that isn't a
+/// well-formed block (bad header, short read, checksum mismatch).
+pub fn parse_edid(connector: &str, edid: &[u8]) -> Option<PanelInfo> {
+    if edid.len() < EDID_BLOCK_LEN || edid[..8] != EDID_HEADER {
+        return None;
+    }
+    let block = &edid[..EDID_BLOCK_LEN];
+    if block.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)) != 0 {
+        return None;
+    }
+
+    let manufacturer = decode_manufacturer(block[8], block[9])?;
+    let product_code = u16::from_le_bytes([block[10], block[11]]);
+
+    let mut name = None;
+    let mut alphanumeric = Vec::new();
+    for offset in DESCRIPTOR_OFFSETS {
+        let desc = &block[offset..offset + 18];
+        // Display descriptors have a zero pixel clock; anything else is a timing
+        if desc[0] != 0 || desc[1] != 0 {
+            continue;
+        }
+        match desc[3] {
+            TAG_PRODUCT_NAME => name = descriptor_text(desc),
+            TAG_ALPHANUMERIC => alphanumeric.extend(descriptor_text(desc)),
+            _ => {}
+        }
+    }
+
+    let texts: Vec<&str> = name
+        .iter()
+        .chain(alphanumeric.iter())
+        .map(String::as_str)
+        .collect();
+    let technology = classify(&manufacturer, &texts);
+
+    // Laptop panels usually leave the name descriptor empty and put the
+    // part number in the last alphanumeric string.
+    let model = name.or_else(|| alphanumeric.last().cloned());
+
+    Some(PanelInfo {
+        connector: connector.to_string(),
+        manufacturer,
+        product_code,
+        model,
+        technology,
+    })
+}
+
+/// Decode the big-endian, 5-bits-per-letter PNP ID at bytes 8-9.
+fn decode_manufacturer(hi: u8, lo: u8) -> Option<String> {
+    let raw = u16::from_be_bytes([hi, lo]);
+    let letters = [(raw >> 10) & 0x1F, (raw >> 5) & 0x1F, raw & 0x1F];
+    letters
+        .iter()
+        .map(|&c| {
+            if (1..=26).contains(&c) {
+                Some((b'A' + c as u8 - 1) as char)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Text payload of a display descriptor: 13 bytes, newline-terminated,
+/// space padded.
+fn descriptor_text(desc: &[u8]) -> Option<String> {
+    let payload = &desc[5..18];
+    let end = payload
+        .iter()
+        .position(|&b| b == b'\n')
+        .unwrap_or(payload.len());
+    let text: String = payload[..end]
+        .iter()
+        .filter(|b| b.is_ascii_graphic() || **b == b' ')
+        .map(|&b| b as char)
+        .collect();
+    let text = text.trim().to_string();
+    if text.is_empty() { None } else { Some(text) }
+}
+
+fn classify(manufacturer: &str, texts: &[&str]) -> PanelTechnology {
+    if texts
+        .iter()
+        .any(|t| t.to_ascii_uppercase().contains("OLED"))
+    {
+        return PanelTechnology::Oled;
+    }
+    for (vendor, prefix, tech) in KNOWN_PANELS {
+        if manufacturer == *vendor && texts.iter().any(|t| t.starts_with(prefix)) {
+            return *tech;
+        }
+    }
+    PanelTechnology::Unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Build a base EDID block with the given PNP bytes, product code and
+    /// display descriptors, then fix up the checksum.
+    fn edid_block(pnp: [u8; 2], product: u16, descriptors: &[(u8, &str)]) -> Vec<u8> {
+        let mut b = vec![0u8; EDID_BLOCK_LEN];
+        b[..8].copy_from_slice(&EDID_HEADER);
+        b[8] = pnp[0];
+        b[9] = pnp[1];
+        b[10..12].copy_from_slice(&product.to_le_bytes());
+        b[18] = 1; // EDID 1.4
+        b[19] = 4;
+        b[20] = 0xA5; // digital, 8 bpc, DisplayPort
+
+        // First descriptor slot holds the preferred detailed timing
+        b[54] = 0xC0;
+        b[55] = 0xD2;
+
+        for (i, (tag, text)) in descriptors.iter().enumerate() {
+            let off = DESCRIPTOR_OFFSETS[i + 1];
+            b[off + 3] = *tag;
+            let mut payload = [b' '; 13];
+            let bytes = text.as_bytes();
+            payload[..bytes.len()].copy_from_slice(bytes);
+            if bytes.len() < 13 {
+                payload[bytes.len()] = b'\n';
+            }
+            b[off + 5..off + 18].copy_from_slice(&payload);
+        }
+
+        let sum = b[..127].iter().fold(0u8, |acc, x| acc.wrapping_add(*x));
+        b[127] = 0u8.wrapping_sub(sum);
+        b
+    }
+
+    const BOE: [u8; 2] = [0x09, 0xE5];
+    const SDC: [u8; 2] = [0x4C, 0x83];
+
+    #[test]
+    fn test_decode_manufacturer() {
+        assert_eq!(decode_manufacturer(0x09, 0xE5).as_deref(), Some("BOE"));
+        assert_eq!(decode_manufacturer(0x4C, 0x83).as_deref(), Some("SDC"));
+        assert_eq!(decode_manufacturer(0x30, 0xE4).as_deref(), Some("LGD"));
+        // Letter index 0 is reserved
+        assert_eq!(decode_manufacturer(0x00, 0x00), None);
+    }
+
+    #[test]
+    fn test_framework16_stock_panel_is_lcd() {
+        let edid = edid_block(
+            BOE,
+            0x0BC9,
+            &[
+                (TAG_ALPHANUMERIC, "BOE CQ"),
+                (TAG_ALPHANUMERIC, "NE160QDM-NZ6"),
+            ],
+        );
+        let panel = parse_edid("card1-eDP-1", &edid).unwrap();
+        assert_eq!(panel.connector, "card1-eDP-1");
+        assert_eq!(panel.manufacturer, "BOE");
+        assert_eq!(panel.product_code, 0x0BC9);
+        assert_eq!(panel.model.as_deref(), Some("NE160QDM-NZ6"));
+        assert_eq!(panel.technology, PanelTechnology::Lcd);
+    }
+
+    #[test]
+    fn test_samsung_atna_panel_is_oled() {
+        let edid = edid_block(
+            SDC,
+            0x4190,
+            &[
+                (TAG_ALPHANUMERIC, "Samsung"),
+                (TAG_ALPHANUMERIC, "ATNA40YK20-0 "),
+            ],
+        );
+        let panel = parse_edid("card0-eDP-1", &edid).unwrap();
+        assert_eq!(panel.manufacturer, "SDC");
+        assert_eq!(panel.model.as_deref(), Some("ATNA40YK20-0"));
+        assert_eq!(panel.technology, PanelTechnology::Oled);
+    }
+
+    #[test]
+    fn test_oled_string_in_descriptor() {
+        let edid = edid_block([0x30, 0xE4], 0x0001, &[(TAG_PRODUCT_NAME, "LG OLED 13")]);
+        let panel = parse_edid("card0-eDP-1", &edid).unwrap();
+        assert_eq!(panel.manufacturer, "LGD");
+        assert_eq!(panel.model.as_deref(), Some("LG OLED 13"));
+        assert_eq!(panel.technology, PanelTechnology::Oled);
+    }
+
+    #[test]
+    fn test_product_name_preferred_over_alphanumeric() {
+        let edid = edid_block(
+            BOE,
+            0x0001,
+            &[
+                (TAG_ALPHANUMERIC, "NE135A1M-NY1"),
+                (TAG_PRODUCT_NAME, "Panel"),
+            ],
+        );
+        let panel = parse_edid("card0-eDP-1", &edid).unwrap();
+        assert_eq!(panel.model.as_deref(), Some("Panel"));
+        // Table lookup still sees the alphanumeric part number
+        assert_eq!(panel.technology, PanelTechnology::Lcd);
+    }
+
+    #[test]
+    fn test_unknown_panel() {
+        let edid = edid_block([0x30, 0xE4], 0x0607, &[(TAG_ALPHANUMERIC, "LP140WF9")]);
+        let panel = parse_edid("card0-eDP-1", &edid).unwrap();
+        assert_eq!(panel.technology, PanelTechnology::Unknown);
+    }
+
+    #[test]
+    fn test_known_prefix_requires_matching_vendor() {
+        // ATNA from a non-Samsung vendor shouldn't hit the table
+        let edid = edid_block(BOE, 0x0001, &[(TAG_ALPHANUMERIC, "ATNA40YK20")]);
+        let panel = parse_edid("card0-eDP-1", &edid).unwrap();
+        assert_eq!(panel.technology, PanelTechnology::Unknown);
+    }
+
+    #[test]
+    fn test_timing_descriptors_ignored() {
+        let mut edid = edid_block(BOE, 0x0001, &[]);
+        // Slot 0 is a detailed timing whose bytes would otherwise decode as text
+        edid[54 + 3] = TAG_PRODUCT_NAME;
+        edid[54 + 5..54 + 9].copy_from_slice(b"OLED");
+        let sum = edid[..127].iter().fold(0u8, |acc, x| acc.wrapping_add(*x));
+        edid[127] = 0u8.wrapping_sub(sum);
+
+        let panel = parse_edid("card0-eDP-1", &edid).unwrap();
+        assert_eq!(panel.model, None);
+        assert_eq!(panel.technology, PanelTechnology::Unknown);
+    }
+
+    #[test]
+    fn test_full_13_byte_text_without_terminator() {
+        let edid = edid_block(SDC, 0x0001, &[(TAG_ALPHANUMERIC, "ATNA33AA08-01")]);
+        let panel = parse_edid("card0-eDP-1", &edid).unwrap();
+        assert_eq!(panel.model.as_deref(), Some("ATNA33AA08-01"));
+    }
+
+    #[test]
+    fn test_extension_blocks_accepted() {
+        let mut edid = edid_block(SDC, 0x0001, &[(TAG_ALPHANUMERIC, "ATNA40YK20")]);
+        edid.extend(std::iter::repeat_n(0xAB, EDID_BLOCK_LEN));
+        assert!(parse_edid("card0-eDP-1", &edid).is_some());
+    }
+
+    #[test]
+    fn test_rejects_bad_header() {
+        let mut edid = edid_block(BOE, 0x0001, &[]);
+        edid[0] = 0x01;
+        assert!(parse_edid("card0-eDP-1", &edid).is_none());
+    }
+
+    #[test]
+    fn test_rejects_bad_checksum() {
+        let mut edid = edid_block(BOE, 0x0001, &[]);
+        edid[127] = edid[127].wrapping_add(1);
+        assert!(parse_edid("card0-eDP-1", &edid).is_none());
+    }
+
+    #[test]
+    fn test_rejects_truncated() {
+        let edid = edid_block(BOE, 0x0001, &[]);
+        assert!(parse_edid("card0-eDP-1", &edid[..100]).is_none());
+        assert!(parse_edid("card0-eDP-1", &[]).is_none());
+    }
+
+    #[test]
+    fn test_detect_skips_empty_edid_and_external_connectors() {
+        let tmp = TempDir::new().unwrap();
+        let drm = tmp.path().join("sys/class/drm");
+        // Disconnected eDP on the dGPU exposes an empty edid file
+        fs::create_dir_all(drm.join("card0-eDP-2")).unwrap();
+        fs::write(drm.join("card0-eDP-2/edid"), b"").unwrap();
+        fs::create_dir_all(drm.join("card1-DP-1")).unwrap();
+        fs::write(
+            drm.join("card1-DP-1/edid"),
+            edid_block(SDC, 0x0001, &[(TAG_ALPHANUMERIC, "ATNA40YK20")]),
+        )
+        .unwrap();
+        fs::create_dir_all(drm.join("card1-eDP-1")).unwrap();
+        fs::write(
+            drm.join("card1-eDP-1/edid"),
+            edid_block(BOE, 0x0BC9, &[(TAG_ALPHANUMERIC, "NE160QDM-NZ6")]),
+        )
+        .unwrap();
+
+        let info = DisplayInfo::detect(&SysfsRoot::new(tmp.path()));
+        let panel = info.panel.unwrap();
+        assert_eq!(panel.connector, "card1-eDP-1");
+        assert_eq!(panel.technology, PanelTechnology::Lcd);
+    }
+
+    #[test]
+    fn test_detect_active_connectors() {
+        let tmp = TempDir::new().unwrap();
+        let drm = tmp.path().join("sys/class/drm");
+        for (connector, status, enabled) in [
+            ("card1-eDP-1", "connected", "enabled"),
+            ("card1-DP-3", "connected", "enabled"),
+            ("card1-DP-4", "disconnected", "disabled"),
+            // Lid closed: still connected, switched off
+            ("card10-eDP-1", "connected", "disabled"),
+            ("card10-HDMI-A-1", "connected", "enabled"),
+        ] {
+            fs::create_dir_all(drm.join(connector)).unwrap();
+            fs::write(drm.join(connector).join("status"), format!("{}\n", status)).unwrap();
+            fs::write(
+                drm.join(connector).join("enabled"),
+                format!("{}\n", enabled),
+            )
+            .unwrap();
+        }
+
+        let info = DisplayInfo::detect(&SysfsRoot::new(tmp.path()));
+        let card1: Vec<_> = info.active_on("card1").collect();
+        assert_eq!(card1, ["card1-DP-3", "card1-eDP-1"]);
+        assert_eq!(
+            info.active_on("card10").collect::<Vec<_>>(),
+            ["card10-HDMI-A-1"]
+        );
+        assert!(is_internal("card1-eDP-1"));
+        assert!(!is_internal("card1-DP-3"));
+    }
+
+    #[test]
+    fn test_supports_abm() {
+        let mut info = DisplayInfo::default();
+        assert!(info.supports_abm());
+        info.panel = Some(PanelInfo {
+            technology: PanelTechnology::Oled,
+            ..PanelInfo::default()
+        });
+        assert!(info.is_oled());
+        assert!(!info.supports_abm());
+    }
+}
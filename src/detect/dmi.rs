@@ -1,4 +1,5 @@
 use crate::sysfs::SysfsRoot;
+use std::fmt;
 
 #[derive(Debug, Clone, Default)]
 pub struct DmiInfo {
@@ -30,6 +31,12 @@ impl DmiInfo {
         }
     }
 
+    /// `bios_version` parsed for comparison; None when absent or in a
+    /// format [`BiosVersion::parse`] doesn't know.
+    pub fn parsed_bios_version(&self) -> Option<BiosVersion> {
+        self.bios_version.as_deref().and_then(BiosVersion::parse)
+    }
+
     pub fn is_framework(&self) -> bool {
         self.board_vendor
             .as_deref()
@@ -45,3 +52,94 @@ impl DmiInfo {
                 || self.board_name.as_deref().is_some_and(|n| n.contains("16")))
     }
 }
+
+/// A `major.minor` BIOS version with an optional respin letter, as Framework
+/// ships them ("03.05", "3.05b"). Orders numerically, a respin after its base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BiosVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub respin: Option<char>,
+}
+
+impl BiosVersion {
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self {
+            major,
+            minor,
+            respin: None,
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let (major, rest) = s.trim().split_once('.')?;
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (minor, suffix) = rest.split_at(digits);
+        let mut suffix = suffix.chars();
+        let respin = match (suffix.next(), suffix.next()) {
+            (None, _) => None,
+            (Some(c), None) if c.is_ascii_alphabetic() => Some(c.to_ascii_lowercase()),
+            _ => return None,
+        };
+        if major.is_empty() || !major.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        Some(Self {
+            major: major.parse().ok()?,
+            minor: minor.parse().ok()?,
+            respin,
+        })
+    }
+}
+
+impl fmt::Display for BiosVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02}", self.major, self.minor)?;
+        if let Some(c) = self.respin {
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bios_version_parse_formats() {
+        assert_eq!(BiosVersion::parse("03.05"), Some(BiosVersion::new(3, 5)));
+        assert_eq!(BiosVersion::parse(" 3.05\n"), Some(BiosVersion::new(3, 5)));
+        assert_eq!(
+            BiosVersion::parse("3.05b"),
+            Some(BiosVersion {
+                respin: Some('b'),
+                ..BiosVersion::new(3, 5)
+            })
+        );
+        for bad in [
+            "",
+            "3",
+            "3.",
+            ".05",
+            "v3.05",
+            "3.05beta",
+            "INSYDE Corp. 1.2",
+        ] {
+            assert_eq!(BiosVersion::parse(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_bios_version_ordering() {
+        let v = |s| BiosVersion::parse(s).unwrap();
+        assert!(v("03.03") < v("3.05"));
+        assert!(v("3.05") < v("3.05b"));
+        assert!(v("3.05b") < v("3.06"));
+        assert!(v("3.10") > v("3.09"));
+        assert_eq!(v("03.05").to_string(), "3.05");
+        assert_eq!(v("3.5b").to_string(), "3.05b");
+    }
+}
@@ -8,10 +8,41 @@ pub struct GpuInfo {
     pub dpm_level: Option<String>,
     pub abm_level: Option<u32>,
     pub has_abm: bool,
+    /// Runtime ABM level of the eDP connector (`amdgpu/panel_power_savings`,
+    /// kernel 6.9+), relative to the sysfs root
+    pub panel_power_savings_path: Option<String>,
+    pub panel_power_savings: Option<u32>,
     /// Discrete GPU info (second DRM card, e.g. Framework 16 expansion bay)
     pub dgpu_card_path: Option<String>,
     pub dgpu_vendor: Option<String>,
     pub dgpu_power_state: Option<String>,
+    /// iGPU memory clock levels from `pp_dpm_mclk`, the active one starred
+    pub mclk_levels: Vec<DpmLevel>,
+}
+
+/// One level of an amdgpu DPM clock table such as `pp_dpm_mclk`
+/// ("1: 800Mhz *"), where the star marks the active one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DpmLevel {
+    pub index: u32,
+    /// e.g. "800Mhz"
+    pub clock: String,
+    pub active: bool,
+}
+
+fn parse_dpm_levels(table: &str) -> Vec<DpmLevel> {
+    table
+        .lines()
+        .filter_map(|line| {
+            let (index, rest) = line.trim().split_once(':')?;
+            let rest = rest.trim();
+            Some(DpmLevel {
+                index: index.parse().ok()?,
+                clock: rest.trim_end_matches('*').trim_end().to_string(),
+                active: rest.ends_with('*'),
+            })
+        })
+        .collect()
 }
 
 impl GpuInfo {
@@ -73,6 +104,11 @@ impl GpuInfo {
                 info.dpm_level = sysfs
                     .read_optional(format!("{}/power_dpm_force_performance_level", card_path))
                     .unwrap_or(None);
+                info.mclk_levels = sysfs
+                    .read_optional(format!("{}/pp_dpm_mclk", card_path))
+                    .unwrap_or(None)
+                    .map(|table| parse_dpm_levels(&table))
+                    .unwrap_or_default();
             }
 
             // Check kernel cmdline for amdgpu.abmlevel
@@ -95,6 +131,14 @@ impl GpuInfo {
                 // ABM is available if the module parameter file exists
                 info.has_abm = true;
             }
+
+            if let Some(path) = find_panel_power_savings(sysfs) {
+                info.panel_power_savings = sysfs
+                    .read_optional(&path)
+                    .unwrap_or(None)
+                    .and_then(|v| v.parse().ok());
+                info.panel_power_savings_path = Some(path);
+            }
         }
 
         info
@@ -103,4 +147,74 @@ impl GpuInfo {
     pub fn is_amd(&self) -> bool {
         self.vendor.as_deref() == Some("0x1002") || self.driver.as_deref() == Some("amdgpu")
     }
+
+    /// DRM name of the iGPU, e.g. "card1", which its connectors are
+    /// prefixed with.
+    pub fn card_name(&self) -> Option<&str> {
+        self.card_path
+            .as_deref()?
+            .strip_prefix("sys/class/drm/")?
+            .strip_suffix("/device")
+    }
+
+    /// The active memory clock, when it is the highest of several levels.
+    pub fn mclk_pinned_at_max(&self) -> Option<&DpmLevel> {
+        let max = self.mclk_levels.iter().max_by_key(|row| row.index)?;
+        (self.mclk_levels.len() > 1 && max.active).then_some(max)
+    }
+}
+
+/// `panel_power_savings` of the first eDP connector that has one.
+fn find_panel_power_savings(sysfs: &SysfsRoot) -> Option<String> {
+    let mut connectors: Vec<String> = sysfs
+        .list_dir("sys/class/drm")
+        .ok()?
+        .into_iter()
+        .filter(|entry| entry.contains("-eDP-"))
+        .collect();
+    connectors.sort();
+    connectors
+        .iter()
+        .map(|c| format!("sys/class/drm/{}/amdgpu/panel_power_savings", c))
+        .find(|path| sysfs.exists(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dpm_clock_table() {
+        let rows = parse_dpm_levels("0: 400Mhz \n1: 800Mhz \n2: 1000Mhz *\n");
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[2].clock, "1000Mhz");
+        assert!(rows[2].active);
+        assert!(!rows[0].active);
+
+        let gpu = GpuInfo {
+            mclk_levels: rows,
+            ..Default::default()
+        };
+        assert_eq!(gpu.mclk_pinned_at_max().unwrap().index, 2);
+        let idle = GpuInfo {
+            mclk_levels: parse_dpm_levels("0: 400Mhz *\n1: 800Mhz\n2: 1000Mhz\n"),
+            ..Default::default()
+        };
+        assert!(idle.mclk_pinned_at_max().is_none());
+        // A single level can't be pinned
+        let single = GpuInfo {
+            mclk_levels: parse_dpm_levels("0: 1000Mhz *\n"),
+            ..Default::default()
+        };
+        assert!(single.mclk_pinned_at_max().is_none());
+    }
+
+    #[test]
+    fn test_card_name() {
+        let gpu = GpuInfo {
+            card_path: Some("sys/class/drm/card1/device".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(gpu.card_name(), Some("card1"));
+    }
 }
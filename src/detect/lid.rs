@@ -0,0 +1,85 @@
+use crate::sysfs::SysfsRoot;
+
+/// Where the ACPI button driver reports each lid switch.
+const LID_BASE: &str = "proc/acpi/button/lid";
+
+/// Lid switch state from the ACPI button driver.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LidInfo {
+    pub found: bool,
+    /// Any lid reports closed. Unknown states count as open, so a broken
+    /// switch never holds the machine in the closed-lid layer.
+    pub closed: bool,
+}
+
+impl LidInfo {
+    pub fn detect(sysfs: &SysfsRoot) -> Self {
+        let mut info = Self::default();
+        let Ok(mut entries) = sysfs.list_dir(LID_BASE) else {
+            return info;
+        };
+        entries.sort();
+        for entry in &entries {
+            let path = format!("{}/{}/state", LID_BASE, entry);
+            let Some(content) = sysfs.read_optional(&path).unwrap_or(None) else {
+                continue;
+            };
+            info.found = true;
+            info.closed |= parse_state(&content) == Some(true);
+        }
+        info
+    }
+}
+
+/// Closed-ness from a state file, e.g. `state:      closed`.
+fn parse_state(content: &str) -> Option<bool> {
+    let value = content.trim().strip_prefix("state:")?.trim();
+    match value {
+        "closed" => Some(true),
+        "open" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn lid(tmp: &TempDir, name: &str, state: &str) {
+        let dir = tmp.path().join(LID_BASE).join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("state"), state).unwrap();
+    }
+
+    #[test]
+    fn test_parse_state() {
+        assert_eq!(parse_state("state:      closed\n"), Some(true));
+        assert_eq!(parse_state("state:      open\n"), Some(false));
+        assert_eq!(parse_state("state:      unsupported\n"), None);
+        assert_eq!(parse_state(""), None);
+    }
+
+    #[test]
+    fn test_detect() {
+        let tmp = TempDir::new().unwrap();
+        let sysfs = SysfsRoot::new(tmp.path());
+        assert_eq!(LidInfo::detect(&sysfs), LidInfo::default());
+
+        lid(&tmp, "LID0", "state:      open\n");
+        assert_eq!(
+            LidInfo::detect(&sysfs),
+            LidInfo {
+                found: true,
+                closed: false
+            }
+        );
+
+        lid(&tmp, "LID0", "state:      closed\n");
+        assert!(LidInfo::detect(&sysfs).closed);
+
+        // A second switch with an unreadable state doesn't mask the first
+        lid(&tmp, "LID1", "state:      unsupported\n");
+        assert!(LidInfo::detect(&sysfs).closed);
+    }
+}
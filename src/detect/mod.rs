@@ -1,13 +1,22 @@
 pub mod ac;
+pub mod acpi_wakeup;
 pub mod battery;
 pub mod cpu;
+pub mod display;
 pub mod dmi;
 pub mod gpu;
+pub mod lid;
 pub mod network;
 pub mod pci;
 pub mod platform;
+pub mod power_supply;
+pub mod powercap;
+pub mod typec;
+pub mod usb;
+pub mod usb_topology;
 
-use crate::sysfs::SysfsRoot;
+use crate::sysfs::{RawValues, SysfsRoot};
+use std::cell::OnceCell;
 
 /// All detected hardware information.
 #[derive(Debug, Clone)]
@@ -15,29 +24,31 @@ pub struct HardwareInfo {
     pub dmi: dmi::DmiInfo,
     pub cpu: cpu::CpuInfo,
     pub gpu: gpu::GpuInfo,
+    pub display: display::DisplayInfo,
     pub battery: battery::BatteryInfo,
     pub ac: ac::AcInfo,
     pub pci: pci::PciInfo,
     pub network: network::NetworkInfo,
     pub platform: platform::PlatformInfo,
+    pub powercap: powercap::PowercapInfo,
+    pub usb: usb::UsbInfo,
+    pub typec: typec::TypecInfo,
     pub kernel_cmdline: String,
+    /// What was read through the detecting root, for findings to cite as
+    /// evidence
+    pub raw: RawValues,
 }
 
 impl HardwareInfo {
+    /// Detect every section. Same as [`HardwareInfo::detect_all`].
     pub fn detect(sysfs: &SysfsRoot) -> Self {
-        let kernel_cmdline = sysfs.read("proc/cmdline").unwrap_or_default();
+        Self::detect_all(sysfs)
+    }
 
-        Self {
-            dmi: dmi::DmiInfo::detect(sysfs),
-            cpu: cpu::CpuInfo::detect(sysfs),
-            gpu: gpu::GpuInfo::detect(sysfs),
-            battery: battery::BatteryInfo::detect(sysfs),
-            ac: ac::AcInfo::detect(sysfs),
-            pci: pci::PciInfo::detect(sysfs),
-            network: network::NetworkInfo::detect(sysfs),
-            platform: platform::PlatformInfo::detect(sysfs),
-            kernel_cmdline,
-        }
+    /// Detect every section eagerly. Commands that only need part of the
+    /// hardware picture should use [`LazyHardware`] instead.
+    pub fn detect_all(sysfs: &SysfsRoot) -> Self {
+        LazyHardware::new(sysfs).into_full()
     }
 
     pub fn has_kernel_param(&self, param: &str) -> bool {
@@ -54,3 +65,189 @@ impl HardwareInfo {
             .map(|p| p[prefix.len()..].to_string())
     }
 }
+
+/// Hardware detection on demand: each section is read from sysfs the first
+/// time it is asked for. Sections are independent of each other, so a
+/// command pays only for the ones it touches (`bop auto` reads `ac` and
+/// stops there when nothing needs to change). [`LazyHardware::into_full`]
+/// fills in the rest, reusing what was already read.
+pub struct LazyHardware<'a> {
+    sysfs: &'a SysfsRoot,
+    dmi: OnceCell<dmi::DmiInfo>,
+    cpu: OnceCell<cpu::CpuInfo>,
+    gpu: OnceCell<gpu::GpuInfo>,
+    display: OnceCell<display::DisplayInfo>,
+    battery: OnceCell<battery::BatteryInfo>,
+    ac: OnceCell<ac::AcInfo>,
+    pci: OnceCell<pci::PciInfo>,
+    network: OnceCell<network::NetworkInfo>,
+    platform: OnceCell<platform::PlatformInfo>,
+    wakeup_counters: OnceCell<Vec<platform::WakeupCounter>>,
+    powercap: OnceCell<powercap::PowercapInfo>,
+    usb: OnceCell<usb::UsbInfo>,
+    typec: OnceCell<typec::TypecInfo>,
+    kernel_cmdline: OnceCell<String>,
+}
+
+impl<'a> LazyHardware<'a> {
+    pub fn new(sysfs: &'a SysfsRoot) -> Self {
+        Self {
+            sysfs,
+            dmi: OnceCell::new(),
+            cpu: OnceCell::new(),
+            gpu: OnceCell::new(),
+            display: OnceCell::new(),
+            battery: OnceCell::new(),
+            ac: OnceCell::new(),
+            pci: OnceCell::new(),
+            network: OnceCell::new(),
+            platform: OnceCell::new(),
+            wakeup_counters: OnceCell::new(),
+            powercap: OnceCell::new(),
+            usb: OnceCell::new(),
+            typec: OnceCell::new(),
+            kernel_cmdline: OnceCell::new(),
+        }
+    }
+
+    /// The root the sections are read from, for whatever isn't one.
+    pub fn sysfs(&self) -> &'a SysfsRoot {
+        self.sysfs
+    }
+
+    pub fn dmi(&self) -> &dmi::DmiInfo {
+        self.dmi.get_or_init(|| dmi::DmiInfo::detect(self.sysfs))
+    }
+
+    pub fn cpu(&self) -> &cpu::CpuInfo {
+        self.cpu.get_or_init(|| cpu::CpuInfo::detect(self.sysfs))
+    }
+
+    pub fn gpu(&self) -> &gpu::GpuInfo {
+        self.gpu.get_or_init(|| gpu::GpuInfo::detect(self.sysfs))
+    }
+
+    pub fn display(&self) -> &display::DisplayInfo {
+        self.display
+            .get_or_init(|| display::DisplayInfo::detect(self.sysfs))
+    }
+
+    pub fn battery(&self) -> &battery::BatteryInfo {
+        self.battery
+            .get_or_init(|| battery::BatteryInfo::detect(self.sysfs))
+    }
+
+    pub fn ac(&self) -> &ac::AcInfo {
+        self.ac.get_or_init(|| ac::AcInfo::detect(self.sysfs))
+    }
+
+    /// Walks every PCI device; one of the expensive sections.
+    pub fn pci(&self) -> &pci::PciInfo {
+        self.pci.get_or_init(|| pci::PciInfo::detect(self.sysfs))
+    }
+
+    pub fn network(&self) -> &network::NetworkInfo {
+        self.network
+            .get_or_init(|| network::NetworkInfo::detect(self.sysfs))
+    }
+
+    /// Depends on [`LazyHardware::wakeup_counters`].
+    pub fn platform(&self) -> &platform::PlatformInfo {
+        self.platform.get_or_init(|| {
+            platform::PlatformInfo::detect_with_counters(
+                self.sysfs,
+                self.wakeup_counters().to_vec(),
+            )
+        })
+    }
+
+    /// Kernel wakeup sources (/sys/class/wakeup), without the rest of the
+    /// platform section.
+    pub fn wakeup_counters(&self) -> &[platform::WakeupCounter] {
+        self.wakeup_counters
+            .get_or_init(|| platform::read_wakeup_counters(self.sysfs))
+    }
+
+    pub fn powercap(&self) -> &powercap::PowercapInfo {
+        self.powercap
+            .get_or_init(|| powercap::PowercapInfo::detect(self.sysfs))
+    }
+
+    /// Walks every USB device; one of the expensive sections.
+    pub fn usb(&self) -> &usb::UsbInfo {
+        self.usb.get_or_init(|| usb::UsbInfo::detect(self.sysfs))
+    }
+
+    pub fn typec(&self) -> &typec::TypecInfo {
+        self.typec
+            .get_or_init(|| typec::TypecInfo::detect(self.sysfs))
+    }
+
+    pub fn kernel_cmdline(&self) -> &str {
+        self.kernel_cmdline
+            .get_or_init(|| self.sysfs.read("proc/cmdline").unwrap_or_default())
+    }
+
+    /// Detect whatever is still missing and return the full picture.
+    pub fn into_full(self) -> HardwareInfo {
+        let sysfs = self.sysfs;
+        HardwareInfo {
+            dmi: self
+                .dmi
+                .into_inner()
+                .unwrap_or_else(|| dmi::DmiInfo::detect(sysfs)),
+            cpu: self
+                .cpu
+                .into_inner()
+                .unwrap_or_else(|| cpu::CpuInfo::detect(sysfs)),
+            gpu: self
+                .gpu
+                .into_inner()
+                .unwrap_or_else(|| gpu::GpuInfo::detect(sysfs)),
+            display: self
+                .display
+                .into_inner()
+                .unwrap_or_else(|| display::DisplayInfo::detect(sysfs)),
+            battery: self
+                .battery
+                .into_inner()
+                .unwrap_or_else(|| battery::BatteryInfo::detect(sysfs)),
+            ac: self
+                .ac
+                .into_inner()
+                .unwrap_or_else(|| ac::AcInfo::detect(sysfs)),
+            pci: self
+                .pci
+                .into_inner()
+                .unwrap_or_else(|| pci::PciInfo::detect(sysfs)),
+            network: self
+                .network
+                .into_inner()
+                .unwrap_or_else(|| network::NetworkInfo::detect(sysfs)),
+            platform: self.platform.into_inner().unwrap_or_else(|| {
+                let counters = self
+                    .wakeup_counters
+                    .into_inner()
+                    .unwrap_or_else(|| platform::read_wakeup_counters(sysfs));
+                platform::PlatformInfo::detect_with_counters(sysfs, counters)
+            }),
+            powercap: self
+                .powercap
+                .into_inner()
+                .unwrap_or_else(|| powercap::PowercapInfo::detect(sysfs)),
+            usb: self
+                .usb
+                .into_inner()
+                .unwrap_or_else(|| usb::UsbInfo::detect(sysfs)),
+            typec: self
+                .typec
+                .into_inner()
+                .unwrap_or_else(|| typec::TypecInfo::detect(sysfs)),
+            kernel_cmdline: self
+                .kernel_cmdline
+                .into_inner()
+                .unwrap_or_else(|| sysfs.read("proc/cmdline").unwrap_or_default()),
+            raw: sysfs.raw_values(),
+        }
+    }
+}
@@ -1,4 +1,8 @@
 use crate::sysfs::SysfsRoot;
+use std::time::Duration;
+
+/// `iw` answers from the kernel immediately; a hang means it's stuck.
+const IW_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Clone, Default)]
 pub struct NetworkInfo {
@@ -39,9 +43,202 @@ impl NetworkInfo {
         info
     }
 
+    /// SSID the WiFi interface is connected to, from `iw dev <iface> link`.
+    /// None when not connected, or when iw is missing or times out.
+    pub fn current_ssid(&self) -> Option<String> {
+        let iface = self.wifi_interface.as_deref()?;
+        let output = crate::cmd::Cmd::new("iw")
+            .args(["dev", iface, "link"])
+            .timeout(IW_TIMEOUT)
+            .output()
+            .ok()?;
+        parse_iw_link_ssid(&String::from_utf8_lossy(&output.stdout))
+    }
+
     pub fn is_mediatek(&self) -> bool {
         self.wifi_driver
             .as_deref()
             .is_some_and(|d| d.starts_with("mt7"))
     }
 }
+
+/// Byte counters from `sys/class/net/<iface>/statistics`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NetCounters {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+impl NetCounters {
+    pub fn read(sysfs: &SysfsRoot, iface: &str) -> Option<Self> {
+        let stats = format!("sys/class/net/{}/statistics", iface);
+        Some(Self {
+            rx_bytes: sysfs.read_parse(format!("{}/rx_bytes", stats)).ok()?,
+            tx_bytes: sysfs.read_parse(format!("{}/tx_bytes", stats)).ok()?,
+        })
+    }
+}
+
+/// Bytes between two readings of one counter. Some drivers keep 32-bit
+/// counters, so a drop from a value that fits in 32 bits is a wrap; a drop
+/// from anything larger is a reset (driver reload), and everything the
+/// counter holds came since.
+fn counter_delta(before: u64, after: u64) -> u64 {
+    if after >= before {
+        after - before
+    } else if before <= u64::from(u32::MAX) {
+        (u64::from(u32::MAX) - before) + after + 1
+    } else {
+        after
+    }
+}
+
+/// Combined rx+tx bytes per second between two samples `elapsed` apart.
+pub fn throughput(before: NetCounters, after: NetCounters, elapsed: Duration) -> u64 {
+    let bytes = counter_delta(before.rx_bytes, after.rx_bytes)
+        .saturating_add(counter_delta(before.tx_bytes, after.tx_bytes));
+    let millis = elapsed.as_millis().max(1);
+    (u128::from(bytes) * 1000 / millis).min(u128::from(u64::MAX)) as u64
+}
+
+/// Sample `iface`'s counters twice, `window` apart, and return the bytes
+/// per second in between. None when the interface has no statistics.
+pub fn sample_throughput(sysfs: &SysfsRoot, iface: &str, window: Duration) -> Option<u64> {
+    let before = NetCounters::read(sysfs, iface)?;
+    let started = std::time::Instant::now();
+    std::thread::sleep(window);
+    let after = NetCounters::read(sysfs, iface)?;
+    Some(throughput(before, after, started.elapsed()))
+}
+
+/// The SSID from `iw dev <iface> link` output: the `SSID:` line under
+/// "Connected to ...". iw escapes backslashes, unprintable bytes and
+/// leading/trailing spaces as `\xNN`; these are decoded. Returns None for
+/// "Not connected." and Some("") for a hidden network without an SSID.
+pub fn parse_iw_link_ssid(output: &str) -> Option<String> {
+    if !output.trim_start().starts_with("Connected to") {
+        return None;
+    }
+    let line = output
+        .lines()
+        .map(str::trim_start)
+        .find(|l| l.starts_with("SSID:"))?;
+    let raw = line["SSID:".len()..].strip_prefix(' ').unwrap_or_default();
+    Some(unescape_ssid(raw))
+}
+
+fn unescape_ssid(raw: &str) -> String {
+    let mut bytes = Vec::with_capacity(raw.len());
+    let mut rest = raw.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'\\'
+            && tail.first() == Some(&b'x')
+            && let Some(hex) = tail.get(1..3)
+            && let Ok(byte) = u8::from_str_radix(&String::from_utf8_lossy(hex), 16)
+        {
+            bytes.push(byte);
+            rest = &tail[3..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counters(rx_bytes: u64, tx_bytes: u64) -> NetCounters {
+        NetCounters { rx_bytes, tx_bytes }
+    }
+
+    #[test]
+    fn test_throughput_from_counter_sequence() {
+        let second = Duration::from_secs(1);
+        let samples = [
+            counters(1_000_000, 50_000),
+            counters(9_000_000, 150_000),
+            counters(9_000_000, 150_000),
+        ];
+        assert_eq!(throughput(samples[0], samples[1], second), 8_100_000);
+        assert_eq!(throughput(samples[1], samples[2], second), 0);
+        // Half a second: twice the rate
+        assert_eq!(
+            throughput(samples[0], samples[1], Duration::from_millis(500)),
+            16_200_000
+        );
+        // A zero window doesn't divide by zero
+        assert_eq!(
+            throughput(samples[0], samples[1], Duration::ZERO),
+            8_100_000_000
+        );
+    }
+
+    #[test]
+    fn test_throughput_across_wrap_and_reset() {
+        let second = Duration::from_secs(1);
+        // 32-bit counter wrapping: 100 bytes to roll over to 0, then 400
+        let before = counters(u64::from(u32::MAX) - 99, 0);
+        assert_eq!(throughput(before, counters(400, 0), second), 500);
+
+        // 64-bit counter dropping: the driver was reloaded, count from zero
+        let before = counters(10_000_000_000, 20_000_000_000);
+        assert_eq!(throughput(before, counters(4_000, 1_000), second), 5_000);
+    }
+
+    #[test]
+    fn test_read_counters() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let stats = tmp.path().join("sys/class/net/wlan0/statistics");
+        std::fs::create_dir_all(&stats).unwrap();
+        std::fs::write(stats.join("rx_bytes"), "123456\n").unwrap();
+        std::fs::write(stats.join("tx_bytes"), "7890\n").unwrap();
+        let sysfs = SysfsRoot::new(tmp.path());
+
+        assert_eq!(
+            NetCounters::read(&sysfs, "wlan0"),
+            Some(counters(123_456, 7_890))
+        );
+        assert_eq!(NetCounters::read(&sysfs, "wlan1"), None);
+        assert_eq!(
+            sample_throughput(&sysfs, "wlan0", Duration::from_millis(1)),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_parse_iw_link_connected() {
+        let output = "\
+Connected to aa:bb:cc:dd:ee:ff (on wlan0)
+\tSSID: OfficeNet
+\tfreq: 5180
+\tRX: 1234 bytes (10 packets)
+\tsignal: -52 dBm
+\trx bitrate: 866.7 MBit/s
+";
+        assert_eq!(parse_iw_link_ssid(output).as_deref(), Some("OfficeNet"));
+    }
+
+    #[test]
+    fn test_parse_iw_link_not_connected() {
+        assert_eq!(parse_iw_link_ssid("Not connected.\n"), None);
+        assert_eq!(parse_iw_link_ssid(""), None);
+    }
+
+    #[test]
+    fn test_parse_iw_link_escaped_and_hidden_ssids() {
+        // Inner spaces are printed as-is; a trailing space, backslash and
+        // non-ASCII bytes are escaped
+        let output =
+            "Connected to aa:bb:cc:dd:ee:ff (on wlan0)\n\tSSID: Caf\\xc3\\xa9 Wi\\x5cFi\\x20\n";
+        assert_eq!(parse_iw_link_ssid(output).as_deref(), Some("Café Wi\\Fi "));
+
+        // Hidden network: iw prints an empty SSID
+        let hidden = "Connected to aa:bb:cc:dd:ee:ff (on wlan0)\n\tSSID: \n\tfreq: 2412\n";
+        assert_eq!(parse_iw_link_ssid(hidden).as_deref(), Some(""));
+        let hidden = "Connected to aa:bb:cc:dd:ee:ff (on wlan0)\n\tSSID:\n";
+        assert_eq!(parse_iw_link_ssid(hidden).as_deref(), Some(""));
+    }
+}
@@ -9,13 +9,113 @@ pub struct PciDevice {
     pub driver: Option<String>,
     pub runtime_pm: Option<String>,
     pub runtime_status: Option<String>,
+    /// "1" on the display controller the firmware booted on; a "0" here
+    /// marks a discrete GPU
+    pub boot_vga: Option<bool>,
+    /// amdgpu's load estimate, only read for display controllers
+    pub gpu_busy_percent: Option<u32>,
+    pub link: PcieLink,
+    /// Bridges between the device and its root complex, nearest first
+    pub upstream_ports: Vec<String>,
 }
 
+/// Negotiated and maximum PCIe link of a device. Speeds are kept as the
+/// kernel prints them, e.g. "16.0 GT/s PCIe".
+#[derive(Debug, Clone, Default)]
+pub struct PcieLink {
+    pub current_speed: Option<String>,
+    pub current_width: Option<u32>,
+    pub max_speed: Option<String>,
+    pub max_width: Option<u32>,
+}
+
+impl PcieLink {
+    pub fn current_gen(&self) -> Option<u8> {
+        self.current_speed.as_deref().and_then(pcie_generation)
+    }
+
+    pub fn max_gen(&self) -> Option<u8> {
+        self.max_speed.as_deref().and_then(pcie_generation)
+    }
+
+    /// "Gen4 x8", or None while the link is down.
+    pub fn current_summary(&self) -> Option<String> {
+        link_summary(self.current_gen()?, self.current_width)
+    }
+
+    pub fn max_summary(&self) -> Option<String> {
+        link_summary(self.max_gen()?, self.max_width)
+    }
+}
+
+fn link_summary(generation: u8, width: Option<u32>) -> Option<String> {
+    Some(match width {
+        Some(w) => format!("Gen{} x{}", generation, w),
+        None => format!("Gen{}", generation),
+    })
+}
+
+/// PCIe generation from a link speed string ("8.0 GT/s PCIe" -> 3).
+/// "Unknown" (link down, device in D3cold) gives None.
+pub fn pcie_generation(speed: &str) -> Option<u8> {
+    let rate: f64 = speed.split_whitespace().next()?.parse().ok()?;
+    [2.5, 5.0, 8.0, 16.0, 32.0, 64.0]
+        .iter()
+        .position(|r| (rate - r).abs() < 0.01)
+        .map(|i| i as u8 + 1)
+}
+
+/// A discrete GPU is considered idle below this load.
+const IDLE_BUSY_PERCENT: u32 = 5;
+
+impl PciDevice {
+    /// PCI class 0x03xxxx: VGA, 3D and other display controllers.
+    pub fn is_display_controller(&self) -> bool {
+        self.class.as_deref().is_some_and(|c| c.starts_with("0x03"))
+    }
+
+    pub fn is_discrete_gpu(&self) -> bool {
+        self.is_display_controller() && self.boot_vga == Some(false)
+    }
+
+    /// Awake but not busy: runtime-suspended GPUs have no link to speak of,
+    /// and an unknown load counts as idle.
+    pub fn is_idle_gpu(&self) -> bool {
+        self.runtime_status.as_deref() != Some("suspended")
+            && self
+                .gpu_busy_percent
+                .is_none_or(|busy| busy < IDLE_BUSY_PERCENT)
+    }
+
+    /// An idle discrete GPU whose link trained above the lowest speed.
+    pub fn holds_idle_link_above_minimum(&self) -> bool {
+        self.is_discrete_gpu()
+            && self.is_idle_gpu()
+            && self.link.current_gen().is_some_and(|g| g > 1)
+    }
+}
+
+/// The kernel's PCIe bandwidth controller (CONFIG_PCIE_THERMAL) registers a
+/// cooling device per downstream port. State 0 is the fastest speed the
+/// link supports; `max_state` pins it at 2.5 GT/s.
+#[derive(Debug, Clone)]
+pub struct LinkSpeedControl {
+    /// Address of the port whose link is controlled
+    pub port: String,
+    /// sysfs-relative cooling device directory
+    pub path: String,
+    pub cur_state: Option<u32>,
+    pub max_state: Option<u32>,
+}
+
+const LINK_SPEED_COOLING_PREFIX: &str = "PCIe_Port_Link_Speed_";
+
 #[derive(Debug, Clone, Default)]
 pub struct PciInfo {
     pub devices: Vec<PciDevice>,
     pub aspm_policy: Option<String>,
     pub aspm_policies_available: Vec<String>,
+    pub link_speed_controls: Vec<LinkSpeedControl>,
 }
 
 impl PciInfo {
@@ -67,6 +167,26 @@ impl PciInfo {
                     .ok()
                     .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()));
 
+                let read = |file: &str| {
+                    sysfs
+                        .read_optional(format!("{}/{}", base, file))
+                        .unwrap_or(None)
+                };
+                let link = PcieLink {
+                    current_speed: read("current_link_speed"),
+                    current_width: read("current_link_width").and_then(|v| v.parse().ok()),
+                    max_speed: read("max_link_speed"),
+                    max_width: read("max_link_width").and_then(|v| v.parse().ok()),
+                };
+                let is_display = class.as_deref().is_some_and(|c| c.starts_with("0x03"));
+                let boot_vga = read("boot_vga").map(|v| v == "1");
+                let gpu_busy_percent = if is_display {
+                    read("gpu_busy_percent").and_then(|v| v.parse().ok())
+                } else {
+                    None
+                };
+                let upstream_ports = upstream_ports(sysfs, &base, &addr);
+
                 info.devices.push(PciDevice {
                     address: addr,
                     class,
@@ -75,10 +195,16 @@ impl PciInfo {
                     driver,
                     runtime_pm,
                     runtime_status,
+                    boot_vga,
+                    gpu_busy_percent,
+                    link,
+                    upstream_ports,
                 });
             }
         }
 
+        info.link_speed_controls = detect_link_speed_controls(sysfs);
+
         info
     }
 
@@ -89,4 +215,175 @@ impl PciInfo {
             .filter(|d| d.runtime_pm.as_deref() != Some("auto"))
             .collect()
     }
+
+    pub fn discrete_gpu(&self) -> Option<&PciDevice> {
+        self.devices.iter().find(|d| d.is_discrete_gpu())
+    }
+
+    /// Bandwidth control for the nearest upstream port of `dev` that has one.
+    pub fn link_speed_control(&self, dev: &PciDevice) -> Option<&LinkSpeedControl> {
+        dev.upstream_ports
+            .iter()
+            .find_map(|port| self.link_speed_controls.iter().find(|c| &c.port == port))
+    }
+}
+
+/// Bridges above a device, read from where its /sys/bus/pci/devices link
+/// points: ../../../devices/pci0000:00/0000:00:01.1/0000:01:00.0 gives
+/// ["0000:00:01.1"] for 0000:01:00.0. Empty when the entry isn't a link,
+/// as in materialized snapshots.
+fn upstream_ports(sysfs: &SysfsRoot, base: &str, addr: &str) -> Vec<String> {
+    let Ok(target) = std::fs::read_link(sysfs.path(base)) else {
+        return Vec::new();
+    };
+    let mut ports: Vec<String> = target
+        .components()
+        .filter_map(|c| c.as_os_str().to_str())
+        .filter(|name| is_pci_address(name) && *name != addr)
+        .map(String::from)
+        .collect();
+    ports.reverse();
+    ports
+}
+
+/// "0000:00:01.1": domain:bus:device.function
+fn is_pci_address(name: &str) -> bool {
+    let parts: Vec<&str> = name.split([':', '.']).collect();
+    parts.len() == 4
+        && [4, 2, 2, 1]
+            .iter()
+            .zip(&parts)
+            .all(|(len, part)| part.len() == *len && part.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn detect_link_speed_controls(sysfs: &SysfsRoot) -> Vec<LinkSpeedControl> {
+    let base = "sys/class/thermal";
+    let mut controls: Vec<LinkSpeedControl> = sysfs
+        .list_dir(base)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|name| name.starts_with("cooling_device"))
+        .filter_map(|name| {
+            let path = format!("{}/{}", base, name);
+            let kind = sysfs
+                .read_optional(format!("{}/type", path))
+                .unwrap_or(None)?;
+            let port = kind.strip_prefix(LINK_SPEED_COOLING_PREFIX)?.to_string();
+            let state = |file: &str| {
+                sysfs
+                    .read_optional(format!("{}/{}", path, file))
+                    .unwrap_or(None)
+                    .and_then(|v| v.parse().ok())
+            };
+            Some(LinkSpeedControl {
+                port,
+                cur_state: state("cur_state"),
+                max_state: state("max_state"),
+                path,
+            })
+        })
+        .collect();
+    controls.sort_by(|a, b| a.port.cmp(&b.port));
+    controls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, path: &str, value: &str) {
+        let full = root.join(path);
+        fs::create_dir_all(full.parent().unwrap()).unwrap();
+        fs::write(full, format!("{}\n", value)).unwrap();
+    }
+
+    #[test]
+    fn test_pcie_generation() {
+        assert_eq!(pcie_generation("2.5 GT/s PCIe"), Some(1));
+        assert_eq!(pcie_generation("8.0 GT/s PCIe"), Some(3));
+        assert_eq!(pcie_generation("16.0 GT/s PCIe"), Some(4));
+        assert_eq!(pcie_generation("32.0 GT/s PCIe"), Some(5));
+        assert_eq!(pcie_generation("Unknown"), None);
+        assert_eq!(pcie_generation("Unknown speed"), None);
+    }
+
+    #[test]
+    fn test_dgpu_link_and_bandwidth_control() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        let dgpu = "sys/devices/pci0000:00/0000:00:01.1/0000:03:00.0";
+        write(root, &format!("{}/class", dgpu), "0x030000");
+        write(root, &format!("{}/boot_vga", dgpu), "0");
+        write(
+            root,
+            &format!("{}/current_link_speed", dgpu),
+            "16.0 GT/s PCIe",
+        );
+        write(root, &format!("{}/current_link_width", dgpu), "8");
+        write(root, &format!("{}/max_link_speed", dgpu), "16.0 GT/s PCIe");
+        write(root, &format!("{}/max_link_width", dgpu), "8");
+        write(root, &format!("{}/gpu_busy_percent", dgpu), "0");
+        fs::create_dir_all(root.join("sys/bus/pci/devices")).unwrap();
+        symlink(
+            "../../../devices/pci0000:00/0000:00:01.1/0000:03:00.0",
+            root.join("sys/bus/pci/devices/0000:03:00.0"),
+        )
+        .unwrap();
+
+        write(root, "sys/class/thermal/cooling_device0/type", "Processor");
+        write(
+            root,
+            "sys/class/thermal/cooling_device3/type",
+            "PCIe_Port_Link_Speed_0000:00:01.1",
+        );
+        write(root, "sys/class/thermal/cooling_device3/cur_state", "0");
+        write(root, "sys/class/thermal/cooling_device3/max_state", "3");
+
+        let info = PciInfo::detect(&SysfsRoot::new(root));
+        let gpu = info.discrete_gpu().unwrap();
+        assert_eq!(gpu.upstream_ports, vec!["0000:00:01.1"]);
+        assert_eq!(gpu.link.current_summary().as_deref(), Some("Gen4 x8"));
+        assert!(gpu.holds_idle_link_above_minimum());
+
+        let control = info.link_speed_control(gpu).unwrap();
+        assert_eq!(control.path, "sys/class/thermal/cooling_device3");
+        assert_eq!(control.cur_state, Some(0));
+        assert_eq!(control.max_state, Some(3));
+    }
+
+    #[test]
+    fn test_downgraded_or_busy_dgpu_is_left_alone() {
+        let mut gpu = PciDevice {
+            address: "0000:03:00.0".to_string(),
+            class: Some("0x030000".to_string()),
+            vendor: None,
+            device: None,
+            driver: None,
+            runtime_pm: None,
+            runtime_status: Some("active".to_string()),
+            boot_vga: Some(false),
+            gpu_busy_percent: Some(0),
+            link: PcieLink {
+                current_speed: Some("2.5 GT/s PCIe".to_string()),
+                current_width: Some(8),
+                max_speed: Some("16.0 GT/s PCIe".to_string()),
+                max_width: Some(8),
+            },
+            upstream_ports: Vec::new(),
+        };
+        assert!(!gpu.holds_idle_link_above_minimum());
+
+        gpu.link.current_speed = Some("16.0 GT/s PCIe".to_string());
+        gpu.gpu_busy_percent = Some(60);
+        assert!(!gpu.holds_idle_link_above_minimum());
+
+        // The iGPU is the boot VGA device
+        gpu.gpu_busy_percent = Some(0);
+        gpu.boot_vga = Some(true);
+        assert!(!gpu.holds_idle_link_above_minimum());
+    }
 }
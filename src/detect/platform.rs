@@ -1,13 +1,96 @@
+use super::acpi_wakeup;
 use crate::sysfs::SysfsRoot;
+use std::time::Duration;
+
+/// The legacy single-file interface; with several handlers registered it
+/// writes to all of them and reads "custom" when they disagree.
+pub const LEGACY_PLATFORM_PROFILE: &str = "/sys/firmware/acpi/platform_profile";
+
+/// What a profile file reads while vendor per-knob tuning is active, or, on
+/// the legacy file, while its handlers disagree. A valid state, not a value
+/// to write.
+pub const CUSTOM_PROFILE: &str = "custom";
+
+/// A profile file can read empty for a moment while the EC switches
+/// profiles; it's read once more after this long.
+const EMPTY_PROFILE_RETRY: Duration = Duration::from_millis(100);
+
+/// Driver name the AMD Platform Management Framework registers its
+/// platform_profile handler under.
+pub const AMD_PMF: &str = "amd-pmf";
 
 #[derive(Debug, Clone, Default)]
 pub struct PlatformInfo {
     pub platform_profile: Option<String>,
     pub platform_profiles_available: Vec<String>,
+    /// Handlers in /sys/class/platform-profile (kernel 6.14+), one per
+    /// driver that registered a profile
+    pub platform_profile_handlers: Vec<PlatformProfileHandler>,
+    /// Whether the amd_pmf module is loaded
+    pub amd_pmf_loaded: bool,
     pub sleep_state: Option<String>,
     pub sleep_states_available: Vec<String>,
     pub mem_sleep: Option<String>,
+    /// Every mode `/sys/power/mem_sleep` offers, e.g. ["s2idle", "deep"]
+    pub mem_sleep_available: Vec<String>,
+    /// The firmware's low-power S0 idle (LPS0) device, e.g. "AMDI0007:00",
+    /// which the kernel's s2idle path calls into; AMD's are its uPEP
+    pub lps0_device: Option<String>,
+    /// FADT LOW_POWER_S0_IDLE_CAPABLE, from the raw table in
+    /// `/sys/firmware/acpi/tables/FACP`; None when the table isn't readable
+    /// (it's root-only)
+    pub low_power_s0_idle: Option<bool>,
     pub acpi_wakeup_sources: Vec<AcpiWakeupSource>,
+    /// Per-source counters from /sys/class/wakeup
+    pub wakeup_counters: Vec<WakeupCounter>,
+    /// Successful suspends since boot (`/sys/power/suspend_stats/success`)
+    pub suspend_count: Option<u64>,
+}
+
+/// One registered platform_profile handler.
+#[derive(Debug, Clone, Default)]
+pub struct PlatformProfileHandler {
+    /// Driver name from `name`, e.g. "amd-pmf"
+    pub name: String,
+    /// Absolute sysfs path of the handler's own `profile` file
+    pub profile_path: String,
+    pub profile: Option<String>,
+}
+
+/// Where bop reads and writes the platform profile, and which driver
+/// handles it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlatformProfileTarget {
+    pub path: String,
+    pub current: Option<String>,
+    /// Handler behind `path`, when the platform-profile class names one
+    pub owner: Option<String>,
+}
+
+impl PlatformProfileTarget {
+    /// Whether the profile reads "custom".
+    pub fn is_custom(&self) -> bool {
+        self.current.as_deref() == Some(CUSTOM_PROFILE)
+    }
+}
+
+/// Read a profile with `read`, once more after `retry` if it came back
+/// empty. Still empty counts as unknown (None).
+fn read_profile(mut read: impl FnMut() -> Option<String>, retry: Duration) -> Option<String> {
+    match read() {
+        Some(profile) if profile.is_empty() => {
+            std::thread::sleep(retry);
+            read().filter(|profile| !profile.is_empty())
+        }
+        profile => profile,
+    }
+}
+
+fn read_profile_file(sysfs: &SysfsRoot, relative: &str) -> Option<String> {
+    read_profile(
+        || sysfs.read_optional(relative).unwrap_or(None),
+        EMPTY_PROFILE_RETRY,
+    )
 }
 
 #[derive(Debug, Clone)]
@@ -16,14 +99,123 @@ pub struct AcpiWakeupSource {
     pub sysfs_node: Option<String>,
     pub status: String, // "enabled" or "disabled"
     pub enabled: bool,
+    /// Another source has the same name, so it can't be toggled on its own
+    pub ambiguous: bool,
+}
+
+/// Kernel wakeup source statistics. Counters reset on reboot.
+#[derive(Debug, Clone, Default)]
+pub struct WakeupCounter {
+    /// Source name from the `name` file, e.g. "PNP0C0D:00" or "0000:c1:00.3";
+    /// stable across reboots unlike the wakeupN directory
+    pub name: String,
+    /// Times the source aborted or woke the system from suspend
+    pub wakeup_count: u64,
+    /// Times the source was activated at all
+    pub active_count: u64,
+    /// Name of the device the source belongs to (target of the `device`
+    /// link), e.g. "0000:c1:00.3"; absent for virtual sources
+    pub device: Option<String>,
+    /// `last_change_ms`: CLOCK_MONOTONIC time of the last activation or
+    /// deactivation, in milliseconds
+    pub last_change_ms: Option<u64>,
+}
+
+impl WakeupCounter {
+    /// Whether this counter belongs to the device behind a `/proc/acpi/wakeup`
+    /// sysfs node such as "pci:0000:c1:00.3" or "platform:PNP0C0D:00".
+    pub fn matches_acpi_node(&self, node: &str) -> bool {
+        let Some((_, device)) = node.split_once(':') else {
+            return false;
+        };
+        self.name == device || self.device.as_deref() == Some(device)
+    }
+}
+
+/// Read every source under /sys/class/wakeup. Sources without a `name` are
+/// skipped.
+pub fn read_wakeup_counters(sysfs: &SysfsRoot) -> Vec<WakeupCounter> {
+    let wakeup_base = "sys/class/wakeup";
+    let Ok(entries) = sysfs.list_dir(wakeup_base) else {
+        return Vec::new();
+    };
+    let mut counters = Vec::new();
+    for entry in entries {
+        let dir = format!("{}/{}", wakeup_base, entry);
+        let Some(name) = sysfs.read_optional(format!("{}/name", dir)).unwrap_or(None) else {
+            continue;
+        };
+        let device = std::fs::read_link(sysfs.path(format!("{}/device", dir)))
+            .ok()
+            .and_then(|target| target.file_name().map(|n| n.to_string_lossy().into_owned()));
+        counters.push(WakeupCounter {
+            name,
+            wakeup_count: sysfs
+                .read_parse(format!("{}/wakeup_count", dir))
+                .unwrap_or(0),
+            active_count: sysfs
+                .read_parse(format!("{}/active_count", dir))
+                .unwrap_or(0),
+            device,
+            last_change_ms: sysfs.read_parse(format!("{}/last_change_ms", dir)).ok(),
+        });
+    }
+    counters
+}
+
+/// Byte offset of the 32-bit Flags field in the FADT.
+const FADT_FLAGS_OFFSET: usize = 112;
+
+/// FADT flag bit 21: the platform supports S0 low-power idle and wants
+/// s2idle over S3.
+const FADT_LOW_POWER_S0_IDLE: u32 = 1 << 21;
+
+/// Where the ACPI namespace's devices show up, as `<HID>:<instance>`.
+const ACPI_DEVICES_DIR: &str = "sys/bus/acpi/devices";
+
+/// Hardware ids the kernel's s2idle code binds as the LPS0 device:
+/// Microsoft's generic one and AMD's uPEP ones.
+const LPS0_DEVICE_IDS: &[&str] = &[
+    "PNP0D80", "AMD0004", "AMD0005", "AMDI0005", "AMDI0006", "AMDI0007",
+];
+
+/// The LPS0 device's name in [`ACPI_DEVICES_DIR`], if the firmware has one.
+pub fn find_lps0_device(sysfs: &SysfsRoot) -> Option<String> {
+    sysfs
+        .list_dir(ACPI_DEVICES_DIR)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|name| LPS0_DEVICE_IDS.contains(&name.split(':').next().unwrap_or_default()))
+}
+
+/// LOW_POWER_S0_IDLE_CAPABLE from a raw FADT, or None when the table is too
+/// short to hold the flags.
+fn fadt_low_power_s0_idle(table: &[u8]) -> Option<bool> {
+    let flags = table.get(FADT_FLAGS_OFFSET..FADT_FLAGS_OFFSET + 4)?;
+    let flags = u32::from_le_bytes(flags.try_into().ok()?);
+    Some(flags & FADT_LOW_POWER_S0_IDLE != 0)
+}
+
+/// The wakeup counter for the device behind an ACPI wake node (the last
+/// column of `/proc/acpi/wakeup`), matched on the source name or its
+/// device link.
+pub fn find_wakeup_counter<'a>(
+    counters: &'a [WakeupCounter],
+    node: &str,
+) -> Option<&'a WakeupCounter> {
+    counters.iter().find(|c| c.matches_acpi_node(node))
 }
 
 impl PlatformInfo {
     pub fn detect(sysfs: &SysfsRoot) -> Self {
+        Self::detect_with_counters(sysfs, read_wakeup_counters(sysfs))
+    }
+
+    /// [`PlatformInfo::detect`] with the wakeup counters already read.
+    pub fn detect_with_counters(sysfs: &SysfsRoot, wakeup_counters: Vec<WakeupCounter>) -> Self {
         let mut info = Self {
-            platform_profile: sysfs
-                .read_optional("sys/firmware/acpi/platform_profile")
-                .unwrap_or(None),
+            platform_profile: read_profile_file(sysfs, "sys/firmware/acpi/platform_profile"),
+            wakeup_counters,
             ..Self::default()
         };
 
@@ -34,6 +226,20 @@ impl PlatformInfo {
             info.platform_profiles_available = avail.split_whitespace().map(String::from).collect();
         }
 
+        info.amd_pmf_loaded = sysfs.exists("sys/module/amd_pmf");
+        let class_base = "sys/class/platform-profile";
+        for entry in sysfs.list_dir(class_base).unwrap_or_default() {
+            let dir = format!("{}/{}", class_base, entry);
+            let Some(name) = sysfs.read_optional(format!("{}/name", dir)).unwrap_or(None) else {
+                continue;
+            };
+            info.platform_profile_handlers.push(PlatformProfileHandler {
+                name,
+                profile_path: format!("/{}/profile", dir),
+                profile: read_profile_file(sysfs, &format!("{}/profile", dir)),
+            });
+        }
+
         // Sleep states
         if let Some(sleep) = sysfs.read_optional("sys/power/state").unwrap_or(None) {
             info.sleep_states_available = sleep.split_whitespace().map(String::from).collect();
@@ -45,6 +251,8 @@ impl PlatformInfo {
                 if word.starts_with('[') && word.ends_with(']') {
                     info.mem_sleep = Some(word[1..word.len() - 1].to_string());
                 }
+                info.mem_sleep_available
+                    .push(word.trim_matches(['[', ']']).to_string());
             }
             if info.mem_sleep.is_none() {
                 // If no brackets, first entry is current
@@ -52,51 +260,190 @@ impl PlatformInfo {
             }
         }
 
+        info.lps0_device = find_lps0_device(sysfs);
+        info.suspend_count = sysfs.read_parse("sys/power/suspend_stats/success").ok();
+        info.low_power_s0_idle = sysfs
+            .read_bytes_optional("sys/firmware/acpi/tables/FACP")
+            .unwrap_or(None)
+            .and_then(|table| fadt_low_power_s0_idle(&table));
+
         // ACPI wakeup sources
         if let Ok(wakeup) = sysfs.read("proc/acpi/wakeup") {
-            for line in wakeup.lines() {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 3 {
-                    let device = parts[0].to_string();
-                    // Format: DEVICE  S-STATE  STATUS  SYSFS_NODE
-                    let status = if parts.contains(&"*enabled") {
-                        "enabled".to_string()
-                    } else if parts.contains(&"*disabled") {
-                        "disabled".to_string()
-                    } else {
-                        // Try to find enabled/disabled in the parts
-                        parts
-                            .iter()
-                            .find(|p| **p == "enabled" || **p == "disabled")
-                            .map(|s| s.to_string())
-                            .unwrap_or_default()
-                    };
-
-                    let sysfs_node = parts.last().and_then(|p| {
-                        if p.starts_with("pci:") || p.starts_with("platform:") {
-                            Some(p.to_string())
-                        } else {
-                            None
-                        }
-                    });
-
-                    let enabled = status.contains("enabled");
-
-                    info.acpi_wakeup_sources.push(AcpiWakeupSource {
-                        device,
-                        sysfs_node,
-                        status,
-                        enabled,
-                    });
-                }
-            }
+            let entries = acpi_wakeup::parse(&wakeup);
+            info.acpi_wakeup_sources = entries
+                .iter()
+                .map(|entry| AcpiWakeupSource {
+                    device: entry.device.clone(),
+                    sysfs_node: entry.sysfs_node().map(String::from),
+                    status: match entry.enabled {
+                        Some(true) => "enabled",
+                        Some(false) => "disabled",
+                        None => "",
+                    }
+                    .to_string(),
+                    enabled: entry.enabled == Some(true),
+                    ambiguous: acpi_wakeup::is_ambiguous(&entries, entry),
+                })
+                .collect();
         }
 
         info
     }
 
+    /// The amd-pmf handler, if the module is loaded and registered one.
+    fn amd_pmf_handler(&self) -> Option<&PlatformProfileHandler> {
+        self.platform_profile_handlers
+            .iter()
+            .find(|h| self.amd_pmf_loaded && h.name == AMD_PMF)
+    }
+
+    /// Where to read and set the platform profile. With amd-pmf loaded and
+    /// registered, its own class node is used: writes through the legacy
+    /// file go to every handler, and the other one can snap the value back.
+    pub fn platform_profile_target(&self) -> PlatformProfileTarget {
+        if let Some(pmf) = self.amd_pmf_handler() {
+            return PlatformProfileTarget {
+                path: pmf.profile_path.clone(),
+                current: pmf.profile.clone(),
+                owner: Some(pmf.name.clone()),
+            };
+        }
+        let owner = match self.platform_profile_handlers.as_slice() {
+            [] => None,
+            [only] => Some(only.name.clone()),
+            many => Some(
+                many.iter()
+                    .map(|h| h.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" + "),
+            ),
+        };
+        PlatformProfileTarget {
+            path: LEGACY_PLATFORM_PROFILE.to_string(),
+            current: self.platform_profile.clone(),
+            owner,
+        }
+    }
+
+    /// Current profile with its handler when known, e.g. "balanced (amd-pmf)".
+    pub fn platform_profile_summary(&self) -> Option<String> {
+        let target = self.platform_profile_target();
+        let current = target.current?;
+        Some(match target.owner {
+            Some(owner) => format!("{} ({})", current, owner),
+            None => current,
+        })
+    }
+
+    /// ACPI wake device (e.g. "XHC1") whose sysfs node belongs to `counter`.
+    pub fn acpi_device_for(&self, counter: &WakeupCounter) -> Option<&str> {
+        self.acpi_wakeup_sources
+            .iter()
+            .find(|s| {
+                s.sysfs_node
+                    .as_deref()
+                    .is_some_and(|node| counter.matches_acpi_node(node))
+            })
+            .map(|s| s.device.as_str())
+    }
+
+    /// Whether the kernel picked `deep` on a platform built for s2idle: s2idle
+    /// is offered, the firmware has an LPS0 device, and the FADT doesn't say
+    /// S0 idle is unsupported. Vendor gating is the caller's.
+    pub fn deep_selected_over_s2idle(&self) -> bool {
+        self.mem_sleep.as_deref() == Some("deep")
+            && self.mem_sleep_available.iter().any(|m| m == "s2idle")
+            && self.lps0_device.is_some()
+            && self.low_power_s0_idle != Some(false)
+    }
+
     pub fn has_s2idle(&self) -> bool {
         self.sleep_states_available.iter().any(|s| s == "mem")
             && self.mem_sleep.as_deref() == Some("s2idle")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Read a profile file that reads each of `values` in turn (missing past
+    /// the end), and how many reads that took.
+    fn read_in_turn(values: &[&str]) -> (Option<String>, usize) {
+        let reads = Cell::new(0);
+        let profile = read_profile(
+            || {
+                let value = values.get(reads.get()).map(|v| v.to_string());
+                reads.set(reads.get() + 1);
+                value
+            },
+            Duration::ZERO,
+        );
+        (profile, reads.get())
+    }
+
+    #[test]
+    fn test_empty_profile_read_again_once() {
+        assert_eq!(
+            read_in_turn(&["", "balanced"]),
+            (Some("balanced".into()), 2)
+        );
+        assert_eq!(read_in_turn(&["", "", "balanced"]), (None, 2));
+        assert_eq!(
+            read_in_turn(&["low-power", ""]),
+            (Some("low-power".into()), 1)
+        );
+        // A missing file isn't retried
+        assert_eq!(read_in_turn(&[]), (None, 1));
+    }
+
+    #[test]
+    fn test_custom_profile_is_kept() {
+        let info = PlatformInfo {
+            platform_profile: read_in_turn(&["", "custom"]).0,
+            ..Default::default()
+        };
+        let target = info.platform_profile_target();
+        assert_eq!(target.current.as_deref(), Some(CUSTOM_PROFILE));
+        assert!(target.is_custom());
+    }
+
+    fn fadt(flags: u32) -> Vec<u8> {
+        let mut table = vec![0u8; 276];
+        table[FADT_FLAGS_OFFSET..FADT_FLAGS_OFFSET + 4].copy_from_slice(&flags.to_le_bytes());
+        table
+    }
+
+    #[test]
+    fn test_fadt_low_power_s0_idle() {
+        assert_eq!(fadt_low_power_s0_idle(&fadt(0x0020_a5b5)), Some(true));
+        assert_eq!(fadt_low_power_s0_idle(&fadt(0x0000_a5b5)), Some(false));
+        assert_eq!(fadt_low_power_s0_idle(&[0u8; 64]), None);
+    }
+
+    #[test]
+    fn test_deep_selected_over_s2idle() {
+        let mut info = PlatformInfo {
+            mem_sleep: Some("deep".to_string()),
+            mem_sleep_available: vec!["s2idle".to_string(), "deep".to_string()],
+            lps0_device: Some("AMDI0007:00".to_string()),
+            ..PlatformInfo::default()
+        };
+        assert!(info.deep_selected_over_s2idle());
+
+        // An unreadable FADT doesn't rule it out; one without the flag does
+        info.low_power_s0_idle = Some(true);
+        assert!(info.deep_selected_over_s2idle());
+        info.low_power_s0_idle = Some(false);
+        assert!(!info.deep_selected_over_s2idle());
+        info.low_power_s0_idle = None;
+
+        info.lps0_device = None;
+        assert!(!info.deep_selected_over_s2idle());
+        info.lps0_device = Some("PNP0D80:00".to_string());
+
+        info.mem_sleep_available = vec!["deep".to_string()];
+        assert!(!info.deep_selected_over_s2idle());
+    }
+}
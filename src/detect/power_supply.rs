@@ -0,0 +1,93 @@
+use crate::sysfs::SysfsRoot;
+use std::cell::OnceCell;
+use std::collections::HashMap;
+
+/// Parse a power supply `uevent` file (`POWER_SUPPLY_ENERGY_NOW=51230000`
+/// lines) into a map keyed by the name after the `POWER_SUPPLY_` prefix,
+/// lowercased to match the attribute file names. Unknown keys are kept and
+/// lines that aren't `KEY=VALUE` are skipped.
+pub fn parse_uevent(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once('=')?;
+            let key = key.strip_prefix("POWER_SUPPLY_").unwrap_or(key);
+            if key.is_empty() {
+                return None;
+            }
+            Some((key.to_ascii_lowercase(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Attribute reader for one supply under `sys/class/power_supply`. The
+/// individual attribute file wins; when it is missing, unreadable (ENODATA
+/// while the driver refreshes), empty, or unparseable, the value comes from
+/// the supply's `uevent` file, read at most once.
+pub struct SupplyAttrs<'a> {
+    sysfs: &'a SysfsRoot,
+    base: String,
+    uevent: OnceCell<HashMap<String, String>>,
+}
+
+impl<'a> SupplyAttrs<'a> {
+    pub fn new(sysfs: &'a SysfsRoot, name: &str) -> Self {
+        Self {
+            sysfs,
+            base: format!("sys/class/power_supply/{}", name),
+            uevent: OnceCell::new(),
+        }
+    }
+
+    fn uevent(&self) -> &HashMap<String, String> {
+        self.uevent.get_or_init(|| {
+            self.sysfs
+                .read_optional(format!("{}/uevent", self.base))
+                .ok()
+                .flatten()
+                .map(|content| parse_uevent(&content))
+                .unwrap_or_default()
+        })
+    }
+
+    fn attribute(&self, attr: &str) -> Option<String> {
+        self.sysfs
+            .read_optional(format!("{}/{}", self.base, attr))
+            .ok()
+            .flatten()
+            .filter(|v| !v.is_empty())
+    }
+
+    pub fn get(&self, attr: &str) -> Option<String> {
+        self.attribute(attr)
+            .or_else(|| self.uevent().get(attr).cloned())
+    }
+
+    pub fn get_u64(&self, attr: &str) -> Option<u64> {
+        self.attribute(attr)
+            .and_then(|v| v.parse().ok())
+            .or_else(|| self.uevent().get(attr).and_then(|v| v.parse().ok()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_uevent_tolerates_unknown_and_malformed_lines() {
+        let map = parse_uevent(
+            "POWER_SUPPLY_NAME=BAT1\n\
+             POWER_SUPPLY_ENERGY_NOW=51230000\n\
+             POWER_SUPPLY_SOMETHING_NEW=x\n\
+             garbage line\n\
+             =orphan\n\
+             DEVTYPE=power_supply\n",
+        );
+        assert_eq!(map.get("name").map(String::as_str), Some("BAT1"));
+        assert_eq!(map.get("energy_now").map(String::as_str), Some("51230000"));
+        assert_eq!(map.get("something_new").map(String::as_str), Some("x"));
+        assert_eq!(map.get("devtype").map(String::as_str), Some("power_supply"));
+        assert_eq!(map.len(), 4);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::sysfs::SysfsRoot;
+use std::os::unix::fs::PermissionsExt;
+
+const POWERCAP_BASE: &str = "sys/class/powercap";
+
+/// RAPL zones from the powercap class. Each zone's constraint 0 is its
+/// long-term power limit, the one firmware sets to the platform's
+/// sustained TDP.
+#[derive(Debug, Clone, Default)]
+pub struct PowercapInfo {
+    pub zones: Vec<PowercapZone>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PowercapZone {
+    /// sysfs path without the leading slash, e.g.
+    /// "sys/class/powercap/intel-rapl:0"
+    pub path: String,
+    /// Zone name from `name`, e.g. "package-0", "core"
+    pub name: String,
+    /// `constraint_0_power_limit_uw`
+    pub power_limit_uw: Option<u64>,
+    /// `constraint_0_max_power_uw`; None (or 0) when firmware reports none
+    pub max_power_uw: Option<u64>,
+    /// The limit file has a write bit set. AMD's RAPL driver and locked
+    /// firmware expose it read-only.
+    pub writable: bool,
+}
+
+impl PowercapZone {
+    /// The whole package (CPU, iGPU and IO), rather than a subzone.
+    pub fn is_package(&self) -> bool {
+        self.name.starts_with("package-")
+    }
+
+    /// Absolute path of the long-term limit.
+    pub fn limit_path(&self) -> String {
+        format!("/{}/constraint_0_power_limit_uw", self.path)
+    }
+}
+
+impl PowercapInfo {
+    pub fn detect(sysfs: &SysfsRoot) -> Self {
+        let Ok(mut entries) = sysfs.list_dir(POWERCAP_BASE) else {
+            return Self::default();
+        };
+        entries.sort();
+        let zones = entries
+            .iter()
+            .filter_map(|entry| {
+                let path = format!("{}/{}", POWERCAP_BASE, entry);
+                let read = |attr: &str| sysfs.read_optional(format!("{}/{}", path, attr)).ok()?;
+                let name = read("name")?;
+                let limit = format!("{}/constraint_0_power_limit_uw", path);
+                let writable = std::fs::metadata(sysfs.path(&limit))
+                    .is_ok_and(|m| m.permissions().mode() & 0o222 != 0);
+                Some(PowercapZone {
+                    name,
+                    power_limit_uw: read("constraint_0_power_limit_uw")
+                        .and_then(|v| v.parse().ok()),
+                    max_power_uw: read("constraint_0_max_power_uw")
+                        .and_then(|v| v.parse().ok())
+                        .filter(|&max| max > 0),
+                    writable,
+                    path,
+                })
+            })
+            .collect();
+        Self { zones }
+    }
+
+    /// Package zones whose long-term limit can be set. Intel machines often
+    /// list the package twice, through MSRs (intel-rapl) and MMIO
+    /// (intel-rapl-mmio); both are returned, since either one caps it.
+    pub fn writable_packages(&self) -> impl Iterator<Item = &PowercapZone> {
+        self.zones
+            .iter()
+            .filter(|z| z.is_package() && z.writable && z.power_limit_uw.is_some())
+    }
+
+    /// The first package zone's limit, writable or not.
+    pub fn package_limit_uw(&self) -> Option<u64> {
+        self.zones
+            .iter()
+            .filter(|z| z.is_package())
+            .find_map(|z| z.power_limit_uw)
+    }
+}
+
+/// Watts as the microwatts powercap takes.
+pub fn watts_to_uw(watts: u32) -> u64 {
+    u64::from(watts) * 1_000_000
+}
+
+/// Microwatts as watts, for display.
+pub fn uw_to_watts(uw: u64) -> f64 {
+    uw as f64 / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn zone(tmp: &TempDir, entry: &str, name: &str, limit: &str, mode: u32) {
+        let dir = tmp.path().join(POWERCAP_BASE).join(entry);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("name"), format!("{}\n", name)).unwrap();
+        let limit_path = dir.join("constraint_0_power_limit_uw");
+        fs::write(&limit_path, format!("{}\n", limit)).unwrap();
+        fs::set_permissions(&limit_path, fs::Permissions::from_mode(mode)).unwrap();
+        fs::write(dir.join("constraint_0_max_power_uw"), "0\n").unwrap();
+    }
+
+    #[test]
+    fn test_detect_zones() {
+        let tmp = TempDir::new().unwrap();
+        zone(&tmp, "intel-rapl:0", "package-0", "28000000", 0o644);
+        zone(&tmp, "intel-rapl:0:0", "core", "0", 0o644);
+        zone(&tmp, "intel-rapl-mmio:0", "package-0", "28000000", 0o444);
+
+        let info = PowercapInfo::detect(&SysfsRoot::new(tmp.path()));
+        assert_eq!(info.zones.len(), 3);
+        // "intel-rapl-mmio:0" sorts first
+        let msr = &info.zones[1];
+        assert_eq!(msr.power_limit_uw, Some(28_000_000));
+        assert_eq!(msr.max_power_uw, None);
+        assert_eq!(
+            msr.limit_path(),
+            "/sys/class/powercap/intel-rapl:0/constraint_0_power_limit_uw"
+        );
+        let writable: Vec<_> = info.writable_packages().map(|z| z.path.as_str()).collect();
+        assert_eq!(writable, ["sys/class/powercap/intel-rapl:0"]);
+        assert_eq!(info.package_limit_uw(), Some(28_000_000));
+    }
+
+    #[test]
+    fn test_unit_conversion() {
+        assert_eq!(watts_to_uw(15), 15_000_000);
+        assert_eq!(watts_to_uw(u32::MAX), 4_294_967_295_000_000);
+        assert_eq!(uw_to_watts(28_500_000), 28.5);
+        assert_eq!(uw_to_watts(0), 0.0);
+    }
+}
@@ -0,0 +1,194 @@
+use crate::sysfs::SysfsRoot;
+
+const TYPEC_BASE: &str = "sys/class/typec";
+
+/// USB Type-C ports and whatever is plugged into them. Everything here is
+/// best effort: the typec class layout differs between ucsi_acpi and
+/// cros_ec_typec and across kernel versions, so any missing attribute is
+/// just `None`.
+#[derive(Debug, Clone, Default)]
+pub struct TypecInfo {
+    pub ports: Vec<TypecPort>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TypecPort {
+    /// sysfs name, e.g. "port0"
+    pub name: String,
+    /// Current power role, "source" or "sink"
+    pub power_role: Option<String>,
+    /// Current data role, "host" or "device"
+    pub data_role: Option<String>,
+    pub partner: Option<TypecPartner>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TypecPartner {
+    pub supports_pd: Option<bool>,
+    /// e.g. "3.0"; "0.0" when the partner doesn't speak PD
+    pub pd_revision: Option<String>,
+    /// "none", "analog_audio", "debug"
+    pub accessory_mode: Option<String>,
+}
+
+impl TypecPort {
+    /// The laptop is supplying power and something is connected to take it,
+    /// which is how Framework expansion cards (HDMI, SSD) are powered.
+    pub fn partner_draws_bus_power(&self) -> bool {
+        self.partner.is_some() && self.power_role.as_deref() == Some("source")
+    }
+}
+
+impl TypecInfo {
+    pub fn detect(sysfs: &SysfsRoot) -> Self {
+        let entries = match sysfs.list_dir(TYPEC_BASE) {
+            Ok(e) => e,
+            Err(_) => return Self::default(),
+        };
+
+        let mut ports: Vec<TypecPort> = entries
+            .iter()
+            .filter(|name| port_index(name).is_some())
+            .map(|name| {
+                let base = format!("{}/{}", TYPEC_BASE, name);
+                let partner_base = format!("{}-partner", base);
+                let read = |path: String| sysfs.read_optional(path).ok().flatten();
+
+                let partner = sysfs.exists(&partner_base).then(|| TypecPartner {
+                    supports_pd: read(format!("{}/supports_usb_power_delivery", partner_base))
+                        .map(|v| v == "yes"),
+                    pd_revision: read(format!("{}/usb_power_delivery_revision", partner_base)),
+                    accessory_mode: read(format!("{}/accessory_mode", partner_base)),
+                });
+
+                TypecPort {
+                    name: name.clone(),
+                    power_role: read(format!("{}/power_role", base))
+                        .and_then(|v| selected_role(&v)),
+                    data_role: read(format!("{}/data_role", base)).and_then(|v| selected_role(&v)),
+                    partner,
+                }
+            })
+            .collect();
+
+        ports.sort_by_key(|p| port_index(&p.name));
+        Self { ports }
+    }
+}
+
+/// "port3" -> 3. Partner, cable and plug entries ("port3-partner") don't match.
+fn port_index(name: &str) -> Option<u32> {
+    name.strip_prefix("port")?.parse().ok()
+}
+
+/// The bracketed choice in "source [sink]", or the sole value on ports
+/// that can't swap roles.
+fn selected_role(value: &str) -> Option<String> {
+    let selected = match (value.find('['), value.find(']')) {
+        (Some(start), Some(end)) if start < end => &value[start + 1..end],
+        _ => value.trim(),
+    };
+    (!selected.is_empty()).then(|| selected.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, path: &str, value: &str) {
+        let full = root.join(path);
+        fs::create_dir_all(full.parent().unwrap()).unwrap();
+        fs::write(full, format!("{}\n", value)).unwrap();
+    }
+
+    fn port(root: &Path, name: &str, power_role: &str) {
+        write(
+            root,
+            &format!("sys/class/typec/{}/power_role", name),
+            power_role,
+        );
+        write(
+            root,
+            &format!("sys/class/typec/{}/data_role", name),
+            "[host] device",
+        );
+    }
+
+    fn partner(root: &Path, port: &str, pd: &str) {
+        let base = format!("sys/class/typec/{}-partner", port);
+        write(root, &format!("{}/supports_usb_power_delivery", base), pd);
+        write(root, &format!("{}/accessory_mode", base), "none");
+    }
+
+    #[test]
+    fn test_ucsi_layout() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        port(root, "port0", "[source] sink");
+        partner(root, "port0", "no");
+        port(root, "port1", "source [sink]");
+        partner(root, "port1", "yes");
+        write(
+            root,
+            "sys/class/typec/port1-partner/usb_power_delivery_revision",
+            "3.0",
+        );
+        port(root, "port2", "[source] sink");
+
+        let info = TypecInfo::detect(&SysfsRoot::new(root));
+        let names: Vec<_> = info.ports.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["port0", "port1", "port2"]);
+
+        let p0 = &info.ports[0];
+        assert!(p0.partner_draws_bus_power());
+        assert_eq!(p0.data_role.as_deref(), Some("host"));
+        assert_eq!(p0.partner.as_ref().unwrap().supports_pd, Some(false));
+
+        // Charger on port1: laptop is the sink, nothing drawn from it
+        assert!(!info.ports[1].partner_draws_bus_power());
+        assert_eq!(
+            info.ports[1]
+                .partner
+                .as_ref()
+                .unwrap()
+                .pd_revision
+                .as_deref(),
+            Some("3.0")
+        );
+        // Empty port2
+        assert!(!info.ports[2].partner_draws_bus_power());
+    }
+
+    #[test]
+    fn test_cros_ec_layout() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        // cros_ec_typec ports that can't swap roles report a bare value
+        port(root, "port0", "sink");
+        port(root, "port1", "source");
+        partner(root, "port1", "no");
+
+        let info = TypecInfo::detect(&SysfsRoot::new(root));
+        assert_eq!(info.ports[0].power_role.as_deref(), Some("sink"));
+        assert!(info.ports[1].partner_draws_bus_power());
+    }
+
+    #[test]
+    fn test_missing_or_partial_typec_class() {
+        let tmp = TempDir::new().unwrap();
+        let info = TypecInfo::detect(&SysfsRoot::new(tmp.path()));
+        assert!(info.ports.is_empty());
+
+        // Port with a partner but no roles
+        fs::create_dir_all(tmp.path().join("sys/class/typec/port0")).unwrap();
+        partner(tmp.path(), "port0", "no");
+        let info = TypecInfo::detect(&SysfsRoot::new(tmp.path()));
+        assert_eq!(info.ports.len(), 1);
+        assert!(info.ports[0].partner.is_some());
+        assert_eq!(info.ports[0].power_role, None);
+        assert!(!info.ports[0].partner_draws_bus_power());
+    }
+}
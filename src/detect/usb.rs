@@ -0,0 +1,311 @@
+use crate::sysfs::SysfsRoot;
+
+/// `bmAttributes` bit 5: device can signal remote wakeup.
+const ATTR_REMOTE_WAKEUP: u8 = 0x20;
+/// `bmAttributes` bit 6: device is self-powered.
+const ATTR_SELF_POWERED: u8 = 0x40;
+
+#[derive(Debug, Clone, Default)]
+pub struct UsbDevice {
+    /// sysfs name, e.g. "1-2" or "usb1"
+    pub name: String,
+    pub product: Option<String>,
+    /// `power/control`: "on" or "auto"
+    pub control: Option<String>,
+    /// `power/runtime_status`: "active", "suspended", ...
+    pub runtime_status: Option<String>,
+    /// `power/persist`: whether USB-persist survives a reset on resume
+    pub persist: Option<bool>,
+    /// Configuration descriptor `bmAttributes`
+    pub attributes: Option<u8>,
+    /// Configuration descriptor `bMaxPower`, e.g. "100mA"
+    pub max_power: Option<String>,
+}
+
+/// Whether `bop apply` should set a device's `power/control` to auto, and why.
+#[derive(Debug, Clone)]
+pub struct AutosuspendDecision {
+    pub include: bool,
+    pub reason: &'static str,
+    /// Descriptor and power-state facts the decision was based on
+    pub factors: Vec<String>,
+}
+
+impl UsbDevice {
+    pub fn remote_wakeup(&self) -> Option<bool> {
+        self.attributes.map(|a| a & ATTR_REMOTE_WAKEUP != 0)
+    }
+
+    pub fn self_powered(&self) -> Option<bool> {
+        self.attributes.map(|a| a & ATTR_SELF_POWERED != 0)
+    }
+
+    fn product_lower(&self) -> String {
+        self.product.as_deref().unwrap_or_default().to_lowercase()
+    }
+
+    pub fn is_input(&self) -> bool {
+        let product = self.product_lower();
+        product.contains("keyboard")
+            || product.contains("mouse")
+            || product.contains("trackpad")
+            || product.contains("touchpad")
+    }
+
+    pub fn is_expansion(&self) -> bool {
+        let product = self.product_lower();
+        product.contains("expansion") || product.contains("displayport") || product.contains("hdmi")
+    }
+
+    fn is_active(&self) -> bool {
+        self.runtime_status.as_deref() == Some("active")
+    }
+
+    /// Decide autosuspend for a device not yet set to auto. Descriptor facts
+    /// take priority over the product-name rules: one that cannot signal
+    /// remote wakeup is left on while in use, since those tend to drop off
+    /// the bus (webcams, audio DACs).
+    pub fn autosuspend_decision(&self, skip_input_expansion: bool) -> AutosuspendDecision {
+        let mut factors = Vec::new();
+        if let Some(product) = &self.product {
+            factors.push(format!("product: {}", product));
+        }
+        factors.push(format!(
+            "remote wakeup: {}",
+            match self.remote_wakeup() {
+                Some(true) => "yes",
+                Some(false) => "no",
+                None => "unknown",
+            }
+        ));
+        if let Some(self_powered) = self.self_powered() {
+            factors.push(format!(
+                "power: {}",
+                if self_powered { "self" } else { "bus" }
+            ));
+        }
+        if let Some(max_power) = &self.max_power {
+            factors.push(format!("bMaxPower: {}", max_power));
+        }
+        if let Some(persist) = self.persist {
+            factors.push(format!("persist: {}", if persist { "on" } else { "off" }));
+        }
+        if let Some(status) = &self.runtime_status {
+            factors.push(format!("runtime_status: {}", status));
+        }
+
+        let (include, reason) = if self.remote_wakeup() == Some(false) && self.is_active() {
+            (false, "in use without remote wakeup; may drop off the bus")
+        } else if self.remote_wakeup().is_none() && self.persist == Some(false) && self.is_active()
+        {
+            (false, "no descriptor info and USB persist off")
+        } else if skip_input_expansion && (self.is_input() || self.is_expansion()) {
+            (false, "input or expansion card device")
+        } else if self.remote_wakeup() == Some(true) {
+            (true, "supports remote wakeup")
+        } else {
+            (true, "no blocking factors")
+        };
+
+        AutosuspendDecision {
+            include,
+            reason,
+            factors,
+        }
+    }
+}
+
+/// A downstream port of a hub, from the hub interface's `<hub>-portN`
+/// directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsbPort {
+    /// Port directory name, e.g. "usb1-port2" or "1-1-port3"
+    pub name: String,
+    /// Hub interface the port belongs to, e.g. "1-0:1.0"
+    pub interface: String,
+    /// Whether a device is attached (the `device` link exists)
+    pub connected: bool,
+    /// `disable`: whether the port is powered off. None on kernels without it.
+    pub disabled: Option<bool>,
+    /// `connect_type`: "hotplug", "hardwired", "not used" or "unknown"
+    pub connect_type: Option<String>,
+    /// The matching port on the hub's other-speed twin (`peer` link), e.g.
+    /// "usb2-port2" for a USB 3 port's USB 2 half
+    pub peer: Option<String>,
+}
+
+impl UsbPort {
+    /// sysfs directory, relative to the sysfs root.
+    pub fn path(&self) -> String {
+        format!("{}/{}/{}", USB_DEVICES, self.interface, self.name)
+    }
+
+    /// Name a device plugged into this port gets, e.g. "1-2" for
+    /// "usb1-port2" and "1-1.3" for "1-1-port3".
+    pub fn device_name(&self) -> Option<String> {
+        port_device_name(&self.name)
+    }
+
+    /// The hub device whose interface holds the port: "usb1" for a root
+    /// hub's "1-0:1.0", "1-1" for "1-1:1.0".
+    pub fn hub(&self) -> Option<String> {
+        let device = self.interface.split_once(':')?.0;
+        match device.split_once('-')? {
+            (bus, "0") => Some(format!("usb{}", bus)),
+            _ => Some(device.to_string()),
+        }
+    }
+}
+
+/// Device name behind a port directory name; see [`UsbPort::device_name`].
+pub fn port_device_name(port: &str) -> Option<String> {
+    let (hub, number) = port.rsplit_once("-port")?;
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    match hub.strip_prefix("usb") {
+        Some(bus) => Some(format!("{}-{}", bus, number)),
+        None => Some(format!("{}.{}", hub, number)),
+    }
+}
+
+const USB_DEVICES: &str = "sys/bus/usb/devices";
+
+#[derive(Debug, Clone, Default)]
+pub struct UsbInfo {
+    /// Devices only; interfaces ("1-2:1.0") are skipped
+    pub devices: Vec<UsbDevice>,
+    /// Ports of every hub, root hubs included
+    pub ports: Vec<UsbPort>,
+}
+
+impl UsbInfo {
+    pub fn detect(sysfs: &SysfsRoot) -> Self {
+        let mut info = Self::default();
+
+        let usb_base = USB_DEVICES;
+        if let Ok(entries) = sysfs.list_dir(usb_base) {
+            for name in entries {
+                if name.contains(':') {
+                    info.ports.extend(detect_ports(sysfs, &name));
+                    continue;
+                }
+                let read = |file: &str| {
+                    sysfs
+                        .read_optional(format!("{}/{}/{}", usb_base, name, file))
+                        .unwrap_or(None)
+                };
+
+                info.devices.push(UsbDevice {
+                    product: read("product"),
+                    control: read("power/control"),
+                    runtime_status: read("power/runtime_status"),
+                    persist: read("power/persist").map(|v| v == "1"),
+                    attributes: read("bmAttributes")
+                        .and_then(|v| u8::from_str_radix(v.trim_start_matches("0x"), 16).ok()),
+                    max_power: read("bMaxPower"),
+                    name,
+                });
+            }
+        }
+
+        info
+    }
+}
+
+/// Ports under a hub interface; other interfaces have none.
+fn detect_ports(sysfs: &SysfsRoot, interface: &str) -> Vec<UsbPort> {
+    let dir = format!("{}/{}", USB_DEVICES, interface);
+    let names = sysfs.list_dir(&dir).unwrap_or_default();
+    names
+        .into_iter()
+        .filter(|name| port_device_name(name).is_some())
+        .map(|name| {
+            let path = format!("{}/{}", dir, name);
+            let read = |file: &str| {
+                sysfs
+                    .read_optional(format!("{}/{}", path, file))
+                    .unwrap_or(None)
+            };
+            UsbPort {
+                connected: sysfs.path(format!("{}/device", path)).exists(),
+                disabled: read("disable").map(|v| v == "1"),
+                connect_type: read("connect_type"),
+                peer: std::fs::read_link(sysfs.path(format!("{}/peer", path)))
+                    .ok()
+                    .and_then(|target| {
+                        target
+                            .file_name()
+                            .and_then(|n| n.to_str())
+                            .map(String::from)
+                    }),
+                interface: interface.to_string(),
+                name,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(attributes: Option<u8>, status: &str) -> UsbDevice {
+        UsbDevice {
+            name: "1-1".to_string(),
+            control: Some("on".to_string()),
+            runtime_status: Some(status.to_string()),
+            attributes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_port_device_and_hub_names() {
+        assert_eq!(port_device_name("usb1-port2").as_deref(), Some("1-2"));
+        assert_eq!(port_device_name("1-1-port3").as_deref(), Some("1-1.3"));
+        assert_eq!(port_device_name("1-1.4-port1").as_deref(), Some("1-1.4.1"));
+        assert_eq!(port_device_name("usb1-port"), None);
+        assert_eq!(port_device_name("power"), None);
+
+        let port = |interface: &str| UsbPort {
+            name: "x-port1".to_string(),
+            interface: interface.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(port("3-0:1.0").hub().as_deref(), Some("usb3"));
+        assert_eq!(port("3-1.2:1.0").hub().as_deref(), Some("3-1.2"));
+    }
+
+    #[test]
+    fn test_remote_wakeup_bit() {
+        assert_eq!(device(Some(0xa0), "active").remote_wakeup(), Some(true));
+        assert_eq!(device(Some(0x80), "active").remote_wakeup(), Some(false));
+        assert_eq!(device(Some(0xc0), "active").self_powered(), Some(true));
+        assert_eq!(device(None, "active").remote_wakeup(), None);
+    }
+
+    #[test]
+    fn test_decision_excludes_active_non_wakeup_device() {
+        let d = device(Some(0x80), "active").autosuspend_decision(false);
+        assert!(!d.include);
+        assert!(d.factors.contains(&"remote wakeup: no".to_string()));
+    }
+
+    #[test]
+    fn test_decision_product_rules_still_apply() {
+        let mut dev = device(Some(0xa0), "active");
+        dev.product = Some("Wireless Mouse".to_string());
+        assert!(!dev.autosuspend_decision(true).include);
+        assert!(dev.autosuspend_decision(false).include);
+    }
+
+    #[test]
+    fn test_decision_unknown_descriptor_with_persist_off() {
+        let mut dev = device(None, "active");
+        dev.persist = Some(false);
+        assert!(!dev.autosuspend_decision(false).include);
+        dev.persist = Some(true);
+        assert!(dev.autosuspend_decision(false).include);
+    }
+}
@@ -0,0 +1,179 @@
+//! Which USB devices sit behind a host controller: PCI address → the root
+//! hubs whose device path runs through it → every device below them,
+//! walked down the device tree so hubs and what's plugged into them count.
+//! `bop wake` and the sleep audit both ask this and must agree.
+
+use crate::sysfs::SysfsRoot;
+use std::path::{Component, Path};
+
+const USB_DEVICES: &str = "sys/bus/usb/devices";
+
+/// A device below a controller's root hub.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopologyDevice {
+    /// sysfs name, e.g. "3-1" or "3-1.2" behind a hub
+    pub name: String,
+    /// "Manufacturer Product", whichever of those exists, or the name
+    pub description: String,
+}
+
+impl TopologyDevice {
+    /// Port chain from the root hub, e.g. "1.2" for "3-1.2".
+    pub fn port(&self) -> &str {
+        self.name
+            .split_once('-')
+            .map_or(self.name.as_str(), |(_, port)| port)
+    }
+}
+
+/// A device directory's name: "N-P" or "N-P.P...", not a root hub ("usbN")
+/// or an interface ("N-P:C.I").
+fn is_device_name(name: &str) -> bool {
+    let Some((bus, port)) = name.split_once('-') else {
+        return false;
+    };
+    !bus.is_empty()
+        && bus.chars().all(|c| c.is_ascii_digit())
+        && !port.is_empty()
+        && port
+            .split('.')
+            .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// The root hubs' device directories under the controller at `pci_address`
+/// (e.g. "0000:c1:00.3"), relative to the sysfs root. An xHCI controller
+/// usually has two, one per USB generation.
+pub fn root_hubs(sysfs: &SysfsRoot, pci_address: &str) -> Vec<String> {
+    let Ok(root) = sysfs.path("").canonicalize() else {
+        return Vec::new();
+    };
+    let hubs = sysfs.list_dir(USB_DEVICES).unwrap_or_default();
+    hubs.iter()
+        .filter(|name| name.starts_with("usb"))
+        .filter_map(|name| {
+            sysfs
+                .path(format!("{}/{}", USB_DEVICES, name))
+                .canonicalize()
+                .ok()
+        })
+        .filter(|path| {
+            path.components()
+                .any(|c| c == Component::Normal(pci_address.as_ref()))
+        })
+        .filter_map(|path| {
+            path.strip_prefix(&root)
+                .ok()
+                .and_then(Path::to_str)
+                .map(String::from)
+        })
+        .collect()
+}
+
+/// Every device behind the controller at `pci_address`, in tree order.
+pub fn controller_devices(sysfs: &SysfsRoot, pci_address: &str) -> Vec<TopologyDevice> {
+    let mut devices = Vec::new();
+    for hub in root_hubs(sysfs, pci_address) {
+        collect_children(sysfs, &hub, &mut devices);
+    }
+    devices
+}
+
+/// Whether anything at all is plugged in behind the controller.
+pub fn controller_has_devices(sysfs: &SysfsRoot, pci_address: &str) -> bool {
+    !controller_devices(sysfs, pci_address).is_empty()
+}
+
+fn collect_children(sysfs: &SysfsRoot, dir: &str, devices: &mut Vec<TopologyDevice>) {
+    for name in sysfs.list_dir(dir).unwrap_or_default() {
+        if !is_device_name(&name) {
+            continue;
+        }
+        let path = format!("{}/{}", dir, name);
+        let product = sysfs
+            .read_optional(format!("{}/product", path))
+            .unwrap_or(None);
+        let manufacturer = sysfs
+            .read_optional(format!("{}/manufacturer", path))
+            .unwrap_or(None);
+        let description = match (manufacturer, product) {
+            (Some(mfg), Some(prod)) => format!("{} {}", mfg, prod),
+            (None, Some(prod)) => prod,
+            (Some(mfg), None) => mfg,
+            (None, None) => name.clone(),
+        };
+        devices.push(TopologyDevice { name, description });
+        collect_children(sysfs, &path, devices);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// A root hub `usbN` under `pci` with `devices` (paths relative to the
+    /// hub, e.g. "3-1/3-1.2") and the bus links the kernel creates.
+    fn add_hub(root: &Path, pci: &str, bus: u32, devices: &[(&str, Option<&str>)]) {
+        let hub = root.join(format!("sys/devices/pci0000:00/{}/usb{}", pci, bus));
+        fs::create_dir_all(&hub).unwrap();
+        let links = root.join(USB_DEVICES);
+        fs::create_dir_all(&links).unwrap();
+        std::os::unix::fs::symlink(&hub, links.join(format!("usb{}", bus))).unwrap();
+        for (path, product) in devices {
+            let dir = hub.join(path);
+            fs::create_dir_all(&dir).unwrap();
+            if let Some(product) = product {
+                fs::write(dir.join("product"), format!("{}\n", product)).unwrap();
+            }
+            let name = dir.file_name().unwrap().to_owned();
+            std::os::unix::fs::symlink(&dir, links.join(name)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_device_names_exclude_hubs_and_interfaces() {
+        assert!(is_device_name("3-1"));
+        assert!(is_device_name("3-1.4.2"));
+        assert!(!is_device_name("usb3"));
+        assert!(!is_device_name("3-1:1.0"));
+        assert!(!is_device_name("3-"));
+        assert!(!is_device_name("3-1."));
+    }
+
+    #[test]
+    fn test_walks_through_hubs_on_every_root_hub() {
+        let tmp = TempDir::new().unwrap();
+        add_hub(
+            tmp.path(),
+            "0000:c1:00.4",
+            3,
+            &[
+                ("3-1", Some("USB2.0 Hub")),
+                ("3-1/3-1:1.0", None),
+                ("3-1/3-1.2", Some("Receiver")),
+            ],
+        );
+        add_hub(tmp.path(), "0000:c1:00.4", 4, &[("4-1", None)]);
+        add_hub(tmp.path(), "0000:c1:00.3", 1, &[("1-1", Some("Keyboard"))]);
+        let sysfs = SysfsRoot::new(tmp.path());
+
+        let devices = controller_devices(&sysfs, "0000:c1:00.4");
+        let names: Vec<_> = devices.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["3-1", "3-1.2", "4-1"]);
+        assert_eq!(devices[1].description, "Receiver");
+        assert_eq!(devices[1].port(), "1.2");
+        assert_eq!(devices[2].description, "4-1");
+        assert_eq!(root_hubs(&sysfs, "0000:c1:00.4").len(), 2);
+    }
+
+    #[test]
+    fn test_pci_address_matches_whole_components() {
+        let tmp = TempDir::new().unwrap();
+        add_hub(tmp.path(), "0000:c1:00.30", 5, &[("5-1", None)]);
+        let sysfs = SysfsRoot::new(tmp.path());
+
+        assert!(!controller_has_devices(&sysfs, "0000:c1:00.3"));
+        assert!(controller_has_devices(&sysfs, "0000:c1:00.30"));
+    }
+}
@@ -0,0 +1,285 @@
+//! Durations as users write them: `"30s"`, `"5m"`, `"2h"`, `"1d"`, or
+//! combined like `"1h30m"`. A bare integer is seconds, which is what the
+//! older `*_secs` config keys held. Serialized back in the same form.
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+const UNITS: &[(char, u64)] = &[('d', 86_400), ('h', 3_600), ('m', 60), ('s', 1)];
+
+/// A whole number of seconds, parsed from and shown as `"1h30m"`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Duration(u64);
+
+impl Duration {
+    pub const fn from_secs(secs: u64) -> Self {
+        Self(secs)
+    }
+
+    pub const fn from_mins(mins: u64) -> Self {
+        Self(mins * 60)
+    }
+
+    pub const fn as_secs(self) -> u64 {
+        self.0
+    }
+
+    pub const fn as_std(self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.0)
+    }
+}
+
+impl From<Duration> for std::time::Duration {
+    fn from(d: Duration) -> Self {
+        d.as_std()
+    }
+}
+
+/// Why a duration string didn't parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDurationError {
+    input: String,
+    reason: &'static str,
+}
+
+impl fmt::Display for ParseDurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid duration \"{}\": {} (use e.g. \"30s\", \"5m\", \"2h\" or \"1h30m\"; a bare number is seconds)",
+            self.input, self.reason
+        )
+    }
+}
+
+impl std::error::Error for ParseDurationError {}
+
+impl FromStr for Duration {
+    type Err = ParseDurationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = |reason| ParseDurationError {
+            input: s.to_string(),
+            reason,
+        };
+        let input = s.trim();
+        if input.is_empty() {
+            return Err(err("empty"));
+        }
+        if input.bytes().all(|b| b.is_ascii_digit()) {
+            return input.parse().map(Self).map_err(|_| err("too large"));
+        }
+
+        let mut total: u64 = 0;
+        let mut last_unit = usize::MAX;
+        let mut rest = input;
+        while !rest.is_empty() {
+            let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if digits == 0 {
+                return Err(err(if rest.starts_with('-') {
+                    "negative"
+                } else {
+                    "expected a number"
+                }));
+            }
+            let (number, tail) = rest.split_at(digits);
+            let number: u64 = number.parse().map_err(|_| err("too large"))?;
+            let mut chars = tail.chars();
+            let Some(unit) = chars.next() else {
+                return Err(err("missing unit after the last number"));
+            };
+            let Some(index) = UNITS.iter().position(|(u, _)| *u == unit) else {
+                return Err(err("unknown unit; expected s, m, h or d"));
+            };
+            // Largest unit first, each at most once: "1h30m", not "30m1h"
+            if last_unit != usize::MAX && index <= last_unit {
+                return Err(err("units out of order or repeated"));
+            }
+            last_unit = index;
+            total = number
+                .checked_mul(UNITS[index].1)
+                .and_then(|secs| total.checked_add(secs))
+                .ok_or_else(|| err("too large"))?;
+            rest = chars.as_str();
+        }
+        Ok(Self(total))
+    }
+}
+
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 == 0 {
+            return write!(f, "0s");
+        }
+        let mut left = self.0;
+        for (unit, secs) in UNITS {
+            if left >= *secs {
+                write!(f, "{}{}", left / secs, unit)?;
+                left %= secs;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Duration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Duration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DurationVisitor)
+    }
+}
+
+struct DurationVisitor;
+
+impl Visitor<'_> for DurationVisitor {
+    type Value = Duration;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a duration like \"30s\", \"5m\" or \"2h\", or whole seconds"
+        )
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Duration, E> {
+        Ok(Duration(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Duration, E> {
+        u64::try_from(v)
+            .map(Duration)
+            .map_err(|_| E::custom(format!("invalid duration {}: negative", v)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Duration, E> {
+        v.parse().map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        for (input, secs) in [
+            ("0", 0),
+            ("45", 45),
+            (" 30 ", 30),
+            ("0s", 0),
+            ("30s", 30),
+            ("5m", 300),
+            ("2h", 7_200),
+            ("1d", 86_400),
+            ("1h30m", 5_400),
+            ("1d2h3m4s", 93_784),
+            ("90m", 5_400),
+            ("120s", 120),
+        ] {
+            assert_eq!(input.parse::<Duration>(), Ok(Duration(secs)), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects() {
+        for (input, reason) in [
+            ("", "empty"),
+            ("   ", "empty"),
+            ("5x", "unknown unit"),
+            ("5 m", "unknown unit"),
+            ("5M", "unknown unit"),
+            ("1.5h", "unknown unit"),
+            ("m", "expected a number"),
+            ("h30m", "expected a number"),
+            ("-5s", "negative"),
+            ("5m10", "missing unit"),
+            ("30m1h", "out of order"),
+            ("5m5m", "out of order"),
+            ("99999999999999999999", "too large"),
+            ("99999999999999999999s", "too large"),
+            ("999999999999999999d", "too large"),
+        ] {
+            let err = input.parse::<Duration>().unwrap_err();
+            assert!(
+                err.to_string().contains(reason),
+                "{:?}: {} should mention {:?}",
+                input,
+                err,
+                reason
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        for (secs, shown) in [
+            (0, "0s"),
+            (59, "59s"),
+            (60, "1m"),
+            (90, "1m30s"),
+            (3_600, "1h"),
+            (5_400, "1h30m"),
+            (86_400, "1d"),
+            (93_784, "1d2h3m4s"),
+        ] {
+            let d = Duration(secs);
+            assert_eq!(d.to_string(), shown);
+            assert_eq!(shown.parse::<Duration>(), Ok(d));
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        timeout: Duration,
+    }
+
+    #[test]
+    fn test_toml_round_trip() {
+        for (toml_in, secs) in [
+            ("timeout = 30", 30),
+            ("timeout = \"30s\"", 30),
+            ("timeout = \"5m\"", 300),
+            ("timeout = \"2h\"", 7_200),
+        ] {
+            let config: Config = toml::from_str(toml_in).unwrap();
+            assert_eq!(config.timeout, Duration(secs), "{}", toml_in);
+            let out = toml::to_string(&config).unwrap();
+            assert_eq!(out, format!("timeout = \"{}\"\n", Duration(secs)));
+            assert_eq!(toml::from_str::<Config>(&out).unwrap(), config);
+        }
+    }
+
+    #[test]
+    fn test_toml_errors_name_the_field() {
+        for toml_in in [
+            "timeout = \"5 minutes\"",
+            "timeout = -5",
+            "timeout = 1.5",
+            "timeout = true",
+        ] {
+            let err = toml::from_str::<Config>(toml_in).unwrap_err().to_string();
+            assert!(err.contains("timeout"), "{}: {}", toml_in, err);
+            assert!(err.contains("duration"), "{}: {}", toml_in, err);
+        }
+    }
+
+    #[test]
+    fn test_merged_config_errors_name_the_key_path() {
+        #[derive(Debug, Deserialize)]
+        struct Outer {
+            #[allow(dead_code)]
+            commands: Config,
+        }
+        let value: toml::Value = toml::from_str("[commands]\ntimeout = \"5x\"").unwrap();
+        let err = value.try_into::<Outer>().unwrap_err().to_string();
+        assert!(err.contains("`commands.timeout`"), "{}", err);
+        assert!(err.contains("invalid duration \"5x\""), "{}", err);
+    }
+}
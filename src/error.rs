@@ -29,6 +29,45 @@ pub enum Error {
     #[error("state file error: {0}")]
     State(String),
 
+    #[error(
+        "refusing to modify {}: not written by bop (no bop header, not in the state manifest); use --force to override",
+        .path.display()
+    )]
+    NotManaged { path: PathBuf },
+
+    #[error(
+        "ACPI wakeup for {device} is not {} after retrying; /proc/acpi/wakeup may have changed underneath (dock hotplug?). before: {before:?}, after: {after:?}",
+        if *.enabled { "enabled" } else { "disabled" }
+    )]
+    AcpiWakeupToggle {
+        device: String,
+        /// The state the toggle was meant to reach
+        enabled: bool,
+        /// The device's /proc/acpi/wakeup line before the first write and
+        /// after the last one; None if it wasn't listed
+        before: Option<String>,
+        after: Option<String>,
+    },
+
+    #[error(
+        "ACPI wakeup device {device} is listed more than once in /proc/acpi/wakeup ({}); writing the name toggles only the first, so it can't be switched on its own",
+        .nodes.join(", ")
+    )]
+    AmbiguousWakeupDevice {
+        device: String,
+        /// Each entry's sysfs node, in table order
+        nodes: Vec<String>,
+    },
+
+    #[error("another bop operation ({0}) is running")]
+    Locked(String),
+
+    #[error("`{command}` timed out after {:.1}s", .elapsed.as_secs_f64())]
+    CommandTimeout {
+        command: String,
+        elapsed: std::time::Duration,
+    },
+
     #[error("bootloader config error: {0}")]
     Bootloader(String),
 
@@ -0,0 +1,592 @@
+//! Everything bop can change on a system, built from the same constants the
+//! apply, auto, doctor and revert code write through. `bop about-safety`
+//! renders it, and the tests check real plans against it, so the list can't
+//! drift from what the code actually touches.
+
+use crate::apply::{CONFLICTING_SERVICES, MODPROBE_DIR, STATE_FILE, kernel_params, systemd, wifi};
+use serde::Serialize;
+
+/// How bop changes a path, which decides how it gets it back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// Runtime kernel setting in sysfs or procfs
+    Tunable,
+    /// File bop creates, starting with the managed header
+    Generated,
+    /// Existing file bop edits in place
+    Edited,
+    /// systemd service bop stops and disables
+    Service,
+    /// bop's own state, history and locks
+    State,
+}
+
+impl Kind {
+    const ALL: [Kind; 5] = [
+        Kind::Tunable,
+        Kind::Generated,
+        Kind::Edited,
+        Kind::Service,
+        Kind::State,
+    ];
+
+    fn heading(self) -> &'static str {
+        match self {
+            Kind::Tunable => {
+                "Runtime kernel settings (lost on reboot unless the persistence unit re-applies them)"
+            }
+            Kind::Generated => {
+                "Files bop creates (never replacing a file it didn't write, unless --force)"
+            }
+            Kind::Edited => "Existing files bop edits",
+            Kind::Service => "Services bop stops and disables (re-enabled on revert)",
+            Kind::State => "bop's own files",
+        }
+    }
+}
+
+/// One path, or family of paths, bop can change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Entry {
+    pub kind: Kind,
+    /// Absolute path. A trailing `/` covers everything below it, and `*`
+    /// matches within a file name.
+    pub path: String,
+    pub what: &'static str,
+    /// Where the original is saved before the first change; None when
+    /// there is nothing to save (a file bop creates, its own state)
+    pub backup: Option<String>,
+    /// Only takes effect after a reboot
+    pub reboot: bool,
+    /// Command that makes the change, when it's not `bop apply`/`bop auto`
+    pub only_via: Option<&'static str>,
+}
+
+impl Entry {
+    fn new(kind: Kind, path: impl Into<String>, what: &'static str) -> Self {
+        let backup = match kind {
+            Kind::Tunable | Kind::Edited | Kind::Service => Some(STATE_FILE.to_string()),
+            Kind::Generated | Kind::State => None,
+        };
+        Self {
+            kind,
+            path: path.into(),
+            what,
+            backup,
+            reboot: false,
+            only_via: None,
+        }
+    }
+
+    fn reboot(mut self) -> Self {
+        self.reboot = true;
+        self
+    }
+
+    fn backup(mut self, backup: Option<String>) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    fn only_via(mut self, command: &'static str) -> Self {
+        self.only_via = Some(command);
+        self
+    }
+
+    /// Whether `path` falls under this entry.
+    pub fn covers(&self, path: &str) -> bool {
+        if let Some(dir) = self.path.strip_suffix('/') {
+            return path.starts_with(&self.path) || path == dir;
+        }
+        match self.path.split_once('*') {
+            Some((prefix, suffix)) => {
+                path.len() >= prefix.len() + suffix.len()
+                    && path.starts_with(prefix)
+                    && path.ends_with(suffix)
+                    && !path[prefix.len()..path.len() - suffix.len()].contains('/')
+            }
+            None => path == self.path,
+        }
+    }
+}
+
+/// Things bop never changes, whatever the preset or config.
+const NEVER_TOUCHED: &[&str] = &[
+    "Files in home directories (the only exception: ~/.config/bop/config.toml, written by `bop config init`)",
+    "Display configuration: monitor layout, resolution, refresh rate, compositor or X11 settings",
+    "Package manager state: bop never installs, removes or upgrades packages",
+    "Firmware, BIOS settings and EFI variables",
+    "Saved network connections (a connection's own wifi.powersave setting still wins)",
+    "Battery charging: /sys/class/power_supply/*/charge_control_start_threshold and \
+     charge_control_end_threshold (a [pins] entry for one only makes `bop status` report drift)",
+];
+
+/// [`NEVER_TOUCHED`], and the kernel parameters bop leaves alone, from the
+/// lists `build_plan` works from.
+pub fn never_touched() -> Vec<String> {
+    use crate::audit::kernel_params::{REMOVED_PARAMS, SET_PARAMS};
+
+    let set: Vec<&str> = SET_PARAMS.iter().map(|p| p.name).collect();
+    let removed: Vec<String> = REMOVED_PARAMS
+        .iter()
+        .map(|(name, when)| format!("{} {}", name, when))
+        .collect();
+    let mut items: Vec<String> = NEVER_TOUCHED.iter().map(|s| s.to_string()).collect();
+    items.push(format!(
+        "Kernel parameters other than {} (a value the user gave one of these is replaced), \
+         except that it removes {}",
+        set.join(", "),
+        removed.join(", and ")
+    ));
+    items
+}
+
+/// Every path bop can change, grouped by [`Kind`] in display order.
+pub fn entries() -> Vec<Entry> {
+    use Kind::*;
+    let disarm_state = format!("/{}", crate::conflicts::DISARM_STATE);
+    let mut entries = vec![
+        Entry::new(
+            Tunable,
+            "/sys/devices/system/cpu/",
+            "CPU energy performance preference, energy_perf_bias, turbo boost",
+        ),
+        Entry::new(
+            Tunable,
+            crate::detect::platform::LEGACY_PLATFORM_PROFILE,
+            "platform profile",
+        ),
+        Entry::new(
+            Tunable,
+            "/sys/class/platform-profile/",
+            "platform profile, per handler (kernel 6.14+)",
+        ),
+        Entry::new(
+            Tunable,
+            "/sys/class/powercap/",
+            "CPU package long-term power limit (supersaver with [aggressive] package_power_limit_w)",
+        ),
+        Entry::new(
+            Tunable,
+            "/sys/module/",
+            "module parameters: PCIe ASPM policy, HDA audio power save",
+        ),
+        Entry::new(
+            Tunable,
+            "/sys/bus/pci/devices/",
+            "PCI runtime power management",
+        ),
+        Entry::new(
+            Tunable,
+            "/sys/bus/usb/devices/",
+            "USB autosuspend, empty ports powered off (supersaver; not re-applied at boot)",
+        ),
+        Entry::new(
+            Tunable,
+            "/sys/class/drm/",
+            "AMD GPU DPM level, panel ABM level",
+        ),
+        Entry::new(
+            Tunable,
+            "/sys/class/thermal/",
+            "PCIe bandwidth control (idle dGPU link speed)",
+        ),
+        Entry::new(Tunable, "/sys/power/mem_sleep", "suspend mode (AMD)"),
+        Entry::new(Tunable, "/proc/sys/kernel/nmi_watchdog", "NMI watchdog"),
+        Entry::new(
+            Tunable,
+            "/proc/sys/vm/dirty_writeback_centisecs",
+            "dirty page writeback interval",
+        ),
+        Entry::new(
+            Tunable,
+            crate::apply::sysfs_writer::ACPI_WAKEUP_PATH,
+            "ACPI wakeup sources (also `bop wake`, and `bop dbus-serve` before suspend)",
+        ),
+        Entry::new(
+            Tunable,
+            "/sys/class/backlight/",
+            "backlight brightness, with [brightness] auto_dim",
+        ),
+        Entry::new(
+            Generated,
+            systemd::SERVICE_PATH,
+            "persistence unit: re-applies the runtime settings at boot",
+        ),
+        Entry::new(
+            Generated,
+            format!("{}/{}", systemd::WANTS_DIR, systemd::SERVICE_NAME),
+            "link that enables the persistence unit",
+        ),
+        Entry::new(
+            Generated,
+            format!("{}/bop-*.conf", MODPROBE_DIR),
+            "module options for hardware quirks",
+        )
+        .reboot(),
+        Entry::new(
+            Generated,
+            wifi::NM_DROP_IN_PATH,
+            "WiFi power save for NetworkManager (next connect)",
+        ),
+        Entry::new(
+            Generated,
+            crate::apply::usb_ports::UDEV_RULE_PATH,
+            "udev rule re-enabling powered-off USB ports when their hub sees a device",
+        ),
+        Entry::new(
+            Generated,
+            crate::apply::sysctl::DROP_IN_PATH,
+            "sysctls of an image root, set by systemd-sysctl at its boot",
+        )
+        .reboot()
+        .only_via("bop apply --offline-root"),
+        Entry::new(
+            Generated,
+            crate::auto::UDEV_RULE_PATH,
+            "auto-switching rule",
+        )
+        .only_via("bop auto enable"),
+        Entry::new(
+            Generated,
+            crate::auto::LID_HOOK_PATH,
+            "acpid lid hook, with [auto] on_lid_close",
+        )
+        .only_via("bop auto enable"),
+        Entry::new(
+            Generated,
+            crate::notify::USER_UNIT_PATH,
+            "notification listener (user service)",
+        )
+        .only_via("bop auto enable --with-notify"),
+        Entry::new(
+            Generated,
+            format!(
+                "/run/systemd/transient/{}.*",
+                crate::apply::rollback::UNIT_NAME
+            ),
+            "automatic rollback timer, gone after it fires or a reboot",
+        )
+        .only_via("bop apply --rollback-after"),
+        Entry::new(
+            Edited,
+            format!("{}/", kernel_params::SYSTEMD_BOOT_ENTRIES_DIR),
+            "kernel command line of systemd-boot entries",
+        )
+        .reboot(),
+        Entry::new(
+            Edited,
+            kernel_params::GRUB_DEFAULT,
+            "kernel command line (GRUB_CMDLINE_LINUX_DEFAULT)",
+        )
+        .reboot(),
+    ];
+    #[cfg(feature = "dbus")]
+    entries.extend([
+        Entry::new(
+            Generated,
+            crate::dbus::UNIT_PATH,
+            "read-only D-Bus service for desktop extensions; re-disables wakeup sources before suspend",
+        )
+        .only_via("bop dbus-serve enable"),
+        Entry::new(
+            Generated,
+            crate::dbus::POLICY_PATH,
+            "system bus policy for the D-Bus service",
+        )
+        .only_via("bop dbus-serve enable"),
+        Entry::new(
+            State,
+            crate::wake::sleep::TIMING_PATH,
+            "whether the wakeup sources re-disabled before the last suspend made it in time",
+        )
+        .only_via("bop dbus-serve"),
+    ]);
+    for path in kernel_params::GRUB_CFG_PATHS {
+        entries.push(
+            Entry::new(
+                Edited,
+                *path,
+                "regenerated by grub-mkconfig after a GRUB edit, and again on revert",
+            )
+            .backup(None)
+            .reboot(),
+        );
+    }
+    for (path, what) in [
+        (
+            crate::conflicts::UDEV_RULES_DIR,
+            "conflicting udev rules (lines commented out)",
+        ),
+        (
+            crate::conflicts::SYSTEMD_DIR,
+            "conflicting units (disabled and stopped)",
+        ),
+        (
+            crate::conflicts::RC_LOCAL,
+            "conflicting rc.local lines (commented out)",
+        ),
+    ] {
+        let path = if path == crate::conflicts::RC_LOCAL {
+            format!("/{}", path)
+        } else {
+            format!("/{}/", path)
+        };
+        entries.push(
+            Entry::new(Edited, path, what)
+                .backup(Some(disarm_state.clone()))
+                .only_via("bop doctor --disarm"),
+        );
+    }
+    for service in CONFLICTING_SERVICES {
+        entries.push(Entry::new(
+            Service,
+            *service,
+            "power daemon that overwrites the same settings",
+        ));
+    }
+    entries.extend([
+        Entry::new(
+            State,
+            STATE_FILE,
+            "every change bop made, with the original values; `bop revert` reads it",
+        ),
+        Entry::new(
+            State,
+            crate::apply::journal::JOURNAL_FILE,
+            "originals of sysfs writes since the last state save; empty unless a run was interrupted",
+        ),
+        Entry::new(
+            State,
+            disarm_state,
+            "originals of files `bop doctor --disarm` changed",
+        ),
+        Entry::new(
+            State,
+            crate::idle_drain::HISTORY_PATH,
+            "idle power history (`bop monitor`)",
+        ),
+        Entry::new(
+            State,
+            crate::audit::upgrade::HISTORY_PATH,
+            "bop version and findings of the last audit, for upgrade notes",
+        ),
+        Entry::new(
+            State,
+            crate::calibration::RESULT_PATH,
+            "measured battery capacity (`bop battery calibrate`)",
+        ),
+        Entry::new(
+            State,
+            crate::crash::CRASH_DIR,
+            "crash bundles for bug reports (`--sysfs-snapshot-on-error`), newest 5",
+        ),
+        Entry::new(
+            State,
+            crate::config::SYSTEM_CONFIG,
+            "system config, written by `bop setup`",
+        ),
+        Entry::new(
+            State,
+            crate::lock::LOCK_PATH,
+            "lock held while an operation runs",
+        ),
+        Entry::new(State, crate::notify::EVENTS_PATH, "queued notifications"),
+        Entry::new(
+            State,
+            crate::status::cache::CACHE_PATH,
+            "last status check's results (`bop status --incremental`)",
+        ),
+        Entry::new(
+            State,
+            systemd::PERSIST_MARKER_PATH,
+            "marks that the persistence unit ran this boot",
+        ),
+    ]);
+    entries
+}
+
+/// The entry that covers `path`, if bop may change it at all.
+pub fn entry_for(path: &str) -> Option<Entry> {
+    entries().into_iter().find(|e| e.covers(path))
+}
+
+/// Plain-text report for `bop about-safety` and the end of `bop setup`.
+pub fn render() -> String {
+    let entries = entries();
+    let width = entries.iter().map(|e| e.path.len()).max().unwrap_or(0);
+    let mut out = String::from("What bop can change\n");
+    for kind in Kind::ALL {
+        out.push_str(&format!("\n{}:\n", kind.heading()));
+        for entry in entries.iter().filter(|e| e.kind == kind) {
+            let mut notes = Vec::new();
+            if let Some(command) = entry.only_via {
+                notes.push(format!("only `{}`", command));
+            }
+            if entry.reboot {
+                notes.push("after reboot".to_string());
+            }
+            let notes = if notes.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", notes.join(", "))
+            };
+            out.push_str(&format!(
+                "  {:<width$}  {}{}\n",
+                entry.path,
+                entry.what,
+                notes,
+                width = width
+            ));
+        }
+    }
+
+    let mut backups: Vec<&str> = entries.iter().filter_map(|e| e.backup.as_deref()).collect();
+    backups.sort_unstable();
+    backups.dedup();
+    out.push_str("\nBackups:\n");
+    for backup in backups {
+        let covered: Vec<&str> = Kind::ALL
+            .into_iter()
+            .filter(|kind| {
+                entries
+                    .iter()
+                    .any(|e| e.kind == *kind && e.backup.as_deref() == Some(backup))
+            })
+            .map(|kind| match kind {
+                Kind::Tunable => "original runtime values",
+                Kind::Edited => "original file contents",
+                Kind::Service => "which services were disabled",
+                _ => "",
+            })
+            .collect();
+        out.push_str(&format!("  {}  {}\n", backup, covered.join(", ")));
+    }
+
+    let reboot: Vec<&str> = entries
+        .iter()
+        .filter(|e| e.reboot)
+        .map(|e| e.path.as_str())
+        .collect();
+    out.push_str(&format!("\nNeeds a reboot: {}\n", reboot.join(", ")));
+
+    out.push_str("\nNever touched:\n");
+    for item in never_touched() {
+        out.push_str(&format!("  - {}\n", item));
+    }
+    out.push_str(
+        "\n`bop revert` undoes what apply and auto changed. `bop auto disable` removes the\n\
+         auto-switching rule and listener, and `bop doctor --undo` restores what --disarm changed.\n",
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_covers() {
+        let dir = Entry::new(Kind::Tunable, "/sys/bus/usb/devices/", "");
+        assert!(dir.covers("/sys/bus/usb/devices/1-1/power/control"));
+        assert!(dir.covers("/sys/bus/usb/devices"));
+        assert!(!dir.covers("/sys/bus/usb/drivers/usb/bind"));
+
+        let glob = Entry::new(Kind::Generated, "/etc/modprobe.d/bop-*.conf", "");
+        assert!(glob.covers("/etc/modprobe.d/bop-rtsx_pci.conf"));
+        assert!(!glob.covers("/etc/modprobe.d/blacklist.conf"));
+        assert!(!glob.covers("/etc/modprobe.d/bop-x/y.conf"));
+
+        let file = Entry::new(Kind::Tunable, "/sys/power/mem_sleep", "");
+        assert!(file.covers("/sys/power/mem_sleep"));
+        assert!(!file.covers("/sys/power/state"));
+    }
+
+    #[test]
+    fn test_render_lists_every_path_bop_writes() {
+        let text = render();
+        for path in [
+            STATE_FILE,
+            crate::apply::journal::JOURNAL_FILE,
+            systemd::SERVICE_PATH,
+            systemd::PERSIST_MARKER_PATH,
+            wifi::NM_DROP_IN_PATH,
+            kernel_params::GRUB_DEFAULT,
+            kernel_params::SYSTEMD_BOOT_ENTRIES_DIR,
+            crate::auto::UDEV_RULE_PATH,
+            crate::apply::usb_ports::UDEV_RULE_PATH,
+            crate::apply::sysctl::DROP_IN_PATH,
+            crate::auto::LID_HOOK_PATH,
+            crate::notify::USER_UNIT_PATH,
+            crate::notify::EVENTS_PATH,
+            crate::status::cache::CACHE_PATH,
+            crate::lock::LOCK_PATH,
+            crate::idle_drain::HISTORY_PATH,
+            crate::calibration::RESULT_PATH,
+            crate::crash::CRASH_DIR,
+            crate::config::SYSTEM_CONFIG,
+            crate::apply::sysfs_writer::ACPI_WAKEUP_PATH,
+            MODPROBE_DIR,
+            "/var/lib/bop/disarmed.json",
+            "/etc/rc.local",
+        ] {
+            assert!(text.contains(path), "{} missing from:\n{}", path, text);
+        }
+        for path in kernel_params::GRUB_CFG_PATHS {
+            assert!(text.contains(path), "{}", path);
+        }
+        #[cfg(feature = "dbus")]
+        for path in [
+            crate::dbus::UNIT_PATH,
+            crate::dbus::POLICY_PATH,
+            crate::wake::sleep::TIMING_PATH,
+        ] {
+            assert!(text.contains(path), "{}", path);
+        }
+        for service in CONFLICTING_SERVICES {
+            assert!(text.contains(service), "{}", service);
+        }
+        for entry in entries() {
+            assert!(text.contains(&entry.path), "{}", entry.path);
+        }
+    }
+
+    #[test]
+    fn test_charge_thresholds_are_never_touched() {
+        let path = "/sys/class/power_supply/BAT1/charge_control_end_threshold";
+        assert!(entry_for(path).is_none());
+        let text = render();
+        let never = text.split("\nNever touched:\n").nth(1).unwrap();
+        assert!(never.contains("charge_control_end_threshold"));
+    }
+
+    #[test]
+    fn test_never_touched_names_the_kernel_params_bop_changes() {
+        let items = never_touched();
+        let params = items.last().unwrap();
+        assert!(params.starts_with("Kernel parameters other than acpi.ec_no_wakeup, "));
+        assert!(params.contains("is replaced"));
+        assert!(params.ends_with(
+            "removes amdgpu.dcdebugmask when [display] force_psr asks, \
+             and amdgpu.abmlevel when it would fight the runtime ABM level"
+        ));
+    }
+
+    #[test]
+    fn test_backups_and_reboot_sections() {
+        let text = render();
+        let backups = text.split("\nBackups:\n").nth(1).unwrap();
+        assert!(backups.starts_with(&format!(
+            "  /var/lib/bop/disarmed.json  original file contents\n  {}  original runtime values, original file contents, which services were disabled\n",
+            STATE_FILE
+        )));
+        let reboot = text
+            .lines()
+            .find(|l| l.starts_with("Needs a reboot: "))
+            .unwrap();
+        assert!(reboot.contains(kernel_params::GRUB_DEFAULT));
+        assert!(reboot.contains("/etc/modprobe.d/bop-*.conf"));
+        assert!(!reboot.contains("/sys/"));
+    }
+}
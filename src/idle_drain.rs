@@ -0,0 +1,510 @@
+//! Idle drain watch for a stuck USB-C retimer. After some dock disconnects
+//! a Framework retimer can stay in a high-power state, drawing ~1.5W more
+//! until its port is power cycled, and nothing reports it. `bop monitor`
+//! compares idle battery draw against what this machine usually draws idle
+//! and records an alert in [`HISTORY_PATH`], which `bop audit` reports.
+
+use crate::config::IdleDrainConfig;
+use crate::error::{Error, Result};
+use crate::sysfs::SysfsRoot;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Idle samples and the current alert, rewritten once per sample.
+pub const HISTORY_PATH: &str = "/var/lib/bop/idle_drain.json";
+
+/// CPU pressure (PSI "some" avg10, percent) above which the machine is busy.
+pub const IDLE_MAX_CPU_PRESSURE: f64 = 5.0;
+/// RAPL core power above which the machine is busy.
+pub const IDLE_MAX_CPU_WATTS: f64 = 3.0;
+/// Idle readings are averaged over this long into one sample, which smooths
+/// the battery's noisy power_now.
+pub const SAMPLE_SECS: u64 = 60;
+/// Idle samples needed before the baseline is trusted: an hour's worth.
+pub const MIN_BASELINE_SAMPLES: usize = 60;
+/// A longer gap between samples (suspend, load, AC) ends a stretch of excess.
+pub const MAX_GAP_SECS: u64 = 5 * 60;
+/// Three days of one-minute samples.
+const MAX_SAMPLES: usize = 3 * 24 * 60;
+
+const CPU_PRESSURE_PATH: &str = "proc/pressure/cpu";
+
+/// Seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Mean battery draw over one idle [`SAMPLE_SECS`] window.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct IdleSample {
+    /// Unix time in seconds at the end of the window
+    pub time: u64,
+    pub watts: f64,
+}
+
+/// Idle draw that stayed above the baseline for the configured time.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DrainAlert {
+    /// When the excess began
+    pub since: u64,
+    /// The latest sample still in excess
+    pub last_seen: u64,
+    pub baseline_watts: f64,
+    pub watts: f64,
+}
+
+impl DrainAlert {
+    pub fn excess_watts(&self) -> f64 {
+        self.watts - self.baseline_watts
+    }
+
+    pub fn minutes(&self) -> u64 {
+        self.last_seen.saturating_sub(self.since) / 60
+    }
+
+    /// Whether the watch was still seeing the excess at `now`, rather than
+    /// the alert being left over from a monitor that has since stopped.
+    pub fn is_current(&self, now: u64) -> bool {
+        now.saturating_sub(self.last_seen) <= MAX_GAP_SECS
+    }
+}
+
+/// What the watch has seen, persisted between runs so the baseline
+/// outlives a single `bop monitor` session.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DrainHistory {
+    /// Normal idle samples, oldest first. Samples taken during an excess
+    /// are left out so a stuck retimer doesn't raise its own baseline.
+    pub samples: Vec<IdleSample>,
+    pub alert: Option<DrainAlert>,
+}
+
+impl DrainHistory {
+    /// The history at `path`; empty if it's missing or doesn't parse.
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let io_err =
+            |e: std::io::Error| Error::Other(format!("failed to write {}: {}", path.display(), e));
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_err)?;
+        }
+        let json = serde_json::to_string(self)
+            .map_err(|e| Error::Other(format!("failed to serialize idle drain history: {}", e)))?;
+        std::fs::write(path, json).map_err(io_err)
+    }
+
+    /// Keep `sample`, dropping the oldest past [`MAX_SAMPLES`].
+    pub fn record(&mut self, sample: IdleSample) {
+        self.samples.push(sample);
+        let excess = self.samples.len().saturating_sub(MAX_SAMPLES);
+        self.samples.drain(..excess);
+    }
+
+    /// Usual idle draw: the median sample, once there are
+    /// [`MIN_BASELINE_SAMPLES`] of them.
+    pub fn baseline(&self) -> Option<f64> {
+        if self.samples.len() < MIN_BASELINE_SAMPLES {
+            return None;
+        }
+        let watts: Vec<f64> = self.samples.iter().map(|s| s.watts).collect();
+        crate::stats::median(&watts)
+    }
+
+    /// The alert, if the watch was still seeing it at `now`.
+    pub fn current_alert(&self, now: u64) -> Option<DrainAlert> {
+        self.alert.filter(|a| a.is_current(now))
+    }
+}
+
+/// CPU pressure from PSI: the "some" avg10 percentage. `None` without PSI
+/// (kernels built without CONFIG_PSI, or booted with psi=0).
+pub fn cpu_pressure(sysfs: &SysfsRoot) -> Option<f64> {
+    let content = sysfs.read_optional(CPU_PRESSURE_PATH).ok()??;
+    parse_pressure(&content)
+}
+
+fn parse_pressure(content: &str) -> Option<f64> {
+    let line = content.lines().find(|l| l.starts_with("some "))?;
+    line.split_whitespace()
+        .find_map(|field| field.strip_prefix("avg10="))?
+        .parse()
+        .ok()
+}
+
+/// Idle by every signal available: CPU pressure and RAPL core power. With
+/// neither there is no telling, so not idle.
+pub fn is_idle(cpu_pressure: Option<f64>, cpu_watts: Option<f64>) -> bool {
+    if cpu_pressure.is_none() && cpu_watts.is_none() {
+        return false;
+    }
+    cpu_pressure.is_none_or(|p| p <= IDLE_MAX_CPU_PRESSURE)
+        && cpu_watts.is_none_or(|w| w <= IDLE_MAX_CPU_WATTS)
+}
+
+/// Averages idle readings into [`SAMPLE_SECS`] samples.
+#[derive(Debug, Default)]
+pub struct IdleWindow {
+    start: Option<u64>,
+    sum: f64,
+    count: u32,
+}
+
+impl IdleWindow {
+    /// Add an idle reading; returns the window's mean once it spans
+    /// [`SAMPLE_SECS`], and starts a new window.
+    pub fn push(&mut self, time: u64, watts: f64) -> Option<IdleSample> {
+        let start = *self.start.get_or_insert(time);
+        self.sum += watts;
+        self.count += 1;
+        if time.saturating_sub(start) < SAMPLE_SECS {
+            return None;
+        }
+        let sample = IdleSample {
+            time,
+            watts: self.sum / f64::from(self.count),
+        };
+        self.reset();
+        Some(sample)
+    }
+
+    /// A busy or charging reading: the window starts over.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// How the latest sample compares with the baseline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Normal,
+    /// Above the baseline by the margin, not yet for long enough
+    Rising {
+        since: u64,
+    },
+    /// Above the baseline for at least the configured time
+    Elevated {
+        since: u64,
+    },
+}
+
+/// Hysteresis over samples: an excess starts when a sample is more than the
+/// margin above the baseline, and only ends when one falls below half the
+/// margin or samples stop for more than [`MAX_GAP_SECS`]. Readings that
+/// wobble around the margin neither start nor end one.
+#[derive(Debug)]
+pub struct ExcessTracker {
+    margin_watts: f64,
+    sustain_secs: u64,
+    since: Option<u64>,
+    last: Option<u64>,
+}
+
+impl ExcessTracker {
+    pub fn new(margin_watts: f64, sustain_secs: u64) -> Self {
+        Self {
+            margin_watts,
+            sustain_secs,
+            since: None,
+            last: None,
+        }
+    }
+
+    pub fn from_config(config: &IdleDrainConfig) -> Self {
+        // A margin of zero would flag every sample above the median
+        Self::new(config.margin_watts.max(0.1), config.sustain.as_secs())
+    }
+
+    pub fn observe(&mut self, sample: IdleSample, baseline_watts: f64) -> Verdict {
+        if self
+            .last
+            .is_some_and(|last| sample.time.saturating_sub(last) > MAX_GAP_SECS)
+        {
+            self.since = None;
+        }
+        self.last = Some(sample.time);
+
+        let excess = sample.watts - baseline_watts;
+        match self.since {
+            Some(_) if excess < self.margin_watts / 2.0 => self.since = None,
+            None if excess > self.margin_watts => self.since = Some(sample.time),
+            _ => {}
+        }
+
+        match self.since {
+            None => Verdict::Normal,
+            Some(since) if sample.time - since >= self.sustain_secs => Verdict::Elevated { since },
+            Some(since) => Verdict::Rising { since },
+        }
+    }
+}
+
+/// The watch as `bop monitor` runs it: readings in, history kept on disk.
+pub struct DrainWatch {
+    path: PathBuf,
+    history: DrainHistory,
+    window: IdleWindow,
+    tracker: ExcessTracker,
+}
+
+impl DrainWatch {
+    pub fn new(config: &IdleDrainConfig, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self {
+            history: DrainHistory::load(&path),
+            path,
+            window: IdleWindow::default(),
+            tracker: ExcessTracker::from_config(config),
+        }
+    }
+
+    /// Feed one reading taken at `time`: the battery draw while discharging
+    /// (`None` on AC) and whether the machine was idle. Returns the alert
+    /// when it newly trips. History is saved once per sample; failing to
+    /// save (not root) just keeps it in memory.
+    pub fn observe(
+        &mut self,
+        time: u64,
+        idle: bool,
+        battery_watts: Option<f64>,
+    ) -> Option<DrainAlert> {
+        let Some(watts) = battery_watts.filter(|_| idle) else {
+            self.window.reset();
+            return None;
+        };
+        let sample = self.window.push(time, watts)?;
+
+        let mut tripped = None;
+        match self.history.baseline() {
+            None => self.history.record(sample),
+            Some(baseline_watts) => match self.tracker.observe(sample, baseline_watts) {
+                Verdict::Normal => {
+                    self.history.record(sample);
+                    self.history.alert = None;
+                }
+                Verdict::Rising { .. } => {}
+                Verdict::Elevated { since } => {
+                    let known = self.history.current_alert(time).is_some();
+                    let alert = DrainAlert {
+                        since,
+                        last_seen: time,
+                        baseline_watts,
+                        watts: sample.watts,
+                    };
+                    self.history.alert = Some(alert);
+                    if !known {
+                        tripped = Some(alert);
+                    }
+                }
+            },
+        }
+        let _ = self.history.save(&self.path);
+        tripped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn sample(minute: u64, watts: f64) -> IdleSample {
+        IdleSample {
+            time: minute * 60,
+            watts,
+        }
+    }
+
+    /// Verdicts for one sample a minute at `watts`, starting at minute 0.
+    fn verdicts(tracker: &mut ExcessTracker, baseline: f64, watts: &[f64]) -> Vec<Verdict> {
+        watts
+            .iter()
+            .enumerate()
+            .map(|(minute, &w)| tracker.observe(sample(minute as u64, w), baseline))
+            .collect()
+    }
+
+    fn history_at(watts: f64, count: usize) -> DrainHistory {
+        let mut history = DrainHistory::default();
+        for minute in 0..count as u64 {
+            history.record(sample(minute, watts));
+        }
+        history
+    }
+
+    #[test]
+    fn test_parse_pressure() {
+        let psi = "some avg10=1.53 avg60=0.87 avg300=0.40 total=12345\n\
+                   full avg10=0.00 avg60=0.00 avg300=0.00 total=0\n";
+        assert_eq!(parse_pressure(psi), Some(1.53));
+        assert_eq!(parse_pressure("full avg10=2.00\n"), None);
+        assert_eq!(parse_pressure(""), None);
+    }
+
+    #[test]
+    fn test_is_idle_needs_every_available_signal() {
+        assert!(is_idle(Some(0.5), Some(1.0)));
+        assert!(is_idle(Some(0.5), None));
+        assert!(is_idle(None, Some(1.0)));
+        assert!(!is_idle(Some(20.0), Some(1.0)));
+        assert!(!is_idle(Some(0.5), Some(8.0)));
+        assert!(!is_idle(None, None));
+    }
+
+    #[test]
+    fn test_idle_window_averages_a_minute() {
+        let mut window = IdleWindow::default();
+        assert_eq!(window.push(0, 6.0), None);
+        assert_eq!(window.push(30, 7.0), None);
+        assert_eq!(
+            window.push(60, 8.0),
+            Some(IdleSample {
+                time: 60,
+                watts: 7.0
+            })
+        );
+        // The next window starts fresh
+        assert_eq!(window.push(62, 5.0), None);
+        window.reset();
+        assert_eq!(window.push(100, 5.0), None);
+        assert_eq!(window.push(159, 5.0), None);
+    }
+
+    #[test]
+    fn test_baseline_needs_enough_samples_and_ignores_outliers() {
+        let mut history = history_at(6.0, MIN_BASELINE_SAMPLES - 1);
+        assert_eq!(history.baseline(), None);
+
+        history.record(sample(100, 6.0));
+        assert_eq!(history.baseline(), Some(6.0));
+
+        // A handful of heavy samples doesn't move the median
+        for minute in 200..210 {
+            history.record(sample(minute, 12.0));
+        }
+        assert_eq!(history.baseline(), Some(6.0));
+    }
+
+    #[test]
+    fn test_history_keeps_the_newest_samples() {
+        let history = history_at(6.0, MAX_SAMPLES + 10);
+        assert_eq!(history.samples.len(), MAX_SAMPLES);
+        assert_eq!(history.samples[0].time, 10 * 60);
+    }
+
+    #[test]
+    fn test_tracker_needs_sustained_excess() {
+        let mut tracker = ExcessTracker::new(1.0, 10 * 60);
+        let mut watts = vec![6.2; 3];
+        watts.extend([7.6; 11]);
+        let seen = verdicts(&mut tracker, 6.0, &watts);
+
+        assert!(seen[..3].iter().all(|v| *v == Verdict::Normal));
+        assert_eq!(seen[3], Verdict::Rising { since: 180 });
+        assert_eq!(seen[12], Verdict::Rising { since: 180 });
+        assert_eq!(seen[13], Verdict::Elevated { since: 180 });
+    }
+
+    #[test]
+    fn test_tracker_ignores_draw_within_margin() {
+        let mut tracker = ExcessTracker::new(1.0, 10 * 60);
+        // Exactly the margin above is not excess
+        let seen = verdicts(&mut tracker, 6.0, &[7.0; 30]);
+        assert!(seen.iter().all(|v| *v == Verdict::Normal));
+    }
+
+    #[test]
+    fn test_tracker_hysteresis() {
+        let mut tracker = ExcessTracker::new(1.0, 10 * 60);
+        // Dips to 6.7W (0.7W over) stay inside the band and keep the excess
+        let mut watts = vec![7.5, 6.7, 7.5, 6.7, 7.5, 6.7, 7.5, 6.7, 7.5, 6.7, 7.5];
+        let seen = verdicts(&mut tracker, 6.0, &watts);
+        assert_eq!(seen[10], Verdict::Elevated { since: 0 });
+
+        // Falling below half the margin ends it
+        let mut tracker = ExcessTracker::new(1.0, 10 * 60);
+        watts[5] = 6.4;
+        let seen = verdicts(&mut tracker, 6.0, &watts);
+        assert_eq!(seen[5], Verdict::Normal);
+        assert_eq!(seen[6], Verdict::Rising { since: 360 });
+        assert_eq!(seen[10], Verdict::Rising { since: 360 });
+    }
+
+    #[test]
+    fn test_tracker_starts_over_after_a_gap() {
+        let mut tracker = ExcessTracker::new(1.0, 10 * 60);
+        for minute in 0..8 {
+            tracker.observe(sample(minute, 7.5), 6.0);
+        }
+        // Six minutes without a sample (busy, suspended or on AC)
+        assert_eq!(
+            tracker.observe(sample(14, 7.5), 6.0),
+            Verdict::Rising { since: 14 * 60 }
+        );
+    }
+
+    #[test]
+    fn test_alert_goes_stale() {
+        let alert = DrainAlert {
+            since: 0,
+            last_seen: 1200,
+            baseline_watts: 6.0,
+            watts: 7.5,
+        };
+        assert_eq!(alert.minutes(), 20);
+        assert_eq!(alert.excess_watts(), 1.5);
+        assert!(alert.is_current(1200 + MAX_GAP_SECS));
+        assert!(!alert.is_current(1201 + MAX_GAP_SECS));
+    }
+
+    #[test]
+    fn test_watch_trips_once_and_keeps_baseline_clean() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("idle_drain.json");
+        history_at(6.0, MIN_BASELINE_SAMPLES).save(&path).unwrap();
+
+        let mut watch = DrainWatch::new(&IdleDrainConfig::default(), &path);
+        let start = 10_000;
+        let mut alerts = Vec::new();
+        // 20 minutes idle at 7.5W, one reading every 2s
+        for t in (start..start + 20 * 60 + 1).step_by(2) {
+            alerts.extend(watch.observe(t, true, Some(7.5)));
+        }
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].baseline_watts, 6.0);
+        assert!(alerts[0].minutes() >= 10);
+
+        let saved = DrainHistory::load(&path);
+        assert_eq!(saved.samples.len(), MIN_BASELINE_SAMPLES);
+        assert!(saved.current_alert(start + 20 * 60).is_some());
+
+        // Back to normal clears it
+        for t in (start + 20 * 60 + 2..start + 22 * 60 + 2).step_by(2) {
+            watch.observe(t, true, Some(6.0));
+        }
+        assert_eq!(DrainHistory::load(&path).alert, None);
+    }
+
+    #[test]
+    fn test_watch_skips_busy_and_charging_readings() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("idle_drain.json");
+        let mut watch = DrainWatch::new(&IdleDrainConfig::default(), &path);
+
+        for t in (0..600).step_by(2) {
+            watch.observe(t, false, Some(9.0));
+            watch.observe(t + 1, true, None);
+        }
+        assert!(DrainHistory::load(&path).samples.is_empty());
+    }
+}
@@ -20,7 +20,7 @@ pub enum ApplyScope {
 
 /// Check for active systemd inhibitors by parsing `systemd-inhibit --list`.
 pub fn check_inhibitors() -> Result<Vec<Inhibitor>> {
-    let output = std::process::Command::new("systemd-inhibit")
+    let output = crate::cmd::Cmd::new("systemd-inhibit")
         .args(["--list", "--no-pager", "--no-legend"])
         .output()?;
 
@@ -1,19 +1,45 @@
 pub mod apply;
 pub mod audit;
+#[cfg(feature = "cli")]
 pub mod auto;
 pub mod brightness;
+pub mod calibration;
+#[cfg(feature = "cli")]
 pub mod cli;
+pub mod cmd;
 pub mod config;
+pub mod conflicts;
+pub mod crash;
+#[cfg(feature = "dbus")]
+pub mod dbus;
 pub mod detect;
+pub mod duration;
 pub mod error;
+#[cfg(feature = "cli")]
+pub mod footprint;
+pub mod idle_drain;
 pub mod inhibitors;
+pub mod lock;
+pub mod managed;
+#[cfg(feature = "cli")]
 pub mod monitor;
 pub mod notify;
+#[cfg(feature = "cli")]
 pub mod output;
+pub mod pins;
 pub mod preset;
 pub mod profile;
+pub mod progress;
+pub mod prompt;
+pub mod quiet_hours;
+pub mod quirks;
+pub mod report;
 pub mod revert;
+#[cfg(feature = "cli")]
+pub mod setup;
 pub mod snapshot;
+pub mod stats;
 pub mod status;
 pub mod sysfs;
 pub mod wake;
+pub mod watch;
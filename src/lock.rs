@@ -0,0 +1,237 @@
+use crate::error::{Error, Result};
+use nix::errno::Errno;
+use nix::fcntl::{Flock, FlockArg};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Shared by every command that changes system state or the state file.
+pub const LOCK_PATH: &str = "/run/bop/op.lock";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Exclusive flock on the operation lock file. The lock file records
+/// "PID OPERATION" for whoever holds it; the flock is released on drop.
+#[derive(Debug)]
+pub struct OpLock {
+    file: Flock<File>,
+}
+
+impl Drop for OpLock {
+    fn drop(&mut self) {
+        // Clear the holder info before the flock itself goes away
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// Take the system-wide operation lock for `operation` ("apply", "revert", ...).
+/// Fails immediately if another bop operation holds it, or after `wait` elapses.
+pub fn acquire(operation: &str, wait: Option<Duration>) -> Result<OpLock> {
+    acquire_at(Path::new(LOCK_PATH), operation, wait)
+}
+
+/// Same as [`acquire`], with an explicit lock file path.
+pub fn acquire_at(path: &Path, operation: &str, wait: Option<Duration>) -> Result<OpLock> {
+    if let Some(dir) = path.parent()
+        && !dir.as_os_str().is_empty()
+    {
+        fs::create_dir_all(dir)
+            .map_err(|e| Error::Other(format!("failed to create {}: {}", dir.display(), e)))?;
+    }
+
+    let deadline = wait.map(|w| Instant::now() + w);
+    loop {
+        match try_lock(path, operation)? {
+            Attempt::Acquired(lock) => return Ok(lock),
+            Attempt::Held(holder) => {
+                match deadline {
+                    Some(deadline) if Instant::now() < deadline => {
+                        std::thread::sleep(POLL_INTERVAL);
+                    }
+                    _ => {
+                        // A dead holder's flock lives on in whatever inherited
+                        // the descriptor. The file is never unlinked: a second
+                        // file would let two processes hold "the" lock at once.
+                        return Err(Error::Locked(match holder {
+                            Some((pid, op)) if !pid_alive(pid) => {
+                                format!("{}, pid {} exited; held by a process it started", op, pid)
+                            }
+                            Some((pid, op)) => format!("{}, pid {}", op, pid),
+                            None => "unknown".to_string(),
+                        }));
+                    }
+                }
+            }
+        }
+    }
+}
+
+enum Attempt {
+    Acquired(OpLock),
+    /// Holder's PID and operation, when the lock file names them
+    Held(Option<(u32, String)>),
+}
+
+/// One non-blocking attempt at the lock.
+fn try_lock(path: &Path, operation: &str) -> Result<Attempt> {
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(|e| Error::Other(format!("failed to open {}: {}", path.display(), e)))?;
+
+    match Flock::lock(file, FlockArg::LockExclusiveNonblock) {
+        Ok(mut file) => {
+            file.set_len(0)
+                .and_then(|_| write!(&mut *file, "{} {}", std::process::id(), operation))
+                .map_err(|e| Error::Other(format!("failed to write {}: {}", path.display(), e)))?;
+            Ok(Attempt::Acquired(OpLock { file }))
+        }
+        Err((_, Errno::EWOULDBLOCK)) => Ok(Attempt::Held(read_holder(path))),
+        Err((_, errno)) => Err(Error::Other(format!(
+            "failed to lock {}: {}",
+            path.display(),
+            errno
+        ))),
+    }
+}
+
+/// Parse "PID OPERATION" from the lock file. Older auto locks held the PID only.
+fn read_holder(path: &Path) -> Option<(u32, String)> {
+    let contents = fs::read_to_string(path).ok()?;
+    let mut parts = contents.trim().splitn(2, ' ');
+    let pid = parts.next()?.parse().ok()?;
+    let operation = parts.next().unwrap_or("unknown").to_string();
+    Some((pid, operation))
+}
+
+fn pid_alive(pid: u32) -> bool {
+    PathBuf::from(format!("/proc/{}", pid)).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_competing_operations_exactly_one_proceeds() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("run/bop/op.lock");
+        let barrier = Arc::new(Barrier::new(2));
+
+        let handles: Vec<_> = ["apply", "revert"]
+            .into_iter()
+            .map(|op| {
+                let path = path.clone();
+                let barrier = Arc::clone(&barrier);
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    let result = acquire_at(&path, op, None);
+                    // Hold until both attempts are done
+                    barrier.wait();
+                    result.map(|_| ()).map_err(|e| e.to_string())
+                })
+            })
+            .collect();
+
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+        let err = results.iter().find_map(|r| r.as_ref().err()).unwrap();
+        assert!(err.starts_with("another bop operation ("), "{}", err);
+    }
+
+    #[test]
+    fn test_lock_records_holder_and_clears_on_drop() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("op.lock");
+
+        let lock = acquire_at(&path, "apply", None).unwrap();
+        assert_eq!(
+            read_holder(&path),
+            Some((std::process::id(), "apply".to_string()))
+        );
+        let err = acquire_at(&path, "revert", None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "another bop operation (apply, pid {}) is running",
+                std::process::id()
+            )
+        );
+
+        drop(lock);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        assert!(acquire_at(&path, "revert", None).is_ok());
+    }
+
+    #[test]
+    fn test_wait_blocks_until_released() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("op.lock");
+
+        let lock = acquire_at(&path, "auto", None).unwrap();
+        let releaser = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            drop(lock);
+        });
+
+        let started = Instant::now();
+        assert!(acquire_at(&path, "apply", Some(Duration::from_secs(10))).is_ok());
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        releaser.join().unwrap();
+    }
+
+    #[test]
+    fn test_wait_times_out() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("op.lock");
+
+        let _lock = acquire_at(&path, "auto", None).unwrap();
+        let err = acquire_at(&path, "apply", Some(Duration::from_millis(250))).unwrap_err();
+        assert!(matches!(err, Error::Locked(_)));
+    }
+
+    #[test]
+    fn test_holder_left_by_dead_pid_is_rewritten() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("op.lock");
+
+        // Not held, but still naming a process that no longer exists
+        fs::write(&path, format!("{} apply", i32::MAX)).unwrap();
+
+        let lock = acquire_at(&path, "revert", None).unwrap();
+        assert_eq!(
+            read_holder(&path),
+            Some((std::process::id(), "revert".to_string()))
+        );
+        drop(lock);
+    }
+
+    #[test]
+    fn test_lock_held_past_its_dead_pid_is_not_stolen() {
+        use std::os::unix::fs::MetadataExt;
+
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("op.lock");
+
+        // Held (say by a child that inherited it), naming a dead process
+        let file = File::create(&path).unwrap();
+        let mut held = Flock::lock(file, FlockArg::LockExclusiveNonblock).unwrap();
+        write!(&mut *held, "{} apply", i32::MAX).unwrap();
+        let inode = fs::metadata(&path).unwrap().ino();
+
+        let err = acquire_at(&path, "revert", Some(Duration::from_millis(250))).unwrap_err();
+        assert!(matches!(err, Error::Locked(_)));
+        assert!(err.to_string().contains("exited"), "{}", err);
+        assert_eq!(fs::metadata(&path).unwrap().ino(), inode);
+
+        drop(held);
+        assert!(acquire_at(&path, "revert", None).is_ok());
+        assert_eq!(fs::metadata(&path).unwrap().ino(), inode);
+    }
+}
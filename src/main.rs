@@ -1,72 +1,238 @@
 use anyhow::Result;
-use bop::cli::{AutoAction, Cli, Command, ConfigAction, WakeAction};
+use bop::apply::offline::OfflineRoot;
+use bop::audit::PowerContext;
+use bop::cli::{
+    AutoAction, BatteryAction, Cli, Command, ConfigAction, MonitorAction, ProfileAction, WakeAction,
+};
 use bop::config::BopConfig;
 use bop::detect::HardwareInfo;
+use bop::output::Terminal;
 use bop::preset::Preset;
+use bop::prompt::Answer;
 use bop::sysfs::SysfsRoot;
 use clap::Parser;
 use colored::Colorize;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    let config = bop::config::load(cli.config.as_ref());
+    let snapshot_on_error = cli.snapshot_on_error();
+    let result = run(cli);
+    if let Err(err) = &result
+        && snapshot_on_error
+    {
+        save_crash_bundle(err);
+    }
+    result
+}
+
+fn run(cli: Cli) -> Result<()> {
+    if let Some(dir) = &cli.state_dir {
+        bop::apply::set_state_dir(dir);
+    }
+    let (config, config_issues) = bop::config::load_checked(cli.config.as_ref());
+    // `config validate` reports them itself
+    if !matches!(
+        cli.command,
+        Command::Config {
+            action: ConfigAction::Validate
+        }
+    ) {
+        bop::config::warn_issues(&config_issues);
+    }
+    bop::cmd::set_timeouts(config.commands.timeouts());
     let cli_preset = cli.effective_preset();
 
     match cli.command {
-        Command::Audit => cmd_audit(cli.json, cli_preset, &config)?,
-        Command::Apply { dry_run } => cmd_apply(dry_run, cli_preset, &config)?,
-        Command::Monitor => cmd_monitor()?,
-        Command::Revert => cmd_revert()?,
-        Command::Status => cmd_status(cli.json)?,
-        Command::Auto { action } => {
-            cmd_auto(action, cli_preset, &config, cli.json, cli.config.as_deref())?
+        Command::Audit {
+            watch_config,
+            context,
+            explain,
+            verbose_json,
+            verbose,
+            oneline,
+        } => {
+            if oneline {
+                cmd_audit_oneline(cli_preset, &config, context)
+            } else if watch_config {
+                cmd_audit_watch(cli_preset, cli.config.as_ref(), context, explain)?
+            } else {
+                let output = AuditOutput {
+                    json: cli.json || verbose_json,
+                    verbose_json,
+                    explain,
+                    verbose,
+                };
+                cmd_audit(output, cli_preset, &config, context)?
+            }
+        }
+        Command::Apply {
+            wait,
+            confirm_keep: true,
+            ..
+        } => cmd_confirm_keep(wait.map(Duration::from))?,
+        Command::Apply {
+            dry_run,
+            explain,
+            report,
+            wait,
+            offline_root,
+            target_snapshot,
+            force,
+            force_disable,
+            confirm_timeout,
+            rollback_after,
+            confirm_keep: false,
+        } => cmd_apply(
+            ApplyFlags {
+                dry_run,
+                explain,
+                report: report.as_deref(),
+                wait: wait.map(Duration::from),
+                offline_root: offline_root.as_deref(),
+                target_snapshot: target_snapshot.as_deref(),
+                force,
+                force_disable,
+                confirm_timeout: confirm_timeout.map(Duration::from),
+                rollback_after: rollback_after.map(Duration::from),
+            },
+            cli_preset,
+            &config,
+        )?,
+        Command::Setup {
+            auto,
+            dim,
+            apply,
+            yes,
+        } => {
+            let flags = bop::setup::SetupFlags {
+                auto,
+                preset: cli_preset,
+                dim,
+                apply,
+                yes,
+            };
+            cmd_setup(flags, &config)?
         }
+        Command::Monitor {
+            action,
+            log,
+            resume,
+        } => cmd_monitor(action, log.as_deref(), resume, &config)?,
+        Command::Battery { action } => cmd_battery(action)?,
+        Command::Profile { action } => cmd_profile(action, cli.json)?,
+        Command::Revert {
+            only,
+            wait,
+            force,
+            quiet,
+        } => cmd_revert(only, wait.map(Duration::from), force, quiet)?,
+        Command::UpgradeArtifacts { force } => cmd_upgrade_artifacts(force)?,
+        Command::Rescue {
+            root,
+            dry_run,
+            all,
+            force,
+        } => cmd_rescue(&root, dry_run, all, force)?,
+        Command::Status {
+            explain_drift,
+            incremental,
+        } => cmd_status(cli.json, explain_drift, incremental, &config)?,
+        Command::AboutSafety => cmd_about_safety(cli.json)?,
+        Command::Doctor {
+            disarm,
+            undo,
+            probe,
+        } => cmd_doctor(disarm, undo, probe, cli.json, cli_preset, &config)?,
+        Command::Auto {
+            action,
+            report,
+            wait,
+            force,
+        } => cmd_auto(
+            action,
+            AutoFlags {
+                report: report.as_deref(),
+                wait: wait.map(Duration::from),
+                force,
+            },
+            cli_preset,
+            &config,
+            cli.json,
+            cli.config.as_deref(),
+        )?,
         Command::Snapshot { output } => cmd_snapshot(output)?,
         Command::Wake { action } => cmd_wake(action)?,
-        Command::Config { action } => cmd_config(action, &config)?,
+        Command::Config { action } => cmd_config(action, &config, &config_issues, cli.json)?,
+        Command::NotifyListen => bop::notify::listen(bop::watch::interrupt_flag())?,
+        #[cfg(feature = "dbus")]
+        Command::DbusServe { action, force } => cmd_dbus_serve(action, force, cli_preset, &config)?,
         Command::Completions { shell } => bop::cli::print_completions(shell),
     }
 
     Ok(())
 }
 
-fn cmd_audit(json: bool, cli_preset: Option<Preset>, config: &BopConfig) -> Result<()> {
+/// Save a crash bundle for `err`. A problem saving it is only a warning;
+/// the error being reported is what `main` returns either way.
+fn save_crash_bundle(err: &anyhow::Error) {
+    let command = std::env::args().collect::<Vec<_>>().join(" ");
+    let chain = err.chain().map(ToString::to_string).collect();
+    let bundle = bop::crash::CrashBundle::capture(&SysfsRoot::system(), &command, chain);
+    match bundle.save_in(Path::new(bop::crash::CRASH_DIR)) {
+        Ok(path) => eprintln!(
+            "Crash bundle saved to {}; attach it to a bug report.",
+            path.display()
+        ),
+        Err(e) => eprintln!("{} Failed to save crash bundle: {}", "!".yellow(), e),
+    }
+}
+
+/// How `bop audit` reports its findings.
+struct AuditOutput {
+    json: bool,
+    verbose_json: bool,
+    explain: bool,
+    /// Print the matched profile's notes
+    verbose: bool,
+}
+
+fn cmd_audit(
+    output: AuditOutput,
+    cli_preset: Option<Preset>,
+    config: &BopConfig,
+    context: Option<PowerContext>,
+) -> Result<()> {
     let effective_preset = bop::config::resolve_preset(config, cli_preset);
     let mut knobs = bop::config::resolve_knobs(config, effective_preset);
 
     let sysfs = SysfsRoot::system();
-    let hw = HardwareInfo::detect(&sysfs);
+    let hw = HardwareInfo::detect_all(&sysfs);
 
     // Resolve adaptive EPP so audit sees the same target as apply
-    if knobs.epp.is_some()
-        && let Some(resolved) = bop::apply::resolve_epp(
-            &config.epp,
-            hw.battery.capacity_percent,
-            &knobs,
-            knobs.epp_locked,
-        )
-    {
-        knobs.epp = Some(std::borrow::Cow::Owned(resolved));
-    }
+    resolve_adaptive_epp(config, &hw, &mut knobs);
 
     // Find matching profile
     let profile = bop::profile::detect_profile(&hw);
 
-    if json {
+    if output.json {
         let (findings, score) = match &profile {
             Some(p) => {
-                let findings = p.audit_with_opts(&hw, effective_preset, &knobs);
+                let mut findings = p.audit_with_opts(&hw, effective_preset, &knobs);
+                bop::audit::annotate_pins(&mut findings, &config.pins);
                 let score = bop::audit::calculate_score(&findings);
                 (findings, score)
             }
             None => (Vec::new(), 100),
         };
-        let profile_name = profile
-            .as_ref()
-            .map(|p| p.name())
-            .unwrap_or("Unknown (generic)");
-        bop::output::print_audit_json(&hw, &findings, score, profile_name);
+        bop::output::print_audit_json(
+            &hw,
+            &findings,
+            score,
+            profile.as_deref(),
+            output.verbose_json,
+        );
         return Ok(());
     }
 
@@ -81,10 +247,30 @@ fn cmd_audit(json: bool, cli_preset: Option<Preset>, config: &BopConfig) -> Resu
     match profile {
         Some(ref p) => {
             println!("  {} {}", "Matched profile:".bold(), p.name().green());
+            if output.verbose {
+                println!();
+                bop::output::print_profile_notes(p.as_ref());
+            }
 
-            let findings = p.audit_with_opts(&hw, effective_preset, &knobs);
-            let score = bop::audit::calculate_score(&findings);
-            bop::output::print_audit_findings(&findings, score);
+            let mut findings = p.audit_with_opts(&hw, effective_preset, &knobs);
+            bop::audit::annotate_pins(&mut findings, &config.pins);
+
+            let view = context.unwrap_or_else(|| PowerContext::current(&hw.ac));
+
+            use bop::audit::upgrade::{AuditHistory, history_path, upgrade_notes};
+            let history = AuditHistory::current(&findings, effective_preset, view);
+            let last = AuditHistory::load(&history_path());
+            if let Some(notes) = last.as_ref().and_then(|l| upgrade_notes(l, &history))
+                && !notes.is_empty()
+            {
+                println!();
+                bop::output::print_upgrade_notes(&notes);
+            }
+            if last.as_ref().is_none_or(|l| history.replaces(l)) {
+                history.save(&history_path());
+            }
+
+            bop::output::print_audit_findings(&findings, view, output.explain);
 
             if !findings.is_empty() {
                 let preset_flag = if cli_preset.is_some() || effective_preset != Preset::Moderate {
@@ -116,23 +302,226 @@ fn cmd_audit(json: bool, cli_preset: Option<Preset>, config: &BopConfig) -> Resu
     Ok(())
 }
 
-fn cmd_apply(dry_run: bool, cli_preset: Option<Preset>, config: &BopConfig) -> Result<()> {
+/// `bop audit --oneline`. Informational, so it never fails.
+fn cmd_audit_oneline(
+    cli_preset: Option<Preset>,
+    config: &BopConfig,
+    context: Option<PowerContext>,
+) {
+    use std::io::IsTerminal;
+
+    let (findings, hw) = quick_findings(config, cli_preset);
+    let view = context.unwrap_or_else(|| PowerContext::current(&hw.ac));
+    let color = std::io::stdout().is_terminal();
+    println!(
+        "{}",
+        bop::output::audit_oneline(findings.as_deref(), view, color)
+    );
+}
+
+/// Findings from the sysfs-only audit (None when no profile matches), and
+/// the hardware they were judged on.
+fn quick_findings(
+    config: &BopConfig,
+    cli_preset: Option<Preset>,
+) -> (Option<Vec<bop::audit::Finding>>, HardwareInfo) {
     let effective_preset = bop::config::resolve_preset(config, cli_preset);
-    let knobs = bop::config::resolve_knobs(config, effective_preset);
+    let mut knobs = bop::config::resolve_knobs(config, effective_preset);
+    let sysfs = SysfsRoot::system();
+    let hw = HardwareInfo::detect_all(&sysfs);
+    resolve_adaptive_epp(config, &hw, &mut knobs);
+
+    let findings = bop::profile::detect_profile(&hw).map(|p| {
+        p.audit_in(
+            &bop::audit::AuditContext::quick(&sysfs),
+            &hw,
+            effective_preset,
+            &knobs,
+        )
+    });
+    (findings, hw)
+}
+
+/// Findings for the current system under `config` (empty when no profile
+/// matches) and the context to judge them in.
+fn audit_findings(
+    config: &BopConfig,
+    cli_preset: Option<Preset>,
+    context: Option<PowerContext>,
+) -> (Vec<bop::audit::Finding>, PowerContext) {
+    let effective_preset = bop::config::resolve_preset(config, cli_preset);
+    let mut knobs = bop::config::resolve_knobs(config, effective_preset);
 
     let sysfs = SysfsRoot::system();
-    let hw = HardwareInfo::detect(&sysfs);
+    let hw = HardwareInfo::detect_all(&sysfs);
+    resolve_adaptive_epp(config, &hw, &mut knobs);
 
-    let profile = bop::profile::detect_profile(&hw);
-    if profile.is_none() {
+    let view = context.unwrap_or_else(|| PowerContext::current(&hw.ac));
+    let mut findings = match bop::profile::detect_profile(&hw) {
+        Some(p) => p.audit_with_opts(&hw, effective_preset, &knobs),
+        None => Vec::new(),
+    };
+    bop::audit::annotate_pins(&mut findings, &config.pins);
+    (findings, view)
+}
+
+fn cmd_audit_watch(
+    cli_preset: Option<Preset>,
+    config_path: Option<&PathBuf>,
+    context: Option<PowerContext>,
+    explain: bool,
+) -> Result<()> {
+    let paths: Vec<PathBuf> = match config_path {
+        Some(path) => vec![path.clone()],
+        None => std::iter::once(PathBuf::from(bop::config::SYSTEM_CONFIG))
+            .chain(bop::config::user_config_path())
+            .collect(),
+    };
+
+    let stop = bop::watch::interrupt_flag();
+    let mut watcher = bop::watch::FileWatcher::new(paths.clone());
+
+    let config = bop::config::load(config_path);
+    let (mut findings, view) = audit_findings(&config, cli_preset, context);
+    let mut score = bop::audit::context_score(&findings, view);
+    bop::output::print_audit_findings(&findings, view, explain);
+    println!();
+    println!(
+        "Watching {} ({}). Press Ctrl+C to stop.",
+        paths
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        if watcher.uses_inotify() {
+            "inotify"
+        } else {
+            "polling"
+        }
+    );
+
+    while watcher.wait_for_change(Duration::from_millis(300), stop) {
+        let config = bop::config::load(config_path);
+        let (new_findings, view) = audit_findings(&config, cli_preset, context);
+        let new_score = bop::audit::context_score(&new_findings, view);
+        let diff = bop::audit::diff_findings(&findings, &new_findings);
+
+        println!();
+        println!(
+            "{} {}",
+            chrono::Local::now().format("%H:%M:%S").to_string().dimmed(),
+            "Config changed, re-audited".bold()
+        );
+        bop::output::print_findings_diff(&diff, score, new_score);
+
+        findings = new_findings;
+        score = new_score;
+    }
+
+    println!();
+    println!("Stopped watching.");
+    Ok(())
+}
+
+fn resolve_adaptive_epp(
+    config: &BopConfig,
+    hw: &HardwareInfo,
+    knobs: &mut bop::preset::PresetKnobs,
+) {
+    if knobs.epp.is_some()
+        && let Some(resolved) = bop::apply::resolve_epp(
+            &config.epp,
+            hw.battery.capacity_percent,
+            knobs,
+            knobs.epp_locked,
+        )
+    {
+        knobs.epp = Some(std::borrow::Cow::Owned(resolved));
+    }
+}
+
+/// Flags of `bop apply`; setup's final apply uses the defaults.
+#[derive(Default)]
+struct ApplyFlags<'a> {
+    dry_run: bool,
+    explain: bool,
+    report: Option<&'a Path>,
+    wait: Option<Duration>,
+    offline_root: Option<&'a Path>,
+    target_snapshot: Option<&'a Path>,
+    force: bool,
+    force_disable: bool,
+    confirm_timeout: Option<Duration>,
+    rollback_after: Option<Duration>,
+}
+
+fn cmd_apply(flags: ApplyFlags, cli_preset: Option<Preset>, config: &BopConfig) -> Result<()> {
+    let ApplyFlags {
+        dry_run,
+        explain,
+        report: report_path,
+        wait,
+        offline_root,
+        target_snapshot,
+        force,
+        force_disable,
+        confirm_timeout,
+        rollback_after,
+    } = flags;
+    let offline_root = offline_root.map(OfflineRoot::new).transpose()?;
+
+    // Lock before detection so the plan reflects whatever a concurrent run left.
+    // An offline root doesn't touch this system, so it needs no lock.
+    let lock = if !dry_run && offline_root.is_none() && nix::unistd::geteuid().is_root() {
+        Some(bop::lock::acquire("apply", wait)?)
+    } else {
+        None
+    };
+
+    let effective_preset = bop::config::resolve_preset(config, cli_preset);
+    let knobs = bop::config::resolve_knobs(config, effective_preset);
+
+    // An image is planned for the machine it will boot on, never the build host
+    let target = target_snapshot
+        .map(|path| -> Result<_> {
+            let snapshot = bop::snapshot::Snapshot::load(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+            let dir = tempfile::TempDir::new()?;
+            let sysfs = snapshot.materialize(dir.path())?;
+            Ok((dir, sysfs))
+        })
+        .transpose()?;
+    let sysfs = match &target {
+        Some((_, sysfs)) => sysfs.clone(),
+        None => SysfsRoot::system(),
+    };
+    let hw = HardwareInfo::detect_all(&sysfs);
+
+    let Some(profile) = bop::profile::detect_profile(&hw) else {
         anyhow::bail!(
             "No hardware profile matched. Cannot apply optimizations for unknown hardware."
         );
+    };
+
+    let mut plan = bop::apply::build_plan(&hw, &sysfs, &knobs, Some(config));
+    plan.preset = Some(effective_preset);
+    if force_disable {
+        plan.force_disable_blocked();
     }
 
-    let plan = bop::apply::build_plan(&hw, &sysfs, &knobs, Some(config));
+    let mut report = report_path.map(|_| {
+        let mut audit_knobs = knobs.clone();
+        resolve_adaptive_epp(config, &hw, &mut audit_knobs);
+        let findings = profile.audit_with_opts(&hw, effective_preset, &audit_knobs);
+        bop::report::ApplyReport::new(&sysfs, "apply", effective_preset, profile.name(), dry_run)
+            .scores(&findings, &plan)
+    });
 
     if plan.is_empty() {
+        if let (Some(path), Some(report)) = (report_path, report.as_mut()) {
+            report.finish(&plan, &Default::default(), &sysfs, None);
+            report.append_to(path)?;
+        }
         println!();
         println!(
             "  {} {}",
@@ -144,6 +533,10 @@ fn cmd_apply(dry_run: bool, cli_preset: Option<Preset>, config: &BopConfig) -> R
             "{}",
             "No changes to apply — system already matches this preset.".green()
         );
+        if explain {
+            println!();
+            bop::output::print_explanations(&plan);
+        }
         return Ok(());
     }
 
@@ -181,16 +574,36 @@ fn cmd_apply(dry_run: bool, cli_preset: Option<Preset>, config: &BopConfig) -> R
         println!();
     }
 
-    bop::apply::print_plan(&plan);
+    bop::output::print_plan(&plan);
+    if explain {
+        bop::output::print_explanations(&plan);
+    }
 
     if dry_run {
+        if let (Some(path), Some(report)) = (report_path, report.as_mut()) {
+            report.finish(&plan, &Default::default(), &sysfs, None);
+            report.append_to(path)?;
+            println!("Report appended to {}", path.display());
+        }
         println!("{}", "Dry run complete. No changes applied.".yellow());
         return Ok(());
     }
 
+    if let Some(root) = &offline_root {
+        return cmd_apply_offline(&plan, &hw, &sysfs, root, force);
+    }
+
     if !nix::unistd::geteuid().is_root() {
         anyhow::bail!("Must run as root: sudo bop apply");
     }
+    if let Some(delay) = rollback_after
+        && delay < bop::apply::rollback::MIN_DELAY
+    {
+        anyhow::bail!(
+            "--rollback-after must be at least {}s, to leave time to check the changes",
+            bop::apply::rollback::MIN_DELAY.as_secs()
+        );
+    }
 
     // Confirm
     if effective_preset >= Preset::Supersaver {
@@ -206,121 +619,421 @@ fn cmd_apply(dry_run: bool, cli_preset: Option<Preset>, config: &BopConfig) -> R
     print!("Continue? [y/N] ");
     std::io::Write::flush(&mut std::io::stdout())?;
 
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input)?;
-    if !input.trim().eq_ignore_ascii_case("y") {
-        println!("Aborted.");
-        return Ok(());
+    let interrupt = bop::watch::InterruptScope::new();
+    let answer = bop::prompt::confirm(std::io::stdin(), confirm_timeout, interrupt.flag())?;
+    drop(interrupt);
+    match answer {
+        Answer::Yes => {}
+        Answer::No => {
+            println!("Aborted.");
+            return Ok(());
+        }
+        // Nothing is applied yet; release the lock before exiting
+        Answer::TimedOut => {
+            println!();
+            eprintln!("No answer within the confirmation timeout. Aborted.");
+            drop(lock);
+            std::process::exit(bop::prompt::TIMEOUT_EXIT_CODE);
+        }
+        Answer::Interrupted => {
+            println!();
+            eprintln!("Interrupted. Aborted.");
+            drop(lock);
+            std::process::exit(bop::prompt::INTERRUPTED_EXIT_CODE);
+        }
     }
 
     println!();
     println!("{}", "Applying optimizations...".bold());
 
-    let state = bop::apply::execute_plan(&plan, &hw, false)?;
+    let mut state = bop::apply::ApplyState::default();
+    let result =
+        bop::apply::execute_plan_tracked(&plan, &hw, false, force, &mut state, &mut Terminal);
 
-    println!();
-    println!("{}", "Applied successfully!".green().bold());
-    // Categorize sysfs changes
-    let mut epp_count = 0usize;
-    let mut pci_count = 0usize;
-    let mut usb_count = 0usize;
-    let mut audio_count = 0usize;
-    let mut gpu_count = 0usize;
-    let mut other_count = 0usize;
-
-    for change in &state.sysfs_changes {
-        if change.path.contains("energy_performance_preference") {
-            epp_count += 1;
-        } else if change.path.contains("/bus/pci/") {
-            pci_count += 1;
-        } else if change.path.contains("/bus/usb/") {
-            usb_count += 1;
-        } else if change.path.contains("snd_hda_intel") {
-            audio_count += 1;
-        } else if change.path.contains("power_dpm") {
-            gpu_count += 1;
-        } else {
-            other_count += 1;
+    if let (Some(path), Some(report)) = (report_path, report.as_mut()) {
+        report.finish(
+            &plan,
+            &state,
+            &sysfs,
+            result.as_ref().err().map(ToString::to_string),
+        );
+        match (report.append_to(path), &result) {
+            (Ok(()), _) => {}
+            // Don't let a report problem mask the apply error
+            (Err(e), Err(_)) => eprintln!("{} Failed to write report: {}", "!".yellow(), e),
+            (Err(e), Ok(())) => return Err(e.into()),
         }
     }
+    result?;
 
-    let mut parts = Vec::new();
-    if epp_count > 0 {
-        parts.push(format!("{} CPU cores", epp_count));
-    }
-    if other_count > 0 {
-        parts.push(format!("{} system", other_count));
-    }
-    if pci_count > 0 {
-        parts.push(format!("{} PCI", pci_count));
+    println!();
+    println!("{}", "Applied successfully!".green().bold());
+    for line in bop::report::summary_lines(&state) {
+        println!("  {}", line);
     }
-    if usb_count > 0 {
-        parts.push(format!("{} USB", usb_count));
+    if let Some(path) = report_path {
+        println!("  Report appended to {}", path.display());
     }
-    if audio_count > 0 {
-        parts.push(format!("{} audio", audio_count));
+    if state.has_slow_phase() {
+        println!();
+        bop::output::print_phase_timings(&state);
     }
-    if gpu_count > 0 {
-        parts.push(format!("{} GPU", gpu_count));
+
+    if !state.kernel_params_added.is_empty() || !state.kernel_params_removed.is_empty() {
+        println!();
+        println!(
+            "{}",
+            "  Kernel parameter changes require a reboot to take effect.".yellow()
+        );
     }
 
-    let sysfs_summary = parts.join(", ");
+    println!();
+    println!(
+        "  State saved. Run {} to undo all changes.",
+        "sudo bop revert".cyan()
+    );
 
-    let mut lines = Vec::new();
-    if !sysfs_summary.is_empty() {
-        lines.push(format!("  Sysfs: {}", sysfs_summary));
-    }
-    if !state.kernel_params_added.is_empty() {
-        lines.push(format!(
-            "  Kernel params: {}",
-            state.kernel_params_added.join(", ")
-        ));
+    if let Some(delay) = rollback_after {
+        bop::apply::rollback::schedule(
+            &mut bop::apply::rollback::SystemdRun,
+            &mut state,
+            delay,
+            chrono::Utc::now(),
+            bop::apply::current_boot_id(),
+        )?;
+        state.save()?;
+        println!();
+        println!(
+            "  {} Reverting automatically {} ({}).",
+            "Rollback:".yellow().bold(),
+            bop::apply::rollback::describe_remaining(delay),
+            bop::apply::rollback::UNIT_NAME
+        );
+        println!(
+            "  Keep the changes with {}, or cancel the timer with {}.",
+            "sudo bop apply --confirm-keep".cyan(),
+            format!(
+                "sudo systemctl stop {}.timer",
+                bop::apply::rollback::UNIT_NAME
+            )
+            .cyan()
+        );
     }
-    if !state.acpi_wakeup_toggled.is_empty() {
-        lines.push(format!(
-            "  ACPI wakeup disabled: {}",
-            state.acpi_wakeup_toggled.join(", ")
-        ));
+
+    Ok(())
+}
+
+/// Cancel the automatic rollback a `bop apply --rollback-after` started.
+fn cmd_confirm_keep(wait: Option<Duration>) -> Result<()> {
+    let is_root = nix::unistd::geteuid().is_root();
+    if !is_root && bop::apply::custom_state_dir().is_none() {
+        anyhow::bail!("Must run as root: sudo bop apply --confirm-keep");
     }
-    if !state.services_disabled.is_empty() {
-        lines.push(format!(
-            "  Services disabled: {}",
-            state.services_disabled.join(", ")
-        ));
+    let _lock = if is_root {
+        Some(bop::lock::acquire("apply", wait)?)
+    } else {
+        None
+    };
+
+    let mut state = bop::apply::ApplyState::load()?.unwrap_or_default();
+    if !bop::apply::rollback::keep(&mut bop::apply::rollback::SystemdRun, &mut state)? {
+        println!("No automatic rollback pending.");
+        return Ok(());
     }
-    for line in &lines {
-        println!("{}", line);
+    state.save()?;
+    println!(
+        "{}",
+        "Changes kept. The automatic rollback is cancelled.".green()
+    );
+    Ok(())
+}
+
+/// Image builds are unattended, so there's no confirmation prompt.
+fn cmd_apply_offline(
+    plan: &bop::apply::ApplyPlan,
+    hw: &HardwareInfo,
+    sysfs: &SysfsRoot,
+    root: &OfflineRoot,
+    force: bool,
+) -> Result<()> {
+    println!(
+        "{}",
+        format!("Installing into {}...", root.path().display()).bold()
+    );
+
+    let mut state = bop::apply::ApplyState::default();
+    bop::apply::execute_plan_offline(
+        plan,
+        hw,
+        sysfs,
+        root,
+        false,
+        force,
+        &mut state,
+        &mut Terminal,
+    )?;
+
+    println!();
+    println!("{}", "Installed successfully!".green().bold());
+    for line in bop::report::summary_lines(&state) {
+        println!("  {}", line);
     }
 
-    if !state.kernel_params_added.is_empty() {
+    if state
+        .kernel_param_backups
+        .iter()
+        .any(|b| b.path == bop::apply::kernel_params::GRUB_DEFAULT)
+    {
         println!();
         println!(
             "{}",
-            "  Kernel parameter changes require a reboot to take effect.".yellow()
+            "  Run grub-mkconfig inside the image before first boot to pick up the kernel parameters."
+                .yellow()
         );
     }
 
     println!();
     println!(
-        "  State saved. Run {} to undo all changes.",
+        "  State saved in the image. Run {} on the booted system to undo all changes.",
         "sudo bop revert".cyan()
     );
 
     Ok(())
 }
 
-fn cmd_monitor() -> Result<()> {
-    bop::monitor::run()?;
+fn cmd_setup(flags: bop::setup::SetupFlags, config: &BopConfig) -> Result<()> {
+    if !nix::unistd::geteuid().is_root() {
+        anyhow::bail!("Must run as root: sudo bop setup");
+    }
+
+    let sysfs = SysfsRoot::system();
+    let hw = HardwareInfo::detect_all(&sysfs);
+    let profile = bop::profile::detect_profile(&hw);
+
+    let preset = bop::config::resolve_preset(config, flags.preset);
+    let mut knobs = bop::config::resolve_knobs(config, preset);
+    resolve_adaptive_epp(config, &hw, &mut knobs);
+    let findings = profile
+        .as_ref()
+        .map(|p| p.audit_with_opts(&hw, preset, &knobs))
+        .unwrap_or_default();
+
+    let hardware = [hw.dmi.product_name.as_deref(), hw.cpu.model_name.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(", ");
+    let ctx = bop::setup::SetupContext {
+        hardware: if hardware.is_empty() {
+            "Unknown".to_string()
+        } else {
+            hardware
+        },
+        profile: profile.as_ref().map(|p| p.name()),
+        findings: &findings,
+        score: bop::audit::calculate_score(&findings),
+        current: config,
+    };
+
+    println!("{}", "bop setup".bold());
+    println!();
+    let answers = bop::setup::run_wizard(
+        &ctx,
+        &flags,
+        &mut std::io::stdin().lock(),
+        &mut std::io::stdout(),
+    )?;
+
+    let new_config = answers.to_config(config);
+    let path = Path::new(bop::config::SYSTEM_CONFIG);
+    bop::setup::write_config(&answers, path)?;
+    println!();
+    println!("{} Config written to {}", ">>".green(), path.display());
+
+    if profile.is_none() {
+        println!(
+            "  {} No hardware profile matched; skipping apply/auto.",
+            "!".yellow()
+        );
+        return Ok(());
+    }
+
+    if answers.auto {
+        bop::auto::enable(
+            None,
+            &new_config,
+            None,
+            None,
+            false,
+            new_config.notifications.enabled,
+        )?;
+    } else if answers.apply {
+        cmd_apply(ApplyFlags::default(), None, &new_config)?;
+    } else {
+        println!("  Run {} when you're ready.", "sudo bop apply".cyan());
+    }
+
+    println!();
+    print!("{}", bop::footprint::render());
+    println!("  Run {} to see this again.", "bop about-safety".cyan());
+
+    Ok(())
+}
+
+fn cmd_monitor(
+    action: Option<MonitorAction>,
+    log: Option<&Path>,
+    resume: bool,
+    config: &BopConfig,
+) -> Result<()> {
+    match action {
+        Some(MonitorAction::Analyze { dir }) => bop::monitor::run_analyze(&dir)?,
+        None => {
+            let log = log.map(|dir| bop::monitor::LogOptions { dir, resume });
+            bop::monitor::run(&config.idle_drain, log)?
+        }
+    }
+    Ok(())
+}
+
+fn cmd_battery(action: BatteryAction) -> Result<()> {
+    match action {
+        BatteryAction::Calibrate => bop::monitor::calibrate::run()?,
+    }
+    Ok(())
+}
+
+fn cmd_profile(action: ProfileAction, json: bool) -> Result<()> {
+    match action {
+        ProfileAction::Show => {
+            let hw = HardwareInfo::detect_all(&SysfsRoot::system());
+            let profile = bop::profile::detect_profile(&hw);
+            if json {
+                bop::output::print_profile_json(profile.as_deref());
+            } else if let Some(p) = profile {
+                println!("  {} {}", "Matched profile:".bold(), p.name().green());
+                println!();
+                bop::output::print_profile_notes(p.as_ref());
+            } else {
+                println!(
+                    "  {} No hardware profile matched; bop audits and applies nothing here.",
+                    "Note:".yellow()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cmd_revert(
+    only: Option<bop::revert::RevertScope>,
+    wait: Option<Duration>,
+    force: bool,
+    quiet: bool,
+) -> Result<()> {
+    let _lock = if nix::unistd::geteuid().is_root() {
+        Some(bop::lock::acquire("revert", wait)?)
+    } else {
+        None
+    };
+    if quiet {
+        bop::revert::revert(
+            only,
+            force,
+            false,
+            &mut bop::progress::ProblemsOnly(Terminal),
+        )?;
+    } else {
+        bop::revert::revert(only, force, false, &mut Terminal)?;
+    }
     Ok(())
 }
 
-fn cmd_revert() -> Result<()> {
-    bop::revert::revert()?;
+fn cmd_upgrade_artifacts(force: bool) -> Result<()> {
+    let _lock = if nix::unistd::geteuid().is_root() {
+        Some(bop::lock::acquire("upgrade-artifacts", None)?)
+    } else {
+        None
+    };
+    bop::apply::unit_migration::upgrade_artifacts(force, &mut Terminal)?;
     Ok(())
 }
 
-fn cmd_status(json: bool) -> Result<()> {
-    let report = match bop::status::check()? {
+/// Runs from a rescue shell against a mounted system, so there is no lock
+/// to take: nothing of that system is running.
+fn cmd_rescue(root: &Path, dry_run: bool, all: bool, force: bool) -> Result<()> {
+    let root = OfflineRoot::new(root)?;
+    let mount = root.path().to_path_buf();
+    let Some(rescue) = bop::revert::rescue::Rescue::load(root)? else {
+        println!(
+            "{}",
+            format!(
+                "No saved state under {}. Nothing to rescue.",
+                mount.display()
+            )
+            .yellow()
+        );
+        return Ok(());
+    };
+    let root = rescue.root();
+    println!(
+        "{}",
+        format!(
+            "Rescuing {} (changes applied at {})",
+            root.path().display(),
+            rescue.state().timestamp
+        )
+        .bold()
+        .underline()
+    );
+    println!();
+
+    let diffs = rescue.backup_diffs()?;
+    if diffs.is_empty() {
+        println!("  No kernel parameter backups recorded.");
+        println!();
+    } else {
+        bop::output::print_backup_diffs(&diffs);
+    }
+    if dry_run {
+        return Ok(());
+    }
+    if !nix::unistd::geteuid().is_root() {
+        anyhow::bail!(
+            "Must run as root: sudo bop rescue --root {}",
+            root.path().display()
+        );
+    }
+
+    if !rescue.run(all, force, &mut Terminal)? {
+        println!(
+            "{}",
+            format!(
+                "Rescue incomplete. Kept the state file at {} so you can retry.",
+                root.resolve(bop::apply::STATE_FILE)?.display()
+            )
+            .yellow()
+        );
+        return Ok(());
+    }
+    println!("{}", "Rescue complete.".green().bold());
+    if bop::revert::rescue::Rescue::load(root.clone())?.is_some() {
+        println!(
+            "  Other changes are still recorded. Run {} after booting, or rescue with --all.",
+            "sudo bop revert".cyan()
+        );
+    }
+    Ok(())
+}
+
+fn cmd_status(
+    json: bool,
+    explain_drift: bool,
+    incremental: bool,
+    config: &BopConfig,
+) -> Result<()> {
+    let full_check_interval = incremental.then(|| config.status.full_check_interval.into());
+    let mut report = match bop::status::check(&config.pins, full_check_interval)? {
         Some(r) => r,
         None => {
             println!(
@@ -330,6 +1043,9 @@ fn cmd_status(json: bool) -> Result<()> {
             return Ok(());
         }
     };
+    if explain_drift {
+        report.explain_drift();
+    }
 
     if json {
         bop::output::print_status_json(&report);
@@ -340,21 +1056,183 @@ fn cmd_status(json: bool) -> Result<()> {
     Ok(())
 }
 
+/// Flags of `bop auto` shared by its subcommands.
+struct AutoFlags<'a> {
+    report: Option<&'a Path>,
+    wait: Option<Duration>,
+    force: bool,
+}
+
 fn cmd_auto(
     action: Option<AutoAction>,
+    flags: AutoFlags,
     cli_preset: Option<Preset>,
     config: &BopConfig,
     json: bool,
     config_path: Option<&Path>,
 ) -> Result<()> {
+    let AutoFlags {
+        report,
+        wait,
+        force,
+    } = flags;
     match action {
         None => {
             // Bare `bop auto` — called by udev
-            bop::auto::run(cli_preset, config)?;
+            bop::auto::run(cli_preset, config, report, wait)?;
+        }
+        Some(AutoAction::Enable { with_notify }) => {
+            bop::auto::enable(cli_preset, config, config_path, report, force, with_notify)?
         }
-        Some(AutoAction::Enable) => bop::auto::enable(cli_preset, config, config_path)?,
-        Some(AutoAction::Disable) => bop::auto::disable()?,
-        Some(AutoAction::Status) => bop::auto::status(json)?,
+        Some(AutoAction::Disable) => bop::auto::disable(force)?,
+        Some(AutoAction::Status) => bop::auto::status(json, config)?,
+    }
+    Ok(())
+}
+
+#[cfg(feature = "dbus")]
+fn cmd_dbus_serve(
+    action: Option<bop::cli::DbusAction>,
+    force: bool,
+    cli_preset: Option<Preset>,
+    config: &BopConfig,
+) -> Result<()> {
+    use bop::cli::DbusAction;
+
+    if !nix::unistd::geteuid().is_root() {
+        return Err(bop::error::Error::NotRoot {
+            operation: "dbus-serve".to_string(),
+        }
+        .into());
+    }
+    let ownership = bop::managed::Ownership::new(&[], force);
+    match action {
+        None => bop::dbus::serve(
+            || dbus_properties(cli_preset, config),
+            bop::watch::interrupt_flag(),
+        )?,
+        Some(DbusAction::Enable) => {
+            bop::dbus::enable(&ownership)?;
+            println!(
+                "{} D-Bus service enabled ({})",
+                ">>".green(),
+                bop::dbus::BUS_NAME
+            );
+            println!("  Unit installed at {}", bop::dbus::UNIT_PATH);
+            println!("  Bus policy installed at {}", bop::dbus::POLICY_PATH);
+        }
+        Some(DbusAction::Disable) => {
+            if bop::dbus::disable(&ownership)? {
+                println!("{} D-Bus service disabled", ">>".green());
+            } else {
+                println!("D-Bus service was not installed.");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Current values for the D-Bus properties. Same score as `bop audit
+/// --oneline`; 100 when no profile matches, as in `bop audit --json`.
+#[cfg(feature = "dbus")]
+fn dbus_properties(cli_preset: Option<Preset>, config: &BopConfig) -> bop::dbus::Properties {
+    let (findings, hw) = quick_findings(config, cli_preset);
+    let view = PowerContext::current(&hw.ac);
+    let status = bop::status::check(&config.pins, Some(config.status.full_check_interval.into()))
+        .ok()
+        .flatten();
+    bop::dbus::Properties {
+        score: bop::audit::context_score(findings.as_deref().unwrap_or_default(), view),
+        optimizations_applied: status.is_some(),
+        power_source: view.to_string(),
+        drifted_count: status.map_or(0, |s| s.drifted_count() as u32),
+    }
+}
+
+fn cmd_about_safety(json: bool) -> Result<()> {
+    if json {
+        let out = serde_json::json!({
+            "entries": bop::footprint::entries(),
+            "never_touched": bop::footprint::never_touched(),
+        });
+        println!("{}", serde_json::to_string_pretty(&out)?);
+    } else {
+        print!("{}", bop::footprint::render());
+    }
+    Ok(())
+}
+
+fn cmd_doctor(
+    disarm: bool,
+    undo: bool,
+    probe: bool,
+    json: bool,
+    cli_preset: Option<Preset>,
+    config: &BopConfig,
+) -> Result<()> {
+    let root = Path::new("/");
+    if (disarm || undo || probe) && !nix::unistd::geteuid().is_root() {
+        return Err(bop::error::Error::NotRoot {
+            operation: "doctor --disarm/--undo/--probe".to_string(),
+        }
+        .into());
+    }
+
+    if undo {
+        let restored = bop::conflicts::undo(root)?;
+        if restored.is_empty() {
+            println!("Nothing to undo.");
+        }
+        for path in restored {
+            println!("{} Restored {}", ">>".green(), path.display());
+        }
+        return Ok(());
+    }
+
+    let conflicts = bop::conflicts::scan(root);
+    // Writing values back races a concurrent apply, so hold its lock
+    let probe = if probe {
+        let _lock = bop::lock::acquire("doctor --probe", None)?;
+        let effective_preset = bop::config::resolve_preset(config, cli_preset);
+        let knobs = bop::config::resolve_knobs(config, effective_preset);
+        let sysfs = SysfsRoot::system();
+        let hw = HardwareInfo::detect_all(&sysfs);
+        let plan = bop::apply::build_plan(&hw, &sysfs, &knobs, Some(config));
+        Some(bop::apply::probe::probe_plan(&plan, &sysfs))
+    } else {
+        None
+    };
+
+    if json && !disarm {
+        let out = serde_json::json!({
+            "conflicts": conflicts,
+            "probe": probe,
+        });
+        println!("{}", serde_json::to_string_pretty(&out)?);
+        return Ok(());
+    }
+
+    bop::output::print_conflicts(&conflicts);
+    if let Some(probe) = &probe {
+        bop::output::print_probe(probe);
+    }
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+
+    if disarm {
+        for path in bop::conflicts::disarm(root, &conflicts)? {
+            println!("{} Disarmed {}", ">>".green(), path.display());
+        }
+        println!(
+            "  Backups recorded; {} puts everything back.",
+            "sudo bop doctor --undo".cyan()
+        );
+    } else {
+        println!(
+            "  Run {} to comment these out (units are disabled instead).",
+            "sudo bop doctor --disarm".cyan()
+        );
     }
     Ok(())
 }
@@ -384,15 +1262,24 @@ fn cmd_snapshot(output: Option<String>) -> Result<()> {
 
 fn cmd_wake(action: WakeAction) -> Result<()> {
     match action {
-        WakeAction::List => bop::wake::list()?,
-        WakeAction::Enable { controller } => bop::wake::enable(&controller)?,
-        WakeAction::Disable { controller } => bop::wake::disable(&controller)?,
-        WakeAction::Scan => bop::wake::scan()?,
+        WakeAction::List => {
+            let sysfs = SysfsRoot::system();
+            let controllers = bop::wake::list(&bop::detect::LazyHardware::new(&sysfs))?;
+            bop::output::print_wake_controllers(&controllers);
+        }
+        WakeAction::Enable { controller } => bop::wake::enable(&controller, &mut Terminal)?,
+        WakeAction::Disable { controller } => bop::wake::disable(&controller, &mut Terminal)?,
+        WakeAction::Scan => bop::wake::scan(&mut Terminal)?,
     }
     Ok(())
 }
 
-fn cmd_config(action: ConfigAction, config: &BopConfig) -> Result<()> {
+fn cmd_config(
+    action: ConfigAction,
+    config: &BopConfig,
+    issues: &[bop::config::ConfigIssue],
+    json: bool,
+) -> Result<()> {
     match action {
         ConfigAction::Show => {
             let toml_str = toml::to_string_pretty(config)?;
@@ -441,6 +1328,28 @@ fn cmd_config(action: ConfigAction, config: &BopConfig) -> Result<()> {
                 println!("  {}   {}", "User:".bold(), "unknown".dimmed());
             }
         }
+        ConfigAction::Validate => {
+            let errors = issues
+                .iter()
+                .filter(|i| i.severity == bop::config::IssueSeverity::Error)
+                .count();
+            if json {
+                println!("{}", serde_json::to_string_pretty(issues)?);
+            } else if issues.is_empty() {
+                println!("{} Config is valid", ">>".green());
+            } else {
+                for issue in issues {
+                    let label = match issue.severity {
+                        bop::config::IssueSeverity::Warning => "warning:".yellow(),
+                        bop::config::IssueSeverity::Error => "error:".red(),
+                    };
+                    println!("  {} {}", label.bold(), issue);
+                }
+            }
+            if errors > 0 {
+                anyhow::bail!("{} config error(s)", errors);
+            }
+        }
     }
     Ok(())
 }
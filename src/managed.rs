@@ -0,0 +1,238 @@
+//! Files bop generates outside its state dir: the persistence unit, the
+//! auto-switching udev rule and modprobe drop-ins. Each starts with the same
+//! header, and the ones `bop apply` creates are also listed in the state
+//! file with a content hash. Before overwriting or deleting one of these
+//! paths bop checks that the file there is still its own, so a user file
+//! that happens to share the name is never clobbered.
+
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// First line of every generated file, up to the version.
+pub const HEADER_PREFIX: &str = "# Managed by bop";
+
+/// Headers written before the common one; still accepted as bop's own.
+const LEGACY_HEADERS: &[&str] = &["# Generated by bop"];
+
+/// `# Managed by bop vX.Y.Z (do not edit)`
+pub fn header() -> String {
+    format!(
+        "{} v{} (do not edit)",
+        HEADER_PREFIX,
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// [`header`] as an XML comment, for files that can't start with `#`
+/// (D-Bus policy).
+pub fn xml_header() -> String {
+    format!("<!-- {} -->", header().trim_start_matches("# "))
+}
+
+/// Whether `content` starts with the current or a legacy bop header, or
+/// its XML form.
+pub fn has_header(content: &str) -> bool {
+    let first = content.lines().next().unwrap_or_default();
+    let first = match first.strip_prefix("<!-- ") {
+        Some(comment) => format!("# {}", comment),
+        None => first.to_string(),
+    };
+    first.starts_with(HEADER_PREFIX) || LEGACY_HEADERS.iter().any(|h| first.starts_with(h))
+}
+
+/// FNV-1a over the file contents, prefixed with the algorithm name. Only
+/// meant to recognise bop's own output, not to resist tampering.
+pub fn content_hash(content: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in content {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("fnv1a64:{:016x}", hash)
+}
+
+/// A file bop wrote, as recorded in the state file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagedFile {
+    /// Path on the system the file belongs to (inside an offline root,
+    /// the path the booted image will see)
+    pub path: String,
+    pub hash: String,
+}
+
+impl ManagedFile {
+    pub fn new(path: impl Into<String>, content: &str) -> Self {
+        Self {
+            path: path.into(),
+            hash: content_hash(content.as_bytes()),
+        }
+    }
+}
+
+/// Decides whether bop may overwrite or delete a file: yes if it is absent,
+/// carries a bop header, or matches the manifest entry for its path; anything
+/// else only with `force`. State files from before the manifest have no
+/// entries, so their artifacts are recognised by header alone.
+#[derive(Debug, Clone, Default)]
+pub struct Ownership {
+    /// Where each managed file is on this system, and its hash
+    manifest: Vec<(PathBuf, String)>,
+    force: bool,
+}
+
+impl Ownership {
+    pub fn new(manifest: &[ManagedFile], force: bool) -> Self {
+        Self::resolved(manifest, force, |path| Some(PathBuf::from(path)))
+    }
+
+    /// Like [`Ownership::new`], for a manifest whose paths `resolve` maps
+    /// to where the files are on this system (inside an offline root).
+    /// Entries it can't map own nothing.
+    pub fn resolved(
+        manifest: &[ManagedFile],
+        force: bool,
+        resolve: impl Fn(&str) -> Option<PathBuf>,
+    ) -> Self {
+        Self {
+            manifest: manifest
+                .iter()
+                .filter_map(|m| Some((resolve(&m.path)?, m.hash.clone())))
+                .collect(),
+            force,
+        }
+    }
+
+    /// Err([`Error::NotManaged`]) if `path` holds a file bop doesn't own.
+    pub fn check(&self, path: &Path) -> Result<()> {
+        if self.force {
+            return Ok(());
+        }
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                return Err(Error::Other(format!(
+                    "failed to read {}: {}",
+                    path.display(),
+                    e
+                )));
+            }
+        };
+        let hash = content_hash(&content);
+        if has_header(&String::from_utf8_lossy(&content))
+            || self
+                .manifest
+                .iter()
+                .any(|(managed, managed_hash)| managed == path && *managed_hash == hash)
+        {
+            Ok(())
+        } else {
+            Err(Error::NotManaged {
+                path: path.to_path_buf(),
+            })
+        }
+    }
+
+    /// Write `content` to `path` after the ownership check.
+    pub fn write(&self, path: &Path, content: &str) -> Result<()> {
+        self.check(path)?;
+        std::fs::write(path, content)
+            .map_err(|e| Error::Other(format!("failed to write {}: {}", path.display(), e)))
+    }
+
+    /// Delete `path` after the ownership check. Returns false if it was
+    /// already gone.
+    pub fn remove(&self, path: &Path) -> Result<bool> {
+        self.check(path)?;
+        match std::fs::remove_file(path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(Error::Other(format!(
+                "failed to remove {}: {}",
+                path.display(),
+                e
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_header_and_legacy_headers() {
+        let current = format!("{}\noptions foo bar=1\n", header());
+        assert!(current.starts_with("# Managed by bop v"));
+        assert!(has_header(&current));
+        // Unit and modprobe files, and the udev rule, from older releases
+        assert!(has_header(
+            "# Generated by bop (Battery Optimization Project)\n"
+        ));
+        assert!(has_header(
+            "# Generated by bop -- removed by `bop revert`\n"
+        ));
+        assert!(has_header("# Managed by bop — do not edit\n"));
+        // D-Bus policy files carry it as an XML comment
+        let xml = format!("{}\n<busconfig/>\n", xml_header());
+        assert!(xml.starts_with("<!-- Managed by bop v"));
+        assert!(xml.lines().next().unwrap().ends_with(" (do not edit) -->"));
+        assert!(has_header(&xml));
+        assert!(!has_header("<!-- Some other tool -->\n"));
+
+        assert!(!has_header("options foo bar=1\n"));
+        assert!(!has_header("options foo bar=1\n# Managed by bop\n"));
+        assert!(!has_header(""));
+    }
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        assert_eq!(content_hash(b""), "fnv1a64:cbf29ce484222325");
+        assert_eq!(content_hash(b"a"), "fnv1a64:af63dc4c8601ec8c");
+        assert_ne!(content_hash(b"options a"), content_hash(b"options b"));
+    }
+
+    #[test]
+    fn test_ownership_refuses_foreign_files_without_force() {
+        let tmp = TempDir::new().unwrap();
+        let ours = tmp.path().join("ours.conf");
+        let legacy = tmp.path().join("legacy.conf");
+        let listed = tmp.path().join("listed.conf");
+        let foreign = tmp.path().join("foreign.conf");
+        std::fs::write(&ours, format!("{}\noptions a b\n", header())).unwrap();
+        std::fs::write(&legacy, "# Generated by bop\noptions a b\n").unwrap();
+        std::fs::write(&listed, "options c d\n").unwrap();
+        std::fs::write(&foreign, "options e f\n").unwrap();
+
+        let manifest = vec![ManagedFile::new(listed.to_string_lossy(), "options c d\n")];
+        let ownership = Ownership::new(&manifest, false);
+        assert!(ownership.check(&ours).is_ok());
+        assert!(ownership.check(&legacy).is_ok());
+        assert!(ownership.check(&listed).is_ok());
+        // Same content as a managed file, but not at its path
+        let copy = tmp.path().join("copy.conf");
+        std::fs::write(&copy, "options c d\n").unwrap();
+        assert!(matches!(
+            ownership.check(&copy),
+            Err(Error::NotManaged { .. })
+        ));
+        assert!(ownership.check(&tmp.path().join("absent.conf")).is_ok());
+
+        assert!(matches!(
+            ownership.write(&foreign, "options x y\n"),
+            Err(Error::NotManaged { .. })
+        ));
+        assert!(matches!(
+            ownership.remove(&foreign),
+            Err(Error::NotManaged { .. })
+        ));
+        assert_eq!(std::fs::read_to_string(&foreign).unwrap(), "options e f\n");
+
+        let forced = Ownership::new(&manifest, true);
+        assert!(forced.remove(&foreign).unwrap());
+        assert!(!foreign.exists());
+        assert!(!forced.remove(&foreign).unwrap());
+    }
+}
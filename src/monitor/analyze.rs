@@ -0,0 +1,179 @@
+//! Aggregate stats over a capture session for `bop monitor analyze`.
+
+use crate::monitor::capture::Sample;
+use chrono::{DateTime, FixedOffset, Timelike};
+
+/// Local hours counted as day; the rest is night.
+pub const DAY_HOURS: std::ops::Range<u32> = 7..22;
+/// Consecutive samples further apart than this (suspend, monitor stopped)
+/// don't count towards the discharge rate.
+pub const MAX_GAP_SECS: i64 = 5 * 60;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Average {
+    sum: f64,
+    count: usize,
+}
+
+impl Average {
+    pub fn add(&mut self, value: Option<f64>) {
+        if let Some(v) = value.filter(|v| v.is_finite()) {
+            self.sum += v;
+            self.count += 1;
+        }
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+/// Averages over one slice of the capture (an hour of the day, day or
+/// night, or all of it).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PeriodStats {
+    pub samples: usize,
+    /// Battery draw while discharging
+    pub battery_watts: Average,
+    pub cpu_watts: Average,
+    /// Capacity lost and time spent between consecutive discharging samples
+    drop_percent: f64,
+    drop_secs: i64,
+}
+
+impl PeriodStats {
+    /// Battery percent lost per hour of discharge.
+    pub fn percent_per_hour(&self) -> Option<f64> {
+        (self.drop_secs > 0).then(|| self.drop_percent * 3600.0 / self.drop_secs as f64)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SessionStats {
+    pub samples: usize,
+    pub first: Option<DateTime<FixedOffset>>,
+    pub last: Option<DateTime<FixedOffset>>,
+    pub total: PeriodStats,
+    /// Indexed by local hour of day
+    pub hours: [PeriodStats; 24],
+    pub day: PeriodStats,
+    pub night: PeriodStats,
+}
+
+/// Stats over `samples`, oldest first. Each sample counts in the hour it
+/// was taken, in its own timezone.
+pub fn analyze(samples: &[Sample]) -> SessionStats {
+    let mut stats = SessionStats {
+        samples: samples.len(),
+        first: samples.first().map(|s| s.time),
+        last: samples.last().map(|s| s.time),
+        ..Default::default()
+    };
+
+    let mut prev: Option<&Sample> = None;
+    for sample in samples {
+        let hour = sample.time.hour();
+        let period = if DAY_HOURS.contains(&hour) {
+            &mut stats.day
+        } else {
+            &mut stats.night
+        };
+        let drop = prev.and_then(|p| discharge_between(p, sample));
+        for slice in [&mut stats.total, &mut stats.hours[hour as usize], period] {
+            slice.samples += 1;
+            if sample.discharging {
+                slice.battery_watts.add(sample.battery_watts);
+            }
+            slice.cpu_watts.add(sample.cpu_watts);
+            if let Some((percent, secs)) = drop {
+                slice.drop_percent += percent;
+                slice.drop_secs += secs;
+            }
+        }
+        prev = Some(sample);
+    }
+    stats
+}
+
+/// Percent lost and seconds elapsed from `prev` to `next`, if the battery
+/// discharged the whole time.
+fn discharge_between(prev: &Sample, next: &Sample) -> Option<(f64, i64)> {
+    if !(prev.discharging && next.discharging) {
+        return None;
+    }
+    let secs = (next.time - prev.time).num_seconds();
+    if secs <= 0 || secs > MAX_GAP_SECS {
+        return None;
+    }
+    let lost = prev.battery_percent? as f64 - next.battery_percent? as f64;
+    Some((lost, secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(time: &str, watts: f64, percent: u32, discharging: bool) -> Sample {
+        Sample {
+            time: DateTime::parse_from_rfc3339(time).unwrap(),
+            battery_watts: Some(watts),
+            cpu_watts: Some(1.0),
+            soc_watts: None,
+            battery_percent: Some(percent),
+            discharging,
+            backlight_percent: None,
+        }
+    }
+
+    #[test]
+    fn test_per_hour_and_day_night() {
+        let samples = [
+            // Night, idle
+            sample("2026-10-15T03:00:00+02:00", 4.0, 80, true),
+            sample("2026-10-15T03:05:00+02:00", 6.0, 79, true),
+            // Day, busier; the gap from 03:05 doesn't count as discharge
+            sample("2026-10-15T10:00:00+02:00", 10.0, 70, true),
+            sample("2026-10-15T10:02:00+02:00", 12.0, 69, true),
+            // Charging: no battery draw counted
+            sample("2026-10-15T10:04:00+02:00", 30.0, 70, false),
+        ];
+        let stats = analyze(&samples);
+
+        assert_eq!(stats.samples, 5);
+        assert_eq!(stats.first, Some(samples[0].time));
+        assert_eq!(stats.hours[3].battery_watts.mean(), Some(5.0));
+        assert_eq!(stats.hours[10].battery_watts.mean(), Some(11.0));
+        assert_eq!(stats.hours[10].samples, 3);
+        assert_eq!(stats.hours[12].samples, 0);
+        assert_eq!(stats.night.battery_watts.mean(), Some(5.0));
+        assert_eq!(stats.day.battery_watts.mean(), Some(11.0));
+        assert_eq!(stats.total.battery_watts.count(), 4);
+
+        // 1% in 5 minutes at night, 1% in 2 minutes by day
+        assert_eq!(stats.night.percent_per_hour(), Some(12.0));
+        assert_eq!(stats.day.percent_per_hour(), Some(30.0));
+        assert_eq!(stats.total.percent_per_hour(), Some(2.0 * 3600.0 / 420.0));
+    }
+
+    #[test]
+    fn test_hour_follows_sample_offset() {
+        // Same instant, recorded in different zones
+        let utc = analyze(&[sample("2026-10-15T22:30:00+00:00", 5.0, 50, true)]);
+        let cest = analyze(&[sample("2026-10-16T00:30:00+02:00", 5.0, 50, true)]);
+        assert_eq!(utc.hours[22].battery_watts.count(), 1);
+        assert_eq!(cest.hours[0].battery_watts.count(), 1);
+        assert_eq!(utc.night.battery_watts.count(), 1);
+    }
+
+    #[test]
+    fn test_empty_session() {
+        let stats = analyze(&[]);
+        assert_eq!(stats.samples, 0);
+        assert_eq!(stats.total.battery_watts.mean(), None);
+        assert_eq!(stats.total.percent_per_hour(), None);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::calibration::{self, Calibration, CalibrationResult, Reading, Stage, Step};
+use crate::detect::battery::BatteryInfo;
+use crate::error::{Error, Result};
+use crate::idle_drain::unix_now;
+use crate::sysfs::SysfsRoot;
+use colored::Colorize;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+/// `bop battery calibrate`: follow one charge -> discharge -> charge cycle,
+/// telling the user what to do at each stage, and save the result.
+pub fn run() -> Result<()> {
+    let sysfs = SysfsRoot::system();
+    let battery = BatteryInfo::detect(&sysfs);
+    if !battery.present {
+        return Err(Error::Other("no battery found".to_string()));
+    }
+
+    println!("{}", "Battery Calibration".bold().underline());
+    println!(
+        "Follows one full charge, discharge to {}% and recharge. Takes several hours; keep this running and don't suspend.",
+        calibration::LOW_PERCENT
+    );
+    if let Some(previous) =
+        CalibrationResult::load_for(Path::new(calibration::RESULT_PATH), &battery)
+    {
+        println!(
+            "  {}",
+            format!(
+                "Last calibration measured {:.1}Wh (gauge error {:+.1}%)",
+                previous.measured_wh,
+                previous.gauge_error_percent()
+            )
+            .dimmed()
+        );
+    }
+    if !nix::unistd::geteuid().is_root() {
+        println!(
+            "  {} not running as root, so the result can't be saved to {}",
+            "Note:".yellow(),
+            calibration::RESULT_PATH
+        );
+    }
+    println!();
+
+    let mut cycle = Calibration::new();
+    print_stage(cycle.stage());
+    loop {
+        let battery = BatteryInfo::detect(&sysfs);
+        match cycle.observe(Reading::from_battery(&battery, unix_now())) {
+            Step::Continue => {}
+            Step::Entered(stage) => {
+                println!();
+                print_stage(stage);
+            }
+            Step::Finished(mut result) => {
+                println!();
+                print_result(&result);
+                result.serial = battery.serial;
+                return result.save(Path::new(calibration::RESULT_PATH));
+            }
+            Step::Aborted(why) => {
+                println!();
+                return Err(Error::Other(format!("calibration aborted: {}", why)));
+            }
+        }
+
+        let percent = battery
+            .capacity_percent
+            .map(|p| format!("{}%", p))
+            .unwrap_or_else(|| "N/A".to_string());
+        print!(
+            "\r  {} {:>5}  discharged {:.1}Wh  charged {:.1}Wh ",
+            battery.status.as_deref().unwrap_or("Unknown").dimmed(),
+            percent,
+            cycle.discharged_wh(),
+            cycle.charged_wh()
+        );
+        let _ = std::io::stdout().flush();
+
+        std::thread::sleep(Duration::from_secs(calibration::SAMPLE_SECS));
+    }
+}
+
+fn print_stage(stage: Stage) {
+    println!("  {} {}", ">>".cyan(), stage.instruction());
+}
+
+fn print_result(result: &CalibrationResult) {
+    println!("{}", "Result".bold());
+    println!("  Measured capacity:  {:.1}Wh", result.measured_wh);
+    println!("  Gauge energy_full:  {:.1}Wh", result.reported_wh);
+    let error = format!("{:+.1}%", result.gauge_error_percent());
+    let error = if result.gauge_error_percent().abs() >= 5.0 {
+        error.yellow().to_string()
+    } else {
+        error
+    };
+    println!("  Fuel gauge error:   {}", error);
+    println!(
+        "  {}",
+        format!(
+            "Delivered {:.1}Wh for {:.1}Wh on the gauge; took {:.1}Wh to recharge",
+            result.discharged_wh, result.gauge_span_wh, result.charged_wh
+        )
+        .dimmed()
+    );
+}
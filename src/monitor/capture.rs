@@ -0,0 +1,515 @@
+//! Capture sessions for `bop monitor --log`. A session is a directory of
+//! CSV parts plus an [`INDEX_FILE`] describing them. A new part starts each
+//! local day and whenever the current one passes [`MAX_PART_BYTES`], so a
+//! week-long capture never grows one file without bound. Every sample is
+//! synced to disk as it is written: a capture that ends with a flat battery
+//! or a crash keeps everything up to the last sample.
+
+use crate::error::{Error, Result};
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Describes the session; rewritten whenever a part is added or the
+/// session is resumed.
+pub const INDEX_FILE: &str = "index.json";
+/// A part past this size is closed and the next sample starts a new one.
+pub const MAX_PART_BYTES: u64 = 10 * 1024 * 1024;
+pub const INDEX_VERSION: u32 = 1;
+
+/// CSV header of every part.
+pub const COLUMNS: [&str; 7] = [
+    "time",
+    "battery_w",
+    "cpu_w",
+    "soc_w",
+    "battery_pct",
+    "discharging",
+    "backlight_pct",
+];
+
+/// One monitor reading. Readings the machine can't provide are `None` and
+/// left empty in the CSV.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// Local time, offset included, so hour-of-day stats don't depend on
+    /// the timezone of the machine reading the capture
+    pub time: DateTime<FixedOffset>,
+    pub battery_watts: Option<f64>,
+    pub cpu_watts: Option<f64>,
+    pub soc_watts: Option<f64>,
+    pub battery_percent: Option<u32>,
+    pub discharging: bool,
+    /// Panel backlight brightness, percent of its maximum
+    pub backlight_percent: Option<u32>,
+}
+
+impl Sample {
+    pub fn to_csv(&self) -> String {
+        let watts = |v: Option<f64>| v.map(|w| format!("{:.3}", w)).unwrap_or_default();
+        format!(
+            "{},{},{},{},{},{},{}",
+            self.time.to_rfc3339(),
+            watts(self.battery_watts),
+            watts(self.cpu_watts),
+            watts(self.soc_watts),
+            self.battery_percent
+                .map(|p| p.to_string())
+                .unwrap_or_default(),
+            u8::from(self.discharging),
+            self.backlight_percent
+                .map(|p| p.to_string())
+                .unwrap_or_default()
+        )
+    }
+
+    /// A line written by [`Sample::to_csv`]; `None` for the header or a
+    /// line cut short by a crash.
+    pub fn parse_csv(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.trim_end().split(',').collect();
+        if fields.len() != COLUMNS.len() {
+            return None;
+        }
+        let percent = |s: &str| -> Option<Option<u32>> {
+            if s.is_empty() {
+                Some(None)
+            } else {
+                s.parse().ok().map(Some)
+            }
+        };
+        let watts = |s: &str| -> Option<Option<f64>> {
+            if s.is_empty() {
+                Some(None)
+            } else {
+                s.parse().ok().map(Some)
+            }
+        };
+        Some(Sample {
+            time: DateTime::parse_from_rfc3339(fields[0]).ok()?,
+            battery_watts: watts(fields[1])?,
+            cpu_watts: watts(fields[2])?,
+            soc_watts: watts(fields[3])?,
+            battery_percent: percent(fields[4])?,
+            discharging: match fields[5] {
+                "0" => false,
+                "1" => true,
+                _ => return None,
+            },
+            backlight_percent: percent(fields[6])?,
+        })
+    }
+}
+
+/// One CSV part of a session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionPart {
+    /// File name inside the session directory
+    pub name: String,
+    /// Local day the part's samples belong to, `YYYY-MM-DD`
+    pub date: String,
+    /// RFC 3339 time of its first sample
+    pub opened: String,
+}
+
+/// Contents of [`INDEX_FILE`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionIndex {
+    pub version: u32,
+    /// RFC 3339 time the session was started
+    pub started: String,
+    pub columns: Vec<String>,
+    pub max_part_bytes: u64,
+    /// Oldest first
+    pub parts: Vec<SessionPart>,
+    /// RFC 3339 times of each `--resume`
+    #[serde(default)]
+    pub resumed: Vec<String>,
+}
+
+impl SessionIndex {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(INDEX_FILE);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| Error::Other(format!("failed to read {}: {}", path.display(), e)))?;
+        serde_json::from_str(&content).map_err(|e| Error::Parse {
+            path,
+            detail: e.to_string(),
+        })
+    }
+
+    /// Replace the index in one rename, so a crash never leaves it half
+    /// written.
+    fn save(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(INDEX_FILE);
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Other(format!("failed to encode capture index: {}", e)))?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json + "\n")
+            .and_then(|()| std::fs::rename(&tmp, &path))
+            .map_err(|e| Error::Other(format!("failed to write {}: {}", path.display(), e)))
+    }
+}
+
+/// The session directory name for one started at `time`. Sorts in start
+/// order.
+pub fn session_name(time: &DateTime<FixedOffset>) -> String {
+    time.format("%Y%m%d-%H%M%S").to_string()
+}
+
+/// `0003-2026-10-15.csv`: sequence first so parts sort in write order even
+/// when one day has several.
+pub fn part_name(seq: usize, date: NaiveDate) -> String {
+    format!("{:04}-{}.csv", seq, date)
+}
+
+/// The most recently started session under `root`, if any.
+pub fn latest_session(root: &Path) -> Result<Option<PathBuf>> {
+    let entries = match std::fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(Error::Other(format!(
+                "failed to read {}: {}",
+                root.display(),
+                e
+            )));
+        }
+    };
+    Ok(entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.join(INDEX_FILE).is_file())
+        .max())
+}
+
+/// The session being written.
+pub struct CaptureLog {
+    dir: PathBuf,
+    index: SessionIndex,
+    /// The current part; `None` until the first sample of a new session
+    part: Option<File>,
+    part_bytes: u64,
+}
+
+impl CaptureLog {
+    /// Start a new session in a fresh directory under `root`.
+    pub fn create(root: &Path, now: DateTime<FixedOffset>, max_part_bytes: u64) -> Result<Self> {
+        let dir = root.join(session_name(&now));
+        let io_err =
+            |e: std::io::Error| Error::Other(format!("failed to create {}: {}", dir.display(), e));
+        std::fs::create_dir_all(root).map_err(io_err)?;
+        std::fs::create_dir(&dir).map_err(io_err)?;
+
+        let index = SessionIndex {
+            version: INDEX_VERSION,
+            started: now.to_rfc3339(),
+            columns: COLUMNS.iter().map(|c| c.to_string()).collect(),
+            max_part_bytes,
+            parts: Vec::new(),
+            resumed: Vec::new(),
+        };
+        index.save(&dir)?;
+        Ok(CaptureLog {
+            dir,
+            index,
+            part: None,
+            part_bytes: 0,
+        })
+    }
+
+    /// Continue the latest session under `root`, appending to its last
+    /// part; a new session if there is none.
+    pub fn resume(root: &Path, now: DateTime<FixedOffset>, max_part_bytes: u64) -> Result<Self> {
+        let Some(dir) = latest_session(root)? else {
+            return Self::create(root, now, max_part_bytes);
+        };
+        let mut index = SessionIndex::load(&dir)?;
+        index.max_part_bytes = max_part_bytes;
+        index.resumed.push(now.to_rfc3339());
+        index.save(&dir)?;
+
+        let mut log = CaptureLog {
+            dir,
+            index,
+            part: None,
+            part_bytes: 0,
+        };
+        if let Some(last) = log.index.parts.last() {
+            let path = log.dir.join(&last.name);
+            let (file, bytes) = open_part(&path)?;
+            log.part = Some(file);
+            log.part_bytes = bytes;
+        }
+        Ok(log)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn index(&self) -> &SessionIndex {
+        &self.index
+    }
+
+    /// Write `sample` through to disk, first starting a new part if the
+    /// day changed or the current part is full.
+    pub fn append(&mut self, sample: &Sample) -> Result<()> {
+        let date = sample.time.date_naive().to_string();
+        let rotate = match (&self.part, self.index.parts.last()) {
+            (Some(_), Some(last)) => {
+                last.date != date || self.part_bytes >= self.index.max_part_bytes
+            }
+            _ => true,
+        };
+        if rotate {
+            self.start_part(sample)?;
+        }
+
+        let line = sample.to_csv() + "\n";
+        let path = self.current_part_path();
+        let part = self.part.as_mut().expect("part opened above");
+        part.write_all(line.as_bytes())
+            .and_then(|()| part.sync_data())
+            .map_err(|e| Error::Other(format!("failed to write {}: {}", path.display(), e)))?;
+        self.part_bytes += line.len() as u64;
+        Ok(())
+    }
+
+    fn current_part_path(&self) -> PathBuf {
+        self.index
+            .parts
+            .last()
+            .map(|p| self.dir.join(&p.name))
+            .unwrap_or_else(|| self.dir.clone())
+    }
+
+    fn start_part(&mut self, sample: &Sample) -> Result<()> {
+        let date = sample.time.date_naive();
+        let name = part_name(self.index.parts.len() + 1, date);
+        let (file, bytes) = open_part(&self.dir.join(&name))?;
+        self.index.parts.push(SessionPart {
+            name,
+            date: date.to_string(),
+            opened: sample.time.to_rfc3339(),
+        });
+        self.index.save(&self.dir)?;
+        self.part = Some(file);
+        self.part_bytes = bytes;
+        Ok(())
+    }
+}
+
+/// Open `path` for appending, writing the header to a new or empty file.
+/// A last line cut short by a crash is ended so the next sample starts on
+/// a line of its own; the reader skips the fragment.
+fn open_part(path: &Path) -> Result<(File, u64)> {
+    let io_err =
+        |e: std::io::Error| Error::Other(format!("failed to open {}: {}", path.display(), e));
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)
+        .map_err(io_err)?;
+    let len = file.metadata().map_err(io_err)?.len();
+    if len == 0 {
+        let header = COLUMNS.join(",") + "\n";
+        file.write_all(header.as_bytes())
+            .and_then(|()| file.sync_data())
+            .map_err(io_err)?;
+        return Ok((file, header.len() as u64));
+    }
+
+    let mut last = [0u8];
+    file.seek(SeekFrom::End(-1))
+        .and_then(|_| file.read_exact(&mut last))
+        .map_err(io_err)?;
+    if last[0] != b'\n' {
+        file.write_all(b"\n").map_err(io_err)?;
+        return Ok((file, len + 1));
+    }
+    Ok((file, len))
+}
+
+/// Every sample in the session at `dir`, oldest first, and the number of
+/// lines that didn't parse (partial lines left by a crash).
+pub fn read_session(dir: &Path) -> Result<(SessionIndex, Vec<Sample>, usize)> {
+    let index = SessionIndex::load(dir)?;
+    let mut samples = Vec::new();
+    let mut skipped = 0;
+    for part in &index.parts {
+        let path = dir.join(&part.name);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            // Listed before its first write landed
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(Error::Other(format!(
+                    "failed to read {}: {}",
+                    path.display(),
+                    e
+                )));
+            }
+        };
+        for line in content.lines().skip(1).filter(|l| !l.is_empty()) {
+            match Sample::parse_csv(line) {
+                Some(sample) => samples.push(sample),
+                None => skipped += 1,
+            }
+        }
+    }
+    Ok((index, samples, skipped))
+}
+
+/// The session to read for `dir`: `dir` itself if it has an index,
+/// otherwise the latest session under it.
+pub fn resolve_session(dir: &Path) -> Result<PathBuf> {
+    if dir.join(INDEX_FILE).is_file() {
+        return Ok(dir.to_path_buf());
+    }
+    latest_session(dir)?.ok_or_else(|| {
+        Error::Other(format!(
+            "no capture session in {} (expected {} or a directory containing sessions)",
+            dir.display(),
+            INDEX_FILE
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn at(time: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(time).unwrap()
+    }
+
+    fn sample(time: &str, watts: f64) -> Sample {
+        Sample {
+            time: at(time),
+            battery_watts: Some(watts),
+            cpu_watts: Some(1.25),
+            soc_watts: None,
+            battery_percent: Some(80),
+            discharging: true,
+            backlight_percent: Some(40),
+        }
+    }
+
+    fn lines(path: &Path) -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_sample_csv_round_trip() {
+        let s = sample("2026-10-15T09:30:00+02:00", 6.5);
+        let line = s.to_csv();
+        assert_eq!(line, "2026-10-15T09:30:00+02:00,6.500,1.250,,80,1,40");
+        assert_eq!(Sample::parse_csv(&line), Some(s));
+        assert_eq!(Sample::parse_csv(&COLUMNS.join(",")), None);
+        assert_eq!(Sample::parse_csv("2026-10-15T09:30:00+02:00,6.5"), None);
+    }
+
+    #[test]
+    fn test_new_part_each_day() {
+        let tmp = TempDir::new().unwrap();
+        let now = at("2026-10-15T23:59:58+02:00");
+        let mut log = CaptureLog::create(tmp.path(), now, MAX_PART_BYTES).unwrap();
+        log.append(&sample("2026-10-15T23:59:58+02:00", 5.0))
+            .unwrap();
+        log.append(&sample("2026-10-16T00:00:00+02:00", 6.0))
+            .unwrap();
+        log.append(&sample("2026-10-16T00:00:02+02:00", 7.0))
+            .unwrap();
+
+        let dir = tmp.path().join("20261015-235958");
+        assert_eq!(log.dir(), dir);
+        let index = SessionIndex::load(&dir).unwrap();
+        let names: Vec<&str> = index.parts.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["0001-2026-10-15.csv", "0002-2026-10-16.csv"]);
+        assert_eq!(index.parts[1].opened, "2026-10-16T00:00:00+02:00");
+
+        assert_eq!(lines(&dir.join("0001-2026-10-15.csv")).len(), 2);
+        let second = lines(&dir.join("0002-2026-10-16.csv"));
+        assert_eq!(second[0], COLUMNS.join(","));
+        assert_eq!(second.len(), 3);
+    }
+
+    #[test]
+    fn test_new_part_when_full() {
+        let tmp = TempDir::new().unwrap();
+        let now = at("2026-10-15T10:00:00Z");
+        // Room for the header and about two samples
+        let mut log = CaptureLog::create(tmp.path(), now, 150).unwrap();
+        for i in 0..5 {
+            let time = format!("2026-10-15T10:00:{:02}Z", i * 2);
+            log.append(&sample(&time, 5.0)).unwrap();
+        }
+
+        let parts = &log.index().parts;
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|p| p.date == "2026-10-15"));
+        let (_, samples, skipped) = read_session(log.dir()).unwrap();
+        assert_eq!(samples.len(), 5);
+        assert_eq!(skipped, 0);
+    }
+
+    #[test]
+    fn test_resume_appends_to_latest_session() {
+        let tmp = TempDir::new().unwrap();
+        let mut old =
+            CaptureLog::create(tmp.path(), at("2026-10-14T08:00:00Z"), MAX_PART_BYTES).unwrap();
+        old.append(&sample("2026-10-14T08:00:00Z", 4.0)).unwrap();
+        let mut log =
+            CaptureLog::create(tmp.path(), at("2026-10-15T08:00:00Z"), MAX_PART_BYTES).unwrap();
+        log.append(&sample("2026-10-15T08:00:00Z", 5.0)).unwrap();
+        let dir = log.dir().to_path_buf();
+        drop(log);
+
+        // Crash mid-write: the last line has no newline
+        let part = dir.join("0001-2026-10-15.csv");
+        let mut file = OpenOptions::new().append(true).open(&part).unwrap();
+        file.write_all(b"2026-10-15T08:00:02Z,5.1").unwrap();
+        drop(file);
+
+        let resumed_at = at("2026-10-15T09:00:00Z");
+        let mut log = CaptureLog::resume(tmp.path(), resumed_at, MAX_PART_BYTES).unwrap();
+        assert_eq!(log.dir(), dir);
+        log.append(&sample("2026-10-15T09:00:00Z", 6.0)).unwrap();
+
+        let index = SessionIndex::load(&dir).unwrap();
+        assert_eq!(index.parts.len(), 1);
+        assert_eq!(index.resumed, vec![resumed_at.to_rfc3339()]);
+        let (_, samples, skipped) = read_session(&dir).unwrap();
+        let watts: Vec<f64> = samples.iter().filter_map(|s| s.battery_watts).collect();
+        assert_eq!(watts, [5.0, 6.0]);
+        assert_eq!(skipped, 1);
+    }
+
+    #[test]
+    fn test_resume_without_session_starts_one() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("capture");
+        let now = at("2026-10-15T08:00:00Z");
+        let mut log = CaptureLog::resume(&root, now, MAX_PART_BYTES).unwrap();
+        log.append(&sample("2026-10-15T08:00:00Z", 5.0)).unwrap();
+        assert_eq!(log.dir(), root.join("20261015-080000"));
+        assert!(log.index().resumed.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_session() {
+        let tmp = TempDir::new().unwrap();
+        assert!(resolve_session(tmp.path()).is_err());
+        let log =
+            CaptureLog::create(tmp.path(), at("2026-10-15T08:00:00Z"), MAX_PART_BYTES).unwrap();
+        assert_eq!(resolve_session(tmp.path()).unwrap(), log.dir());
+        assert_eq!(resolve_session(log.dir()).unwrap(), log.dir());
+    }
+}
@@ -0,0 +1,340 @@
+//! Battery draw before and after a service change, for `bop monitor
+//! analyze`. Deliberately simple: discharging samples on each side of the
+//! change are averaged in fixed windows and the two sides compared.
+//! Anything else that moved at the same time lands in the difference too,
+//! so the comparison names what it can see (brightness, AC cycles) and says
+//! how far to trust it.
+
+use crate::monitor::analyze::Average;
+use crate::monitor::capture::Sample;
+use crate::stats::{self, Confidence};
+use chrono::{DateTime, FixedOffset};
+use std::collections::BTreeMap;
+
+/// Samples up to this long before and after the change are compared.
+pub const SPAN_SECS: i64 = 24 * 3600;
+/// Discharging samples are averaged per window of this length, counted
+/// from the change.
+pub const WINDOW_SECS: i64 = 10 * 60;
+/// A window counts once it has this many discharging samples: a minute at
+/// the monitor's two-second interval.
+pub const MIN_WINDOW_SAMPLES: usize = 30;
+/// Windows needed on each side before a difference is reported.
+pub const MIN_WINDOWS: usize = 3;
+/// Backlight averages this far apart (percentage points) can account for a
+/// watt on their own.
+pub const BACKLIGHT_CONFOUND_POINTS: f64 = 10.0;
+/// Fewer windows per plug-in than this and discharge came in short
+/// stretches, each starting with the draw right after unplugging.
+pub const MIN_WINDOWS_PER_PLUG_IN: usize = 3;
+
+/// The samples on one side of a change.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Side {
+    /// Mean battery draw of each window with enough discharging samples,
+    /// weighted by its sample count
+    pub windows: Vec<(f64, f64)>,
+    /// Times the charger was plugged in: a discharging sample followed by
+    /// one that isn't
+    pub plug_ins: usize,
+    /// Over discharging samples
+    pub backlight_percent: Average,
+}
+
+impl Side {
+    pub fn watts(&self) -> Option<f64> {
+        stats::weighted_mean(&self.windows)
+    }
+
+    pub fn samples(&self) -> usize {
+        self.windows.iter().map(|&(_, count)| count as usize).sum()
+    }
+
+    /// Standard error of [`Side::watts`], treating windows as independent.
+    fn standard_error(&self) -> Option<f64> {
+        let variance = stats::weighted_variance(&self.windows)?;
+        Some((variance / self.windows.len() as f64).sqrt())
+    }
+
+    fn confidence(&self) -> Confidence {
+        match (self.watts(), stats::weighted_variance(&self.windows)) {
+            (Some(mean), Some(variance)) => {
+                Confidence::from_samples(self.windows.len(), mean, variance)
+            }
+            _ => Confidence::Low,
+        }
+    }
+}
+
+/// Something besides the change that differs enough between the two sides
+/// to explain the difference on its own.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Confounder {
+    Backlight { before: f64, after: f64 },
+    AcCycles { before: usize, after: usize },
+}
+
+impl std::fmt::Display for Confounder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Confounder::Backlight { before, after } => write!(
+                f,
+                "backlight averaged {:.0}% before and {:.0}% after",
+                before, after
+            ),
+            Confounder::AcCycles { before, after } => write!(
+                f,
+                "plugged in {} time(s) before and {} after, so discharge came in short stretches",
+                before, after
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Comparison {
+    pub before: Side,
+    pub after: Side,
+}
+
+impl Comparison {
+    /// After minus before, in watts; None without [`MIN_WINDOWS`] on both
+    /// sides.
+    pub fn difference(&self) -> Option<f64> {
+        if self.before.windows.len() < MIN_WINDOWS || self.after.windows.len() < MIN_WINDOWS {
+            return None;
+        }
+        Some(self.after.watts()? - self.before.watts()?)
+    }
+
+    /// Whether the difference is under twice its standard error, i.e. the
+    /// windows vary enough on their own to produce it.
+    pub fn within_noise(&self) -> bool {
+        let (Some(difference), Some(before), Some(after)) = (
+            self.difference(),
+            self.before.standard_error(),
+            self.after.standard_error(),
+        ) else {
+            return true;
+        };
+        difference.abs() < 2.0 * (before.powi(2) + after.powi(2)).sqrt()
+    }
+
+    pub fn confounders(&self) -> Vec<Confounder> {
+        let mut found = Vec::new();
+        if let (Some(before), Some(after)) = (
+            self.before.backlight_percent.mean(),
+            self.after.backlight_percent.mean(),
+        ) && (after - before).abs() >= BACKLIGHT_CONFOUND_POINTS
+        {
+            found.push(Confounder::Backlight { before, after });
+        }
+        let choppy = |side: &Side| side.windows.len() < side.plug_ins * MIN_WINDOWS_PER_PLUG_IN;
+        if choppy(&self.before) || choppy(&self.after) {
+            found.push(Confounder::AcCycles {
+                before: self.before.plug_ins,
+                after: self.after.plug_ins,
+            });
+        }
+        found
+    }
+
+    /// Low when a confounder could explain the difference or it's within
+    /// the noise; otherwise the weaker of the two sides.
+    pub fn confidence(&self) -> Confidence {
+        if self.difference().is_none() || self.within_noise() || !self.confounders().is_empty() {
+            return Confidence::Low;
+        }
+        self.before.confidence().min(self.after.confidence())
+    }
+}
+
+/// Compare [`SPAN_SECS`] of `samples` (oldest first) on each side of `at`.
+pub fn compare(samples: &[Sample], at: DateTime<FixedOffset>) -> Comparison {
+    #[derive(Default)]
+    struct Acc {
+        windows: BTreeMap<i64, (f64, usize)>,
+        plug_ins: usize,
+        backlight_percent: Average,
+        was_discharging: bool,
+    }
+
+    let (mut before, mut after) = (Acc::default(), Acc::default());
+    for sample in samples {
+        let offset = (sample.time - at).num_seconds();
+        if !(-SPAN_SECS..SPAN_SECS).contains(&offset) {
+            continue;
+        }
+        let acc = if offset < 0 { &mut before } else { &mut after };
+        if acc.was_discharging && !sample.discharging {
+            acc.plug_ins += 1;
+        }
+        acc.was_discharging = sample.discharging;
+        if !sample.discharging {
+            continue;
+        }
+        if let Some(watts) = sample.battery_watts.filter(|w| w.is_finite()) {
+            let window = acc
+                .windows
+                .entry(offset.div_euclid(WINDOW_SECS))
+                .or_default();
+            window.0 += watts;
+            window.1 += 1;
+        }
+        acc.backlight_percent
+            .add(sample.backlight_percent.map(f64::from));
+    }
+
+    let side = |acc: Acc| Side {
+        windows: acc
+            .windows
+            .into_values()
+            .filter(|&(_, count)| count >= MIN_WINDOW_SAMPLES)
+            .map(|(sum, count)| (sum / count as f64, count as f64))
+            .collect(),
+        plug_ins: acc.plug_ins,
+        backlight_percent: acc.backlight_percent,
+    };
+    Comparison {
+        before: side(before),
+        after: side(after),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(time).unwrap()
+    }
+
+    /// One sample every two seconds from `start` for `minutes`, drawing
+    /// `watts(i)` for the i-th sample.
+    fn stream(
+        start: &str,
+        minutes: i64,
+        discharging: bool,
+        backlight: u32,
+        watts: impl Fn(i64) -> f64,
+    ) -> Vec<Sample> {
+        let start = at(start);
+        (0..minutes * 30)
+            .map(|i| Sample {
+                time: start + chrono::TimeDelta::seconds(i * 2),
+                battery_watts: Some(watts(i)),
+                cpu_watts: None,
+                soc_watts: None,
+                battery_percent: Some(80),
+                discharging,
+                backlight_percent: Some(backlight),
+            })
+            .collect()
+    }
+
+    /// A small sawtooth around `base`, so windows vary a little.
+    fn around(base: f64) -> impl Fn(i64) -> f64 {
+        move |i| base + ((i / 300) % 3) as f64 * 0.1
+    }
+
+    const CHANGE: &str = "2026-10-15T12:00:00+02:00";
+
+    #[test]
+    fn test_step_change_is_measured() {
+        let mut samples = stream("2026-10-15T10:00:00+02:00", 120, true, 40, around(9.0));
+        samples.extend(stream(CHANGE, 120, true, 40, around(7.5)));
+        let comparison = compare(&samples, at(CHANGE));
+
+        assert_eq!(comparison.before.windows.len(), 12);
+        assert_eq!(comparison.after.windows.len(), 12);
+        assert_eq!(comparison.before.samples(), 3600);
+        let difference = comparison.difference().unwrap();
+        assert!((difference + 1.5).abs() < 1e-9, "{}", difference);
+        assert!(!comparison.within_noise());
+        assert!(comparison.confounders().is_empty());
+        assert_eq!(comparison.confidence(), Confidence::High);
+    }
+
+    #[test]
+    fn test_samples_outside_the_span_or_on_ac_are_ignored() {
+        // A day and a half early, and an hour charging right before
+        let mut samples = stream("2026-10-13T22:00:00+02:00", 60, true, 40, |_| 20.0);
+        samples.extend(stream("2026-10-15T11:00:00+02:00", 60, false, 40, |_| 30.0));
+        samples.extend(stream(CHANGE, 60, true, 40, |_| 8.0));
+        let comparison = compare(&samples, at(CHANGE));
+
+        assert!(comparison.before.windows.is_empty());
+        assert_eq!(comparison.after.watts(), Some(8.0));
+        assert_eq!(comparison.difference(), None);
+        assert_eq!(comparison.confidence(), Confidence::Low);
+    }
+
+    #[test]
+    fn test_sparse_windows_dont_count() {
+        // Twenty seconds of discharge per window isn't a window
+        let samples: Vec<Sample> = (0..6)
+            .flat_map(|w| {
+                let start = at(CHANGE) + chrono::TimeDelta::minutes(w * 10);
+                stream(&start.to_rfc3339(), 1, true, 40, |_| 8.0)
+                    .into_iter()
+                    .take(10)
+            })
+            .collect();
+        assert!(compare(&samples, at(CHANGE)).after.windows.is_empty());
+    }
+
+    #[test]
+    fn test_backlight_change_is_a_confounder() {
+        let mut samples = stream("2026-10-15T10:00:00+02:00", 120, true, 30, around(9.0));
+        samples.extend(stream(CHANGE, 120, true, 80, around(11.0)));
+        let comparison = compare(&samples, at(CHANGE));
+
+        assert!((comparison.difference().unwrap() - 2.0).abs() < 1e-9);
+        assert_eq!(
+            comparison.confounders(),
+            [Confounder::Backlight {
+                before: 30.0,
+                after: 80.0
+            }]
+        );
+        assert_eq!(comparison.confidence(), Confidence::Low);
+    }
+
+    #[test]
+    fn test_ac_cycles_are_counted_and_flag_short_stretches() {
+        let mut samples = stream("2026-10-15T10:00:00+02:00", 120, true, 40, around(9.0));
+        // After: four 20-minute stretches on battery, each followed by AC
+        for hour in 0..4 {
+            let start = at(CHANGE) + chrono::TimeDelta::minutes(hour * 40);
+            samples.extend(stream(&start.to_rfc3339(), 20, true, 40, around(7.0)));
+            let plugged = start + chrono::TimeDelta::minutes(20);
+            samples.extend(stream(&plugged.to_rfc3339(), 20, false, 40, |_| 25.0));
+        }
+        let comparison = compare(&samples, at(CHANGE));
+
+        assert_eq!(comparison.before.plug_ins, 0);
+        assert_eq!(comparison.after.plug_ins, 4);
+        assert_eq!(comparison.after.windows.len(), 8);
+        assert_eq!(
+            comparison.confounders(),
+            [Confounder::AcCycles {
+                before: 0,
+                after: 4
+            }]
+        );
+        assert_eq!(comparison.confidence(), Confidence::Low);
+    }
+
+    #[test]
+    fn test_difference_within_noise_is_low_confidence() {
+        // Windows swing between 6W and 12W; a 0.1W shift is lost in that
+        let swing = |base: f64| move |i: i64| base + if (i / 300) % 2 == 0 { 0.0 } else { 6.0 };
+        let mut samples = stream("2026-10-15T10:00:00+02:00", 120, true, 40, swing(6.0));
+        samples.extend(stream(CHANGE, 120, true, 40, swing(6.1)));
+        let comparison = compare(&samples, at(CHANGE));
+
+        assert!(comparison.difference().is_some());
+        assert!(comparison.within_noise());
+        assert_eq!(comparison.confidence(), Confidence::Low);
+    }
+}
@@ -1,24 +1,65 @@
+pub mod analyze;
+pub mod calibrate;
+pub mod capture;
+pub mod changes;
 pub mod power_draw;
 
+use crate::audit::retimer;
+use crate::calibration::{self, CalibrationResult};
+use crate::config::IdleDrainConfig;
 use crate::detect::battery::BatteryInfo;
+use crate::detect::typec::TypecInfo;
 use crate::error::Result;
+use crate::idle_drain::{self, DrainAlert, DrainWatch};
+use crate::monitor::capture::{CaptureLog, Sample};
+use crate::notify::{self, EventKind};
 use crate::sysfs::SysfsRoot;
+use chrono::{DateTime, FixedOffset};
 use colored::Colorize;
+use std::collections::BTreeMap;
 use std::io::Write;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
-/// Run the real-time power monitor.
-pub fn run() -> Result<()> {
+/// `bop monitor --log`: where to capture every sample.
+pub struct LogOptions<'a> {
+    /// Holds one directory per session
+    pub dir: &'a Path,
+    /// Continue the latest session instead of starting one
+    pub resume: bool,
+}
+
+/// Run the real-time power monitor. On machines with USB-C ports it also
+/// runs the idle drain watch configured by `drain`.
+pub fn run(drain: &IdleDrainConfig, log: Option<LogOptions>) -> Result<()> {
     let sysfs = SysfsRoot::system();
 
     println!("{}", "Power Monitor".bold().underline());
     println!("Press Ctrl+C to stop");
 
+    let mut capture = match log {
+        Some(log) => {
+            let now = chrono::Local::now().fixed_offset();
+            let capture = if log.resume {
+                CaptureLog::resume(log.dir, now, capture::MAX_PART_BYTES)?
+            } else {
+                CaptureLog::create(log.dir, now, capture::MAX_PART_BYTES)?
+            };
+            println!("Logging to {}", capture.dir().display());
+            Some(capture)
+        }
+        None => None,
+    };
+    // Recorded so `bop monitor analyze` can tell brightness changes apart
+    let backlight = capture
+        .as_ref()
+        .and_then(|_| crate::brightness::find_backlight(&sysfs));
+
     let start = Instant::now();
     let rapl = power_draw::RaplReader::new(&sysfs);
-    let mut prev_rapl = rapl.read_energy();
-
-    let has_rapl = prev_rapl.is_some();
+    let first_rapl = rapl.read_energy();
+    let mut has_rapl = first_rapl.is_some();
+    let mut rapl_tracker = power_draw::RaplTracker::new(first_rapl);
     if !has_rapl {
         println!(
             "  {} RAPL counters unavailable (try running with sudo for CPU/SoC power)",
@@ -26,50 +67,64 @@ pub fn run() -> Result<()> {
         );
     }
 
-    println!();
-    if has_rapl {
-        println!(
-            "{} {} {} {} {} {}",
-            format!("{:>8}", "Time").dimmed(),
-            format!("{:>10}", "Battery W").cyan(),
-            format!("{:>10}", "CPU W").cyan(),
-            format!("{:>10}", "SoC W").cyan(),
-            format!("{:>10}", "Batt %").cyan(),
-            format!("{:>10}", "Est Hours").cyan(),
-        );
-    } else {
+    // Idle drain is blamed on USB-C retimers, so only watched on machines
+    // that expose the typec class at all
+    let has_typec = !TypecInfo::detect(&sysfs).ports.is_empty();
+
+    let calibrated = CalibrationResult::load_for(
+        Path::new(calibration::RESULT_PATH),
+        &BatteryInfo::detect(&sysfs),
+    );
+    if let Some(result) = &calibrated {
         println!(
-            "{} {} {} {}",
-            format!("{:>8}", "Time").dimmed(),
-            format!("{:>10}", "Battery W").cyan(),
-            format!("{:>10}", "Batt %").cyan(),
-            format!("{:>10}", "Est Hours").cyan(),
+            "  {}",
+            format!(
+                "Est Hours uses the calibrated capacity ({:.1}Wh measured)",
+                result.measured_wh
+            )
+            .dimmed()
         );
     }
-    println!("{}", "-".repeat(if has_rapl { 63 } else { 41 }).dimmed());
+
+    let mut drain_watch = has_typec.then(|| DrainWatch::new(drain, idle_drain::HISTORY_PATH));
+
+    println!();
+    print_header(has_rapl);
 
     loop {
         std::thread::sleep(Duration::from_secs(2));
 
         let elapsed = start.elapsed();
         let battery = BatteryInfo::detect(&sysfs);
-        let curr_rapl = rapl.read_energy();
 
         // Battery power
         let bat_power = battery.power_watts();
 
         // RAPL power (delta over 2 seconds)
-        let (cpu_power, soc_power) = if let (Some(prev), Some(curr)) = (&prev_rapl, &curr_rapl) {
-            let dt = 2.0; // seconds
-            let cpu_w = (curr.cpu_uj.saturating_sub(prev.cpu_uj)) as f64 / 1_000_000.0 / dt;
-            let soc_w = (curr.soc_uj.saturating_sub(prev.soc_uj)) as f64 / 1_000_000.0 / dt;
-            (Some(cpu_w), Some(soc_w))
-        } else {
-            (None, None)
+        let (cpu_power, soc_power) = match rapl_tracker.observe(rapl.read_energy(), 2.0) {
+            power_draw::RaplSample::Power {
+                cpu_watts,
+                soc_watts,
+            } => (Some(cpu_watts), Some(soc_watts)),
+            power_draw::RaplSample::Skipped => (None, None),
+            power_draw::RaplSample::Stuck => {
+                println!();
+                println!(
+                    "  {} RAPL counters present but not updating — CPU/SoC power hidden",
+                    "Note:".yellow()
+                );
+                has_rapl = false;
+                print_header(has_rapl);
+                (None, None)
+            }
         };
 
-        // Estimated remaining hours
-        let est_hours = match (battery.energy_wh(), bat_power) {
+        // Estimated remaining hours, from the calibrated capacity if measured
+        let energy = match &calibrated {
+            Some(result) => result.corrected_energy_wh(&battery),
+            None => battery.energy_wh(),
+        };
+        let est_hours = match (energy, bat_power) {
             (Some(energy), Some(power)) if power > 0.5 => Some(energy / power),
             _ => None,
         };
@@ -89,24 +144,15 @@ pub fn run() -> Result<()> {
             .map(|p| format!("{}%", p))
             .unwrap_or_else(|| "N/A".to_string());
 
+        let mut values = vec![fmt(bat_power, "W")];
         if has_rapl {
-            print!(
-                "\r{:>8} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                time_str,
-                fmt(bat_power, "W"),
-                fmt(cpu_power, "W"),
-                fmt(soc_power, "W"),
-                batt_pct,
-                fmt(est_hours, "h"),
-            );
-        } else {
-            print!(
-                "\r{:>8} {:>10} {:>10} {:>10}",
-                time_str,
-                fmt(bat_power, "W"),
-                batt_pct,
-                fmt(est_hours, "h"),
-            );
+            values.extend([fmt(cpu_power, "W"), fmt(soc_power, "W")]);
+        }
+        values.extend([batt_pct, fmt(est_hours, "h")]);
+
+        print!("\r{:>8}", time_str);
+        for value in &values {
+            print!(" {:>10}", value);
         }
         let _ = std::io::stdout().flush();
 
@@ -115,6 +161,256 @@ pub fn run() -> Result<()> {
             println!();
         }
 
-        prev_rapl = curr_rapl;
+        if let Some(capture) = capture.as_mut() {
+            capture.append(&Sample {
+                time: chrono::Local::now().fixed_offset(),
+                battery_watts: bat_power,
+                cpu_watts: cpu_power,
+                soc_watts: soc_power,
+                battery_percent: battery.capacity_percent,
+                discharging: battery.is_discharging(),
+                backlight_percent: backlight
+                    .as_deref()
+                    .and_then(|device| crate::brightness::percent(&sysfs, device)),
+            })?;
+        }
+
+        if let Some(watch) = drain_watch.as_mut() {
+            let idle = idle_drain::is_idle(idle_drain::cpu_pressure(&sysfs), cpu_power);
+            let draw = bat_power.filter(|_| battery.is_discharging());
+            if let Some(alert) = watch.observe(idle_drain::unix_now(), idle, draw) {
+                report_drain(&alert, &TypecInfo::detect(&sysfs), drain.notify);
+            }
+        }
+    }
+}
+
+fn print_header(has_rapl: bool) {
+    let mut headers = vec!["Battery W"];
+    if has_rapl {
+        headers.extend(["CPU W", "SoC W"]);
+    }
+    headers.extend(["Batt %", "Est Hours"]);
+
+    print!("{}", format!("{:>8}", "Time").dimmed());
+    for header in &headers {
+        print!(" {}", format!("{:>10}", header).cyan());
+    }
+    println!();
+    println!("{}", "-".repeat(8 + 11 * headers.len()).dimmed());
+}
+
+fn report_drain(alert: &DrainAlert, typec: &TypecInfo, send_notification: bool) {
+    let summary = retimer::summary(alert);
+    let remedy = retimer::remedy(typec);
+
+    println!();
+    println!("  {} {}", "Warning:".yellow().bold(), summary);
+    println!("  {}", remedy.dimmed());
+    if send_notification {
+        let _ = notify::send(EventKind::Drain, &summary, &remedy);
+    }
+}
+
+/// `bop monitor analyze`: aggregate stats for the session at `dir`, or the
+/// latest one under it.
+pub fn run_analyze(dir: &Path) -> Result<()> {
+    let session = capture::resolve_session(dir)?;
+    let (index, samples, skipped) = capture::read_session(&session)?;
+    let stats = analyze::analyze(&samples);
+
+    println!("{}", "Capture Session".bold().underline());
+    println!("  Session:  {}", session.display());
+    println!("  Started:  {}", index.started);
+    if let (Some(first), Some(last)) = (stats.first, stats.last) {
+        let span = last - first;
+        println!(
+            "  Samples:  {} over {}h {:02}m in {} file(s)",
+            stats.samples,
+            span.num_hours(),
+            span.num_minutes() % 60,
+            index.parts.len()
+        );
+    } else {
+        println!("  Samples:  none");
+    }
+    if !index.resumed.is_empty() {
+        println!("  Resumed:  {} time(s)", index.resumed.len());
+    }
+    if skipped > 0 {
+        println!(
+            "  {} {} incomplete line(s) skipped",
+            "Note:".yellow(),
+            skipped
+        );
+    }
+    if stats.samples == 0 {
+        return Ok(());
+    }
+
+    let fmt = |v: Option<f64>, suffix: &str| -> String {
+        v.map(|w| format!("{:.1}{}", w, suffix))
+            .unwrap_or_else(|| "N/A".to_string())
+    };
+    let row = |label: &str, period: &analyze::PeriodStats| {
+        println!(
+            "{:>8} {:>10} {:>10} {:>10} {:>10}",
+            label,
+            fmt(period.battery_watts.mean(), "W"),
+            fmt(period.cpu_watts.mean(), "W"),
+            fmt(period.percent_per_hour(), "%/h"),
+            period.samples
+        );
+    };
+
+    println!();
+    print!("{}", format!("{:>8}", "Hour").dimmed());
+    for header in ["Battery W", "CPU W", "Drain", "Samples"] {
+        print!(" {}", format!("{:>10}", header).cyan());
+    }
+    println!();
+    println!("{}", "-".repeat(8 + 11 * 4).dimmed());
+    for (hour, period) in stats.hours.iter().enumerate() {
+        if period.samples > 0 {
+            row(&format!("{:02}:00", hour), period);
+        }
+    }
+    println!("{}", "-".repeat(8 + 11 * 4).dimmed());
+    row("Day", &stats.day);
+    row("Night", &stats.night);
+    row("All", &stats.total);
+    println!();
+    println!(
+        "  {}",
+        format!(
+            "Day is {:02}:00-{:02}:00 local time. Battery W and Drain count discharging samples only.",
+            analyze::DAY_HOURS.start,
+            analyze::DAY_HOURS.end
+        )
+        .dimmed()
+    );
+
+    if let Some(state) = crate::apply::ApplyState::load().ok().flatten() {
+        print_service_changes(&state, &samples, &stats);
+    }
+    Ok(())
+}
+
+/// Battery draw before and after the services bop disabled, when the
+/// capture covers the time they were.
+fn print_service_changes(
+    state: &crate::apply::ApplyState,
+    samples: &[Sample],
+    stats: &analyze::SessionStats,
+) {
+    if state.services_disabled.is_empty() {
+        return;
+    }
+    let (Some(first), Some(last)) = (stats.first, stats.last) else {
+        return;
+    };
+
+    // Services disabled in the same run share a change; the state's own
+    // timestamp moves with every re-apply, so it can't stand in for one
+    let mut changes: BTreeMap<_, Vec<&str>> = BTreeMap::new();
+    let mut unknown = Vec::new();
+    for service in &state.services_disabled {
+        match state
+            .services_disabled_at
+            .get(service)
+            .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+        {
+            Some(at) => changes
+                .entry(at.with_timezone(first.offset()))
+                .or_default()
+                .push(service.as_str()),
+            None => unknown.push(service.as_str()),
+        }
+    }
+
+    println!();
+    println!("{}", "Service Changes".bold().underline());
+    if !unknown.is_empty() {
+        println!("  Disabled {} at an unrecorded time", unknown.join(", "));
+        println!(
+            "  {}",
+            "The state predates per-service times; revert and apply again to compare.".dimmed()
+        );
+    }
+    for (at, services) in changes {
+        print_service_change(&services, at, first, last, samples);
+    }
+}
+
+fn print_service_change(
+    services: &[&str],
+    at: DateTime<FixedOffset>,
+    first: DateTime<FixedOffset>,
+    last: DateTime<FixedOffset>,
+    samples: &[Sample],
+) {
+    println!(
+        "  Disabled {} at {}",
+        services.join(", "),
+        at.format("%Y-%m-%d %H:%M")
+    );
+    if at < first || at > last {
+        println!(
+            "  {}",
+            "Outside this capture; record one that spans the change to compare.".dimmed()
+        );
+        return;
+    }
+
+    let comparison = changes::compare(samples, at);
+    let side = |label: &str, side: &changes::Side| {
+        println!(
+            "  {:<8}{:>8}  {} window(s), {} sample(s)",
+            label,
+            side.watts()
+                .map(|w| format!("{:.2}W", w))
+                .unwrap_or_else(|| "N/A".to_string()),
+            side.windows.len(),
+            side.samples()
+        );
+    };
+    side("Before:", &comparison.before);
+    side("After:", &comparison.after);
+    match comparison.difference() {
+        Some(difference) => println!(
+            "  Difference: {:+.2}W (confidence: {})",
+            difference,
+            comparison.confidence()
+        ),
+        None => println!(
+            "  Difference: N/A (needs {} windows of discharge on each side)",
+            changes::MIN_WINDOWS
+        ),
+    }
+    if comparison.difference().is_some() && comparison.within_noise() {
+        println!(
+            "  {} within the usual variation between windows",
+            "Note:".yellow()
+        );
+    }
+    for confounder in comparison.confounders() {
+        println!("  {} {}", "Note:".yellow(), confounder);
+    }
+    if comparison.before.backlight_percent.count() == 0
+        || comparison.after.backlight_percent.count() == 0
+    {
+        println!(
+            "  {} no backlight readings on both sides, so brightness changes can't be ruled out",
+            "Note:".yellow()
+        );
     }
+    println!(
+        "  {}",
+        format!(
+            "Discharging samples up to {}h either side, averaged per {}-minute window. Everything else bop changed in the same run counts too.",
+            changes::SPAN_SECS / 3600,
+            changes::WINDOW_SECS / 60
+        )
+        .dimmed()
+    );
 }
@@ -1,13 +1,18 @@
 use crate::sysfs::SysfsRoot;
 
+/// Intervals with no counter movement before the counters count as stuck.
+/// Real counters move within one: even an idle package burns milliwatts.
+pub const STUCK_AFTER_INTERVALS: u32 = 3;
+
 /// RAPL (Running Average Power Limit) energy counters.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RaplEnergy {
     pub cpu_uj: u64, // microjoules
     pub soc_uj: u64, // microjoules (package includes CPU + iGPU + IO)
 }
 
 pub struct RaplReader {
+    sysfs: SysfsRoot,
     cpu_path: Option<String>,
     soc_path: Option<String>,
 }
@@ -24,15 +29,11 @@ impl RaplReader {
                 if let Some(name) = sysfs.read_optional(&name_path).unwrap_or(None) {
                     let energy_path = format!("{}/{}/energy_uj", rapl_base, entry);
                     match name.as_str() {
-                        "core" => {
-                            if sysfs.exists(&energy_path) {
-                                cpu_path = Some(energy_path);
-                            }
+                        "core" if sysfs.exists(&energy_path) => {
+                            cpu_path = Some(energy_path);
                         }
-                        "package-0" => {
-                            if sysfs.exists(&energy_path) {
-                                soc_path = Some(energy_path);
-                            }
+                        "package-0" if sysfs.exists(&energy_path) => {
+                            soc_path = Some(energy_path);
                         }
                         _ => {}
                     }
@@ -40,22 +41,24 @@ impl RaplReader {
             }
         }
 
-        Self { cpu_path, soc_path }
+        Self {
+            sysfs: sysfs.clone(),
+            cpu_path,
+            soc_path,
+        }
     }
 
     pub fn read_energy(&self) -> Option<RaplEnergy> {
-        let sysfs = SysfsRoot::system();
-
         let cpu_uj = self
             .cpu_path
             .as_ref()
-            .and_then(|p| sysfs.read_parse::<u64>(p).ok())
+            .and_then(|p| self.sysfs.read_parse::<u64>(p).ok())
             .unwrap_or(0);
 
         let soc_uj = self
             .soc_path
             .as_ref()
-            .and_then(|p| sysfs.read_parse::<u64>(p).ok())
+            .and_then(|p| self.sysfs.read_parse::<u64>(p).ok())
             .unwrap_or(0);
 
         if cpu_uj == 0 && soc_uj == 0 {
@@ -65,3 +68,216 @@ impl RaplReader {
         Some(RaplEnergy { cpu_uj, soc_uj })
     }
 }
+
+/// What one interval between two counter readings says.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RaplSample {
+    /// Average draw over the interval
+    Power { cpu_watts: f64, soc_watts: f64 },
+    /// Nothing usable: a reading failed, a counter went backwards (reset or
+    /// wrap), or the counters haven't moved yet
+    Skipped,
+    /// The counters never moved; returned once, [`RaplSample::Skipped`]
+    /// from then on
+    Stuck,
+}
+
+/// Turns successive counter readings into power, spotting counters that
+/// exist but never increment (a BIOS bug on some AMD boards) so they
+/// aren't shown as a steady 0.0W.
+#[derive(Debug)]
+pub struct RaplTracker {
+    prev: Option<RaplEnergy>,
+    /// Intervals so far in which no counter moved
+    unchanged: u32,
+    /// A counter has moved at least once, so a still interval is real
+    moved: bool,
+    stuck: bool,
+}
+
+impl RaplTracker {
+    pub fn new(first: Option<RaplEnergy>) -> Self {
+        Self {
+            prev: first,
+            unchanged: 0,
+            moved: false,
+            stuck: false,
+        }
+    }
+
+    pub fn is_stuck(&self) -> bool {
+        self.stuck
+    }
+
+    /// Feed the next reading, taken `secs` after the previous one.
+    pub fn observe(&mut self, curr: Option<RaplEnergy>, secs: f64) -> RaplSample {
+        let prev = std::mem::replace(&mut self.prev, curr);
+        if self.stuck {
+            return RaplSample::Skipped;
+        }
+        let (Some(prev), Some(curr)) = (prev, curr) else {
+            return RaplSample::Skipped;
+        };
+        if curr.cpu_uj < prev.cpu_uj || curr.soc_uj < prev.soc_uj {
+            // Wraparound arithmetic here would show hundreds of watts
+            return RaplSample::Skipped;
+        }
+        if curr == prev && !self.moved {
+            self.unchanged += 1;
+            if self.unchanged >= STUCK_AFTER_INTERVALS {
+                self.stuck = true;
+                return RaplSample::Stuck;
+            }
+            return RaplSample::Skipped;
+        }
+        self.moved = true;
+        RaplSample::Power {
+            cpu_watts: (curr.cpu_uj - prev.cpu_uj) as f64 / 1_000_000.0 / secs,
+            soc_watts: (curr.soc_uj - prev.soc_uj) as f64 / 1_000_000.0 / secs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn energy(cpu_uj: u64, soc_uj: u64) -> Option<RaplEnergy> {
+        Some(RaplEnergy { cpu_uj, soc_uj })
+    }
+
+    /// Samples for readings taken a second apart, the first seeding the tracker.
+    fn samples(readings: &[Option<RaplEnergy>]) -> (RaplTracker, Vec<RaplSample>) {
+        let mut tracker = RaplTracker::new(readings[0]);
+        let samples = readings[1..]
+            .iter()
+            .map(|r| tracker.observe(*r, 1.0))
+            .collect();
+        (tracker, samples)
+    }
+
+    #[test]
+    fn test_moving_counters_give_power() {
+        let (tracker, samples) = samples(&[
+            energy(1_000_000, 5_000_000),
+            energy(3_000_000, 9_000_000),
+            energy(3_500_000, 10_000_000),
+        ]);
+        assert_eq!(
+            samples,
+            [
+                RaplSample::Power {
+                    cpu_watts: 2.0,
+                    soc_watts: 4.0
+                },
+                RaplSample::Power {
+                    cpu_watts: 0.5,
+                    soc_watts: 1.0
+                },
+            ]
+        );
+        assert!(!tracker.is_stuck());
+    }
+
+    #[test]
+    fn test_stuck_counters_are_reported_once() {
+        let still = energy(42, 4_200);
+        let (tracker, samples) = samples(&[still; 6]);
+        assert_eq!(
+            samples,
+            [
+                RaplSample::Skipped,
+                RaplSample::Skipped,
+                RaplSample::Stuck,
+                RaplSample::Skipped,
+                RaplSample::Skipped,
+            ]
+        );
+        assert!(tracker.is_stuck());
+    }
+
+    #[test]
+    fn test_still_interval_after_movement_is_real_zero() {
+        let (tracker, samples) = samples(&[
+            energy(0, 100),
+            energy(0, 1_100),
+            energy(0, 1_100),
+            energy(0, 1_100),
+            energy(0, 1_100),
+        ]);
+        assert_eq!(
+            samples[0],
+            RaplSample::Power {
+                cpu_watts: 0.0,
+                soc_watts: 0.001
+            }
+        );
+        assert!(samples[1..].iter().all(|s| *s
+            == RaplSample::Power {
+                cpu_watts: 0.0,
+                soc_watts: 0.0
+            }));
+        assert!(!tracker.is_stuck());
+    }
+
+    #[test]
+    fn test_counter_reset_discards_the_interval() {
+        let (_, samples) = samples(&[
+            energy(u64::from(u32::MAX) - 1_000_000, 8_000_000),
+            // package counter wrapped, core counter kept going
+            energy(u64::from(u32::MAX), 500_000),
+            energy(u64::from(u32::MAX) + 2_000_000, 2_500_000),
+        ]);
+        assert_eq!(samples[0], RaplSample::Skipped);
+        assert_eq!(
+            samples[1],
+            RaplSample::Power {
+                cpu_watts: 2.0,
+                soc_watts: 2.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_missing_reading_is_skipped_without_counting_as_stuck() {
+        let (tracker, samples) = samples(&[
+            energy(10, 10),
+            None,
+            energy(10, 10),
+            energy(10, 10),
+            energy(10, 10),
+            energy(20, 20),
+        ]);
+        assert_eq!(samples[..4], [RaplSample::Skipped; 4]);
+        assert!(matches!(samples[4], RaplSample::Power { .. }));
+        assert!(!tracker.is_stuck());
+    }
+
+    #[test]
+    fn test_reader_follows_sysfs_counters() {
+        let tmp = TempDir::new().unwrap();
+        let sysfs = SysfsRoot::new(tmp.path());
+        for (zone, name, value) in [
+            ("intel-rapl:0", "package-0", "0"),
+            ("intel-rapl:0:0", "core", "0"),
+        ] {
+            let dir = tmp.path().join("sys/class/powercap").join(zone);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("name"), name).unwrap();
+            std::fs::write(dir.join("energy_uj"), value).unwrap();
+        }
+
+        let reader = RaplReader::new(&sysfs);
+        // Counters that read zero are treated as unavailable
+        assert_eq!(reader.read_energy(), None);
+
+        let write = |zone: &str, value: &str| {
+            let path = tmp.path().join("sys/class/powercap").join(zone);
+            std::fs::write(path.join("energy_uj"), value).unwrap();
+        };
+        write("intel-rapl:0", "9000");
+        write("intel-rapl:0:0", "4000");
+        assert_eq!(reader.read_energy(), energy(4_000, 9_000));
+    }
+}
@@ -1,55 +1,366 @@
-use anyhow::Result;
+//! Desktop notifications. Root-side bop (`bop auto`, run by udev) can't
+//! reliably reach a user's session bus, so it only appends an event record
+//! to [`EVENTS_PATH`]; `bop notify-listen`, started in the user session by
+//! the bop-notify user unit, tails that file and raises the notification.
+//! `bop dbus-serve` tails the same file to signal auto switches.
 
-/// Send a desktop notification. Finds the active graphical session and
-/// runs notify-send as that user with their D-Bus session address.
-/// Fails silently if no graphical session is found.
-pub fn send(title: &str, body: &str) -> Result<()> {
-    // Find graphical sessions via loginctl
-    let output = std::process::Command::new("loginctl")
-        .args(["list-sessions", "--no-legend", "--no-pager"])
-        .output()?;
+use crate::error::{Error, Result};
+use crate::managed::Ownership;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-    if !output.status.success() {
-        return Ok(());
+/// Root-writable, world-readable event file, one JSON object per line.
+pub const EVENTS_PATH: &str = "/run/bop/events";
+/// Past this size the event file is started over; listeners notice it
+/// shrinking and read from the top.
+const EVENTS_MAX_BYTES: u64 = 64 * 1024;
+
+pub const USER_UNIT_NAME: &str = "bop-notify.service";
+/// Installed for every user; `systemctl --global enable` starts it with
+/// each graphical session.
+pub const USER_UNIT_PATH: &str = "/etc/systemd/user/bop-notify.service";
+
+/// Events of one kind closer together than this raise a single
+/// notification: a wobbly cable fires several power_supply changes.
+pub const DEBOUNCE_SECS: u64 = 5;
+
+/// What happened, so the listener can debounce per kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Applied,
+    Reverted,
+    /// Idle draw stuck above the usual, see [`crate::idle_drain`]
+    Drain,
+    /// Applied with the latency-sensitive items held back for a nearly
+    /// empty battery (`[auto] critical_battery_percent`)
+    Critical,
+}
+
+impl EventKind {
+    /// notify-send `--urgency`: critical notifications stay up until
+    /// dismissed.
+    pub fn urgency(self) -> &'static str {
+        match self {
+            EventKind::Critical => "critical",
+            _ => "normal",
+        }
+    }
+}
+
+/// One line of the event file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotifyEvent {
+    pub kind: EventKind,
+    pub title: String,
+    pub body: String,
+    /// Unix time in seconds
+    pub time: u64,
+    /// Recorded for other readers of the file (`bop dbus-serve`) but not
+    /// shown, because notifications for it are turned off
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub quiet: bool,
+}
+
+impl NotifyEvent {
+    pub fn new(kind: EventKind, title: &str, body: &str) -> Self {
+        Self {
+            kind,
+            title: title.to_string(),
+            body: body.to_string(),
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            quiet: false,
+        }
+    }
+
+    /// Record the event without showing it.
+    pub fn quiet(mut self) -> Self {
+        self.quiet = true;
+        self
+    }
+
+    /// The event as a JSON line, newline included.
+    pub fn to_line(&self) -> String {
+        let mut line = serde_json::to_string(self).expect("event serializes");
+        line.push('\n');
+        line
+    }
+}
+
+/// Queue a desktop notification for the session-side listener. Without a
+/// listener running the event is simply never shown.
+pub fn send(kind: EventKind, title: &str, body: &str) -> Result<()> {
+    record(&NotifyEvent::new(kind, title, body))
+}
+
+/// Append `event` to [`EVENTS_PATH`].
+pub fn record(event: &NotifyEvent) -> Result<()> {
+    append_event(Path::new(EVENTS_PATH), event)
+}
+
+/// Append `event` to the event file at `path`, starting the file over once
+/// it has grown past [`EVENTS_MAX_BYTES`].
+pub fn append_event(path: &Path, event: &NotifyEvent) -> Result<()> {
+    let io_err =
+        |e: std::io::Error| Error::Other(format!("failed to write {}: {}", path.display(), e));
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(io_err)?;
+    }
+    if std::fs::metadata(path).is_ok_and(|m| m.len() > EVENTS_MAX_BYTES) {
+        std::fs::write(path, "").map_err(io_err)?;
     }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o644)
+        .open(path)
+        .and_then(|mut file| file.write_all(event.to_line().as_bytes()))
+        .map_err(io_err)
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+/// Events from the complete lines of `chunk`, and the number of bytes
+/// those lines took. A trailing line without its newline is still being
+/// written and is left for the next read. Lines that don't parse are
+/// skipped.
+pub fn parse_events(chunk: &[u8]) -> (Vec<NotifyEvent>, usize) {
+    let Some(end) = chunk.iter().rposition(|&b| b == b'\n') else {
+        return (Vec::new(), 0);
+    };
+    let events = chunk[..end]
+        .split(|&b| b == b'\n')
+        .filter_map(|line| serde_json::from_slice(line).ok())
+        .collect();
+    (events, end + 1)
+}
 
-    for line in stdout.lines() {
-        let fields: Vec<&str> = line.split_whitespace().collect();
-        if fields.len() < 3 {
-            continue;
+/// Drops events that follow one of the same kind within [`DEBOUNCE_SECS`].
+#[derive(Debug, Default)]
+pub struct Debouncer {
+    last_shown: HashMap<EventKind, u64>,
+}
+
+impl Debouncer {
+    /// Whether `event` should be shown.
+    pub fn accept(&mut self, event: &NotifyEvent) -> bool {
+        if let Some(&last) = self.last_shown.get(&event.kind)
+            && event.time >= last
+            && event.time - last < DEBOUNCE_SECS
+        {
+            return false;
         }
-        let session_id = fields[0];
-        let uid = fields[1];
-        let user = fields[2];
-
-        // Check if this session is graphical
-        let session_type = std::process::Command::new("loginctl")
-            .args(["show-session", session_id, "--property=Type", "--value"])
-            .output();
-
-        let session_type = match session_type {
-            Ok(o) => String::from_utf8_lossy(&o.stdout).trim().to_string(),
-            Err(_) => continue,
+        self.last_shown.insert(event.kind, event.time);
+        true
+    }
+}
+
+/// Read position in the event file.
+pub struct EventTail {
+    path: PathBuf,
+    offset: usize,
+}
+
+impl EventTail {
+    /// Starts at the current end of the file, so events from before the
+    /// listener started aren't replayed at login.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let offset = std::fs::metadata(&path).map_or(0, |m| m.len() as usize);
+        Self { path, offset }
+    }
+
+    /// Events appended since the last read.
+    pub fn read_new(&mut self) -> Vec<NotifyEvent> {
+        let Ok(content) = std::fs::read(&self.path) else {
+            self.offset = 0;
+            return Vec::new();
         };
+        if content.len() < self.offset {
+            // Started over (size cap or reboot)
+            self.offset = 0;
+        }
+        let (events, used) = parse_events(&content[self.offset..]);
+        self.offset += used;
+        events
+    }
+}
+
+/// `bop notify-listen`: raise a notification for each new event until
+/// `stop` is set.
+pub fn listen(stop: &AtomicBool) -> Result<()> {
+    let mut tail = EventTail::new(EVENTS_PATH);
+    let mut debouncer = Debouncer::default();
+    let mut watcher = crate::watch::FileWatcher::new(vec![PathBuf::from(EVENTS_PATH)]);
+
+    while watcher.wait_for_change(Duration::from_millis(200), stop) {
+        for event in tail.read_new() {
+            if !event.quiet && debouncer.accept(&event) {
+                let _ = crate::cmd::Cmd::new("notify-send")
+                    .args(["--urgency", event.kind.urgency()])
+                    .args([&event.title, &event.body])
+                    .status();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The user unit that runs the listener in each graphical session.
+pub fn render_user_unit() -> String {
+    format!(
+        "{}
+[Unit]
+Description=bop desktop notifications
+PartOf=graphical-session.target
+After=graphical-session.target
+
+[Service]
+ExecStart=/usr/bin/bop notify-listen
+Restart=on-failure
+
+[Install]
+WantedBy=graphical-session.target
+",
+        crate::managed::header()
+    )
+}
+
+/// Install and globally enable the listener unit.
+pub fn install_user_unit(ownership: &Ownership) -> Result<()> {
+    ownership.write(Path::new(USER_UNIT_PATH), &render_user_unit())?;
+    let status = crate::cmd::Cmd::new("systemctl")
+        .args(["--global", "enable", USER_UNIT_NAME])
+        .status()?;
+    if !status.success() {
+        return Err(Error::Other(format!(
+            "systemctl --global enable {} failed",
+            USER_UNIT_NAME
+        )));
+    }
+    Ok(())
+}
+
+/// Disable and remove the listener unit. Returns whether it was there.
+pub fn remove_user_unit(ownership: &Ownership) -> Result<bool> {
+    let path = Path::new(USER_UNIT_PATH);
+    if !path.exists() {
+        return Ok(false);
+    }
+    ownership.check(path)?;
+    let _ = crate::cmd::Cmd::new("systemctl")
+        .args(["--global", "disable", USER_UNIT_NAME])
+        .status();
+    ownership.remove(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
 
-        if session_type != "wayland" && session_type != "x11" {
-            continue;
+    fn event(kind: EventKind, time: u64) -> NotifyEvent {
+        NotifyEvent {
+            kind,
+            title: "bop".to_string(),
+            body: "Power optimizations applied (on battery)".to_string(),
+            time,
+            quiet: false,
         }
+    }
 
-        // Get the session's runtime directory for D-Bus
-        let runtime_dir = format!("/run/user/{}", uid);
-        let dbus_addr = format!("unix:path={}/bus", runtime_dir);
+    #[test]
+    fn test_event_serializes_as_one_json_line() {
+        let line = event(EventKind::Applied, 1_760_000_000).to_line();
+        assert_eq!(
+            line,
+            "{\"kind\":\"applied\",\"title\":\"bop\",\
+             \"body\":\"Power optimizations applied (on battery)\",\"time\":1760000000}\n"
+        );
+        let (events, used) = parse_events(line.as_bytes());
+        assert_eq!(events, [event(EventKind::Applied, 1_760_000_000)]);
+        assert_eq!(used, line.len());
+    }
 
-        // Run notify-send as the session user
-        let _ = std::process::Command::new("runuser")
-            .args(["-u", user, "--", "notify-send", title, body])
-            .env("DBUS_SESSION_BUS_ADDRESS", &dbus_addr)
-            .status();
+    #[test]
+    fn test_quiet_flag_round_trips_and_defaults_off() {
+        let quiet = event(EventKind::Reverted, 5).quiet();
+        let line = quiet.to_line();
+        assert!(line.contains("\"quiet\":true"));
+        assert_eq!(parse_events(line.as_bytes()).0, [quiet]);
+        // Lines from older releases have no flag and are shown
+        let (events, _) =
+            parse_events(b"{\"kind\":\"applied\",\"title\":\"bop\",\"body\":\"b\",\"time\":1}\n");
+        assert!(!events[0].quiet);
+    }
+
+    #[test]
+    fn test_parse_events_leaves_partial_line_and_skips_garbage() {
+        let first = event(EventKind::Applied, 10).to_line();
+        let second = event(EventKind::Reverted, 20).to_line();
+        let chunk = format!("{}not json\n{}{{\"kind\":\"app", first, second);
+
+        let (events, used) = parse_events(chunk.as_bytes());
+        assert_eq!(
+            events,
+            [
+                event(EventKind::Applied, 10),
+                event(EventKind::Reverted, 20)
+            ]
+        );
+        assert_eq!(&chunk[used..], "{\"kind\":\"app");
+        assert_eq!(parse_events(b"{\"kind\""), (Vec::new(), 0));
+    }
 
-        return Ok(()); // Only notify the first graphical session
+    #[test]
+    fn test_only_critical_events_are_urgent() {
+        assert_eq!(EventKind::Critical.urgency(), "critical");
+        assert_eq!(EventKind::Applied.urgency(), "normal");
+        let line = event(EventKind::Critical, 1).to_line();
+        assert!(line.contains("\"kind\":\"critical\""));
     }
 
-    Ok(()) // No graphical session found, fail silently
+    #[test]
+    fn test_debouncer_collapses_bursts_per_kind() {
+        let mut debouncer = Debouncer::default();
+        assert!(debouncer.accept(&event(EventKind::Applied, 100)));
+        assert!(!debouncer.accept(&event(EventKind::Applied, 102)));
+        // Other kinds aren't held back
+        assert!(debouncer.accept(&event(EventKind::Reverted, 103)));
+        assert!(debouncer.accept(&event(EventKind::Applied, 105)));
+        // A clock that went backwards doesn't swallow events
+        assert!(debouncer.accept(&event(EventKind::Applied, 50)));
+    }
+
+    #[test]
+    fn test_tail_reads_only_new_events_and_follows_restarts() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("run/bop/events");
+        append_event(&path, &event(EventKind::Applied, 1)).unwrap();
+
+        let mut tail = EventTail::new(&path);
+        assert!(tail.read_new().is_empty());
+
+        append_event(&path, &event(EventKind::Reverted, 2)).unwrap();
+        assert_eq!(tail.read_new(), [event(EventKind::Reverted, 2)]);
+        assert!(tail.read_new().is_empty());
+
+        // The file started over, e.g. after hitting the size cap
+        std::fs::write(&path, event(EventKind::Applied, 3).to_line()).unwrap();
+        assert_eq!(tail.read_new(), [event(EventKind::Applied, 3)]);
+    }
+
+    #[test]
+    fn test_user_unit_carries_header_and_runs_listener() {
+        let unit = render_user_unit();
+        assert!(crate::managed::has_header(&unit));
+        assert!(unit.contains("ExecStart=/usr/bin/bop notify-listen"));
+        assert!(unit.contains("WantedBy=graphical-session.target"));
+    }
 }
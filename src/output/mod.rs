@@ -1,6 +1,14 @@
-use crate::audit::{Finding, Severity};
+use crate::apply::wifi::{NM_DROP_IN_PATH, WifiPowerSave};
+use crate::apply::{ApplyPlan, ApplyState, MODPROBE_DIR, SLOW_PHASE_MS};
+use crate::audit::{self, Finding, FindingsDiff, PowerContext, Remedy, Severity};
 use crate::detect::HardwareInfo;
-use crate::status::StatusReport;
+use crate::profile::HardwareProfile;
+use crate::progress::{Progress, ProgressSink};
+use crate::report::JSON_SCHEMA_VERSION;
+use crate::revert::rescue::{BackupDiff, DiffLine};
+use crate::status::drift::{DriftCause, FdScan};
+use crate::status::{BootStatus, StatusReport};
+use crate::wake::WakeController;
 use colored::Colorize;
 
 const LABEL_W: usize = 18;
@@ -41,7 +49,13 @@ pub fn print_hardware_summary(hw: &HardwareInfo) {
         ),
         (
             "EPP",
-            hw.cpu.epp.as_deref().unwrap_or("Unknown").to_string(),
+            hw.cpu
+                .epp_summary()
+                .unwrap_or_else(|| "Unknown".to_string()),
+        ),
+        (
+            "Boost",
+            hw.cpu.boost_summary().unwrap_or_else(|| "N/A".to_string()),
         ),
         (
             "GPU Driver",
@@ -50,10 +64,8 @@ pub fn print_hardware_summary(hw: &HardwareInfo) {
         (
             "Platform Profile",
             hw.platform
-                .platform_profile
-                .as_deref()
-                .unwrap_or("N/A")
-                .to_string(),
+                .platform_profile_summary()
+                .unwrap_or_else(|| "N/A".to_string()),
         ),
         (
             "ASPM Policy",
@@ -69,6 +81,18 @@ pub fn print_hardware_summary(hw: &HardwareInfo) {
         ),
     ];
 
+    if let Some(panel) = &hw.display.panel {
+        let value = match &panel.model {
+            Some(model) => format!("{} {} ({})", panel.manufacturer, model, panel.technology),
+            None => format!("{} ({})", panel.manufacturer, panel.technology),
+        };
+        rows.push(("Panel", value));
+    }
+
+    if let Some(supplies) = hw.ac.supplies_summary() {
+        rows.push(("AC", supplies));
+    }
+
     if hw.battery.present {
         if let (Some(cap), Some(health)) =
             (hw.battery.usable_capacity_wh(), hw.battery.health_percent)
@@ -80,6 +104,25 @@ pub fn print_hardware_summary(hw: &HardwareInfo) {
         }
     }
 
+    if let Some(gpu) = hw.pci.discrete_gpu()
+        && let Some(current) = gpu.link.current_summary()
+    {
+        let value = match gpu.link.max_summary() {
+            Some(max) if max != current => format!("{} (max {})", current, max),
+            _ => current,
+        };
+        rows.push(("dGPU Link", value));
+    }
+
+    let port_rows: Vec<(String, String)> = hw
+        .typec
+        .ports
+        .iter()
+        .filter(|p| p.partner_draws_bus_power())
+        .map(|p| (format!("USB-C {}", p.name), "powering card".to_string()))
+        .collect();
+    rows.extend(port_rows.iter().map(|(l, v)| (l.as_str(), v.clone())));
+
     // Box width from content
     let eff_label_w = rows
         .iter()
@@ -106,23 +149,108 @@ pub fn print_hardware_summary(hw: &HardwareInfo) {
     println!("╰{}╯", "─".repeat(inner_w + 2));
 }
 
-pub fn print_audit_findings(findings: &[Finding], score: u32) {
+/// Findings relevant in `view`, then the ones that aren't (e.g. battery-only
+/// findings while on AC) in a dimmed section, then the battery and AC scores.
+/// With `explain`, each finding also lists the files it was based on.
+pub fn print_audit_findings(findings: &[Finding], view: PowerContext, explain: bool) {
     if findings.is_empty() {
         println!("{}", "  No issues found. System is well optimized.".green());
         return;
     }
 
     let mut sorted: Vec<&Finding> = findings.iter().collect();
-    sorted.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    sorted.sort_by(|a, b| audit::canonical_order(a, b));
+    let (relevant, other): (Vec<&Finding>, Vec<&Finding>) = sorted
+        .into_iter()
+        .partition(|f| f.applies_on.relevant_in(view));
 
-    let count = findings.len();
-    let title = format!("Findings ({})", count);
     let divider_w: usize = 64;
-    let fill = divider_w.saturating_sub(4 + title.len());
-    println!("── {} {}", title.bold(), "─".repeat(fill));
+    let section = |title: String| {
+        let fill = divider_w.saturating_sub(4 + title.len());
+        println!("── {} {}", title.bold(), "─".repeat(fill));
+    };
+
+    section(format!("Findings ({})", relevant.len()));
+    if relevant.is_empty() {
+        println!("  {}", "No issues found here.".green());
+    }
+    print_finding_list(&relevant, false, explain);
+
+    if !other.is_empty() {
+        println!();
+        let on = match view {
+            PowerContext::Ac => "AC",
+            _ => "battery",
+        };
+        section(format!("Not relevant on {} ({})", on, other.len()));
+        print_finding_list(&other, true, explain);
+    }
+
+    println!("{}", "─".repeat(divider_w));
+
+    for (label, context) in [
+        ("Battery score", PowerContext::Battery),
+        ("AC score", PowerContext::Ac),
+    ] {
+        let score = audit::context_score(findings, context);
+        let score_str = format!("{}: {}/100", label, score);
+        if score >= 80 {
+            println!("  {}", score_str.green().bold());
+        } else if score >= 50 {
+            println!("  {}", score_str.yellow().bold());
+        } else {
+            println!("  {}", score_str.red().bold());
+        }
+    }
+}
 
+/// `bop audit --oneline`: the score and finding counts for `view` in one
+/// line, e.g. `bop: score 82/100, 3 findings (1 high) — run `bop audit`
+/// for details`. `findings` is None when no profile matched.
+pub fn audit_oneline(findings: Option<&[Finding]>, view: PowerContext, color: bool) -> String {
+    let Some(findings) = findings else {
+        return "bop: no hardware profile matched — run `bop audit` for details".to_string();
+    };
+    let relevant: Vec<&Finding> = findings
+        .iter()
+        .filter(|f| f.applies_on.relevant_in(view))
+        .collect();
+    let score = audit::context_score(findings, view);
+    let mut score_str = format!("score {}/100", score);
+    if color {
+        score_str = if score >= 80 {
+            score_str.green().to_string()
+        } else if score >= 50 {
+            score_str.yellow().to_string()
+        } else {
+            score_str.red().to_string()
+        };
+    }
+    if relevant.is_empty() {
+        return format!("bop: {}, no findings", score_str);
+    }
+    let high = relevant
+        .iter()
+        .filter(|f| f.severity == Severity::High)
+        .count();
+    let plural = if relevant.len() == 1 { "" } else { "s" };
+    let high_str = if high > 0 {
+        format!(" ({} high)", high)
+    } else {
+        String::new()
+    };
+    format!(
+        "bop: {}, {} finding{}{} — run `bop audit` for details",
+        score_str,
+        relevant.len(),
+        plural,
+        high_str
+    )
+}
+
+fn print_finding_list(findings: &[&Finding], dim: bool, explain: bool) {
     let mut prev_severity: Option<Severity> = None;
-    for finding in sorted {
+    for finding in findings {
         if prev_severity.is_some() && prev_severity != Some(finding.severity) {
             println!();
         }
@@ -134,8 +262,11 @@ pub fn print_audit_findings(findings: &[Finding], score: u32) {
             Severity::Low => " LOW".blue().bold(),
             Severity::Info => "INFO".dimmed().bold(),
         };
-
-        println!("  {} {}", sev, finding.description);
+        if dim {
+            println!("  {} {}", sev.dimmed(), finding.description.dimmed());
+        } else {
+            println!("  {} {}", sev, finding.description);
+        }
 
         let mut detail_parts = Vec::new();
         if !finding.current_value.is_empty() && !finding.recommended_value.is_empty() {
@@ -151,58 +282,178 @@ pub fn print_audit_findings(findings: &[Finding], score: u32) {
         if !finding.impact.is_empty() {
             detail_parts.push(finding.impact.clone());
         }
+        if finding.remedy == Remedy::ManualOnly {
+            detail_parts.push("manual fix only".to_string());
+        }
+        if finding.pinned {
+            detail_parts.push("pinned by user".to_string());
+        }
         if !detail_parts.is_empty() {
             println!("       {}", detail_parts.join("  ·  ").dimmed());
         }
+        if explain {
+            for (path, raw) in &finding.evidence {
+                let raw = raw.trim_end().replace('\n', " ⏎ ");
+                println!("       {}", format!("{}: {}", path, raw).dimmed());
+            }
+        }
     }
+}
 
-    println!("{}", "─".repeat(divider_w));
+pub fn print_audit_json(
+    hw: &HardwareInfo,
+    findings: &[Finding],
+    score: u32,
+    profile: Option<&dyn HardwareProfile>,
+    verbose: bool,
+) {
+    let output = audit_json(hw, findings, score, profile, verbose);
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
 
-    let score_str = format!("Score: {}/100", score);
-    if score >= 80 {
-        println!("  {}", score_str.green().bold());
-    } else if score >= 50 {
-        println!("  {}", score_str.yellow().bold());
-    } else {
-        println!("  {}", score_str.red().bold());
-    }
+/// What a profile does differently, as `profile_notes` in the audit JSON.
+fn profile_notes_json(profile: &dyn HardwareProfile) -> serde_json::Value {
+    serde_json::json!({
+        "description": profile.description(),
+        "special_behaviors": profile.special_behaviors(),
+    })
 }
 
-pub fn print_audit_json(hw: &HardwareInfo, findings: &[Finding], score: u32, profile_name: &str) {
+/// `bop profile show --json`: the audit JSON's profile fields alone.
+pub fn print_profile_json(profile: Option<&dyn HardwareProfile>) {
     let output = serde_json::json!({
-        "profile": profile_name,
+        "schema_version": JSON_SCHEMA_VERSION,
+        "profile": profile.map_or("Unknown (generic)", |p| p.name()),
+        "profile_notes": profile.map(profile_notes_json),
+    });
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+/// `bop audit --json` document. Findings are in canonical order. `verbose`
+/// (`--verbose-json`) adds each finding's evidence: the files it was based on
+/// and their raw contents as detection read them.
+pub fn audit_json(
+    hw: &HardwareInfo,
+    findings: &[Finding],
+    score: u32,
+    profile: Option<&dyn HardwareProfile>,
+    verbose: bool,
+) -> serde_json::Value {
+    let mut sorted: Vec<&Finding> = findings.iter().collect();
+    sorted.sort_by(|a, b| audit::canonical_order(a, b));
+
+    serde_json::json!({
+        "schema_version": JSON_SCHEMA_VERSION,
+        "profile": profile.map_or("Unknown (generic)", |p| p.name()),
+        "profile_notes": profile.map(profile_notes_json),
         "score": score,
+        "battery_score": audit::context_score(findings, PowerContext::Battery),
+        "ac_score": audit::context_score(findings, PowerContext::Ac),
         "hardware": {
             "board_vendor": hw.dmi.board_vendor,
             "board_name": hw.dmi.board_name,
             "cpu": hw.cpu.model_name,
             "gpu_driver": hw.gpu.driver,
+            "panel": hw.display.panel.as_ref().map(|p| serde_json::json!({
+                "manufacturer": p.manufacturer,
+                "product_code": p.product_code,
+                "model": p.model,
+                "technology": p.technology.to_string(),
+            })),
             "battery_health": hw.battery.health_percent,
             "platform_profile": hw.platform.platform_profile,
+            "platform_profile_owner": hw.platform.platform_profile_target().owner,
         },
-        "findings": findings.iter().map(|f| serde_json::json!({
-            "severity": format!("{:?}", f.severity),
-            "category": f.category,
-            "description": f.description,
-            "current": f.current_value,
-            "recommended": f.recommended_value,
-            "impact": f.impact,
-            "path": f.path,
-            "weight": f.weight,
-        })).collect::<Vec<_>>(),
-    });
+        "findings": sorted
+            .iter()
+            .map(|f| finding_json(f, verbose))
+            .collect::<Vec<_>>(),
+    })
+}
 
-    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+fn finding_json(f: &Finding, verbose: bool) -> serde_json::Value {
+    let mut finding = serde_json::json!({
+        "id": f.id(),
+        "severity": f.severity,
+        "severity_rank": f.severity.rank(),
+        "category": f.category,
+        "description": f.description,
+        "current": f.current_value,
+        "recommended": f.recommended_value,
+        "impact": f.impact,
+        "path": f.path,
+        "manual_only": f.remedy == Remedy::ManualOnly,
+        "applies_on": f.applies_on,
+        "pinned": f.pinned,
+        "weight": f.weight,
+    });
+    if verbose {
+        finding["evidence"] = f
+            .evidence
+            .iter()
+            .map(|(path, raw)| serde_json::json!({ "path": path, "raw": raw }))
+            .collect();
+    }
+    finding
 }
 
 pub fn print_status(report: &StatusReport) {
-    println!(
-        "{} (applied {})",
-        "bop status".bold(),
-        report.timestamp.dimmed()
-    );
+    let mut applied = match report.preset {
+        Some(preset) => format!("applied {}, preset {}", report.timestamp, preset),
+        None => format!("applied {}", report.timestamp),
+    };
+    if let Some(ms) = report.apply_duration_ms {
+        applied.push_str(&format!(", took {}", format_ms(ms)));
+    }
+    println!("{} ({})", "bop status".bold(), applied.dimmed());
+    if report.check == crate::status::cache::CheckMode::Incremental {
+        println!(
+            "{}",
+            format!("Services as of the full check at {}", report.full_check_at).dimmed()
+        );
+    }
     println!();
 
+    if let Some(rollback) = &report.rollback {
+        if rollback.lost_to_reboot {
+            println!(
+                "  {} the automatic rollback was cancelled by a reboot; undo the rest with {}",
+                "!".yellow(),
+                "sudo bop revert".dimmed()
+            );
+        } else {
+            println!(
+                "  {} automatic rollback {}; keep the changes with {}",
+                "!".yellow(),
+                crate::apply::rollback::describe_remaining(std::time::Duration::from_secs(
+                    rollback.remaining_secs
+                )),
+                "sudo bop apply --confirm-keep".dimmed()
+            );
+        }
+        println!();
+    }
+
+    // Everything would read as drifted; say why instead
+    if report.boot == Some(BootStatus::NotApplied) {
+        println!(
+            "  {}",
+            "Optimizations have not been applied this boot (last applied during a previous boot)"
+                .yellow()
+                .bold()
+        );
+        if report.systemd_unit.as_ref().is_some_and(|u| u.exists) {
+            println!(
+                "  The persistence unit did not run. Check: {}",
+                format!("systemctl status {}", crate::apply::systemd::SERVICE_NAME).dimmed()
+            );
+            println!("  Or apply them now: {}", "sudo bop apply".dimmed());
+        } else {
+            println!("  Apply them again: {}", "sudo bop apply".dimmed());
+        }
+        return;
+    }
+
     // Sysfs
     if !report.sysfs.is_empty() {
         let active = report.sysfs.iter().filter(|s| s.active).count();
@@ -214,19 +465,40 @@ pub fn print_status(report: &StatusReport) {
             total
         );
         for s in &report.sysfs {
+            let pinned = if s.pinned { " (pinned)" } else { "" };
             if s.active {
-                println!("     {} {}  {}", "✓".green(), s.path.dimmed(), s.expected);
+                println!(
+                    "     {} {}  {}{}",
+                    "✓".green(),
+                    s.path.dimmed(),
+                    s.expected,
+                    pinned.dimmed()
+                );
             } else if let Some(actual) = &s.actual {
-                println!("     {} {}", "✗".red(), s.path);
+                println!("     {} {}{}", "✗".red(), s.path, pinned.dimmed());
                 println!(
                     "       expected: {}  actual: {}",
                     s.expected.green(),
                     actual.red()
                 );
+                if let Some(cause) = report
+                    .drift
+                    .as_ref()
+                    .and_then(|d| d.causes.iter().find(|c| c.path == s.path))
+                {
+                    print_drift_cause(cause);
+                }
             } else {
                 println!("     {} {}  (path not found)", "?".yellow(), s.path);
             }
         }
+        if let Some(note) = report.drift.as_ref().and_then(|d| match d.fd_scan {
+            FdScan::Complete => None,
+            FdScan::Truncated => Some("open-file scan stopped early; holders may be missing"),
+            FdScan::SkippedNotRoot => Some("open files not checked: run as root to include them"),
+        }) {
+            println!("     {}", note.dimmed());
+        }
         println!();
     }
 
@@ -247,6 +519,23 @@ pub fn print_status(report: &StatusReport) {
                 println!("     {} {} re-enabled (drifted)", "✗".red(), w.device);
             }
         }
+        if let Some(late) = &report.late_sleep_toggle {
+            let lag = match (late.done_us, late.suspend_entry_us) {
+                (Some(done), Some(entry)) if done > entry => {
+                    format!(" ({} ms after suspend entry)", (done - entry) / 1000)
+                }
+                _ => String::new(),
+            };
+            println!(
+                "     {} toggle applied too late before the last suspend{}; the firmware may have kept its wake configuration",
+                "!".yellow(),
+                lag
+            );
+            println!(
+                "       check other sleep hooks and InhibitDelayMaxSec in {}",
+                "logind.conf".dimmed()
+            );
+        }
         println!();
     }
 
@@ -288,6 +577,25 @@ pub fn print_status(report: &StatusReport) {
         } else {
             println!("     {} {} missing", "✗".red(), unit.path);
         }
+        if let Some(sr) = &report.soft_reboot {
+            if sr.reapplied {
+                println!(
+                    "     {} re-applied after soft reboot ({} this boot)",
+                    "✓".green(),
+                    sr.count
+                );
+            } else {
+                println!(
+                    "     {} not re-run since last soft reboot ({} this boot)",
+                    "✗".red(),
+                    sr.count
+                );
+                println!(
+                    "       run: {}",
+                    "systemctl restart bop-powersave.service".dimmed()
+                );
+            }
+        }
         println!();
     }
 
@@ -323,6 +631,599 @@ pub fn print_status(report: &StatusReport) {
     }
 }
 
+fn print_drift_cause(cause: &DriftCause) {
+    let likely = cause
+        .likely_cause
+        .as_deref()
+        .unwrap_or("unknown: no process, daemon or default points at it");
+    println!("       likely cause: {}", likely.yellow());
+    for open in &cause.open_by {
+        println!(
+            "       {}",
+            format!("open by {} (pid {})", open.process, open.pid).dimmed()
+        );
+    }
+    for sig in &cause.signatures {
+        println!(
+            "       {}",
+            format!("{} {}: {}", sig.unit, sig.source, sig.value).dimmed()
+        );
+    }
+    if !cause.running_daemons.is_empty() {
+        println!(
+            "       {}",
+            format!("running: {}", cause.running_daemons.join(", ")).dimmed()
+        );
+    }
+}
+
 pub fn print_status_json(report: &StatusReport) {
-    println!("{}", serde_json::to_string_pretty(report).unwrap());
+    let mut value = serde_json::to_value(report).unwrap();
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("schema_version".to_string(), JSON_SCHEMA_VERSION.into());
+    }
+    println!("{}", serde_json::to_string_pretty(&value).unwrap());
+}
+
+pub fn print_plan(plan: &ApplyPlan) {
+    println!("{}", "Apply Plan".bold().underline());
+    println!();
+
+    if !plan.sysfs_writes.is_empty() {
+        println!("  {} Runtime sysfs changes:", ">>".cyan());
+        for write in &plan.sysfs_writes {
+            println!(
+                "     {} {}",
+                write.description.dimmed(),
+                write.path.dimmed()
+            );
+        }
+        println!();
+    }
+
+    if !plan.kernel_params.is_empty() || !plan.kernel_params_remove.is_empty() {
+        println!("  {} Kernel parameters (requires reboot):", ">>".cyan());
+        for param in &plan.kernel_params {
+            println!("     {}", param);
+        }
+        for param in &plan.kernel_params_remove {
+            println!("     {} {}", "remove".yellow(), param);
+        }
+        println!();
+    }
+
+    if !plan.services_to_disable.is_empty() {
+        println!("  {} Services to disable:", ">>".cyan());
+        for svc in &plan.services_to_disable {
+            println!("     {}", svc);
+        }
+        println!();
+    }
+
+    if !plan.services_blocked.is_empty() {
+        for blocked in &plan.services_blocked {
+            println!("  {} {}", "WARNING:".yellow().bold(), blocked.warning());
+        }
+        println!(
+            "    Pass {} to disable them anyway.",
+            "--force-disable".cyan()
+        );
+        println!();
+    }
+
+    if !plan.acpi_wakeup_disable.is_empty() {
+        println!(
+            "  {} ACPI wakeup sources to disable (volatile, resets on reboot):",
+            ">>".cyan()
+        );
+        for dev in &plan.acpi_wakeup_disable {
+            println!("     {}", dev);
+        }
+        println!();
+    }
+
+    if !plan.modprobe_configs.is_empty() {
+        println!(
+            "  {} Module options (apply on next module load):",
+            ">>".cyan()
+        );
+        for config in &plan.modprobe_configs {
+            let options = config
+                .content
+                .lines()
+                .find(|l| l.starts_with("options "))
+                .unwrap_or_default();
+            println!(
+                "     {}  {}",
+                options,
+                format!("{}/{}", MODPROBE_DIR, config.filename).dimmed()
+            );
+        }
+        println!();
+    }
+
+    if plan.wifi_power_save == Some(WifiPowerSave::NetworkManager) {
+        println!(
+            "  {} WiFi power save through NetworkManager (applies on next connect):",
+            ">>".cyan()
+        );
+        println!("     wifi.powersave = 3  {}", NM_DROP_IN_PATH.dimmed());
+        println!();
+    }
+
+    if plan.systemd_service {
+        println!(
+            "  {} Will generate bop-powersave.service for boot persistence",
+            ">>".cyan()
+        );
+        println!();
+    }
+}
+
+/// Print the reasoning behind per-device decisions in the plan.
+pub fn print_explanations(plan: &ApplyPlan) {
+    if plan.explanations.is_empty() {
+        return;
+    }
+
+    println!("{}", "Decisions".bold().underline());
+    println!();
+    for exp in &plan.explanations {
+        let mark = if exp.included {
+            "+".green()
+        } else {
+            "-".yellow()
+        };
+        println!("  {} {}  {}", mark, exp.subject, exp.reason);
+        if !exp.factors.is_empty() {
+            println!("       {}", exp.factors.join("  ·  ").dimmed());
+        }
+    }
+    println!();
+}
+
+/// Renders core [`Progress`] events to the terminal.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Terminal;
+
+impl ProgressSink for Terminal {
+    fn emit(&mut self, event: Progress) {
+        match event {
+            Progress::Heading(title) => {
+                println!("{}", title.bold().underline());
+                println!();
+            }
+            Progress::Section(title) => println!("  {} {}", ">>".cyan(), title),
+            Progress::Step(text) => println!("     {}", text),
+            Progress::Detail(text) => println!("     {}", text.dimmed()),
+            Progress::EndSection => println!(),
+            Progress::Info(text) => println!("{}", text),
+            Progress::Warning(text) => eprintln!("{} {}", "!".yellow(), text),
+            Progress::Failure(text) => eprintln!("     {} {}", "!".red(), text),
+            Progress::Success(text) => println!("{}", text.green().bold()),
+        }
+    }
+}
+
+/// Print USB wake controllers for `bop wake list`.
+pub fn print_wake_controllers(controllers: &[WakeController]) {
+    println!("{}", " Wake Sources".bold());
+    println!();
+
+    for ctrl in controllers {
+        let wake_badge = if ctrl.enabled {
+            "enabled".green().to_string()
+        } else {
+            "disabled".dimmed().to_string()
+        };
+
+        let addr = ctrl.pci_address.as_deref().unwrap_or("N/A");
+
+        let last_wake = if ctrl.wake_count > 0 {
+            format!(
+                "{:<20}",
+                format!("{} ({} wakes)", ctrl.last_wake_label(), ctrl.wake_count)
+            )
+        } else {
+            format!("{:<20}", ctrl.last_wake_label())
+        };
+
+        print!(
+            "  {} {}  {}  {} {}",
+            format!("{:<5}", ctrl.name).bold(),
+            wake_badge,
+            format!("{:<12}", addr).dimmed(),
+            "last wake:".dimmed(),
+            last_wake
+        );
+
+        if ctrl.has_devices {
+            print!("  {}", ctrl.device_descriptions.join(", "));
+        }
+
+        println!();
+    }
+
+    println!();
+
+    // Warn about disabled controllers with devices
+    for ctrl in controllers {
+        if ctrl.ambiguous {
+            continue;
+        }
+        if !ctrl.enabled && ctrl.has_devices {
+            println!(
+                "  {} {} has connected devices but wake is disabled!",
+                "WARNING:".yellow().bold(),
+                ctrl.name
+            );
+            println!(
+                "    Run `bop wake enable {}` to allow these devices to wake the system.",
+                ctrl.name
+            );
+        }
+    }
+
+    let ambiguous: std::collections::BTreeSet<&str> = controllers
+        .iter()
+        .filter(|c| c.ambiguous)
+        .map(|c| c.name.as_str())
+        .collect();
+    for name in ambiguous {
+        println!(
+            "  {} {} is listed more than once; writing the name to /proc/acpi/wakeup toggles only the first, so bop leaves them alone.",
+            "NOTICE:".cyan(),
+            name
+        );
+    }
+
+    // Note about expansion cards
+    let disabled_empty: Vec<_> = controllers
+        .iter()
+        .filter(|c| !c.enabled && !c.has_devices && crate::wake::is_usb_wakeup_source(&c.name))
+        .collect();
+    if !disabled_empty.is_empty() {
+        let names: Vec<_> = disabled_empty.iter().map(|c| c.name.as_str()).collect();
+        println!();
+        println!(
+            "  {} Wake disabled on {} expansion card USB controllers ({}).",
+            "NOTICE:".cyan(),
+            names.len(),
+            names.join(", ")
+        );
+        println!(
+            "    USB expansion cards plugged into these ports will NOT wake the system from sleep."
+        );
+        println!(
+            "    Run `bop wake enable <controller>` to re-enable, or `bop wake scan` to auto-detect."
+        );
+    }
+}
+
+/// `bop doctor` report: each conflicting line with where it lives.
+pub fn print_conflicts(conflicts: &[crate::conflicts::Conflict]) {
+    if conflicts.is_empty() {
+        println!(
+            "{}",
+            "  No udev rules, units or rc.local lines fighting bop's settings.".green()
+        );
+        return;
+    }
+    println!(
+        "{}",
+        format!("Conflicting entries ({})", conflicts.len()).bold()
+    );
+    for c in conflicts {
+        println!(
+            "  {} {}:{}  sets {}",
+            "!".yellow(),
+            c.file.display(),
+            c.line,
+            c.target
+        );
+        println!("       {}", c.text.dimmed());
+    }
+    println!();
+}
+
+/// `bop doctor --probe` report: failures first, then the summary.
+pub fn print_probe(report: &crate::apply::probe::ProbeReport) {
+    use crate::apply::probe::ProbeStatus;
+
+    println!("{}", "Write probe".bold());
+    if report.results.is_empty() {
+        println!("{}", "  Nothing planned to probe.".green());
+        println!();
+        return;
+    }
+    for r in &report.results {
+        match &r.status {
+            ProbeStatus::Writable => {}
+            ProbeStatus::Denied => {
+                println!("  {} {}  permission denied", "✗".red(), r.path);
+                println!("       {}", r.description.dimmed());
+            }
+            ProbeStatus::Error(e) => {
+                println!("  {} {}  {}", "✗".red(), r.path, e);
+                println!("       {}", r.description.dimmed());
+            }
+            ProbeStatus::Skipped(why) => {
+                println!("  {} {}  not probed: {}", "-".dimmed(), r.path, why);
+                println!("       {}", r.description.dimmed());
+            }
+        }
+    }
+    let summary = report.summary();
+    if report.failed() == 0 {
+        println!("  {}", summary.green());
+    } else {
+        println!("  {}", summary.yellow());
+    }
+    println!();
+}
+
+/// Compact summary of what a re-audit changed, for `bop audit --watch-config`.
+pub fn print_findings_diff(diff: &FindingsDiff, score_before: u32, score_after: u32) {
+    let delta = score_after as i64 - score_before as i64;
+    let delta_str = match delta {
+        d if d > 0 => format!("+{}", d).green().to_string(),
+        d if d < 0 => d.to_string().red().to_string(),
+        _ => "±0".dimmed().to_string(),
+    };
+    println!(
+        "  {} {} → {} ({})",
+        "Score:".bold(),
+        score_before,
+        score_after,
+        delta_str
+    );
+
+    if diff.is_empty() {
+        println!("  {}", "No change in findings.".dimmed());
+        return;
+    }
+    for f in &diff.appeared {
+        println!("  {} {}", "+".red().bold(), f.description);
+    }
+    for f in &diff.resolved {
+        println!("  {} {}", "-".green().bold(), f.description);
+    }
+    for (before, after) in &diff.changed {
+        println!(
+            "  {} {}  {}",
+            "~".yellow().bold(),
+            after.description,
+            format!("({} → {})", before.current_value, after.current_value).dimmed()
+        );
+    }
+}
+
+/// The first audit after an upgrade: how its findings differ from the
+/// last audit's.
+/// `bop audit --verbose` and `bop profile show`: what the matched profile
+/// does differently from a generic laptop.
+pub fn print_profile_notes(profile: &dyn HardwareProfile) {
+    println!("  {}", "Profile notes:".bold());
+    println!("  {}", profile.description());
+    for behavior in profile.special_behaviors() {
+        println!("  {} {}", "-".dimmed(), behavior);
+    }
+}
+
+pub fn print_upgrade_notes(notes: &crate::audit::upgrade::UpgradeNotes) {
+    println!(
+        "  {} bop {} → {}",
+        "What changed for your hardware:".bold(),
+        notes.from,
+        notes.to
+    );
+    for f in &notes.new_checks {
+        println!(
+            "  {} {} {}",
+            "+".yellow().bold(),
+            f.description,
+            "(new)".dimmed()
+        );
+    }
+    for (before, after) in &notes.changed {
+        println!(
+            "  {} {}  {}",
+            "~".yellow().bold(),
+            after.description,
+            format!(
+                "(recommended {} → {})",
+                before.recommended_value, after.recommended_value
+            )
+            .dimmed()
+        );
+    }
+    for f in &notes.no_longer_reported {
+        println!(
+            "  {} {} {}",
+            "-".green().bold(),
+            f.description,
+            "(no longer reported)".dimmed()
+        );
+    }
+    println!(
+        "  Run {} to see what each finding is based on.",
+        "bop audit --explain".cyan()
+    );
+    println!();
+}
+
+/// `1.2s`, or `350ms` under a second.
+pub fn format_ms(ms: u64) -> String {
+    if ms < 1_000 {
+        format!("{}ms", ms)
+    } else {
+        format!("{:.1}s", ms as f64 / 1_000.0)
+    }
+}
+
+/// Per-phase timing of an apply, shown when a phase was slow.
+pub fn print_phase_timings(state: &ApplyState) {
+    let total = state.duration_ms.unwrap_or_default();
+    println!("  {} Took {}:", ">>".cyan(), format_ms(total));
+    for (phase, timing) in &state.timings {
+        let line = format!(
+            "{:<LABEL_W$} {:>7}",
+            phase.to_string(),
+            format_ms(timing.duration_ms)
+        );
+        if timing.duration_ms > SLOW_PHASE_MS {
+            println!("     {}", line.yellow());
+        } else {
+            println!("     {}", line.dimmed());
+        }
+    }
+}
+
+/// Boot entry backups for `bop rescue`: what restoring each one changes.
+pub fn print_backup_diffs(diffs: &[BackupDiff]) {
+    println!("{}", " Kernel parameter backups".bold());
+    println!();
+    for diff in diffs {
+        println!("  {} {}", ">>".cyan(), diff.path);
+        if diff.current.is_none() {
+            println!(
+                "     {}",
+                format!("missing at {}", diff.host_path.display()).yellow()
+            );
+        } else if diff.is_unchanged() {
+            println!("     {}", "already matches the backup".dimmed());
+            println!();
+            continue;
+        }
+        for line in &diff.lines {
+            match line {
+                DiffLine::Same(text) => println!("     {}", format!("  {}", text).dimmed()),
+                DiffLine::Removed(text) => println!("     {}", format!("- {}", text).red()),
+                DiffLine::Added(text) => println!("     {}", format!("+ {}", text).green()),
+            }
+        }
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysfs::SysfsRoot;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_audit_oneline() {
+        let findings = vec![
+            Finding::new(Severity::High, "CPU", "EPP").weight(8),
+            Finding::new(Severity::Medium, "PCI", "ASPM")
+                .applies_on(PowerContext::Battery)
+                .weight(4),
+            Finding::new(Severity::Low, "Audio", "power save")
+                .applies_on(PowerContext::Battery)
+                .weight(2),
+        ];
+        assert_eq!(
+            audit_oneline(Some(&findings), PowerContext::Battery, false),
+            "bop: score 53/100, 3 findings (1 high) — run `bop audit` for details"
+        );
+        // On AC the battery-only findings don't count
+        assert_eq!(
+            audit_oneline(Some(&findings), PowerContext::Ac, false),
+            "bop: score 20/100, 1 finding (1 high) — run `bop audit` for details"
+        );
+        assert_eq!(
+            audit_oneline(Some(&findings[1..]), PowerContext::Battery, false),
+            "bop: score 70/100, 2 findings — run `bop audit` for details"
+        );
+        assert_eq!(
+            audit_oneline(Some(&[]), PowerContext::Battery, false),
+            "bop: score 100/100, no findings"
+        );
+        assert!(audit_oneline(None, PowerContext::Battery, false).contains("no hardware profile"));
+        let line = audit_oneline(Some(&findings), PowerContext::Battery, false);
+        assert!(!line.contains('\n') && !line.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_audit_json_evidence_only_when_verbose() {
+        let tmp = TempDir::new().unwrap();
+        let hw = HardwareInfo::detect(&SysfsRoot::new(tmp.path()));
+        let findings = vec![
+            Finding::new(Severity::Low, "Audio", "power save")
+                .path("/sys/module/y")
+                .evidence("/sys/module/y", "0\n"),
+        ];
+
+        let json = audit_json(&hw, &findings, 90, None, false);
+        assert!(json["findings"][0].get("evidence").is_none());
+
+        let json = audit_json(&hw, &findings, 90, None, true);
+        let evidence = &json["findings"][0]["evidence"];
+        assert_eq!(evidence[0]["path"], "/sys/module/y");
+        assert_eq!(evidence[0]["raw"], "0\n");
+    }
+
+    #[test]
+    fn test_audit_json_carries_profile_notes() {
+        let tmp = TempDir::new().unwrap();
+        let hw = HardwareInfo::detect(&SysfsRoot::new(tmp.path()));
+        let profile = crate::profile::framework16_amd::Framework16Amd;
+
+        let json = audit_json(&hw, &[], 100, Some(&profile), false);
+        assert_eq!(json["profile"], profile.name());
+        assert_eq!(json["profile_notes"]["description"], profile.description());
+        assert_eq!(
+            json["profile_notes"]["special_behaviors"]
+                .as_array()
+                .unwrap()
+                .len(),
+            profile.special_behaviors().len()
+        );
+
+        let json = audit_json(&hw, &[], 100, None, false);
+        assert_eq!(json["profile"], "Unknown (generic)");
+        assert!(json["profile_notes"].is_null());
+    }
+
+    #[test]
+    fn test_audit_json_orders_findings_and_ranks_severity() {
+        let tmp = TempDir::new().unwrap();
+        let hw = HardwareInfo::detect(&SysfsRoot::new(tmp.path()));
+        let findings = vec![
+            Finding::new(Severity::Low, "Sysctl", "writeback")
+                .path("/proc/sys/vm/x")
+                .applies_on(PowerContext::Battery)
+                .weight(4),
+            Finding::new(Severity::High, "Kernel", "EC wakeup").path("/proc/cmdline"),
+            Finding::new(Severity::Low, "Audio", "power save").path("/sys/module/y"),
+        ];
+
+        let json = audit_json(&hw, &findings, 70, None, false);
+        let listed: Vec<(&str, u64, &str)> = json["findings"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| {
+                (
+                    f["severity"].as_str().unwrap(),
+                    f["severity_rank"].as_u64().unwrap(),
+                    f["id"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                ("high", 3, "kernel:/proc/cmdline"),
+                ("low", 1, "audio:/sys/module/y"),
+                ("low", 1, "sysctl:/proc/sys/vm/x"),
+            ]
+        );
+        assert_eq!(json["schema_version"], JSON_SCHEMA_VERSION);
+        assert_eq!(json["findings"][2]["applies_on"], "battery");
+        assert_eq!(json["findings"][0]["applies_on"], "both");
+        assert_eq!(json["battery_score"], 87);
+        assert_eq!(json["ac_score"], 100);
+    }
 }
@@ -0,0 +1,299 @@
+//! User pins: the `[pins]` config table. Each entry maps a sysfs/procfs
+//! path to the value the user keeps there, or to `"leave"`. bop never
+//! writes a pinned path, from `apply`, `auto` or the persistence unit, and
+//! `bop status` checks a pinned path against the user's value rather than
+//! bop's own.
+
+use crate::sysfs::SysfsRoot;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Pin value that only keeps bop away, without a value for status to check.
+pub const LEAVE: &str = "leave";
+
+/// `[pins]`: absolute path (`*` and `?` globs allowed within a path
+/// segment, e.g. `cpu*`) to a value or [`LEAVE`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Pins(BTreeMap<String, String>);
+
+/// The pin that governs one path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pin<'a> {
+    /// The `[pins]` key that matched
+    pub pattern: &'a str,
+    /// The pinned value; None for [`LEAVE`]
+    pub value: Option<&'a str>,
+}
+
+impl std::fmt::Display for Pin<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value {
+            Some(value) => write!(f, "pinned by user to {}", value),
+            None => write!(f, "pinned by user (leave)"),
+        }
+    }
+}
+
+impl Pins {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The `[pins]` keys, in sorted order.
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    pub fn insert(&mut self, pattern: impl Into<String>, value: impl Into<String>) {
+        self.0.insert(pattern.into(), value.into());
+    }
+
+    pub fn remove(&mut self, pattern: &str) {
+        self.0.remove(pattern);
+    }
+
+    /// The pin for `path`, if any matches. When several do, the most
+    /// specific wins: an exact path over any glob, then the longest
+    /// pattern, then the first in sorted order.
+    pub fn get(&self, path: &str) -> Option<Pin<'_>> {
+        self.0
+            .iter()
+            .filter(|(pattern, _)| path_matches(pattern, path))
+            .min_by_key(|(pattern, _)| (has_wildcard(pattern), std::cmp::Reverse(pattern.len())))
+            .map(|(pattern, value)| pin(pattern, value))
+    }
+
+    /// The pin covering an audit finding's path. Finding paths are loose
+    /// (relative to a device dir, or globbed themselves), so the finding
+    /// matches when its segments line up with the tail of a pattern's.
+    pub fn for_finding(&self, finding_path: &str) -> Option<Pin<'_>> {
+        let tail = segments(finding_path);
+        if tail.is_empty() {
+            return None;
+        }
+        self.0
+            .iter()
+            .filter(|(pattern, _)| {
+                let segs = segments(pattern);
+                segs.len() >= tail.len()
+                    && segs[segs.len() - tail.len()..]
+                        .iter()
+                        .zip(&tail)
+                        .all(|(p, f)| segment_matches(p, f) || segment_matches(f, p))
+            })
+            .min_by_key(|(pattern, _)| (has_wildcard(pattern), std::cmp::Reverse(pattern.len())))
+            .map(|(pattern, value)| pin(pattern, value))
+    }
+
+    /// Every existing path a value pin covers under `sysfs`, with the value
+    /// to expect there. Paths that a more specific pin governs are left to
+    /// that pin.
+    pub fn expand(&self, sysfs: &SysfsRoot) -> Vec<(String, String)> {
+        let mut found = Vec::new();
+        for (pattern, value) in &self.0 {
+            if value.trim() == LEAVE {
+                continue;
+            }
+            let mut paths = vec![String::new()];
+            for seg in segments(pattern) {
+                paths = paths
+                    .into_iter()
+                    .flat_map(|dir| {
+                        if !has_wildcard(seg) {
+                            return vec![format!("{}/{}", dir, seg)];
+                        }
+                        let mut names = sysfs
+                            .list_dir(dir.trim_start_matches('/'))
+                            .unwrap_or_default();
+                        names.sort();
+                        names
+                            .into_iter()
+                            .filter(|name| segment_matches(seg, name))
+                            .map(|name| format!("{}/{}", dir, name))
+                            .collect()
+                    })
+                    .collect();
+            }
+            for path in paths {
+                if sysfs.exists(path.trim_start_matches('/'))
+                    && self.get(&path).map(|p| p.pattern) == Some(pattern.as_str())
+                {
+                    found.push((path, value.trim().to_string()));
+                }
+            }
+        }
+        found
+    }
+}
+
+fn pin<'a>(pattern: &'a str, value: &'a str) -> Pin<'a> {
+    let value = value.trim();
+    Pin {
+        pattern,
+        value: (value != LEAVE).then_some(value),
+    }
+}
+
+fn segments(path: &str) -> Vec<&str> {
+    path.split('/').filter(|s| !s.is_empty()).collect()
+}
+
+fn has_wildcard(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Whether `path` matches `pattern` segment by segment.
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let pat = segments(pattern);
+    let segs = segments(path);
+    pat.len() == segs.len() && pat.iter().zip(&segs).all(|(p, s)| segment_matches(p, s))
+}
+
+/// `*` matches any run of characters and `?` any one, never crossing a `/`.
+fn segment_matches(pattern: &str, text: &str) -> bool {
+    fn matches(p: &[char], t: &[char]) -> bool {
+        match (p.split_first(), t.split_first()) {
+            (None, None) => true,
+            (Some(('*', rest)), _) => matches(rest, t) || (!t.is_empty() && matches(p, &t[1..])),
+            (Some(('?', rest)), Some((_, t_rest))) => matches(rest, t_rest),
+            (Some((c, rest)), Some((d, t_rest))) if c == d => matches(rest, t_rest),
+            _ => false,
+        }
+    }
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    matches(&p, &t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const EPP: &str = "/sys/devices/system/cpu/cpu*/cpufreq/energy_performance_preference";
+
+    fn pins(entries: &[(&str, &str)]) -> Pins {
+        let mut pins = Pins::default();
+        for (pattern, value) in entries {
+            pins.insert(*pattern, *value);
+        }
+        pins
+    }
+
+    #[test]
+    fn test_glob_stays_within_a_segment() {
+        let pins = pins(&[(EPP, "balance_performance")]);
+        let cpu3 = "/sys/devices/system/cpu/cpu3/cpufreq/energy_performance_preference";
+        assert_eq!(pins.get(cpu3).unwrap().value, Some("balance_performance"));
+        // `*` doesn't swallow a `/`, and the whole path has to match
+        assert!(
+            pins.get("/sys/devices/system/cpu/cpufreq/policy0/energy_performance_preference")
+                .is_none()
+        );
+        assert!(pins.get("/sys/devices/system/cpu/cpu3/cpufreq").is_none());
+
+        assert!(path_matches(
+            "/sys/bus/pci/devices/0000:0?:00.0/power/control",
+            "/sys/bus/pci/devices/0000:01:00.0/power/control"
+        ));
+        assert!(!path_matches(
+            "/sys/bus/pci/devices/0000:0?:00.0/power/control",
+            "/sys/bus/pci/devices/0000:10:00.0/power/control"
+        ));
+    }
+
+    #[test]
+    fn test_most_specific_pin_wins() {
+        let pins = pins(&[
+            (
+                "/sys/devices/system/cpu/*/cpufreq/energy_performance_preference",
+                "power",
+            ),
+            (EPP, "balance_performance"),
+            (
+                "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference",
+                "leave",
+            ),
+        ]);
+        let cpu0 = pins
+            .get("/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference")
+            .unwrap();
+        assert_eq!(cpu0.value, None);
+        assert_eq!(cpu0.to_string(), "pinned by user (leave)");
+        // Longer glob beats the shorter one
+        let cpu1 = pins
+            .get("/sys/devices/system/cpu/cpu1/cpufreq/energy_performance_preference")
+            .unwrap();
+        assert_eq!(cpu1.pattern, EPP);
+        assert_eq!(cpu1.to_string(), "pinned by user to balance_performance");
+    }
+
+    #[test]
+    fn test_finding_paths_match_by_tail() {
+        let pins = pins(&[(EPP, "balance_performance")]);
+        assert!(
+            pins.for_finding("cpu*/cpufreq/energy_performance_preference")
+                .is_some()
+        );
+        assert!(
+            pins.for_finding("/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference")
+                .is_some()
+        );
+        assert!(pins.for_finding("cpufreq/boost").is_none());
+        assert!(pins.for_finding("").is_none());
+    }
+
+    #[test]
+    fn test_expand_finds_existing_paths_for_value_pins() {
+        let tmp = TempDir::new().unwrap();
+        for cpu in ["cpu0", "cpu1"] {
+            let dir = tmp
+                .path()
+                .join(format!("sys/devices/system/cpu/{}/cpufreq", cpu));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("energy_performance_preference"), "balance_power\n").unwrap();
+        }
+        fs::create_dir_all(tmp.path().join("sys/devices/system/cpu/cpuidle")).unwrap();
+        let sysfs = SysfsRoot::new(tmp.path());
+
+        let pins = pins(&[
+            (EPP, "balance_performance"),
+            (
+                "/sys/devices/system/cpu/cpu1/cpufreq/energy_performance_preference",
+                "leave",
+            ),
+            ("/proc/sys/kernel/nmi_watchdog", "1"),
+        ]);
+        // cpu1 belongs to its leave pin; nmi_watchdog doesn't exist here
+        assert_eq!(
+            pins.expand(&sysfs),
+            vec![(
+                "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference".to_string(),
+                "balance_performance".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parses_from_toml() {
+        let config: crate::config::BopConfig = toml::from_str(
+            r#"
+            [pins]
+            "/sys/devices/system/cpu/cpu*/cpufreq/energy_performance_preference" = "balance_performance"
+            "/sys/module/snd_hda_intel/parameters/power_save" = "leave"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config
+                .pins
+                .get("/sys/module/snd_hda_intel/parameters/power_save")
+                .unwrap()
+                .value,
+            None
+        );
+        assert!(!config.pins.is_empty());
+    }
+}
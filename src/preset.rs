@@ -1,8 +1,8 @@
-use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
 #[serde(rename_all = "lowercase")]
 pub enum Preset {
     Off,
@@ -52,10 +52,26 @@ pub struct PresetKnobs {
     pub kernel_params: bool,
     pub acpi_wakeup_filter: bool,
     pub gpu_dpm: bool,
+    /// Pin an idle discrete GPU's PCIe link at 2.5 GT/s where the kernel
+    /// exposes a bandwidth control for its port.
+    pub pcie_link_downgrade: bool,
+    /// Power off empty USB ports that haven't had a device since boot.
+    pub usb_port_disable: bool,
+    /// Force `acpi.ec_no_wakeup=1` on (Some(true)) or off (Some(false)).
+    /// None leaves it to the hardware profile, since it breaks lid wake on
+    /// some laptops.
+    pub ec_no_wakeup: Option<bool>,
+    /// Long-term RAPL package power limit in watts. Only set from
+    /// `[aggressive] package_power_limit_w`, for supersaver.
+    pub package_power_limit_w: Option<u32>,
     /// Set by clamp_for_reduced() or resolve_knobs() when EPP was explicitly
     /// set (override or clamp). Prevents adaptive resolution from overriding
     /// the value, and allows writing EPP even when current is "power".
     pub epp_locked: bool,
+    /// Set by resolve_knobs() when the platform profile was explicitly
+    /// configured. Allows replacing a "custom" profile, which is otherwise
+    /// left to the vendor tuning that set it.
+    pub platform_profile_locked: bool,
 }
 
 impl PresetKnobs {
@@ -85,6 +101,15 @@ impl PresetKnobs {
             self.platform_profile = moderate.platform_profile;
         }
 
+        // dGPU link: never pin link speed in reduced mode
+        self.pcie_link_downgrade = moderate.pcie_link_downgrade;
+
+        // USB ports: never power them off in reduced mode
+        self.usb_port_disable = moderate.usb_port_disable;
+
+        // Package power: never cap it in reduced mode
+        self.package_power_limit_w = moderate.package_power_limit_w;
+
         // EPP: cap at balance_power (not power), and lock to prevent
         // adaptive resolution from escalating back to "power"
         if self.epp.as_deref() == Some("power") {
@@ -109,6 +134,9 @@ impl PresetKnobs {
             || self.kernel_params
             || self.acpi_wakeup_filter
             || self.gpu_dpm
+            || self.pcie_link_downgrade
+            || self.usb_port_disable
+            || self.package_power_limit_w.is_some()
     }
 }
 
@@ -128,7 +156,12 @@ impl Preset {
                 kernel_params: false,
                 acpi_wakeup_filter: false,
                 gpu_dpm: false,
+                pcie_link_downgrade: false,
+                usb_port_disable: false,
+                ec_no_wakeup: None,
+                package_power_limit_w: None,
                 epp_locked: false,
+                platform_profile_locked: false,
             },
             Preset::Default => PresetKnobs {
                 epp: None,
@@ -143,7 +176,12 @@ impl Preset {
                 kernel_params: true,
                 acpi_wakeup_filter: true,
                 gpu_dpm: false,
+                pcie_link_downgrade: false,
+                usb_port_disable: false,
+                ec_no_wakeup: None,
+                package_power_limit_w: None,
                 epp_locked: false,
+                platform_profile_locked: false,
             },
             Preset::Moderate => PresetKnobs {
                 epp: Some(Cow::Borrowed("balance_power")),
@@ -158,7 +196,12 @@ impl Preset {
                 kernel_params: true,
                 acpi_wakeup_filter: true,
                 gpu_dpm: true,
+                pcie_link_downgrade: false,
+                usb_port_disable: false,
+                ec_no_wakeup: None,
+                package_power_limit_w: None,
                 epp_locked: false,
+                platform_profile_locked: false,
             },
             Preset::Saver => PresetKnobs {
                 epp: Some(Cow::Borrowed("balance_power")),
@@ -173,7 +216,12 @@ impl Preset {
                 kernel_params: true,
                 acpi_wakeup_filter: true,
                 gpu_dpm: true,
+                pcie_link_downgrade: false,
+                usb_port_disable: false,
+                ec_no_wakeup: None,
+                package_power_limit_w: None,
                 epp_locked: false,
+                platform_profile_locked: false,
             },
             Preset::Supersaver => PresetKnobs {
                 epp: Some(Cow::Borrowed("power")),
@@ -188,7 +236,12 @@ impl Preset {
                 kernel_params: true,
                 acpi_wakeup_filter: true,
                 gpu_dpm: true,
+                pcie_link_downgrade: true,
+                usb_port_disable: true,
+                ec_no_wakeup: None,
+                package_power_limit_w: None,
                 epp_locked: false,
+                platform_profile_locked: false,
             },
         }
     }
@@ -255,6 +308,10 @@ mod tests {
         assert_eq!(k.turbo_boost, Some(false));
         assert_eq!(k.usb_autosuspend, UsbPolicy::All);
         assert_eq!(k.platform_profile, PlatformProfilePolicy::ForceLowPower);
+        assert!(k.pcie_link_downgrade);
+        assert!(!Preset::Saver.knobs().pcie_link_downgrade);
+        assert!(k.usb_port_disable);
+        assert!(!Preset::Saver.knobs().usb_port_disable);
     }
 
     #[test]
@@ -267,6 +324,8 @@ mod tests {
         assert_eq!(k.usb_autosuspend, UsbPolicy::SkipInputExpansion);
         assert_eq!(k.platform_profile, PlatformProfilePolicy::FixPerformance);
         assert_eq!(k.epp.as_deref(), Some("balance_power"));
+        assert!(!k.pcie_link_downgrade);
+        assert!(!k.usb_port_disable);
         // Non-aggressive knobs should be unchanged
         assert!(k.pci_runtime_pm);
         assert!(k.audio_power_save);
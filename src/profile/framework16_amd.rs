@@ -1,12 +1,48 @@
-use crate::audit::{self, Finding};
+use crate::audit::{self, AuditContext, Finding, PowerContext, Severity};
 use crate::detect::HardwareInfo;
+use crate::detect::dmi::BiosVersion;
 use crate::preset::{PlatformProfilePolicy, Preset, PresetKnobs, UsbPolicy};
-use crate::profile::HardwareProfile;
-use crate::sysfs::SysfsRoot;
+use crate::profile::{HardwareProfile, wants_ec_no_wakeup};
+
+/// First BIOS whose EC no longer raises spurious wakes from s2idle.
+pub const EC_WAKE_FIX_BIOS: BiosVersion = BiosVersion::new(3, 5);
 
 #[derive(Debug)]
 pub struct Framework16Amd;
 
+/// Whether the firmware predates the EC wake fix. A version that doesn't
+/// parse counts as affected, which keeps the workaround recommended.
+fn has_ec_wake_bug(hw: &HardwareInfo) -> bool {
+    hw.dmi
+        .parsed_bios_version()
+        .is_none_or(|v| v < EC_WAKE_FIX_BIOS)
+}
+
+/// Suggest a BIOS update when the firmware is known to predate the fix.
+fn check_bios(hw: &HardwareInfo) -> Vec<Finding> {
+    let Some(version) = hw.dmi.parsed_bios_version() else {
+        return Vec::new();
+    };
+    if version >= EC_WAKE_FIX_BIOS {
+        return Vec::new();
+    }
+    vec![
+        Finding::new(
+            Severity::Info,
+            "Firmware",
+            format!("BIOS {} has the EC sleep wake bug — update BIOS", version),
+        )
+        .applies_on(PowerContext::Battery)
+        .current(version.to_string())
+        .recommended(format!("{}+", EC_WAKE_FIX_BIOS))
+        .impact("Fixes spurious wakes in s2idle without acpi.ec_no_wakeup")
+        .path("/sys/class/dmi/id/bios_version")
+        .evidence_from(&hw.raw, "/sys/class/dmi/id/bios_version")
+        .manual_only()
+        .weight(0),
+    ]
+}
+
 impl HardwareProfile for Framework16Amd {
     fn name(&self) -> &str {
         "Framework Laptop 16 (AMD Ryzen 7040 Series)"
@@ -16,8 +52,26 @@ impl HardwareProfile for Framework16Amd {
         hw.dmi.is_framework_16() && hw.cpu.is_amd()
     }
 
-    fn audit_with_opts(
+    fn description(&self) -> &str {
+        "Framework Laptop 16 with a Ryzen 7040: known firmware and sleep quirks on top of the generic checks"
+    }
+
+    fn special_behaviors(&self) -> Vec<String> {
+        vec![
+            format!(
+                "Recommends acpi.ec_no_wakeup=1 and a BIOS update on firmware before {}, whose EC wakes the machine from s2idle",
+                EC_WAKE_FIX_BIOS
+            ),
+            "Keeps XHC0 (internal keyboard and Bluetooth) enabled when filtering ACPI wake sources".to_string(),
+            "Audits the boot parameters s2idle and idle power depend on (RTC ACPI alarm, NVMe APST), which the generic profile leaves alone".to_string(),
+            "Recommends panel power savings (ABM) on battery for the backlit panel, and checks refresh rate and PSR".to_string(),
+            "Watches for USB-C retimers left powered after undocking".to_string(),
+        ]
+    }
+
+    fn audit_in(
         &self,
+        ctx: &AuditContext,
         hw: &HardwareInfo,
         _preset: Preset,
         knobs: &PresetKnobs,
@@ -26,20 +80,30 @@ impl HardwareProfile for Framework16Amd {
             return Vec::new();
         }
 
-        let sysfs = SysfsRoot::system();
+        let sysfs = ctx.sysfs;
         let mut findings = Vec::new();
 
         // Always-safe checks when any knob is active
         if knobs.audio_power_save {
-            findings.extend(audit::audio::check(&sysfs));
+            findings.extend(audit::audio::check(sysfs));
         }
         if knobs.nmi_watchdog_disable || knobs.dirty_writeback.is_some() {
-            findings.extend(audit::sysctl::check_with_knobs(&sysfs, knobs));
+            findings.extend(audit::sysctl::check_with_knobs(sysfs, knobs));
         }
         if knobs.kernel_params {
-            findings.extend(audit::kernel_params::check(hw));
+            findings.extend(audit::kernel_params::check_with_opts(
+                hw,
+                wants_ec_no_wakeup(hw, knobs),
+            ));
+            findings.extend(audit::kernel_params::check_lid_wake(
+                hw,
+                ctx.sleep_log().as_deref(),
+            ));
         }
 
+        findings.extend(check_bios(hw));
+        findings.extend(audit::retimer::check(hw, ctx.drain_alert().as_ref()));
+
         // Hardware-specific checks driven by knobs
         if knobs.epp.is_some()
             || knobs.platform_profile != PlatformProfilePolicy::NoChange
@@ -47,30 +111,68 @@ impl HardwareProfile for Framework16Amd {
         {
             findings.extend(audit::cpu_power::check_with_knobs(hw, knobs));
         }
+        if knobs.has_any_active() {
+            findings.extend(audit::powercap::check(hw, knobs, self));
+        }
         if knobs.aspm_policy.is_some() || knobs.pci_runtime_pm {
             findings.extend(audit::pci_power::check_with_knobs(hw, knobs));
         }
         if knobs.usb_autosuspend != UsbPolicy::NoChange {
-            findings.extend(audit::usb_power::check_with_knobs(&sysfs, knobs));
+            findings.extend(audit::usb_power::check_with_knobs(sysfs, knobs));
         }
         if knobs.gpu_dpm {
             findings.extend(audit::gpu_power::check(hw));
         }
+        if knobs.gpu_dpm || knobs.pcie_link_downgrade {
+            findings.extend(audit::gpu_power::check_link(hw, knobs));
+        }
 
         // Informational checks — run whenever doing real optimizations
         if knobs.epp.is_some() || knobs.pci_runtime_pm || knobs.gpu_dpm {
-            findings.extend(audit::network_power::check(hw));
-            findings.extend(audit::display::check(hw, &sysfs));
+            // WiFi power save is only visible through iw
+            if ctx.commands {
+                findings.extend(audit::network_power::check(hw));
+            }
+            findings.extend(audit::gpu_power::check_multi_display_mclk(hw));
+            findings.extend(audit::display::check(hw, sysfs));
         }
         if knobs.epp.is_some() || knobs.pci_runtime_pm || knobs.gpu_dpm || knobs.acpi_wakeup_filter
         {
-            findings.extend(audit::sleep::check(hw, &sysfs));
+            findings.extend(audit::sleep::check(
+                hw,
+                sysfs,
+                ctx.wakeup_baseline().as_ref(),
+            ));
         }
         // Service conflict check — matches apply's has_any_active() gate
-        if knobs.has_any_active() {
+        if knobs.has_any_active() && ctx.commands {
             findings.extend(audit::services::check());
+            findings.extend(audit::service_cpu::check(hw, sysfs));
         }
+        findings.extend(audit::conflicts::check(sysfs));
 
         findings
     }
+
+    fn kernel_params(&self, hw: &HardwareInfo) -> &'static [&'static str] {
+        // Fixes spurious EC wakes draining the battery in s2idle, until the
+        // BIOS fixes them itself
+        if has_ec_wake_bug(hw) {
+            &["acpi.ec_no_wakeup=1"]
+        } else {
+            &[]
+        }
+    }
+
+    fn package_power_floor_w(&self) -> u32 {
+        10
+    }
+
+    fn nominal_tdp_w(&self, platform_profile: &str) -> Option<u32> {
+        match platform_profile {
+            "performance" => Some(45),
+            "low-power" => Some(30),
+            _ => None,
+        }
+    }
 }
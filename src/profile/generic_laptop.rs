@@ -1,8 +1,7 @@
-use crate::audit::{self, Finding};
+use crate::audit::{self, AuditContext, Finding};
 use crate::detect::HardwareInfo;
 use crate::preset::{PlatformProfilePolicy, Preset, PresetKnobs, UsbPolicy};
-use crate::profile::HardwareProfile;
-use crate::sysfs::SysfsRoot;
+use crate::profile::{HardwareProfile, wants_ec_no_wakeup};
 
 /// Fallback profile for any laptop without a dedicated profile.
 /// Runs hardware-agnostic audit checks that are safe for all machines.
@@ -18,8 +17,20 @@ impl HardwareProfile for GenericLaptop {
         hw.battery.present
     }
 
-    fn audit_with_opts(
+    fn description(&self) -> &str {
+        "Any laptop with a battery and no dedicated profile; only checks that are safe on every machine"
+    }
+
+    fn special_behaviors(&self) -> Vec<String> {
+        vec![
+            "Boot parameters aren't audited; acpi.ec_no_wakeup only when the config turns it on, since it breaks lid wake on many machines".to_string(),
+            "No firmware, panel or retimer checks; the display check is only the memory clock held up by several displays".to_string(),
+        ]
+    }
+
+    fn audit_in(
         &self,
+        ctx: &AuditContext,
         hw: &HardwareInfo,
         _preset: Preset,
         knobs: &PresetKnobs,
@@ -28,15 +39,25 @@ impl HardwareProfile for GenericLaptop {
             return Vec::new();
         }
 
-        let sysfs = SysfsRoot::system();
+        let sysfs = ctx.sysfs;
         let mut findings = Vec::new();
 
         // Always-safe checks when any knob is active
         if knobs.audio_power_save {
-            findings.extend(audit::audio::check(&sysfs));
+            findings.extend(audit::audio::check(sysfs));
         }
         if knobs.nmi_watchdog_disable || knobs.dirty_writeback.is_some() {
-            findings.extend(audit::sysctl::check_with_knobs(&sysfs, knobs));
+            findings.extend(audit::sysctl::check_with_knobs(sysfs, knobs));
+        }
+
+        // acpi.ec_no_wakeup breaks lid wake on many non-Framework machines,
+        // so it's only audited here when the config forces it on
+        if knobs.kernel_params && wants_ec_no_wakeup(hw, knobs) {
+            findings.extend(audit::kernel_params::check_ec_no_wakeup(hw));
+            findings.extend(audit::kernel_params::check_lid_wake(
+                hw,
+                ctx.sleep_log().as_deref(),
+            ));
         }
 
         // Hardware-specific checks driven by knobs
@@ -46,25 +67,39 @@ impl HardwareProfile for GenericLaptop {
         {
             findings.extend(audit::cpu_power::check_with_knobs(hw, knobs));
         }
+        findings.extend(audit::powercap::check(hw, knobs, self));
         if knobs.aspm_policy.is_some() || knobs.pci_runtime_pm {
             findings.extend(audit::pci_power::check_with_knobs(hw, knobs));
         }
         if knobs.usb_autosuspend != UsbPolicy::NoChange {
-            findings.extend(audit::usb_power::check_with_knobs(&sysfs, knobs));
+            findings.extend(audit::usb_power::check_with_knobs(sysfs, knobs));
+        }
+        if knobs.gpu_dpm || knobs.pcie_link_downgrade {
+            findings.extend(audit::gpu_power::check_link(hw, knobs));
         }
 
         // Informational checks — run whenever doing real optimizations
         if knobs.epp.is_some() || knobs.pci_runtime_pm || knobs.gpu_dpm {
-            findings.extend(audit::network_power::check(hw));
+            // WiFi power save is only visible through iw
+            if ctx.commands {
+                findings.extend(audit::network_power::check(hw));
+            }
+            findings.extend(audit::gpu_power::check_multi_display_mclk(hw));
         }
         if knobs.epp.is_some() || knobs.pci_runtime_pm || knobs.gpu_dpm || knobs.acpi_wakeup_filter
         {
-            findings.extend(audit::sleep::check(hw, &sysfs));
+            findings.extend(audit::sleep::check(
+                hw,
+                sysfs,
+                ctx.wakeup_baseline().as_ref(),
+            ));
         }
         // Service conflict check — matches apply's has_any_active() gate
-        if knobs.has_any_active() {
+        if knobs.has_any_active() && ctx.commands {
             findings.extend(audit::services::check());
+            findings.extend(audit::service_cpu::check(hw, sysfs));
         }
+        findings.extend(audit::conflicts::check(sysfs));
 
         findings
     }
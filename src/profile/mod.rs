@@ -1,9 +1,10 @@
 pub mod framework16_amd;
 pub mod generic_laptop;
 
-use crate::audit::Finding;
+use crate::audit::{AuditContext, Finding};
 use crate::detect::HardwareInfo;
 use crate::preset::{Preset, PresetKnobs};
+use crate::sysfs::SysfsRoot;
 
 /// A hardware profile encodes laptop-specific power optimization knowledge.
 pub trait HardwareProfile: std::fmt::Debug {
@@ -13,6 +14,17 @@ pub trait HardwareProfile: std::fmt::Debug {
     /// Whether this profile matches the detected hardware
     fn matches(&self, hw: &HardwareInfo) -> bool;
 
+    /// One sentence on what this profile is for, shown with its notes
+    fn description(&self) -> &str {
+        ""
+    }
+
+    /// What this profile does differently from a generic laptop, and why;
+    /// one short sentence each
+    fn special_behaviors(&self) -> Vec<String> {
+        Vec::new()
+    }
+
     /// Run all audit checks specific to this hardware
     fn audit(&self, hw: &HardwareInfo) -> Vec<Finding> {
         self.audit_with_opts(hw, Preset::Moderate, &Preset::Moderate.knobs())
@@ -24,7 +36,53 @@ pub trait HardwareProfile: std::fmt::Debug {
         hw: &HardwareInfo,
         preset: Preset,
         knobs: &PresetKnobs,
+    ) -> Vec<Finding> {
+        let sysfs = SysfsRoot::system();
+        self.audit_in(&AuditContext::live(&sysfs), hw, preset, knobs)
+    }
+
+    /// Run audit checks against `ctx` instead of the live system.
+    fn audit_in(
+        &self,
+        ctx: &AuditContext,
+        hw: &HardwareInfo,
+        preset: Preset,
+        knobs: &PresetKnobs,
     ) -> Vec<Finding>;
+
+    /// Machine-specific kernel params (`key=value`) this profile recommends
+    /// on top of the generic set. May depend on the firmware in `hw`.
+    fn kernel_params(&self, _hw: &HardwareInfo) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Lowest `[aggressive] package_power_limit_w` this machine takes, in
+    /// watts; a lower one is refused.
+    fn package_power_floor_w(&self) -> u32 {
+        DEFAULT_PACKAGE_POWER_FLOOR_W
+    }
+
+    /// Sustained package power, in watts, the firmware allows under
+    /// `platform_profile`, when known.
+    fn nominal_tdp_w(&self, _platform_profile: &str) -> Option<u32> {
+        None
+    }
+}
+
+/// Package power floor for machines without a profile that knows better.
+pub const DEFAULT_PACKAGE_POWER_FLOOR_W: u32 = 8;
+
+/// The matched profile's package power floor.
+pub fn package_power_floor_w(hw: &HardwareInfo) -> u32 {
+    detect_profile(hw).map_or(DEFAULT_PACKAGE_POWER_FLOOR_W, |p| p.package_power_floor_w())
+}
+
+/// Whether `acpi.ec_no_wakeup=1` should be recommended: the config override
+/// wins, otherwise it's up to the matched profile.
+pub fn wants_ec_no_wakeup(hw: &HardwareInfo, knobs: &PresetKnobs) -> bool {
+    knobs.ec_no_wakeup.unwrap_or_else(|| {
+        detect_profile(hw).is_some_and(|p| p.kernel_params(hw).contains(&"acpi.ec_no_wakeup=1"))
+    })
 }
 
 /// Registry of all known hardware profiles.
@@ -40,3 +98,27 @@ pub fn all_profiles() -> Vec<Box<dyn HardwareProfile>> {
 pub fn detect_profile(hw: &HardwareInfo) -> Option<Box<dyn HardwareProfile>> {
     all_profiles().into_iter().find(|p| p.matches(hw))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_profile_describes_itself() {
+        for profile in all_profiles() {
+            assert!(
+                !profile.description().trim().is_empty(),
+                "{} has no description",
+                profile.name()
+            );
+            assert!(
+                profile
+                    .special_behaviors()
+                    .iter()
+                    .all(|b| !b.trim().is_empty()),
+                "{} lists an empty behavior",
+                profile.name()
+            );
+        }
+    }
+}
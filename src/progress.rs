@@ -0,0 +1,55 @@
+/// What a core operation (apply, revert, wake) is doing, one line at a time.
+/// Core code never prints: the CLI renders these to the terminal, and an
+/// embedder can collect them or drop them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Progress {
+    /// Operation title, e.g. "Reverting changes (applied at ...)"
+    Heading(String),
+    /// Start of a group of steps, e.g. "Restoring sysfs values:"
+    Section(String),
+    /// One step within the current section
+    Step(String),
+    /// Secondary detail, e.g. "(will take effect after reboot)"
+    Detail(String),
+    /// End of the current group of steps
+    EndSection,
+    /// Plain status line
+    Info(String),
+    /// Something went wrong but the operation carried on
+    Warning(String),
+    /// One step failed
+    Failure(String),
+    /// The operation finished successfully
+    Success(String),
+}
+
+/// Receiver for [`Progress`] events.
+pub trait ProgressSink {
+    fn emit(&mut self, event: Progress);
+}
+
+/// Discards every event.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Quiet;
+
+impl ProgressSink for Quiet {
+    fn emit(&mut self, _event: Progress) {}
+}
+
+/// Passes on warnings and failures only.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProblemsOnly<S>(pub S);
+
+impl<S: ProgressSink> ProgressSink for ProblemsOnly<S> {
+    fn emit(&mut self, event: Progress) {
+        if matches!(event, Progress::Warning(_) | Progress::Failure(_)) {
+            self.0.emit(event);
+        }
+    }
+}
+
+impl ProgressSink for Vec<Progress> {
+    fn emit(&mut self, event: Progress) {
+        self.push(event);
+    }
+}
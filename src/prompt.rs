@@ -0,0 +1,150 @@
+//! Yes/no confirmation that doesn't block forever. A deployment tool can
+//! hand bop a TTY with nobody behind it, and apply holds the operation lock
+//! while it asks, so the read polls and gives up on a deadline or Ctrl+C.
+
+use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Exit status when the prompt times out, as timeout(1) uses.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+/// Exit status when the prompt is interrupted: 128 + SIGINT.
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+const TICK: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    Yes,
+    /// Anything but "y", including end of input
+    No,
+    TimedOut,
+    Interrupted,
+}
+
+/// Wait for a line on `input` and read it as a yes/no answer. Gives up
+/// after `timeout`, if any, or as soon as `stop` is set.
+pub fn confirm(
+    input: impl AsFd,
+    timeout: Option<Duration>,
+    stop: &AtomicBool,
+) -> io::Result<Answer> {
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let mut line = Vec::new();
+    let mut buf = [0u8; 256];
+
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            return Ok(Answer::Interrupted);
+        }
+        let wait = match deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    return Ok(Answer::TimedOut);
+                }
+                left.min(TICK)
+            }
+            None => TICK,
+        };
+
+        let mut fds = [PollFd::new(input.as_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, PollTimeout::from(wait.as_millis() as u16)) {
+            Ok(0) | Err(nix::errno::Errno::EINTR) => continue,
+            Ok(_) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let n = match nix::unistd::read(input.as_fd().as_raw_fd(), &mut buf) {
+            Ok(n) => n,
+            Err(nix::errno::Errno::EINTR | nix::errno::Errno::EAGAIN) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        line.extend_from_slice(&buf[..n]);
+        if n == 0 || line.contains(&b'\n') {
+            let answer = String::from_utf8_lossy(&line);
+            let first = answer.lines().next().unwrap_or_default();
+            return Ok(if first.trim().eq_ignore_ascii_case("y") {
+                Answer::Yes
+            } else {
+                Answer::No
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use std::os::fd::OwnedFd;
+
+    fn pipe() -> (OwnedFd, File) {
+        let (read, write) = nix::unistd::pipe().unwrap();
+        (read, File::from(write))
+    }
+
+    fn answer_for(input: &str) -> Answer {
+        let (read, mut write) = pipe();
+        write.write_all(input.as_bytes()).unwrap();
+        drop(write);
+        confirm(&read, Some(Duration::from_secs(5)), &AtomicBool::new(false)).unwrap()
+    }
+
+    #[test]
+    fn test_confirm_reads_answer() {
+        assert_eq!(answer_for("y\n"), Answer::Yes);
+        assert_eq!(answer_for(" Y \n"), Answer::Yes);
+        assert_eq!(answer_for("n\n"), Answer::No);
+        assert_eq!(answer_for("yes please\n"), Answer::No);
+        // End of input without an answer
+        assert_eq!(answer_for(""), Answer::No);
+        assert_eq!(answer_for("y"), Answer::Yes);
+    }
+
+    #[test]
+    fn test_confirm_joins_line_split_across_writes() {
+        let (read, mut write) = pipe();
+        let writer = std::thread::spawn(move || {
+            write.write_all(b" ").unwrap();
+            std::thread::sleep(Duration::from_millis(150));
+            write.write_all(b"y\n").unwrap();
+            // Keep the pipe open so only the newline ends the answer
+            std::thread::sleep(Duration::from_millis(300));
+        });
+        let answer = confirm(&read, None, &AtomicBool::new(false)).unwrap();
+        assert_eq!(answer, Answer::Yes);
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_confirm_times_out_on_silent_input() {
+        let (read, _write) = pipe();
+        let start = Instant::now();
+        let answer = confirm(
+            &read,
+            Some(Duration::from_millis(250)),
+            &AtomicBool::new(false),
+        );
+        assert_eq!(answer.unwrap(), Answer::TimedOut);
+        assert!(start.elapsed() >= Duration::from_millis(250));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_confirm_stops_when_interrupted() {
+        let (read, _write) = pipe();
+        let stop = AtomicBool::new(false);
+        let answer = std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(Duration::from_millis(150));
+                stop.store(true, Ordering::Relaxed);
+            });
+            confirm(&read, None, &stop).unwrap()
+        });
+        assert_eq!(answer, Answer::Interrupted);
+    }
+}
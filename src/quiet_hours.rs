@@ -0,0 +1,174 @@
+//! `[auto] quiet_hours`: a daily window in local time, written `"23:00-07:00"`,
+//! during which `bop auto` keeps to what the power source calls for. A
+//! window whose end is before its start runs past midnight.
+
+use chrono::{NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Start and end, in minutes after midnight. The start is in the window,
+/// the end isn't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct QuietHours {
+    start: u32,
+    end: u32,
+}
+
+impl QuietHours {
+    /// Whether `time` falls in the window.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        let minute = time.hour() * 60 + time.minute();
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+
+    /// Whether the window is on now, in local time.
+    pub fn active_now(&self) -> bool {
+        self.contains(chrono::Local::now().time())
+    }
+
+    /// Time from `time` to the next end of the window.
+    pub fn until_end(&self, time: NaiveTime) -> Duration {
+        const DAY: u32 = 24 * 60 * 60;
+        let now = time.num_seconds_from_midnight();
+        Duration::from_secs(u64::from((self.end * 60 + DAY - now) % DAY))
+    }
+
+    /// Start and end are the same, so the window never opens.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// "HH:MM", 00:00 to 23:59.
+fn parse_time(s: &str) -> Option<u32> {
+    let (hours, minutes) = s.trim().split_once(':')?;
+    if minutes.len() != 2 {
+        return None;
+    }
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+impl FromStr for QuietHours {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || {
+            format!(
+                "invalid quiet hours \"{}\": use \"HH:MM-HH:MM\" in local time, e.g. \"23:00-07:00\"",
+                s
+            )
+        };
+        let (start, end) = s.split_once('-').ok_or_else(err)?;
+        Ok(Self {
+            start: parse_time(start).ok_or_else(err)?,
+            end: parse_time(end).ok_or_else(err)?,
+        })
+    }
+}
+
+impl TryFrom<String> for QuietHours {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<QuietHours> for String {
+    fn from(q: QuietHours) -> Self {
+        q.to_string()
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = |m: u32| format!("{:02}:{:02}", m / 60, m % 60);
+        write!(f, "{}-{}", time(self.start), time(self.end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(time, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let quiet: QuietHours = "23:00-07:00".parse().unwrap();
+        assert_eq!(quiet.to_string(), "23:00-07:00");
+        assert_eq!(
+            " 9:30 - 12:05".parse::<QuietHours>().unwrap().to_string(),
+            "09:30-12:05"
+        );
+        for bad in [
+            "",
+            "23:00",
+            "24:00-07:00",
+            "23:60-07:00",
+            "23-07",
+            "23:0-07:00",
+            "a:00-07:00",
+        ] {
+            assert!(bad.parse::<QuietHours>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_window_wraps_past_midnight() {
+        let quiet: QuietHours = "23:00-07:00".parse().unwrap();
+        assert!(quiet.contains(at("23:00")));
+        assert!(quiet.contains(at("00:00")));
+        assert!(quiet.contains(at("06:59")));
+        assert!(!quiet.contains(at("07:00")));
+        assert!(!quiet.contains(at("22:59")));
+        assert!(!quiet.contains(at("12:00")));
+    }
+
+    #[test]
+    fn test_window_within_a_day() {
+        let quiet: QuietHours = "13:00-14:30".parse().unwrap();
+        assert!(quiet.contains(at("13:00")));
+        assert!(quiet.contains(at("14:29")));
+        assert!(!quiet.contains(at("14:30")));
+        assert!(!quiet.contains(at("00:30")));
+
+        let empty: QuietHours = "07:00-07:00".parse().unwrap();
+        assert!(empty.is_empty());
+        assert!(!empty.contains(at("07:00")));
+    }
+
+    #[test]
+    fn test_until_end() {
+        let quiet: QuietHours = "23:00-07:00".parse().unwrap();
+        assert_eq!(quiet.until_end(at("23:00")), Duration::from_secs(8 * 3600));
+        assert_eq!(quiet.until_end(at("06:30")), Duration::from_secs(30 * 60));
+        let seconds = NaiveTime::from_hms_opt(6, 59, 30).unwrap();
+        assert_eq!(quiet.until_end(seconds), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        #[derive(Serialize, Deserialize)]
+        struct Auto {
+            quiet_hours: QuietHours,
+        }
+        let auto: Auto = toml::from_str("quiet_hours = \"22:15-06:45\"").unwrap();
+        assert!(auto.quiet_hours.contains(at("03:00")));
+        assert_eq!(
+            toml::to_string(&auto).unwrap().trim(),
+            "quiet_hours = \"22:15-06:45\""
+        );
+        assert!(toml::from_str::<Auto>("quiet_hours = \"late\"").is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Per-device runtime PM quirks, keyed by PCI `vendor:device` ID.
+//!
+//! SD card readers are both the most common runtime-PM blockers and the most
+//! common devices broken by enabling it, so they get explicit handling. To add
+//! a reader, append an entry to `PCI_QUIRKS` — the unit tests check that every
+//! key parses and is unique.
+
+/// What `bop apply` should do with a device's `power/control`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimePmAction {
+    /// Runtime PM works; treat like any other device.
+    SafeAuto,
+    /// Runtime PM works only with a module parameter set at load time.
+    NeedsModuleParam {
+        module: &'static str,
+        param: &'static str,
+    },
+    /// Enabling runtime PM breaks the device; leave it on.
+    NeverAuto,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PciQuirk {
+    /// Lowercase hex `vendor:device`, e.g. "10ec:525a"
+    pub id: &'static str,
+    pub name: &'static str,
+    pub action: RuntimePmAction,
+    /// Shown in audit output
+    pub note: &'static str,
+}
+
+const RTSX_RUNTIME_PM: RuntimePmAction = RuntimePmAction::NeedsModuleParam {
+    module: "rtsx_pci",
+    param: "enable_runtime_pm=1",
+};
+
+pub const PCI_QUIRKS: &[PciQuirk] = &[
+    // Realtek (rtsx_pci)
+    PciQuirk {
+        id: "10ec:5227",
+        name: "Realtek RTS5227",
+        action: RuntimePmAction::NeverAuto,
+        note: "card detection stops after runtime suspend",
+    },
+    PciQuirk {
+        id: "10ec:5229",
+        name: "Realtek RTS5229",
+        action: RuntimePmAction::NeverAuto,
+        note: "card detection stops after runtime suspend",
+    },
+    PciQuirk {
+        id: "10ec:5249",
+        name: "Realtek RTS5249",
+        action: RTSX_RUNTIME_PM,
+        note: "stays active without the driver's runtime PM support",
+    },
+    PciQuirk {
+        id: "10ec:522a",
+        name: "Realtek RTS522A",
+        action: RTSX_RUNTIME_PM,
+        note: "stays active without the driver's runtime PM support",
+    },
+    PciQuirk {
+        id: "10ec:525a",
+        name: "Realtek RTS525A",
+        action: RTSX_RUNTIME_PM,
+        note: "stays active without the driver's runtime PM support",
+    },
+    PciQuirk {
+        id: "10ec:5260",
+        name: "Realtek RTS5260",
+        action: RTSX_RUNTIME_PM,
+        note: "stays active without the driver's runtime PM support",
+    },
+    PciQuirk {
+        id: "10ec:5261",
+        name: "Realtek RTS5261",
+        action: RTSX_RUNTIME_PM,
+        note: "stays active without the driver's runtime PM support",
+    },
+    // Genesys Logic (sdhci-pci)
+    PciQuirk {
+        id: "17a0:9750",
+        name: "Genesys GL9750",
+        action: RuntimePmAction::NeverAuto,
+        note: "fails to resume from runtime suspend on several laptops",
+    },
+    PciQuirk {
+        id: "17a0:9755",
+        name: "Genesys GL9755",
+        action: RuntimePmAction::SafeAuto,
+        note: "runtime PM supported by sdhci-pci",
+    },
+    PciQuirk {
+        id: "17a0:e763",
+        name: "Genesys GL9763E",
+        action: RuntimePmAction::SafeAuto,
+        note: "runtime PM supported by sdhci-pci",
+    },
+];
+
+/// Parse a `vendor:device` key into numeric IDs.
+fn parse_id(id: &str) -> Option<(u16, u16)> {
+    let (vendor, device) = id.split_once(':')?;
+    Some((
+        u16::from_str_radix(vendor, 16).ok()?,
+        u16::from_str_radix(device, 16).ok()?,
+    ))
+}
+
+/// Look up a device by the sysfs `vendor`/`device` values ("0x10ec", "0x525a").
+pub fn lookup_pci(vendor: &str, device: &str) -> Option<&'static PciQuirk> {
+    let parse = |s: &str| u16::from_str_radix(s.trim().trim_start_matches("0x"), 16).ok();
+    let key = (parse(vendor)?, parse(device)?);
+    PCI_QUIRKS.iter().find(|q| parse_id(q.id) == Some(key))
+}
+
+/// Modprobe drop-in setting a module parameter, e.g. `bop-rtsx_pci.conf`.
+pub fn modprobe_config(module: &str, param: &str) -> crate::apply::ModprobeConfig {
+    crate::apply::ModprobeConfig {
+        filename: format!("bop-{}.conf", module),
+        content: format!(
+            "{}\n# Removed by `bop revert`\noptions {} {}\n",
+            crate::managed::header(),
+            module,
+            param
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_every_entry_parses_and_is_unique() {
+        let mut seen = HashSet::new();
+        for quirk in PCI_QUIRKS {
+            let key = parse_id(quirk.id).unwrap_or_else(|| panic!("bad id {}", quirk.id));
+            assert_eq!(
+                quirk.id,
+                quirk.id.to_ascii_lowercase(),
+                "ids are lowercase hex"
+            );
+            assert!(seen.insert(key), "duplicate quirk key {}", quirk.id);
+            assert!(!quirk.name.is_empty());
+            if let RuntimePmAction::NeedsModuleParam { module, param } = quirk.action {
+                assert!(!module.is_empty() && param.contains('='));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lookup_sysfs_format() {
+        let q = lookup_pci("0x10ec", "0x525a").unwrap();
+        assert_eq!(q.name, "Realtek RTS525A");
+        assert_eq!(lookup_pci("0x10EC", "0x525A").unwrap().id, "10ec:525a");
+        assert!(lookup_pci("0x1022", "0x14e8").is_none());
+        assert!(lookup_pci("garbage", "0x525a").is_none());
+    }
+
+    #[test]
+    fn test_modprobe_config() {
+        let cfg = modprobe_config("rtsx_pci", "enable_runtime_pm=1");
+        assert_eq!(cfg.filename, "bop-rtsx_pci.conf");
+        assert!(
+            cfg.content
+                .ends_with("options rtsx_pci enable_runtime_pm=1\n")
+        );
+    }
+}
@@ -0,0 +1,552 @@
+//! Rollout reports written by `bop apply --report` and `bop auto --report`.
+//!
+//! A report file is a JSON array; each run appends one entry and nothing in
+//! bop reads it back. It is history for the admin, not state — `bop revert`
+//! never touches it.
+
+use crate::apply::wifi::{NM_DROP_IN_PATH, WifiPowerSave};
+use crate::apply::{ApplyPlan, ApplyState};
+use crate::audit::{Finding, PowerContext, context_score};
+use crate::error::{Error, Result};
+use crate::sysfs::SysfsRoot;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// Bumped on breaking changes to any JSON output (audit, status, reports).
+pub const JSON_SCHEMA_VERSION: u32 = 2;
+
+/// How long an append waits for another one to the same file.
+const APPEND_LOCK_WAIT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemStatus {
+    /// Dry run: would be changed
+    Planned,
+    Applied,
+    /// Written, but takes effect later (reboot or module reload)
+    Deferred,
+    /// Already in the target state; nothing was written
+    Unchanged,
+    /// Not reached because the run failed first
+    NotApplied,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportItem {
+    /// sysfs, kernel_param, kernel_param_removal, service, acpi_wakeup,
+    /// modprobe, wifi_drop_in or systemd_unit
+    pub kind: String,
+    pub target: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub status: ItemStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApplyReport {
+    pub schema_version: u32,
+    pub bop_version: String,
+    pub hostname: String,
+    /// "apply" or "auto"
+    pub command: String,
+    pub preset: String,
+    pub profile: String,
+    pub dry_run: bool,
+    pub started_at: String,
+    pub finished_at: String,
+    pub score_before: u32,
+    /// Score with every finding the plan addresses removed
+    pub score_projected: u32,
+    pub items: Vec<ReportItem>,
+    /// Same lines as the terminal summary after apply
+    pub summary: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl ApplyReport {
+    pub fn new(
+        sysfs: &SysfsRoot,
+        command: &str,
+        preset: impl ToString,
+        profile: &str,
+        dry_run: bool,
+    ) -> Self {
+        let hostname = sysfs
+            .read_optional("proc/sys/kernel/hostname")
+            .unwrap_or(None)
+            .unwrap_or_else(|| "unknown".to_string());
+        Self {
+            schema_version: JSON_SCHEMA_VERSION,
+            bop_version: env!("CARGO_PKG_VERSION").to_string(),
+            hostname,
+            command: command.to_string(),
+            preset: preset.to_string(),
+            profile: profile.to_string(),
+            dry_run,
+            started_at: chrono::Utc::now().to_rfc3339(),
+            finished_at: String::new(),
+            score_before: 100,
+            score_projected: 100,
+            items: Vec::new(),
+            summary: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Score the audit findings as they are now and as the plan would leave
+    /// them. Apply tunes for battery, so these are battery-context scores.
+    pub fn scores(mut self, findings: &[Finding], plan: &ApplyPlan) -> Self {
+        self.score_before = context_score(findings, PowerContext::Battery);
+        let remaining: Vec<Finding> = findings
+            .iter()
+            .filter(|f| !addressed_by_plan(f, plan))
+            .cloned()
+            .collect();
+        self.score_projected = context_score(&remaining, PowerContext::Battery);
+        self
+    }
+
+    /// Fill in per-item outcomes from what execution recorded in `state`.
+    pub fn finish(
+        &mut self,
+        plan: &ApplyPlan,
+        state: &ApplyState,
+        sysfs: &SysfsRoot,
+        error: Option<String>,
+    ) {
+        let failed = error.is_some();
+        let pending = |dry_run: bool| {
+            if dry_run {
+                ItemStatus::Planned
+            } else if failed {
+                ItemStatus::NotApplied
+            } else {
+                ItemStatus::Unchanged
+            }
+        };
+        let mut items = Vec::new();
+
+        for write in &plan.sysfs_writes {
+            let item = match state.sysfs_changes.iter().find(|c| c.path == write.path) {
+                Some(change) => ReportItem {
+                    kind: "sysfs".to_string(),
+                    target: write.path.clone(),
+                    before: Some(change.restore_value()),
+                    after: Some(change.expected_value()),
+                    status: ItemStatus::Applied,
+                },
+                None => {
+                    let relative = write.path.strip_prefix('/').unwrap_or(&write.path);
+                    ReportItem {
+                        kind: "sysfs".to_string(),
+                        target: write.path.clone(),
+                        before: sysfs
+                            .read_optional(relative)
+                            .unwrap_or(None)
+                            .map(|v| crate::sysfs::normalize_value(&v)),
+                        after: Some(write.value.clone()),
+                        status: pending(self.dry_run),
+                    }
+                }
+            };
+            items.push(item);
+        }
+
+        for param in &plan.kernel_params {
+            let status = if state.kernel_params_added.contains(param) {
+                ItemStatus::Deferred
+            } else {
+                pending(self.dry_run)
+            };
+            items.push(simple_item("kernel_param", param, status));
+        }
+
+        for param in &plan.kernel_params_remove {
+            let status = if state.kernel_params_removed.contains(param) {
+                ItemStatus::Deferred
+            } else {
+                pending(self.dry_run)
+            };
+            items.push(simple_item("kernel_param_removal", param, status));
+        }
+
+        for svc in &plan.services_to_disable {
+            let status = if state.services_disabled.contains(svc) {
+                ItemStatus::Applied
+            } else {
+                pending(self.dry_run)
+            };
+            items.push(simple_item("service", svc, status));
+        }
+
+        for device in &plan.acpi_wakeup_disable {
+            let status = if state.acpi_wakeup_toggled.contains(device) {
+                ItemStatus::Applied
+            } else {
+                pending(self.dry_run)
+            };
+            items.push(simple_item("acpi_wakeup", device, status));
+        }
+
+        for config in &plan.modprobe_configs {
+            let status = if state
+                .modprobe_files_created
+                .iter()
+                .any(|p| p.ends_with(&config.filename))
+            {
+                ItemStatus::Deferred
+            } else {
+                pending(self.dry_run)
+            };
+            items.push(simple_item("modprobe", &config.filename, status));
+        }
+
+        if plan.wifi_power_save == Some(WifiPowerSave::NetworkManager) {
+            let status = if state.wifi_files_created.is_empty() {
+                pending(self.dry_run)
+            } else {
+                ItemStatus::Deferred
+            };
+            items.push(simple_item("wifi_drop_in", NM_DROP_IN_PATH, status));
+        }
+
+        if plan.systemd_service && !plan.sysfs_writes.is_empty() {
+            let status = if state.systemd_units_created.is_empty() {
+                pending(self.dry_run)
+            } else {
+                ItemStatus::Applied
+            };
+            items.push(simple_item("systemd_unit", "bop-powersave.service", status));
+        }
+
+        self.items = items;
+        self.summary = summary_lines(state);
+        self.error = error;
+        self.finished_at = chrono::Utc::now().to_rfc3339();
+    }
+
+    /// Append this report to the JSON array at `path`, creating it if needed.
+    /// Holds a lock beside the file while it does, since a dry run or an
+    /// offline apply writes reports without the operation lock.
+    pub fn append_to(&self, path: &Path) -> Result<()> {
+        let _lock = crate::lock::acquire_at(
+            &path.with_extension("json.lock"),
+            "report",
+            Some(APPEND_LOCK_WAIT),
+        )?;
+        let mut history: Vec<serde_json::Value> = match std::fs::read_to_string(path) {
+            Ok(s) if !s.trim().is_empty() => serde_json::from_str(&s).map_err(|e| {
+                Error::Other(format!(
+                    "{} is not a bop report file (expected a JSON array): {}",
+                    path.display(),
+                    e
+                ))
+            })?,
+            Ok(_) => Vec::new(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(Error::Other(format!(
+                    "failed to read {}: {}",
+                    path.display(),
+                    e
+                )));
+            }
+        };
+        history.push(
+            serde_json::to_value(self)
+                .map_err(|e| Error::Other(format!("failed to encode report: {}", e)))?,
+        );
+
+        let json = serde_json::to_string_pretty(&history)
+            .map_err(|e| Error::Other(format!("failed to encode report: {}", e)))?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json + "\n")
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .map_err(|e| Error::Other(format!("failed to write {}: {}", path.display(), e)))
+    }
+}
+
+fn simple_item(kind: &str, target: &str, status: ItemStatus) -> ReportItem {
+    ReportItem {
+        kind: kind.to_string(),
+        target: target.to_string(),
+        before: None,
+        after: None,
+        status,
+    }
+}
+
+/// One line per category of recorded change, as printed after `bop apply`.
+pub fn summary_lines(state: &ApplyState) -> Vec<String> {
+    // Categorize sysfs changes
+    let mut epp_count = 0usize;
+    let mut pci_count = 0usize;
+    let mut usb_count = 0usize;
+    let mut audio_count = 0usize;
+    let mut gpu_count = 0usize;
+    let mut other_count = 0usize;
+
+    for change in &state.sysfs_changes {
+        if change.path.contains("energy_performance_preference") {
+            epp_count += 1;
+        } else if change.path.contains("/bus/pci/") {
+            pci_count += 1;
+        } else if change.path.contains("/bus/usb/") {
+            usb_count += 1;
+        } else if change.path.contains("snd_hda_intel") {
+            audio_count += 1;
+        } else if change.path.contains("power_dpm") {
+            gpu_count += 1;
+        } else {
+            other_count += 1;
+        }
+    }
+
+    let mut parts = Vec::new();
+    if epp_count > 0 {
+        parts.push(format!("{} CPU cores", epp_count));
+    }
+    if other_count > 0 {
+        parts.push(format!("{} system", other_count));
+    }
+    if pci_count > 0 {
+        parts.push(format!("{} PCI", pci_count));
+    }
+    if usb_count > 0 {
+        parts.push(format!("{} USB", usb_count));
+    }
+    if audio_count > 0 {
+        parts.push(format!("{} audio", audio_count));
+    }
+    if gpu_count > 0 {
+        parts.push(format!("{} GPU", gpu_count));
+    }
+
+    let mut lines = Vec::new();
+    if !parts.is_empty() {
+        lines.push(format!("Sysfs: {}", parts.join(", ")));
+    }
+    if !state.kernel_params_added.is_empty() {
+        lines.push(format!(
+            "Kernel params: {}",
+            state.kernel_params_added.join(", ")
+        ));
+    }
+    if !state.kernel_params_removed.is_empty() {
+        lines.push(format!(
+            "Kernel params removed: {}",
+            state.kernel_params_removed.join(", ")
+        ));
+    }
+    if !state.acpi_wakeup_toggled.is_empty() {
+        lines.push(format!(
+            "ACPI wakeup disabled: {}",
+            state.acpi_wakeup_toggled.join(", ")
+        ));
+    }
+    if !state.services_disabled.is_empty() {
+        lines.push(format!(
+            "Services disabled: {}",
+            state.services_disabled.join(", ")
+        ));
+    }
+    if !state.modprobe_files_created.is_empty() {
+        lines.push(format!(
+            "Module options: {}",
+            state.modprobe_files_created.join(", ")
+        ));
+    }
+    if !state.wifi_files_created.is_empty() {
+        lines.push(format!(
+            "WiFi power save: {}",
+            state.wifi_files_created.join(", ")
+        ));
+    }
+    if !state.udev_files_created.is_empty() {
+        lines.push(format!(
+            "USB port re-enable rule: {}",
+            state.udev_files_created.join(", ")
+        ));
+    }
+    if !state.sysctl_files_created.is_empty() {
+        lines.push(format!(
+            "Sysctls: {}",
+            state.sysctl_files_created.join(", ")
+        ));
+    }
+    lines
+}
+
+/// Whether applying `plan` resolves `finding`. Finding paths are loose
+/// (absolute, relative to the cpu dir, or globbed), so match them as a
+/// `*`-wildcard suffix of a planned write path.
+fn addressed_by_plan(finding: &Finding, plan: &ApplyPlan) -> bool {
+    if finding.weight == 0 {
+        return false;
+    }
+    if !finding.recommended_value.is_empty()
+        && plan
+            .kernel_params
+            .iter()
+            .any(|p| finding.recommended_value.contains(p.as_str()))
+    {
+        return true;
+    }
+    if finding.category == "Services"
+        && plan.services_to_disable.iter().any(|svc| {
+            finding
+                .description
+                .contains(svc.trim_end_matches(".service"))
+        })
+    {
+        return true;
+    }
+    match finding.path.as_deref() {
+        Some("/proc/acpi/wakeup") => !plan.acpi_wakeup_disable.is_empty(),
+        Some(pattern) => plan
+            .sysfs_writes
+            .iter()
+            .any(|w| path_suffix_matches(pattern, &w.path)),
+        None => false,
+    }
+}
+
+fn path_suffix_matches(pattern: &str, path: &str) -> bool {
+    let pat: Vec<&str> = pattern.split('/').filter(|s| !s.is_empty()).collect();
+    let segs: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    if pat.is_empty() || pat.len() > segs.len() {
+        return false;
+    }
+    pat.iter()
+        .zip(&segs[segs.len() - pat.len()..])
+        .all(|(p, s)| wildcard_match(p, s))
+}
+
+/// `*` matches any run of characters within one path segment.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(tail) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=tail.len())
+                .filter(|&i| tail.is_char_boundary(i))
+                .any(|i| wildcard_match(rest, &tail[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply::{PlannedSysfsWrite, SysfsChange};
+    use crate::audit::Severity;
+    use tempfile::TempDir;
+
+    fn write(path: &str, value: &str) -> PlannedSysfsWrite {
+        PlannedSysfsWrite {
+            path: path.to_string(),
+            value: value.to_string(),
+            description: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_path_suffix_matches() {
+        let epp = "/sys/devices/system/cpu/cpu3/cpufreq/energy_performance_preference";
+        assert!(path_suffix_matches(
+            "cpu*/cpufreq/energy_performance_preference",
+            epp
+        ));
+        assert!(path_suffix_matches(
+            "/sys/bus/pci/devices/*/power/control",
+            "/sys/bus/pci/devices/0000:00:08.1/power/control"
+        ));
+        assert!(!path_suffix_matches(
+            "/sys/bus/usb/devices/*/power/control",
+            "/sys/bus/pci/devices/0000:00:08.1/power/control"
+        ));
+        assert!(!path_suffix_matches("cpu*/cpufreq/scaling_governor", epp));
+    }
+
+    #[test]
+    fn test_projected_score_drops_addressed_findings() {
+        let plan = ApplyPlan {
+            sysfs_writes: vec![write("/sys/firmware/acpi/platform_profile", "low-power")],
+            ..Default::default()
+        };
+        let findings = vec![
+            Finding::new(Severity::Medium, "cpu", "profile")
+                .path("/sys/firmware/acpi/platform_profile")
+                .weight(6),
+            Finding::new(Severity::Low, "gpu", "other").weight(2),
+        ];
+        let tmp = TempDir::new().unwrap();
+        let report = ApplyReport::new(&SysfsRoot::new(tmp.path()), "apply", "moderate", "x", true)
+            .scores(&findings, &plan);
+        assert_eq!(report.score_before, 60);
+        assert_eq!(report.score_projected, 80);
+    }
+
+    #[test]
+    fn test_finish_marks_failure_and_unreached_items() {
+        let tmp = TempDir::new().unwrap();
+        let sysfs = SysfsRoot::new(tmp.path());
+        let plan = ApplyPlan {
+            sysfs_writes: vec![write("/a", "1"), write("/b", "2")],
+            kernel_params: vec!["foo=1".to_string()],
+            ..Default::default()
+        };
+        let state = ApplyState {
+            sysfs_changes: vec![SysfsChange::new("/a", "0\n", "1")],
+            ..Default::default()
+        };
+
+        let mut report = ApplyReport::new(&sysfs, "apply", "moderate", "x", false);
+        report.finish(&plan, &state, &sysfs, Some("boom".to_string()));
+
+        assert_eq!(report.items[0].status, ItemStatus::Applied);
+        assert_eq!(report.items[0].before.as_deref(), Some("0"));
+        assert_eq!(report.items[1].status, ItemStatus::NotApplied);
+        assert_eq!(report.items[2].status, ItemStatus::NotApplied);
+        assert_eq!(report.summary, vec!["Sysfs: 1 system".to_string()]);
+        assert_eq!(report.error.as_deref(), Some("boom"));
+    }
+
+    #[test]
+    fn test_append_keeps_history() {
+        let tmp = TempDir::new().unwrap();
+        let sysfs = SysfsRoot::new(tmp.path());
+        let path = tmp.path().join("report.json");
+
+        let report = ApplyReport::new(&sysfs, "apply", "moderate", "x", true);
+        report.append_to(&path).unwrap();
+        report.append_to(&path).unwrap();
+
+        let history: Vec<ApplyReport> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].schema_version, JSON_SCHEMA_VERSION);
+
+        // Concurrent appends (say a dry run next to `bop auto`) all land
+        let appends: Vec<_> = (0..8)
+            .map(|_| {
+                let (report, path) = (report.clone(), path.clone());
+                std::thread::spawn(move || report.append_to(&path).unwrap())
+            })
+            .collect();
+        appends.into_iter().for_each(|t| t.join().unwrap());
+        let history: Vec<ApplyReport> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(history.len(), 10);
+
+        std::fs::write(&path, "{}").unwrap();
+        assert!(
+            report.append_to(&path).is_err(),
+            "refuses to clobber non-arrays"
+        );
+    }
+}
@@ -1,43 +1,121 @@
+pub mod rescue;
+
+use crate::apply::rollback::TimerOps;
+use crate::apply::sysfs_writer::{ProcAcpiWakeup, WakeupTable, toggle_acpi_wakeup_in};
 use crate::apply::{self, ApplyState};
 use crate::error::{Error, Result};
+use crate::managed::Ownership;
+use crate::progress::{Progress, ProgressSink};
 use crate::sysfs::SysfsRoot;
-use colored::Colorize;
 
-pub fn revert() -> Result<()> {
-    if !nix::unistd::geteuid().is_root() {
+/// A category of recorded changes that `bop revert --only` can target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum RevertScope {
+    Sysfs,
+    AcpiWakeup,
+    KernelParams,
+    Services,
+    Modprobe,
+    Wifi,
+    Systemd,
+}
+
+impl std::fmt::Display for RevertScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            RevertScope::Sysfs => "sysfs",
+            RevertScope::AcpiWakeup => "acpi-wakeup",
+            RevertScope::KernelParams => "kernel-params",
+            RevertScope::Services => "services",
+            RevertScope::Modprobe => "modprobe",
+            RevertScope::Wifi => "wifi",
+            RevertScope::Systemd => "systemd",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn in_scope(only: Option<RevertScope>, scope: RevertScope) -> bool {
+    only.is_none_or(|o| o == scope)
+}
+
+/// Undo the recorded changes in `only` (all of them if `None`). Generated
+/// files are only deleted if bop still recognises them, unless `force`.
+/// With `keep_rollback`, for a revert that a re-apply follows, a pending
+/// automatic rollback stays scheduled and recorded so it reverts whatever
+/// is applied next.
+pub fn revert(
+    only: Option<RevertScope>,
+    force: bool,
+    keep_rollback: bool,
+    progress: &mut dyn ProgressSink,
+) -> Result<()> {
+    // A state under --state-dir is the caller's own; file permissions decide
+    if !nix::unistd::geteuid().is_root() && apply::custom_state_dir().is_none() {
         return Err(Error::NotRoot {
             operation: "revert".to_string(),
         });
     }
 
+    let recovered = apply::journal::reconcile()?;
+    if recovered > 0 {
+        progress.emit(Progress::Warning(format!(
+            "Recovered {} original value(s) from an interrupted run",
+            recovered
+        )));
+    }
+
     let state = match ApplyState::load()? {
         Some(s) => s,
         None => {
-            println!("{}", "No saved state found. Nothing to revert.".yellow());
+            progress.emit(Progress::Info(
+                "No saved state found. Nothing to revert.".to_string(),
+            ));
             return Ok(());
         }
     };
 
-    println!(
-        "{} (applied at {})",
-        "Reverting changes".bold().underline(),
+    progress.emit(Progress::Heading(format!(
+        "Reverting changes (applied at {})",
         state.timestamp
-    );
-    println!();
+    )));
 
-    let all_succeeded = revert_loaded_state(&state)?;
+    let all_succeeded = revert_loaded_state(
+        &state,
+        only,
+        force,
+        keep_rollback,
+        &mut ProcAcpiWakeup,
+        progress,
+    )?;
 
-    if all_succeeded {
-        println!("{}", "Revert complete.".green().bold());
+    if all_succeeded && let Some(scope) = only {
+        progress.emit(Progress::Success(format!("Reverted {}.", scope)));
+        if ApplyState::file_path().exists() {
+            progress.emit(Progress::Info(
+                "  Other changes are still recorded. Run `sudo bop revert` to undo them."
+                    .to_string(),
+            ));
+        }
+    } else if all_succeeded {
+        progress.emit(Progress::Success("Revert complete.".to_string()));
+        // Nothing left for a pending automatic rollback to do, unless a
+        // re-apply follows
+        if let Some(pending) = &state.rollback
+            && !keep_rollback
+            && let Err(e) = apply::rollback::SystemdRun.stop(&pending.unit)
+        {
+            progress.emit(Progress::Warning(format!(
+                "Could not cancel the automatic rollback: {}",
+                e
+            )));
+        }
     } else {
-        eprintln!(
-            "{}",
-            format!(
-                "Revert incomplete. Kept state file at {} so you can retry after resolving failures.",
-                ApplyState::file_path().display()
-            )
-            .yellow()
-        );
+        progress.emit(Progress::Warning(format!(
+            "Revert incomplete. Kept state file at {} so you can retry after resolving failures.",
+            ApplyState::file_path().display()
+        )));
     }
 
     // Show reboot note whenever kernel params were actually reverted,
@@ -46,41 +124,122 @@ pub fn revert() -> Result<()> {
         match ApplyState::load() {
             Ok(s) => s,
             Err(e) => {
-                eprintln!(
-                    "{} Failed to reload state after partial revert ({}); assuming no kernel params remain.",
-                    "!".yellow(),
+                progress.emit(Progress::Warning(format!(
+                    "Failed to reload state after partial revert ({}); assuming no kernel params remain.",
                     e
-                );
+                )));
                 None
             }
         }
     } else {
         None
     };
-    let had_kernel_params =
-        !state.kernel_param_backups.is_empty() || !state.kernel_params_added.is_empty();
+    let had_kernel_params = in_scope(only, RevertScope::KernelParams)
+        && (!state.kernel_param_backups.is_empty() || !state.kernel_params_added.is_empty());
     let still_has_kernel_params = remaining
         .as_ref()
         .is_some_and(|r| !r.kernel_param_backups.is_empty() || !r.kernel_params_added.is_empty());
     if had_kernel_params && !still_has_kernel_params {
-        println!(
-            "{}",
-            "  Note: Kernel parameter changes require a reboot to take effect.".yellow()
-        );
+        progress.emit(Progress::Info(
+            "  Note: Kernel parameter changes require a reboot to take effect.".to_string(),
+        ));
     }
 
     Ok(())
 }
 
-fn revert_loaded_state(state: &ApplyState) -> Result<bool> {
-    let remaining = revert_steps(state);
-    if has_pending_reverts(&remaining) {
+/// Revert the selected scope and persist whatever is left (failures plus
+/// out-of-scope changes, and with `keep_rollback` a pending rollback).
+/// Returns true if everything in scope was reverted.
+fn revert_loaded_state(
+    state: &ApplyState,
+    only: Option<RevertScope>,
+    force: bool,
+    keep_rollback: bool,
+    wakeup: &mut dyn WakeupTable,
+    progress: &mut dyn ProgressSink,
+) -> Result<bool> {
+    let remaining = revert_steps(state, only, force, wakeup, progress);
+    let scope_succeeded = !has_pending_reverts(&partition_scope(&remaining, only).0);
+    if has_pending_reverts(&remaining) || (keep_rollback && remaining.rollback.is_some()) {
         remaining.save()?;
-        Ok(false)
     } else {
         ApplyState::remove_file()?;
-        Ok(true)
     }
+    Ok(scope_succeeded)
+}
+
+/// Split a state into the changes selected by `only` and everything else.
+fn partition_scope(state: &ApplyState, only: Option<RevertScope>) -> (ApplyState, ApplyState) {
+    let mut selected = ApplyState {
+        timestamp: state.timestamp.clone(),
+        boot_id: state.boot_id.clone(),
+        preset: state.preset,
+        ..Default::default()
+    };
+    let mut rest = selected.clone();
+    // A pending automatic rollback still has the rest to undo
+    rest.rollback = state.rollback.clone();
+
+    fn side<'a>(
+        only: Option<RevertScope>,
+        scope: RevertScope,
+        selected: &'a mut ApplyState,
+        rest: &'a mut ApplyState,
+    ) -> &'a mut ApplyState {
+        if in_scope(only, scope) {
+            selected
+        } else {
+            rest
+        }
+    }
+
+    let sysfs = side(only, RevertScope::Sysfs, &mut selected, &mut rest);
+    sysfs.sysfs_changes = state.sysfs_changes.clone();
+    sysfs.udev_files_created = state.udev_files_created.clone();
+    sysfs.sysctl_files_created = state.sysctl_files_created.clone();
+    side(only, RevertScope::AcpiWakeup, &mut selected, &mut rest).acpi_wakeup_toggled =
+        state.acpi_wakeup_toggled.clone();
+    let kp = side(only, RevertScope::KernelParams, &mut selected, &mut rest);
+    kp.kernel_params_added = state.kernel_params_added.clone();
+    kp.kernel_params_removed = state.kernel_params_removed.clone();
+    kp.kernel_param_backups = state.kernel_param_backups.clone();
+    let services = side(only, RevertScope::Services, &mut selected, &mut rest);
+    services.services_disabled = state.services_disabled.clone();
+    services.services_disabled_at = state.services_disabled_at.clone();
+    side(only, RevertScope::Modprobe, &mut selected, &mut rest).modprobe_files_created =
+        state.modprobe_files_created.clone();
+    let wifi = side(only, RevertScope::Wifi, &mut selected, &mut rest);
+    wifi.wifi_files_created = state.wifi_files_created.clone();
+    wifi.wifi_exception = state.wifi_exception.clone();
+    side(only, RevertScope::Systemd, &mut selected, &mut rest).systemd_units_created =
+        state.systemd_units_created.clone();
+    for file in &state.managed_files {
+        let scope = if state.modprobe_files_created.contains(&file.path) {
+            RevertScope::Modprobe
+        } else if state.wifi_files_created.contains(&file.path) {
+            RevertScope::Wifi
+        } else if state.udev_files_created.contains(&file.path)
+            || state.sysctl_files_created.contains(&file.path)
+        {
+            RevertScope::Sysfs
+        } else {
+            RevertScope::Systemd
+        };
+        side(only, scope, &mut selected, &mut rest)
+            .managed_files
+            .push(file.clone());
+    }
+    // Brightness has no scope of its own; only a full revert restores it
+    let brightness_side = if only.is_none() {
+        &mut selected
+    } else {
+        &mut rest
+    };
+    brightness_side.brightness_original = state.brightness_original;
+    brightness_side.brightness_device = state.brightness_device.clone();
+
+    (selected, rest)
 }
 
 fn has_pending_reverts(state: &ApplyState) -> bool {
@@ -90,148 +249,299 @@ fn has_pending_reverts(state: &ApplyState) -> bool {
         || !state.kernel_params_added.is_empty()
         || !state.services_disabled.is_empty()
         || !state.systemd_units_created.is_empty()
+        || !state.modprobe_files_created.is_empty()
+        || !state.wifi_files_created.is_empty()
+        || !state.udev_files_created.is_empty()
+        || !state.sysctl_files_created.is_empty()
         || state.brightness_original.is_some()
+        || state.wifi_exception.is_some()
 }
 
-fn revert_steps(state: &ApplyState) -> ApplyState {
-    let mut remaining = ApplyState {
-        timestamp: state.timestamp.clone(),
-        ..Default::default()
-    };
+fn revert_steps(
+    state: &ApplyState,
+    only: Option<RevertScope>,
+    force: bool,
+    wakeup: &mut dyn WakeupTable,
+    progress: &mut dyn ProgressSink,
+) -> ApplyState {
+    // Out-of-scope changes carry over untouched; in-scope ones are added
+    // back below only if restoring them fails.
+    let (selected, mut remaining) = partition_scope(state, only);
+    let state = &selected;
+    let ownership = Ownership::new(&state.managed_files, force);
 
     // Restore backlight brightness
     if let Some(original) = state.brightness_original {
         let sysfs = SysfsRoot::system();
-        match crate::brightness::restore(original, &sysfs) {
-            Ok(()) => {
-                println!(
-                    "  {} Restored backlight brightness to {}",
-                    ">>".cyan(),
-                    original
-                );
-            }
+        match crate::brightness::restore(original, state.brightness_device.as_deref(), &sysfs) {
+            Ok(()) => progress.emit(Progress::Section(format!(
+                "Restored backlight brightness to {}",
+                original
+            ))),
             Err(e) => {
-                eprintln!(
-                    "     {} Failed to restore backlight brightness: {}",
-                    "!".red(),
+                progress.emit(Progress::Failure(format!(
+                    "Failed to restore backlight brightness: {}",
                     e
-                );
+                )));
                 remaining.brightness_original = Some(original);
+                remaining.brightness_device = state.brightness_device.clone();
             }
         }
     }
 
     // Revert sysfs changes
     if !state.sysfs_changes.is_empty() {
-        println!("  {} Restoring sysfs values:", ">>".cyan());
+        progress.emit(Progress::Section("Restoring sysfs values:".to_string()));
         for change in &state.sysfs_changes {
-            match std::fs::write(&change.path, &change.original_value) {
-                Ok(()) => {
-                    println!(
-                        "     {} {} -> {}",
-                        change.path.dimmed(),
-                        change.new_value.red(),
-                        change.original_value.green()
-                    );
-                }
+            let restore = change.restore_value();
+            match std::fs::write(&change.path, &restore) {
+                Ok(()) => progress.emit(Progress::Step(format!(
+                    "{} {} -> {}",
+                    change.path,
+                    change.expected_value(),
+                    restore
+                ))),
                 Err(e) => {
-                    eprintln!(
-                        "     {} Failed to restore {}: {}",
-                        "!".red(),
-                        change.path,
-                        e
-                    );
+                    progress.emit(Progress::Failure(format!(
+                        "Failed to restore {}: {}",
+                        change.path, e
+                    )));
                     remaining.sysfs_changes.push(change.clone());
                 }
             }
         }
-        println!();
+        progress.emit(Progress::EndSection);
+    }
+
+    // Remove the udev rule that re-enables USB ports; the restores above
+    // already turned them back on
+    if !state.udev_files_created.is_empty() {
+        progress.emit(Progress::Section("Removing udev rules:".to_string()));
+        for path in &state.udev_files_created {
+            match ownership.remove(std::path::Path::new(path)) {
+                Ok(true) => progress.emit(Progress::Step(format!("removed {}", path))),
+                Ok(false) => progress.emit(Progress::Step(format!("already gone {}", path))),
+                Err(e) => {
+                    progress.emit(Progress::Failure(format!(
+                        "Failed to remove {}: {}",
+                        path, e
+                    )));
+                    remaining.udev_files_created.push(path.clone());
+                }
+            }
+        }
+        apply::usb_ports::reload_udev_rules();
+        progress.emit(Progress::EndSection);
+    }
+
+    // Remove the sysctl drop-in an offline root was given
+    if !state.sysctl_files_created.is_empty() {
+        progress.emit(Progress::Section("Removing sysctl drop-ins:".to_string()));
+        for path in &state.sysctl_files_created {
+            match ownership.remove(std::path::Path::new(path)) {
+                Ok(true) => progress.emit(Progress::Step(format!("removed {}", path))),
+                Ok(false) => progress.emit(Progress::Step(format!("already gone {}", path))),
+                Err(e) => {
+                    progress.emit(Progress::Failure(format!(
+                        "Failed to remove {}: {}",
+                        path, e
+                    )));
+                    remaining.sysctl_files_created.push(path.clone());
+                }
+            }
+        }
+        progress.emit(Progress::Detail(
+            "(will take effect after reboot)".to_string(),
+        ));
+        progress.emit(Progress::EndSection);
     }
 
     // Re-enable ACPI wakeup sources (toggle them back)
     if !state.acpi_wakeup_toggled.is_empty() {
-        println!("  {} Re-enabling ACPI wakeup sources:", ">>".cyan());
+        progress.emit(Progress::Section(
+            "Re-enabling ACPI wakeup sources:".to_string(),
+        ));
         for device in &state.acpi_wakeup_toggled {
-            match apply::sysfs_writer::toggle_acpi_wakeup(device) {
-                Ok(()) => println!("     {} {}", "enabled".green(), device),
+            match toggle_acpi_wakeup_in(wakeup, device, true) {
+                Ok(_) => progress.emit(Progress::Step(format!("enabled {}", device))),
                 Err(e) => {
-                    eprintln!("     {} Failed to toggle {}: {}", "!".red(), device, e);
+                    progress.emit(Progress::Failure(format!(
+                        "Failed to toggle {}: {}",
+                        device, e
+                    )));
                     remaining.acpi_wakeup_toggled.push(device.clone());
                 }
             }
         }
-        println!();
+        progress.emit(Progress::EndSection);
     }
 
     // Restore kernel params
     if !state.kernel_param_backups.is_empty() {
-        println!("  {} Restoring kernel parameter boot entries:", ">>".cyan());
+        progress.emit(Progress::Section(
+            "Restoring kernel parameter boot entries:".to_string(),
+        ));
         for backup in &state.kernel_param_backups {
-            println!("     {}", backup.path);
+            progress.emit(Progress::Step(backup.path.clone()));
         }
         match apply::kernel_params::restore_kernel_param_backups(&state.kernel_param_backups) {
-            Ok(()) => println!("     {}", "(will take effect after reboot)".dimmed()),
+            Ok(()) => progress.emit(Progress::Detail(
+                "(will take effect after reboot)".to_string(),
+            )),
             Err(e) => {
-                eprintln!("     {} Failed: {}", "!".red(), e);
+                progress.emit(Progress::Failure(format!("Failed: {}", e)));
                 remaining.kernel_param_backups = state.kernel_param_backups.clone();
+                remaining.kernel_params_removed = state.kernel_params_removed.clone();
             }
         }
-        println!();
+        progress.emit(Progress::EndSection);
     } else if !state.kernel_params_added.is_empty() {
         // Backward compatibility for state files created before backup support.
-        println!("  {} Removing kernel parameters:", ">>".cyan());
+        progress.emit(Progress::Section("Removing kernel parameters:".to_string()));
         for param in &state.kernel_params_added {
-            println!("     {}", param);
+            progress.emit(Progress::Step(param.clone()));
         }
         match apply::kernel_params::remove_kernel_params(&state.kernel_params_added) {
-            Ok(()) => println!("     {}", "(will take effect after reboot)".dimmed()),
+            Ok(()) => progress.emit(Progress::Detail(
+                "(will take effect after reboot)".to_string(),
+            )),
             Err(e) => {
-                eprintln!("     {} Failed: {}", "!".red(), e);
+                progress.emit(Progress::Failure(format!("Failed: {}", e)));
                 remaining.kernel_params_added = state.kernel_params_added.clone();
             }
         }
-        println!();
+        progress.emit(Progress::EndSection);
     }
 
     // Re-enable services
     if !state.services_disabled.is_empty() {
-        println!("  {} Re-enabling services:", ">>".cyan());
+        progress.emit(Progress::Section("Re-enabling services:".to_string()));
         for svc in &state.services_disabled {
             match apply::services::enable_service(svc) {
-                Ok(()) => println!("     {} {}", "enabled".green(), svc),
+                Ok(()) => progress.emit(Progress::Step(format!("enabled {}", svc))),
                 Err(e) => {
-                    eprintln!("     {} Failed to enable {}: {}", "!".red(), svc, e);
+                    progress.emit(Progress::Failure(format!(
+                        "Failed to enable {}: {}",
+                        svc, e
+                    )));
                     remaining.services_disabled.push(svc.clone());
+                    if let Some(at) = state.services_disabled_at.get(svc) {
+                        remaining
+                            .services_disabled_at
+                            .insert(svc.clone(), at.clone());
+                    }
                 }
             }
         }
-        println!();
+        progress.emit(Progress::EndSection);
+    }
+
+    // Remove modprobe drop-ins
+    if !state.modprobe_files_created.is_empty() {
+        progress.emit(Progress::Section("Removing module options:".to_string()));
+        for path in &state.modprobe_files_created {
+            match ownership.remove(std::path::Path::new(path)) {
+                Ok(true) => progress.emit(Progress::Step(format!("removed {}", path))),
+                Ok(false) => progress.emit(Progress::Step(format!("already gone {}", path))),
+                Err(e) => {
+                    progress.emit(Progress::Failure(format!(
+                        "Failed to remove {}: {}",
+                        path, e
+                    )));
+                    remaining.modprobe_files_created.push(path.clone());
+                }
+            }
+        }
+        progress.emit(Progress::Detail(
+            "(will take effect on next module load)".to_string(),
+        ));
+        progress.emit(Progress::EndSection);
+    }
+
+    // Remove NetworkManager drop-ins
+    if !state.wifi_files_created.is_empty() {
+        progress.emit(Progress::Section(
+            "Removing WiFi power save drop-ins:".to_string(),
+        ));
+        for path in &state.wifi_files_created {
+            match ownership.remove(std::path::Path::new(path)) {
+                Ok(true) => progress.emit(Progress::Step(format!("removed {}", path))),
+                Ok(false) => progress.emit(Progress::Step(format!("already gone {}", path))),
+                Err(e) => {
+                    progress.emit(Progress::Failure(format!(
+                        "Failed to remove {}: {}",
+                        path, e
+                    )));
+                    remaining.wifi_files_created.push(path.clone());
+                }
+            }
+        }
+        apply::wifi::reload_network_manager();
+        progress.emit(Progress::Detail(
+            "(will take effect on next connect)".to_string(),
+        ));
+        progress.emit(Progress::EndSection);
+    }
+
+    // Put back what a network exception changed
+    if let Some(exception) = &state.wifi_exception {
+        let setting = if exception.original { "on" } else { "off" };
+        if apply::wifi::set_power_save(&exception.iface, exception.original) {
+            progress.emit(Progress::Section(format!(
+                "Restored WiFi power save {} on {}",
+                setting, exception.iface
+            )));
+        } else {
+            progress.emit(Progress::Failure(format!(
+                "Failed to restore WiFi power save {} on {}",
+                setting, exception.iface
+            )));
+            remaining.wifi_exception = Some(exception.clone());
+        }
     }
 
     // Remove systemd units
     if !state.systemd_units_created.is_empty() {
-        println!("  {} Removing systemd units:", ">>".cyan());
-        match apply::systemd::remove_service() {
+        progress.emit(Progress::Section("Removing systemd units:".to_string()));
+        match apply::systemd::remove_service(&ownership) {
             Ok(()) => {
                 for unit in &state.systemd_units_created {
-                    println!("     {} {}", "removed".green(), unit);
+                    progress.emit(Progress::Step(format!("removed {}", unit)));
                 }
             }
             Err(e) => {
-                eprintln!("     {} Failed: {}", "!".red(), e);
+                progress.emit(Progress::Failure(format!("Failed: {}", e)));
                 remaining.systemd_units_created = state.systemd_units_created.clone();
             }
         }
-        println!();
+        progress.emit(Progress::EndSection);
     }
 
+    // Manifest entries follow the files still recorded
+    remaining.managed_files.extend(
+        state
+            .managed_files
+            .iter()
+            .filter(|m| {
+                remaining.modprobe_files_created.contains(&m.path)
+                    || remaining.wifi_files_created.contains(&m.path)
+                    || remaining.udev_files_created.contains(&m.path)
+                    || remaining.sysctl_files_created.contains(&m.path)
+                    || remaining.systemd_units_created.contains(&m.path)
+            })
+            .cloned(),
+    );
+
     remaining
 }
 
 #[cfg(test)]
 mod tests {
-    use super::revert_loaded_state;
-    use crate::apply::{ApplyState, SysfsChange, sysfs_writer};
+    use super::{RevertScope, revert_loaded_state};
+    use crate::apply::sysfs_writer::{MockWakeupTable, ProcAcpiWakeup};
+    use crate::apply::{ApplyState, SysfsChange, wifi};
+    use crate::managed;
+    use crate::progress::{Progress, Quiet};
     use std::fs;
     use std::path::PathBuf;
     use std::sync::{LazyLock, Mutex};
@@ -252,10 +562,6 @@ mod tests {
         StateFileOverrideGuard
     }
 
-    fn set_acpi_wakeup_path_override(path: PathBuf) -> sysfs_writer::AcpiWakeupPathGuard {
-        sysfs_writer::set_acpi_wakeup_path_override_for_tests(path)
-    }
-
     #[test]
     fn test_revert_keeps_state_when_a_restore_step_fails() {
         let _test_guard = TEST_LOCK.lock().expect("test lock poisoned");
@@ -273,16 +579,12 @@ mod tests {
         let state = ApplyState {
             timestamp: "2026-02-18T00:00:00Z".to_string(),
             sysfs_changes: vec![
-                SysfsChange {
-                    path: ok_path.to_string_lossy().into_owned(),
-                    original_value: "old-value".to_string(),
-                    new_value: "new-value".to_string(),
-                },
-                SysfsChange {
-                    path: failing_path_str.clone(),
-                    original_value: "old-fail".to_string(),
-                    new_value: "new-fail".to_string(),
-                },
+                SysfsChange::new(
+                    ok_path.to_string_lossy().into_owned(),
+                    "old-value",
+                    "new-value",
+                ),
+                SysfsChange::new(failing_path_str.clone(), "old-fail", "new-fail"),
             ],
             ..Default::default()
         };
@@ -290,7 +592,9 @@ mod tests {
         state.save().expect("failed to save state");
         assert!(state_path.exists(), "state file should be created");
 
-        let all_succeeded = revert_loaded_state(&state).expect("revert execution failed");
+        let all_succeeded =
+            revert_loaded_state(&state, None, false, false, &mut ProcAcpiWakeup, &mut Quiet)
+                .expect("revert execution failed");
         assert!(
             !all_succeeded,
             "revert should report partial failure when one restore step fails"
@@ -324,9 +628,10 @@ mod tests {
         let state_path = tmp.path().join("state.json");
         let _state_override = set_state_file_override(state_path.clone());
 
-        let acpi_wakeup_path = tmp.path().join("acpi-wakeup");
-        let _acpi_override = set_acpi_wakeup_path_override(acpi_wakeup_path.clone());
-        fs::write(&acpi_wakeup_path, "").expect("failed to seed acpi wakeup mock");
+        let mut wakeup = MockWakeupTable::new(
+            "Device\tS-state\t  Status   Sysfs node\n\
+             XHC0\t  S3\t*disabled  pci:0000:c1:00.3\n",
+        );
 
         let missing_parent = tmp.path().join("missing");
         let failing_path = missing_parent.join("restore-fail");
@@ -334,11 +639,11 @@ mod tests {
 
         let state = ApplyState {
             timestamp: "2026-02-18T00:00:00Z".to_string(),
-            sysfs_changes: vec![SysfsChange {
-                path: failing_path_str.clone(),
-                original_value: "old-fail".to_string(),
-                new_value: "new-fail".to_string(),
-            }],
+            sysfs_changes: vec![SysfsChange::new(
+                failing_path_str.clone(),
+                "old-fail",
+                "new-fail",
+            )],
             acpi_wakeup_toggled: vec!["XHC0".to_string()],
             ..Default::default()
         };
@@ -346,7 +651,9 @@ mod tests {
         state.save().expect("failed to save state");
         assert!(state_path.exists(), "state file should be created");
 
-        let all_succeeded = revert_loaded_state(&state).expect("revert execution failed");
+        let all_succeeded =
+            revert_loaded_state(&state, None, false, false, &mut wakeup, &mut Quiet)
+                .expect("revert execution failed");
         assert!(
             !all_succeeded,
             "revert should report partial failure when any restore step fails"
@@ -368,13 +675,97 @@ mod tests {
             remaining.sysfs_changes[0].path, failing_path_str,
             "the failed sysfs path should stay in persisted state"
         );
+        assert_eq!(wakeup.enabled("XHC0"), Some(true));
         assert_eq!(
-            fs::read_to_string(&acpi_wakeup_path).expect("failed to read acpi wakeup mock"),
-            "XHC0",
+            wakeup.writes,
+            vec!["XHC0".to_string()],
             "ACPI wakeup toggle should run once and not be retained in state"
         );
     }
 
+    #[test]
+    fn test_revert_keeps_acpi_source_whose_toggle_does_not_stick() {
+        let _test_guard = TEST_LOCK.lock().expect("test lock poisoned");
+        let tmp = TempDir::new().expect("failed to create temp dir");
+        let state_path = tmp.path().join("state.json");
+        let _state_override = set_state_file_override(state_path.clone());
+
+        let mut wakeup = MockWakeupTable::new(
+            "XHC1\tS3\t*disabled\tpci:0000:c1:00.4\n\
+             XHC10\tS3\t*enabled\tpci:0000:c4:00.3\n",
+        );
+        wakeup.misdirected_writes = 2;
+
+        let state = ApplyState {
+            timestamp: "2026-02-18T00:00:00Z".to_string(),
+            acpi_wakeup_toggled: vec!["XHC1".to_string()],
+            ..Default::default()
+        };
+        state.save().expect("failed to save state");
+
+        assert!(!revert_loaded_state(&state, None, false, false, &mut wakeup, &mut Quiet).unwrap());
+        let remaining = ApplyState::load().unwrap().unwrap();
+        assert_eq!(remaining.acpi_wakeup_toggled, ["XHC1"]);
+        assert_eq!(wakeup.writes, ["XHC1", "XHC1"]);
+    }
+
+    #[test]
+    fn test_revert_reports_steps_to_progress_sink() {
+        let _test_guard = TEST_LOCK.lock().expect("test lock poisoned");
+        let tmp = TempDir::new().expect("failed to create temp dir");
+        let _state_override = set_state_file_override(tmp.path().join("state.json"));
+
+        let path = tmp.path().join("value");
+        fs::write(&path, "new").unwrap();
+        let path = path.to_string_lossy().into_owned();
+        let state = ApplyState {
+            sysfs_changes: vec![SysfsChange::new(path.clone(), "old", "new")],
+            ..Default::default()
+        };
+
+        let mut events = Vec::new();
+        assert!(
+            revert_loaded_state(&state, None, false, false, &mut ProcAcpiWakeup, &mut events)
+                .unwrap()
+        );
+        assert_eq!(
+            events,
+            vec![
+                Progress::Section("Restoring sysfs values:".to_string()),
+                Progress::Step(format!("{} new -> old", path)),
+                Progress::EndSection,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_revert_writes_selected_choice_not_raw_list() {
+        let _test_guard = TEST_LOCK.lock().expect("test lock poisoned");
+        let tmp = TempDir::new().expect("failed to create temp dir");
+        let _state_override = set_state_file_override(tmp.path().join("state.json"));
+
+        let mem_sleep = tmp.path().join("mem_sleep");
+        fs::write(&mem_sleep, "deep").unwrap();
+
+        let change = SysfsChange::new(
+            mem_sleep.to_string_lossy().into_owned(),
+            "[s2idle] deep\n",
+            "deep",
+        );
+        assert_eq!(change.original_value, "[s2idle] deep\n", "raw kept as read");
+        assert_eq!(change.original_normalized, "s2idle");
+
+        let state = ApplyState {
+            sysfs_changes: vec![change],
+            ..Default::default()
+        };
+        assert!(
+            revert_loaded_state(&state, None, false, false, &mut ProcAcpiWakeup, &mut Quiet)
+                .unwrap()
+        );
+        assert_eq!(fs::read_to_string(&mem_sleep).unwrap(), "s2idle");
+    }
+
     #[test]
     fn test_revert_removes_state_when_all_steps_succeed() {
         let _test_guard = TEST_LOCK.lock().expect("test lock poisoned");
@@ -387,18 +778,20 @@ mod tests {
 
         let state = ApplyState {
             timestamp: "2026-02-18T00:00:00Z".to_string(),
-            sysfs_changes: vec![SysfsChange {
-                path: restored_path.to_string_lossy().into_owned(),
-                original_value: "old-value".to_string(),
-                new_value: "new-value".to_string(),
-            }],
+            sysfs_changes: vec![SysfsChange::new(
+                restored_path.to_string_lossy().into_owned(),
+                "old-value",
+                "new-value",
+            )],
             ..Default::default()
         };
 
         state.save().expect("failed to save state");
         assert!(state_path.exists(), "state file should be created");
 
-        let all_succeeded = revert_loaded_state(&state).expect("revert execution failed");
+        let all_succeeded =
+            revert_loaded_state(&state, None, false, false, &mut ProcAcpiWakeup, &mut Quiet)
+                .expect("revert execution failed");
         assert!(
             all_succeeded,
             "revert should succeed when all steps succeed"
@@ -412,4 +805,221 @@ mod tests {
             "old-value"
         );
     }
+
+    #[test]
+    fn test_revert_before_reapply_keeps_pending_rollback() {
+        let _test_guard = TEST_LOCK.lock().expect("test lock poisoned");
+        let tmp = TempDir::new().expect("failed to create temp dir");
+        let state_path = tmp.path().join("state.json");
+        let _state_override = set_state_file_override(state_path.clone());
+
+        let restored_path = tmp.path().join("restore-ok");
+        fs::write(&restored_path, "new-value").expect("failed to seed writable sysfs mock");
+        let pending = crate::apply::rollback::PendingRollback {
+            unit: "bop-rollback".to_string(),
+            due: "2026-02-18T00:30:00+00:00".to_string(),
+            boot_id: None,
+        };
+        let state = ApplyState {
+            timestamp: "2026-02-18T00:00:00Z".to_string(),
+            sysfs_changes: vec![SysfsChange::new(
+                restored_path.to_string_lossy().into_owned(),
+                "old-value",
+                "new-value",
+            )],
+            rollback: Some(pending.clone()),
+            ..Default::default()
+        };
+        state.save().expect("failed to save state");
+
+        assert!(
+            revert_loaded_state(&state, None, false, true, &mut ProcAcpiWakeup, &mut Quiet)
+                .expect("revert execution failed")
+        );
+        let remaining = ApplyState::load()
+            .expect("failed to load remaining state")
+            .expect("the pending rollback should stay recorded");
+        assert!(remaining.sysfs_changes.is_empty());
+        assert_eq!(remaining.rollback, Some(pending));
+
+        // A plain revert leaves nothing for the timer
+        state.save().expect("failed to save state");
+        assert!(
+            revert_loaded_state(&state, None, false, false, &mut ProcAcpiWakeup, &mut Quiet)
+                .expect("revert execution failed")
+        );
+        assert!(!state_path.exists());
+    }
+
+    #[test]
+    fn test_revert_only_scope_keeps_other_changes_recorded() {
+        let _test_guard = TEST_LOCK.lock().expect("test lock poisoned");
+        let tmp = TempDir::new().expect("failed to create temp dir");
+        let state_path = tmp.path().join("state.json");
+        let _state_override = set_state_file_override(state_path.clone());
+
+        let sysfs_path = tmp.path().join("value");
+        fs::write(&sysfs_path, "new").unwrap();
+
+        let state = ApplyState {
+            timestamp: "2026-02-18T00:00:00Z".to_string(),
+            sysfs_changes: vec![SysfsChange::new(
+                sysfs_path.to_string_lossy().into_owned(),
+                "old",
+                "new",
+            )],
+            services_disabled: vec!["tlp.service".to_string()],
+            ..Default::default()
+        };
+        state.save().unwrap();
+
+        let succeeded = revert_loaded_state(
+            &state,
+            Some(RevertScope::Sysfs),
+            false,
+            false,
+            &mut ProcAcpiWakeup,
+            &mut Quiet,
+        )
+        .unwrap();
+        assert!(succeeded, "every in-scope change was reverted");
+        assert_eq!(fs::read_to_string(&sysfs_path).unwrap(), "old");
+
+        let remaining = ApplyState::load().unwrap().expect("state kept");
+        assert!(remaining.sysfs_changes.is_empty());
+        assert_eq!(remaining.services_disabled, vec!["tlp.service".to_string()]);
+        assert_eq!(remaining.timestamp, state.timestamp);
+    }
+
+    #[test]
+    fn test_revert_only_scope_with_nothing_else_removes_state() {
+        let _test_guard = TEST_LOCK.lock().expect("test lock poisoned");
+        let tmp = TempDir::new().expect("failed to create temp dir");
+        let state_path = tmp.path().join("state.json");
+        let _state_override = set_state_file_override(state_path.clone());
+
+        let sysfs_path = tmp.path().join("value");
+        fs::write(&sysfs_path, "new").unwrap();
+        let state = ApplyState {
+            sysfs_changes: vec![SysfsChange::new(
+                sysfs_path.to_string_lossy().into_owned(),
+                "old",
+                "new",
+            )],
+            ..Default::default()
+        };
+        state.save().unwrap();
+
+        assert!(
+            revert_loaded_state(
+                &state,
+                Some(RevertScope::Sysfs),
+                false,
+                false,
+                &mut ProcAcpiWakeup,
+                &mut Quiet
+            )
+            .unwrap()
+        );
+        assert!(!state_path.exists());
+    }
+
+    #[test]
+    fn test_revert_keeps_modprobe_files_bop_no_longer_owns() {
+        let _test_guard = TEST_LOCK.lock().expect("test lock poisoned");
+        let tmp = TempDir::new().expect("failed to create temp dir");
+        let state_path = tmp.path().join("state.json");
+        let _state_override = set_state_file_override(state_path.clone());
+
+        // Written by an older bop: legacy header, no manifest entry
+        let legacy = tmp.path().join("bop-rtsx_pci.conf");
+        fs::write(
+            &legacy,
+            "# Generated by bop -- removed by `bop revert`\noptions rtsx_pci enable_runtime_pm=1\n",
+        )
+        .unwrap();
+        // Since replaced by the user's own file of the same name
+        let replaced = tmp.path().join("bop-snd_hda_intel.conf");
+        let ours = format!(
+            "{}\noptions snd_hda_intel power_save=1\n",
+            managed::header()
+        );
+        fs::write(&replaced, "options snd_hda_intel power_save=0\n").unwrap();
+
+        let paths = [&legacy, &replaced].map(|p| p.to_string_lossy().into_owned());
+        let state = ApplyState {
+            modprobe_files_created: paths.to_vec(),
+            managed_files: vec![managed::ManagedFile::new(&paths[1], &ours)],
+            ..Default::default()
+        };
+        state.save().unwrap();
+
+        assert!(
+            !revert_loaded_state(&state, None, false, false, &mut ProcAcpiWakeup, &mut Quiet)
+                .unwrap()
+        );
+        assert!(!legacy.exists());
+        assert_eq!(
+            fs::read_to_string(&replaced).unwrap(),
+            "options snd_hda_intel power_save=0\n"
+        );
+        let remaining = ApplyState::load().unwrap().expect("state kept");
+        assert_eq!(remaining.modprobe_files_created, vec![paths[1].clone()]);
+        assert_eq!(remaining.managed_files, state.managed_files);
+
+        // --force deletes it regardless
+        assert!(
+            revert_loaded_state(
+                &remaining,
+                None,
+                true,
+                false,
+                &mut ProcAcpiWakeup,
+                &mut Quiet
+            )
+            .unwrap()
+        );
+        assert!(!replaced.exists());
+        assert!(!state_path.exists());
+    }
+
+    #[test]
+    fn test_revert_only_wifi_removes_drop_in() {
+        let _test_guard = TEST_LOCK.lock().expect("test lock poisoned");
+        let tmp = TempDir::new().expect("failed to create temp dir");
+        let state_path = tmp.path().join("state.json");
+        let _state_override = set_state_file_override(state_path.clone());
+
+        let drop_in = tmp.path().join("bop-wifi-powersave.conf");
+        let content = wifi::nm_drop_in();
+        fs::write(&drop_in, &content).unwrap();
+        let path = drop_in.to_string_lossy().into_owned();
+
+        let state = ApplyState {
+            wifi_files_created: vec![path.clone()],
+            services_disabled: vec!["tlp.service".to_string()],
+            managed_files: vec![managed::ManagedFile::new(&path, &content)],
+            ..Default::default()
+        };
+        state.save().unwrap();
+
+        assert!(
+            revert_loaded_state(
+                &state,
+                Some(RevertScope::Wifi),
+                false,
+                false,
+                &mut ProcAcpiWakeup,
+                &mut Quiet
+            )
+            .unwrap()
+        );
+        assert!(!drop_in.exists());
+        let remaining = ApplyState::load()
+            .unwrap()
+            .expect("services still recorded");
+        assert!(remaining.wifi_files_created.is_empty());
+        assert!(remaining.managed_files.is_empty());
+        assert_eq!(remaining.services_disabled, vec!["tlp.service".to_string()]);
+    }
 }
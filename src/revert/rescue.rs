@@ -0,0 +1,602 @@
+//! `bop rescue`: undo bop's changes to a system that no longer boots, from a
+//! live USB or recovery shell with its root filesystem mounted somewhere
+//! (e.g. /mnt). Every path goes through [`OfflineRoot`], so absolute
+//! symlinks in the mounted tree lead back into it instead of into the
+//! rescue system.
+
+use super::{RevertScope, has_pending_reverts, partition_scope};
+use crate::apply::offline::OfflineRoot;
+use crate::apply::{ApplyState, STATE_FILE, kernel_params, systemd};
+use crate::error::{Error, Result};
+use crate::managed::Ownership;
+use crate::progress::{Progress, ProgressSink};
+use std::path::PathBuf;
+
+/// One line of a [`BackupDiff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    /// In both the current file and the backup
+    Same(String),
+    /// Only in the current file; restoring drops it
+    Removed(String),
+    /// Only in the backup; restoring brings it back
+    Added(String),
+}
+
+/// A recorded boot entry backup next to what the file holds now.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupDiff {
+    /// Path as the rescued system sees it
+    pub path: String,
+    /// Where that is under the mount point
+    pub host_path: PathBuf,
+    /// None if the file is missing under the root
+    pub current: Option<String>,
+    pub lines: Vec<DiffLine>,
+}
+
+impl BackupDiff {
+    /// Whether the file already matches the backup.
+    pub fn is_unchanged(&self) -> bool {
+        self.current.is_some() && self.lines.iter().all(|l| matches!(l, DiffLine::Same(_)))
+    }
+}
+
+/// A mounted system and the state bop recorded on it.
+#[derive(Debug, Clone)]
+pub struct Rescue {
+    root: OfflineRoot,
+    state: ApplyState,
+}
+
+impl Rescue {
+    /// Load the state file under `root`; None if nothing is recorded there.
+    pub fn load(root: OfflineRoot) -> Result<Option<Self>> {
+        let state = ApplyState::load_from(&root.resolve(STATE_FILE)?)?;
+        Ok(state.map(|state| Self { root, state }))
+    }
+
+    pub fn root(&self) -> &OfflineRoot {
+        &self.root
+    }
+
+    pub fn state(&self) -> &ApplyState {
+        &self.state
+    }
+
+    /// Each kernel param backup diffed against the file under the root.
+    pub fn backup_diffs(&self) -> Result<Vec<BackupDiff>> {
+        self.state
+            .kernel_param_backups
+            .iter()
+            .map(|backup| {
+                let host_path = self.root.resolve(&backup.path)?;
+                let current = std::fs::read_to_string(&host_path).ok();
+                let lines = line_diff(current.as_deref().unwrap_or(""), &backup.original_content);
+                Ok(BackupDiff {
+                    path: backup.path.clone(),
+                    host_path,
+                    current,
+                    lines,
+                })
+            })
+            .collect()
+    }
+
+    /// Restore the kernel params, or with `all` undo everything the mounted
+    /// system has recorded, then save what's left to its state file (or
+    /// delete it). Returns true if everything attempted was undone.
+    pub fn run(&self, all: bool, force: bool, progress: &mut dyn ProgressSink) -> Result<bool> {
+        let only = (!all).then_some(RevertScope::KernelParams);
+        let (selected, mut remaining) = partition_scope(&self.state, only);
+        let state = &selected;
+
+        self.restore_kernel_params(state, &mut remaining, progress);
+        if all {
+            self.revert_persistent(state, &mut remaining, force, progress);
+        }
+
+        let state_path = self.root.resolve(STATE_FILE)?;
+        if has_pending_reverts(&remaining) {
+            remaining.save_to(&state_path)?;
+        } else if state_path.exists() {
+            std::fs::remove_file(&state_path)
+                .map_err(|e| Error::State(format!("failed to remove state file: {}", e)))?;
+        }
+        Ok(!has_pending_reverts(&partition_scope(&remaining, only).0))
+    }
+
+    fn restore_kernel_params(
+        &self,
+        state: &ApplyState,
+        remaining: &mut ApplyState,
+        progress: &mut dyn ProgressSink,
+    ) {
+        if !state.kernel_param_backups.is_empty() {
+            progress.emit(Progress::Section(
+                "Restoring kernel parameter boot entries:".to_string(),
+            ));
+            for backup in &state.kernel_param_backups {
+                progress.emit(Progress::Step(backup.path.clone()));
+            }
+            match kernel_params::restore_kernel_param_backups_offline(
+                &state.kernel_param_backups,
+                &self.root,
+            ) {
+                Ok(()) => {
+                    if state
+                        .kernel_param_backups
+                        .iter()
+                        .any(|b| b.path == kernel_params::GRUB_DEFAULT)
+                    {
+                        progress.emit(Progress::Detail(format!(
+                            "(run grub-mkconfig in a chroot into {} to rebuild grub.cfg)",
+                            self.root.path().display()
+                        )));
+                    }
+                }
+                Err(e) => {
+                    progress.emit(Progress::Failure(format!("Failed: {}", e)));
+                    remaining.kernel_param_backups = state.kernel_param_backups.clone();
+                    remaining.kernel_params_removed = state.kernel_params_removed.clone();
+                }
+            }
+            progress.emit(Progress::EndSection);
+        } else if !state.kernel_params_added.is_empty() {
+            // State files from before backup support only name the params
+            progress.emit(Progress::Section("Removing kernel parameters:".to_string()));
+            for param in &state.kernel_params_added {
+                progress.emit(Progress::Step(param.clone()));
+            }
+            if let Err(e) = kernel_params::edit_kernel_params_offline(
+                &[],
+                &state.kernel_params_added,
+                &self.root,
+            ) {
+                progress.emit(Progress::Failure(format!("Failed: {}", e)));
+                remaining.kernel_params_added = state.kernel_params_added.clone();
+            }
+            progress.emit(Progress::EndSection);
+        }
+    }
+
+    /// Everything but the kernel params. Runtime changes (sysfs values, ACPI
+    /// wakeup, brightness) ended with the boot that made them; once the
+    /// persistence unit is gone nothing brings them back, so they are only
+    /// dropped from the state.
+    fn revert_persistent(
+        &self,
+        state: &ApplyState,
+        remaining: &mut ApplyState,
+        force: bool,
+        progress: &mut dyn ProgressSink,
+    ) {
+        let ownership = Ownership::resolved(&state.managed_files, force, |path| {
+            self.root.resolve(path).ok()
+        });
+
+        if !state.services_disabled.is_empty() {
+            progress.emit(Progress::Section("Re-enabling services:".to_string()));
+            for svc in &state.services_disabled {
+                match self.enable_service(svc) {
+                    Ok(()) => progress.emit(Progress::Step(format!("enabled {}", svc))),
+                    Err(e) => {
+                        progress.emit(Progress::Failure(format!(
+                            "Failed to enable {}: {}",
+                            svc, e
+                        )));
+                        remaining.services_disabled.push(svc.clone());
+                        if let Some(at) = state.services_disabled_at.get(svc) {
+                            remaining
+                                .services_disabled_at
+                                .insert(svc.clone(), at.clone());
+                        }
+                    }
+                }
+            }
+            progress.emit(Progress::EndSection);
+        }
+
+        let files = [
+            (
+                "Removing module options:",
+                &state.modprobe_files_created,
+                &mut remaining.modprobe_files_created,
+            ),
+            (
+                "Removing WiFi power save drop-ins:",
+                &state.wifi_files_created,
+                &mut remaining.wifi_files_created,
+            ),
+            (
+                "Removing udev rules:",
+                &state.udev_files_created,
+                &mut remaining.udev_files_created,
+            ),
+            (
+                "Removing sysctl drop-ins:",
+                &state.sysctl_files_created,
+                &mut remaining.sysctl_files_created,
+            ),
+            (
+                "Removing systemd units:",
+                &state.systemd_units_created,
+                &mut remaining.systemd_units_created,
+            ),
+        ];
+        for (section, created, kept) in files {
+            if created.is_empty() {
+                continue;
+            }
+            progress.emit(Progress::Section(section.to_string()));
+            for path in created {
+                match self
+                    .root
+                    .resolve(path)
+                    .and_then(|host| ownership.remove(&host))
+                {
+                    Ok(true) => progress.emit(Progress::Step(format!("removed {}", path))),
+                    Ok(false) => progress.emit(Progress::Step(format!("already gone {}", path))),
+                    Err(e) => {
+                        progress.emit(Progress::Failure(format!(
+                            "Failed to remove {}: {}",
+                            path, e
+                        )));
+                        kept.push(path.clone());
+                    }
+                }
+            }
+            progress.emit(Progress::EndSection);
+        }
+
+        // The unit's enablement link, once the unit itself is gone
+        if !remaining
+            .systemd_units_created
+            .iter()
+            .any(|u| u == systemd::SERVICE_PATH)
+            && let Ok(wants) = self.root.resolve(systemd::WANTS_DIR)
+        {
+            let link = wants.join(systemd::SERVICE_NAME);
+            if link.is_symlink() {
+                let _ = std::fs::remove_file(&link);
+            }
+        }
+
+        remaining.managed_files.extend(
+            state
+                .managed_files
+                .iter()
+                .filter(|m| {
+                    remaining.modprobe_files_created.contains(&m.path)
+                        || remaining.wifi_files_created.contains(&m.path)
+                        || remaining.udev_files_created.contains(&m.path)
+                        || remaining.sysctl_files_created.contains(&m.path)
+                        || remaining.systemd_units_created.contains(&m.path)
+                })
+                .cloned(),
+        );
+
+        let runtime = state.sysfs_changes.len()
+            + state.acpi_wakeup_toggled.len()
+            + usize::from(state.brightness_original.is_some());
+        if runtime > 0 {
+            progress.emit(Progress::Info(format!(
+                "Dropped {} runtime change(s); they ended with the last boot.",
+                runtime
+            )));
+        }
+    }
+
+    /// `systemctl --root` edits the unit links in the mounted tree without
+    /// a running systemd.
+    fn enable_service(&self, service: &str) -> Result<()> {
+        let root = format!("--root={}", self.root.path().display());
+        let _ = crate::cmd::Cmd::new("systemctl")
+            .arg(&root)
+            .args(["unmask", service])
+            .status();
+        let status = crate::cmd::Cmd::new("systemctl")
+            .arg(&root)
+            .args(["enable", service])
+            .status()?;
+        if !status.success() {
+            return Err(Error::Other(format!("systemctl enable {} failed", service)));
+        }
+        Ok(())
+    }
+}
+
+/// Line diff taking `current` to `original`, by longest common subsequence.
+/// Boot entries are a few lines, so the quadratic table is fine.
+pub fn line_diff(current: &str, original: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = current.lines().collect();
+    let new: Vec<&str> = original.lines().collect();
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply::kernel_params::KernelParamBackup;
+    use crate::apply::{MODPROBE_DIR, SysfsChange};
+    use crate::managed::ManagedFile;
+    use crate::progress::Quiet;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    const LINUX_ORIGINAL: &str =
+        "title Arch Linux\nlinux /vmlinuz-linux\noptions root=UUID=abc rw\n";
+    const LINUX_BROKEN: &str = "title Arch Linux\nlinux /vmlinuz-linux\noptions root=UUID=abc rw amdgpu.dcdebugmask=0x410\n";
+    const LTS_ORIGINAL: &str =
+        "title Arch Linux (LTS)\nlinux /vmlinuz-linux-lts\noptions root=UUID=abc rw\n";
+
+    /// A system mounted for rescue: /boot is an absolute symlink to /efi,
+    /// as when the ESP is mounted there, and both entries carry bop's
+    /// parameter while the state file holds their backups.
+    fn mounted_root(extra: impl FnOnce(&Path, &mut ApplyState)) -> (TempDir, OfflineRoot) {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        fs::create_dir_all(base.join("efi/loader/entries")).unwrap();
+        symlink("/efi", base.join("boot")).unwrap();
+        fs::write(base.join("efi/loader/entries/arch.conf"), LINUX_BROKEN).unwrap();
+        fs::write(
+            base.join("efi/loader/entries/arch-lts.conf"),
+            LTS_ORIGINAL.replace(" rw\n", " rw amdgpu.dcdebugmask=0x410\n"),
+        )
+        .unwrap();
+
+        let mut state = ApplyState {
+            timestamp: "2026-10-14T09:00:00Z".to_string(),
+            kernel_params_added: vec!["amdgpu.dcdebugmask=0x410".to_string()],
+            kernel_param_backups: vec![
+                KernelParamBackup {
+                    path: "/boot/loader/entries/arch.conf".to_string(),
+                    original_content: LINUX_ORIGINAL.to_string(),
+                },
+                KernelParamBackup {
+                    path: "/boot/loader/entries/arch-lts.conf".to_string(),
+                    original_content: LTS_ORIGINAL.to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+        extra(base, &mut state);
+        state.save_to(&base.join("var/lib/bop/state.json")).unwrap();
+        let root = OfflineRoot::new(base).unwrap();
+        (tmp, root)
+    }
+
+    #[test]
+    fn test_rescue_lists_backups_with_diffs() {
+        let (tmp, root) = mounted_root(|_, _| {});
+        let rescue = Rescue::load(root).unwrap().unwrap();
+        let diffs = rescue.backup_diffs().unwrap();
+
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].path, "/boot/loader/entries/arch.conf");
+        // Found through the ESP link inside the mount, not the host's /boot
+        assert_eq!(
+            diffs[0].host_path,
+            tmp.path()
+                .canonicalize()
+                .unwrap()
+                .join("efi/loader/entries/arch.conf")
+        );
+        assert_eq!(diffs[0].current.as_deref(), Some(LINUX_BROKEN));
+        assert!(!diffs[0].is_unchanged());
+        assert!(diffs[0].lines.contains(&DiffLine::Removed(
+            "options root=UUID=abc rw amdgpu.dcdebugmask=0x410".to_string()
+        )));
+        assert!(
+            diffs[0]
+                .lines
+                .contains(&DiffLine::Added("options root=UUID=abc rw".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_rescue_restores_boot_entries_in_mounted_root() {
+        let (tmp, root) = mounted_root(|_, _| {});
+        let base = root.path().to_path_buf();
+        let rescue = Rescue::load(root).unwrap().unwrap();
+
+        assert!(rescue.run(false, false, &mut Quiet).unwrap());
+        assert_eq!(
+            fs::read_to_string(base.join("efi/loader/entries/arch.conf")).unwrap(),
+            LINUX_ORIGINAL
+        );
+        assert_eq!(
+            fs::read_to_string(base.join("efi/loader/entries/arch-lts.conf")).unwrap(),
+            LTS_ORIGINAL
+        );
+        // The symlink was followed, not replaced
+        assert!(base.join("boot").is_symlink());
+        // Nothing else was recorded, so the state file goes
+        assert!(!tmp.path().join("var/lib/bop/state.json").exists());
+        assert!(
+            rescue
+                .backup_diffs()
+                .unwrap()
+                .iter()
+                .all(|d| d.is_unchanged())
+        );
+    }
+
+    #[test]
+    fn test_rescue_without_mounted_esp_keeps_state() {
+        let (tmp, root) = mounted_root(|base, _| {
+            fs::remove_dir_all(base.join("efi/loader")).unwrap();
+        });
+        let rescue = Rescue::load(root).unwrap().unwrap();
+        assert!(rescue.backup_diffs().unwrap()[0].current.is_none());
+
+        let mut events = Vec::new();
+        assert!(!rescue.run(false, false, &mut events).unwrap());
+        assert!(
+            events
+                .iter()
+                .any(|e| matches!(e, Progress::Failure(text) if text.contains("mounted")))
+        );
+        // Not recreated on the empty mount point
+        assert!(!tmp.path().join("efi/loader/entries/arch.conf").exists());
+        let kept = ApplyState::load_from(&tmp.path().join("var/lib/bop/state.json"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(kept.kernel_param_backups.len(), 2);
+    }
+
+    fn with_generated_files(base: &Path, state: &mut ApplyState) {
+        let modprobe = format!("{}/bop-rtsx_pci.conf", MODPROBE_DIR);
+        let content = format!(
+            "{}\noptions rtsx_pci enable_runtime_pm=1\n",
+            crate::managed::header()
+        );
+        fs::create_dir_all(base.join("etc/modprobe.d")).unwrap();
+        fs::write(base.join(modprobe.trim_start_matches('/')), &content).unwrap();
+        let wants = base.join(systemd::WANTS_DIR.trim_start_matches('/'));
+        fs::create_dir_all(&wants).unwrap();
+        fs::write(
+            base.join(systemd::SERVICE_PATH.trim_start_matches('/')),
+            format!("{}\n[Unit]\n", crate::managed::header()),
+        )
+        .unwrap();
+        symlink(systemd::SERVICE_PATH, wants.join(systemd::SERVICE_NAME)).unwrap();
+
+        state.managed_files = vec![ManagedFile::new(modprobe.clone(), &content)];
+        state.modprobe_files_created = vec![modprobe];
+        state.systemd_units_created = vec![systemd::SERVICE_PATH.to_string()];
+        state.sysfs_changes = vec![SysfsChange::new(
+            "/sys/module/pcie_aspm/parameters/policy",
+            "default",
+            "powersupersave",
+        )];
+    }
+
+    #[test]
+    fn test_rescue_kernel_params_only_keeps_other_changes() {
+        let (tmp, root) = mounted_root(with_generated_files);
+        let rescue = Rescue::load(root).unwrap().unwrap();
+
+        assert!(rescue.run(false, false, &mut Quiet).unwrap());
+        let kept = ApplyState::load_from(&tmp.path().join("var/lib/bop/state.json"))
+            .unwrap()
+            .unwrap();
+        assert!(kept.kernel_param_backups.is_empty());
+        assert!(kept.kernel_params_added.is_empty());
+        assert_eq!(kept.modprobe_files_created.len(), 1);
+        assert_eq!(kept.sysfs_changes.len(), 1);
+        assert!(tmp.path().join("etc/modprobe.d/bop-rtsx_pci.conf").exists());
+    }
+
+    #[test]
+    fn test_rescue_all_removes_generated_files() {
+        let (tmp, root) = mounted_root(with_generated_files);
+        let base = root.path().to_path_buf();
+        let rescue = Rescue::load(root).unwrap().unwrap();
+
+        assert!(rescue.run(true, false, &mut Quiet).unwrap());
+        assert!(!base.join("etc/modprobe.d/bop-rtsx_pci.conf").exists());
+        assert!(
+            !base
+                .join("etc/systemd/system/bop-powersave.service")
+                .exists()
+        );
+        assert!(
+            !base
+                .join("etc/systemd/system/multi-user.target.wants/bop-powersave.service")
+                .is_symlink()
+        );
+        assert_eq!(
+            fs::read_to_string(base.join("efi/loader/entries/arch.conf")).unwrap(),
+            LINUX_ORIGINAL
+        );
+        assert!(!tmp.path().join("var/lib/bop/state.json").exists());
+    }
+
+    #[test]
+    fn test_rescue_all_keeps_foreign_files() {
+        let (tmp, root) = mounted_root(|base, state| {
+            with_generated_files(base, state);
+            // Someone replaced bop's drop-in with their own
+            fs::write(
+                base.join("etc/modprobe.d/bop-rtsx_pci.conf"),
+                "options rtsx_pci enable_runtime_pm=0\n",
+            )
+            .unwrap();
+        });
+        let rescue = Rescue::load(root).unwrap().unwrap();
+
+        assert!(!rescue.run(true, false, &mut Quiet).unwrap());
+        assert!(tmp.path().join("etc/modprobe.d/bop-rtsx_pci.conf").exists());
+        let kept = ApplyState::load_from(&tmp.path().join("var/lib/bop/state.json"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(kept.modprobe_files_created.len(), 1);
+        assert!(kept.systemd_units_created.is_empty());
+        assert!(kept.sysfs_changes.is_empty());
+    }
+
+    #[test]
+    fn test_no_state_under_root() {
+        let tmp = TempDir::new().unwrap();
+        let root = OfflineRoot::new(tmp.path()).unwrap();
+        assert!(Rescue::load(root).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_line_diff_marks_changed_lines() {
+        let current =
+            "title Linux\noptions root=UUID=abc rw nvme_core.default_ps_max_latency_us=0\n";
+        let original = "title Linux\noptions root=UUID=abc rw\n";
+        assert_eq!(
+            line_diff(current, original),
+            vec![
+                DiffLine::Same("title Linux".to_string()),
+                DiffLine::Removed(
+                    "options root=UUID=abc rw nvme_core.default_ps_max_latency_us=0".to_string()
+                ),
+                DiffLine::Added("options root=UUID=abc rw".to_string()),
+            ]
+        );
+        assert!(
+            line_diff(original, original)
+                .iter()
+                .all(|l| matches!(l, DiffLine::Same(_)))
+        );
+        assert_eq!(
+            line_diff("", "a\nb\n"),
+            vec![
+                DiffLine::Added("a".to_string()),
+                DiffLine::Added("b".to_string())
+            ]
+        );
+    }
+}
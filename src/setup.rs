@@ -0,0 +1,454 @@
+//! `bop setup`: first-run wizard.
+//!
+//! The wizard only asks questions and fills in existing `BopConfig` fields;
+//! the follow-up actions are the ordinary `bop apply` and `bop auto enable`.
+//! Input and output are generic so tests can script a whole session.
+
+use crate::audit::{Finding, Severity};
+use crate::config::BopConfig;
+use crate::error::{Error, Result};
+use crate::preset::Preset;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// How many findings to walk through before asking questions.
+const TOP_FINDINGS: usize = 3;
+
+/// Answers given on the command line; each one skips its question.
+#[derive(Debug, Clone, Default)]
+pub struct SetupFlags {
+    pub auto: Option<bool>,
+    pub preset: Option<Preset>,
+    pub dim: Option<bool>,
+    pub apply: Option<bool>,
+    /// Take the default for every question not answered by a flag
+    pub yes: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetupAnswers {
+    /// Install the udev rule (`bop auto enable`)
+    pub auto: bool,
+    pub preset: Preset,
+    pub dim: bool,
+    /// Run `bop apply` now (only asked when not auto-switching)
+    pub apply: bool,
+}
+
+impl SetupAnswers {
+    /// Fold the answers into `base`, leaving every other setting alone.
+    pub fn to_config(&self, base: &BopConfig) -> BopConfig {
+        let mut config = base.clone();
+        config.preset = Some(self.preset);
+        config.auto.aggressive = false;
+        config.brightness.auto_dim = self.dim;
+        config
+    }
+}
+
+/// What the wizard knows about the machine before asking anything.
+pub struct SetupContext<'a> {
+    pub hardware: String,
+    pub profile: Option<&'a str>,
+    pub findings: &'a [Finding],
+    pub score: u32,
+    pub current: &'a BopConfig,
+}
+
+struct Prompter<'a, R, W> {
+    input: &'a mut R,
+    out: &'a mut W,
+    yes: bool,
+}
+
+impl<R: BufRead, W: Write> Prompter<'_, R, W> {
+    /// Read one answer line; None on EOF.
+    fn read_line(&mut self) -> Result<Option<String>> {
+        let mut line = String::new();
+        let n = self.input.read_line(&mut line).map_err(io_err)?;
+        Ok((n > 0).then(|| line.trim().to_lowercase()))
+    }
+
+    fn yes_no(&mut self, preset: Option<bool>, question: &str, default: bool) -> Result<bool> {
+        if let Some(answer) = preset {
+            return Ok(answer);
+        }
+        if self.yes {
+            return Ok(default);
+        }
+        let hint = if default { "[Y/n]" } else { "[y/N]" };
+        loop {
+            write!(self.out, "{} {} ", question, hint).map_err(io_err)?;
+            self.out.flush().map_err(io_err)?;
+            match self.read_line()?.as_deref() {
+                None | Some("") => return Ok(default),
+                Some("y" | "yes") => return Ok(true),
+                Some("n" | "no") => return Ok(false),
+                Some(_) => writeln!(self.out, "  Please answer y or n.").map_err(io_err)?,
+            }
+        }
+    }
+
+    fn preset(&mut self, preset: Option<Preset>, default: Preset) -> Result<Preset> {
+        if let Some(p) = preset {
+            return Ok(p);
+        }
+        if self.yes {
+            return Ok(default);
+        }
+        let choices = [
+            (Preset::Moderate, "balanced savings, no noticeable slowdown"),
+            (
+                Preset::Saver,
+                "low-power platform profile on top of moderate",
+            ),
+            (
+                Preset::Supersaver,
+                "turbo off and aggressive PCIe/USB power saving",
+            ),
+        ];
+        writeln!(self.out, "How aggressive should bop be on battery?").map_err(io_err)?;
+        for (i, (p, desc)) in choices.iter().enumerate() {
+            let mark = if *p == default { " (default)" } else { "" };
+            writeln!(self.out, "  {}) {:<11} {}{}", i + 1, p, desc, mark).map_err(io_err)?;
+        }
+        loop {
+            write!(self.out, "Choice [1-3]: ").map_err(io_err)?;
+            self.out.flush().map_err(io_err)?;
+            let Some(line) = self.read_line()? else {
+                return Ok(default);
+            };
+            if line.is_empty() {
+                return Ok(default);
+            }
+            let picked = line
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|i| choices.get(i).map(|(p, _)| *p))
+                .or_else(|| {
+                    choices
+                        .iter()
+                        .map(|(p, _)| *p)
+                        .find(|p| p.to_string() == line)
+                });
+            match picked {
+                Some(p) => return Ok(p),
+                None => writeln!(self.out, "  Please pick 1, 2 or 3.").map_err(io_err)?,
+            }
+        }
+    }
+}
+
+fn io_err(e: std::io::Error) -> Error {
+    Error::Other(format!("setup: {}", e))
+}
+
+/// Short plain-language explanation of the worst findings.
+fn explain_findings(out: &mut impl Write, findings: &[Finding], score: u32) -> Result<()> {
+    let mut actionable: Vec<&Finding> = findings
+        .iter()
+        .filter(|f| f.severity > Severity::Info)
+        .collect();
+    if actionable.is_empty() {
+        writeln!(out, "Audit score {}/100: nothing needs fixing.", score).map_err(io_err)?;
+        return Ok(());
+    }
+    actionable.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then(b.weight.cmp(&a.weight))
+            .then_with(|| crate::audit::canonical_order(a, b))
+    });
+
+    writeln!(
+        out,
+        "Audit score {}/100. The biggest things bop would fix:",
+        score
+    )
+    .map_err(io_err)?;
+    for f in actionable.iter().take(TOP_FINDINGS) {
+        writeln!(out, "  - {}", f.description).map_err(io_err)?;
+        if !f.impact.is_empty() {
+            writeln!(out, "    Why it matters: {}", f.impact).map_err(io_err)?;
+        }
+    }
+    if actionable.len() > TOP_FINDINGS {
+        writeln!(
+            out,
+            "  ...and {} more (see `bop audit`).",
+            actionable.len() - TOP_FINDINGS
+        )
+        .map_err(io_err)?;
+    }
+    Ok(())
+}
+
+/// Run the question-and-answer part of setup.
+pub fn run_wizard(
+    ctx: &SetupContext,
+    flags: &SetupFlags,
+    input: &mut impl BufRead,
+    out: &mut impl Write,
+) -> Result<SetupAnswers> {
+    writeln!(out, "Detected: {}", ctx.hardware).map_err(io_err)?;
+    match ctx.profile {
+        Some(name) => writeln!(out, "Profile:  {}", name).map_err(io_err)?,
+        None => writeln!(
+            out,
+            "Profile:  none matched (bop will refuse to apply on this machine)"
+        )
+        .map_err(io_err)?,
+    }
+    writeln!(out).map_err(io_err)?;
+    explain_findings(out, ctx.findings, ctx.score)?;
+    writeln!(out).map_err(io_err)?;
+
+    let mut p = Prompter {
+        input,
+        out,
+        yes: flags.yes,
+    };
+
+    let auto = p.yes_no(
+        flags.auto,
+        "Apply optimizations automatically when you unplug, and undo them on AC?",
+        true,
+    )?;
+    let default_preset = ctx
+        .current
+        .preset
+        .filter(|p| *p >= Preset::Moderate)
+        .unwrap_or(Preset::Moderate);
+    let preset = p.preset(flags.preset, default_preset)?;
+    let dim = p.yes_no(
+        flags.dim,
+        "Dim the screen on battery?",
+        ctx.current.brightness.auto_dim,
+    )?;
+    // auto enable already applies when on battery
+    let apply = if auto {
+        false
+    } else {
+        p.yes_no(flags.apply, "Apply optimizations now?", true)?
+    };
+
+    Ok(SetupAnswers {
+        auto,
+        preset,
+        dim,
+        apply,
+    })
+}
+
+/// Write the answers into the config at `path`, creating it if needed.
+/// Only the keys the wizard asks about change; comments, other settings
+/// and keys this version doesn't know survive.
+pub fn write_config(answers: &SetupAnswers, path: &Path) -> Result<()> {
+    let existing = match std::fs::read_to_string(path) {
+        Ok(text) => Some(text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            return Err(Error::Other(format!(
+                "failed to read {}: {}",
+                path.display(),
+                e
+            )));
+        }
+    };
+    let mut doc: toml_edit::DocumentMut = match &existing {
+        Some(text) => text
+            .parse()
+            .map_err(|e| Error::Other(format!("{} is not valid TOML: {}", path.display(), e)))?,
+        None => toml_edit::DocumentMut::new(),
+    };
+
+    doc["preset"] = toml_edit::value(answers.preset.to_string());
+    // The deprecated flag would read as supersaver without a preset
+    if let Some(auto) = doc.get_mut("auto").and_then(|t| t.as_table_like_mut()) {
+        auto.remove("aggressive");
+    }
+    doc["brightness"]["auto_dim"] = toml_edit::value(answers.dim);
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| Error::Other(format!("failed to create {}: {}", parent.display(), e)))?;
+    }
+    let content = match existing {
+        Some(_) => doc.to_string(),
+        None => format!(
+            "# Written by `bop setup`. See `bop config init` for every option.\n\n{}",
+            doc
+        ),
+    };
+    let tmp = path.with_extension("toml.bop-new");
+    std::fs::write(&tmp, content)
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&tmp);
+            Error::Other(format!("failed to write {}: {}", path.display(), e))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn findings() -> Vec<Finding> {
+        vec![
+            Finding::new(Severity::Info, "Display", "OLED panel").weight(0),
+            Finding::new(Severity::High, "CPU", "EPP set to performance")
+                .impact("2-4W wasted at idle")
+                .weight(8),
+            Finding::new(Severity::Low, "USB", "3 USB devices not autosuspending").weight(2),
+        ]
+    }
+
+    fn run(script: &str, flags: SetupFlags) -> (SetupAnswers, String) {
+        let findings = findings();
+        let current = BopConfig::default();
+        let ctx = SetupContext {
+            hardware: "Framework Laptop 16".to_string(),
+            profile: Some("Framework Laptop 16 (AMD Ryzen 7040)"),
+            findings: &findings,
+            score: 57,
+            current: &current,
+        };
+        let mut input = Cursor::new(script.as_bytes().to_vec());
+        let mut out = Vec::new();
+        let answers = run_wizard(&ctx, &flags, &mut input, &mut out).unwrap();
+        (answers, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_scripted_session() {
+        let (answers, out) = run("n\n3\ny\nn\n", SetupFlags::default());
+        assert_eq!(
+            answers,
+            SetupAnswers {
+                auto: false,
+                preset: Preset::Supersaver,
+                dim: true,
+                apply: false,
+            }
+        );
+        assert!(out.contains("Detected: Framework Laptop 16"));
+        assert!(out.contains("Audit score 57/100"));
+        // Worst finding first, with its impact; Info findings left out
+        let epp = out.find("EPP set to performance").unwrap();
+        assert!(epp < out.find("USB devices").unwrap());
+        assert!(out.contains("Why it matters: 2-4W wasted at idle"));
+        assert!(!out.contains("OLED"));
+    }
+
+    #[test]
+    fn test_invalid_answers_reprompt() {
+        let (answers, out) = run("maybe\ny\nsaver\n\n\n", SetupFlags::default());
+        assert!(answers.auto);
+        assert_eq!(answers.preset, Preset::Saver);
+        assert!(!answers.apply, "apply is not asked when auto-switching");
+        assert!(out.contains("Please answer y or n."));
+    }
+
+    #[test]
+    fn test_flags_skip_questions() {
+        let flags = SetupFlags {
+            auto: Some(false),
+            preset: Some(Preset::Saver),
+            apply: Some(true),
+            yes: true,
+            ..Default::default()
+        };
+        // Nothing on stdin: every question is answered by a flag or default
+        let (answers, out) = run("", flags);
+        assert_eq!(answers.preset, Preset::Saver);
+        assert!(answers.apply && !answers.auto && !answers.dim);
+        assert!(!out.contains("[y/N]") && !out.contains("[Y/n]"));
+    }
+
+    #[test]
+    fn test_eof_takes_defaults() {
+        let (answers, _) = run("", SetupFlags::default());
+        assert!(answers.auto);
+        assert_eq!(answers.preset, Preset::Moderate);
+    }
+
+    #[test]
+    fn test_answers_merge_into_existing_config() {
+        let mut base = BopConfig::default();
+        base.overrides.turbo_boost = Some(true);
+        base.auto.aggressive = true;
+        let answers = SetupAnswers {
+            auto: true,
+            preset: Preset::Saver,
+            dim: true,
+            apply: false,
+        };
+        let config = answers.to_config(&base);
+        assert_eq!(config.preset, Some(Preset::Saver));
+        assert!(
+            !config.auto.aggressive,
+            "deprecated flag replaced by preset"
+        );
+        assert!(config.brightness.auto_dim);
+        assert_eq!(config.overrides.turbo_boost, Some(true));
+    }
+
+    #[test]
+    fn test_write_config_keeps_comments_and_other_keys() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("etc/bop/config.toml");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(
+            &path,
+            "# my laptop\n\
+             preset = \"moderate\"\n\n\
+             [auto]\n\
+             aggressive = true\n\
+             on_lid_close = \"apply\"\n\n\
+             [overrides]\n\
+             turbo_boost = true # keep turbo\n\
+             future_knob = 3\n",
+        )
+        .unwrap();
+        let answers = SetupAnswers {
+            auto: true,
+            preset: Preset::Saver,
+            dim: true,
+            apply: false,
+        };
+        write_config(&answers, &path).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("# my laptop\n"));
+        assert!(text.contains("turbo_boost = true # keep turbo\n"));
+        assert!(text.contains("future_knob = 3\n"));
+        assert!(!text.contains("aggressive"));
+
+        let loaded = crate::config::load(Some(&path));
+        assert_eq!(loaded.preset, Some(Preset::Saver));
+        assert!(loaded.brightness.auto_dim);
+        assert_eq!(loaded.auto.on_lid_close, crate::config::LidAction::Apply);
+        assert_eq!(loaded.overrides.turbo_boost, Some(true));
+    }
+
+    #[test]
+    fn test_write_config_starts_a_missing_file() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("etc/bop/config.toml");
+        let answers = SetupAnswers {
+            auto: false,
+            preset: Preset::Supersaver,
+            dim: false,
+            apply: true,
+        };
+        write_config(&answers, &path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("# Written by `bop setup`."));
+        assert_eq!(
+            crate::config::load(Some(&path)).preset,
+            Some(Preset::Supersaver)
+        );
+    }
+}
@@ -30,11 +30,15 @@ const SINGLE_FILE_PATHS: &[&str] = &[
     // CPU (global)
     "sys/devices/system/cpu/cpufreq/boost",
     "sys/devices/system/cpu/amd_pstate/status",
+    // Hybrid core types (Intel P/E-cores)
+    "sys/devices/cpu_core/cpus",
+    "sys/devices/cpu_atom/cpus",
     // Platform / sleep
     "sys/firmware/acpi/platform_profile",
     "sys/firmware/acpi/platform_profile_choices",
     "sys/power/state",
     "sys/power/mem_sleep",
+    "sys/power/suspend_stats/success",
     // PCI ASPM
     "sys/module/pcie_aspm/parameters/policy",
     // Audio
@@ -85,6 +89,20 @@ impl Snapshot {
         // Capture battery/power supply
         capture_power_supply(sysfs, &mut files, &mut dirs);
 
+        // Capture PCIe bandwidth controls
+        capture_link_speed_cooling(sysfs, &mut files);
+
+        // Capture USB-C ports and partners
+        capture_typec(sysfs, &mut files, &mut dirs);
+
+        // Capture platform_profile handlers
+        capture_platform_profile(sysfs, &mut files, &mut dirs);
+
+        // Capture the LPS0 device, which s2idle detection looks for
+        if let Some(device) = crate::detect::platform::find_lps0_device(sysfs) {
+            dirs.push(format!("sys/bus/acpi/devices/{}", device));
+        }
+
         Self {
             version: env!("CARGO_PKG_VERSION").to_string(),
             timestamp: chrono_now(),
@@ -147,6 +165,31 @@ fn capture_per_cpu(sysfs: &SysfsRoot, files: &mut BTreeMap<String, String>) {
                 files.insert(path, val);
             }
         }
+
+        let epb = format!("{}/{}/power/energy_perf_bias", cpu_base, entry);
+        if let Some(val) = sysfs.read_optional(&epb).unwrap_or(None) {
+            files.insert(epb, val);
+        }
+    }
+
+    // cpufreq policies, which group the CPUs above
+    let policy_base = format!("{}/cpufreq", cpu_base);
+    for entry in sysfs.list_dir(&policy_base).unwrap_or_default() {
+        if !entry.starts_with("policy") {
+            continue;
+        }
+        for file in &[
+            "affected_cpus",
+            "scaling_driver",
+            "scaling_governor",
+            "energy_performance_preference",
+            "boost",
+        ] {
+            let path = format!("{}/{}/{}", policy_base, entry, file);
+            if let Some(val) = sysfs.read_optional(&path).unwrap_or(None) {
+                files.insert(path, val);
+            }
+        }
     }
 }
 
@@ -171,6 +214,12 @@ fn capture_pci_devices(
             "device",
             "power/control",
             "power/runtime_status",
+            "boot_vga",
+            "gpu_busy_percent",
+            "current_link_speed",
+            "current_link_width",
+            "max_link_speed",
+            "max_link_width",
         ] {
             let path = format!("{}/{}", base, file);
             if let Some(val) = sysfs.read_optional(&path).unwrap_or(None) {
@@ -191,6 +240,32 @@ fn capture_pci_devices(
     }
 }
 
+fn capture_link_speed_cooling(sysfs: &SysfsRoot, files: &mut BTreeMap<String, String>) {
+    let thermal_base = "sys/class/thermal";
+    let entries = match sysfs.list_dir(thermal_base) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+
+    for entry in entries.iter().filter(|e| e.starts_with("cooling_device")) {
+        let base = format!("{}/{}", thermal_base, entry);
+        let type_path = format!("{}/type", base);
+        let Some(kind) = sysfs.read_optional(&type_path).unwrap_or(None) else {
+            continue;
+        };
+        if !kind.starts_with("PCIe_Port_Link_Speed_") {
+            continue;
+        }
+        files.insert(type_path, kind);
+        for file in &["cur_state", "max_state"] {
+            let path = format!("{}/{}", base, file);
+            if let Some(val) = sysfs.read_optional(&path).unwrap_or(None) {
+                files.insert(path, val);
+            }
+        }
+    }
+}
+
 fn capture_usb_devices(
     sysfs: &SysfsRoot,
     files: &mut BTreeMap<String, String>,
@@ -211,10 +286,14 @@ fn capture_usb_devices(
 
         for file in &[
             "power/control",
+            "power/runtime_status",
+            "power/persist",
             "product",
             "manufacturer",
             "idVendor",
             "idProduct",
+            "bmAttributes",
+            "bMaxPower",
         ] {
             let path = format!("{}/{}", base, file);
             if let Some(val) = sysfs.read_optional(&path).unwrap_or(None) {
@@ -257,10 +336,12 @@ fn capture_drm(sysfs: &SysfsRoot, files: &mut BTreeMap<String, String>, dirs: &m
                 );
             }
         } else if entry.contains('-') {
-            // Connector — capture status
-            let status_path = format!("{}/status", base);
-            if let Some(val) = sysfs.read_optional(&status_path).unwrap_or(None) {
-                files.insert(status_path, val);
+            // Connector — capture status and runtime ABM
+            for file in &["status", "amdgpu/panel_power_savings"] {
+                let path = format!("{}/{}", base, file);
+                if let Some(val) = sysfs.read_optional(&path).unwrap_or(None) {
+                    files.insert(path, val);
+                }
             }
         }
     }
@@ -351,6 +432,37 @@ fn capture_power_supply(
             "current_now",
             "voltage_now",
             "cycle_count",
+            "uevent",
+        ] {
+            let path = format!("{}/{}", base, file);
+            if let Some(val) = sysfs.read_optional(&path).unwrap_or(None) {
+                files.insert(path, val);
+            }
+        }
+    }
+}
+
+fn capture_typec(sysfs: &SysfsRoot, files: &mut BTreeMap<String, String>, dirs: &mut Vec<String>) {
+    let typec_base = "sys/class/typec";
+    let entries = match sysfs.list_dir(typec_base) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+
+    // Ports and their partners; cables and plugs don't affect detection
+    for entry in entries
+        .iter()
+        .filter(|e| !e.contains("-cable") && !e.contains("-plug"))
+    {
+        let base = format!("{}/{}", typec_base, entry);
+        dirs.push(base.clone());
+
+        for file in &[
+            "power_role",
+            "data_role",
+            "supports_usb_power_delivery",
+            "usb_power_delivery_revision",
+            "accessory_mode",
         ] {
             let path = format!("{}/{}", base, file);
             if let Some(val) = sysfs.read_optional(&path).unwrap_or(None) {
@@ -360,9 +472,30 @@ fn capture_power_supply(
     }
 }
 
+fn capture_platform_profile(
+    sysfs: &SysfsRoot,
+    files: &mut BTreeMap<String, String>,
+    dirs: &mut Vec<String>,
+) {
+    // Module presence is all that matters for amd_pmf
+    if sysfs.exists("sys/module/amd_pmf") {
+        dirs.push("sys/module/amd_pmf".to_string());
+    }
+
+    let class_base = "sys/class/platform-profile";
+    for entry in sysfs.list_dir(class_base).unwrap_or_default() {
+        for file in &["name", "profile", "choices"] {
+            let path = format!("{}/{}/{}", class_base, entry, file);
+            if let Some(val) = sysfs.read_optional(&path).unwrap_or(None) {
+                files.insert(path, val);
+            }
+        }
+    }
+}
+
 fn chrono_now() -> String {
     // Simple timestamp without requiring chrono crate
-    let output = std::process::Command::new("date")
+    let output = crate::cmd::Cmd::new("date")
         .arg("--iso-8601=seconds")
         .output();
     match output {
@@ -0,0 +1,146 @@
+//! Small numeric helpers for turning repeated measurements (e.g. battery
+//! drain per suspend) into an estimate with a confidence hint.
+
+/// Mean of `samples` weighted by their paired weights, e.g. %/hour drain
+/// weighted by session length so a five-minute nap counts less than a
+/// night. Pairs with a non-positive or non-finite weight are skipped;
+/// `None` when nothing is left.
+pub fn weighted_mean(samples: &[(f64, f64)]) -> Option<f64> {
+    let (sum, total) = usable(samples).fold((0.0, 0.0), |(sum, total), (value, weight)| {
+        (sum + value * weight, total + weight)
+    });
+    (total > 0.0).then(|| sum / total)
+}
+
+/// Weighted population variance around [`weighted_mean`].
+pub fn weighted_variance(samples: &[(f64, f64)]) -> Option<f64> {
+    let mean = weighted_mean(samples)?;
+    let (sum, total) = usable(samples).fold((0.0, 0.0), |(sum, total), (value, weight)| {
+        (sum + weight * (value - mean).powi(2), total + weight)
+    });
+    Some(sum / total)
+}
+
+/// Middle value of `values`, averaging the two middle ones for an even
+/// count. Non-finite values are skipped; `None` when nothing is left.
+/// Unlike the mean, a few outliers (e.g. a stretch of unusual drain) barely
+/// move it.
+pub fn median(values: &[f64]) -> Option<f64> {
+    let mut sorted: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+    if sorted.is_empty() {
+        return None;
+    }
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    Some(if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    })
+}
+
+/// Hours until `percent` of charge is used up at `percent_per_hour`.
+/// `None` for a non-positive rate: the battery isn't draining.
+pub fn hours_to_empty(percent: f64, percent_per_hour: f64) -> Option<f64> {
+    (percent_per_hour > 0.0 && percent_per_hour.is_finite())
+        .then(|| percent.max(0.0) / percent_per_hour)
+}
+
+/// How far to trust an estimate from `count` samples with the given mean
+/// and variance. Ordered from least to most trustworthy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl Confidence {
+    /// High needs at least five samples spread less than 25% of the mean
+    /// (coefficient of variation); medium needs three and under 50%.
+    pub fn from_samples(count: usize, mean: f64, variance: f64) -> Self {
+        let spread = if mean > 0.0 {
+            variance.max(0.0).sqrt() / mean
+        } else {
+            f64::INFINITY
+        };
+        match count {
+            5.. if spread < 0.25 => Confidence::High,
+            3.. if spread < 0.5 => Confidence::Medium,
+            _ => Confidence::Low,
+        }
+    }
+}
+
+impl std::fmt::Display for Confidence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Confidence::Low => write!(f, "low"),
+            Confidence::Medium => write!(f, "medium"),
+            Confidence::High => write!(f, "high"),
+        }
+    }
+}
+
+fn usable(samples: &[(f64, f64)]) -> impl Iterator<Item = (f64, f64)> + '_ {
+    samples
+        .iter()
+        .copied()
+        .filter(|(value, weight)| value.is_finite() && weight.is_finite() && *weight > 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weighted_mean() {
+        // 8h at 0.5%/h and 1h at 5%/h: the long session dominates
+        assert_eq!(weighted_mean(&[(0.5, 8.0), (5.0, 1.0)]), Some(1.0));
+        assert_eq!(weighted_mean(&[(2.0, 1.0), (4.0, 1.0)]), Some(3.0));
+        assert_eq!(weighted_mean(&[(9.0, 0.0), (1.0, 2.0)]), Some(1.0));
+        assert_eq!(weighted_mean(&[(f64::NAN, 1.0), (1.0, 1.0)]), Some(1.0));
+        assert_eq!(weighted_mean(&[]), None);
+        assert_eq!(weighted_mean(&[(1.0, 0.0)]), None);
+    }
+
+    #[test]
+    fn test_weighted_variance() {
+        assert_eq!(weighted_variance(&[(1.0, 3.0), (1.0, 1.0)]), Some(0.0));
+        assert_eq!(weighted_variance(&[(2.0, 1.0), (4.0, 1.0)]), Some(1.0));
+        // Mean 1.0; 8 * 0.25 + 1 * 16 over 9
+        assert_eq!(weighted_variance(&[(0.5, 8.0), (5.0, 1.0)]), Some(2.0));
+        assert_eq!(weighted_variance(&[]), None);
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&[3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(&[4.0, 1.0, 3.0, 2.0]), Some(2.5));
+        // One outlier doesn't drag it
+        assert_eq!(median(&[5.0, 5.0, 5.0, 50.0, 5.0]), Some(5.0));
+        assert_eq!(median(&[f64::NAN, 1.0]), Some(1.0));
+        assert_eq!(median(&[]), None);
+    }
+
+    #[test]
+    fn test_hours_to_empty() {
+        assert_eq!(hours_to_empty(80.0, 0.5), Some(160.0));
+        assert_eq!(hours_to_empty(-1.0, 0.5), Some(0.0));
+        assert_eq!(hours_to_empty(80.0, 0.0), None);
+        assert_eq!(hours_to_empty(80.0, -0.2), None);
+        assert_eq!(hours_to_empty(80.0, f64::INFINITY), None);
+    }
+
+    #[test]
+    fn test_confidence_from_sample_count_and_spread() {
+        assert_eq!(Confidence::from_samples(6, 1.0, 0.01), Confidence::High);
+        // Enough samples, too noisy for high
+        assert_eq!(Confidence::from_samples(6, 1.0, 0.16), Confidence::Medium);
+        assert_eq!(Confidence::from_samples(3, 1.0, 0.01), Confidence::Medium);
+        assert_eq!(Confidence::from_samples(3, 1.0, 1.0), Confidence::Low);
+        assert_eq!(Confidence::from_samples(2, 1.0, 0.0), Confidence::Low);
+        assert_eq!(Confidence::from_samples(10, 0.0, 0.0), Confidence::Low);
+        assert_eq!(Confidence::Medium.to_string(), "medium");
+    }
+}
@@ -0,0 +1,399 @@
+//! Cache behind `bop status --incremental`. A full check runs systemctl
+//! twice per disabled service plus once for the soft-reboot counter, which
+//! dominates a frequent status check. The incremental mode reuses the last
+//! full check's service results until something says they may be stale
+//! (one `systemctl is-active` for all of them catches a start, unit file
+//! mtimes an enable), and only reparses /proc/cmdline and /proc/acpi/wakeup
+//! when their content changed. Sysfs values are always re-read: they are
+//! what drifts.
+
+use super::{KernelParamStatus, ServiceStatus, WakeupStatus};
+use crate::managed::content_hash;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Under /run so a reboot starts from a full check.
+pub const CACHE_PATH: &str = "/run/bop/status-cache.json";
+
+/// [`CACHE_PATH`], or next to the state file under `--state-dir`: the
+/// cache describes that state, not the system's.
+pub fn cache_path() -> PathBuf {
+    match crate::apply::custom_state_dir() {
+        Some(dir) => dir.join("status-cache.json"),
+        None => PathBuf::from(CACHE_PATH),
+    }
+}
+
+/// Where systemd looks for admin unit files; a change here or in one of
+/// its `.wants`/`.requires` directories can re-enable a service bop
+/// disabled.
+pub const UNIT_DIR: &str = "/etc/systemd/system";
+
+/// What `systemctl is-active` prints for a unit `is-active --quiet`
+/// counts as running.
+const ACTIVE_STATES: &[&str] = &["active", "reloading", "refreshing"];
+
+/// What a status check cost, reported in its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckMode {
+    /// Every source read and every service queried
+    Full,
+    /// Service results reused from the last full check
+    Incremental,
+}
+
+/// Why an incremental run fell back to a full check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FullReason {
+    NoCache,
+    StateChanged,
+    NewBoot,
+    UnitFilesChanged,
+    ServiceStarted,
+    IntervalElapsed,
+}
+
+/// The fingerprints an incremental run compares against the cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sources {
+    pub state_hash: String,
+    pub boot_id: String,
+    /// Latest mtime of [`UNIT_DIR`] and its dependency directories, in
+    /// nanoseconds since the epoch
+    pub unit_files_mtime: Option<u64>,
+    /// Disabled services running now
+    pub active_services: Vec<String>,
+    /// Seconds since the epoch
+    pub now: u64,
+}
+
+impl Sources {
+    pub fn read(state_content: &[u8], boot_id: &str, services: &[String]) -> Self {
+        Self {
+            state_hash: content_hash(state_content),
+            boot_id: boot_id.to_string(),
+            unit_files_mtime: unit_files_mtime(Path::new(UNIT_DIR)),
+            active_services: active_services(services),
+            now: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+        }
+    }
+}
+
+fn mtime_nanos(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    let since = modified.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(since.as_nanos()).ok()
+}
+
+/// The latest mtime of `dir` and the `.wants`/`.requires` directories in
+/// it. `systemctl enable` only adds a link to one of those, and creating
+/// or removing one of them touches `dir`.
+fn unit_files_mtime(dir: &Path) -> Option<u64> {
+    let own = mtime_nanos(dir)?;
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Some(own);
+    };
+    let links = entries
+        .flatten()
+        .filter(|e| {
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            name.ends_with(".wants") || name.ends_with(".requires")
+        })
+        .filter_map(|e| mtime_nanos(&e.path()));
+    Some(links.fold(own, u64::max))
+}
+
+/// Which of `services` are running, from a single `systemctl is-active`.
+fn active_services(services: &[String]) -> Vec<String> {
+    if services.is_empty() {
+        return Vec::new();
+    }
+    // Exits non-zero when any unit is inactive; the states are printed anyway
+    let output = crate::cmd::Cmd::new("systemctl")
+        .arg("is-active")
+        .args(services)
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+        .unwrap_or_default();
+    parse_is_active(services, &output)
+}
+
+/// `systemctl is-active a b ...` prints one state per unit, in order.
+fn parse_is_active(services: &[String], output: &str) -> Vec<String> {
+    services
+        .iter()
+        .zip(output.lines())
+        .filter(|(_, state)| ACTIVE_STATES.contains(&state.trim()))
+        .map(|(service, _)| service.clone())
+        .collect()
+}
+
+/// A parse result with the hash of the content it came from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Parsed<T> {
+    pub hash: String,
+    pub value: T,
+}
+
+impl<T: Clone> Parsed<T> {
+    /// The cached value if `content` is unchanged, else `parse(content)`.
+    pub fn reuse_or(cached: Option<&Self>, content: &str, parse: impl FnOnce(&str) -> T) -> Self {
+        let hash = content_hash(content.as_bytes());
+        match cached {
+            Some(parsed) if parsed.hash == hash => parsed.clone(),
+            _ => Self {
+                hash,
+                value: parse(content),
+            },
+        }
+    }
+}
+
+/// The last check's results and the fingerprints they hold for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusCache {
+    pub state_hash: String,
+    pub boot_id: String,
+    pub unit_files_mtime: Option<u64>,
+    /// When services were last queried, seconds since the epoch
+    pub full_check_at: u64,
+    pub services: Vec<ServiceStatus>,
+    pub soft_reboots: Option<u32>,
+    pub cmdline: Parsed<Vec<KernelParamStatus>>,
+    pub acpi_wakeup: Parsed<Vec<WakeupStatus>>,
+}
+
+impl StatusCache {
+    /// The cache at `path`; None if it's missing or doesn't parse.
+    pub fn load(path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Best effort: without root there's nowhere to write, and the next
+    /// incremental run just does a full check.
+    pub fn save(&self, path: &Path) {
+        let Ok(json) = serde_json::to_string(self) else {
+            return;
+        };
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let _ = std::fs::write(path, json);
+    }
+
+    /// Why the service results can't be reused, or None if they can.
+    /// Cached parses of /proc files are still reused by content hash.
+    pub fn stale(
+        cache: Option<&Self>,
+        sources: &Sources,
+        full_interval: Duration,
+    ) -> Option<FullReason> {
+        let Some(cache) = cache else {
+            return Some(FullReason::NoCache);
+        };
+        if cache.state_hash != sources.state_hash {
+            Some(FullReason::StateChanged)
+        } else if cache.boot_id != sources.boot_id {
+            Some(FullReason::NewBoot)
+        } else if cache.unit_files_mtime != sources.unit_files_mtime {
+            Some(FullReason::UnitFilesChanged)
+        } else if cache
+            .services
+            .iter()
+            .any(|s| s.still_stopped && sources.active_services.contains(&s.name))
+        {
+            Some(FullReason::ServiceStarted)
+        } else if sources.now.saturating_sub(cache.full_check_at) >= full_interval.as_secs()
+            || sources.now < cache.full_check_at
+        {
+            Some(FullReason::IntervalElapsed)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn sources() -> Sources {
+        Sources {
+            state_hash: content_hash(b"{}"),
+            boot_id: "boot-a".to_string(),
+            unit_files_mtime: Some(1_000),
+            active_services: Vec::new(),
+            now: 10_000,
+        }
+    }
+
+    fn cache(sources: &Sources) -> StatusCache {
+        StatusCache {
+            state_hash: sources.state_hash.clone(),
+            boot_id: sources.boot_id.clone(),
+            unit_files_mtime: sources.unit_files_mtime,
+            full_check_at: sources.now,
+            services: vec![ServiceStatus {
+                name: "tlp.service".to_string(),
+                still_stopped: true,
+            }],
+            soft_reboots: Some(0),
+            cmdline: Parsed {
+                hash: String::new(),
+                value: Vec::new(),
+            },
+            acpi_wakeup: Parsed {
+                hash: String::new(),
+                value: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_fresh_cache_is_reused() {
+        let mut now = sources();
+        let cached = cache(&now);
+        now.now += HOUR.as_secs() - 1;
+        assert_eq!(StatusCache::stale(Some(&cached), &now, HOUR), None);
+    }
+
+    #[test]
+    fn test_invalidation_rules() {
+        let base = sources();
+        let cached = cache(&base);
+        assert_eq!(
+            StatusCache::stale(None, &base, HOUR),
+            Some(FullReason::NoCache)
+        );
+
+        let mut applied_again = base.clone();
+        applied_again.state_hash = content_hash(b"{\"preset\":\"saver\"}");
+        assert_eq!(
+            StatusCache::stale(Some(&cached), &applied_again, HOUR),
+            Some(FullReason::StateChanged)
+        );
+
+        let mut rebooted = base.clone();
+        rebooted.boot_id = "boot-b".to_string();
+        assert_eq!(
+            StatusCache::stale(Some(&cached), &rebooted, HOUR),
+            Some(FullReason::NewBoot)
+        );
+
+        // A unit file or an enablement link was added or removed
+        let mut units = base.clone();
+        units.unit_files_mtime = Some(2_000);
+        assert_eq!(
+            StatusCache::stale(Some(&cached), &units, HOUR),
+            Some(FullReason::UnitFilesChanged)
+        );
+        units.unit_files_mtime = None;
+        assert_eq!(
+            StatusCache::stale(Some(&cached), &units, HOUR),
+            Some(FullReason::UnitFilesChanged)
+        );
+
+        // `systemctl start tlp` leaves nothing on disk
+        let mut started = base.clone();
+        started.active_services = vec!["tlp.service".to_string()];
+        assert_eq!(
+            StatusCache::stale(Some(&cached), &started, HOUR),
+            Some(FullReason::ServiceStarted)
+        );
+
+        let mut later = base.clone();
+        later.now += HOUR.as_secs();
+        assert_eq!(
+            StatusCache::stale(Some(&cached), &later, HOUR),
+            Some(FullReason::IntervalElapsed)
+        );
+        // Clock went backwards: don't trust the cache's age
+        let mut earlier = base.clone();
+        earlier.now -= 1;
+        assert_eq!(
+            StatusCache::stale(Some(&cached), &earlier, HOUR),
+            Some(FullReason::IntervalElapsed)
+        );
+        // A zero interval always checks in full
+        assert_eq!(
+            StatusCache::stale(Some(&cached), &base, Duration::ZERO),
+            Some(FullReason::IntervalElapsed)
+        );
+    }
+
+    #[test]
+    fn test_enable_link_changes_the_unit_files_key() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let wants = tmp.path().join("multi-user.target.wants");
+        std::fs::create_dir(&wants).unwrap();
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        for dir in [tmp.path(), wants.as_path()] {
+            std::fs::File::open(dir).unwrap().set_modified(old).unwrap();
+        }
+        let before = unit_files_mtime(tmp.path());
+        assert_eq!(before, Some(1_000_000_000_000));
+
+        // What `systemctl enable tlp` does: a link in the wants directory
+        std::os::unix::fs::symlink(
+            "/usr/lib/systemd/system/tlp.service",
+            wants.join("tlp.service"),
+        )
+        .unwrap();
+        assert_eq!(mtime_nanos(tmp.path()), before);
+        assert_ne!(unit_files_mtime(tmp.path()), before);
+    }
+
+    #[test]
+    fn test_is_active_output_names_running_services() {
+        let services = [
+            "tlp.service".to_string(),
+            "power-profiles-daemon.service".to_string(),
+            "tuned.service".to_string(),
+        ];
+        assert_eq!(
+            parse_is_active(&services, "inactive\nactive\nfailed\n"),
+            ["power-profiles-daemon.service"]
+        );
+        assert!(parse_is_active(&services, "").is_empty());
+    }
+
+    #[test]
+    fn test_parsed_reuses_unchanged_content() {
+        let parse_count = std::cell::Cell::new(0);
+        let parse = |content: &str| {
+            parse_count.set(parse_count.get() + 1);
+            content.split_whitespace().count()
+        };
+        let first = Parsed::reuse_or(None, "a b c", parse);
+        assert_eq!(first.value, 3);
+        let again = Parsed::reuse_or(Some(&first), "a b c", parse);
+        assert_eq!(again.value, 3);
+        assert_eq!(parse_count.get(), 1);
+        let changed = Parsed::reuse_or(Some(&first), "a b", parse);
+        assert_eq!(changed.value, 2);
+        assert_eq!(parse_count.get(), 2);
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("bop/status-cache.json");
+        let cached = cache(&sources());
+        cached.save(&path);
+        let loaded = StatusCache::load(&path).unwrap();
+        assert_eq!(loaded.state_hash, cached.state_hash);
+        assert_eq!(loaded.services[0].name, "tlp.service");
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(StatusCache::load(&path).is_none());
+        assert!(StatusCache::load(&tmp.path().join("missing.json")).is_none());
+    }
+}
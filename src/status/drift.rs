@@ -0,0 +1,391 @@
+//! `bop status --explain-drift`: circumstantial evidence for who changed a
+//! drifted sysfs value. Nothing here is proof, only which processes have
+//! the file open, which known power daemons are running, and whether the
+//! value is one a daemon writes by default.
+
+use crate::conflicts::{self, DAEMONS, Signature};
+use crate::status::SysfsStatus;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Most fd links read in one scan.
+pub const FD_SCAN_MAX_LINKS: usize = 50_000;
+/// Give up on the fd scan after this long.
+pub const FD_SCAN_TIMEOUT: Duration = Duration::from_secs(2);
+/// Processes scanned between short pauses, so a scan on a busy machine
+/// doesn't hog a core.
+const FD_SCAN_BATCH: usize = 256;
+const FD_SCAN_PAUSE: Duration = Duration::from_millis(1);
+
+/// A process holding a drifted file open.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OpenFile {
+    pub pid: u32,
+    pub process: String,
+}
+
+/// How far the /proc fd scan got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FdScan {
+    #[default]
+    Complete,
+    /// Stopped at [`FD_SCAN_MAX_LINKS`] or [`FD_SCAN_TIMEOUT`]
+    Truncated,
+    /// Other users' fds can't be read without root
+    SkippedNotRoot,
+}
+
+/// Evidence for one drifted value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DriftCause {
+    pub path: String,
+    pub expected: String,
+    pub actual: String,
+    /// Best guess from the evidence below; None when nothing points anywhere
+    pub likely_cause: Option<String>,
+    pub open_by: Vec<OpenFile>,
+    /// Units from the daemon watchlist that are running
+    pub running_daemons: Vec<String>,
+    /// Daemon defaults the live value matches
+    pub signatures: Vec<Signature>,
+}
+
+/// `--explain-drift` section of the status report.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DriftReport {
+    pub fd_scan: FdScan,
+    pub causes: Vec<DriftCause>,
+}
+
+/// What was gathered from the system, shared by every drifted item.
+#[derive(Debug, Clone, Default)]
+pub struct Evidence {
+    /// Drifted path -> processes with it open
+    pub open_files: BTreeMap<String, Vec<OpenFile>>,
+    pub fd_scan: FdScan,
+    pub running_daemons: Vec<&'static str>,
+    pub signatures: Vec<Signature>,
+}
+
+impl Evidence {
+    /// Gather evidence for `paths` from the running system.
+    pub fn gather(paths: &[&str]) -> Self {
+        let proc_root = Path::new("/proc");
+        let (open_files, fd_scan) = if nix::unistd::geteuid().is_root() {
+            scan_open_files(proc_root, paths, FD_SCAN_MAX_LINKS, FD_SCAN_TIMEOUT)
+        } else {
+            (BTreeMap::new(), FdScan::SkippedNotRoot)
+        };
+        let processes = process_names(proc_root);
+        let running_daemons = DAEMONS
+            .iter()
+            .filter(|d| processes.iter().any(|p| p == d.process) || unit_active(d.unit))
+            .map(|d| d.unit)
+            .collect();
+        Self {
+            open_files,
+            fd_scan,
+            running_daemons,
+            signatures: conflicts::load_signatures(Path::new("/")),
+        }
+    }
+}
+
+/// A cause for each drifted entry in `sysfs` (changed, not missing).
+pub fn explain(sysfs: &[SysfsStatus], evidence: &Evidence) -> Vec<DriftCause> {
+    sysfs
+        .iter()
+        .filter(|s| !s.active)
+        .filter_map(|s| {
+            let actual = s.actual.as_ref()?;
+            let open_by = evidence
+                .open_files
+                .get(&s.path)
+                .cloned()
+                .unwrap_or_default();
+            let signatures: Vec<Signature> =
+                conflicts::matching_signatures(&evidence.signatures, &s.path, actual)
+                    .into_iter()
+                    .cloned()
+                    .collect();
+            let running_daemons: Vec<String> = evidence
+                .running_daemons
+                .iter()
+                .map(|d| d.to_string())
+                .collect();
+            let likely_cause = likely_cause(&open_by, &signatures, &running_daemons);
+            Some(DriftCause {
+                path: s.path.clone(),
+                expected: s.expected.clone(),
+                actual: actual.clone(),
+                likely_cause,
+                open_by,
+                running_daemons,
+                signatures,
+            })
+        })
+        .collect()
+}
+
+/// Strongest evidence first: a process holding the file, then a running
+/// daemon whose default matches, then a matching default of a daemon that
+/// isn't running (it may have run earlier this boot), then any running
+/// daemon.
+fn likely_cause(
+    open_by: &[OpenFile],
+    signatures: &[Signature],
+    running: &[String],
+) -> Option<String> {
+    if let Some(open) = open_by.first() {
+        return Some(format!("{} (pid {}) has it open", open.process, open.pid));
+    }
+    let is_running = |unit: &str| running.iter().any(|r| r == unit);
+    if let Some(sig) = signatures.iter().find(|s| is_running(s.unit)) {
+        return Some(format!(
+            "{}: the value matches its {}",
+            sig.unit, sig.source
+        ));
+    }
+    if let Some(sig) = signatures.first() {
+        return Some(format!(
+            "{} (not running now): the value matches its {}",
+            sig.unit, sig.source
+        ));
+    }
+    (!running.is_empty()).then(|| format!("possibly {} (running)", running.join(", ")))
+}
+
+/// Processes under `proc_root` with one of `paths` open. Each path is also
+/// matched by its resolved form, since fd links point at the real file
+/// (`cpu0/cpufreq` is a link to `cpufreq/policy0`). Stops after
+/// `max_links` fd links or `timeout`, pausing between batches.
+pub fn scan_open_files(
+    proc_root: &Path,
+    paths: &[&str],
+    max_links: usize,
+    timeout: Duration,
+) -> (BTreeMap<String, Vec<OpenFile>>, FdScan) {
+    let mut found: BTreeMap<String, Vec<OpenFile>> = BTreeMap::new();
+    let targets: Vec<(&str, PathBuf)> = paths
+        .iter()
+        .map(|p| {
+            let resolved = std::fs::canonicalize(p).unwrap_or_else(|_| PathBuf::from(p));
+            (*p, resolved)
+        })
+        .collect();
+    if targets.is_empty() {
+        return (found, FdScan::Complete);
+    }
+
+    let started = Instant::now();
+    let mut links = 0;
+    for (i, pid) in pids(proc_root).into_iter().enumerate() {
+        if i > 0 && i % FD_SCAN_BATCH == 0 {
+            std::thread::sleep(FD_SCAN_PAUSE);
+        }
+        if started.elapsed() >= timeout {
+            return (found, FdScan::Truncated);
+        }
+        let dir = proc_root.join(pid.to_string());
+        let Ok(fds) = std::fs::read_dir(dir.join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            if links >= max_links {
+                return (found, FdScan::Truncated);
+            }
+            links += 1;
+            let Ok(link) = std::fs::read_link(fd.path()) else {
+                continue;
+            };
+            for (path, resolved) in &targets {
+                if link == Path::new(path) || link == *resolved {
+                    let entry = found.entry(path.to_string()).or_default();
+                    if !entry.iter().any(|o| o.pid == pid) {
+                        entry.push(OpenFile {
+                            pid,
+                            process: read_comm(&dir),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    (found, FdScan::Complete)
+}
+
+/// Numeric entries of `proc_root`, sorted.
+fn pids(proc_root: &Path) -> Vec<u32> {
+    let mut pids: Vec<u32> = std::fs::read_dir(proc_root)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| e.file_name().to_str()?.parse().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    pids.sort_unstable();
+    pids
+}
+
+fn read_comm(pid_dir: &Path) -> String {
+    std::fs::read_to_string(pid_dir.join("comm"))
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Every process name under `proc_root`.
+fn process_names(proc_root: &Path) -> Vec<String> {
+    pids(proc_root)
+        .into_iter()
+        .map(|pid| read_comm(&proc_root.join(pid.to_string())))
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn unit_active(unit: &str) -> bool {
+    crate::cmd::Cmd::new("systemctl")
+        .args(["is-active", "--quiet", unit])
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn drifted(path: &str, expected: &str, actual: &str) -> SysfsStatus {
+        SysfsStatus {
+            path: path.to_string(),
+            expected: expected.to_string(),
+            actual: Some(actual.to_string()),
+            active: false,
+            pinned: false,
+        }
+    }
+
+    fn fake_process(proc_root: &Path, pid: u32, comm: &str, open: &[&Path]) {
+        let dir = proc_root.join(pid.to_string());
+        fs::create_dir_all(dir.join("fd")).unwrap();
+        fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+        for (fd, target) in open.iter().enumerate() {
+            std::os::unix::fs::symlink(target, dir.join("fd").join(fd.to_string())).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_scan_finds_holders_through_resolved_paths() {
+        let tmp = TempDir::new().unwrap();
+        let policy = tmp.path().join("sys/cpufreq/policy0");
+        fs::create_dir_all(&policy).unwrap();
+        let epp = policy.join("energy_performance_preference");
+        fs::write(&epp, "balance_performance\n").unwrap();
+        // cpu0/cpufreq links to the policy dir, like on a real system
+        let cpu0 = tmp.path().join("sys/cpu0");
+        fs::create_dir_all(&cpu0).unwrap();
+        std::os::unix::fs::symlink(&policy, cpu0.join("cpufreq")).unwrap();
+        let via_cpu0 = cpu0.join("cpufreq/energy_performance_preference");
+
+        let proc_root = tmp.path().join("proc");
+        fake_process(&proc_root, 42, "ppd-helper", &[&epp]);
+        fake_process(&proc_root, 7, "bash", &[&tmp.path().join("sys")]);
+        fs::create_dir_all(proc_root.join("self")).unwrap();
+
+        let path = via_cpu0.to_str().unwrap();
+        let (found, scan) = scan_open_files(&proc_root, &[path], 100, Duration::from_secs(5));
+        assert_eq!(scan, FdScan::Complete);
+        assert_eq!(
+            found[path],
+            [OpenFile {
+                pid: 42,
+                process: "ppd-helper".to_string()
+            }]
+        );
+        assert_eq!(process_names(&proc_root), ["bash", "ppd-helper"]);
+    }
+
+    #[test]
+    fn test_scan_stops_at_its_budget() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("value");
+        fs::write(&file, "1\n").unwrap();
+        let proc_root = tmp.path().join("proc");
+        for pid in 1..=3 {
+            fake_process(&proc_root, pid, "holder", &[&file, &file]);
+        }
+        let path = file.to_str().unwrap();
+
+        let (found, scan) = scan_open_files(&proc_root, &[path], 3, Duration::from_secs(5));
+        assert_eq!(scan, FdScan::Truncated);
+        assert_eq!(found[path].len(), 2);
+
+        let (found, scan) = scan_open_files(&proc_root, &[path], 100, Duration::ZERO);
+        assert_eq!(scan, FdScan::Truncated);
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn test_likely_cause_prefers_stronger_evidence() {
+        let epp = "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference";
+        let status = [
+            drifted(epp, "balance_power", "balance_performance"),
+            drifted("/proc/sys/kernel/nmi_watchdog", "0", "1"),
+            // Not drifted, or missing: no cause
+            SysfsStatus {
+                active: true,
+                ..drifted("/sys/a", "x", "x")
+            },
+            SysfsStatus {
+                actual: None,
+                ..drifted("/sys/b", "x", "x")
+            },
+        ];
+        let mut evidence = Evidence {
+            signatures: conflicts::signatures(&BTreeMap::new()),
+            ..Default::default()
+        };
+
+        // Only a daemon default to go on
+        let causes = explain(&status, &evidence);
+        assert_eq!(causes.len(), 2);
+        assert_eq!(
+            causes[0].likely_cause.as_deref(),
+            Some(
+                "power-profiles-daemon.service (not running now): the value matches its balanced profile"
+            )
+        );
+        assert_eq!(causes[1].likely_cause, None);
+
+        // A running daemon beats one that isn't
+        evidence.running_daemons = vec!["tlp.service"];
+        let causes = explain(&status, &evidence);
+        assert_eq!(
+            causes[0].likely_cause.as_deref(),
+            Some("tlp.service: the value matches its CPU_ENERGY_PERF_POLICY_ON_AC default")
+        );
+        assert_eq!(
+            causes[1].likely_cause.as_deref(),
+            Some("possibly tlp.service (running)")
+        );
+        assert_eq!(causes[0].signatures.len(), 2);
+
+        // A process holding the file beats both
+        evidence.open_files.insert(
+            epp.to_string(),
+            vec![OpenFile {
+                pid: 1234,
+                process: "auto-cpufreq".to_string(),
+            }],
+        );
+        let causes = explain(&status, &evidence);
+        assert_eq!(
+            causes[0].likely_cause.as_deref(),
+            Some("auto-cpufreq (pid 1234) has it open")
+        );
+    }
+}
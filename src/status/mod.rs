@@ -1,5 +1,13 @@
+pub mod cache;
+pub mod drift;
+
 use crate::apply::ApplyState;
-use serde::Serialize;
+use crate::pins::Pins;
+use crate::sysfs::SysfsRoot;
+use cache::{CheckMode, Parsed, StatusCache};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
 
 /// Status of a single sysfs value after apply.
 #[derive(Debug, Clone, Serialize)]
@@ -8,24 +16,26 @@ pub struct SysfsStatus {
     pub expected: String,
     pub actual: Option<String>,
     pub active: bool,
+    /// `expected` is the user's `[pins]` value, not bop's
+    pub pinned: bool,
 }
 
 /// Status of an ACPI wakeup source.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WakeupStatus {
     pub device: String,
     pub active: bool, // true = still disabled as intended
 }
 
 /// Status of a kernel parameter.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KernelParamStatus {
     pub param: String,
     pub in_cmdline: bool,
 }
 
 /// Status of a disabled service.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceStatus {
     pub name: String,
     pub still_stopped: bool,
@@ -38,15 +48,70 @@ pub struct UnitStatus {
     pub exists: bool,
 }
 
+/// Soft-reboot state of the running kernel boot.
+#[derive(Debug, Clone, Serialize)]
+pub struct SoftRebootStatus {
+    /// `systemctl soft-reboot` cycles since the kernel booted
+    pub count: u32,
+    /// Whether the persistence unit ran again after the latest one
+    pub reapplied: bool,
+}
+
+/// An automatic rollback started by `bop apply --rollback-after`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RollbackStatus {
+    /// When the timer fires (RFC 3339)
+    pub due: String,
+    /// Seconds until then, zero once due
+    pub remaining_secs: u64,
+    /// The system rebooted since, which cancelled the timer
+    pub lost_to_reboot: bool,
+}
+
+/// Whether the recorded changes were made during the running kernel boot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BootStatus {
+    /// Applied during this boot
+    Current,
+    /// Applied during an earlier boot; the persistence unit re-ran them
+    /// during this one
+    Reapplied,
+    /// Applied during an earlier boot and nothing has re-run them since
+    NotApplied,
+}
+
 /// Full status report.
 #[derive(Debug, Clone, Serialize)]
 pub struct StatusReport {
     pub timestamp: String,
+    /// Preset the changes were made for; None for older state files
+    pub preset: Option<crate::preset::Preset>,
+    /// How long that apply took; None for older state files
+    pub apply_duration_ms: Option<u64>,
     pub sysfs: Vec<SysfsStatus>,
     pub acpi_wakeup: Vec<WakeupStatus>,
     pub kernel_params: Vec<KernelParamStatus>,
     pub services: Vec<ServiceStatus>,
     pub systemd_unit: Option<UnitStatus>,
+    /// Present only when userspace has been soft-rebooted under this kernel
+    pub soft_reboot: Option<SoftRebootStatus>,
+    /// None for state files that predate the recorded boot id
+    pub boot: Option<BootStatus>,
+    /// Present only while an automatic rollback is pending
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rollback: Option<RollbackStatus>,
+    /// The wakeup sources `bop dbus-serve` re-disabled before the last
+    /// suspend this boot, present only when that landed after suspend entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub late_sleep_toggle: Option<crate::wake::sleep::SleepToggle>,
+    /// Evidence for who changed drifted values; only with `--explain-drift`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drift: Option<drift::DriftReport>,
+    pub check: CheckMode,
+    /// When services were last queried (RFC 3339); older than the report
+    /// itself after an incremental check
+    pub full_check_at: String,
 }
 
 impl StatusReport {
@@ -80,6 +145,21 @@ impl StatusReport {
             + self.systemd_unit.iter().filter(|u| !u.exists).count()
     }
 
+    /// Gather evidence for each drifted sysfs value into [`Self::drift`].
+    pub fn explain_drift(&mut self) {
+        let paths: Vec<&str> = self
+            .sysfs
+            .iter()
+            .filter(|s| !s.active && s.actual.is_some())
+            .map(|s| s.path.as_str())
+            .collect();
+        let evidence = drift::Evidence::gather(&paths);
+        self.drift = Some(drift::DriftReport {
+            fd_scan: evidence.fd_scan,
+            causes: drift::explain(&self.sysfs, &evidence),
+        });
+    }
+
     /// Count of kernel params written to boot entries but not yet in running cmdline.
     pub fn pending_reboot_count(&self) -> usize {
         self.kernel_params.iter().filter(|k| !k.in_cmdline).count()
@@ -94,38 +174,70 @@ impl StatusReport {
     }
 }
 
-/// Check sysfs values from the saved state against live filesystem.
-fn check_sysfs(state: &ApplyState) -> Vec<SysfsStatus> {
-    state
+/// Check sysfs values from the saved state against live filesystem. A
+/// pinned path is checked against its pinned value instead (or not at all
+/// for "leave"), along with every path a value pin covers under `sysfs`.
+fn check_sysfs(state: &ApplyState, pins: &Pins, sysfs: &SysfsRoot) -> Vec<SysfsStatus> {
+    let mut statuses: Vec<SysfsStatus> = state
         .sysfs_changes
         .iter()
-        .map(|change| {
-            let actual = std::fs::read_to_string(&change.path)
-                .ok()
-                .map(|s| s.trim().to_string());
-            let active = actual.as_deref() == Some(change.new_value.trim());
-            SysfsStatus {
-                path: change.path.clone(),
-                expected: change.new_value.trim().to_string(),
-                actual,
-                active,
-            }
+        .filter_map(|change| match pins.get(&change.path) {
+            Some(pin) => pin.value.map(|value| {
+                sysfs_status(
+                    &change.path,
+                    Path::new(&change.path),
+                    value.to_string(),
+                    true,
+                )
+            }),
+            None => Some(sysfs_status(
+                &change.path,
+                Path::new(&change.path),
+                change.expected_value(),
+                false,
+            )),
         })
-        .collect()
+        .collect();
+    for (path, value) in pins.expand(sysfs) {
+        let live = sysfs.path(path.trim_start_matches('/'));
+        if statuses
+            .iter()
+            .any(|s| s.path == path || Path::new(&s.path) == live)
+        {
+            continue;
+        }
+        statuses.push(sysfs_status(&path, &live, value, true));
+    }
+    statuses
+}
+
+/// Status of `path`, read from `live`.
+fn sysfs_status(path: &str, live: &Path, expected: String, pinned: bool) -> SysfsStatus {
+    let actual = std::fs::read_to_string(live)
+        .ok()
+        .map(|s| crate::sysfs::normalize_value(&s));
+    let active = actual.as_deref() == Some(expected.as_str());
+    SysfsStatus {
+        path: path.to_string(),
+        expected,
+        actual,
+        active,
+        pinned,
+    }
 }
 
 /// Check ACPI wakeup sources against /proc/acpi/wakeup.
 fn check_acpi_wakeup(state: &ApplyState, acpi_wakeup_content: &str) -> Vec<WakeupStatus> {
+    let entries = crate::detect::acpi_wakeup::parse(acpi_wakeup_content);
     state
         .acpi_wakeup_toggled
         .iter()
         .map(|device| {
-            // Parse /proc/acpi/wakeup to find this device's current state.
-            // Lines look like: "XHC1      S0    *disabled   pci:0000:00:08.1"
-            let actual_disabled = acpi_wakeup_content.lines().any(|line| {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                parts.first() == Some(&device.as_str()) && parts.contains(&"*disabled")
-            });
+            // A name that has since become ambiguous can't be vouched for
+            let actual_disabled = crate::detect::acpi_wakeup::lookup(&entries, device)
+                .ok()
+                .flatten()
+                .is_some_and(|entry| entry.enabled == Some(false));
             WakeupStatus {
                 device: device.clone(),
                 active: actual_disabled,
@@ -155,11 +267,11 @@ fn check_services(state: &ApplyState) -> Vec<ServiceStatus> {
         .services_disabled
         .iter()
         .map(|svc| {
-            let is_active = std::process::Command::new("systemctl")
+            let is_active = crate::cmd::Cmd::new("systemctl")
                 .args(["is-active", "--quiet", svc])
                 .status()
                 .is_ok_and(|s| s.success())
-                || std::process::Command::new("systemctl")
+                || crate::cmd::Cmd::new("systemctl")
                     .args(["is-enabled", "--quiet", svc])
                     .status()
                     .is_ok_and(|s| s.success());
@@ -179,25 +291,150 @@ fn check_systemd_units(state: &ApplyState) -> Option<UnitStatus> {
     })
 }
 
-/// Build a full status report from saved state.
+/// Compare the persistence unit's /run marker ("<boot_id> <soft_reboots>")
+/// against the live boot. Returns None when there has been no soft reboot.
+fn check_soft_reboot(
+    boot_id: &str,
+    soft_reboots: Option<u32>,
+    marker: Option<&str>,
+) -> Option<SoftRebootStatus> {
+    let count = soft_reboots.filter(|&n| n > 0)?;
+    let reapplied = marker.is_some_and(|m| {
+        let mut parts = m.split_whitespace();
+        parts.next() == Some(boot_id) && parts.next().and_then(|n| n.parse().ok()) == Some(count)
+    });
+    Some(SoftRebootStatus { count, reapplied })
+}
+
+/// Compare the boot id recorded at apply time, and the persistence unit's
+/// /run marker, against the live boot.
+fn check_rollback(
+    state: &ApplyState,
+    boot_id: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<RollbackStatus> {
+    let pending = state.rollback.as_ref()?;
+    Some(RollbackStatus {
+        due: pending.due.clone(),
+        remaining_secs: pending.remaining(now).unwrap_or_default().as_secs(),
+        lost_to_reboot: pending.lost_to_reboot(boot_id),
+    })
+}
+
+fn check_boot(boot_id: &str, recorded: Option<&str>, marker: Option<&str>) -> Option<BootStatus> {
+    let recorded = recorded?;
+    if recorded == boot_id {
+        Some(BootStatus::Current)
+    } else if marker.and_then(|m| m.split_whitespace().next()) == Some(boot_id) {
+        Some(BootStatus::Reapplied)
+    } else {
+        Some(BootStatus::NotApplied)
+    }
+}
+
+/// Soft reboots since kernel boot, from systemd's manager property.
+/// None on systemd older than 256, which doesn't expose the counter.
+fn soft_reboots_count() -> Option<u32> {
+    let output = crate::cmd::Cmd::new("systemctl")
+        .args(["show", "-P", "SoftRebootsCount"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Build a status report from saved state. With `incremental` (the
+/// full-check interval), service results from [`cache::CACHE_PATH`] are
+/// reused while they hold; every check refreshes the cache.
 /// Returns None if no state file exists.
-pub fn check() -> crate::error::Result<Option<StatusReport>> {
-    let state = match ApplyState::load()? {
+pub fn check(
+    pins: &Pins,
+    incremental: Option<Duration>,
+) -> crate::error::Result<Option<StatusReport>> {
+    // Intents a crashed run left count too; status doesn't write them back
+    let state = match crate::apply::journal::load_state()? {
         Some(s) => s,
         None => return Ok(None),
     };
+    let state_content = std::fs::read(ApplyState::file_path()).unwrap_or_default();
 
     let acpi_content = std::fs::read_to_string("/proc/acpi/wakeup").unwrap_or_default();
     let cmdline = std::fs::read_to_string("/proc/cmdline").unwrap_or_default();
 
-    Ok(Some(StatusReport {
+    let boot_id = crate::apply::current_boot_id().unwrap_or_default();
+    let sources = cache::Sources::read(&state_content, &boot_id, &state.services_disabled);
+    let cache_path = cache::cache_path();
+    let cache_path = cache_path.as_path();
+    let cached = incremental.and_then(|_| StatusCache::load(cache_path));
+    let reuse = incremental
+        .is_some_and(|interval| StatusCache::stale(cached.as_ref(), &sources, interval).is_none());
+    let cached = cached.filter(|c| c.state_hash == sources.state_hash && c.boot_id == boot_id);
+
+    let (services, soft_reboots, full_check_at) = match cached.as_ref().filter(|_| reuse) {
+        Some(c) => (c.services.clone(), c.soft_reboots, c.full_check_at),
+        None => (
+            check_services(&state),
+            if state.systemd_units_created.is_empty() {
+                None
+            } else {
+                soft_reboots_count()
+            },
+            sources.now,
+        ),
+    };
+    let kernel_params = Parsed::reuse_or(cached.as_ref().map(|c| &c.cmdline), &cmdline, |c| {
+        check_kernel_params(&state, c)
+    });
+    let acpi_wakeup = Parsed::reuse_or(
+        cached.as_ref().map(|c| &c.acpi_wakeup),
+        &acpi_content,
+        |c| check_acpi_wakeup(&state, c),
+    );
+
+    let marker = std::fs::read_to_string(crate::apply::systemd::PERSIST_MARKER_PATH).ok();
+    let soft_reboot = check_soft_reboot(&boot_id, soft_reboots, marker.as_deref());
+    let boot = check_boot(&boot_id, state.boot_id.as_deref(), marker.as_deref());
+
+    let report = StatusReport {
         timestamp: state.timestamp.clone(),
-        sysfs: check_sysfs(&state),
-        acpi_wakeup: check_acpi_wakeup(&state, &acpi_content),
-        kernel_params: check_kernel_params(&state, &cmdline),
-        services: check_services(&state),
+        preset: state.preset,
+        apply_duration_ms: state.duration_ms,
+        sysfs: check_sysfs(&state, pins, &SysfsRoot::system()),
+        acpi_wakeup: acpi_wakeup.value.clone(),
+        kernel_params: kernel_params.value.clone(),
+        services: services.clone(),
         systemd_unit: check_systemd_units(&state),
-    }))
+        soft_reboot,
+        boot,
+        rollback: check_rollback(&state, &boot_id, chrono::Utc::now()),
+        late_sleep_toggle: crate::wake::sleep::late_toggle(
+            Path::new(crate::wake::sleep::TIMING_PATH),
+            &boot_id,
+        ),
+        drift: None,
+        check: if reuse {
+            CheckMode::Incremental
+        } else {
+            CheckMode::Full
+        },
+        full_check_at: chrono::DateTime::from_timestamp(full_check_at as i64, 0)
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_default(),
+    };
+    StatusCache {
+        state_hash: sources.state_hash,
+        boot_id,
+        unit_files_mtime: sources.unit_files_mtime,
+        full_check_at,
+        services,
+        soft_reboots,
+        cmdline: kernel_params,
+        acpi_wakeup,
+    }
+    .save(cache_path);
+    Ok(Some(report))
 }
 
 #[cfg(test)]
@@ -214,15 +451,15 @@ mod tests {
         fs::write(&path, "low-power\n").unwrap();
 
         let state = ApplyState {
-            sysfs_changes: vec![SysfsChange {
-                path: path.to_string_lossy().into_owned(),
-                original_value: "performance".to_string(),
-                new_value: "low-power".to_string(),
-            }],
+            sysfs_changes: vec![SysfsChange::new(
+                path.to_string_lossy().into_owned(),
+                "performance",
+                "low-power",
+            )],
             ..Default::default()
         };
 
-        let result = check_sysfs(&state);
+        let result = check_sysfs(&state, &Pins::default(), &SysfsRoot::new(tmp.path()));
         assert_eq!(result.len(), 1);
         assert!(result[0].active);
         assert_eq!(result[0].actual.as_deref(), Some("low-power"));
@@ -235,15 +472,15 @@ mod tests {
         fs::write(&path, "performance\n").unwrap();
 
         let state = ApplyState {
-            sysfs_changes: vec![SysfsChange {
-                path: path.to_string_lossy().into_owned(),
-                original_value: "performance".to_string(),
-                new_value: "low-power".to_string(),
-            }],
+            sysfs_changes: vec![SysfsChange::new(
+                path.to_string_lossy().into_owned(),
+                "performance",
+                "low-power",
+            )],
             ..Default::default()
         };
 
-        let result = check_sysfs(&state);
+        let result = check_sysfs(&state, &Pins::default(), &SysfsRoot::new(tmp.path()));
         assert_eq!(result.len(), 1);
         assert!(!result[0].active);
         assert_eq!(result[0].actual.as_deref(), Some("performance"));
@@ -252,20 +489,133 @@ mod tests {
     #[test]
     fn test_check_sysfs_missing_path() {
         let state = ApplyState {
-            sysfs_changes: vec![SysfsChange {
-                path: "/nonexistent/path/does/not/exist".to_string(),
-                original_value: "old".to_string(),
-                new_value: "new".to_string(),
-            }],
+            sysfs_changes: vec![SysfsChange::new(
+                "/nonexistent/path/does/not/exist",
+                "old",
+                "new",
+            )],
             ..Default::default()
         };
 
-        let result = check_sysfs(&state);
+        let result = check_sysfs(&state, &Pins::default(), &SysfsRoot::new("/nonexistent"));
         assert_eq!(result.len(), 1);
         assert!(!result[0].active);
         assert!(result[0].actual.is_none());
     }
 
+    #[test]
+    fn test_check_sysfs_choice_list_and_whitespace() {
+        let tmp = TempDir::new().unwrap();
+        let mem_sleep = tmp.path().join("mem_sleep");
+        fs::write(&mem_sleep, "s2idle [deep]\n").unwrap();
+        let choices = tmp.path().join("choices");
+        fs::write(&choices, "low-power balanced  performance\n").unwrap();
+
+        let state = ApplyState {
+            sysfs_changes: vec![
+                SysfsChange::new(
+                    mem_sleep.to_string_lossy().into_owned(),
+                    "[s2idle] deep\n",
+                    "deep",
+                ),
+                SysfsChange::new(
+                    choices.to_string_lossy().into_owned(),
+                    "",
+                    "low-power balanced performance\n",
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let result = check_sysfs(&state, &Pins::default(), &SysfsRoot::new(tmp.path()));
+        assert!(result[0].active, "bracketed selection matches the write");
+        assert_eq!(result[0].expected, "deep");
+        assert_eq!(result[0].actual.as_deref(), Some("deep"));
+        assert!(result[1].active, "whole value compared, not first token");
+        assert_eq!(result[1].expected, "low-power balanced performance");
+    }
+
+    #[test]
+    fn test_check_sysfs_legacy_state_without_normalized_fields() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("mem_sleep");
+        fs::write(&path, "s2idle [deep]\n").unwrap();
+
+        let json = format!(
+            r#"{{"path":{:?},"original_value":"s2idle","new_value":"deep"}}"#,
+            path.to_string_lossy()
+        );
+        let change: SysfsChange = serde_json::from_str(&json).unwrap();
+        assert!(change.new_normalized.is_empty());
+
+        let state = ApplyState {
+            sysfs_changes: vec![change],
+            ..Default::default()
+        };
+        assert!(check_sysfs(&state, &Pins::default(), &SysfsRoot::new(tmp.path()))[0].active);
+    }
+
+    #[test]
+    fn test_check_sysfs_against_pins() {
+        let tmp = TempDir::new().unwrap();
+        for cpu in ["cpu0", "cpu1"] {
+            let dir = tmp
+                .path()
+                .join(format!("sys/devices/system/cpu/{}/cpufreq", cpu));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(
+                dir.join("energy_performance_preference"),
+                "balance_performance\n",
+            )
+            .unwrap();
+        }
+        let watchdog = tmp.path().join("nmi_watchdog");
+        fs::write(&watchdog, "1\n").unwrap();
+        let cpu0 = tmp
+            .path()
+            .join("sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference");
+
+        // Applied before the pins were added
+        let state = ApplyState {
+            sysfs_changes: vec![
+                SysfsChange::new(
+                    cpu0.to_string_lossy().into_owned(),
+                    "balance_performance",
+                    "balance_power",
+                ),
+                SysfsChange::new(watchdog.to_string_lossy().into_owned(), "1", "0"),
+            ],
+            ..Default::default()
+        };
+        let mut pins = Pins::default();
+        pins.insert(
+            "/sys/devices/system/cpu/cpu*/cpufreq/energy_performance_preference",
+            "balance_performance",
+        );
+        pins.insert(
+            format!(
+                "{}/sys/devices/system/cpu/cpu*/cpufreq/energy_performance_preference",
+                tmp.path().display()
+            ),
+            "balance_performance",
+        );
+        pins.insert(watchdog.to_string_lossy(), "leave");
+
+        let result = check_sysfs(&state, &pins, &SysfsRoot::new(tmp.path()));
+        // nmi_watchdog is left alone; cpu0 from the state and cpu1 from the
+        // pin are both checked against the pinned value
+        let paths: Vec<&str> = result.iter().map(|s| s.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                cpu0.to_str().unwrap(),
+                "/sys/devices/system/cpu/cpu1/cpufreq/energy_performance_preference",
+            ]
+        );
+        assert!(result.iter().all(|s| s.pinned && s.active));
+        assert_eq!(result[0].expected, "balance_performance");
+    }
+
     #[test]
     fn test_check_acpi_wakeup_disabled() {
         let content = "\
@@ -338,18 +688,22 @@ XHC1\t  S0\t*enabled   pci:0000:c4:00.4";
     fn test_report_counts() {
         let report = StatusReport {
             timestamp: "2026-02-18T00:00:00Z".to_string(),
+            preset: None,
+            apply_duration_ms: None,
             sysfs: vec![
                 SysfsStatus {
                     path: "a".into(),
                     expected: "x".into(),
                     actual: Some("x".into()),
                     active: true,
+                    pinned: false,
                 },
                 SysfsStatus {
                     path: "b".into(),
                     expected: "y".into(),
                     actual: Some("z".into()),
                     active: false,
+                    pinned: false,
                 },
             ],
             acpi_wakeup: vec![WakeupStatus {
@@ -371,6 +725,13 @@ XHC1\t  S0\t*enabled   pci:0000:c4:00.4";
                 path: "/etc/systemd/system/bop.service".into(),
                 exists: true,
             }),
+            soft_reboot: None,
+            late_sleep_toggle: None,
+            drift: None,
+            boot: None,
+            rollback: None,
+            check: cache::CheckMode::Full,
+            full_check_at: String::new(),
         };
 
         assert_eq!(report.total_count(), 6);
@@ -381,4 +742,103 @@ XHC1\t  S0\t*enabled   pci:0000:c4:00.4";
         assert_eq!(report.pending_reboot_count(), 1);
         assert_eq!(report.unknown_count(), 0);
     }
+
+    #[test]
+    fn test_rollback_status_counts_down_and_notices_reboot() {
+        let now = chrono::DateTime::parse_from_rfc3339("2026-10-15T10:06:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let mut state = ApplyState::default();
+        assert!(check_rollback(&state, "boot-a", now).is_none());
+
+        state.rollback = Some(crate::apply::rollback::PendingRollback {
+            unit: "bop-rollback".into(),
+            due: "2026-10-15T10:30:00+00:00".into(),
+            boot_id: Some("boot-a".into()),
+        });
+        let status = check_rollback(&state, "boot-a", now).unwrap();
+        assert_eq!(status.remaining_secs, 24 * 60);
+        assert!(!status.lost_to_reboot);
+        assert!(
+            check_rollback(&state, "boot-b", now)
+                .unwrap()
+                .lost_to_reboot
+        );
+    }
+
+    #[test]
+    fn test_soft_reboot_none_without_soft_reboots() {
+        assert!(check_soft_reboot("abc", Some(0), Some("abc 0")).is_none());
+        // Older systemd: counter unavailable
+        assert!(check_soft_reboot("abc", None, None).is_none());
+    }
+
+    #[test]
+    fn test_soft_reboot_reapplied() {
+        let status = check_soft_reboot("abc", Some(2), Some("abc 2\n")).unwrap();
+        assert_eq!(status.count, 2);
+        assert!(status.reapplied);
+    }
+
+    #[test]
+    fn test_soft_reboot_marker_from_previous_userspace() {
+        // Unit last ran before the second soft reboot
+        let status = check_soft_reboot("abc", Some(2), Some("abc 1")).unwrap();
+        assert!(!status.reapplied);
+    }
+
+    #[test]
+    fn test_soft_reboot_marker_stale_boot_or_missing() {
+        assert!(
+            !check_soft_reboot("abc", Some(1), Some("old 1"))
+                .unwrap()
+                .reapplied
+        );
+        // Marker written by a systemd without SoftRebootsCount
+        assert!(
+            !check_soft_reboot("abc", Some(1), Some("abc"))
+                .unwrap()
+                .reapplied
+        );
+        assert!(!check_soft_reboot("abc", Some(1), None).unwrap().reapplied);
+    }
+
+    #[test]
+    fn test_boot_same_boot() {
+        assert_eq!(
+            check_boot("abc", Some("abc"), None),
+            Some(BootStatus::Current)
+        );
+    }
+
+    #[test]
+    fn test_boot_rebooted_with_unit() {
+        assert_eq!(
+            check_boot("new", Some("old"), Some("new 0\n")),
+            Some(BootStatus::Reapplied)
+        );
+        // Marker from a systemd without SoftRebootsCount
+        assert_eq!(
+            check_boot("new", Some("old"), Some("new")),
+            Some(BootStatus::Reapplied)
+        );
+    }
+
+    #[test]
+    fn test_boot_rebooted_without_unit() {
+        assert_eq!(
+            check_boot("new", Some("old"), None),
+            Some(BootStatus::NotApplied)
+        );
+        // /run survived, e.g. across a soft reboot, but the marker is stale
+        assert_eq!(
+            check_boot("new", Some("old"), Some("old 0")),
+            Some(BootStatus::NotApplied)
+        );
+    }
+
+    #[test]
+    fn test_boot_unknown_for_legacy_state() {
+        assert_eq!(check_boot("abc", None, Some("abc 0")), None);
+    }
 }
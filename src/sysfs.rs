@@ -1,30 +1,104 @@
 use crate::error::{Error, Result};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Contents of the text files read through a [`SysfsRoot`], as read and
+/// keyed by relative path, so findings can cite their evidence without
+/// reading anything again. A handle: clones share one table, which later
+/// reads through the root keep adding to.
+#[derive(Debug, Clone, Default)]
+pub struct RawValues(Arc<Mutex<BTreeMap<String, String>>>);
+
+impl RawValues {
+    pub fn get(&self, relative: &str) -> Option<String> {
+        let raw = self.0.lock().ok()?;
+        raw.get(relative.trim_start_matches('/')).cloned()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.lock().map_or(true, |raw| raw.is_empty())
+    }
+
+    fn insert(&self, relative: &Path, content: &str) {
+        if let Ok(mut raw) = self.0.lock() {
+            let key = relative.to_string_lossy();
+            raw.insert(key.trim_start_matches('/').to_string(), content.to_string());
+        }
+    }
+}
+
+/// Environment variable that points [`SysfsRoot::system`] at another tree,
+/// e.g. a materialized snapshot, for running the binary against fixtures.
+pub const ROOT_ENV: &str = "BOP_SYSFS_ROOT";
 
 /// Abstraction over sysfs/procfs filesystem root.
 /// Defaults to `/` in production, redirectable to a temp directory for testing.
 #[derive(Debug, Clone)]
 pub struct SysfsRoot {
     root: PathBuf,
+    /// Reads and directory listings so far, shared between clones; only
+    /// kept when asked for with [`SysfsRoot::counting_reads`]
+    reads: Option<Arc<AtomicUsize>>,
+    /// What those reads returned, shared between clones
+    raw: RawValues,
 }
 
 impl Default for SysfsRoot {
     fn default() -> Self {
-        Self {
-            root: PathBuf::from("/"),
-        }
+        Self::new("/")
     }
 }
 
 impl SysfsRoot {
-    /// Create a SysfsRoot pointing at the real system.
+    /// Create a SysfsRoot pointing at the real system, or at
+    /// [`ROOT_ENV`] when that is set.
     pub fn system() -> Self {
-        Self::default()
+        match std::env::var_os(ROOT_ENV) {
+            Some(root) if !root.is_empty() => Self::new(root),
+            _ => Self::default(),
+        }
     }
 
     /// Create a SysfsRoot pointing at a custom directory (for testing).
     pub fn new(root: impl Into<PathBuf>) -> Self {
-        Self { root: root.into() }
+        Self {
+            root: root.into(),
+            reads: None,
+            raw: RawValues::default(),
+        }
+    }
+
+    /// Count the reads made through this root from now on, e.g. to check
+    /// how much a command's detection costs.
+    pub fn counting_reads(mut self) -> Self {
+        self.reads = Some(Arc::default());
+        self
+    }
+
+    /// Number of file reads and directory listings made through this root
+    /// since [`SysfsRoot::counting_reads`]; 0 for a root that doesn't count.
+    pub fn read_count(&self) -> usize {
+        self.reads
+            .as_ref()
+            .map_or(0, |reads| reads.load(Ordering::Relaxed))
+    }
+
+    fn count_read(&self) {
+        if let Some(reads) = &self.reads {
+            reads.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn record(&self, relative: &Path, content: &str) {
+        self.raw.insert(relative, content);
+    }
+
+    /// Everything read through this root and its clones, so far and from
+    /// now on. Shared, not copied.
+    pub fn raw_values(&self) -> RawValues {
+        self.raw.clone()
     }
 
     /// Resolve a path relative to this root.
@@ -35,17 +109,37 @@ impl SysfsRoot {
 
     /// Read a sysfs/procfs file, trimming whitespace.
     pub fn read(&self, relative: impl AsRef<Path>) -> Result<String> {
+        self.count_read();
+        let relative = relative.as_ref();
         let path = self.path(relative);
-        std::fs::read_to_string(&path)
-            .map(|s| s.trim().to_string())
-            .map_err(|e| Error::SysfsRead { path, source: e })
+        let content =
+            std::fs::read_to_string(&path).map_err(|e| Error::SysfsRead { path, source: e })?;
+        self.record(relative, &content);
+        Ok(content.trim().to_string())
     }
 
     /// Read a sysfs file, returning None if it doesn't exist.
     pub fn read_optional(&self, relative: impl AsRef<Path>) -> Result<Option<String>> {
+        self.count_read();
+        let relative = relative.as_ref();
         let path = self.path(relative);
         match std::fs::read_to_string(&path) {
-            Ok(s) => Ok(Some(s.trim().to_string())),
+            Ok(s) => {
+                self.record(relative, &s);
+                Ok(Some(s.trim().to_string()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Ok(None),
+            Err(e) => Err(Error::SysfsRead { path, source: e }),
+        }
+    }
+
+    /// Read a binary sysfs attribute (e.g. EDID), returning None if it doesn't exist.
+    pub fn read_bytes_optional(&self, relative: impl AsRef<Path>) -> Result<Option<Vec<u8>>> {
+        self.count_read();
+        let path = self.path(relative);
+        match std::fs::read(&path) {
+            Ok(b) => Ok(Some(b)),
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
             Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Ok(None),
             Err(e) => Err(Error::SysfsRead { path, source: e }),
@@ -73,6 +167,7 @@ impl SysfsRoot {
 
     /// List entries in a sysfs directory.
     pub fn list_dir(&self, relative: impl AsRef<Path>) -> Result<Vec<String>> {
+        self.count_read();
         let path = self.path(relative);
         let entries = std::fs::read_dir(&path).map_err(|e| Error::SysfsRead {
             path: path.clone(),
@@ -101,6 +196,29 @@ impl SysfsRoot {
     pub fn root(&self) -> &Path {
         &self.root
     }
+
+    /// Whether this is the running system's own tree. Commands (systemctl,
+    /// journalctl, nmcli) describe the host, so they only go with this.
+    pub fn is_host(&self) -> bool {
+        self.root == Path::new("/")
+    }
+}
+
+/// Canonical form of a sysfs value for comparison and restore.
+///
+/// Choice files like `mem_sleep` ("s2idle [deep]") read back as the full list
+/// with the active entry bracketed; that entry is what a write accepts. Other
+/// values are trimmed with internal whitespace runs collapsed to one space.
+pub fn normalize_value(raw: &str) -> String {
+    if let Some(start) = raw.find('[')
+        && let Some(len) = raw[start + 1..].find(']')
+    {
+        let selected = raw[start + 1..start + 1 + len].trim();
+        if !selected.is_empty() {
+            return selected.to_string();
+        }
+    }
+    raw.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
 #[cfg(test)]
@@ -120,6 +238,30 @@ mod tests {
         assert_eq!(sysfs.read_parse::<u32>("sys/test/value").unwrap(), 42);
     }
 
+    #[test]
+    fn test_raw_values_keep_what_was_read() {
+        let tmp = tempfile::tempdir().unwrap();
+        let sysfs = SysfsRoot::new(tmp.path()).counting_reads();
+        fs::create_dir_all(tmp.path().join("sys/test")).unwrap();
+        fs::write(tmp.path().join("sys/test/value"), "42\n").unwrap();
+        fs::write(tmp.path().join("sys/test/unread"), "7\n").unwrap();
+
+        assert!(sysfs.raw_values().is_empty());
+        sysfs.clone().read_optional("sys/test/value").unwrap();
+        assert_eq!(sysfs.read_optional("sys/test/missing").unwrap(), None);
+
+        let raw = sysfs.raw_values();
+        assert_eq!(raw.get("sys/test/value").as_deref(), Some("42\n"));
+        assert_eq!(raw.get("/sys/test/value").as_deref(), Some("42\n"));
+        assert_eq!(raw.get("sys/test/unread"), None);
+        assert_eq!(raw.get("sys/test/missing"), None);
+        // Reads after the handle was taken show up in it too
+        sysfs.read("sys/test/unread").unwrap();
+        assert_eq!(raw.get("sys/test/unread").as_deref(), Some("7\n"));
+        assert_eq!(sysfs.read_count(), 3);
+        assert_eq!(SysfsRoot::new(tmp.path()).read_count(), 0);
+    }
+
     #[test]
     fn test_read_optional_missing() {
         let tmp = tempfile::tempdir().unwrap();
@@ -140,4 +282,18 @@ mod tests {
         let entries = sysfs.list_dir("sys/devices").unwrap();
         assert_eq!(entries, vec!["a", "b"]);
     }
+
+    #[test]
+    fn test_normalize_value() {
+        assert_eq!(normalize_value("auto\n"), "auto");
+        assert_eq!(normalize_value("  low-power \n"), "low-power");
+        assert_eq!(normalize_value("s2idle [deep]\n"), "deep");
+        assert_eq!(normalize_value("[s2idle] deep"), "s2idle");
+        assert_eq!(
+            normalize_value("low-power  balanced\tperformance\n"),
+            "low-power balanced performance"
+        );
+        assert_eq!(normalize_value("[]"), "[]");
+        assert_eq!(normalize_value(""), "");
+    }
 }
@@ -1,110 +1,122 @@
+pub mod sleep;
+
 use crate::apply::sysfs_writer;
+use crate::detect::acpi_wakeup;
+use crate::detect::platform::{WakeupCounter, find_wakeup_counter};
+use crate::detect::{LazyHardware, usb_topology};
 use crate::error::{Error, Result};
+use crate::progress::{Progress, ProgressSink};
 use crate::sysfs::SysfsRoot;
-use colored::Colorize;
 
 #[derive(Debug, Clone)]
 pub struct WakeController {
     pub name: String,
     pub pci_address: Option<String>,
+    /// Last column of /proc/acpi/wakeup, e.g. "pci:0000:c1:00.3"
+    pub sysfs_node: Option<String>,
     pub enabled: bool,
+    /// Another controller has the same name, so neither can be toggled
+    pub ambiguous: bool,
     pub has_devices: bool,
     pub device_descriptions: Vec<String>,
+    /// Times the controller woke the system since boot (/sys/class/wakeup)
+    pub wake_count: u64,
+    /// Milliseconds since it last fired; None if it hasn't since boot
+    pub last_wake_ago_ms: Option<u64>,
+}
+
+impl WakeController {
+    /// "last wake" column: relative time of the last wake, or "-" if the
+    /// controller hasn't woken the system since boot.
+    pub fn last_wake_label(&self) -> String {
+        match self.last_wake_ago_ms {
+            Some(ago) if self.wake_count > 0 => format_ago(ago),
+            _ if self.wake_count > 0 => "since boot".to_string(),
+            _ => "-".to_string(),
+        }
+    }
+}
+
+/// Coarse relative time: "40s ago", "12m ago", "3h ago", "2d ago".
+fn format_ago(ms: u64) -> String {
+    let secs = ms / 1000;
+    match secs {
+        0..60 => format!("{}s ago", secs),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
 }
 
 /// Framework 16 USB host controllers use the XHC* naming convention in ACPI.
 /// Other ACPI wake sources (LID0, PBTN, GPP6, NHI0, etc.) are not USB
 /// controllers and should not be toggled by wake scan.
-fn is_usb_wakeup_source(name: &str) -> bool {
+pub fn is_usb_wakeup_source(name: &str) -> bool {
     name.starts_with("XHC")
 }
 
 /// Whether scan should disable this controller's wake capability.
 /// XHC0 is exempt because it is the primary USB controller (keyboard/trackpad).
 fn should_disable_in_scan(ctrl: &WakeController) -> bool {
-    is_usb_wakeup_source(&ctrl.name) && !ctrl.has_devices && ctrl.enabled && ctrl.name != "XHC0"
+    is_usb_wakeup_source(&ctrl.name)
+        && !ctrl.ambiguous
+        && !ctrl.has_devices
+        && ctrl.enabled
+        && ctrl.name != "XHC0"
 }
 
 /// Whether scan should enable this controller's wake capability.
 fn should_enable_in_scan(ctrl: &WakeController) -> bool {
-    is_usb_wakeup_source(&ctrl.name) && ctrl.has_devices && !ctrl.enabled
+    is_usb_wakeup_source(&ctrl.name) && !ctrl.ambiguous && ctrl.has_devices && !ctrl.enabled
 }
 
-/// List all USB controllers and their wakeup status.
-pub fn list() -> Result<()> {
-    let sysfs = SysfsRoot::system();
-    let controllers = scan_controllers(&sysfs)?;
-
-    println!("{}", " Wake Sources".bold());
-    println!();
+/// All ACPI wake controllers with their wakeup status, connected devices and
+/// wake activity since boot.
+pub fn list(hw: &LazyHardware) -> Result<Vec<WakeController>> {
+    let now_ms = nix::time::clock_gettime(nix::time::ClockId::CLOCK_MONOTONIC)
+        .map(|ts| ts.tv_sec() as u64 * 1000 + ts.tv_nsec() as u64 / 1_000_000)
+        .unwrap_or(0);
+    let mut controllers = scan_controllers(hw.sysfs())?;
+    attribute_wakes(&mut controllers, hw.wakeup_counters(), now_ms);
+    Ok(controllers)
+}
 
-    for ctrl in &controllers {
-        let wake_badge = if ctrl.enabled {
-            "enabled".green().to_string()
-        } else {
-            "disabled".dimmed().to_string()
+/// Fill in each controller's wake count and last wake from the kernel's
+/// wakeup accounting. `now_ms` is the current CLOCK_MONOTONIC time, the
+/// clock `last_change_ms` is kept in.
+fn attribute_wakes(controllers: &mut [WakeController], counters: &[WakeupCounter], now_ms: u64) {
+    for ctrl in controllers {
+        let Some(counter) = ctrl
+            .sysfs_node
+            .as_deref()
+            .and_then(|node| find_wakeup_counter(counters, node))
+        else {
+            continue;
         };
-
-        let addr = ctrl.pci_address.as_deref().unwrap_or("N/A");
-
-        print!(
-            "  {} {}  {}",
-            format!("{:<5}", ctrl.name).bold(),
-            wake_badge,
-            addr.dimmed()
-        );
-
-        if ctrl.has_devices {
-            print!("  {}", ctrl.device_descriptions.join(", "));
-        }
-
-        println!();
-    }
-
-    println!();
-
-    // Warn about disabled controllers with devices
-    for ctrl in &controllers {
-        if !ctrl.enabled && ctrl.has_devices {
-            println!(
-                "  {} {} has connected devices but wake is disabled!",
-                "WARNING:".yellow().bold(),
-                ctrl.name
-            );
-            println!(
-                "    Run `bop wake enable {}` to allow these devices to wake the system.",
-                ctrl.name
-            );
+        ctrl.wake_count = counter.wakeup_count;
+        if counter.wakeup_count > 0 {
+            ctrl.last_wake_ago_ms = counter
+                .last_change_ms
+                .map(|last| now_ms.saturating_sub(last));
         }
     }
+}
 
-    // Note about expansion cards
-    let disabled_empty: Vec<_> = controllers
-        .iter()
-        .filter(|c| !c.enabled && !c.has_devices && is_usb_wakeup_source(&c.name))
-        .collect();
-    if !disabled_empty.is_empty() {
-        let names: Vec<_> = disabled_empty.iter().map(|c| c.name.as_str()).collect();
-        println!();
-        println!(
-            "  {} Wake disabled on {} expansion card USB controllers ({}).",
-            "NOTICE:".cyan(),
-            names.len(),
-            names.join(", ")
-        );
-        println!(
-            "    USB expansion cards plugged into these ports will NOT wake the system from sleep."
-        );
-        println!(
-            "    Run `bop wake enable <controller>` to re-enable, or `bop wake scan` to auto-detect."
-        );
+/// Whether `controller` has wakeup enabled. An error if it isn't listed in
+/// /proc/acpi/wakeup, or is listed more than once.
+fn controller_enabled(sysfs: &SysfsRoot, controller: &str) -> Result<bool> {
+    let entries = acpi_wakeup::parse(&sysfs.read("proc/acpi/wakeup")?);
+    match acpi_wakeup::lookup(&entries, controller)? {
+        Some(entry) => Ok(entry.enabled == Some(true)),
+        None => Err(Error::Other(format!(
+            "Controller '{}' not found in /proc/acpi/wakeup",
+            controller
+        ))),
     }
-
-    Ok(())
 }
 
 /// Enable wakeup for a controller.
-pub fn enable(controller: &str) -> Result<()> {
+pub fn enable(controller: &str, progress: &mut dyn ProgressSink) -> Result<()> {
     if !nix::unistd::geteuid().is_root() {
         return Err(Error::NotRoot {
             operation: "wake enable".to_string(),
@@ -113,42 +125,30 @@ pub fn enable(controller: &str) -> Result<()> {
 
     let sysfs = SysfsRoot::system();
 
-    // Check if controller exists
-    let wakeup = sysfs.read("proc/acpi/wakeup")?;
-    if !wakeup.lines().any(|l| l.starts_with(controller)) {
-        return Err(Error::Other(format!(
-            "Controller '{}' not found in /proc/acpi/wakeup",
-            controller
-        )));
-    }
-
-    // Check current state
-    let is_enabled = wakeup
-        .lines()
-        .any(|l| l.starts_with(controller) && l.contains("*enabled"));
+    let is_enabled = controller_enabled(&sysfs, controller)?;
 
     if is_enabled {
-        println!("{} is already enabled.", controller);
+        progress.emit(Progress::Info(format!(
+            "{} is already enabled.",
+            controller
+        )));
         return Ok(());
     }
 
-    sysfs_writer::toggle_acpi_wakeup(controller)?;
-    println!(
-        "{} Wake {} for {}",
-        "OK".green().bold(),
-        "enabled".green(),
+    sysfs_writer::toggle_acpi_wakeup(controller, true)?;
+    progress.emit(Progress::Success(format!(
+        "Wake enabled for {}",
         controller
-    );
-    println!(
-        "  {}",
-        "Note: This is volatile and resets on reboot. Run `bop apply` to persist.".dimmed()
-    );
+    )));
+    progress.emit(Progress::Detail(
+        "Note: This is volatile and resets on reboot. Run `bop apply` to persist.".to_string(),
+    ));
 
     Ok(())
 }
 
 /// Disable wakeup for a controller.
-pub fn disable(controller: &str) -> Result<()> {
+pub fn disable(controller: &str, progress: &mut dyn ProgressSink) -> Result<()> {
     if !nix::unistd::geteuid().is_root() {
         return Err(Error::NotRoot {
             operation: "wake disable".to_string(),
@@ -157,36 +157,27 @@ pub fn disable(controller: &str) -> Result<()> {
 
     let sysfs = SysfsRoot::system();
 
-    let wakeup = sysfs.read("proc/acpi/wakeup")?;
-    if !wakeup.lines().any(|l| l.starts_with(controller)) {
-        return Err(Error::Other(format!(
-            "Controller '{}' not found in /proc/acpi/wakeup",
-            controller
-        )));
-    }
-
-    let is_enabled = wakeup
-        .lines()
-        .any(|l| l.starts_with(controller) && l.contains("*enabled"));
+    let is_enabled = controller_enabled(&sysfs, controller)?;
 
     if !is_enabled {
-        println!("{} is already disabled.", controller);
+        progress.emit(Progress::Info(format!(
+            "{} is already disabled.",
+            controller
+        )));
         return Ok(());
     }
 
-    sysfs_writer::toggle_acpi_wakeup(controller)?;
-    println!(
-        "{} Wake {} for {}",
-        "OK".green().bold(),
-        "disabled".yellow(),
+    sysfs_writer::toggle_acpi_wakeup(controller, false)?;
+    progress.emit(Progress::Success(format!(
+        "Wake disabled for {}",
         controller
-    );
+    )));
 
     Ok(())
 }
 
 /// Scan all controllers and auto-enable those with connected devices.
-pub fn scan() -> Result<()> {
+pub fn scan(progress: &mut dyn ProgressSink) -> Result<()> {
     if !nix::unistd::geteuid().is_root() {
         return Err(Error::NotRoot {
             operation: "wake scan".to_string(),
@@ -196,34 +187,36 @@ pub fn scan() -> Result<()> {
     let sysfs = SysfsRoot::system();
     let controllers = scan_controllers(&sysfs)?;
 
-    println!("{}", "Scanning USB controllers...".bold());
-    println!();
+    progress.emit(Progress::Heading("Scanning USB controllers...".to_string()));
 
     let mut changes = 0;
 
     for ctrl in &controllers {
         if should_enable_in_scan(ctrl) {
-            println!(
+            progress.emit(Progress::Info(format!(
                 "  {} has connected devices, enabling wake...",
-                ctrl.name.bold()
-            );
-            sysfs_writer::toggle_acpi_wakeup(&ctrl.name)?;
+                ctrl.name
+            )));
+            sysfs_writer::toggle_acpi_wakeup(&ctrl.name, true)?;
             changes += 1;
         } else if should_disable_in_scan(ctrl) {
-            println!(
+            progress.emit(Progress::Info(format!(
                 "  {} has no connected devices, disabling wake...",
-                ctrl.name.bold()
-            );
-            sysfs_writer::toggle_acpi_wakeup(&ctrl.name)?;
+                ctrl.name
+            )));
+            sysfs_writer::toggle_acpi_wakeup(&ctrl.name, false)?;
             changes += 1;
         }
     }
 
     if changes == 0 {
-        println!("  No changes needed.");
+        progress.emit(Progress::Info("  No changes needed.".to_string()));
     } else {
-        println!();
-        println!("{} {} controllers updated.", "OK".green().bold(), changes);
+        progress.emit(Progress::EndSection);
+        progress.emit(Progress::Success(format!(
+            "{} controllers updated.",
+            changes
+        )));
     }
 
     Ok(())
@@ -231,103 +224,44 @@ pub fn scan() -> Result<()> {
 
 /// Scan all controllers and detect connected devices.
 fn scan_controllers(sysfs: &SysfsRoot) -> Result<Vec<WakeController>> {
-    let wakeup_content = sysfs.read("proc/acpi/wakeup")?;
+    let entries = acpi_wakeup::parse(&sysfs.read("proc/acpi/wakeup")?);
     let mut controllers = Vec::new();
 
-    let usb_devices = sysfs.list_dir("sys/bus/usb/devices").unwrap_or_default();
-
-    for line in wakeup_content.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() < 3 {
-            continue;
-        }
-
-        let name = parts[0].to_string();
+    for entry in &entries {
+        let name = entry.device.clone();
 
         // Only process USB host controllers (XHC*) and other notable sources
         let is_usb_controller = is_usb_wakeup_source(&name);
 
-        let enabled = line.contains("*enabled");
+        let enabled = entry.enabled == Some(true);
+        let sysfs_node = entry.sysfs_node().map(String::from);
+        let pci_address = entry.pci_address().map(String::from);
 
-        let pci_address = parts
-            .iter()
-            .find(|p| p.starts_with("pci:"))
-            .map(|p| p.trim_start_matches("pci:").to_string());
-
-        let (has_devices, device_descriptions) = if is_usb_controller {
-            find_usb_devices_for_controller(&name, &pci_address, &usb_devices, sysfs)
-        } else {
-            (false, Vec::new())
+        let device_descriptions: Vec<String> = match &pci_address {
+            Some(pci) if is_usb_controller => usb_topology::controller_devices(sysfs, pci)
+                .into_iter()
+                .map(|d| d.description)
+                .collect(),
+            _ => Vec::new(),
         };
+        let has_devices = !device_descriptions.is_empty();
 
         controllers.push(WakeController {
             name,
             pci_address,
+            sysfs_node,
             enabled,
+            ambiguous: acpi_wakeup::is_ambiguous(&entries, entry),
             has_devices,
             device_descriptions,
+            wake_count: 0,
+            last_wake_ago_ms: None,
         });
     }
 
     Ok(controllers)
 }
 
-/// Find USB devices connected through a specific controller.
-fn find_usb_devices_for_controller(
-    _controller_name: &str,
-    pci_address: &Option<String>,
-    usb_devices: &[String],
-    sysfs: &SysfsRoot,
-) -> (bool, Vec<String>) {
-    let Some(pci_addr) = pci_address else {
-        return (false, Vec::new());
-    };
-
-    let mut descriptions = Vec::new();
-
-    // Find root hubs that belong to this PCI address
-    for usb_dev in usb_devices {
-        if !usb_dev.starts_with("usb") {
-            continue;
-        }
-
-        let dev_path = format!("sys/bus/usb/devices/{}", usb_dev);
-        let resolved = sysfs.path(&dev_path);
-        let Ok(canonical) = std::fs::canonicalize(&resolved) else {
-            continue;
-        };
-
-        if !canonical.to_string_lossy().contains(pci_addr.as_str()) {
-            continue;
-        }
-
-        // This root hub belongs to our controller -- find child devices
-        let bus_num = usb_dev.trim_start_matches("usb");
-        for other_dev in usb_devices {
-            if other_dev.starts_with(&format!("{}-", bus_num)) && !other_dev.contains(':') {
-                // This is a real USB device
-                let product = sysfs
-                    .read_optional(format!("sys/bus/usb/devices/{}/product", other_dev))
-                    .unwrap_or(None);
-                let manufacturer = sysfs
-                    .read_optional(format!("sys/bus/usb/devices/{}/manufacturer", other_dev))
-                    .unwrap_or(None);
-
-                let desc = match (manufacturer, product) {
-                    (Some(mfg), Some(prod)) => format!("{} {}", mfg, prod),
-                    (None, Some(prod)) => prod,
-                    (Some(mfg), None) => mfg,
-                    (None, None) => other_dev.clone(),
-                };
-                descriptions.push(desc);
-            }
-        }
-    }
-
-    let has_devices = !descriptions.is_empty();
-    (has_devices, descriptions)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -379,6 +313,84 @@ PBTN\tS4\t*disabled\tplatform:PNP0C0C:00
         std::os::unix::fs::symlink(&child_dev, &child_link).unwrap();
     }
 
+    /// /sys/class/wakeup entries for the controllers in `create_wakeup_fixture`
+    fn create_wakeup_class_fixture(root: &Path) {
+        let sources: &[(&str, &str, Option<&str>, u64, u64)] = &[
+            // (node, name, device link target, wakeup_count, last_change_ms)
+            (
+                "wakeup0",
+                "0000:c1:00.3",
+                Some("devices/pci0000:00/0000:c1:00.3"),
+                2,
+                3_000_000,
+            ),
+            // Named differently from its device; matched through the link
+            (
+                "wakeup1",
+                "xhci-hcd.1.auto",
+                Some("devices/pci0000:00/0000:c1:00.4"),
+                37,
+                3_590_000,
+            ),
+            (
+                "wakeup2",
+                "PNP0C0D:00",
+                Some("devices/platform/PNP0C0D:00"),
+                0,
+                10_000,
+            ),
+            ("wakeup3", "alarmtimer.0.auto", None, 5, 3_599_000),
+        ];
+        for (node, name, device, count, last_change) in sources {
+            let dir = root.join("sys/class/wakeup").join(node);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("name"), format!("{}\n", name)).unwrap();
+            fs::write(dir.join("wakeup_count"), format!("{}\n", count)).unwrap();
+            fs::write(dir.join("active_count"), format!("{}\n", count)).unwrap();
+            fs::write(dir.join("last_change_ms"), format!("{}\n", last_change)).unwrap();
+            if let Some(device) = device {
+                let target = root.join("sys").join(device);
+                fs::create_dir_all(&target).unwrap();
+                std::os::unix::fs::symlink(&target, dir.join("device")).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn list_attributes_last_wake_per_controller() {
+        let tmp = TempDir::new().unwrap();
+        create_wakeup_fixture(tmp.path());
+        create_wakeup_class_fixture(tmp.path());
+
+        let sysfs = SysfsRoot::new(tmp.path());
+        let mut controllers = scan_controllers(&sysfs).unwrap();
+        let hw = LazyHardware::new(&sysfs);
+        attribute_wakes(&mut controllers, hw.wakeup_counters(), 3_600_000);
+        let ctrl = |name: &str| controllers.iter().find(|c| c.name == name).unwrap();
+
+        assert_eq!(ctrl("XHC0").wake_count, 2);
+        assert_eq!(ctrl("XHC0").last_wake_ago_ms, Some(600_000));
+        assert_eq!(ctrl("XHC0").last_wake_label(), "10m ago");
+        assert_eq!(ctrl("XHC1").wake_count, 37);
+        assert_eq!(ctrl("XHC1").last_wake_label(), "10s ago");
+
+        // Present in the wakeup class but never fired since boot
+        assert_eq!(ctrl("LID0").wake_count, 0);
+        assert_eq!(ctrl("LID0").last_wake_ago_ms, None);
+        assert_eq!(ctrl("LID0").last_wake_label(), "-");
+        // No wakeup class entry at all
+        assert_eq!(ctrl("GPP6").last_wake_label(), "-");
+    }
+
+    #[test]
+    fn format_ago_picks_coarsest_unit() {
+        assert_eq!(format_ago(999), "0s ago");
+        assert_eq!(format_ago(59_999), "59s ago");
+        assert_eq!(format_ago(60_000), "1m ago");
+        assert_eq!(format_ago(2 * 3_600_000 + 1), "2h ago");
+        assert_eq!(format_ago(3 * 86_400_000), "3d ago");
+    }
+
     #[test]
     fn scan_enable_filter_excludes_non_usb_wake_sources() {
         let tmp = TempDir::new().unwrap();
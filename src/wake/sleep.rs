@@ -0,0 +1,410 @@
+//! Re-disabling ACPI wakeup sources right before suspend, and checking after
+//! resume that it landed in time.
+//!
+//! Drivers and firmware re-arm wakeup sources behind bop's back, and the
+//! table only matters at the moment the system suspends, so `bop
+//! dbus-serve` re-disables the ones bop disabled on logind's
+//! PrepareForSleep while holding a delay lock. If the lock wasn't honoured
+//! (another hook ran past `InhibitDelayMaxSec`, the daemon was slow), the
+//! writes can land after suspend started, when the firmware has already
+//! taken its wake configuration. Both ends of the work are marked in the
+//! kernel log, so they share a clock and an order with the kernel's own
+//! `PM: suspend entry` line; after resume the three are compared and the
+//! result kept in [`TIMING_PATH`] for `bop status`.
+
+use crate::apply::sysfs_writer::{WakeupTable, toggle_acpi_wakeup_in};
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// Outcome of the last pre-sleep re-disable.
+pub const TIMING_PATH: &str = "/var/lib/bop/sleep_toggle.json";
+pub const KMSG_PATH: &str = "/dev/kmsg";
+
+/// The kernel's line as `pm_suspend` starts; userspace is frozen right
+/// after it.
+const SUSPEND_ENTRY: &str = "PM: suspend entry";
+const STARTED: &str = "bop: re-disabling wakeup sources before sleep";
+const DONE: &str = "bop: wakeup sources re-disabled before sleep";
+
+/// Writes finishing less than this before suspend entry count as late: the
+/// freezer starts right after the entry line, and a write racing it may or
+/// may not be seen.
+pub const LATE_MARGIN_US: u64 = 10_000;
+
+/// Whether the re-disable finished before suspend entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Timing {
+    InTime,
+    TooLate,
+    /// No suspend entry after the start mark (suspend was aborted), or
+    /// the marks were lost from the kernel log
+    Unknown,
+}
+
+/// When the toggles completed relative to suspend entry, both in kernel
+/// log time. A pure function of the two, so it's testable without a
+/// suspend.
+pub fn attribute(done_us: Option<u64>, entry_us: Option<u64>, margin_us: u64) -> Timing {
+    match (done_us, entry_us) {
+        (Some(done), Some(entry)) if done + margin_us <= entry => Timing::InTime,
+        (Some(_), Some(_)) => Timing::TooLate,
+        _ => Timing::Unknown,
+    }
+}
+
+/// One record of the kernel log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KmsgRecord {
+    /// Microseconds since boot
+    pub timestamp_us: u64,
+    pub message: String,
+}
+
+/// Records in `/dev/kmsg` format (`prio,seq,timestamp,flags;message`, one
+/// per line, continuation lines starting with a space skipped).
+pub fn parse_kmsg(text: &str) -> Vec<KmsgRecord> {
+    text.lines()
+        .filter_map(|line| {
+            let (header, message) = line.split_once(';')?;
+            let timestamp_us = header.split(',').nth(2)?.parse().ok()?;
+            Some(KmsgRecord {
+                timestamp_us,
+                message: message.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Kernel log time of the done mark tagged `tag` and of the first suspend
+/// entry after the start mark, in that order. Both None when the start
+/// mark is gone.
+pub fn find_marks(records: &[KmsgRecord], tag: u64) -> (Option<u64>, Option<u64>) {
+    let started = format!("{} #{}", STARTED, tag);
+    let done = format!("{} #{}", DONE, tag);
+    let Some(start) = records.iter().position(|r| r.message == started) else {
+        return (None, None);
+    };
+    let after = &records[start + 1..];
+    (
+        after
+            .iter()
+            .find(|r| r.message == done)
+            .map(|r| r.timestamp_us),
+        after
+            .iter()
+            .find(|r| r.message.starts_with(SUSPEND_ENTRY))
+            .map(|r| r.timestamp_us),
+    )
+}
+
+/// What the pre-sleep hook did, carried over to the resume check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreSleep {
+    /// Marks this cycle's lines in the kernel log
+    pub tag: u64,
+    /// Sources found re-enabled and disabled again
+    pub redisabled: Vec<String>,
+    /// Sources that couldn't be disabled, with the error
+    pub failed: Vec<String>,
+}
+
+/// Disable each of `devices` again in `table`, between a start and a done
+/// mark written to `kmsg`. A mark that can't be written leaves the timing
+/// unknown; the toggles still happen.
+pub fn before_sleep(
+    devices: &[String],
+    table: &mut dyn WakeupTable,
+    kmsg: &mut dyn Write,
+    tag: u64,
+) -> PreSleep {
+    let _ = kmsg.write_all(format!("<6>{} #{}\n", STARTED, tag).as_bytes());
+    let mut redisabled = Vec::new();
+    let mut failed = Vec::new();
+    for device in devices {
+        match toggle_acpi_wakeup_in(table, device, false) {
+            Ok(true) => redisabled.push(device.clone()),
+            Ok(false) => {}
+            Err(e) => failed.push(format!("{}: {}", device, e)),
+        }
+    }
+    let _ = kmsg.write_all(format!("<6>{} #{}\n", DONE, tag).as_bytes());
+    PreSleep {
+        tag,
+        redisabled,
+        failed,
+    }
+}
+
+/// The result of one pre-sleep re-disable, as kept in [`TIMING_PATH`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SleepToggle {
+    /// Kernel boot the sleep happened in; the timestamps are relative to it
+    pub boot_id: Option<String>,
+    pub redisabled: Vec<String>,
+    #[serde(default)]
+    pub failed: Vec<String>,
+    /// Kernel log time the toggles completed
+    pub done_us: Option<u64>,
+    /// Kernel log time of `PM: suspend entry`
+    pub suspend_entry_us: Option<u64>,
+    pub timing: Timing,
+}
+
+impl SleepToggle {
+    /// Attribute `pre` from the kernel log `records` read after resume.
+    pub fn resolve(pre: &PreSleep, records: &[KmsgRecord], boot_id: Option<String>) -> Self {
+        let (done_us, suspend_entry_us) = find_marks(records, pre.tag);
+        Self {
+            boot_id,
+            redisabled: pre.redisabled.clone(),
+            failed: pre.failed.clone(),
+            done_us,
+            suspend_entry_us,
+            timing: attribute(done_us, suspend_entry_us, LATE_MARGIN_US),
+        }
+    }
+
+    /// One line for the daemon's log.
+    pub fn describe(&self) -> String {
+        let what = if self.redisabled.is_empty() {
+            "wakeup sources checked".to_string()
+        } else {
+            format!("re-disabled {}", self.redisabled.join(", "))
+        };
+        let when = match (self.done_us, self.suspend_entry_us) {
+            (Some(done), Some(entry)) if done <= entry => {
+                format!("{} ms before suspend entry", format_ms(entry - done))
+            }
+            (Some(done), Some(entry)) => {
+                format!("{} ms after suspend entry", format_ms(done - entry))
+            }
+            _ => "timing unknown".to_string(),
+        };
+        let verdict = match self.timing {
+            Timing::TooLate => ", too late",
+            Timing::InTime | Timing::Unknown => "",
+        };
+        format!("Before sleep: {} ({}{})", what, when, verdict)
+    }
+
+    /// The record at `path`; None if there is none or it doesn't parse.
+    pub fn load(path: &Path) -> Option<Self> {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let io_err =
+            |e: std::io::Error| Error::Other(format!("failed to write {}: {}", path.display(), e));
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_err)?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Other(format!("failed to serialize sleep timing: {}", e)))?;
+        std::fs::write(path, json + "\n").map_err(io_err)
+    }
+}
+
+fn format_ms(us: u64) -> String {
+    format!("{:.1}", us as f64 / 1000.0)
+}
+
+/// The record at `path` if the last sleep of boot `boot_id` had its
+/// re-disable land too late, for the post-resume warning in `bop status`.
+pub fn late_toggle(path: &Path, boot_id: &str) -> Option<SleepToggle> {
+    SleepToggle::load(path)
+        .filter(|t| t.timing == Timing::TooLate && t.boot_id.as_deref() == Some(boot_id))
+}
+
+/// CLOCK_MONOTONIC in microseconds; a fresh tag per sleep.
+pub fn monotonic_us() -> u64 {
+    nix::time::clock_gettime(nix::time::ClockId::CLOCK_MONOTONIC)
+        .map(|ts| ts.tv_sec() as u64 * 1_000_000 + ts.tv_nsec() as u64 / 1000)
+        .unwrap_or(0)
+}
+
+/// Every record still in the kernel's log buffer. Each read of
+/// `/dev/kmsg` returns one record; one overwritten while reading is
+/// skipped.
+pub fn read_kmsg() -> Result<Vec<KmsgRecord>> {
+    let read_err = |e: std::io::Error| Error::SysfsRead {
+        path: KMSG_PATH.into(),
+        source: e,
+    };
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(nix::fcntl::OFlag::O_NONBLOCK.bits())
+        .open(KMSG_PATH)
+        .map_err(read_err)?;
+    let mut text = String::new();
+    let mut buf = vec![0u8; 8192];
+    loop {
+        match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => text.push_str(&String::from_utf8_lossy(&buf[..n])),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => continue,
+            Err(e) => return Err(read_err(e)),
+        }
+    }
+    Ok(parse_kmsg(&text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply::sysfs_writer::MockWakeupTable;
+
+    const TABLE: &str = "\
+XHC1\tS3\t*enabled\tpci:0000:c1:00.4
+GPP6\tS4\t*disabled\tpci:0000:00:02.2
+LID0\tS4\t*enabled\tplatform:PNP0C0D:00
+";
+
+    fn devices(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    /// A kernel log of one sleep: the marks at `started`/`done` and the
+    /// suspend entry at `entry`, ordered by time as the kernel would.
+    fn log(tag: u64, started: u64, done: u64, entry: u64) -> String {
+        let mut lines = [
+            (started, format!("{} #{}", STARTED, tag)),
+            (done, format!("{} #{}", DONE, tag)),
+            (entry, "PM: suspend entry (s2idle)".to_string()),
+            (entry + 900_000, "PM: suspend exit".to_string()),
+        ];
+        lines.sort();
+        lines
+            .iter()
+            .enumerate()
+            .map(|(seq, (us, msg))| format!("6,{},{},-;{}\n", 100 + seq, us, msg))
+            .collect()
+    }
+
+    #[test]
+    fn test_attribute() {
+        assert_eq!(
+            attribute(Some(1_000_000), Some(1_200_000), LATE_MARGIN_US),
+            Timing::InTime
+        );
+        assert_eq!(
+            attribute(Some(1_195_000), Some(1_200_000), LATE_MARGIN_US),
+            Timing::TooLate
+        );
+        assert_eq!(
+            attribute(Some(1_300_000), Some(1_200_000), LATE_MARGIN_US),
+            Timing::TooLate
+        );
+        assert_eq!(
+            attribute(Some(1_000_000), None, LATE_MARGIN_US),
+            Timing::Unknown
+        );
+        assert_eq!(
+            attribute(None, Some(1_200_000), LATE_MARGIN_US),
+            Timing::Unknown
+        );
+    }
+
+    #[test]
+    fn test_parse_kmsg_skips_continuation_lines() {
+        let records = parse_kmsg(
+            "6,5,1500123,-;PM: suspend entry (deep)\n SUBSYSTEM=acpi\n12,6,1500200,-,caller=T1;bop: x\n",
+        );
+        assert_eq!(
+            records,
+            [
+                KmsgRecord {
+                    timestamp_us: 1_500_123,
+                    message: "PM: suspend entry (deep)".to_string(),
+                },
+                KmsgRecord {
+                    timestamp_us: 1_500_200,
+                    message: "bop: x".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_marks_of_an_earlier_sleep_are_ignored() {
+        let text = log(2, 100, 200, 5_000) + &log(20, 9_000, 9_500, 9_800);
+        let records = parse_kmsg(&text);
+        assert_eq!(find_marks(&records, 20), (Some(9_500), Some(9_800)));
+        assert_eq!(find_marks(&records, 2), (Some(200), Some(5_000)));
+        assert_eq!(find_marks(&records, 3), (None, None));
+    }
+
+    #[test]
+    fn test_toggle_after_suspend_entry_is_too_late() {
+        let pre = PreSleep {
+            tag: 7,
+            redisabled: devices(&["XHC1"]),
+            failed: Vec::new(),
+        };
+        // The daemon was frozen mid-work: its done mark follows resume
+        let records = parse_kmsg(&log(7, 2_000_000, 3_100_000, 2_050_000));
+        let toggle = SleepToggle::resolve(&pre, &records, Some("b1".to_string()));
+        assert_eq!(toggle.timing, Timing::TooLate);
+        assert_eq!(
+            toggle.describe(),
+            "Before sleep: re-disabled XHC1 (1050.0 ms after suspend entry, too late)"
+        );
+
+        let records = parse_kmsg(&log(7, 2_000_000, 2_000_400, 2_050_000));
+        let toggle = SleepToggle::resolve(&pre, &records, None);
+        assert_eq!(toggle.timing, Timing::InTime);
+        assert_eq!(
+            toggle.describe(),
+            "Before sleep: re-disabled XHC1 (49.6 ms before suspend entry)"
+        );
+    }
+
+    #[test]
+    fn test_before_sleep_redisables_between_marks() {
+        let mut table = MockWakeupTable::new(TABLE);
+        let mut kmsg = Vec::new();
+        let pre = before_sleep(
+            &devices(&["XHC1", "GPP6", "NHI0"]),
+            &mut table,
+            &mut kmsg,
+            42,
+        );
+        assert_eq!(pre.redisabled, ["XHC1"]);
+        assert!(pre.failed.is_empty());
+        assert_eq!(table.writes, ["XHC1"]);
+        assert_eq!(table.enabled("LID0"), Some(true));
+        assert_eq!(
+            String::from_utf8(kmsg).unwrap(),
+            format!("<6>{} #42\n<6>{} #42\n", STARTED, DONE)
+        );
+    }
+
+    #[test]
+    fn test_late_toggle_only_for_this_boot() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("sleep_toggle.json");
+        assert!(late_toggle(&path, "b1").is_none());
+
+        let mut toggle = SleepToggle {
+            boot_id: Some("b1".to_string()),
+            redisabled: devices(&["XHC1"]),
+            failed: Vec::new(),
+            done_us: Some(3_000),
+            suspend_entry_us: Some(2_000),
+            timing: Timing::TooLate,
+        };
+        toggle.save(&path).unwrap();
+        assert_eq!(late_toggle(&path, "b1"), Some(toggle.clone()));
+        assert!(late_toggle(&path, "b2").is_none());
+
+        toggle.timing = Timing::InTime;
+        toggle.save(&path).unwrap();
+        assert!(late_toggle(&path, "b1").is_none());
+    }
+}
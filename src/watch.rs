@@ -0,0 +1,244 @@
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+const TICK: Duration = Duration::from_millis(100);
+/// How often files are stat'ed when inotify isn't available.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What a stat says about a file; `None` when it doesn't exist.
+type Fingerprint = Option<(SystemTime, u64)>;
+
+fn fingerprint(path: &PathBuf) -> Fingerprint {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+/// Watches a set of files for changes. Uses inotify on their parent
+/// directories, so editors that save by rename-over are caught too, and
+/// falls back to polling when inotify or a directory isn't available.
+pub struct FileWatcher {
+    paths: Vec<PathBuf>,
+    fingerprints: Vec<Fingerprint>,
+    inotify: Option<Inotify>,
+}
+
+impl FileWatcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let mut watcher = Self::polling(paths);
+        watcher.inotify = watcher.init_inotify();
+        watcher
+    }
+
+    /// A watcher that only polls.
+    pub fn polling(paths: Vec<PathBuf>) -> Self {
+        let fingerprints = paths.iter().map(fingerprint).collect();
+        Self {
+            paths,
+            fingerprints,
+            inotify: None,
+        }
+    }
+
+    fn init_inotify(&self) -> Option<Inotify> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC).ok()?;
+        let mask = AddWatchFlags::IN_CLOSE_WRITE
+            | AddWatchFlags::IN_MOVED_TO
+            | AddWatchFlags::IN_MOVED_FROM
+            | AddWatchFlags::IN_CREATE
+            | AddWatchFlags::IN_DELETE;
+        for path in &self.paths {
+            // A missing directory can't be watched; poll everything instead
+            let dir = path.parent()?;
+            inotify.add_watch(dir, mask).ok()?;
+        }
+        Some(inotify)
+    }
+
+    pub fn uses_inotify(&self) -> bool {
+        self.inotify.is_some()
+    }
+
+    fn file_names(&self) -> Vec<OsString> {
+        self.paths
+            .iter()
+            .filter_map(|p| p.file_name().map(OsString::from))
+            .collect()
+    }
+
+    /// Whether inotify reported an event for one of the watched files.
+    fn inotify_fired(&self) -> bool {
+        let Some(inotify) = &self.inotify else {
+            return false;
+        };
+        let names = self.file_names();
+        let mut fired = false;
+        // Non-blocking: EAGAIN once the queue is drained
+        while let Ok(events) = inotify.read_events() {
+            if events.is_empty() {
+                break;
+            }
+            fired |= events
+                .iter()
+                .any(|e| e.name.as_ref().is_some_and(|n| names.contains(n)));
+        }
+        fired
+    }
+
+    /// Whether any file's stat changed since the last settled change.
+    fn fingerprints_changed(&self) -> bool {
+        self.paths
+            .iter()
+            .zip(&self.fingerprints)
+            .any(|(path, old)| fingerprint(path) != *old)
+    }
+
+    /// Block until a watched file changes and then stays quiet for
+    /// `debounce` (editors often write several times per save). Returns
+    /// false without waiting further once `stop` is set.
+    pub fn wait_for_change(&mut self, debounce: Duration, stop: &AtomicBool) -> bool {
+        let mut last_change: Option<Instant> = None;
+        let mut last_poll = Instant::now();
+
+        while !stop.load(Ordering::Relaxed) {
+            std::thread::sleep(TICK);
+
+            let changed = if self.inotify.is_some() {
+                self.inotify_fired()
+            } else if last_poll.elapsed() >= POLL_INTERVAL || last_change.is_some() {
+                last_poll = Instant::now();
+                let changed = self.fingerprints_changed();
+                if changed {
+                    self.fingerprints = self.paths.iter().map(fingerprint).collect();
+                }
+                changed
+            } else {
+                false
+            };
+
+            if changed {
+                last_change = Some(Instant::now());
+            } else if last_change.is_some_and(|t| t.elapsed() >= debounce) {
+                self.fingerprints = self.paths.iter().map(fingerprint).collect();
+                return true;
+            }
+        }
+        false
+    }
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_: nix::libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+/// Turn Ctrl+C into a flag a watch loop can check, so it can stop cleanly
+/// instead of the process being killed mid-output.
+pub fn interrupt_flag() -> &'static AtomicBool {
+    use nix::sys::signal::{SaFlags, SigAction, SigHandler, SigSet, Signal, sigaction};
+
+    let action = SigAction::new(
+        SigHandler::Handler(on_interrupt),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+    let _ = unsafe { sigaction(Signal::SIGINT, &action) };
+    &INTERRUPTED
+}
+
+/// Ctrl+C handling for a bounded stretch, e.g. a prompt: while the guard
+/// lives SIGINT only sets [`interrupt_flag`]'s flag, and dropping it puts
+/// the previous disposition back.
+pub struct InterruptScope {
+    previous: Option<nix::sys::signal::SigAction>,
+}
+
+impl InterruptScope {
+    pub fn new() -> Self {
+        use nix::sys::signal::{SaFlags, SigAction, SigHandler, SigSet, Signal, sigaction};
+
+        INTERRUPTED.store(false, Ordering::Relaxed);
+        let action = SigAction::new(
+            SigHandler::Handler(on_interrupt),
+            SaFlags::empty(),
+            SigSet::empty(),
+        );
+        // SAFETY: as in interrupt_flag
+        let previous = unsafe { sigaction(Signal::SIGINT, &action) }.ok();
+        Self { previous }
+    }
+
+    pub fn flag(&self) -> &'static AtomicBool {
+        &INTERRUPTED
+    }
+}
+
+impl Default for InterruptScope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for InterruptScope {
+    fn drop(&mut self) {
+        if let Some(previous) = &self.previous {
+            // SAFETY: restores the disposition that was installed before
+            let _ =
+                unsafe { nix::sys::signal::sigaction(nix::sys::signal::Signal::SIGINT, previous) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn change_after(path: PathBuf, delay: Duration) -> std::thread::JoinHandle<()> {
+        std::thread::spawn(move || {
+            std::thread::sleep(delay);
+            std::fs::write(&path, "preset = \"saver\"\n").unwrap();
+        })
+    }
+
+    #[test]
+    fn test_inotify_detects_change() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(&path, "preset = \"moderate\"\n").unwrap();
+
+        let mut watcher = FileWatcher::new(vec![path.clone()]);
+        assert!(watcher.uses_inotify());
+        let writer = change_after(path, Duration::from_millis(150));
+        let stop = AtomicBool::new(false);
+        assert!(watcher.wait_for_change(Duration::from_millis(200), &stop));
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_polling_detects_new_file() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("missing/config.toml");
+
+        // Parent doesn't exist yet, so inotify can't watch it
+        let mut watcher = FileWatcher::new(vec![path.clone()]);
+        assert!(!watcher.uses_inotify());
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let writer = change_after(path, Duration::from_millis(100));
+        let stop = AtomicBool::new(false);
+        assert!(watcher.wait_for_change(Duration::from_millis(200), &stop));
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_stop_flag_ends_wait() {
+        let tmp = TempDir::new().unwrap();
+        let mut watcher = FileWatcher::polling(vec![tmp.path().join("config.toml")]);
+        let stop = AtomicBool::new(true);
+        assert!(!watcher.wait_for_change(Duration::from_millis(200), &stop));
+    }
+}
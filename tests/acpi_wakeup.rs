@@ -0,0 +1,85 @@
+//! The /proc/acpi/wakeup parser against tables with firmware quirks, kept in
+//! tests/fixtures/acpi_wakeup. A table that trips the parser gets a file
+//! there and a test here.
+
+use bop::detect::acpi_wakeup::{self, WakeupEntry};
+use bop::error::Error;
+use std::fs;
+use std::path::Path;
+
+fn corpus(name: &str) -> Vec<WakeupEntry> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/acpi_wakeup")
+        .join(format!("{}.txt", name));
+    let table = fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+    acpi_wakeup::parse(&table)
+}
+
+/// (device, enabled, first node) per entry.
+fn summary(entries: &[WakeupEntry]) -> Vec<(&str, Option<bool>, Option<&str>)> {
+    entries
+        .iter()
+        .map(|e| (e.device.as_str(), e.enabled, e.sysfs_node()))
+        .collect()
+}
+
+#[test]
+fn test_duplicate_names_stay_apart_and_refuse_lookup() {
+    let entries = corpus("duplicate_names");
+    assert_eq!(entries.len(), 6);
+    let gpp6: Vec<_> = entries.iter().filter(|e| e.device == "GPP6").collect();
+    assert_eq!(gpp6.len(), 2);
+    assert_eq!((gpp6[0].index, gpp6[1].index), (0, 1));
+    assert_eq!(gpp6[0].enabled, Some(true));
+    assert_eq!(gpp6[1].enabled, Some(false));
+
+    match acpi_wakeup::lookup(&entries, "GPP6").unwrap_err() {
+        Error::AmbiguousWakeupDevice { device, nodes } => {
+            assert_eq!(device, "GPP6");
+            assert_eq!(nodes, ["pci:0000:00:02.2", "pci:0000:00:02.4"]);
+        }
+        other => panic!("unexpected error: {other}"),
+    }
+    let xhc1 = acpi_wakeup::lookup(&entries, "XHC1").unwrap().unwrap();
+    assert_eq!(xhc1.pci_address(), Some("0000:c1:00.4"));
+}
+
+#[test]
+fn test_status_glued_to_sleep_state() {
+    let entries = corpus("glued_status");
+    assert_eq!(
+        summary(&entries),
+        [
+            ("GP17", Some(true), Some("pci:0000:00:08.1")),
+            ("XHC0", Some(false), Some("pci:0000:c1:00.3")),
+            ("XHC1", Some(true), Some("pci:0000:c1:00.4")),
+            ("LID0", Some(true), Some("platform:PNP0C0D:00")),
+        ]
+    );
+    assert!(entries.iter().all(|e| e.sleep_state.is_some()));
+}
+
+#[test]
+fn test_missing_columns_dont_shift_fields() {
+    let entries = corpus("missing_columns");
+    assert_eq!(
+        summary(&entries),
+        [
+            ("PWRB", Some(true), None),
+            // No valid flag, still a status
+            ("SLPB", Some(false), None),
+            ("ECLK", None, None),
+            ("XHC1", Some(true), Some("pci:0000:c1:00.4")),
+        ]
+    );
+    assert_eq!(entries[0].sleep_state.as_deref(), Some("S4"));
+}
+
+#[test]
+fn test_continuation_lines_add_nodes() {
+    let entries = corpus("continuation_lines");
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].nodes, ["pci:0000:00:02.1", "pci:0000:01:00.0"]);
+    assert_eq!(entries[0].pci_address(), Some("0000:00:02.1"));
+    assert_eq!(entries[1].device, "XHC0");
+}
@@ -0,0 +1,312 @@
+//! End-to-end runs of the compiled `bop` binary against fixture roots.
+//!
+//! Snapshots from tests/fixtures are materialized into a temp dir and the
+//! binary pointed at them with `BOP_SYSFS_ROOT`; `--state-dir` keeps the
+//! state file there too. Nothing needs root or reads the host's sysfs. The
+//! JSON outputs are compared with tests/fixtures/golden/cli; after an
+//! intentional change, regenerate them and commit:
+//!
+//!     UPDATE_GOLDENS=1 cargo test --test cli
+#![cfg(feature = "cli")]
+
+use bop::apply::rollback::PendingRollback;
+use bop::apply::{ApplyState, SysfsChange};
+use bop::snapshot::Snapshot;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// A materialized snapshot, an empty config and a state dir.
+struct Fixture {
+    tmp: TempDir,
+}
+
+impl Fixture {
+    fn new(snapshot: &str) -> Self {
+        let tmp = TempDir::new().unwrap();
+        let snap = Snapshot::load(&fixtures_dir().join(format!("{}.json", snapshot)))
+            .unwrap_or_else(|e| panic!("failed to load {}: {}", snapshot, e));
+        snap.materialize(&tmp.path().join("root")).unwrap();
+        fs::write(tmp.path().join("config.toml"), "").unwrap();
+        fs::create_dir_all(tmp.path().join("state")).unwrap();
+        Self { tmp }
+    }
+
+    fn root(&self) -> PathBuf {
+        self.tmp.path().join("root")
+    }
+
+    fn state_dir(&self) -> PathBuf {
+        self.tmp.path().join("state")
+    }
+
+    /// `bop <args>` with the fixture's root, config and state dir.
+    fn bop(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_bop"))
+            .arg("--config")
+            .arg(self.tmp.path().join("config.toml"))
+            .arg("--state-dir")
+            .arg(self.state_dir())
+            .args(args)
+            .env("BOP_SYSFS_ROOT", self.root())
+            .env("HOME", self.tmp.path())
+            .env("NO_COLOR", "1")
+            .output()
+            .expect("failed to run bop")
+    }
+
+    /// Save a state whose changes are `(path under the root, original,
+    /// applied)`.
+    fn write_state(&self, changes: &[(&str, &str, &str)]) {
+        let state = ApplyState {
+            timestamp: "2026-01-01T00:00:00+00:00".to_string(),
+            sysfs_changes: changes
+                .iter()
+                .map(|(path, original, new)| {
+                    SysfsChange::new(self.root().join(path).to_string_lossy(), *original, *new)
+                })
+                .collect(),
+            ..Default::default()
+        };
+        fs::write(
+            self.state_dir().join("state.json"),
+            serde_json::to_string_pretty(&state).unwrap(),
+        )
+        .unwrap();
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Exit code 0, or a panic with everything the run printed.
+fn assert_success(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "exit {:?}\n--- stdout\n{}\n--- stderr\n{}",
+        output.status.code(),
+        stdout(output),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    stdout(output)
+}
+
+fn check_golden(name: &str, actual: &serde_json::Value) {
+    let actual = format!("{}\n", serde_json::to_string_pretty(actual).unwrap());
+    let path = fixtures_dir().join(format!("golden/cli/{}.json", name));
+
+    if std::env::var_os("UPDATE_GOLDENS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        actual == expected,
+        "{} no longer matches {}\n\n--- expected\n{}\n--- actual\n{}\n\
+         If the change is intentional, run `UPDATE_GOLDENS=1 cargo test --test cli` \
+         and commit the updated golden file.",
+        name,
+        path.display(),
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn test_audit_text_names_profile_and_scores() {
+    let fixture = Fixture::new("framework16_amd");
+    let out = assert_success(&fixture.bop(&["audit"]));
+    assert!(
+        out.contains("Matched profile: Framework Laptop 16"),
+        "{}",
+        out
+    );
+    assert!(out.contains("ASPM policy at 'default'"), "{}", out);
+    assert!(out.contains("Battery score: 89/100"), "{}", out);
+}
+
+#[test]
+fn test_audit_json_matches_golden() {
+    for snapshot in ["framework16_amd", "generic_laptop"] {
+        let fixture = Fixture::new(snapshot);
+        let out = assert_success(&fixture.bop(&["audit", "--json"]));
+        let json: serde_json::Value = serde_json::from_str(&out)
+            .unwrap_or_else(|e| panic!("{}: audit --json is not JSON ({}):\n{}", snapshot, e, out));
+        check_golden(&format!("audit_{}", snapshot), &json);
+    }
+}
+
+#[test]
+fn test_first_audit_after_upgrade_prints_what_changed() {
+    let fixture = Fixture::new("framework16_amd");
+    let history = fixture.state_dir().join("audit-history.json");
+    fs::write(
+        &history,
+        r#"{"version": "0.0.1", "findings": [{"id": "old:/sys/gone", "severity": "low",
+            "description": "A check that went away", "recommended_value": "1"}]}"#,
+    )
+    .unwrap();
+
+    let out = assert_success(&fixture.bop(&["audit"]));
+    assert!(
+        out.contains("What changed for your hardware: bop 0.0.1 →"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("A check that went away (no longer reported)"),
+        "{}",
+        out
+    );
+    assert!(out.contains("(new)"), "{}", out);
+    assert!(
+        fs::read_to_string(&history)
+            .unwrap()
+            .contains(env!("CARGO_PKG_VERSION"))
+    );
+
+    // Once per upgrade
+    let out = assert_success(&fixture.bop(&["audit"]));
+    assert!(!out.contains("What changed"), "{}", out);
+}
+
+#[test]
+fn test_apply_dry_run_lists_plan_and_changes_nothing() {
+    let fixture = Fixture::new("framework16_amd");
+    let policy = fixture
+        .root()
+        .join("sys/module/pcie_aspm/parameters/policy");
+    let before = fs::read_to_string(&policy).unwrap();
+
+    let out = assert_success(&fixture.bop(&["apply", "--dry-run"]));
+    assert!(
+        out.contains("Set PCIe ASPM policy to powersave /sys/module/pcie_aspm/parameters/policy"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("Dry run complete. No changes applied."),
+        "{}",
+        out
+    );
+    assert_eq!(fs::read_to_string(&policy).unwrap(), before);
+    assert!(!fixture.state_dir().join("state.json").exists());
+}
+
+#[test]
+fn test_status_without_state() {
+    let fixture = Fixture::new("framework16_amd");
+    let out = assert_success(&fixture.bop(&["status"]));
+    assert!(out.contains("No optimizations applied"), "{}", out);
+}
+
+#[test]
+fn test_status_reports_drift_from_prepared_state() {
+    let fixture = Fixture::new("framework16_amd");
+    fixture.write_state(&[
+        (
+            "sys/module/pcie_aspm/parameters/policy",
+            "[default] performance powersave powersupersave",
+            "powersave",
+        ),
+        (
+            "sys/firmware/acpi/platform_profile",
+            "performance",
+            "balanced",
+        ),
+    ]);
+
+    let out = assert_success(&fixture.bop(&["status"]));
+    assert!(
+        out.contains("expected: powersave  actual: default"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("1/2 optimizations active, 1 drifted"),
+        "{}",
+        out
+    );
+
+    let out = assert_success(&fixture.bop(&["status", "--json"]));
+    let root = fixture.root().to_string_lossy().into_owned();
+    let mut json: serde_json::Value = serde_json::from_str(&out.replace(&root, "$ROOT")).unwrap();
+    // When the check ran
+    json.as_object_mut().unwrap().remove("full_check_at");
+    check_golden("status_drift", &json);
+}
+
+#[test]
+fn test_status_shows_pending_rollback_and_confirm_keep_without_one() {
+    let fixture = Fixture::new("framework16_amd");
+    let out = assert_success(&fixture.bop(&["apply", "--confirm-keep"]));
+    assert!(out.contains("No automatic rollback pending."), "{}", out);
+
+    let due = chrono::Utc::now() + chrono::TimeDelta::seconds(24 * 60 - 30);
+    let state = ApplyState {
+        timestamp: "2026-01-01T00:00:00+00:00".to_string(),
+        rollback: Some(PendingRollback {
+            unit: "bop-rollback".to_string(),
+            due: due.to_rfc3339(),
+            boot_id: None,
+        }),
+        ..Default::default()
+    };
+    fs::write(
+        fixture.state_dir().join("state.json"),
+        serde_json::to_string_pretty(&state).unwrap(),
+    )
+    .unwrap();
+    let out = assert_success(&fixture.bop(&["status"]));
+    assert!(out.contains("automatic rollback in 24 minutes"), "{}", out);
+}
+
+#[test]
+fn test_revert_restores_fixture_files_and_removes_state() {
+    let fixture = Fixture::new("framework16_amd");
+    let profile = fixture.root().join("sys/firmware/acpi/platform_profile");
+    fs::write(&profile, "low-power\n").unwrap();
+    fixture.write_state(&[(
+        "sys/firmware/acpi/platform_profile",
+        "balanced\n",
+        "low-power",
+    )]);
+
+    let out = assert_success(&fixture.bop(&["revert"]));
+    assert!(out.contains("low-power -> balanced"), "{}", out);
+    assert!(out.contains("Revert complete."), "{}", out);
+    assert_eq!(fs::read_to_string(&profile).unwrap(), "balanced");
+    assert!(!fixture.state_dir().join("state.json").exists());
+
+    let out = assert_success(&fixture.bop(&["revert"]));
+    assert!(out.contains("Nothing to revert"), "{}", out);
+}
+
+#[test]
+fn test_wake_list_shows_controllers_without_the_header() {
+    let fixture = Fixture::new("framework16_amd");
+    let out = assert_success(&fixture.bop(&["wake", "list"]));
+    assert!(out.contains("XHC0  enabled  0000:c1:00.3"), "{}", out);
+    assert!(out.contains("XHC1  disabled  0000:c1:00.4"), "{}", out);
+    assert!(!out.contains("Device"), "{}", out);
+}
+
+#[test]
+fn test_exit_codes_for_usage_and_runtime_errors() {
+    let fixture = Fixture::new("framework16_amd");
+    let usage = fixture.bop(&["no-such-command"]);
+    assert_eq!(usage.status.code(), Some(2));
+    let conflicting = fixture.bop(&["--preset", "saver", "--aggressive", "audit"]);
+    assert_eq!(conflicting.status.code(), Some(2));
+
+    let failed = fixture.bop(&["wake", "enable", "NOPE"]);
+    assert_eq!(failed.status.code(), Some(1));
+}
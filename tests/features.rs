@@ -0,0 +1,52 @@
+//! The core library (`--no-default-features`) is meant for embedding and must
+//! not pull in the CLI stack.
+use std::process::Command;
+
+const CLI_ONLY_CRATES: &[&str] = &[
+    "clap",
+    "clap_complete",
+    "clap_mangen",
+    "colored",
+    "indicatif",
+];
+
+/// Crate names in the feature-resolved normal dependency graph.
+fn dependency_names(extra_args: &[&str]) -> Vec<String> {
+    let output = Command::new(env!("CARGO"))
+        .args([
+            "tree", "--edges", "normal", "--prefix", "none", "--format", "{p}",
+        ])
+        .args(extra_args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to run cargo tree");
+    assert!(
+        output.status.success(),
+        "cargo tree failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(String::from)
+        .collect()
+}
+
+#[test]
+fn test_core_build_has_no_cli_dependencies() {
+    let core = dependency_names(&["--no-default-features"]);
+    assert!(core.iter().any(|name| name == "serde"), "{:?}", core);
+    for krate in CLI_ONLY_CRATES {
+        assert!(
+            !core.iter().any(|name| name == krate),
+            "{} is in the --no-default-features dependency graph",
+            krate
+        );
+    }
+
+    // And the default build still has them, so the check above means something
+    let full = dependency_names(&[]);
+    for krate in ["clap", "colored"] {
+        assert!(full.iter().any(|name| name == krate), "{} missing", krate);
+    }
+}
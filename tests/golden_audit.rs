@@ -0,0 +1,121 @@
+//! Golden audit scores for the checked-in snapshot fixtures.
+//!
+//! Each snapshot in tests/fixtures is materialized and audited offline; the
+//! battery and AC scores and the findings (severity, power context and id,
+//! in canonical order) must match tests/fixtures/golden exactly,
+//! so any change to weights, checks or scoring shows up as a reviewable diff.
+//!
+//! After an intentional change, regenerate the goldens and commit them:
+//!
+//!     UPDATE_GOLDENS=1 cargo test --test golden_audit
+
+use bop::audit::{self, AuditContext, PowerContext};
+use bop::detect::HardwareInfo;
+use bop::preset::Preset;
+use bop::profile;
+use bop::snapshot::Snapshot;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+const PRESETS: &[Preset] = &[Preset::Moderate, Preset::Supersaver];
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// Score and canonically ordered findings for one snapshot under each preset.
+fn render_audit(snapshot: &str) -> String {
+    let snap = Snapshot::load(&fixtures_dir().join(format!("{}.json", snapshot)))
+        .unwrap_or_else(|e| panic!("failed to load {}: {}", snapshot, e));
+    let tmp = TempDir::new().unwrap();
+    let sysfs = snap.materialize(tmp.path()).unwrap();
+    let hw = HardwareInfo::detect(&sysfs);
+    let profile =
+        profile::detect_profile(&hw).unwrap_or_else(|| panic!("{}: no profile matches", snapshot));
+    let ctx = AuditContext::offline(&sysfs);
+
+    let mut out = format!("profile: {}\n", profile.name());
+    for preset in PRESETS {
+        let mut findings = profile.audit_in(&ctx, &hw, *preset, &preset.knobs());
+        audit::sort_findings(&mut findings);
+        let ids: BTreeSet<String> = findings.iter().map(|f| f.id()).collect();
+        assert_eq!(
+            ids.len(),
+            findings.len(),
+            "{} ({}): finding ids must be unique: {:?}",
+            snapshot,
+            preset,
+            findings.iter().map(|f| f.id()).collect::<Vec<_>>()
+        );
+
+        out.push_str(&format!(
+            "\n[{}]\nbattery score: {}\nac score: {}\n",
+            preset,
+            audit::context_score(&findings, PowerContext::Battery),
+            audit::context_score(&findings, PowerContext::Ac)
+        ));
+        for finding in &findings {
+            out.push_str(&format!(
+                "{:<6} {:<7} {}\n",
+                finding.severity,
+                finding.applies_on,
+                finding.id()
+            ));
+        }
+    }
+    out
+}
+
+fn check_golden(snapshot: &str) {
+    let actual = render_audit(snapshot);
+    let path = fixtures_dir().join(format!("golden/{}.golden", snapshot));
+
+    if std::env::var_os("UPDATE_GOLDENS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        actual == expected,
+        "audit of {} no longer matches {}\n\n--- expected\n{}\n--- actual\n{}\n\
+         If the change is intentional, run `UPDATE_GOLDENS=1 cargo test --test golden_audit` \
+         and commit the updated golden file.",
+        snapshot,
+        path.display(),
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn test_golden_framework16_amd() {
+    check_golden("framework16_amd");
+}
+
+/// Same machine on BIOS 3.05, which fixed the EC wake bug: no BIOS update
+/// finding and no acpi.ec_no_wakeup recommendation.
+#[test]
+fn test_golden_framework16_amd_bios_3_05() {
+    check_golden("framework16_amd_bios_3_05");
+}
+
+#[test]
+fn test_golden_generic_laptop() {
+    check_golden("generic_laptop");
+}
+
+#[test]
+fn test_golden_generic_laptop_acpi_cpufreq() {
+    check_golden("generic_laptop_acpi_cpufreq");
+}
+
+/// Intel hybrid with P- and E-cores at different EPPs: one EPP finding per
+/// core type that needs a change.
+#[test]
+fn test_golden_intel_hybrid() {
+    check_golden("intel_hybrid");
+}
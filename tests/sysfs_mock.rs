@@ -1,7 +1,7 @@
 use bop::apply;
 use bop::audit;
 use bop::config::{BopConfig, EppConfig, EppHint, EppThreshold};
-use bop::detect::HardwareInfo;
+use bop::detect::{HardwareInfo, LazyHardware};
 use bop::preset;
 use bop::preset::Preset;
 use bop::profile;
@@ -33,7 +33,8 @@ fn create_framework16_fixture(root: &Path) {
     )
     .unwrap();
     fs::write(dmi.join("product_family"), "Framework Laptop\n").unwrap();
-    fs::write(dmi.join("bios_version"), "03.05\n").unwrap();
+    // Before the EC wake fix, so the ec_no_wakeup workaround applies
+    fs::write(dmi.join("bios_version"), "03.03\n").unwrap();
 
     // CPU
     let cpu_base = root.join("sys/devices/system/cpu");
@@ -280,6 +281,716 @@ fn test_build_plan_skips_abmlevel_at_or_above_3() {
     );
 }
 
+#[test]
+fn test_build_plan_keeps_acceptable_nvme_latency() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+
+    // 5500us is not bop's value but still inside the accepted range
+    fs::write(
+        tmp.path().join("proc/cmdline"),
+        "root=UUID=abc rw nvme_core.default_ps_max_latency_us=5500\n",
+    )
+    .unwrap();
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    assert!(
+        !plan
+            .kernel_params
+            .iter()
+            .any(|p| p.starts_with("nvme_core.default_ps_max_latency_us"))
+    );
+
+    // Absent, it only matters once there's an NVMe drive
+    fs::write(tmp.path().join("proc/cmdline"), "root=UUID=abc rw\n").unwrap();
+    let hw = HardwareInfo::detect(&sysfs);
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    assert!(
+        !plan
+            .kernel_params
+            .iter()
+            .any(|p| p.starts_with("nvme_core.default_ps_max_latency_us"))
+    );
+    let nvme = tmp.path().join("sys/bus/pci/devices/0000:02:00.0");
+    fs::create_dir_all(&nvme).unwrap();
+    fs::write(nvme.join("class"), "0x010802\n").unwrap();
+    let hw = HardwareInfo::detect(&sysfs);
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    assert!(
+        plan.kernel_params
+            .contains(&"nvme_core.default_ps_max_latency_us=100000".to_string())
+    );
+    fs::remove_dir_all(&nvme).unwrap();
+
+    fs::write(
+        tmp.path().join("proc/cmdline"),
+        "root=UUID=abc rw nvme_core.default_ps_max_latency_us=0\n",
+    )
+    .unwrap();
+    let hw = HardwareInfo::detect(&sysfs);
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    assert!(
+        plan.kernel_params
+            .contains(&"nvme_core.default_ps_max_latency_us=100000".to_string())
+    );
+}
+
+/// Framework 16 on a kernel that selected deep sleep, with AMD's uPEP
+/// device and an FADT advertising S0 low-power idle.
+fn create_framework16_deep_fixture(root: &Path) {
+    create_framework16_fixture(root);
+    let power = root.join("sys/power");
+    fs::write(power.join("mem_sleep"), "s2idle [deep]\n").unwrap();
+    fs::create_dir_all(root.join("sys/bus/acpi/devices/AMDI0007:00")).unwrap();
+    let tables = root.join("sys/firmware/acpi/tables");
+    fs::create_dir_all(&tables).unwrap();
+    let mut facp = vec![0u8; 276];
+    facp[112..116].copy_from_slice(&(1u32 << 21).to_le_bytes());
+    fs::write(tables.join("FACP"), facp).unwrap();
+}
+
+#[test]
+fn test_build_plan_forces_s2idle_when_deep_selected() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_deep_fixture(tmp.path());
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    assert_eq!(hw.platform.mem_sleep.as_deref(), Some("deep"));
+    assert_eq!(hw.platform.low_power_s0_idle, Some(true));
+
+    let findings = audit::sleep::check(&hw, &sysfs, None);
+    let finding = findings
+        .iter()
+        .find(|f| f.path.as_deref() == Some("/sys/power/mem_sleep"))
+        .unwrap();
+    assert_eq!(finding.severity, audit::Severity::Medium);
+    assert_eq!(finding.remedy, audit::Remedy::Apply);
+
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    let write = plan
+        .sysfs_writes
+        .iter()
+        .find(|w| w.path == "/sys/power/mem_sleep")
+        .expect("runtime switch to s2idle");
+    assert_eq!(write.value, "s2idle");
+    assert!(
+        plan.kernel_params
+            .contains(&"mem_sleep_default=s2idle".to_string())
+    );
+
+    // Without the wakeup filter, neither the switch nor the parameter
+    let mut knobs = moderate_knobs();
+    knobs.acpi_wakeup_filter = false;
+    assert!(knobs.kernel_params);
+    let plan = apply::build_plan(&hw, &sysfs, &knobs, None);
+    assert!(
+        !plan
+            .sysfs_writes
+            .iter()
+            .any(|w| w.path == "/sys/power/mem_sleep")
+    );
+    assert!(
+        !plan
+            .kernel_params
+            .iter()
+            .any(|p| p.starts_with("mem_sleep_default"))
+    );
+
+    // Already on s2idle (the stock fixture): nothing to force
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    assert!(
+        !plan
+            .sysfs_writes
+            .iter()
+            .any(|w| w.path == "/sys/power/mem_sleep")
+    );
+    assert!(
+        !plan
+            .kernel_params
+            .iter()
+            .any(|p| p.starts_with("mem_sleep_default"))
+    );
+}
+
+#[test]
+fn test_build_plan_leaves_deep_without_lps0_device() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_deep_fixture(tmp.path());
+    // suspend_stats alone says nothing about the s2idle path
+    fs::remove_dir(tmp.path().join("sys/bus/acpi/devices/AMDI0007:00")).unwrap();
+    fs::create_dir_all(tmp.path().join("sys/power/suspend_stats")).unwrap();
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    assert_eq!(hw.platform.lps0_device, None);
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    assert!(
+        !plan
+            .sysfs_writes
+            .iter()
+            .any(|w| w.path == "/sys/power/mem_sleep")
+    );
+}
+
+#[test]
+fn test_build_plan_leaves_deep_without_low_power_idle() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_deep_fixture(tmp.path());
+    // FADT without LOW_POWER_S0_IDLE_CAPABLE: deep is the platform's choice
+    fs::write(
+        tmp.path().join("sys/firmware/acpi/tables/FACP"),
+        vec![0u8; 276],
+    )
+    .unwrap();
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    assert!(
+        !plan
+            .sysfs_writes
+            .iter()
+            .any(|w| w.path == "/sys/power/mem_sleep")
+    );
+    assert!(
+        !plan
+            .kernel_params
+            .iter()
+            .any(|p| p.starts_with("mem_sleep_default"))
+    );
+}
+
+/// Minimal EDID base block for a Samsung ATNA (AMOLED) panel.
+fn samsung_oled_edid() -> Vec<u8> {
+    let mut b = vec![0u8; 128];
+    b[..8].copy_from_slice(&[0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]);
+    b[8] = 0x4C; // "SDC"
+    b[9] = 0x83;
+    b[18] = 1;
+    b[19] = 4;
+    b[72 + 3] = 0xFE;
+    b[72 + 5..72 + 16].copy_from_slice(b"ATNA40YK20\n");
+    let sum = b[..127].iter().fold(0u8, |acc, x| acc.wrapping_add(*x));
+    b[127] = 0u8.wrapping_sub(sum);
+    b
+}
+
+#[test]
+fn test_oled_panel_skips_abmlevel() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+
+    let connector = tmp.path().join("sys/class/drm/card0-eDP-1");
+    fs::create_dir_all(&connector).unwrap();
+    fs::write(connector.join("edid"), samsung_oled_edid()).unwrap();
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    assert!(hw.display.is_oled());
+
+    let kernel_findings = audit::kernel_params::check(&hw);
+    assert!(
+        !kernel_findings
+            .iter()
+            .any(|f| f.recommended_value.contains("abmlevel")),
+        "ABM should not be recommended on OLED"
+    );
+
+    let display_findings = audit::display::check(&hw, &sysfs);
+    assert!(
+        display_findings
+            .iter()
+            .any(|f| f.description.contains("OLED"))
+    );
+
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    assert!(
+        !plan
+            .kernel_params
+            .iter()
+            .any(|p| p.starts_with("amdgpu.abmlevel"))
+    );
+    // Other kernel params are still planned
+    assert!(
+        plan.kernel_params
+            .contains(&"acpi.ec_no_wakeup=1".to_string())
+    );
+}
+
+const PANEL_POWER_SAVINGS: &str = "/sys/class/drm/card0-eDP-1/amdgpu/panel_power_savings";
+
+/// Framework 16 with ABM set through neither, either or both of its
+/// mechanisms: `panel_power_savings` at runtime and `amdgpu.abmlevel` at boot.
+fn create_abm_fixture(root: &Path, runtime: Option<u32>, param: Option<u32>) {
+    create_framework16_fixture(root);
+    if let Some(level) = runtime {
+        let amdgpu = root.join("sys/class/drm/card0-eDP-1/amdgpu");
+        fs::create_dir_all(&amdgpu).unwrap();
+        fs::write(amdgpu.join("panel_power_savings"), format!("{}\n", level)).unwrap();
+    }
+    let param = param.map(|level| format!("amdgpu.abmlevel={}", level));
+    set_cmdline(root, param.as_deref().unwrap_or_default());
+}
+
+fn abm_findings(hw: &HardwareInfo) -> Vec<audit::Finding> {
+    audit::kernel_params::check(hw)
+        .into_iter()
+        .filter(|f| f.description.contains("ABM") || f.description.contains("Backlight"))
+        .collect()
+}
+
+#[test]
+fn test_abm_neither_mechanism_set() {
+    let tmp = TempDir::new().unwrap();
+    create_abm_fixture(tmp.path(), None, None);
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    assert!(hw.gpu.panel_power_savings_path.is_none());
+
+    let findings = abm_findings(&hw);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].id(), "kernel:/proc/cmdline#amdgpu.abmlevel");
+
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    assert!(
+        plan.kernel_params
+            .contains(&"amdgpu.abmlevel=3".to_string())
+    );
+    assert!(plan.kernel_params_remove.is_empty());
+}
+
+#[test]
+fn test_abm_runtime_only_is_raised_at_runtime() {
+    let tmp = TempDir::new().unwrap();
+    create_abm_fixture(tmp.path(), Some(0), None);
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    assert_eq!(hw.gpu.panel_power_savings, Some(0));
+
+    let findings = abm_findings(&hw);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].severity, audit::Severity::Medium);
+    assert_eq!(findings[0].path.as_deref(), Some(PANEL_POWER_SAVINGS));
+    assert_eq!(findings[0].recommended_value, "panel_power_savings=3");
+    assert_eq!(
+        findings[0].evidence,
+        vec![(PANEL_POWER_SAVINGS.to_string(), "0\n".to_string())]
+    );
+
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    let write = plan
+        .sysfs_writes
+        .iter()
+        .find(|w| w.path == PANEL_POWER_SAVINGS)
+        .expect("runtime ABM write");
+    assert_eq!(write.value, "3");
+    assert!(
+        !plan
+            .kernel_params
+            .iter()
+            .any(|p| p.starts_with("amdgpu.abmlevel"))
+    );
+    assert!(plan.kernel_params_remove.is_empty());
+}
+
+#[test]
+fn test_abm_param_only_keeps_using_the_param() {
+    let tmp = TempDir::new().unwrap();
+    create_abm_fixture(tmp.path(), None, Some(1));
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+
+    let findings = abm_findings(&hw);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].description, "ABM level below recommended");
+    assert_eq!(findings[0].current_value, "amdgpu.abmlevel=1");
+
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    assert!(
+        plan.kernel_params
+            .contains(&"amdgpu.abmlevel=3".to_string())
+    );
+    assert!(plan.kernel_params_remove.is_empty());
+    assert!(
+        !plan
+            .sysfs_writes
+            .iter()
+            .any(|w| w.path.ends_with("panel_power_savings"))
+    );
+}
+
+#[test]
+fn test_abm_both_set_differently_reconciles_to_runtime() {
+    let tmp = TempDir::new().unwrap();
+    create_abm_fixture(tmp.path(), Some(3), Some(1));
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+
+    let findings = abm_findings(&hw);
+    assert_eq!(findings.len(), 1, "one finding covers both mechanisms");
+    let finding = &findings[0];
+    assert_eq!(finding.severity, audit::Severity::Medium);
+    assert!(finding.description.contains("different levels"));
+    assert_eq!(
+        finding.current_value,
+        "panel_power_savings=3 now, amdgpu.abmlevel=1 again after resume"
+    );
+    assert_eq!(finding.path.as_deref(), Some(PANEL_POWER_SAVINGS));
+
+    // Runtime is kept (and written, so the unit replays it at boot); the
+    // parameter goes, backed up like any other kernel param change
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    let write = plan
+        .sysfs_writes
+        .iter()
+        .find(|w| w.path == PANEL_POWER_SAVINGS)
+        .expect("runtime ABM write");
+    assert_eq!(write.value, "3");
+    assert_eq!(plan.kernel_params_remove, vec!["amdgpu.abmlevel"]);
+    assert!(
+        !plan
+            .kernel_params
+            .iter()
+            .any(|p| p.starts_with("amdgpu.abmlevel"))
+    );
+    assert!(
+        plan.explanations
+            .iter()
+            .any(|e| e.subject == "amdgpu.abmlevel" && e.included)
+    );
+
+    // Pinning the runtime attribute leaves the parameter as the only ABM
+    let mut config = BopConfig::default();
+    config.pins.insert(PANEL_POWER_SAVINGS, "leave");
+    let pinned = apply::build_plan(&hw, &sysfs, &moderate_knobs(), Some(&config));
+    assert!(pinned.kernel_params_remove.is_empty());
+}
+
+#[test]
+fn test_abm_both_set_to_the_same_level_is_left_alone() {
+    let tmp = TempDir::new().unwrap();
+    create_abm_fixture(tmp.path(), Some(3), Some(3));
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+
+    assert!(abm_findings(&hw).is_empty());
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    assert!(plan.kernel_params_remove.is_empty());
+    assert!(
+        !plan
+            .sysfs_writes
+            .iter()
+            .any(|w| w.path == PANEL_POWER_SAVINGS)
+    );
+}
+
+fn add_pci_device(root: &Path, addr: &str, vendor: &str, device: &str, control: &str) {
+    let dev = root.join("sys/bus/pci/devices").join(addr);
+    fs::create_dir_all(dev.join("power")).unwrap();
+    fs::write(dev.join("power/control"), format!("{}\n", control)).unwrap();
+    fs::write(dev.join("vendor"), format!("{}\n", vendor)).unwrap();
+    fs::write(dev.join("device"), format!("{}\n", device)).unwrap();
+    fs::write(dev.join("class"), "0xff0000\n").unwrap();
+}
+
+#[test]
+fn test_sd_reader_needing_module_param_gets_modprobe_config() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    add_pci_device(tmp.path(), "0000:02:00.0", "0x10ec", "0x525a", "on");
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+
+    assert!(
+        plan.sysfs_writes
+            .iter()
+            .any(|w| w.path == "/sys/bus/pci/devices/0000:02:00.0/power/control")
+    );
+    assert_eq!(plan.modprobe_configs.len(), 1);
+    assert_eq!(plan.modprobe_configs[0].filename, "bop-rtsx_pci.conf");
+    assert!(
+        plan.modprobe_configs[0]
+            .content
+            .contains("options rtsx_pci enable_runtime_pm=1")
+    );
+
+    let findings = audit::pci_power::check_with_knobs(&hw, &moderate_knobs());
+    assert!(findings.iter().any(|f| f.description
+        == "Realtek RTS525A known to require rtsx_pci.enable_runtime_pm=1 for runtime PM"));
+}
+
+/// Framework 16 expansion-bay dGPU behind root port 0000:00:01.1, linked
+/// from /sys/bus/pci/devices the way the kernel lays it out.
+fn add_dgpu(root: &Path, current_speed: &str, bandwidth_control: bool) {
+    let real = root.join("sys/devices/pci0000:00/0000:00:01.1/0000:03:00.0");
+    fs::create_dir_all(real.join("power")).unwrap();
+    for (file, value) in [
+        ("vendor", "0x1002"),
+        ("device", "0x7480"),
+        ("class", "0x030000"),
+        ("boot_vga", "0"),
+        ("gpu_busy_percent", "0"),
+        ("power/control", "auto"),
+        ("power/runtime_status", "active"),
+        ("current_link_speed", current_speed),
+        ("current_link_width", "8"),
+        ("max_link_speed", "16.0 GT/s PCIe"),
+        ("max_link_width", "8"),
+    ] {
+        fs::write(real.join(file), format!("{}\n", value)).unwrap();
+    }
+    let devices = root.join("sys/bus/pci/devices");
+    fs::create_dir_all(&devices).unwrap();
+    std::os::unix::fs::symlink(
+        "../../../devices/pci0000:00/0000:00:01.1/0000:03:00.0",
+        devices.join("0000:03:00.0"),
+    )
+    .unwrap();
+
+    if bandwidth_control {
+        let cdev = root.join("sys/class/thermal/cooling_device7");
+        fs::create_dir_all(&cdev).unwrap();
+        fs::write(cdev.join("type"), "PCIe_Port_Link_Speed_0000:00:01.1\n").unwrap();
+        fs::write(cdev.join("cur_state"), "0\n").unwrap();
+        fs::write(cdev.join("max_state"), "3\n").unwrap();
+    }
+}
+
+#[test]
+fn test_idle_dgpu_at_gen4_is_downgraded_on_supersaver() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    add_dgpu(tmp.path(), "16.0 GT/s PCIe", true);
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+
+    let findings = audit::gpu_power::check_link(&hw, &supersaver_knobs());
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].severity, audit::Severity::Info);
+    assert_eq!(findings[0].remedy, audit::Remedy::Apply);
+    assert_eq!(findings[0].current_value, "Gen4 x8 (max Gen4 x8)");
+
+    let write_path = "/sys/class/thermal/cooling_device7/cur_state";
+    let plan = apply::build_plan(&hw, &sysfs, &supersaver_knobs(), None);
+    let write = plan
+        .sysfs_writes
+        .iter()
+        .find(|w| w.path == write_path)
+        .expect("supersaver pins the dGPU link");
+    assert_eq!(write.value, "3");
+
+    // Moderate reports it but leaves the link alone
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    assert!(!plan.sysfs_writes.iter().any(|w| w.path == write_path));
+    assert_eq!(
+        audit::gpu_power::check_link(&hw, &moderate_knobs()).len(),
+        1
+    );
+}
+
+#[test]
+fn test_idle_dgpu_without_bandwidth_control_is_manual_only() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    add_dgpu(tmp.path(), "16.0 GT/s PCIe", false);
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+
+    let findings = audit::gpu_power::check_link(&hw, &supersaver_knobs());
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].remedy, audit::Remedy::ManualOnly);
+
+    let plan = apply::build_plan(&hw, &sysfs, &supersaver_knobs(), None);
+    assert!(!plan.sysfs_writes.iter().any(|w| w.path.contains("thermal")));
+    let explanation = plan
+        .explanations
+        .iter()
+        .find(|e| e.subject == "dGPU link 0000:03:00.0")
+        .unwrap();
+    assert!(!explanation.included);
+}
+
+#[test]
+fn test_already_downgraded_dgpu_link_is_left_alone() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    add_dgpu(tmp.path(), "2.5 GT/s PCIe", true);
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+
+    assert!(audit::gpu_power::check_link(&hw, &supersaver_knobs()).is_empty());
+    let plan = apply::build_plan(&hw, &sysfs, &supersaver_knobs(), None);
+    assert!(!plan.sysfs_writes.iter().any(|w| w.path.contains("thermal")));
+}
+
+#[test]
+fn test_package_power_limit_is_capped_above_the_profile_floor() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    let zone = tmp.path().join("sys/class/powercap/intel-rapl:0");
+    fs::create_dir_all(&zone).unwrap();
+    fs::write(zone.join("name"), "package-0\n").unwrap();
+    fs::write(zone.join("constraint_0_power_limit_uw"), "45000000\n").unwrap();
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let limit_path = "/sys/class/powercap/intel-rapl:0/constraint_0_power_limit_uw";
+    let knobs = |watts| preset::PresetKnobs {
+        package_power_limit_w: Some(watts),
+        ..supersaver_knobs()
+    };
+
+    let plan = apply::build_plan(&hw, &sysfs, &knobs(15), None);
+    let write = plan
+        .sysfs_writes
+        .iter()
+        .find(|w| w.path == limit_path)
+        .expect("supersaver caps the package");
+    assert_eq!(write.value, "15000000");
+
+    // Framework 16's floor is 10W
+    let plan = apply::build_plan(&hw, &sysfs, &knobs(8), None);
+    assert!(!plan.sysfs_writes.iter().any(|w| w.path == limit_path));
+    let explanation = plan
+        .explanations
+        .iter()
+        .find(|e| e.subject == "package power limit")
+        .unwrap();
+    assert!(!explanation.included);
+
+    // Unset, the limit is only reported
+    let plan = apply::build_plan(&hw, &sysfs, &supersaver_knobs(), None);
+    assert!(!plan.sysfs_writes.iter().any(|w| w.path == limit_path));
+    let profile = profile::detect_profile(&hw).unwrap();
+    let findings = profile.audit_with_opts(&hw, Preset::Supersaver, &supersaver_knobs());
+    assert!(
+        findings
+            .iter()
+            .any(|f| f.path.as_deref() == Some(limit_path))
+    );
+}
+
+/// Connect displays to the iGPU (card0) and set its memory clock table.
+fn add_displays(root: &Path, connectors: &[&str], mclk: &str) {
+    for connector in connectors {
+        let dir = root.join("sys/class/drm").join(connector);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("status"), "connected\n").unwrap();
+        fs::write(dir.join("enabled"), "enabled\n").unwrap();
+    }
+    fs::write(root.join("sys/class/drm/card0/device/pp_dpm_mclk"), mclk).unwrap();
+}
+
+const MCLK_PINNED: &str = "0: 400Mhz \n1: 800Mhz \n2: 1000Mhz *\n";
+
+#[test]
+fn test_multi_display_mclk_pinning_is_explained() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    add_displays(
+        tmp.path(),
+        &["card0-eDP-1", "card0-DP-1", "card0-DP-2"],
+        MCLK_PINNED,
+    );
+
+    let hw = HardwareInfo::detect(&SysfsRoot::new(tmp.path()));
+    let findings = audit::gpu_power::check_multi_display_mclk(&hw);
+    assert_eq!(findings.len(), 1);
+    let finding = &findings[0];
+    assert_eq!(finding.severity, audit::Severity::Info);
+    assert_eq!(finding.remedy, audit::Remedy::ManualOnly);
+    assert_eq!(
+        finding.description,
+        "iGPU memory clock pinned at 1000Mhz with 3 displays (2 external)"
+    );
+    assert_eq!(
+        finding.current_value,
+        "level 2 of 2 (card0-DP-1, card0-DP-2, card0-eDP-1)"
+    );
+    assert!(
+        finding
+            .evidence
+            .iter()
+            .any(|(_, value)| value.contains("1000Mhz *"))
+    );
+
+    let profile = profile::detect_profile(&hw).unwrap();
+    let findings = profile.audit_with_opts(&hw, Preset::Moderate, &moderate_knobs());
+    assert!(findings.iter().any(|f| f.description.contains("pinned at")));
+}
+
+#[test]
+fn test_single_display_mclk_is_not_reported() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    // The internal panel alone, clock at the top for a moment
+    add_displays(tmp.path(), &["card0-eDP-1"], MCLK_PINNED);
+    let hw = HardwareInfo::detect(&SysfsRoot::new(tmp.path()));
+    assert!(audit::gpu_power::check_multi_display_mclk(&hw).is_empty());
+
+    // Several displays, but the clock comes down
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    add_displays(
+        tmp.path(),
+        &["card0-eDP-1", "card0-DP-1"],
+        "0: 400Mhz *\n1: 800Mhz \n2: 1000Mhz \n",
+    );
+    let hw = HardwareInfo::detect(&SysfsRoot::new(tmp.path()));
+    assert!(audit::gpu_power::check_multi_display_mclk(&hw).is_empty());
+}
+
+#[test]
+fn test_sd_reader_never_auto_is_left_on() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    add_pci_device(tmp.path(), "0000:03:00.0", "0x17a0", "0x9750", "on");
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+
+    assert!(
+        !plan
+            .sysfs_writes
+            .iter()
+            .any(|w| w.path.contains("0000:03:00.0"))
+    );
+    assert!(plan.modprobe_configs.is_empty());
+    // Other non-auto devices are still planned
+    assert!(
+        plan.sysfs_writes
+            .iter()
+            .any(|w| w.path.contains("0000:00:00.0"))
+    );
+
+    let findings = audit::pci_power::check_with_knobs(&hw, &moderate_knobs());
+    assert!(findings.iter().any(|f| {
+        f.description
+            .starts_with("Genesys GL9750 runtime PM left off")
+    }));
+    // The skipped reader doesn't count toward the aggregate finding
+    let aggregate = findings
+        .iter()
+        .find(|f| {
+            f.description
+                .contains("PCI devices not using runtime power management")
+        })
+        .unwrap();
+    assert!(aggregate.description.starts_with("3/"));
+}
+
 #[test]
 fn test_battery_info() {
     let tmp = TempDir::new().unwrap();
@@ -378,6 +1089,29 @@ fn test_apply_plan_only_disables_usb_wake_sources() {
     assert!(!plan.acpi_wakeup_disable.contains(&"SLPB".to_string()));
 }
 
+#[test]
+fn test_apply_plan_skips_duplicated_wake_source_names() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    // Writing XHC1 would toggle whichever is listed first
+    fs::write(
+        tmp.path().join("proc/acpi/wakeup"),
+        "\
+XHC0\tS3\t*enabled\tpci:0000:c1:00.3
+XHC1\tS3\t*enabled\tpci:0000:c1:00.4
+XHC1\tS3\t*enabled\tpci:0000:c4:00.3
+XHC3\tS3\t*enabled\tpci:0000:c3:00.3
+",
+    )
+    .unwrap();
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+
+    assert_eq!(plan.acpi_wakeup_disable, ["XHC3"]);
+}
+
 #[test]
 fn test_apply_plan_does_not_disable_usb4_nhi_wake_source() {
     let tmp = TempDir::new().unwrap();
@@ -483,6 +1217,43 @@ fn test_audit_dgpu_not_d3cold() {
     assert_eq!(dgpu_finding.severity, audit::Severity::Medium);
 }
 
+#[test]
+fn test_findings_cite_what_detection_read() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    let dgpu = tmp.path().join("sys/class/drm/card1/device");
+    fs::create_dir_all(&dgpu).unwrap();
+    fs::write(dgpu.join("vendor"), "0x1002\n").unwrap();
+    fs::write(dgpu.join("power_state"), "D0\n").unwrap();
+
+    let sysfs = SysfsRoot::new(tmp.path()).counting_reads();
+    let hw = HardwareInfo::detect(&sysfs);
+    let reads = sysfs.read_count();
+
+    let mut findings = audit::kernel_params::check(&hw);
+    findings.extend(audit::gpu_power::check(&hw));
+    assert_eq!(sysfs.read_count(), reads, "evidence must not re-read");
+
+    let cmdline = fs::read_to_string(tmp.path().join("proc/cmdline")).unwrap();
+    let kernel = findings.iter().find(|f| f.category == "Kernel").unwrap();
+    assert_eq!(
+        kernel.evidence,
+        vec![("/proc/cmdline".to_string(), cmdline)]
+    );
+
+    let dgpu = findings
+        .iter()
+        .find(|f| f.description.contains("D3cold"))
+        .unwrap();
+    assert_eq!(
+        dgpu.evidence,
+        vec![(
+            "/sys/class/drm/card1/device/power_state".to_string(),
+            "D0\n".to_string()
+        )]
+    );
+}
+
 #[test]
 fn test_audit_dgpu_d3cold_no_finding() {
     let tmp = TempDir::new().unwrap();
@@ -557,6 +1328,114 @@ fn test_build_plan_includes_usb_autosuspend() {
     );
 }
 
+/// Add a USB device: product, `bmAttributes`, `bMaxPower`, then
+/// `power/control`, `power/runtime_status` and `power/persist`.
+fn add_usb_device(root: &Path, name: &str, descriptor: [&str; 3], power: [&str; 3]) {
+    let dev = root.join("sys/bus/usb/devices").join(name);
+    fs::create_dir_all(dev.join("power")).unwrap();
+    for (file, value) in ["product", "bmAttributes", "bMaxPower"]
+        .iter()
+        .zip(descriptor)
+        .chain(
+            ["power/control", "power/runtime_status", "power/persist"]
+                .iter()
+                .zip(power),
+        )
+    {
+        fs::write(dev.join(file), format!("{}\n", value)).unwrap();
+    }
+}
+
+#[test]
+fn test_usb_autosuspend_uses_descriptor_factors() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    // Receiver: remote wakeup, bus powered
+    add_usb_device(
+        tmp.path(),
+        "1-1",
+        ["Unifying Receiver", "a0", "98mA"],
+        ["on", "active", "1"],
+    );
+    // Webcam: no remote wakeup, in use
+    add_usb_device(
+        tmp.path(),
+        "1-2",
+        ["HD Webcam", "80", "500mA"],
+        ["on", "active", "1"],
+    );
+    // Audio DAC: no remote wakeup, persist off
+    add_usb_device(
+        tmp.path(),
+        "1-3",
+        ["USB Audio DAC", "80", "100mA"],
+        ["on", "active", "0"],
+    );
+    // Card reader: remote wakeup, in use
+    add_usb_device(
+        tmp.path(),
+        "1-4",
+        ["Card Reader", "a0", "200mA"],
+        ["on", "active", "1"],
+    );
+    // Self-powered hub with remote wakeup, already auto
+    add_usb_device(
+        tmp.path(),
+        "2-1",
+        ["USB Hub", "e0", "0mA"],
+        ["auto", "suspended", "1"],
+    );
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+
+    let planned = |name: &str| {
+        plan.sysfs_writes
+            .iter()
+            .any(|w| w.path == format!("/sys/bus/usb/devices/{}/power/control", name))
+    };
+    assert!(planned("1-1"), "remote-wakeup receiver gets autosuspend");
+    assert!(
+        !planned("1-2"),
+        "active webcam without remote wakeup is left on"
+    );
+    assert!(
+        !planned("1-3"),
+        "active DAC without remote wakeup is left on"
+    );
+    assert!(planned("1-4"), "remote-wakeup card reader gets autosuspend");
+    assert!(!planned("2-1"), "already auto");
+
+    // Every considered device has an explanation with its factors
+    let explain = |name: &str| {
+        plan.explanations
+            .iter()
+            .find(|e| e.subject == format!("USB {}", name))
+            .unwrap_or_else(|| panic!("no explanation for {}", name))
+    };
+    assert!(explain("1-1").included);
+    let webcam = explain("1-2");
+    assert!(!webcam.included);
+    assert!(webcam.factors.contains(&"remote wakeup: no".to_string()));
+    assert!(webcam.factors.contains(&"bMaxPower: 500mA".to_string()));
+    assert!(explain("1-3").factors.contains(&"persist: off".to_string()));
+    assert!(
+        explain("1-4")
+            .factors
+            .contains(&"runtime_status: active".to_string())
+    );
+    assert!(!plan.explanations.iter().any(|e| e.subject == "USB 2-1"));
+
+    // Audit counts the same devices the plan changes
+    let findings = audit::usb_power::check_with_knobs(&sysfs, &moderate_knobs());
+    assert!(
+        findings
+            .iter()
+            .any(|f| f.description.starts_with("2/5 USB devices"))
+    );
+}
+
 #[test]
 fn test_build_plan_includes_audio_and_gpu_dpm() {
     let tmp = TempDir::new().unwrap();
@@ -646,16 +1525,16 @@ fn test_status_sysfs_active_and_drifted() {
     let state = ApplyState {
         timestamp: "2026-01-01T00:00:00Z".to_string(),
         sysfs_changes: vec![
-            SysfsChange {
-                path: epp_path.to_string_lossy().into_owned(),
-                original_value: "balance_performance".to_string(),
-                new_value: "balance_power".to_string(),
-            },
-            SysfsChange {
-                path: profile_path.to_string_lossy().into_owned(),
-                original_value: "performance".to_string(),
-                new_value: "low-power".to_string(),
-            },
+            SysfsChange::new(
+                epp_path.to_string_lossy().into_owned(),
+                "balance_performance",
+                "balance_power",
+            ),
+            SysfsChange::new(
+                profile_path.to_string_lossy().into_owned(),
+                "performance",
+                "low-power",
+            ),
         ],
         ..Default::default()
     };
@@ -730,11 +1609,11 @@ fn test_revert_restores_sysfs_values_integration() {
             i
         );
         fs::write(&path, "balance_power\n").unwrap();
-        sysfs_changes.push(SysfsChange {
-            path: path.to_string_lossy().into_owned(),
-            original_value: original,
-            new_value: "balance_power".to_string(),
-        });
+        sysfs_changes.push(SysfsChange::new(
+            path.to_string_lossy().into_owned(),
+            original,
+            "balance_power",
+        ));
     }
 
     // Platform profile: performance -> low-power
@@ -745,21 +1624,21 @@ fn test_revert_restores_sysfs_values_integration() {
         .to_string();
     assert_eq!(profile_original, "performance");
     fs::write(&profile_path, "low-power\n").unwrap();
-    sysfs_changes.push(SysfsChange {
-        path: profile_path.to_string_lossy().into_owned(),
-        original_value: profile_original,
-        new_value: "low-power".to_string(),
-    });
+    sysfs_changes.push(SysfsChange::new(
+        profile_path.to_string_lossy().into_owned(),
+        profile_original,
+        "low-power",
+    ));
 
     // ASPM policy: default -> powersave
     let aspm_path = tmp.path().join("sys/module/pcie_aspm/parameters/policy");
     let aspm_original = fs::read_to_string(&aspm_path).unwrap().trim().to_string();
     fs::write(&aspm_path, "powersave\n").unwrap();
-    sysfs_changes.push(SysfsChange {
-        path: aspm_path.to_string_lossy().into_owned(),
-        original_value: aspm_original,
-        new_value: "powersave".to_string(),
-    });
+    sysfs_changes.push(SysfsChange::new(
+        aspm_path.to_string_lossy().into_owned(),
+        aspm_original,
+        "powersave",
+    ));
 
     // Build the ApplyState.
     let state = ApplyState {
@@ -861,11 +1740,11 @@ fn test_apply_then_revert_round_trip() {
     for ((relative_path, original), (_, new_value)) in key_paths.iter().zip(plan_values.iter()) {
         let full = tmp.path().join(relative_path);
         fs::write(&full, format!("{}\n", new_value)).unwrap();
-        sysfs_changes.push(SysfsChange {
-            path: full.to_string_lossy().into_owned(),
-            original_value: original.to_string(),
-            new_value: new_value.to_string(),
-        });
+        sysfs_changes.push(SysfsChange::new(
+            full.to_string_lossy().into_owned(),
+            original.to_string(),
+            new_value.to_string(),
+        ));
     }
 
     let state = ApplyState {
@@ -959,105 +1838,299 @@ fn test_audit_dirty_writeback_low() {
 }
 
 #[test]
-fn test_build_plan_includes_sysctl_writes() {
+fn test_build_plan_includes_sysctl_writes() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+
+    // Add sysctl files with suboptimal values
+    let nmi_dir = tmp.path().join("proc/sys/kernel");
+    fs::create_dir_all(&nmi_dir).unwrap();
+    fs::write(nmi_dir.join("nmi_watchdog"), "1\n").unwrap();
+
+    let vm_dir = tmp.path().join("proc/sys/vm");
+    fs::create_dir_all(&vm_dir).unwrap();
+    fs::write(vm_dir.join("dirty_writeback_centisecs"), "500\n").unwrap();
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+
+    assert!(
+        plan.sysfs_writes
+            .iter()
+            .any(|w| w.path == "/proc/sys/kernel/nmi_watchdog" && w.value == "0"),
+        "Expected plan to include sysfs write for nmi_watchdog -> 0"
+    );
+
+    assert!(
+        plan.sysfs_writes
+            .iter()
+            .any(|w| w.path == "/proc/sys/vm/dirty_writeback_centisecs" && w.value == "1500"),
+        "Expected plan to include sysfs write for dirty_writeback_centisecs -> 1500"
+    );
+}
+
+#[test]
+fn test_audit_display_refresh_rate_suggestion() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+
+    // Add an eDP connector that is connected
+    let edp = tmp.path().join("sys/class/drm/card0-eDP-1");
+    fs::create_dir_all(&edp).unwrap();
+    fs::write(edp.join("status"), "connected\n").unwrap();
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let findings = audit::display::check(&hw, &sysfs);
+
+    let refresh_finding = findings
+        .iter()
+        .find(|f| f.description.contains("refresh rate"))
+        .expect("Expected an Info finding about display refresh rate");
+
+    assert_eq!(refresh_finding.severity, audit::Severity::Info);
+    assert_eq!(refresh_finding.weight, 0);
+    assert!(refresh_finding.impact.contains("1W"));
+}
+
+#[test]
+fn test_audit_psr_disabled() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+
+    // Set cmdline with amdgpu.dcdebugmask=0x10
+    fs::write(
+        tmp.path().join("proc/cmdline"),
+        "initrd=\\initramfs-linux.img root=UUID=abc123 rw amdgpu.dcdebugmask=0x10\n",
+    )
+    .unwrap();
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let findings = audit::display::check(&hw, &sysfs);
+
+    let psr_finding = findings
+        .iter()
+        .find(|f| f.description.contains("PSR disabled"))
+        .expect("Expected an Info finding about PSR being disabled");
+
+    assert_eq!(psr_finding.severity, audit::Severity::Info);
+    assert_eq!(psr_finding.weight, 0);
+    assert_eq!(psr_finding.current_value, "0x10");
+    assert!(psr_finding.impact.contains("0.5-1.5W"));
+}
+
+#[test]
+fn test_audit_psr_not_flagged_without_dcdebugmask() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+
+    // Default cmdline without dcdebugmask
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let findings = audit::display::check(&hw, &sysfs);
+
+    assert!(
+        !findings
+            .iter()
+            .any(|f| f.description.contains("PSR disabled")),
+        "Should not emit PSR finding when dcdebugmask is not set"
+    );
+}
+
+fn set_cmdline(root: &Path, extra: &str) {
+    fs::write(
+        root.join("proc/cmdline"),
+        format!(
+            "initrd=\\initramfs-linux.img root=UUID=abc123 rw {}\n",
+            extra
+        ),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_psr_mask_kept_by_default() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    set_cmdline(tmp.path(), "amdgpu.dcdebugmask=0x10");
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let finding = audit::display::check(&hw, &sysfs)
+        .into_iter()
+        .find(|f| f.description == "PSR disabled by user kernel parameter")
+        .expect("Expected the PSR acknowledgement");
+    assert_eq!(finding.severity, audit::Severity::Info);
+    assert!(
+        finding
+            .recommended_value
+            .contains("Remove amdgpu.dcdebugmask=0x10 to re-enable, known flicker trade-off")
+    );
+
+    let plan = apply::build_plan(
+        &hw,
+        &sysfs,
+        &supersaver_knobs(),
+        Some(&BopConfig::default()),
+    );
+    assert!(plan.kernel_params_remove.is_empty());
+    assert!(
+        !plan
+            .kernel_params
+            .iter()
+            .any(|p| p.starts_with("amdgpu.dcdebugmask"))
+    );
+    let explanation = plan
+        .explanations
+        .iter()
+        .find(|e| e.subject == "amdgpu.dcdebugmask")
+        .expect("Expected the mask to be explained");
+    assert!(!explanation.included);
+}
+
+#[test]
+fn test_psr_mask_removed_with_force_psr() {
     let tmp = TempDir::new().unwrap();
     create_framework16_fixture(tmp.path());
-
-    // Add sysctl files with suboptimal values
-    let nmi_dir = tmp.path().join("proc/sys/kernel");
-    fs::create_dir_all(&nmi_dir).unwrap();
-    fs::write(nmi_dir.join("nmi_watchdog"), "1\n").unwrap();
-
-    let vm_dir = tmp.path().join("proc/sys/vm");
-    fs::create_dir_all(&vm_dir).unwrap();
-    fs::write(vm_dir.join("dirty_writeback_centisecs"), "500\n").unwrap();
+    set_cmdline(tmp.path(), "amdgpu.dcdebugmask=0x10");
 
     let sysfs = SysfsRoot::new(tmp.path());
     let hw = HardwareInfo::detect(&sysfs);
-    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    let mut config = BopConfig::default();
+    config.display.force_psr = true;
 
+    let plan = apply::build_plan(&hw, &sysfs, &supersaver_knobs(), Some(&config));
+    assert_eq!(plan.kernel_params_remove, vec!["amdgpu.dcdebugmask"]);
     assert!(
-        plan.sysfs_writes
+        !plan
+            .kernel_params
             .iter()
-            .any(|w| w.path == "/proc/sys/kernel/nmi_watchdog" && w.value == "0"),
-        "Expected plan to include sysfs write for nmi_watchdog -> 0"
+            .any(|p| p.starts_with("amdgpu.dcdebugmask"))
     );
-
     assert!(
-        plan.sysfs_writes
+        plan.explanations
             .iter()
-            .any(|w| w.path == "/proc/sys/vm/dirty_writeback_centisecs" && w.value == "1500"),
-        "Expected plan to include sysfs write for dirty_writeback_centisecs -> 1500"
+            .any(|e| e.subject == "amdgpu.dcdebugmask" && e.included)
     );
+
+    // Persistent removals are dropped from reduced plans like additions are
+    let reduced = apply::build_plan_reduced(&hw, &sysfs, &supersaver_knobs(), Some(&config));
+    assert!(reduced.kernel_params_remove.is_empty());
 }
 
 #[test]
-fn test_audit_display_refresh_rate_suggestion() {
+fn test_psr_no_mask_nothing_to_remove() {
     let tmp = TempDir::new().unwrap();
     create_framework16_fixture(tmp.path());
 
-    // Add an eDP connector that is connected
-    let edp = tmp.path().join("sys/class/drm/card0-eDP-1");
-    fs::create_dir_all(&edp).unwrap();
-    fs::write(edp.join("status"), "connected\n").unwrap();
-
     let sysfs = SysfsRoot::new(tmp.path());
     let hw = HardwareInfo::detect(&sysfs);
-    let findings = audit::display::check(&hw, &sysfs);
+    let mut config = BopConfig::default();
+    config.display.force_psr = true;
 
-    let refresh_finding = findings
-        .iter()
-        .find(|f| f.description.contains("refresh rate"))
-        .expect("Expected an Info finding about display refresh rate");
-
-    assert_eq!(refresh_finding.severity, audit::Severity::Info);
-    assert_eq!(refresh_finding.weight, 0);
-    assert!(refresh_finding.impact.contains("1W"));
+    let plan = apply::build_plan(&hw, &sysfs, &supersaver_knobs(), Some(&config));
+    assert!(plan.kernel_params_remove.is_empty());
+    assert!(
+        !plan
+            .explanations
+            .iter()
+            .any(|e| e.subject == "amdgpu.dcdebugmask")
+    );
 }
 
 #[test]
-fn test_audit_psr_disabled() {
+fn test_pins_keep_paths_out_of_plans() {
     let tmp = TempDir::new().unwrap();
     create_framework16_fixture(tmp.path());
 
-    // Set cmdline with amdgpu.dcdebugmask=0x10
-    fs::write(
-        tmp.path().join("proc/cmdline"),
-        "initrd=\\initramfs-linux.img root=UUID=abc123 rw amdgpu.dcdebugmask=0x10\n",
-    )
-    .unwrap();
-
     let sysfs = SysfsRoot::new(tmp.path());
     let hw = HardwareInfo::detect(&sysfs);
-    let findings = audit::display::check(&hw, &sysfs);
+    let mut config = BopConfig::default();
+    // Pins beat [overrides] as well as the preset
+    config.overrides.epp = Some("power".to_string());
+    config.pins.insert(
+        "/sys/devices/system/cpu/cpu*/cpufreq/energy_performance_preference",
+        "balance_performance",
+    );
+    config
+        .pins
+        .insert("/sys/devices/system/cpu/cpufreq/boost", "leave");
+    let knobs = bop::config::resolve_knobs(&config, Preset::Supersaver);
 
-    let psr_finding = findings
-        .iter()
-        .find(|f| f.description.contains("Panel Self-Refresh"))
-        .expect("Expected an Info finding about PSR being disabled");
+    let unpinned = apply::build_plan(&hw, &sysfs, &knobs, None);
+    assert!(
+        unpinned
+            .sysfs_writes
+            .iter()
+            .any(|w| w.path.ends_with("energy_performance_preference"))
+    );
+    assert!(
+        unpinned
+            .sysfs_writes
+            .iter()
+            .any(|w| w.path.ends_with("cpufreq/boost"))
+    );
 
-    assert_eq!(psr_finding.severity, audit::Severity::Info);
-    assert_eq!(psr_finding.weight, 0);
-    assert_eq!(psr_finding.current_value, "0x10");
-    assert!(psr_finding.impact.contains("0.5-1.5W"));
+    for plan in [
+        apply::build_plan(&hw, &sysfs, &knobs, Some(&config)),
+        apply::build_plan_reduced(&hw, &sysfs, &knobs, Some(&config)),
+    ] {
+        assert!(!plan.sysfs_writes.is_empty(), "unpinned writes stay");
+        assert!(
+            !plan
+                .sysfs_writes
+                .iter()
+                .any(|w| w.path.ends_with("energy_performance_preference")
+                    || w.path.ends_with("cpufreq/boost"))
+        );
+    }
+
+    let plan = apply::build_plan(&hw, &sysfs, &knobs, Some(&config));
+    let epp = plan
+        .explanations
+        .iter()
+        .find(|e| e.subject == "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference")
+        .unwrap();
+    assert!(!epp.included);
+    assert_eq!(epp.reason, "pinned by user to balance_performance");
+    assert!(epp.factors.contains(&"bop would set power".to_string()));
+    let boost = plan
+        .explanations
+        .iter()
+        .find(|e| e.subject == "/sys/devices/system/cpu/cpufreq/boost")
+        .unwrap();
+    assert_eq!(boost.reason, "pinned by user (leave)");
 }
 
 #[test]
-fn test_audit_psr_not_flagged_without_dcdebugmask() {
+fn test_audit_marks_pinned_findings() {
     let tmp = TempDir::new().unwrap();
     create_framework16_fixture(tmp.path());
 
-    // Default cmdline without dcdebugmask
     let sysfs = SysfsRoot::new(tmp.path());
     let hw = HardwareInfo::detect(&sysfs);
-    let findings = audit::display::check(&hw, &sysfs);
+    let profile = profile::detect_profile(&hw).unwrap();
+    let mut findings = profile.audit_with_opts(&hw, Preset::Moderate, &moderate_knobs());
 
-    assert!(
-        !findings
-            .iter()
-            .any(|f| f.description.contains("Panel Self-Refresh")),
-        "Should not emit PSR finding when dcdebugmask is not set"
+    let mut config = BopConfig::default();
+    config.pins.insert(
+        "/sys/devices/system/cpu/cpu*/cpufreq/energy_performance_preference",
+        "balance_performance",
     );
+    audit::annotate_pins(&mut findings, &config.pins);
+
+    let epp = findings
+        .iter()
+        .find(|f| {
+            f.path
+                .as_deref()
+                .is_some_and(|p| p.ends_with("energy_performance_preference"))
+        })
+        .unwrap();
+    assert!(epp.pinned);
+    assert_eq!(findings.iter().filter(|f| f.pinned).count(), 1);
 }
 
 #[test]
@@ -1201,6 +2274,306 @@ fn create_generic_laptop_fixture(root: &Path) {
     fs::write(vm.join("dirty_writeback_centisecs"), "500\n").unwrap();
 }
 
+/// Older Intel laptop booted with `intel_pstate=disable`: acpi-cpufreq with the
+/// ondemand governor, no EPP, and energy_perf_bias left at performance.
+fn create_generic_laptop_acpi_cpufreq_fixture(root: &Path) {
+    create_generic_laptop_fixture(root);
+    fs::write(root.join("proc/cmdline"), "intel_pstate=disable\n").unwrap();
+
+    let cpu_base = root.join("sys/devices/system/cpu");
+    for i in 0..4 {
+        let cpu_dir = cpu_base.join(format!("cpu{}", i));
+        let cpufreq = cpu_dir.join("cpufreq");
+        fs::write(cpufreq.join("scaling_driver"), "acpi-cpufreq\n").unwrap();
+        fs::write(cpufreq.join("scaling_governor"), "ondemand\n").unwrap();
+        fs::remove_file(cpufreq.join("energy_performance_preference")).unwrap();
+        fs::remove_file(cpufreq.join("energy_performance_available_preferences")).unwrap();
+
+        fs::create_dir_all(cpu_dir.join("power")).unwrap();
+        fs::write(cpu_dir.join("power/energy_perf_bias"), "0\n").unwrap();
+    }
+}
+
+#[test]
+fn test_acpi_cpufreq_epb_detection() {
+    let tmp = TempDir::new().unwrap();
+    create_generic_laptop_acpi_cpufreq_fixture(tmp.path());
+
+    let hw = HardwareInfo::detect(&SysfsRoot::new(tmp.path()));
+    assert!(hw.cpu.is_acpi_cpufreq());
+    assert_eq!(hw.cpu.epp, None);
+    assert_eq!(
+        hw.cpu.energy_perf_bias,
+        vec![(0, 0), (1, 0), (2, 0), (3, 0)]
+    );
+    assert!(hw.cpu.uses_epb());
+}
+
+#[test]
+fn test_acpi_cpufreq_audit_flags_epb_and_ondemand() {
+    let tmp = TempDir::new().unwrap();
+    create_generic_laptop_acpi_cpufreq_fixture(tmp.path());
+
+    let hw = HardwareInfo::detect(&SysfsRoot::new(tmp.path()));
+    let findings = audit::cpu_power::check_with_knobs(&hw, &moderate_knobs());
+
+    let epb = findings
+        .iter()
+        .find(|f| f.path.as_deref() == Some("/sys/devices/system/cpu/cpu*/power/energy_perf_bias"))
+        .expect("EPB finding");
+    assert_eq!(epb.severity, audit::Severity::Medium);
+    assert_eq!(epb.current_value, "0");
+
+    // cpu0 already at the target doesn't hide the others
+    let epb_path = |cpu: u32| {
+        tmp.path().join(format!(
+            "sys/devices/system/cpu/cpu{}/power/energy_perf_bias",
+            cpu
+        ))
+    };
+    fs::write(epb_path(0), "8\n").unwrap();
+    fs::write(epb_path(1), "8\n").unwrap();
+    fs::write(epb_path(3), "4\n").unwrap();
+    let hw = HardwareInfo::detect(&SysfsRoot::new(tmp.path()));
+    let findings = audit::cpu_power::check_with_knobs(&hw, &moderate_knobs());
+    let epb = findings
+        .iter()
+        .find(|f| f.path.as_deref() == Some("/sys/devices/system/cpu/cpu*/power/energy_perf_bias"))
+        .expect("EPB finding");
+    assert_eq!(epb.current_value, "0 on CPUs 2");
+
+    let governor = findings
+        .iter()
+        .find(|f| f.current_value == "ondemand")
+        .expect("ondemand governor finding");
+    assert_eq!(governor.severity, audit::Severity::Low);
+    assert_eq!(governor.recommended_value, "schedutil");
+
+    // schedutil is acceptable
+    for i in 0..4 {
+        fs::write(
+            tmp.path().join(format!(
+                "sys/devices/system/cpu/cpu{}/cpufreq/scaling_governor",
+                i
+            )),
+            "schedutil\n",
+        )
+        .unwrap();
+    }
+    let hw = HardwareInfo::detect(&SysfsRoot::new(tmp.path()));
+    let findings = audit::cpu_power::check_with_knobs(&hw, &moderate_knobs());
+    assert!(!findings.iter().any(
+        |f| f.path.as_deref() == Some("/sys/devices/system/cpu/cpu*/cpufreq/scaling_governor")
+    ));
+}
+
+#[test]
+fn test_acpi_cpufreq_plan_writes_epb() {
+    let tmp = TempDir::new().unwrap();
+    create_generic_laptop_acpi_cpufreq_fixture(tmp.path());
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let knobs = moderate_knobs();
+    let plan = apply::build_plan(&hw, &sysfs, &knobs, None);
+
+    let target = audit::cpu_power::epb_for_epp(knobs.epp.as_deref().unwrap()).to_string();
+    let epb_writes: Vec<_> = plan
+        .sysfs_writes
+        .iter()
+        .filter(|w| w.path.ends_with("/power/energy_perf_bias"))
+        .collect();
+    assert_eq!(epb_writes.len(), 4);
+    assert!(epb_writes.iter().all(|w| w.value == target));
+    assert!(
+        epb_writes
+            .iter()
+            .any(|w| w.path == "/sys/devices/system/cpu/cpu0/power/energy_perf_bias")
+    );
+    assert!(
+        !plan
+            .sysfs_writes
+            .iter()
+            .any(|w| w.path.ends_with("energy_performance_preference"))
+    );
+}
+
+#[test]
+fn test_intel_pstate_with_epp_skips_epb() {
+    let tmp = TempDir::new().unwrap();
+    create_generic_laptop_fixture(tmp.path());
+    // EPB exists on HWP systems too, but EPP takes precedence there
+    let power = tmp.path().join("sys/devices/system/cpu/cpu0/power");
+    fs::create_dir_all(&power).unwrap();
+    fs::write(power.join("energy_perf_bias"), "0\n").unwrap();
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    assert!(!hw.cpu.uses_epb());
+
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    assert!(
+        !plan
+            .sysfs_writes
+            .iter()
+            .any(|w| w.path.ends_with("energy_perf_bias"))
+    );
+    let findings = audit::cpu_power::check_with_knobs(&hw, &moderate_knobs());
+    assert!(
+        !findings
+            .iter()
+            .any(|f| f.path.as_deref()
+                == Some("/sys/devices/system/cpu/cpu*/power/energy_perf_bias"))
+    );
+}
+
+fn plan_has_ec_no_wakeup(root: &Path, knobs: &bop::preset::PresetKnobs) -> bool {
+    let sysfs = SysfsRoot::new(root);
+    let hw = HardwareInfo::detect(&sysfs);
+    apply::build_plan(&hw, &sysfs, knobs, None)
+        .kernel_params
+        .contains(&"acpi.ec_no_wakeup=1".to_string())
+}
+
+#[test]
+fn test_ec_no_wakeup_comes_from_profile() {
+    let fw = TempDir::new().unwrap();
+    create_framework16_fixture(fw.path());
+    assert!(plan_has_ec_no_wakeup(fw.path(), &moderate_knobs()));
+
+    let generic = TempDir::new().unwrap();
+    create_generic_laptop_fixture(generic.path());
+    assert!(
+        !plan_has_ec_no_wakeup(generic.path(), &moderate_knobs()),
+        "generic laptops must not get acpi.ec_no_wakeup=1 by default"
+    );
+
+    // Other generic kernel params are unaffected
+    let sysfs = SysfsRoot::new(generic.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    assert!(
+        plan.kernel_params
+            .contains(&"rtc_cmos.use_acpi_alarm=1".to_string())
+    );
+}
+
+#[test]
+fn test_quick_audit_runs_no_commands() {
+    // `bop audit --oneline` runs from shell startup, so it must stay sysfs-only
+    let fixtures: [fn(&Path); 2] = [create_framework16_fixture, create_generic_laptop_fixture];
+    for create in fixtures {
+        let tmp = TempDir::new().unwrap();
+        create(tmp.path());
+        let sysfs = SysfsRoot::new(tmp.path());
+        let hw = HardwareInfo::detect_all(&sysfs);
+        let profile = profile::detect_profile(&hw).unwrap();
+
+        let spawns = bop::cmd::spawns_on_this_thread();
+        for (preset, knobs) in [
+            (Preset::Moderate, moderate_knobs()),
+            (Preset::Supersaver, supersaver_knobs()),
+        ] {
+            let findings =
+                profile.audit_in(&audit::AuditContext::quick(&sysfs), &hw, preset, &knobs);
+            assert!(!findings.is_empty(), "{}", profile.name());
+        }
+        assert_eq!(
+            bop::cmd::spawns_on_this_thread(),
+            spawns,
+            "{}",
+            profile.name()
+        );
+    }
+}
+
+#[test]
+fn test_ec_no_wakeup_follows_framework_bios_version() {
+    let fw = TempDir::new().unwrap();
+    create_framework16_fixture(fw.path());
+    let bios = fw.path().join("sys/class/dmi/id/bios_version");
+    let audit_recommends = |root: &Path| {
+        let sysfs = SysfsRoot::new(root);
+        let hw = HardwareInfo::detect(&sysfs);
+        let findings = profile::detect_profile(&hw).unwrap().audit_in(
+            &audit::AuditContext::offline(&sysfs),
+            &hw,
+            Preset::Moderate,
+            &moderate_knobs(),
+        );
+        (
+            findings
+                .iter()
+                .any(|f| f.recommended_value == "acpi.ec_no_wakeup=1"),
+            findings.iter().any(|f| f.category == "Firmware"),
+        )
+    };
+
+    // Fixed firmware: neither the workaround nor an update nag
+    for version in ["03.05", "3.05b", "03.07"] {
+        fs::write(&bios, format!("{}\n", version)).unwrap();
+        assert!(
+            !plan_has_ec_no_wakeup(fw.path(), &moderate_knobs()),
+            "{}",
+            version
+        );
+        assert_eq!(audit_recommends(fw.path()), (false, false), "{}", version);
+    }
+
+    // Older firmware: workaround plus a suggestion to update
+    fs::write(&bios, "03.03\n").unwrap();
+    assert!(plan_has_ec_no_wakeup(fw.path(), &moderate_knobs()));
+    assert_eq!(audit_recommends(fw.path()), (true, true));
+
+    // Unparseable version: generic Framework behavior, no update nag
+    fs::write(&bios, "INSYDE Corp.\n").unwrap();
+    assert!(plan_has_ec_no_wakeup(fw.path(), &moderate_knobs()));
+    assert_eq!(audit_recommends(fw.path()), (true, false));
+
+    // The config override still wins on fixed firmware
+    fs::write(&bios, "03.05\n").unwrap();
+    let mut on = moderate_knobs();
+    on.ec_no_wakeup = Some(true);
+    assert!(plan_has_ec_no_wakeup(fw.path(), &on));
+}
+
+#[test]
+fn test_ec_no_wakeup_config_override() {
+    let force_on = BopConfig {
+        overrides: bop::config::KnobOverrides {
+            ec_no_wakeup: Some(true),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let force_off = BopConfig {
+        overrides: bop::config::KnobOverrides {
+            ec_no_wakeup: Some(false),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let on = bop::config::resolve_knobs(&force_on, Preset::Moderate);
+    let off = bop::config::resolve_knobs(&force_off, Preset::Moderate);
+
+    let generic = TempDir::new().unwrap();
+    create_generic_laptop_fixture(generic.path());
+    assert!(plan_has_ec_no_wakeup(generic.path(), &on));
+
+    let fw = TempDir::new().unwrap();
+    create_framework16_fixture(fw.path());
+    assert!(!plan_has_ec_no_wakeup(fw.path(), &off));
+
+    // Audit follows the same override on Framework
+    let hw = HardwareInfo::detect(&SysfsRoot::new(fw.path()));
+    let findings = audit::kernel_params::check_with_opts(&hw, off.ec_no_wakeup.unwrap_or(true));
+    assert!(
+        !findings
+            .iter()
+            .any(|f| f.recommended_value == "acpi.ec_no_wakeup=1")
+    );
+}
+
 #[test]
 fn test_generic_laptop_profile_matches() {
     let tmp = TempDir::new().unwrap();
@@ -1379,19 +2752,130 @@ fn test_snapshot_framework16_detection() {
 }
 
 #[test]
-fn test_snapshot_framework16_profile_matches() {
-    let snap = Snapshot::load(&snapshot_fixture_path()).unwrap();
+fn test_snapshot_framework16_profile_matches() {
+    let snap = Snapshot::load(&snapshot_fixture_path()).unwrap();
+    let tmp = TempDir::new().unwrap();
+    let sysfs = snap.materialize(tmp.path()).unwrap();
+
+    let hw = HardwareInfo::detect(&sysfs);
+    let matched = profile::detect_profile(&hw);
+
+    assert!(matched.is_some(), "Should match a profile");
+    assert_eq!(
+        matched.unwrap().name(),
+        "Framework Laptop 16 (AMD Ryzen 7040 Series)",
+        "Should match Framework 16 AMD profile"
+    );
+}
+
+fn intel_hybrid_sysfs(root: &Path) -> SysfsRoot {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/intel_hybrid.json");
+    Snapshot::load(&path).unwrap().materialize(root).unwrap()
+}
+
+#[test]
+fn test_snapshot_intel_hybrid_groups_policies() {
+    let tmp = TempDir::new().unwrap();
+    let hw = HardwareInfo::detect(&intel_hybrid_sysfs(tmp.path()));
+
+    assert_eq!(hw.cpu.policies.len(), 12);
+    assert_eq!(hw.cpu.policies[4].name, "policy4");
+    assert_eq!(hw.cpu.policies[4].cpus, vec![4]);
+    assert_eq!(
+        hw.cpu.policies[0].core_type,
+        Some(bop::detect::cpu::CoreType::Performance)
+    );
+    assert_eq!(
+        hw.cpu.policies[11].core_type,
+        Some(bop::detect::cpu::CoreType::Efficiency)
+    );
+    assert_eq!(hw.cpu.epp.as_deref(), Some("balance_performance"));
+    assert_eq!(
+        hw.cpu.epp_summary().as_deref(),
+        Some("balance_performance (P-cores), balance_power (E-cores)")
+    );
+    assert_eq!(hw.cpu.boost_summary().as_deref(), Some("enabled"));
+}
+
+#[test]
+fn test_snapshot_intel_hybrid_plan_writes_per_policy() {
+    let tmp = TempDir::new().unwrap();
+    let sysfs = intel_hybrid_sysfs(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let epp_writes = |plan: &apply::ApplyPlan| -> Vec<(String, String)> {
+        plan.sysfs_writes
+            .iter()
+            .filter(|w| w.path.ends_with("energy_performance_preference"))
+            .map(|w| (w.path.clone(), w.value.clone()))
+            .collect()
+    };
+    let epp_path = |cpu: u32| {
+        format!("/sys/devices/system/cpu/cpu{cpu}/cpufreq/energy_performance_preference")
+    };
+
+    // Only the P-cores are off the moderate target
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    let expected: Vec<(String, String)> = (0..4)
+        .map(|cpu| (epp_path(cpu), "balance_power".to_string()))
+        .collect();
+    assert_eq!(epp_writes(&plan), expected);
+
+    let plan = apply::build_plan(&hw, &sysfs, &supersaver_knobs(), None);
+    let expected: Vec<(String, String)> = (0..12)
+        .map(|cpu| (epp_path(cpu), "power".to_string()))
+        .collect();
+    assert_eq!(epp_writes(&plan), expected);
+}
+
+#[test]
+fn test_snapshot_intel_hybrid_boost_per_policy() {
     let tmp = TempDir::new().unwrap();
-    let sysfs = snap.materialize(tmp.path()).unwrap();
-
+    let sysfs = intel_hybrid_sysfs(tmp.path());
+    // Each policy with its own switch: P-cores boosting, E-cores not
+    for n in 0..12 {
+        fs::write(
+            tmp.path()
+                .join(format!("sys/devices/system/cpu/cpufreq/policy{n}/boost")),
+            if n < 4 { "1" } else { "0" },
+        )
+        .unwrap();
+    }
     let hw = HardwareInfo::detect(&sysfs);
-    let matched = profile::detect_profile(&hw);
+    assert_eq!(
+        hw.cpu.boost_summary().as_deref(),
+        Some("enabled (P-cores), disabled (E-cores)")
+    );
 
-    assert!(matched.is_some(), "Should match a profile");
+    let mut knobs = supersaver_knobs();
+    knobs.turbo_boost = Some(false);
+    let plan = apply::build_plan(&hw, &sysfs, &knobs, None);
+    let boost_writes: Vec<&str> = plan
+        .sysfs_writes
+        .iter()
+        .filter(|w| w.path.ends_with("/boost"))
+        .map(|w| w.path.as_str())
+        .collect();
+    let expected: Vec<String> = (0..4)
+        .map(|n| format!("/sys/devices/system/cpu/cpufreq/policy{n}/boost"))
+        .collect();
+    assert_eq!(boost_writes, expected);
+
+    let findings: Vec<_> = bop::audit::cpu_power::check_with_knobs(&hw, &knobs)
+        .into_iter()
+        .filter(|f| f.description.starts_with("Turbo boost"))
+        .collect();
+    assert_eq!(findings.len(), 1);
+    assert!(findings[0].description.ends_with("on P-cores"));
+
+    // Re-enabling touches only the E-cores
+    knobs.turbo_boost = Some(true);
+    let plan = apply::build_plan(&hw, &sysfs, &knobs, None);
     assert_eq!(
-        matched.unwrap().name(),
-        "Framework Laptop 16 (AMD Ryzen 7040 Series)",
-        "Should match Framework 16 AMD profile"
+        plan.sysfs_writes
+            .iter()
+            .filter(|w| w.path.ends_with("/boost"))
+            .count(),
+        8
     );
 }
 
@@ -1838,6 +3322,51 @@ fn test_adaptive_epp_custom_thresholds() {
     }
 }
 
+#[test]
+fn test_critical_battery_holds_back_usb_and_wifi_but_keeps_low_battery_epp() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    set_battery_capacity(tmp.path(), 4);
+    add_usb_device(
+        tmp.path(),
+        "1-1",
+        ["External SSD", "a0", "896mA"],
+        ["on", "active", "1"],
+    );
+
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let config = BopConfig {
+        epp: EppConfig {
+            adaptive: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut plan = apply::build_plan(&hw, &sysfs, &supersaver_knobs(), Some(&config));
+    plan.wifi_power_save = Some(apply::wifi::WifiPowerSave::Iw("wlan0".to_string()));
+    let usb_write = |plan: &apply::ApplyPlan| {
+        plan.sysfs_writes
+            .iter()
+            .any(|w| w.path == "/sys/bus/usb/devices/1-1/power/control")
+    };
+    assert!(usb_write(&plan));
+    let kept = plan.sysfs_writes.len() - 1;
+
+    assert_eq!(plan.hold_latency_sensitive(), 2);
+    assert!(!usb_write(&plan));
+    assert_eq!(plan.wifi_power_save, None);
+    assert_eq!(plan.sysfs_writes.len(), kept);
+    // Adaptive EPP's low-battery level is the harmless kind and stays
+    let epp: Vec<_> = plan
+        .sysfs_writes
+        .iter()
+        .filter(|w| w.path.contains("energy_performance_preference"))
+        .collect();
+    assert!(!epp.is_empty());
+    assert!(epp.iter().all(|w| w.value == "power"));
+}
+
 #[test]
 fn test_adaptive_epp_disabled_uses_balance_power() {
     let tmp = TempDir::new().unwrap();
@@ -1880,8 +3409,9 @@ fn test_brightness_dim_and_restore_integration() {
     };
 
     // Dim
-    let original = bop::brightness::dim(&config, &sysfs).unwrap();
-    assert_eq!(original, Some(1000), "Should return original brightness");
+    let changed = bop::brightness::dim(&config, &sysfs).unwrap().unwrap();
+    assert_eq!(changed.original, 1000, "Should return original brightness");
+    assert_eq!(changed.device, "amdgpu_bl1");
 
     // Verify brightness was written
     let bl_path = tmp.path().join("sys/class/backlight/amdgpu_bl1/brightness");
@@ -1889,7 +3419,7 @@ fn test_brightness_dim_and_restore_integration() {
     assert_eq!(dimmed, "600", "Brightness should be dimmed to 60%");
 
     // Restore
-    bop::brightness::restore(1000, &sysfs).unwrap();
+    bop::brightness::restore(1000, Some(&changed.device), &sysfs).unwrap();
     let restored = fs::read_to_string(&bl_path).unwrap().trim().to_string();
     assert_eq!(
         restored, "1000",
@@ -2131,3 +3661,635 @@ fn test_inhibitor_scope_determines_plan_type() {
     let scope = should_apply(&bop::config::InhibitorMode::Skip, &[]);
     assert_eq!(scope, ApplyScope::Full);
 }
+
+#[test]
+fn test_dry_run_report_contains_every_plan_item() {
+    use bop::report::{ApplyReport, ItemStatus};
+
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let knobs = moderate_knobs();
+    let profile = profile::detect_profile(&hw).unwrap();
+    let plan = apply::build_plan(&hw, &sysfs, &knobs, None);
+    assert!(!plan.is_empty());
+
+    let findings = profile.audit_with_opts(&hw, Preset::Moderate, &knobs);
+    let mut report = ApplyReport::new(&sysfs, "apply", Preset::Moderate, profile.name(), true)
+        .scores(&findings, &plan);
+    report.finish(&plan, &apply::ApplyState::default(), &sysfs, None);
+
+    let expected = plan.sysfs_writes.len()
+        + plan.kernel_params.len()
+        + plan.services_to_disable.len()
+        + plan.acpi_wakeup_disable.len()
+        + plan.modprobe_configs.len()
+        + usize::from(plan.systemd_service && !plan.sysfs_writes.is_empty());
+    assert_eq!(report.items.len(), expected);
+    assert!(report.items.iter().all(|i| i.status == ItemStatus::Planned));
+
+    for write in &plan.sysfs_writes {
+        let item = report
+            .items
+            .iter()
+            .find(|i| i.kind == "sysfs" && i.target == write.path)
+            .unwrap_or_else(|| panic!("{} missing from report", write.path));
+        assert_eq!(item.after.as_deref(), Some(write.value.as_str()));
+    }
+    for param in &plan.kernel_params {
+        assert!(report.items.iter().any(|i| &i.target == param));
+    }
+    let epp = report
+        .items
+        .iter()
+        .find(|i| {
+            i.target
+                .ends_with("cpu0/cpufreq/energy_performance_preference")
+        })
+        .unwrap();
+    assert_eq!(
+        epp.before.as_deref(),
+        Some("balance_performance"),
+        "dry run reads before values"
+    );
+
+    assert!(report.score_projected > report.score_before);
+    assert!(report.summary.is_empty(), "nothing was applied");
+    assert_eq!(report.profile, profile.name());
+
+    let path = tmp.path().join("rollout.json");
+    report.append_to(&path).unwrap();
+    let history: Vec<ApplyReport> =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(history[0].items.len(), expected);
+}
+
+#[test]
+fn test_lazy_detection_matches_eager() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    let sysfs = SysfsRoot::new(tmp.path());
+
+    let eager = HardwareInfo::detect_all(&sysfs);
+    let lazy = LazyHardware::new(&sysfs);
+
+    assert_eq!(format!("{:?}", lazy.dmi()), format!("{:?}", eager.dmi));
+    assert_eq!(format!("{:?}", lazy.cpu()), format!("{:?}", eager.cpu));
+    assert_eq!(format!("{:?}", lazy.gpu()), format!("{:?}", eager.gpu));
+    assert_eq!(
+        format!("{:?}", lazy.display()),
+        format!("{:?}", eager.display)
+    );
+    assert_eq!(
+        format!("{:?}", lazy.battery()),
+        format!("{:?}", eager.battery)
+    );
+    assert_eq!(format!("{:?}", lazy.ac()), format!("{:?}", eager.ac));
+    assert_eq!(format!("{:?}", lazy.pci()), format!("{:?}", eager.pci));
+    assert_eq!(
+        format!("{:?}", lazy.network()),
+        format!("{:?}", eager.network)
+    );
+    assert_eq!(
+        format!("{:?}", lazy.platform()),
+        format!("{:?}", eager.platform)
+    );
+    assert_eq!(
+        format!("{:?}", lazy.wakeup_counters()),
+        format!("{:?}", eager.platform.wakeup_counters)
+    );
+    assert_eq!(format!("{:?}", lazy.usb()), format!("{:?}", eager.usb));
+    assert_eq!(format!("{:?}", lazy.typec()), format!("{:?}", eager.typec));
+    assert_eq!(lazy.kernel_cmdline(), eager.kernel_cmdline);
+
+    // Sections already read are reused, the rest filled in
+    let full = lazy.into_full();
+    assert_eq!(format!("{:?}", full), format!("{:?}", eager));
+}
+
+#[test]
+fn test_lazy_ac_reads_far_less_than_full_detection() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+
+    let sysfs = SysfsRoot::new(tmp.path()).counting_reads();
+    let lazy = LazyHardware::new(&sysfs);
+    assert!(lazy.ac().found);
+    let lazy_reads = sysfs.read_count();
+
+    let sysfs = SysfsRoot::new(tmp.path()).counting_reads();
+    HardwareInfo::detect_all(&sysfs);
+    let full_reads = sysfs.read_count();
+
+    assert!(lazy_reads > 0);
+    assert!(
+        lazy_reads * 5 < full_reads,
+        "AC only: {} reads, full: {} reads",
+        lazy_reads,
+        full_reads
+    );
+}
+
+/// amd-pmf loaded alongside the ACPI handler, both registered with the
+/// platform-profile class (kernel 6.14+). The legacy file reads "custom"
+/// because the two disagree.
+fn add_amd_pmf_handlers(root: &Path) {
+    fs::create_dir_all(root.join("sys/module/amd_pmf")).unwrap();
+    fs::write(root.join("sys/firmware/acpi/platform_profile"), "custom\n").unwrap();
+    for (node, name, profile) in [
+        ("platform-profile-0", "acpi", "balanced"),
+        ("platform-profile-1", "amd-pmf", "performance"),
+    ] {
+        let dir = root.join("sys/class/platform-profile").join(node);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("name"), format!("{}\n", name)).unwrap();
+        fs::write(dir.join("profile"), format!("{}\n", profile)).unwrap();
+        fs::write(dir.join("choices"), "low-power balanced performance\n").unwrap();
+    }
+}
+
+fn platform_profile_write(plan: &apply::ApplyPlan) -> &apply::PlannedSysfsWrite {
+    plan.sysfs_writes
+        .iter()
+        .find(|w| w.description.starts_with("Set platform profile"))
+        .expect("plan should set the platform profile")
+}
+
+#[test]
+fn test_platform_profile_without_amd_pmf_uses_legacy_file() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+
+    assert!(!hw.platform.amd_pmf_loaded);
+    assert!(hw.platform.platform_profile_handlers.is_empty());
+    assert_eq!(
+        hw.platform.platform_profile_summary().as_deref(),
+        Some("performance")
+    );
+
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    let write = platform_profile_write(&plan);
+    assert_eq!(write.path, "/sys/firmware/acpi/platform_profile");
+    assert_eq!(write.value, "low-power");
+    assert_eq!(write.description, "Set platform profile to low-power");
+}
+
+#[test]
+fn test_platform_profile_routed_through_amd_pmf_handler() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    add_amd_pmf_handlers(tmp.path());
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+
+    assert!(hw.platform.amd_pmf_loaded);
+    assert_eq!(hw.platform.platform_profile_handlers.len(), 2);
+    assert_eq!(hw.platform.platform_profile.as_deref(), Some("custom"));
+    let target = hw.platform.platform_profile_target();
+    assert_eq!(target.owner.as_deref(), Some("amd-pmf"));
+    assert_eq!(target.current.as_deref(), Some("performance"));
+    assert_eq!(
+        hw.platform.platform_profile_summary().as_deref(),
+        Some("performance (amd-pmf)")
+    );
+
+    // "custom" on the legacy file isn't "performance", but amd-pmf's own
+    // node is, so the fix still applies, through that node
+    let plan = apply::build_plan(&hw, &sysfs, &moderate_knobs(), None);
+    let write = platform_profile_write(&plan);
+    assert_eq!(
+        write.path,
+        "/sys/class/platform-profile/platform-profile-1/profile"
+    );
+    assert_eq!(
+        write.description,
+        "Set platform profile to low-power (handler: amd-pmf)"
+    );
+
+    let findings = audit::cpu_power::check_with_knobs(&hw, &moderate_knobs());
+    let finding = findings
+        .iter()
+        .find(|f| {
+            f.description
+                .starts_with("Platform profile set to performance")
+        })
+        .expect("performance finding");
+    assert_eq!(finding.current_value, "performance (amd-pmf)");
+    assert_eq!(
+        finding.path.as_deref(),
+        Some("/sys/class/platform-profile/platform-profile-1/profile")
+    );
+}
+
+#[test]
+fn test_custom_platform_profile_left_alone_without_override() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    fs::write(
+        tmp.path().join("sys/firmware/acpi/platform_profile"),
+        "custom\n",
+    )
+    .unwrap();
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    assert!(hw.platform.platform_profile_target().is_custom());
+
+    let plans_profile = |knobs: &bop::preset::PresetKnobs| {
+        apply::build_plan(&hw, &sysfs, knobs, None)
+            .sysfs_writes
+            .iter()
+            .any(|w| w.description.starts_with("Set platform profile"))
+    };
+    assert!(!plans_profile(&supersaver_knobs()));
+
+    let findings = audit::cpu_power::check_with_knobs(&hw, &supersaver_knobs());
+    let custom: Vec<_> = findings
+        .iter()
+        .filter(|f| f.description.starts_with("Platform profile"))
+        .collect();
+    assert_eq!(custom.len(), 1);
+    assert_eq!(custom[0].severity, audit::Severity::Info);
+    assert_eq!(custom[0].current_value, "custom");
+
+    let mut knobs = supersaver_knobs();
+    knobs.platform_profile_locked = true;
+    assert!(plans_profile(&knobs));
+}
+
+#[test]
+fn test_empty_platform_profile_reads_as_unknown() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    fs::write(tmp.path().join("sys/firmware/acpi/platform_profile"), "\n").unwrap();
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    assert_eq!(hw.platform.platform_profile, None);
+
+    let plan = apply::build_plan(&hw, &sysfs, &supersaver_knobs(), None);
+    assert!(
+        !plan
+            .sysfs_writes
+            .iter()
+            .any(|w| w.description.starts_with("Set platform profile"))
+    );
+}
+
+#[test]
+fn test_snapshot_round_trips_platform_profile_handlers() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    add_amd_pmf_handlers(tmp.path());
+    let snapshot = Snapshot::capture(&SysfsRoot::new(tmp.path()));
+
+    let restored = TempDir::new().unwrap();
+    let sysfs = snapshot.materialize(restored.path()).unwrap();
+    let hw = HardwareInfo::detect(&sysfs);
+    assert!(hw.platform.amd_pmf_loaded);
+    assert_eq!(
+        hw.platform.platform_profile_target().owner.as_deref(),
+        Some("amd-pmf")
+    );
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_every_planned_write_is_in_the_safety_summary() {
+    let text = bop::footprint::render();
+    let fixtures: [fn(&Path); 4] = [
+        create_framework16_fixture,
+        create_framework16_deep_fixture,
+        create_generic_laptop_fixture,
+        create_generic_laptop_acpi_cpufreq_fixture,
+    ];
+    for create in fixtures {
+        let tmp = TempDir::new().unwrap();
+        create(tmp.path());
+        let sysfs = SysfsRoot::new(tmp.path());
+        let hw = HardwareInfo::detect(&sysfs);
+        for knobs in [moderate_knobs(), supersaver_knobs()] {
+            let plan = apply::build_plan(&hw, &sysfs, &knobs, None);
+            let paths = plan
+                .sysfs_writes
+                .iter()
+                .map(|w| w.path.clone())
+                .chain(
+                    plan.modprobe_configs
+                        .iter()
+                        .map(|c| format!("{}/{}", apply::MODPROBE_DIR, c.filename)),
+                )
+                .chain(plan.services_to_disable.iter().cloned());
+            for path in paths {
+                let entry = bop::footprint::entry_for(&path)
+                    .unwrap_or_else(|| panic!("{} is not in the safety summary", path));
+                assert!(text.contains(&entry.path), "{}", entry.path);
+            }
+            let names = plan
+                .kernel_params
+                .iter()
+                .chain(&plan.kernel_params_remove)
+                .map(|p| p.split('=').next().unwrap());
+            for name in names {
+                assert!(text.contains(name), "{} is not in the safety summary", name);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_probe_writes_values_back_and_reports_unwritable_files() {
+    use bop::apply::probe::{ProbeStatus, probe_plan};
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    let plan = apply::build_plan(&hw, &sysfs, &supersaver_knobs(), None);
+    assert!(plan.sysfs_writes.len() >= 3);
+    assert!(!plan.acpi_wakeup_disable.is_empty());
+
+    // A directory where the attribute should be, and a read-only file
+    let gone = plan.sysfs_writes[0]
+        .path
+        .trim_start_matches('/')
+        .to_string();
+    fs::remove_file(tmp.path().join(&gone)).unwrap();
+    fs::create_dir(tmp.path().join(&gone)).unwrap();
+    let read_only = plan.sysfs_writes[1]
+        .path
+        .trim_start_matches('/')
+        .to_string();
+    fs::set_permissions(
+        tmp.path().join(&read_only),
+        fs::Permissions::from_mode(0o444),
+    )
+    .unwrap();
+    let before: Vec<_> = plan.sysfs_writes[1..]
+        .iter()
+        .map(|w| fs::read_to_string(tmp.path().join(w.path.trim_start_matches('/'))).unwrap())
+        .collect();
+
+    let report = probe_plan(&plan, &sysfs);
+
+    let by_path = |path: &str| report.results.iter().find(|r| r.path == path).unwrap();
+    assert!(matches!(
+        by_path(&plan.sysfs_writes[0].path).status,
+        ProbeStatus::Error(_)
+    ));
+    // Root writes through file modes, so only an unprivileged run sees EACCES
+    let expected = if nix::unistd::geteuid().is_root() {
+        ProbeStatus::Writable
+    } else {
+        ProbeStatus::Denied
+    };
+    assert_eq!(by_path(&plan.sysfs_writes[1].path).status, expected);
+    for w in &plan.sysfs_writes[2..] {
+        assert_eq!(by_path(&w.path).status, ProbeStatus::Writable, "{}", w.path);
+    }
+    let wakeup: Vec<_> = report
+        .results
+        .iter()
+        .filter(|r| r.path == apply::sysfs_writer::ACPI_WAKEUP_PATH)
+        .collect();
+    assert_eq!(wakeup.len(), plan.acpi_wakeup_disable.len());
+    assert!(
+        wakeup
+            .iter()
+            .all(|r| matches!(r.status, ProbeStatus::Skipped(_)))
+    );
+
+    // What was written back is what was selected; a real attribute renders
+    // the choice list again, the fixture file just keeps the value
+    for (w, before) in plan.sysfs_writes[1..].iter().zip(&before) {
+        let value = by_path(&w.path).value.clone().unwrap();
+        let before = before.trim();
+        assert!(before == value || before.contains(&format!("[{}]", value)));
+        let after = fs::read_to_string(tmp.path().join(w.path.trim_start_matches('/'))).unwrap();
+        assert_eq!(after, value);
+    }
+    assert_eq!(report.probed(), plan.sysfs_writes.len());
+    assert!(report.summary().ends_with(&format!(
+        "of {} planned writes would fail",
+        plan.sysfs_writes.len()
+    )));
+}
+
+/// Wakeup source name, PCI address, and its root hubs: bus number and
+/// devices as paths below the hub, such as "3-1/3-1.2".
+type UsbController<'a> = (&'a str, &'a str, &'a [(u32, &'a [&'a str])]);
+
+/// A /proc/acpi/wakeup table and the USB tree behind each controller.
+fn create_usb_topology(root: &Path, controllers: &[UsbController]) {
+    let mut table = String::new();
+    let links = root.join("sys/bus/usb/devices");
+    fs::create_dir_all(&links).unwrap();
+    for (name, pci, hubs) in controllers {
+        table.push_str(&format!("{}\tS3\t*enabled\tpci:{}\n", name, pci));
+        let pci_dir = root.join("sys/devices/pci0000:00").join(pci);
+        fs::create_dir_all(&pci_dir).unwrap();
+        for (bus, devices) in hubs.iter() {
+            let hub = pci_dir.join(format!("usb{}", bus));
+            fs::create_dir_all(&hub).unwrap();
+            std::os::unix::fs::symlink(&hub, links.join(format!("usb{}", bus))).unwrap();
+            for device in devices.iter() {
+                let dir = hub.join(device);
+                fs::create_dir_all(&dir).unwrap();
+                let name = dir.file_name().unwrap().to_owned();
+                if !name.to_string_lossy().contains(':') {
+                    std::os::unix::fs::symlink(&dir, links.join(name)).unwrap();
+                }
+            }
+        }
+    }
+    table.push_str("LID0\tS4\t*enabled\tplatform:PNP0C0D:00\n");
+    fs::create_dir_all(root.join("proc/acpi")).unwrap();
+    fs::write(root.join("proc/acpi/wakeup"), table).unwrap();
+}
+
+#[test]
+fn test_wake_list_and_sleep_audit_agree_on_connected_devices() {
+    let topologies: &[&[UsbController]] = &[
+        // Nothing plugged in
+        &[
+            ("XHC0", "0000:c1:00.3", &[(1, &[])]),
+            ("XHC1", "0000:c1:00.4", &[(3, &[]), (4, &[])]),
+        ],
+        // A device directly on a port
+        &[
+            ("XHC0", "0000:c1:00.3", &[(1, &["1-1"])]),
+            ("XHC1", "0000:c1:00.4", &[(3, &["3-2", "3-2/3-2:1.0"])]),
+        ],
+        // Only behind a hub, on the controller's second root hub
+        &[
+            (
+                "XHC1",
+                "0000:c1:00.4",
+                &[(3, &[]), (4, &["4-1", "4-1/4-1.3"])],
+            ),
+            ("XHC2", "0000:c3:00.3", &[(5, &[])]),
+        ],
+        // A hub with nothing behind it still counts
+        &[("XHC1", "0000:c1:00.4", &[(3, &["3-1", "3-1/3-1:1.0"])])],
+        // One address a prefix of another's
+        &[
+            ("XHC1", "0000:c1:00.3", &[(1, &[])]),
+            ("XHC3", "0000:c1:00.30", &[(2, &["2-1"])]),
+        ],
+    ];
+    for topology in topologies {
+        let tmp = TempDir::new().unwrap();
+        create_usb_topology(tmp.path(), topology);
+        let sysfs = SysfsRoot::new(tmp.path());
+
+        let controllers = bop::wake::list(&LazyHardware::new(&sysfs)).unwrap();
+        let hw = HardwareInfo::detect(&sysfs);
+        let findings = audit::sleep::check(&hw, &sysfs, None);
+        let flagged = findings
+            .iter()
+            .find(|f| f.description.contains("unnecessary ACPI wakeup sources"))
+            .map(|f| f.current_value.trim_start_matches("Enabled: ").to_string())
+            .unwrap_or_default();
+        let flagged: Vec<&str> = flagged.split(", ").filter(|s| !s.is_empty()).collect();
+
+        for (name, pci, hubs) in *topology {
+            let ctrl = controllers.iter().find(|c| c.name == *name).unwrap();
+            let plugged: Vec<_> = hubs
+                .iter()
+                .flat_map(|(_, devices)| devices.iter())
+                .filter(|d| !d.contains(':'))
+                .collect();
+            assert_eq!(ctrl.has_devices, !plugged.is_empty(), "{} {}", name, pci);
+            assert_eq!(ctrl.device_descriptions.len(), plugged.len(), "{}", name);
+            if *name != "XHC0" {
+                assert_eq!(
+                    flagged.contains(name),
+                    !ctrl.has_devices,
+                    "{}: wake list and sleep audit disagree",
+                    name
+                );
+            }
+        }
+        assert!(flagged.contains(&"LID0"));
+    }
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_snapshot_on_error_defaults_per_command() {
+    use bop::cli::Cli;
+    use clap::Parser;
+
+    let parse = |args: &[&str]| Cli::try_parse_from(args).unwrap().snapshot_on_error();
+    assert!(parse(&["bop", "auto"]));
+    assert!(!parse(&["bop", "auto", "--sysfs-snapshot-on-error=false"]));
+    assert!(!parse(&["bop", "auto", "status"]));
+    assert!(!parse(&["bop", "apply"]));
+    assert!(parse(&["bop", "apply", "--sysfs-snapshot-on-error"]));
+    assert!(parse(&["bop", "--sysfs-snapshot-on-error", "revert"]));
+}
+
+/// A root hub port directory under `usb<bus>`'s hub interface, with the
+/// device link the kernel creates when something is attached.
+fn create_usb_port(root: &Path, bus: u32, port: u32, device: Option<&str>, peer: Option<&str>) {
+    let interface = root.join(format!(
+        "sys/devices/pci0000:00/0000:c1:00.3/usb{}/{}-0:1.0",
+        bus, bus
+    ));
+    let dir = interface.join(format!("usb{}-port{}", bus, port));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("disable"), "0\n").unwrap();
+    fs::write(dir.join("connect_type"), "hotplug\n").unwrap();
+    let links = root.join("sys/bus/usb/devices");
+    fs::create_dir_all(&links).unwrap();
+    let link = links.join(format!("{}-0:1.0", bus));
+    if !link.exists() {
+        std::os::unix::fs::symlink(&interface, link).unwrap();
+    }
+    if let Some(device) = device {
+        let device_dir = interface.parent().unwrap().join(device);
+        fs::create_dir_all(&device_dir).unwrap();
+        std::os::unix::fs::symlink(&device_dir, dir.join("device")).unwrap();
+    }
+    if let Some(peer) = peer {
+        std::os::unix::fs::symlink(interface.join(peer), dir.join("peer")).unwrap();
+    }
+}
+
+#[test]
+fn test_usb_port_disable_only_takes_ports_empty_since_boot() {
+    let tmp = TempDir::new().unwrap();
+    create_framework16_fixture(tmp.path());
+    // Occupied, empty since boot, and empty now but used earlier this boot
+    create_usb_port(tmp.path(), 1, 1, Some("1-1"), None);
+    create_usb_port(tmp.path(), 1, 2, None, None);
+    create_usb_port(tmp.path(), 1, 3, None, None);
+    let sysfs = SysfsRoot::new(tmp.path());
+    let hw = HardwareInfo::detect(&sysfs);
+    assert_eq!(hw.usb.ports.len(), 3);
+    assert!(hw.usb.ports[0].connected);
+    assert_eq!(hw.usb.ports[1].disabled, Some(false));
+
+    let log = "usb 1-3: new full-speed USB device number 4 using xhci_hcd\n\
+               usb 1-3: USB disconnect, device number 4\n";
+    let history = apply::usb_ports::parse_connect_history(log);
+    let mut plan = apply::ApplyPlan::default();
+    apply::plan_port_disables(&mut plan, &hw.usb.ports, Some(&history));
+    let paths: Vec<_> = plan.sysfs_writes.iter().map(|w| w.path.as_str()).collect();
+    assert_eq!(paths, ["/sys/bus/usb/devices/1-0:1.0/usb1-port2/disable"]);
+    assert_eq!(plan.sysfs_writes[0].value, "1");
+    let reasons: Vec<_> = plan
+        .explanations
+        .iter()
+        .map(|e| (e.subject.as_str(), e.reason.as_str()))
+        .collect();
+    assert_eq!(
+        reasons,
+        [
+            ("USB port usb1-port1", "a device is plugged in"),
+            ("USB port usb1-port2", "empty since boot"),
+            ("USB port usb1-port3", "had a device since boot"),
+        ]
+    );
+
+    // A copied root has no kernel log of its own, so the full plan leaves
+    // every port alone
+    let plan = apply::build_plan(&hw, &sysfs, &supersaver_knobs(), None);
+    assert!(
+        !plan
+            .sysfs_writes
+            .iter()
+            .any(|w| apply::usb_ports::is_port_disable_path(&w.path))
+    );
+}
+
+#[test]
+fn test_usb_port_disable_respects_a_used_usb3_peer() {
+    let tmp = TempDir::new().unwrap();
+    create_usb_port(
+        tmp.path(),
+        1,
+        2,
+        None,
+        Some("../../usb2/2-0:1.0/usb2-port2"),
+    );
+    create_usb_port(
+        tmp.path(),
+        2,
+        2,
+        None,
+        Some("../../usb1/1-0:1.0/usb1-port2"),
+    );
+    let sysfs = SysfsRoot::new(tmp.path());
+    let usb = bop::detect::usb::UsbInfo::detect(&sysfs);
+    assert_eq!(usb.ports[0].peer.as_deref(), Some("usb2-port2"));
+
+    let history = apply::usb_ports::parse_connect_history(
+        "usb 2-2: new SuperSpeed USB device number 2 using xhci_hcd\n",
+    );
+    let mut plan = apply::ApplyPlan::default();
+    apply::plan_port_disables(&mut plan, &usb.ports, Some(&history));
+    assert!(plan.sysfs_writes.is_empty());
+}
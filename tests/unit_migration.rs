@@ -0,0 +1,175 @@
+//! Moving persistence units written by older bop versions to the current
+//! format, against the unit files kept in tests/fixtures/units: an
+//! untouched inline-echo unit, one a user edited afterwards, and a current
+//! one.
+
+use bop::apply::unit_migration::{Migration, UnitFormat, detect_format, migrate_at};
+use bop::apply::{ApplyState, SysfsChange};
+use bop::managed::{ManagedFile, content_hash};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+const PROFILE: &str = "/sys/firmware/acpi/platform_profile";
+const EPP: &str = "/sys/devices/system/cpu/cpufreq/policy0/energy_performance_preference";
+
+fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/units")
+        .join(format!("{}.service", name));
+    fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+/// The fixture installed as bop-powersave.service in a temp dir.
+fn install(name: &str) -> (TempDir, PathBuf) {
+    let tmp = TempDir::new().unwrap();
+    let path = tmp.path().join("bop-powersave.service");
+    fs::write(&path, fixture(name)).unwrap();
+    (tmp, path)
+}
+
+/// The state the inline-echo fixture was generated with, from before the
+/// manifest existed.
+fn legacy_state() -> ApplyState {
+    ApplyState {
+        timestamp: "2025-06-01T12:00:00+00:00".to_string(),
+        sysfs_changes: vec![
+            SysfsChange::new(PROFILE, "balanced\n", "low-power"),
+            SysfsChange::new(EPP, "balance_performance\n", "power"),
+        ],
+        acpi_wakeup_toggled: vec!["XHC1".to_string()],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_detect_format() {
+    assert_eq!(
+        detect_format(&fixture("inline_echo")),
+        UnitFormat::InlineEcho
+    );
+    assert_eq!(
+        detect_format(&fixture("inline_echo_modified")),
+        UnitFormat::InlineEcho
+    );
+    assert_eq!(detect_format(&fixture("current")), UnitFormat::Current);
+}
+
+#[test]
+fn test_old_unit_is_rewritten_from_state_and_recorded() {
+    let (_tmp, path) = install("inline_echo");
+    let mut state = legacy_state();
+
+    let Migration::Migrated(file) = migrate_at(&path, &mut state, false).unwrap() else {
+        panic!("not migrated");
+    };
+    let unit = fs::read_to_string(&path).unwrap();
+    assert_eq!(detect_format(&unit), UnitFormat::Current);
+    assert!(unit.starts_with("# Managed by bop v"));
+    for line in [
+        format!(
+            "ExecStart=/bin/sh -c 'printf %%s \"$$1\" > \"$$2\"' bop-write \"low-power\" \"{}\"",
+            PROFILE
+        ),
+        format!(
+            "ExecStart=/bin/sh -c 'printf %%s \"$$1\" > \"$$2\"' bop-write \"power\" \"{}\"",
+            EPP
+        ),
+        "ExecStart=/usr/bin/iw dev wlan0 set power_save on".to_string(),
+        "Conflicts=soft-reboot.target".to_string(),
+    ] {
+        assert!(
+            unit.lines().any(|l| l == line),
+            "{} missing from:\n{}",
+            line,
+            unit
+        );
+    }
+    assert!(unit.contains("echo \"XHC1\" > /proc/acpi/wakeup"));
+
+    // The state's manifest now knows the new unit
+    let recorded = path.to_string_lossy().into_owned();
+    assert_eq!(file.hash, content_hash(unit.as_bytes()));
+    assert_eq!(state.managed_files, [file]);
+    assert_eq!(state.systemd_units_created, [recorded]);
+    // Nothing else in the state moved
+    assert_eq!(state.sysfs_changes.len(), 2);
+    assert_eq!(state.sysfs_changes[0].original_value, "balanced\n");
+
+    // No stray temp file, and a second run has nothing to do
+    assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+    assert_eq!(
+        migrate_at(&path, &mut state, false).unwrap(),
+        Migration::Current
+    );
+}
+
+#[test]
+fn test_manifest_hash_decides_when_present() {
+    let (_tmp, path) = install("inline_echo");
+    let recorded = path.to_string_lossy().into_owned();
+
+    let mut state = legacy_state();
+    state.managed_files = vec![ManagedFile::new(&recorded, &fixture("inline_echo"))];
+    assert!(matches!(
+        migrate_at(&path, &mut state, false).unwrap(),
+        Migration::Migrated(_)
+    ));
+    assert_eq!(state.managed_files.len(), 1);
+
+    // Same unit, but the manifest remembers other contents
+    let (_tmp, path) = install("inline_echo");
+    let mut state = legacy_state();
+    state.managed_files = vec![ManagedFile::new(
+        path.to_string_lossy(),
+        &fixture("inline_echo_modified"),
+    )];
+    assert_eq!(
+        migrate_at(&path, &mut state, false).unwrap(),
+        Migration::Modified
+    );
+}
+
+#[test]
+fn test_modified_old_unit_is_left_alone_unless_forced() {
+    let (_tmp, path) = install("inline_echo_modified");
+    let mut state = legacy_state();
+
+    assert_eq!(
+        migrate_at(&path, &mut state, false).unwrap(),
+        Migration::Modified
+    );
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        fixture("inline_echo_modified")
+    );
+    assert!(state.managed_files.is_empty());
+
+    // Forced: rendered from the state, so the edited value and the extra
+    // line are gone
+    assert!(matches!(
+        migrate_at(&path, &mut state, true).unwrap(),
+        Migration::Migrated(_)
+    ));
+    let unit = fs::read_to_string(&path).unwrap();
+    assert!(unit.contains("bop-write \"low-power\""));
+    assert!(!unit.contains("snd_hda_intel"));
+}
+
+#[test]
+fn test_current_or_missing_unit_is_untouched() {
+    let (_tmp, path) = install("current");
+    let mut state = legacy_state();
+    assert_eq!(
+        migrate_at(&path, &mut state, false).unwrap(),
+        Migration::Current
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), fixture("current"));
+
+    fs::remove_file(&path).unwrap();
+    assert_eq!(
+        migrate_at(&path, &mut state, false).unwrap(),
+        Migration::NotInstalled
+    );
+    assert!(state.managed_files.is_empty());
+}